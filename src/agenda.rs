@@ -0,0 +1,423 @@
+use anyhow::{Context, Result, bail};
+use chrono::{Datelike, Days, NaiveDate};
+use regex::Regex;
+use std::{
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+
+use crate::atomic;
+use crate::journal::journal_path;
+use crate::meta;
+use crate::metadata::read_metadata;
+use crate::vault::{Note, all_notes};
+
+/// The metadata fields holding the day a note is due back to its reader.
+pub const REMINDER_FIELDS: [&str; 2] = ["remind", "due"];
+
+static DATE_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b(\d{4}-\d{2}-\d{2})\b").unwrap());
+/// A checkbox list item: `- [ ] task`, `* [x] task`...
+pub(crate) static TASK_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*[-*+] \[([ xX])\]\s+(.*)$").unwrap());
+/// An org heading with a TODO keyword
+pub(crate) static ORG_TODO_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\*+\s+(TODO|DONE)\s+(.*)$").unwrap());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemKind {
+    /// A task with a day: a checkbox item, or an org TODO heading with a planning line
+    Task,
+    /// A reminder field of a note
+    Reminder,
+    /// The periodic note of the day
+    Journal,
+}
+
+/// An entry of the agenda, pointing back to where it is written.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgendaItem {
+    pub kind: ItemKind,
+    pub date: NaiveDate,
+    pub text: String,
+    /// The main file of the note
+    pub file: PathBuf,
+    /// The line of a task, from 1
+    pub line: usize,
+    /// The line of the day of a task, the planning line of org TODO headings
+    pub date_line: usize,
+    /// The field of a reminder
+    pub field: Option<String>,
+    pub done: bool,
+}
+
+/// The days of the agenda: `date`, or the week (Monday to Sunday) it is in.
+pub fn range(date: NaiveDate, week: bool) -> (NaiveDate, NaiveDate) {
+    if !week {
+        return (date, date);
+    }
+    let monday = date - Days::new(date.weekday().num_days_from_monday() as u64);
+    (monday, monday + Days::new(6))
+}
+
+/// The items of the days from `from` to `to`, by day. Open tasks and reminders overdue by
+/// `today` show on today when it is one of the days.
+pub fn collect(
+    note_root: &Path,
+    journal_pattern: &str,
+    from: NaiveDate,
+    to: NaiveDate,
+    today: NaiveDate,
+) -> Result<Vec<AgendaItem>> {
+    let in_range = |item: &AgendaItem| {
+        (from..=to).contains(&item.date)
+            || (!item.done && item.date < today && (from..=to).contains(&today))
+    };
+
+    let mut items = Vec::new();
+    for note in all_notes(note_root)? {
+        let Ok(file) = note.main_file_path() else {
+            continue;
+        };
+        let Ok(content) = fs::read_to_string(&file) else {
+            continue;
+        };
+        items.extend(tasks(&content, &file).into_iter().filter(in_range));
+
+        let Ok(metadata) = read_metadata(&file) else {
+            continue;
+        };
+        for field in REMINDER_FIELDS {
+            let Some(date) = metadata
+                .get(field)
+                .and_then(|v| v.as_str().map(str::to_string))
+                .and_then(|v| NaiveDate::parse_from_str(v.get(..10)?, "%Y-%m-%d").ok())
+            else {
+                continue;
+            };
+            let item = AgendaItem {
+                kind: ItemKind::Reminder,
+                date,
+                text: metadata.title.clone().unwrap_or_else(|| note.note_name()),
+                file: file.clone(),
+                line: 0,
+                date_line: 0,
+                field: Some(field.to_string()),
+                done: false,
+            };
+            if in_range(&item) {
+                items.push(item);
+            }
+        }
+    }
+
+    let mut date = from;
+    while date <= to {
+        let file = note_root.join(journal_path(journal_pattern, date)?);
+        if file.is_file() {
+            items.push(AgendaItem {
+                kind: ItemKind::Journal,
+                date,
+                text: format!("Journal {}", date.format("%A")),
+                file,
+                line: 0,
+                date_line: 0,
+                field: None,
+                done: false,
+            });
+        }
+        date = date + Days::new(1);
+    }
+
+    items.sort_by(|a, b| {
+        shown_on(a, today)
+            .cmp(&shown_on(b, today))
+            .then(a.done.cmp(&b.done))
+            .then_with(|| a.file.cmp(&b.file))
+            .then(a.line.cmp(&b.line))
+    });
+    Ok(items)
+}
+
+/// The day an item is listed on: today for overdue open items.
+pub fn shown_on(item: &AgendaItem, today: NaiveDate) -> NaiveDate {
+    if item.done || item.kind == ItemKind::Journal {
+        item.date
+    } else {
+        item.date.max(today)
+    }
+}
+
+/// The tasks with a day of a note: checkbox items with a `YYYY-MM-DD` day, like
+/// `- [ ] send the report due:2025-03-04`, and org TODO headings scheduled on the heading or on
+/// the planning line below it, like `SCHEDULED: <2025-03-04 Tue>`. Code blocks are skipped.
+fn tasks(content: &str, file: &Path) -> Vec<AgendaItem> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut items = Vec::new();
+    let mut in_code = false;
+
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim().to_lowercase();
+        if trimmed.starts_with("```")
+            || trimmed.starts_with("#+begin_src")
+            || trimmed.starts_with("#+end_src")
+        {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+
+        let (done, text, date_line) = if let Some(caps) = TASK_REGEX.captures(line) {
+            (&caps[1] != " ", caps[2].to_string(), i)
+        } else if let Some(caps) = ORG_TODO_REGEX.captures(line) {
+            let planning = lines.get(i + 1).filter(|next| {
+                let next = next.trim_start();
+                next.starts_with("SCHEDULED:") || next.starts_with("DEADLINE:")
+            });
+            let date_line = if DATE_REGEX.is_match(line) || planning.is_none() {
+                i
+            } else {
+                i + 1
+            };
+            (&caps[1] == "DONE", caps[2].to_string(), date_line)
+        } else {
+            continue;
+        };
+
+        let Some(date) = DATE_REGEX
+            .captures(lines[date_line])
+            .and_then(|caps| NaiveDate::parse_from_str(&caps[1], "%Y-%m-%d").ok())
+        else {
+            continue;
+        };
+        items.push(AgendaItem {
+            kind: ItemKind::Task,
+            date,
+            text: text.trim().to_string(),
+            file: file.to_path_buf(),
+            line: i + 1,
+            date_line: date_line + 1,
+            field: None,
+            done,
+        });
+    }
+    items
+}
+
+/// Mark a task done, or open again. A reminder done is removed from its note.
+pub fn set_done(item: &AgendaItem, done: bool) -> Result<()> {
+    match item.kind {
+        ItemKind::Task => edit_line(item, item.line, |line| {
+            if let Some(caps) = TASK_REGEX.captures(line) {
+                let mark = caps.get(1).unwrap();
+                let check = if done { "x" } else { " " };
+                Some(format!(
+                    "{}{}{}",
+                    &line[..mark.start()],
+                    check,
+                    &line[mark.end()..]
+                ))
+            } else {
+                let (from, to) = if done {
+                    ("TODO", "DONE")
+                } else {
+                    ("DONE", "TODO")
+                };
+                let caps = ORG_TODO_REGEX.captures(line)?;
+                let keyword = caps.get(1).unwrap();
+                (keyword.as_str() == from).then(|| {
+                    format!(
+                        "{}{}{}",
+                        &line[..keyword.start()],
+                        to,
+                        &line[keyword.end()..]
+                    )
+                })
+            }
+        }),
+        ItemKind::Reminder if done => meta::update(
+            &item.file,
+            item.field.as_deref().unwrap_or_default(),
+            None,
+            false,
+        ),
+        ItemKind::Reminder => Ok(()),
+        ItemKind::Journal => bail!("A journal note cannot be done"),
+    }
+}
+
+/// Move an item to another day, in its note.
+pub fn reschedule(item: &AgendaItem, date: NaiveDate) -> Result<()> {
+    let old = item.date.format("%Y-%m-%d").to_string();
+    let new = date.format("%Y-%m-%d").to_string();
+    match item.kind {
+        ItemKind::Task => edit_line(item, item.date_line, |line| {
+            line.contains(&old).then(|| line.replacen(&old, &new, 1))
+        }),
+        ItemKind::Reminder => meta::update(
+            &item.file,
+            item.field.as_deref().unwrap_or_default(),
+            Some(&new.into()),
+            false,
+        ),
+        ItemKind::Journal => bail!("A journal note cannot be rescheduled"),
+    }
+}
+
+/// Rewrite a line of the file of an item, failing when the note changed since the agenda was
+/// read and the line is not the one of the item anymore.
+fn edit_line(
+    item: &AgendaItem,
+    line_number: usize,
+    edit: impl FnOnce(&str) -> Option<String>,
+) -> Result<()> {
+    let content = fs::read_to_string(&item.file)
+        .with_context(|| format!("Failed to read note '{}'", item.file.display()))?;
+    let mut lines: Vec<&str> = content.split('\n').collect();
+    let changed = "The note changed since the agenda was read, refresh it";
+    let Some(line) = lines.get(line_number - 1) else {
+        bail!(changed);
+    };
+    let Some(updated) = edit(line) else {
+        bail!(changed);
+    };
+    lines[line_number - 1] = &updated;
+    atomic::write(&item.file, lines.join("\n"))
+}
+
+/// The agenda as text, by day, with the paths relative to the note root.
+pub fn render(
+    items: &[AgendaItem],
+    note_root: &Path,
+    from: NaiveDate,
+    to: NaiveDate,
+    today: NaiveDate,
+) -> String {
+    let mut out = String::new();
+    let mut date = from;
+    while date <= to {
+        let title = date.format("%A %Y-%m-%d");
+        if date == today {
+            let _ = writeln!(out, "{} (today)", title);
+        } else {
+            let _ = writeln!(out, "{}", title);
+        }
+        for item in items.iter().filter(|item| shown_on(item, today) == date) {
+            let _ = writeln!(out, "  {}", describe(item, note_root, today));
+        }
+        date = date + Days::new(1);
+    }
+    out
+}
+
+/// An item on one line: its status, text, note, and how overdue it is.
+pub fn describe(item: &AgendaItem, note_root: &Path, today: NaiveDate) -> String {
+    let status = match (item.kind, item.done) {
+        (ItemKind::Task, true) => "[x]",
+        (ItemKind::Task, false) => "[ ]",
+        (ItemKind::Reminder, _) => "(!)",
+        (ItemKind::Journal, _) => " ~ ",
+    };
+    let mut location = item
+        .file
+        .strip_prefix(note_root)
+        .unwrap_or(&item.file)
+        .display()
+        .to_string();
+    if item.line > 0 {
+        location = format!("{}:{}", location, item.line);
+    }
+    let overdue = if !item.done && item.kind != ItemKind::Journal && item.date < today {
+        format!(" (due {})", item.date)
+    } else {
+        String::new()
+    };
+    format!("{} {}{}  {}", status, item.text, overdue, location)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_agenda() {
+        let today = NaiveDate::from_ymd_opt(2025, 3, 12).unwrap();
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        let tasks_file = root.join("tasks.md");
+        fs::write(
+            &tasks_file,
+            "# Tasks\n\n- [ ] send the report due:2025-03-12\n- [x] call Bob 2025-03-13\n- [ ] late 2025-03-01\n- [ ] someday\n\n```\n- [ ] not a task 2025-03-12\n```\n",
+        )
+        .unwrap();
+        let org = root.join("plan.org");
+        fs::write(
+            &org,
+            "* TODO Review the design\n  SCHEDULED: <2025-03-14 Fri>\n* Notes 2025-03-14\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("paper.md"),
+            "---\ntitle: Read the paper\nremind: 2025-03-16\n---\n",
+        )
+        .unwrap();
+        fs::create_dir_all(root.join("journal")).unwrap();
+        fs::write(root.join("journal/2025-03-10.md"), "").unwrap();
+
+        let (from, to) = range(today, true);
+        assert_eq!(from, NaiveDate::from_ymd_opt(2025, 3, 10).unwrap());
+        let items = collect(root, "journal/%Y-%m-%d.md", from, to, today).unwrap();
+        let texts: Vec<&str> = items.iter().map(|i| i.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            [
+                "Journal Monday",
+                "send the report due:2025-03-12",
+                "late 2025-03-01",
+                "call Bob 2025-03-13",
+                "Review the design",
+                "Read the paper"
+            ]
+        );
+        let rendered = render(&items, root, from, to, today);
+        assert!(rendered.contains(
+            "Wednesday 2025-03-12 (today)\n  [ ] send the report due:2025-03-12  tasks.md:3\n  \
+             [ ] late 2025-03-01 (due 2025-03-01)  tasks.md:5\n"
+        ));
+
+        // Only the day, the overdue task still shows on today
+        let day = collect(root, "journal/%Y-%m-%d.md", today, today, today).unwrap();
+        assert_eq!(day.len(), 2);
+
+        let design = &items[4];
+        assert_eq!((design.line, design.date_line), (1, 2));
+        set_done(design, true).unwrap();
+        reschedule(design, today).unwrap();
+        assert_eq!(
+            fs::read_to_string(&org).unwrap(),
+            "* DONE Review the design\n  SCHEDULED: <2025-03-12 Fri>\n* Notes 2025-03-14\n"
+        );
+
+        set_done(&items[1], true).unwrap();
+        reschedule(&items[2], today + Days::new(1)).unwrap();
+        let content = fs::read_to_string(&tasks_file).unwrap();
+        assert!(content.contains("- [x] send the report due:2025-03-12\n"));
+        assert!(content.contains("- [ ] late 2025-03-13\n"));
+        // The line does not hold the day of the item anymore
+        assert!(reschedule(&items[2], today).is_err());
+
+        reschedule(&items[5], today).unwrap();
+        assert_eq!(
+            meta::get(&root.join("paper.md"), "remind").unwrap(),
+            "2025-03-12"
+        );
+        set_done(&items[5], true).unwrap();
+        assert!(meta::get(&root.join("paper.md"), "remind").is_err());
+        assert!(set_done(&items[0], true).is_err());
+    }
+}
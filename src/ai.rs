@@ -0,0 +1,476 @@
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::{
+    io::{self, BufRead, IsTerminal, Write},
+    ops::AddAssign,
+    path::Path,
+};
+use ureq::{
+    Agent,
+    http::{HeaderName, HeaderValue},
+    middleware::{Middleware, MiddlewareNext},
+};
+
+use crate::cli::{AiCommand, AuthCommand};
+use crate::enrich::{self, EnrichOptions};
+use crate::process::find_note_dir;
+use crate::vault::all_notes;
+
+const KEYRING_SERVICE: &str = "noxe";
+
+/// A built-in OpenAI-compatible provider.
+#[derive(Debug)]
+pub struct Provider {
+    pub name: &'static str,
+    pub base_url: &'static str,
+    /// The default chat model
+    pub chat_model: &'static str,
+    /// The default embedding model, `None` if the provider has no embeddings endpoint
+    pub embedding_model: Option<&'static str>,
+}
+
+pub const PROVIDERS: [Provider; 4] = [
+    Provider {
+        name: "openai",
+        base_url: "https://api.openai.com/v1",
+        chat_model: "gpt-4o-mini",
+        embedding_model: Some("text-embedding-3-small"),
+    },
+    Provider {
+        name: "gemini",
+        base_url: "https://generativelanguage.googleapis.com/v1beta/openai",
+        chat_model: "gemini-2.0-flash",
+        embedding_model: Some("text-embedding-004"),
+    },
+    Provider {
+        name: "dashscope",
+        base_url: "https://dashscope.aliyuncs.com/compatible-mode/v1",
+        chat_model: "qwen-turbo",
+        embedding_model: Some("text-embedding-v3"),
+    },
+    Provider {
+        name: "deepseek",
+        base_url: "https://api.deepseek.com/v1",
+        chat_model: "deepseek-chat",
+        embedding_model: None,
+    },
+];
+
+pub fn find_provider(name: &str) -> Result<&'static Provider> {
+    match PROVIDERS.iter().find(|p| p.name == name) {
+        Some(provider) => Ok(provider),
+        None => bail!(
+            "Unknown AI provider '{}' (expected one of: {})",
+            name,
+            PROVIDERS.map(|p| p.name).join(", ")
+        ),
+    }
+}
+
+pub fn process_ai_command(command: AiCommand) -> Result<()> {
+    match command {
+        AiCommand::Enrich {
+            note_path,
+            all: _,
+            note_root,
+            provider,
+            model,
+            embedding_model,
+            tasks,
+            jobs,
+            override_lock,
+        } => {
+            let root = Path::new(&note_root);
+            let notes = match note_path {
+                Some(note_path) => vec![find_note_dir(&note_path, &note_root)?],
+                None => all_notes(root)?,
+            };
+
+            let options = EnrichOptions {
+                provider,
+                model,
+                embedding_model,
+                tasks,
+                jobs,
+                override_lock,
+            };
+            enrich::enrich(root, &notes, &options)?.print();
+        }
+        AiCommand::Auth { command } => match command {
+            AuthCommand::Set { provider } => {
+                let key = read_api_key(&provider)?;
+                keyring_entry(&provider)?
+                    .set_password(&key)
+                    .with_context(|| format!("Failed to store the API key of '{}'", provider))?;
+
+                println!("API key of '{}' stored in the OS keychain", provider);
+            }
+            AuthCommand::Status { provider, check } => {
+                let providers = match provider {
+                    Some(p) => vec![p],
+                    None => PROVIDERS.iter().map(|p| p.name.to_string()).collect(),
+                };
+
+                for provider in providers {
+                    let (key, source) = match api_key(&provider) {
+                        Ok(Some(found)) => found,
+                        Ok(None) => {
+                            println!("{}: not set", provider);
+                            continue;
+                        }
+                        Err(e) => {
+                            println!("{}: {:#}", provider, e);
+                            continue;
+                        }
+                    };
+
+                    print!("{}: {} ({})", provider, source, mask(&key));
+                    if check {
+                        print!(", {}", check_api_key(&provider, &key));
+                    }
+                    println!();
+                }
+            }
+            AuthCommand::Remove { provider } => {
+                match keyring_entry(&provider)?.delete_credential() {
+                    Ok(()) => println!("API key of '{}' removed", provider),
+                    Err(keyring::Error::NoEntry) => {
+                        bail!("No API key of '{}' in the OS keychain", provider)
+                    }
+                    Err(e) => {
+                        return Err(e).with_context(|| {
+                            format!("Failed to remove the API key of '{}'", provider)
+                        });
+                    }
+                }
+            }
+        },
+    }
+
+    Ok(())
+}
+
+/// Token usage reported by a provider.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+impl AddAssign for Usage {
+    fn add_assign(&mut self, other: Usage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+    }
+}
+
+impl Usage {
+    fn from_response(res: &Value) -> Self {
+        let tokens = |key: &str| res.pointer(key).and_then(Value::as_u64).unwrap_or(0);
+        Usage {
+            prompt_tokens: tokens("/usage/prompt_tokens"),
+            completion_tokens: tokens("/usage/completion_tokens"),
+        }
+    }
+}
+
+/// A client of an OpenAI-compatible provider, authenticated with the stored API key.
+#[derive(Debug)]
+pub struct Client {
+    provider: &'static Provider,
+    /// The provider's base URL, unless overridden by `NOXE_AI_BASE_URL`
+    base_url: String,
+    key: String,
+    agent: Agent,
+}
+
+impl Client {
+    pub fn new(provider: &str) -> Result<Self> {
+        let provider = find_provider(provider)?;
+        let Some((key, _)) = api_key(provider.name)? else {
+            bail!(
+                "No API key of '{}', run `noxe ai auth set {}` or set {}",
+                provider.name,
+                provider.name,
+                env_var_name(provider.name)
+            );
+        };
+
+        let base_url = match std::env::var("NOXE_AI_BASE_URL") {
+            Ok(url) if !url.is_empty() => url.trim_end_matches('/').to_string(),
+            _ => provider.base_url.to_string(),
+        };
+
+        Ok(Client {
+            provider,
+            base_url,
+            key,
+            agent: agent(),
+        })
+    }
+
+    pub fn provider(&self) -> &'static Provider {
+        self.provider
+    }
+
+    fn post(&self, endpoint: &str, body: &Value) -> Result<Value> {
+        let mut res = self
+            .agent
+            .post(format!("{}/{}", self.base_url, endpoint))
+            .header("Authorization", format!("Bearer {}", self.key))
+            .send_json(body)
+            .with_context(|| format!("Failed to reach '{}'", self.provider.name))?;
+
+        let status = res.status();
+        let text = res
+            .body_mut()
+            .read_to_string()
+            .with_context(|| format!("Failed to read the response of '{}'", self.provider.name))?;
+        let value: Value = serde_json::from_str(&text).unwrap_or(Value::Null);
+
+        if !status.is_success() {
+            let message = value
+                .pointer("/error/message")
+                .and_then(Value::as_str)
+                .unwrap_or(&text);
+            bail!("'{}' returned {}: {}", self.provider.name, status, message);
+        }
+
+        Ok(value)
+    }
+
+    /// Send a system and a user message, returning the reply.
+    pub fn chat(&self, model: &str, system: &str, user: &str) -> Result<(String, Usage)> {
+        let res = self.post(
+            "chat/completions",
+            &json!({
+                "model": model,
+                "messages": [
+                    { "role": "system", "content": system },
+                    { "role": "user", "content": user },
+                ],
+            }),
+        )?;
+
+        let reply = res
+            .pointer("/choices/0/message/content")
+            .and_then(Value::as_str)
+            .with_context(|| format!("Unexpected chat response from '{}'", self.provider.name))?;
+
+        Ok((reply.trim().to_string(), Usage::from_response(&res)))
+    }
+
+    /// Compute the embedding vectors of texts, in the same order.
+    pub fn embed(&self, model: &str, inputs: &[String]) -> Result<(Vec<Vec<f32>>, Usage)> {
+        let res = self.post("embeddings", &json!({ "model": model, "input": inputs }))?;
+        let unexpected = || {
+            format!(
+                "Unexpected embedding response from '{}'",
+                self.provider.name
+            )
+        };
+
+        let mut data = res
+            .pointer("/data")
+            .and_then(Value::as_array)
+            .with_context(unexpected)?
+            .clone();
+        if data.len() != inputs.len() {
+            bail!(unexpected());
+        }
+        // The order of the results is given by their index
+        data.sort_by_key(|d| d.get("index").and_then(Value::as_u64));
+
+        let vectors = data
+            .iter()
+            .map(|d| {
+                let vector = d.get("embedding").and_then(Value::as_array)?;
+                Some(
+                    vector
+                        .iter()
+                        .map(|v| v.as_f64().unwrap_or_default() as f32)
+                        .collect(),
+                )
+            })
+            .collect::<Option<Vec<Vec<f32>>>>()
+            .with_context(unexpected)?;
+
+        Ok((vectors, Usage::from_response(&res)))
+    }
+}
+
+/// Where an API key comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeySource {
+    Env,
+    Keychain,
+}
+
+impl std::fmt::Display for KeySource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeySource::Env => write!(f, "environment"),
+            KeySource::Keychain => write!(f, "keychain"),
+        }
+    }
+}
+
+/// The environment variable overriding the stored API key, e.g. `NOXE_OPENAI_API_KEY`.
+pub fn env_var_name(provider: &str) -> String {
+    format!(
+        "NOXE_{}_API_KEY",
+        provider.to_uppercase().replace(['-', '.'], "_")
+    )
+}
+
+/// Look up the API key of a provider. The environment variable takes precedence over the
+/// OS keychain.
+pub fn api_key(provider: &str) -> Result<Option<(String, KeySource)>> {
+    if let Ok(key) = std::env::var(env_var_name(provider))
+        && !key.is_empty()
+    {
+        return Ok(Some((key, KeySource::Env)));
+    }
+
+    match keyring_entry(provider)?.get_password() {
+        Ok(key) => Ok(Some((key, KeySource::Keychain))),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Failed to read the API key of '{}'", provider)),
+    }
+}
+
+fn keyring_entry(provider: &str) -> Result<keyring::Entry> {
+    keyring::Entry::new(KEYRING_SERVICE, provider)
+        .with_context(|| format!("Failed to access the OS keychain for '{}'", provider))
+}
+
+fn read_api_key(provider: &str) -> Result<String> {
+    let stdin = io::stdin();
+    if stdin.is_terminal() {
+        eprint!("Enter the API key of '{}': ", provider);
+        io::stderr().flush()?;
+    }
+
+    let mut key = String::new();
+    stdin
+        .lock()
+        .read_line(&mut key)
+        .with_context(|| "Failed to read the API key")?;
+
+    let key = key.trim().to_string();
+    if key.is_empty() {
+        bail!("Empty API key");
+    }
+
+    Ok(key)
+}
+
+/// Send an authenticated request to the provider to check that the key is accepted.
+fn check_api_key(provider: &str, key: &str) -> String {
+    let Ok(provider) = find_provider(provider) else {
+        return "unknown endpoint".to_string();
+    };
+
+    match agent()
+        .get(format!("{}/models", provider.base_url))
+        .header("Authorization", format!("Bearer {}", key))
+        .call()
+    {
+        Ok(res) if res.status().is_success() => "valid".to_string(),
+        Ok(res) => format!("rejected ({})", res.status()),
+        Err(e) => format!("unreachable ({})", e),
+    }
+}
+
+/// Mask a secret for display, keeping only a few characters at both ends.
+pub fn mask(secret: &str) -> String {
+    let chars: Vec<char> = secret.chars().collect();
+    if chars.len() <= 8 {
+        return "****".to_string();
+    }
+
+    let head: String = chars[..4].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("{}…{}", head, tail)
+}
+
+const SENSITIVE_HEADERS: [&str; 5] = [
+    "authorization",
+    "proxy-authorization",
+    "x-api-key",
+    "api-key",
+    "cookie",
+];
+
+/// Render a header value for logging, hiding credentials.
+pub fn redact_header(name: &HeaderName, value: &HeaderValue) -> String {
+    if !SENSITIVE_HEADERS.contains(&name.as_str()) {
+        return value.to_str().unwrap_or("<binary>").to_string();
+    }
+
+    match value.to_str().ok().and_then(|v| v.split_once(' ')) {
+        // Keep the scheme, e.g. `Bearer ****`
+        Some((scheme, _)) => format!("{} ****", scheme),
+        None => "****".to_string(),
+    }
+}
+
+/// Logs requests at debug level with credentials redacted.
+#[derive(Debug)]
+struct RedactingLogger;
+
+impl Middleware for RedactingLogger {
+    fn handle(
+        &self,
+        request: ureq::http::Request<ureq::SendBody>,
+        next: MiddlewareNext,
+    ) -> Result<ureq::http::Response<ureq::Body>, ureq::Error> {
+        tracing::debug!("{} {}", request.method(), request.uri());
+        for (name, value) in request.headers() {
+            tracing::debug!("  {}: {}", name, redact_header(name, value));
+        }
+
+        let res = next.handle(request);
+        if let Ok(res) = &res {
+            tracing::debug!("=> {}", res.status());
+        }
+        res
+    }
+}
+
+/// The HTTP agent used for every request to AI providers.
+pub fn agent() -> Agent {
+    Agent::config_builder()
+        .http_status_as_error(false)
+        .middleware(RedactingLogger)
+        .build()
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_header() {
+        let auth = HeaderName::from_static("authorization");
+        let value = HeaderValue::from_static("Bearer sk-test-0000000000000000");
+        assert_eq!(redact_header(&auth, &value), "Bearer ****");
+
+        let key = HeaderName::from_static("x-api-key");
+        let value = HeaderValue::from_static("secret");
+        assert_eq!(redact_header(&key, &value), "****");
+
+        let content_type = HeaderName::from_static("content-type");
+        let value = HeaderValue::from_static("application/json");
+        assert_eq!(redact_header(&content_type, &value), "application/json");
+    }
+
+    #[test]
+    fn test_mask_and_env_var_name() {
+        assert_eq!(mask("sk-test-0000000000000000"), "sk-t…0000");
+        assert_eq!(mask("short"), "****");
+        assert_eq!(env_var_name("open-ai"), "NOXE_OPEN_AI_API_KEY");
+    }
+}
@@ -0,0 +1,155 @@
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Local};
+use std::{
+    ffi::OsStr,
+    fs,
+    io::{self, IsTerminal, Read},
+    path::{Path, PathBuf},
+};
+
+use crate::capture;
+use crate::cli::NoteType;
+use crate::process::lookup_note;
+use crate::snippet::section_end;
+use crate::vault::Note;
+
+/// The note to append to: an existing note, by path or name, else the filenote to create
+/// under the note root, with the extension of `note_type` when the name has none. Also
+/// returns whether the note exists.
+pub fn resolve(
+    note_path: &OsStr,
+    note_root: &OsStr,
+    note_type: NoteType,
+) -> Result<(PathBuf, bool)> {
+    if let Some(note) = lookup_note(note_path, note_root)?
+        && note.exists()
+    {
+        return Ok((note, true));
+    }
+
+    let mut path = Path::new(note_root).join(note_path);
+    if path.note_type().is_err() {
+        path.as_mut_os_string().push(format!(".{}", note_type));
+    }
+    let exists = path.exists();
+    Ok((path, exists))
+}
+
+/// The text to append: the arguments, else stdin when it is not a terminal.
+pub fn read_text(args: &[String]) -> Result<String> {
+    if !args.is_empty() {
+        return Ok(args.join(" "));
+    }
+
+    let mut stdin = io::stdin();
+    if stdin.is_terminal() {
+        bail!("Nothing to append: give the text as arguments or on stdin");
+    }
+    let mut text = String::new();
+    stdin
+        .read_to_string(&mut text)
+        .with_context(|| "Failed to read the text from stdin")?;
+    Ok(text)
+}
+
+/// Append `text` to the end of the section under `heading`, which is added at the end of the
+/// note when missing, or to the end of the note. With `now`, the text is a list item starting
+/// with the time, like the entries of `noxe capture`.
+pub fn append(
+    main_file: &Path,
+    text: &str,
+    heading: Option<&str>,
+    now: Option<DateTime<Local>>,
+) -> Result<()> {
+    let text = text.trim();
+    if text.is_empty() {
+        bail!("Nothing to append");
+    }
+
+    let note_type = main_file.note_type()?;
+    let content = fs::read_to_string(main_file)
+        .with_context(|| format!("Failed to read note '{}'", main_file.display()))?;
+    let entry = match now {
+        Some(now) => capture::entry(note_type, text, now),
+        None => format!("{}\n", text),
+    };
+
+    let content = match heading {
+        None => {
+            let mut content = content;
+            if !content.is_empty() && !content.ends_with('\n') {
+                content.push('\n');
+            }
+            content + &entry
+        }
+        Some(heading) => insert_under(&content, note_type, heading, &entry)?,
+    };
+
+    fs::write(main_file, content)
+        .with_context(|| format!("Failed to write note '{}'", main_file.display()))
+}
+
+/// Insert `entry` right after the last line of the section under `heading`, so that appended
+/// entries follow each other. A missing heading is added, as a second-level heading.
+fn insert_under(content: &str, note_type: NoteType, heading: &str, entry: &str) -> Result<String> {
+    let marker = match note_type {
+        NoteType::Md => "##",
+        NoteType::Typ => "==",
+        NoteType::Org => "**",
+        NoteType::Custom(ext) => bail!("'{}' notes have no headings", ext),
+    };
+
+    let mut lines: Vec<&str> = content.lines().collect();
+    match section_end(&lines, note_type, heading) {
+        Some(at) => {
+            lines.splice(at..at, entry.lines());
+        }
+        None => {
+            let heading = format!("{} {}", marker, heading.trim());
+            while lines.last().is_some_and(|l| l.trim().is_empty()) {
+                lines.pop();
+            }
+            lines.extend(["", &heading, ""]);
+            return Ok(lines.join("\n").trim_start().to_string() + "\n" + entry);
+        }
+    }
+    Ok(lines.join("\n") + "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_append() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        fs::write(
+            root.join("inbox.md"),
+            "# Inbox\n\n## Ideas\n\n- old\n\n## Done\n",
+        )
+        .unwrap();
+
+        let (note, exists) = resolve("inbox".as_ref(), root.as_os_str(), NoteType::Md).unwrap();
+        assert_eq!(
+            (note.as_path(), exists),
+            (root.join("inbox.md").as_path(), true)
+        );
+        let (new, exists) = resolve("log".as_ref(), root.as_os_str(), NoteType::Typ).unwrap();
+        assert_eq!((new, exists), (root.join("log.typ"), false));
+
+        let now = Local.with_ymd_and_hms(2025, 3, 4, 9, 5, 0).unwrap();
+        append(&note, "idea: a\n", Some("ideas"), Some(now)).unwrap();
+        append(&note, "idea: b", Some("Ideas"), None).unwrap();
+        append(&note, "rust", Some("Reading"), None).unwrap();
+        append(&note, "end", None, None).unwrap();
+        assert_eq!(
+            fs::read_to_string(&note).unwrap(),
+            "# Inbox\n\n## Ideas\n\n- old\n- **2025-03-04 09:05** idea: a\nidea: b\n\n## Done\n\n## Reading\n\nrust\nend\n"
+        );
+
+        assert!(append(&note, " \n", None, None).is_err());
+    }
+}
@@ -0,0 +1,152 @@
+use anyhow::{Result, bail};
+use chrono::{DateTime, Local, Months, NaiveDate};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::metadata::read_metadata;
+use crate::rename::Plan;
+use crate::vault::{Note, Scope};
+
+/// The directory of the note root holding the archived notes, with the categories they had.
+pub const ARCHIVE_DIR: &str = "archive";
+
+/// The directory of the note root holding the deleted notes kept for a while.
+pub const TRASH_DIR: &str = "trash";
+
+/// Check whether a note is under the archive of the vault.
+pub fn is_archived(note_root: &Path, note: &Path) -> bool {
+    note.strip_prefix(note_root)
+        .is_ok_and(|rel| rel.starts_with(ARCHIVE_DIR))
+}
+
+/// Check whether a note is under the trash of the vault.
+pub fn is_trashed(note_root: &Path, note: &Path) -> bool {
+    note.strip_prefix(note_root)
+        .is_ok_and(|rel| rel.starts_with(TRASH_DIR))
+}
+
+/// The moves archiving the notes: `lang/rust.md` goes to `archive/lang/rust.md`.
+pub fn archive_plan(note_root: &Path, notes: &[PathBuf]) -> Result<Plan> {
+    notes
+        .iter()
+        .map(|note| {
+            if is_archived(note_root, note) {
+                bail!("'{}' is already archived", note.display());
+            }
+            let Ok(rel) = note.strip_prefix(note_root) else {
+                bail!("'{}' is not in '{}'", note.display(), note_root.display());
+            };
+            Ok((note.clone(), note_root.join(ARCHIVE_DIR).join(rel)))
+        })
+        .collect()
+}
+
+/// The moves bringing archived notes back to their category.
+pub fn unarchive_plan(note_root: &Path, notes: &[PathBuf]) -> Result<Plan> {
+    let archive = note_root.join(ARCHIVE_DIR);
+    notes
+        .iter()
+        .map(|note| match note.strip_prefix(&archive) {
+            Ok(rel) => Ok((note.clone(), note_root.join(rel))),
+            Err(_) => bail!("'{}' is not archived", note.display()),
+        })
+        .collect()
+}
+
+/// Parse an age like `30d`, `2w`, `6m` or `1y` into the date that far before `today`.
+pub fn cutoff(age: &str, today: NaiveDate) -> Result<NaiveDate> {
+    let invalid = || anyhow::anyhow!("Invalid age '{}', expected e.g. 30d, 2w, 6m or 1y", age);
+
+    let unit = age.chars().last().ok_or_else(invalid)?;
+    let n: u32 = age[..age.len() - unit.len_utf8()]
+        .parse()
+        .map_err(|_| invalid())?;
+    let date = match unit {
+        'd' => today.checked_sub_days(chrono::Days::new(n.into())),
+        'w' => today.checked_sub_days(chrono::Days::new(u64::from(n) * 7)),
+        'm' => today.checked_sub_months(Months::new(n)),
+        'y' => today.checked_sub_months(Months::new(n.saturating_mul(12))),
+        _ => return Err(invalid()),
+    };
+    date.ok_or_else(invalid)
+}
+
+/// The date of a note: the date of its metadata, or the modification date of its main file.
+pub fn note_date(note: &Path) -> Option<NaiveDate> {
+    let main_file = note.main_file_path().ok()?;
+    read_metadata(&main_file)
+        .ok()
+        .and_then(|m| m.date)
+        .and_then(|d| NaiveDate::parse_from_str(d.get(..10)?, "%Y-%m-%d").ok())
+        .or_else(|| {
+            let modified = fs::metadata(&main_file).ok()?.modified().ok()?;
+            Some(DateTime::<Local>::from(modified).date_naive())
+        })
+}
+
+/// The active notes whose date is before `cutoff`.
+pub fn older_than(note_root: &Path, notes: &[PathBuf], cutoff: NaiveDate) -> Vec<PathBuf> {
+    notes
+        .iter()
+        .filter(|note| Scope::Active.contains(note_root, note))
+        .filter(|note| note_date(note).is_some_and(|d| d < cutoff))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rename::execute;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_archive() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        fs::create_dir_all(root.join("lang")).unwrap();
+        fs::write(
+            root.join("lang/rust.md"),
+            "---\ndate: \"2020-01-05\"\n---\n[[go.md]]\n",
+        )
+        .unwrap();
+        fs::write(root.join("lang/go.md"), "---\ndate: \"2024-12-01\"\n---\n").unwrap();
+
+        let today = NaiveDate::from_ymd_opt(2025, 1, 20).unwrap();
+        assert_eq!(
+            cutoff("1y", today).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 1, 20).unwrap()
+        );
+        assert_eq!(
+            cutoff("2w", today).unwrap(),
+            NaiveDate::from_ymd_opt(2025, 1, 6).unwrap()
+        );
+        assert!(cutoff("1x", today).is_err());
+        assert!(cutoff("y", today).is_err());
+
+        let notes = vec![root.join("lang/go.md"), root.join("lang/rust.md")];
+        let old = older_than(root, &notes, cutoff("1y", today).unwrap());
+        assert_eq!(old, [root.join("lang/rust.md")]);
+
+        let plan = archive_plan(root, &old).unwrap();
+        assert_eq!(plan[0].1, root.join("archive/lang/rust.md"));
+        execute(root, &plan, false).unwrap();
+        let archived = root.join("archive/lang/rust.md");
+        assert!(is_archived(root, &archived));
+        assert!(!is_archived(root, &root.join("lang/go.md")));
+        assert!(Scope::Archived.contains(root, &archived));
+        assert!(!Scope::Active.contains(root, &archived));
+        assert!(Scope::Trash.contains(root, &root.join("trash/old.md")));
+        assert!(!Scope::Active.contains(root, &root.join("trash/old.md")));
+        assert!(Scope::All.contains(root, &archived));
+        assert!(archive_plan(root, std::slice::from_ref(&archived)).is_err());
+
+        let plan = unarchive_plan(root, &[archived]).unwrap();
+        assert_eq!(plan[0].1, root.join("lang/rust.md"));
+        execute(root, &plan, false).unwrap();
+        assert!(root.join("lang/rust.md").exists());
+        assert!(unarchive_plan(root, &[root.join("lang/go.md")]).is_err());
+    }
+}
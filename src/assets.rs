@@ -0,0 +1,156 @@
+use anyhow::{Context, Result, bail};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+use crate::archive::TRASH_DIR;
+use crate::cite::{bib_keys, citations, sources, strip_code};
+use crate::cli::NoteType;
+use crate::vault::Note;
+
+/// The directories of a dirnote holding the files its body refers to.
+pub const ASSET_DIRS: [&str; 2] = ["images", "bibliography"];
+
+/// The files under the asset directories of the dirnotes that their body never refers to.
+///
+/// A file is referred to when its name, as is or percent-encoded, appears in any file of the
+/// note. A bibliography is used too when one of its entries is cited, and is left alone when it
+/// has none, like the `refs.bib` of new dirnotes. Notes whose files cannot be read, like
+/// encrypted ones, are skipped.
+pub fn unused(notes: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut unused = Vec::new();
+    for note in notes.iter().filter(|n| n.is_dirnote()) {
+        let Ok(main_file) = note.main_file_path() else {
+            continue;
+        };
+        let Ok(note_type) = main_file.note_type() else {
+            continue;
+        };
+        let Ok(contents) = sources(note, &main_file)
+            .iter()
+            .map(fs::read_to_string)
+            .collect::<Result<Vec<String>, _>>()
+        else {
+            continue;
+        };
+        let cited = if matches!(note_type, NoteType::Md | NoteType::Typ) {
+            let stripped: Vec<String> = contents.iter().map(|c| strip_code(c)).collect();
+            citations(note_type, &stripped)
+        } else {
+            Default::default()
+        };
+
+        for dir in ASSET_DIRS {
+            let mut files: Vec<PathBuf> = WalkDir::new(note.join(dir))
+                .into_iter()
+                .filter_map(|e| e.ok())
+                .filter(|e| e.file_type().is_file())
+                .map(|e| e.into_path())
+                .collect();
+            files.sort();
+
+            for file in files {
+                let name = file.file_name().unwrap_or_default().to_string_lossy();
+                let encoded = name.replace(' ', "%20");
+                if contents
+                    .iter()
+                    .any(|c| c.contains(name.as_ref()) || c.contains(&encoded))
+                {
+                    continue;
+                }
+                if dir == "bibliography" {
+                    let keys = bib_keys(&file);
+                    if keys.is_empty() || keys.iter().any(|k| cited.contains(k)) {
+                        continue;
+                    }
+                }
+                unused.push(file);
+            }
+        }
+    }
+    Ok(unused)
+}
+
+/// Move the files to the trash of the vault, keeping their paths: `lang/rust/images/a.png`
+/// goes to `trash/lang/rust/images/a.png`. Returns where they went.
+pub fn trash(note_root: &Path, files: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut trashed = Vec::new();
+    for file in files {
+        let Ok(rel) = file.strip_prefix(note_root) else {
+            bail!("'{}' is not in '{}'", file.display(), note_root.display());
+        };
+        let to = note_root.join(TRASH_DIR).join(rel);
+        if to.exists() {
+            bail!("'{}' already exists", to.display());
+        }
+        if let Some(dir) = to.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create directory '{}'", dir.display()))?;
+        }
+        fs::rename(file, &to)
+            .with_context(|| format!("Failed to move '{}' to the trash", file.display()))?;
+        trashed.push(to);
+    }
+    Ok(trashed)
+}
+
+/// Delete the files.
+pub fn delete(files: &[PathBuf]) -> Result<()> {
+    for file in files {
+        fs::remove_file(file).with_context(|| format!("Failed to delete '{}'", file.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_unused() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        let note = root.join("lang/rust");
+        fs::create_dir_all(note.join("images/diagrams")).unwrap();
+        fs::create_dir_all(note.join("bibliography")).unwrap();
+        fs::create_dir_all(note.join("chapter")).unwrap();
+        fs::write(
+            note.join("main.md"),
+            "![logo](images/logo.png)\n![a b](images/a%20b.png)\nAs said [@knuth84].\n",
+        )
+        .unwrap();
+        fs::write(
+            note.join("chapter/ownership.md"),
+            "![](images/diagrams/own.svg)\n",
+        )
+        .unwrap();
+        for image in ["logo.png", "a b.png", "diagrams/own.svg", "old.png"] {
+            fs::write(note.join("images").join(image), "img").unwrap();
+        }
+        fs::write(note.join("bibliography/refs.bib"), "").unwrap();
+        fs::write(note.join("bibliography/cited.bib"), "@book{knuth84,}\n").unwrap();
+        fs::write(note.join("bibliography/other.bib"), "@book{other,}\n").unwrap();
+        // A filenote has no assets
+        fs::write(root.join("tokio.md"), "# Tokio\n").unwrap();
+
+        let notes = [note.clone(), root.join("tokio.md")];
+        let unused = unused(&notes).unwrap();
+        assert_eq!(
+            unused,
+            [
+                note.join("images/old.png"),
+                note.join("bibliography/other.bib"),
+            ]
+        );
+
+        let trashed = trash(root, &unused[..1]).unwrap();
+        assert_eq!(trashed, [root.join("trash/lang/rust/images/old.png")]);
+        assert!(trashed[0].is_file());
+        assert!(!note.join("images/old.png").exists());
+        delete(&unused[1..]).unwrap();
+        assert!(!note.join("bibliography/other.bib").exists());
+    }
+}
@@ -0,0 +1,231 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::state::StateStore;
+
+/// The state file of a multi-note write in progress.
+pub const TRANSACTION: &str = "transaction";
+
+/// Whether writes are flushed to the disk before noxe goes on, set by `fsync` in the config.
+/// Slower, but a power loss right after a write cannot lose it.
+pub fn fsync_enabled() -> bool {
+    std::env::var("NOXE_FSYNC").is_ok_and(|v| !matches!(v.trim(), "" | "0" | "false"))
+}
+
+/// Replace the content of a file at once: the content is written to a temporary file next to
+/// it, which is then renamed over it, so that the file is never left half-written. The file
+/// keeps its permissions, and a symlink is written through, to its target.
+pub fn write(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    let target = path
+        .is_symlink()
+        .then(|| fs::canonicalize(path).ok())
+        .flatten();
+    let path = target.as_deref().unwrap_or(path);
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp_path = path.with_file_name(format!(".{}.noxe-tmp", name));
+    let fsync = fsync_enabled();
+
+    let mut file = File::create(&tmp_path)
+        .with_context(|| format!("Failed to write '{}'", tmp_path.display()))?;
+    let written = file
+        .write_all(contents.as_ref())
+        .and_then(|_| match fs::metadata(path) {
+            Ok(metadata) => file.set_permissions(metadata.permissions()),
+            Err(_) => Ok(()),
+        })
+        .and_then(|_| if fsync { file.sync_all() } else { Ok(()) });
+    drop(file);
+    if let Err(e) = written {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e).with_context(|| format!("Failed to write '{}'", tmp_path.display()));
+    }
+
+    if let Err(e) = fs::rename(&tmp_path, path) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e).with_context(|| format!("Failed to write '{}'", path.display()));
+    }
+    if fsync && cfg!(unix) {
+        // The rename is only durable once the directory is
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            File::open(dir).and_then(|d| d.sync_all()).ok();
+        }
+    }
+    Ok(())
+}
+
+/// Files rewritten together. The new contents are first recorded in the vault state, so that
+/// when noxe is killed halfway, the next transaction of the vault completes the writes before
+/// its own, and no operation is left half-done.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Transaction {
+    writes: Vec<FileWrite>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct FileWrite {
+    /// Relative to the note root, so that the cwd of the recovery does not matter
+    path: PathBuf,
+    contents: String,
+    /// When the file was last modified before the transaction, if it existed
+    modified: Option<SystemTime>,
+}
+
+impl Transaction {
+    pub fn new() -> Self {
+        Transaction::default()
+    }
+
+    pub fn write(&mut self, path: &Path, contents: impl Into<String>) {
+        self.writes.push(FileWrite {
+            path: path.to_path_buf(),
+            contents: contents.into(),
+            modified: None,
+        });
+    }
+
+    /// Write the files. A failing write is not retried later: the record is dropped, and the
+    /// caller reverts what needs to be.
+    pub fn commit(mut self, note_root: &Path) -> Result<()> {
+        recover(note_root)?;
+        if self.writes.is_empty() {
+            return Ok(());
+        }
+
+        for write in &mut self.writes {
+            write.path = relative_to(note_root, &write.path);
+            write.modified = modified(&note_root.join(&write.path));
+        }
+        let store = StateStore::new(note_root);
+        store.save(TRANSACTION, &self)?;
+        let result = self
+            .writes
+            .iter()
+            .try_for_each(|w| write(&note_root.join(&w.path), &w.contents));
+        store.remove(TRANSACTION)?;
+        result
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// The path relative to the note root, whatever the cwd it was given from, or the absolute
+/// path of a file out of the vault.
+fn relative_to(note_root: &Path, path: &Path) -> PathBuf {
+    if let Ok(rel_path) = path.strip_prefix(note_root)
+        && path.is_relative() == note_root.is_relative()
+    {
+        return rel_path.to_path_buf();
+    }
+    let absolute = |path: &Path| fs::canonicalize(path).or_else(|_| std::path::absolute(path));
+    let path = match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => absolute(parent).map(|p| p.join(name)),
+        _ => absolute(path),
+    }
+    .unwrap_or_else(|_| path.to_path_buf());
+    match absolute(note_root) {
+        Ok(root) => path
+            .strip_prefix(&root)
+            .map_or(path.clone(), Path::to_path_buf),
+        Err(_) => path,
+    }
+}
+
+/// Complete the transaction of the vault interrupted by a crash, if any. The files modified
+/// since it was recorded, either written before the crash or edited since, are left alone.
+/// Returns the number of files written again.
+pub fn recover(note_root: &Path) -> Result<usize> {
+    let store = StateStore::new(note_root);
+    if !store.exists(TRANSACTION) {
+        return Ok(0);
+    }
+
+    let transaction: Transaction = store.load(TRANSACTION)?;
+    let mut written = 0;
+    for w in &transaction.writes {
+        let path = note_root.join(&w.path);
+        if modified(&path) != w.modified {
+            continue;
+        }
+        write(&path, &w.contents)
+            .with_context(|| "Failed to complete an interrupted write of notes")?;
+        written += 1;
+    }
+    store.remove(TRANSACTION)?;
+    if written > 0 {
+        eprintln!("Completed an interrupted write of {} file(s)", written);
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_transaction() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        let a = root.join("a.md");
+        let b = root.join("b.md");
+        fs::write(&a, "old a").unwrap();
+
+        write(&a, "new a").unwrap();
+        assert_eq!(fs::read_to_string(&a).unwrap(), "new a");
+        assert_eq!(fs::read_dir(root).unwrap().count(), 1);
+
+        // A transaction recorded but not applied, as after a crash, `c.md` edited since
+        let c = root.join("c.md");
+        fs::write(&c, "old c").unwrap();
+        let mut interrupted = Transaction::new();
+        interrupted.write(Path::new("a.md"), "a, interrupted");
+        interrupted.write(Path::new("b.md"), "b, interrupted");
+        interrupted.write(Path::new("c.md"), "c, interrupted");
+        interrupted.writes[2].modified = Some(SystemTime::UNIX_EPOCH);
+        interrupted.writes[0].modified = modified(&a);
+        StateStore::new(root)
+            .save(TRANSACTION, &interrupted)
+            .unwrap();
+
+        let mut transaction = Transaction::new();
+        transaction.write(&a, "a, next");
+        transaction.commit(root).unwrap();
+        assert_eq!(fs::read_to_string(&a).unwrap(), "a, next");
+        assert_eq!(fs::read_to_string(&b).unwrap(), "b, interrupted");
+        assert_eq!(fs::read_to_string(&c).unwrap(), "old c");
+        assert!(!StateStore::new(root).exists(TRANSACTION));
+        assert_eq!(recover(root).unwrap(), 0);
+
+        let mut failing = Transaction::new();
+        failing.write(&root.join("missing/c.md"), "c");
+        assert!(failing.commit(root).is_err());
+        assert!(!StateStore::new(root).exists(TRANSACTION));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_keeps_mode_and_links() {
+        use std::os::unix::fs::{PermissionsExt, symlink};
+
+        let tmp_dir = tempdir().unwrap();
+        let note = tmp_dir.path().join("note.md");
+        let link = tmp_dir.path().join("link.md");
+        fs::write(&note, "old").unwrap();
+        fs::set_permissions(&note, fs::Permissions::from_mode(0o600)).unwrap();
+        symlink(&note, &link).unwrap();
+
+        write(&link, "new").unwrap();
+        assert!(link.is_symlink());
+        assert_eq!(fs::read_to_string(&note).unwrap(), "new");
+        let mode = fs::metadata(&note).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
@@ -0,0 +1,199 @@
+use anyhow::{Context, Result, bail};
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::atomic;
+use crate::cli::NoteType;
+use crate::vault::Note;
+
+/// The directory of a dirnote holding its images.
+pub const IMAGES_DIR: &str = "images";
+/// The directory of a dirnote holding its other files.
+pub const ATTACHMENTS_DIR: &str = "attachments";
+
+const IMAGE_EXTS: [&str; 8] = ["png", "jpg", "jpeg", "gif", "svg", "webp", "bmp", "avif"];
+
+/// A file attached to a note.
+#[derive(Debug, PartialEq)]
+pub struct Attachment {
+    /// The file in the note, relative to it: `images/diagram.png`
+    pub path: PathBuf,
+    /// Whether the note already had the same file, which is then reused
+    pub existed: bool,
+}
+
+impl Attachment {
+    /// The link to the attachment in a note of the type: an image is embedded, another file
+    /// linked.
+    pub fn link(&self, note_type: NoteType) -> String {
+        let path = self.path.to_string_lossy().replace('\\', "/");
+        let name = self.path.file_name().unwrap_or_default().to_string_lossy();
+        match (note_type, is_image(&self.path)) {
+            (NoteType::Typ, true) => format!("#image(\"{}\")", path),
+            (NoteType::Typ, false) => format!("#link(\"{}\")[{}]", path, name),
+            (NoteType::Org, true) => format!("[[file:{}]]", path),
+            (NoteType::Org, false) => format!("[[file:{}][{}]]", path, name),
+            (_, true) => format!("![{}]({})", stem(&self.path), path.replace(' ', "%20")),
+            (_, false) => format!("[{}]({})", name, path.replace(' ', "%20")),
+        }
+    }
+}
+
+/// Copy `file` into the `images/` of a dirnote, or its `attachments/` when it is no image, or
+/// into `dir` when given. A file of that directory with the same content is reused rather than
+/// copied again, and a different file of the same name gets a numbered one: `diagram-2.png`.
+pub fn attach(note: &Path, file: &Path, dir: Option<&str>) -> Result<Attachment> {
+    if !note.is_dirnote() {
+        bail!(
+            "'{}' is a filenote, only dirnotes hold attachments",
+            note.display()
+        );
+    }
+    if let Some(dir) = dir
+        && !Path::new(dir)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+    {
+        bail!("Invalid directory '{}', it must be inside the note", dir);
+    }
+    let content = fs::read(file).with_context(|| format!("Failed to read '{}'", file.display()))?;
+    let dir = dir.unwrap_or(if is_image(file) {
+        IMAGES_DIR
+    } else {
+        ATTACHMENTS_DIR
+    });
+    let target_dir = note.join(dir);
+
+    if target_dir.is_dir() {
+        let mut entries: Vec<PathBuf> = fs::read_dir(&target_dir)
+            .with_context(|| format!("Failed to read '{}'", target_dir.display()))?
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.is_file())
+            .collect();
+        entries.sort();
+        for entry in entries {
+            if fs::metadata(&entry).is_ok_and(|m| m.len() == content.len() as u64)
+                && fs::read(&entry).is_ok_and(|existing| existing == content)
+            {
+                return Ok(Attachment {
+                    path: Path::new(dir).join(entry.file_name().unwrap()),
+                    existed: true,
+                });
+            }
+        }
+    }
+
+    let Some(name) = file.file_name() else {
+        bail!("'{}' is not a file", file.display());
+    };
+    let mut target = target_dir.join(name);
+    let mut n = 1;
+    while target.exists() {
+        n += 1;
+        let numbered = match file.extension() {
+            Some(ext) => format!("{}-{}.{}", stem(file), n, ext.to_string_lossy()),
+            None => format!("{}-{}", stem(file), n),
+        };
+        target = target_dir.join(numbered);
+    }
+    fs::create_dir_all(&target_dir)
+        .with_context(|| format!("Failed to create directory '{}'", target_dir.display()))?;
+    fs::write(&target, &content)
+        .with_context(|| format!("Failed to write '{}'", target.display()))?;
+
+    Ok(Attachment {
+        path: Path::new(dir).join(target.file_name().unwrap()),
+        existed: false,
+    })
+}
+
+/// Append the link to the main file of the note, on a line of its own.
+pub fn insert_link(main_file: &Path, link: &str) -> Result<()> {
+    let mut content = fs::read_to_string(main_file)
+        .with_context(|| format!("Failed to read '{}'", main_file.display()))?;
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    content.push_str(link);
+    content.push('\n');
+    atomic::write(main_file, content)
+}
+
+fn is_image(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| IMAGE_EXTS.contains(&ext.to_string_lossy().to_lowercase().as_str()))
+}
+
+fn stem(path: &Path) -> String {
+    path.file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_attach() {
+        let tmp_dir = tempdir().unwrap();
+        let note = tmp_dir.path().join("rust");
+        fs::create_dir_all(note.join("images")).unwrap();
+        fs::write(note.join("main.md"), "# Rust").unwrap();
+        let downloads = tmp_dir.path().join("downloads");
+        fs::create_dir_all(downloads.join("other")).unwrap();
+        fs::write(downloads.join("borrow check.png"), "png").unwrap();
+        fs::write(downloads.join("other/borrow check.png"), "another png").unwrap();
+        fs::write(downloads.join("paper.pdf"), "pdf").unwrap();
+
+        let image = attach(&note, &downloads.join("borrow check.png"), None).unwrap();
+        assert_eq!(image.path, Path::new("images/borrow check.png"));
+        assert!(!image.existed);
+        assert_eq!(
+            image.link(NoteType::Md),
+            "![borrow check](images/borrow%20check.png)"
+        );
+        assert_eq!(
+            image.link(NoteType::Typ),
+            "#image(\"images/borrow check.png\")"
+        );
+
+        // The same content is reused, another one of the same name numbered
+        let again = attach(&note, &downloads.join("borrow check.png"), None).unwrap();
+        assert_eq!(again.path, image.path);
+        assert!(again.existed);
+        let other = attach(&note, &downloads.join("other/borrow check.png"), None).unwrap();
+        assert_eq!(other.path, Path::new("images/borrow check-2.png"));
+        assert_eq!(
+            fs::read_to_string(note.join(&other.path)).unwrap(),
+            "another png"
+        );
+
+        let paper = attach(&note, &downloads.join("paper.pdf"), None).unwrap();
+        assert_eq!(paper.path, Path::new("attachments/paper.pdf"));
+        assert_eq!(
+            paper.link(NoteType::Typ),
+            "#link(\"attachments/paper.pdf\")[paper.pdf]"
+        );
+        insert_link(&note.join("main.md"), &paper.link(NoteType::Md)).unwrap();
+        assert_eq!(
+            fs::read_to_string(note.join("main.md")).unwrap(),
+            "# Rust\n[paper.pdf](attachments/paper.pdf)\n"
+        );
+
+        let filenote = tmp_dir.path().join("tokio.md");
+        fs::write(&filenote, "# Tokio\n").unwrap();
+        assert!(attach(&filenote, &downloads.join("paper.pdf"), None).is_err());
+
+        // Only directories inside the note
+        assert!(attach(&note, &downloads.join("paper.pdf"), Some("../../x")).is_err());
+        assert!(attach(&note, &downloads.join("paper.pdf"), Some("/tmp")).is_err());
+        let doc = attach(&note, &downloads.join("paper.pdf"), Some("docs/papers")).unwrap();
+        assert_eq!(doc.path, Path::new("docs/papers/paper.pdf"));
+    }
+}
@@ -0,0 +1,234 @@
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Local, NaiveDateTime};
+use ignore::WalkBuilder;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::cli::BackupCommand;
+use crate::record::human_size;
+
+/// The extension of backups.
+pub const BACKUP_EXT: &str = ".tar.zst";
+/// The time in the names of backups: `notes-20250102-030405.tar.zst`
+const TIME_FORMAT: &str = "%Y%m%d-%H%M%S";
+const ZSTD_LEVEL: i32 = 3;
+
+pub fn process_backup_command(command: BackupCommand) -> Result<()> {
+    match command {
+        BackupCommand::Restore { archive, into } => {
+            let into = match into {
+                Some(into) => into,
+                None => {
+                    let name = archive.file_name().unwrap_or_default().to_string_lossy();
+                    PathBuf::from(name.strip_suffix(BACKUP_EXT).unwrap_or(&name))
+                }
+            };
+            let files = restore(&archive, &into)?;
+            println!("Restored {} files into '{}'", files, into.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Where backups go without `--output`.
+pub fn default_dir() -> Result<PathBuf> {
+    let dir = dirs::data_local_dir().context("Cannot determine the local data directory")?;
+    Ok(dir.join("noxe").join("backups"))
+}
+
+/// The name of the backup of the vault made at `time`: `<vault>-<time>.tar.zst`.
+pub fn archive_name(note_root: &Path, time: DateTime<Local>) -> String {
+    format!(
+        "{}-{}{}",
+        vault_name(note_root),
+        time.format(TIME_FORMAT),
+        BACKUP_EXT
+    )
+}
+
+fn vault_name(note_root: &Path) -> String {
+    fs::canonicalize(note_root)
+        .ok()
+        .and_then(|root| root.file_name().map(|n| n.to_string_lossy().to_string()))
+        .unwrap_or_else(|| "vault".to_string())
+}
+
+/// Archive the vault into a zstd-compressed tar. Files of `.gitignore` and `.ignore` are left
+/// out, as are `.git/` and backups in the vault. Returns the number of files archived.
+pub fn create(note_root: &Path, archive: &Path) -> Result<usize> {
+    if let Some(dir) = archive.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create directory '{}'", dir.display()))?;
+    }
+    // Written aside first, so that a failed backup is never taken for one
+    let partial = archive.with_file_name(format!(
+        ".{}.partial",
+        archive.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    let result = write_archive(note_root, &partial).and_then(|files| {
+        fs::rename(&partial, archive)
+            .with_context(|| format!("Failed to write '{}'", archive.display()))?;
+        Ok(files)
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&partial);
+    }
+    result
+}
+
+fn write_archive(note_root: &Path, archive: &Path) -> Result<usize> {
+    let file = fs::File::create(archive)
+        .with_context(|| format!("Failed to create '{}'", archive.display()))?;
+    let mut builder = tar::Builder::new(zstd::Encoder::new(file, ZSTD_LEVEL)?);
+    builder.follow_symlinks(false);
+
+    let mut files = 0;
+    let walk = WalkBuilder::new(note_root)
+        .hidden(false)
+        .filter_entry(|entry| {
+            let name = entry.file_name().to_string_lossy();
+            name != ".git" && !name.ends_with(BACKUP_EXT) && !name.ends_with(".partial")
+        })
+        .build();
+    for entry in walk {
+        let entry = entry.with_context(|| format!("Failed to walk '{}'", note_root.display()))?;
+        let path = entry.path();
+        let rel_path = path.strip_prefix(note_root)?;
+        if rel_path.as_os_str().is_empty() {
+            continue;
+        }
+
+        if entry.file_type().is_some_and(|t| t.is_dir()) {
+            builder.append_dir(rel_path, path)
+        } else {
+            files += 1;
+            builder.append_path_with_name(path, rel_path)
+        }
+        .with_context(|| format!("Failed to archive '{}'", path.display()))?;
+    }
+
+    builder.into_inner()?.finish()?;
+    Ok(files)
+}
+
+/// Delete the oldest backups of the vault in `dir`, keeping the `keep` latest. Returns the
+/// deleted ones.
+pub fn prune(dir: &Path, note_root: &Path, keep: usize) -> Result<Vec<PathBuf>> {
+    let prefix = format!("{}-", vault_name(note_root));
+    let mut backups: Vec<(NaiveDateTime, PathBuf)> = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read '{}'", dir.display()))?
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let name = path.file_name()?.to_str()?;
+            let time = name.strip_prefix(&prefix)?.strip_suffix(BACKUP_EXT)?;
+            let time = NaiveDateTime::parse_from_str(time, TIME_FORMAT).ok()?;
+            Some((time, path))
+        })
+        .collect();
+    backups.sort();
+
+    let old = backups.len().saturating_sub(keep);
+    let mut deleted = Vec::new();
+    for (_, path) in backups.into_iter().take(old) {
+        fs::remove_file(&path).with_context(|| format!("Failed to delete '{}'", path.display()))?;
+        deleted.push(path);
+    }
+    Ok(deleted)
+}
+
+/// Unpack a backup into `into`, which must not exist or be empty. Returns the number of files
+/// restored.
+pub fn restore(archive: &Path, into: &Path) -> Result<usize> {
+    if into.exists()
+        && fs::read_dir(into)
+            .with_context(|| format!("Failed to read '{}'", into.display()))?
+            .next()
+            .is_some()
+    {
+        bail!(
+            "'{}' is not empty, restore into a new directory",
+            into.display()
+        );
+    }
+
+    let file = fs::File::open(archive)
+        .with_context(|| format!("Failed to open '{}'", archive.display()))?;
+    let mut tar = tar::Archive::new(zstd::Decoder::new(file)?);
+    fs::create_dir_all(into)
+        .with_context(|| format!("Failed to create directory '{}'", into.display()))?;
+
+    let mut files = 0;
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_dir() {
+            files += 1;
+        }
+        // Refuses paths escaping `into`
+        entry
+            .unpack_in(into)
+            .with_context(|| format!("Failed to restore '{}'", archive.display()))?;
+    }
+    Ok(files)
+}
+
+/// A summary of a backup, printed once it is written.
+pub fn summary(archive: &Path, files: usize) -> String {
+    let size = fs::metadata(archive).map_or(0, |m| m.len());
+    format!(
+        "Backed up {} files to '{}' ({})",
+        files,
+        archive.display(),
+        human_size(size)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_backup() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path().join("notes");
+        fs::create_dir_all(root.join("lang/rust/images")).unwrap();
+        fs::create_dir_all(root.join(".noxe")).unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::create_dir_all(root.join("empty")).unwrap();
+        fs::write(root.join("lang/rust/main.md"), "# Rust\n").unwrap();
+        fs::write(root.join("lang/rust/images/a.png"), "png").unwrap();
+        fs::write(root.join(".noxe/review.json"), "{}").unwrap();
+        fs::write(root.join(".git/HEAD"), "ref").unwrap();
+        fs::write(root.join(".ignore"), "*.log\n").unwrap();
+        fs::write(root.join("build.log"), "log").unwrap();
+
+        let archive = tmp_dir.path().join("backups/notes-20250102-030405.tar.zst");
+        assert_eq!(create(&root, &archive).unwrap(), 4);
+
+        let into = tmp_dir.path().join("restored");
+        assert_eq!(restore(&archive, &into).unwrap(), 4);
+        assert_eq!(
+            fs::read_to_string(into.join("lang/rust/main.md")).unwrap(),
+            "# Rust\n"
+        );
+        assert!(into.join(".noxe/review.json").is_file());
+        assert!(into.join("empty").is_dir());
+        assert!(!into.join("build.log").exists());
+        assert!(!into.join(".git").exists());
+        // Only into a fresh directory
+        assert!(restore(&archive, &into).is_err());
+
+        let dir = archive.parent().unwrap();
+        for time in ["20250101-000000", "20250103-000000"] {
+            fs::write(dir.join(format!("notes-{}.tar.zst", time)), "").unwrap();
+        }
+        fs::write(dir.join("other-20240101-000000.tar.zst"), "").unwrap();
+        let deleted = prune(dir, &root, 2).unwrap();
+        assert_eq!(deleted, [dir.join("notes-20250101-000000.tar.zst")]);
+        assert!(archive.exists());
+        assert!(dir.join("other-20240101-000000.tar.zst").exists());
+    }
+}
@@ -0,0 +1,161 @@
+use anyhow::{Context, Result, bail};
+use memmap2::Mmap;
+use std::{fs::File, io::Read, path::Path};
+
+use crate::record::human_size;
+
+/// The bytes of a note shown by `show`, `outline` and the previews, unless
+/// `NOXE_PREVIEW_LIMIT` (in KiB) says otherwise.
+const DEFAULT_PREVIEW_LIMIT: u64 = 1 << 20;
+/// The bytes of a note read for its metadata and its language, which are at its top.
+pub const METADATA_LIMIT: u64 = 256 << 10;
+/// The bytes of a note indexed by `search --near`, so that a vault of huge notes does not
+/// fill the memory.
+pub const CONTENT_LIMIT: u64 = 8 << 20;
+
+/// The start of a file, read up to a limit.
+#[derive(Debug)]
+pub struct Head {
+    pub text: String,
+    /// The size of the whole file, when the text is cut
+    pub truncated: Option<u64>,
+}
+
+impl Head {
+    /// The notice to print after a cut text.
+    pub fn notice(&self) -> Option<String> {
+        self.truncated
+            .map(|total| truncation_notice(self.text.len(), total))
+    }
+}
+
+/// The notice to print after the first `shown` bytes of a text of `total` bytes.
+pub fn truncation_notice(shown: usize, total: u64) -> String {
+    format!(
+        "[truncated: showing the first {} of {}, pass --full to show everything]",
+        human_size(shown as u64),
+        human_size(total)
+    )
+}
+
+/// The limit of previews: `NOXE_PREVIEW_LIMIT` KiB, 1 MiB by default.
+pub fn preview_limit() -> u64 {
+    std::env::var("NOXE_PREVIEW_LIMIT")
+        .ok()
+        .and_then(|kib| kib.trim().parse::<u64>().ok())
+        .filter(|kib| *kib > 0)
+        .map_or(DEFAULT_PREVIEW_LIMIT, |kib| kib << 10)
+}
+
+/// Read a text file up to `limit` bytes, without loading the rest. A cut text ends on a whole
+/// line, or on a whole character when its first line is longer than the limit.
+pub fn read_head(path: &Path, limit: u64) -> Result<Head> {
+    let file =
+        File::open(path).with_context(|| format!("Failed to read note '{}'", path.display()))?;
+    let total = file.metadata().map(|m| m.len()).unwrap_or(0);
+
+    let mut bytes = Vec::new();
+    // One more byte tells whether there is more than the limit, whatever the size says
+    file.take(limit.saturating_add(1))
+        .read_to_end(&mut bytes)
+        .with_context(|| format!("Failed to read note '{}'", path.display()))?;
+    let truncated = bytes.len() as u64 > limit;
+    if truncated {
+        bytes.truncate(limit as usize);
+    }
+
+    let Some(text) = cut_text(&bytes, truncated) else {
+        bail!("'{}' is not valid UTF-8 text", path.display());
+    };
+    Ok(Head {
+        text: text.to_string(),
+        truncated: truncated.then(|| total.max(limit + 1)),
+    })
+}
+
+/// Memory-map a text file and pass its first `limit` bytes, cut like [`read_head`], to `f`.
+/// The file is never copied as a whole, which keeps indexing a vault of huge notes cheap.
+pub fn map_head<T>(path: &Path, limit: u64, f: impl FnOnce(&str) -> T) -> Result<T> {
+    let file =
+        File::open(path).with_context(|| format!("Failed to read note '{}'", path.display()))?;
+    // SAFETY: the map is only read, and only while `f` runs. Notes are not expected to be
+    // truncated by another process in the meantime.
+    let map = unsafe { Mmap::map(&file) }
+        .with_context(|| format!("Failed to read note '{}'", path.display()))?;
+
+    let truncated = map.len() as u64 > limit;
+    let bytes = if truncated {
+        &map[..limit as usize]
+    } else {
+        &map[..]
+    };
+    let Some(text) = cut_text(bytes, truncated) else {
+        bail!("'{}' is not valid UTF-8 text", path.display());
+    };
+    Ok(f(text))
+}
+
+/// The text of `bytes`, which are the start of a longer text when `truncated`: then it is cut
+/// on its last whole line, or on its last whole character when it has no line break. `None`
+/// if it is not UTF-8.
+fn cut_text(bytes: &[u8], truncated: bool) -> Option<&str> {
+    if !truncated {
+        return std::str::from_utf8(bytes).ok();
+    }
+
+    let bytes = match bytes.iter().rposition(|&b| b == b'\n') {
+        Some(end) => &bytes[..=end],
+        None => bytes,
+    };
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Some(text),
+        // A character cut at the limit
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&bytes[..e.valid_up_to()]).ok(),
+        Err(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_read_head() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("huge.md");
+        fs::write(&path, "# Title\nsecond line\nthird\n").unwrap();
+
+        let head = read_head(&path, 1024).unwrap();
+        assert_eq!(head.text, "# Title\nsecond line\nthird\n");
+        assert!(head.truncated.is_none() && head.notice().is_none());
+
+        // Cut on the last whole line
+        let head = read_head(&path, 12).unwrap();
+        assert_eq!(head.text, "# Title\n");
+        assert_eq!(head.truncated, Some(26));
+        assert!(head.notice().unwrap().contains("first 8 of 26"));
+
+        // Cut on the last whole character of a long line
+        fs::write(&path, "héé").unwrap();
+        assert_eq!(read_head(&path, 4).unwrap().text, "hé");
+
+        fs::write(&path, [0x66, 0xff, 0x0a]).unwrap();
+        assert!(read_head(&path, 1024).is_err());
+    }
+
+    #[test]
+    fn test_map_head() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("huge.md");
+        fs::write(&path, "# Title\nsecond line\nthird\n").unwrap();
+
+        let lines = map_head(&path, 1024, |text| text.lines().count()).unwrap();
+        assert_eq!(lines, 3);
+        assert_eq!(map_head(&path, 12, str::to_string).unwrap(), "# Title\n");
+
+        fs::write(&path, "").unwrap();
+        assert_eq!(map_head(&path, 1024, str::to_string).unwrap(), "");
+    }
+}
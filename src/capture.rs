@@ -0,0 +1,333 @@
+use anyhow::{Context, Result, bail};
+use chrono::{
+    DateTime, Local,
+    format::{Item, StrftimeItems},
+};
+use serde::Deserialize;
+use std::{
+    ffi::OsString,
+    fs,
+    io::{self, BufRead, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::append;
+use crate::cli::NoteType;
+use crate::process::{exec_with, split_command};
+use crate::state::StateStore;
+use crate::vault::{Note, TemplateVars, render_template};
+
+pub const DEFAULT_INBOX: &str = "inbox.md";
+
+/// The scratch file edited when capturing with the editor, in the vault state.
+const DRAFT_FILE: &str = "capture-draft";
+
+/// Append a timestamped entry to the inbox note, creating it if needed.
+pub fn capture(note_root: &Path, inbox: &str, text: &str, now: DateTime<Local>) -> Result<PathBuf> {
+    let text = text.trim();
+    if text.is_empty() {
+        bail!("Nothing to capture");
+    }
+
+    let inbox = note_root.join(inbox);
+    let note_type = inbox.note_type().with_context(|| {
+        format!(
+            "The inbox '{}' must be a note file, e.g. inbox.md",
+            inbox.display()
+        )
+    })?;
+
+    let mut content = if inbox.exists() {
+        fs::read_to_string(&inbox)
+            .with_context(|| format!("Failed to read note '{}'", inbox.display()))?
+    } else {
+        if let Some(parent) = inbox.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory '{}'", parent.display()))?;
+        }
+        default_content(note_type)
+    };
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    content.push_str(&entry(note_type, text, now));
+
+    fs::write(&inbox, content)
+        .with_context(|| format!("Failed to write note '{}'", inbox.display()))?;
+    Ok(inbox)
+}
+
+/// A named capture template of the config, like `todo`, routing its entries to a note and a
+/// heading, so that `noxe capture todo "fix the build"` files the entry in the right place.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct CaptureTemplate {
+    /// The note the entries go to, relative to the note root. strftime placeholders are
+    /// expanded, e.g. `journal/%Y/%m/%Y-%m-%d.md`. The inbox by default
+    pub target: Option<String>,
+    /// The heading the entries are appended under, added when missing. The end of the note by
+    /// default
+    pub heading: Option<String>,
+    /// The entry, with the variables `{{text}}`, `{{date}}`, `{{time}}` and those of the
+    /// prompts. A timestamped list item of the text by default
+    pub entry: Option<String>,
+    /// Variables asked for when capturing, unless given with `--set NAME=VALUE`
+    pub prompts: Vec<String>,
+}
+
+impl CaptureTemplate {
+    /// Whether the entry includes the captured text. Templates whose entry only has prompts
+    /// need none.
+    pub fn takes_text(&self) -> bool {
+        self.entry.as_ref().is_none_or(|entry| {
+            render_template(entry, &TemplateVars::new())
+                != render_template(
+                    entry,
+                    &TemplateVars::from([("text".to_string(), String::new())]),
+                )
+        })
+    }
+}
+
+/// Capture with a template: render the entry and append it to the target of the template,
+/// creating the target if needed. `vars` are the values of the prompts given on the command
+/// line; the others are asked for.
+pub fn capture_with(
+    note_root: &Path,
+    template: &CaptureTemplate,
+    inbox: &str,
+    text: &str,
+    mut vars: TemplateVars,
+    now: DateTime<Local>,
+) -> Result<PathBuf> {
+    let pattern = template.target.as_deref().unwrap_or(inbox);
+    let target = note_root.join(expand_target(pattern, now)?);
+    let note_type = target.note_type().with_context(|| {
+        format!(
+            "The capture target '{}' must be a note file, e.g. todo.md",
+            target.display()
+        )
+    })?;
+
+    for prompt in &template.prompts {
+        if !vars.contains_key(prompt) {
+            vars.insert(prompt.clone(), ask(prompt)?);
+        }
+    }
+
+    let entry = match &template.entry {
+        Some(entry) => {
+            vars.insert("text".to_string(), text.trim().to_string());
+            vars.insert("date".to_string(), now.format("%Y-%m-%d").to_string());
+            vars.insert("time".to_string(), now.format("%H:%M").to_string());
+            render_template(entry, &vars)
+        }
+        None if text.trim().is_empty() => bail!("Nothing to capture"),
+        None => entry(note_type, text.trim(), now),
+    };
+
+    if !target.exists() {
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory '{}'", parent.display()))?;
+        }
+        let content = if template.target.is_none() {
+            default_content(note_type)
+        } else {
+            String::new()
+        };
+        fs::write(&target, content)
+            .with_context(|| format!("Failed to create note '{}'", target.display()))?;
+    }
+    append::append(&target, &entry, template.heading.as_deref(), None)?;
+    Ok(target)
+}
+
+/// Expand the strftime placeholders of the target of a capture template.
+pub fn expand_target(pattern: &str, now: DateTime<Local>) -> Result<PathBuf> {
+    if StrftimeItems::new(pattern).any(|item| item == Item::Error) {
+        bail!("Invalid placeholder in the capture target '{}'", pattern);
+    }
+    Ok(PathBuf::from(now.format(pattern).to_string()))
+}
+
+/// Parse the `NAME=VALUE` options giving the values of prompts.
+pub fn parse_vars(vars: &[String]) -> Result<TemplateVars> {
+    vars.iter()
+        .map(|var| match var.split_once('=') {
+            Some((name, value)) => Ok((name.trim().to_string(), value.to_string())),
+            None => bail!("Invalid variable '{}', expected NAME=VALUE", var),
+        })
+        .collect()
+}
+
+fn ask(prompt: &str) -> Result<String> {
+    let stdin = io::stdin();
+    if !stdin.is_terminal() {
+        bail!(
+            "No value for '{}': give it with --set {}=VALUE",
+            prompt,
+            prompt
+        );
+    }
+    eprint!("{}: ", prompt);
+    io::stderr().flush()?;
+    let mut value = String::new();
+    stdin
+        .lock()
+        .read_line(&mut value)
+        .with_context(|| "Failed to read user input")?;
+    Ok(value.trim().to_string())
+}
+
+fn default_content(note_type: NoteType) -> String {
+    match note_type {
+        NoteType::Md => "# Inbox\n\n".to_string(),
+        NoteType::Typ => "= Inbox\n\n".to_string(),
+        NoteType::Org => "#+TITLE: Inbox\n\n".to_string(),
+        NoteType::Custom(_) => String::new(),
+    }
+}
+
+/// A list item starting with the time, the following lines of the text indented under it.
+pub fn entry(note_type: NoteType, text: &str, now: DateTime<Local>) -> String {
+    let stamp = match note_type {
+        NoteType::Md => now.format("**%Y-%m-%d %H:%M**").to_string(),
+        NoteType::Typ => now.format("*%Y-%m-%d %H:%M*").to_string(),
+        NoteType::Org => now.format("[%Y-%m-%d %a %H:%M]").to_string(),
+        NoteType::Custom(_) => now.format("%Y-%m-%d %H:%M").to_string(),
+    };
+
+    let mut lines = text.lines();
+    let mut entry = format!("- {} {}\n", stamp, lines.next().unwrap_or_default());
+    for line in lines {
+        if line.trim().is_empty() {
+            entry.push('\n');
+        } else {
+            entry.push_str(&format!("  {}\n", line));
+        }
+    }
+    entry
+}
+
+/// The text to capture: the arguments, else stdin when it is not a terminal, else what the
+/// user writes in the editor.
+pub fn read_text(note_root: &Path, args: &[String], edit: &[OsString]) -> Result<String> {
+    if !args.is_empty() {
+        return Ok(args.join(" "));
+    }
+
+    let mut stdin = io::stdin();
+    if !stdin.is_terminal() {
+        let mut text = String::new();
+        stdin
+            .read_to_string(&mut text)
+            .with_context(|| "Failed to read the text from stdin")?;
+        return Ok(text);
+    }
+
+    let store = StateStore::new(note_root);
+    store.init()?;
+    let path = store.dir().join(DRAFT_FILE);
+    fs::write(&path, "")
+        .with_context(|| format!("Failed to create the draft '{}'", path.display()))?;
+
+    exec_with(&path, &editor(edit))?;
+
+    let text = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read the draft '{}'", path.display()))?;
+    let _ = fs::remove_file(&path);
+    Ok(text)
+}
+
+/// The edit command, else `$EDITOR`, else vim.
+fn editor(edit: &[OsString]) -> Vec<OsString> {
+    if !edit.is_empty() {
+        return edit.to_vec();
+    }
+    match std::env::var_os("EDITOR") {
+        Some(editor) if !editor.is_empty() => split_command(&editor.to_string_lossy()),
+        _ => vec!["vim".into()],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_capture() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        let now = Local.with_ymd_and_hms(2025, 3, 4, 9, 5, 0).unwrap();
+
+        let inbox = capture(root, DEFAULT_INBOX, "Read about lifetimes\n", now).unwrap();
+        capture(root, DEFAULT_INBOX, "Call Bob\nabout the\n\ndraft", now).unwrap();
+        assert_eq!(
+            fs::read_to_string(&inbox).unwrap(),
+            "# Inbox\n\n- **2025-03-04 09:05** Read about lifetimes\n- **2025-03-04 09:05** Call Bob\n  about the\n\n  draft\n"
+        );
+
+        let org = capture(root, "gtd/inbox.org", "Idea", now).unwrap();
+        assert_eq!(
+            fs::read_to_string(org).unwrap(),
+            "#+TITLE: Inbox\n\n- [2025-03-04 Tue 09:05] Idea\n"
+        );
+
+        assert!(capture(root, DEFAULT_INBOX, "  \n", now).is_err());
+        assert!(capture(root, "inbox.txt", "Idea", now).is_err());
+    }
+
+    #[test]
+    fn test_capture_with() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        let now = Local.with_ymd_and_hms(2025, 3, 4, 9, 5, 0).unwrap();
+
+        let todo = CaptureTemplate {
+            target: Some("todo.md".to_string()),
+            heading: Some("Tasks".to_string()),
+            entry: Some("- [ ] {{text}} ({{project}}, {{date}})".to_string()),
+            prompts: vec!["project".to_string()],
+        };
+        assert!(todo.takes_text());
+        let vars = parse_vars(&["project=noxe".to_string()]).unwrap();
+        let note = capture_with(root, &todo, DEFAULT_INBOX, "fix the build", vars, now).unwrap();
+        assert_eq!(
+            fs::read_to_string(&note).unwrap(),
+            "## Tasks\n\n- [ ] fix the build (noxe, 2025-03-04)\n"
+        );
+        // Not a terminal, the prompt cannot be asked
+        assert!(capture_with(root, &todo, DEFAULT_INBOX, "x", TemplateVars::new(), now).is_err());
+
+        let log = CaptureTemplate {
+            target: Some("journal/%Y/%m-%d.md".to_string()),
+            heading: Some("Log".to_string()),
+            ..Default::default()
+        };
+        let note = capture_with(
+            root,
+            &log,
+            DEFAULT_INBOX,
+            "shipped",
+            TemplateVars::new(),
+            now,
+        )
+        .unwrap();
+        assert_eq!(note, root.join("journal/2025/03-04.md"));
+        assert_eq!(
+            fs::read_to_string(&note).unwrap(),
+            "## Log\n\n- **2025-03-04 09:05** shipped\n"
+        );
+
+        let mood = CaptureTemplate {
+            entry: Some("mood: {{mood}}".to_string()),
+            ..Default::default()
+        };
+        assert!(!mood.takes_text());
+        assert!(expand_target("a/%Q.md", now).is_err());
+        assert!(parse_vars(&["mood".to_string()]).is_err());
+    }
+}
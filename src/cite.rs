@@ -0,0 +1,738 @@
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+use walkdir::WalkDir;
+
+use crate::ai;
+use crate::atomic;
+use crate::cli::{BibCommand, NoteType};
+use crate::include::normalize;
+use crate::metadata::parse_metadata;
+use crate::process::find_note_dir;
+use crate::vault::{Note, Scope, scoped_notes};
+
+/// The directory of the note root holding the bibliographies shared by all notes.
+pub const SHARED_DIR: &str = "bibliography";
+
+/// The bibliography of a dirnote `noxe bib add` appends to.
+pub const REFS_FILE: &str = "refs.bib";
+
+/// Where the bibliography of a note comes from.
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BibScope {
+    /// The bibliographies of the note itself
+    Note,
+    /// The shared `bibliography/` directory of the vault
+    Vault,
+}
+
+/// `@article{key,` entries, and the `@string`/`@comment`/`@preamble` directives.
+static BIB_ENTRY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^\s*@(\w+)\s*[{(]\s*([^,\s]+)\s*,").unwrap());
+
+/// The prefixes a DOI may come with.
+const DOI_PREFIXES: [&str; 4] = [
+    "https://doi.org/",
+    "http://doi.org/",
+    "https://dx.doi.org/",
+    "doi:",
+];
+
+/// The start of any `@type{` or `@type(` entry of a `.bib` file.
+static BIB_START: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?m)^\s*@\w+\s*[{(]").unwrap());
+
+/// The top-level keys of hayagriva (`.yml`) bibliographies.
+static YAML_ENTRY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^([\w][^:\s]*):\s*$").unwrap());
+
+/// `[@key]`, `[see @key, p. 3; @other]`, `[-@key]`
+static PANDOC_CITATION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\[[^\[\]]*@[^\[\]]*\]").unwrap());
+
+/// `@key`, not preceded by a word character as in e-mail addresses.
+static AT_KEY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:^|[^\w@])@([\w][\w:.\-/]*)").unwrap());
+
+static TYPST_CITE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"#cite\(\s*<([^>\s]+)>").unwrap());
+
+static TYPST_LABEL: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<([\w:.\-]+)>").unwrap());
+
+/// `#bibliography("refs.bib")` and `#bibliography(("a.bib", "b.yml"))`
+static TYPST_BIBLIOGRAPHY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"#bibliography\(([^)]*)\)").unwrap());
+
+static QUOTED: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#""([^"]+)""#).unwrap());
+
+/// `[^label]` references and `[^label]:` definitions of markdown footnotes.
+static FOOTNOTE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)(^)?\[\^([^\]\s]+)\](:)?").unwrap());
+
+static CODE_SPAN: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"`[^`\n]*`").unwrap());
+
+/// A problem of the citations or footnotes of the vault. Paths are relative to the note root.
+#[derive(Debug, PartialEq)]
+pub enum Finding {
+    UnknownKey { note: PathBuf, key: String },
+    DuplicateKey { key: String, files: Vec<PathBuf> },
+    Uncited { file: PathBuf, key: String },
+    UndefinedFootnote { note: PathBuf, label: String },
+    UnusedFootnote { note: PathBuf, label: String },
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Finding::UnknownKey { note, key } => write!(
+                f,
+                "{}: citation key '{}' is in none of its bibliographies",
+                note.display(),
+                key
+            ),
+            Finding::DuplicateKey { key, files } => write!(
+                f,
+                "{}: key '{}' is defined more than once",
+                files
+                    .iter()
+                    .map(|p| p.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                key
+            ),
+            Finding::Uncited { file, key } => {
+                write!(f, "{}: '{}' is never cited", file.display(), key)
+            }
+            Finding::UndefinedFootnote { note, label } => {
+                write!(
+                    f,
+                    "{}: footnote '[^{}]' is not defined",
+                    note.display(),
+                    label
+                )
+            }
+            Finding::UnusedFootnote { note, label } => write!(
+                f,
+                "{}: footnote '[^{}]' is defined but never referenced",
+                note.display(),
+                label
+            ),
+        }
+    }
+}
+
+pub fn process_bib_command(command: BibCommand) -> Result<()> {
+    match command {
+        BibCommand::List {
+            note_path,
+            shared,
+            note_root,
+            bibliography_order,
+        } => {
+            let root = Path::new(&note_root);
+            let files = match note_path {
+                Some(note_path) if !shared => {
+                    let note = find_note_dir(&note_path, &note_root)?;
+                    match bib_files(root, &note, &bibliography_order)? {
+                        Some((_, files)) => files,
+                        None => bail!("'{}' has no bibliography", note.display()),
+                    }
+                }
+                _ => shared_bib_files(root),
+            };
+            if files.is_empty() {
+                bail!("No bibliography in '{}'", root.join(SHARED_DIR).display());
+            }
+
+            let entries: Vec<(String, PathBuf)> = files
+                .iter()
+                .flat_map(|file| {
+                    let file_name = file.strip_prefix(root).unwrap_or(file).to_path_buf();
+                    bib_keys(file)
+                        .into_iter()
+                        .map(move |k| (k, file_name.clone()))
+                })
+                .collect();
+            let width = entries.iter().map(|(k, _)| k.len()).max().unwrap_or(0);
+            for (key, file) in entries {
+                println!("{:<width$}  {}", key, file.display());
+            }
+        }
+        BibCommand::Add {
+            note_path,
+            source,
+            note_root,
+        } => {
+            let root = Path::new(&note_root);
+            let note = find_note_dir(&note_path, &note_root)?;
+            if !note.is_dirnote() {
+                bail!(
+                    "'{}' is a filenote, only dirnotes have a bibliography/",
+                    note.display()
+                );
+            }
+
+            let bibtex = if Path::new(&source).is_file() {
+                fs::read_to_string(&source)
+                    .with_context(|| format!("Failed to read '{}'", source))?
+            } else {
+                fetch_doi(&source)?
+            };
+            let file = note.join(SHARED_DIR).join(REFS_FILE);
+            let (added, skipped) = add_entries(&file, &bibtex)?;
+            let file = file.strip_prefix(root).unwrap_or(&file);
+            for key in &added {
+                println!("Added '{}' to '{}'", key, file.display());
+            }
+            for key in &skipped {
+                println!("'{}' is already in '{}'", key, file.display());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Fetch the BibTeX entry of a DOI (`10.1145/3290368`, `doi:...` or `https://doi.org/...`)
+/// from doi.org.
+pub fn fetch_doi(doi: &str) -> Result<String> {
+    let doi = doi.trim();
+    let doi = DOI_PREFIXES
+        .iter()
+        .find_map(|prefix| doi.strip_prefix(prefix))
+        .unwrap_or(doi);
+    if !doi.starts_with("10.") {
+        bail!("'{}' is neither a BibTeX file nor a DOI", doi);
+    }
+
+    let mut res = ai::agent()
+        .get(format!("https://doi.org/{}", doi))
+        .header("Accept", "application/x-bibtex; charset=utf-8")
+        .call()
+        .with_context(|| format!("Failed to reach doi.org for '{}'", doi))?;
+    let status = res.status();
+    let text = res
+        .body_mut()
+        .read_to_string()
+        .with_context(|| format!("Failed to read the entry of '{}'", doi))?;
+    if !status.is_success() {
+        bail!("doi.org returned {} for '{}'", status, doi);
+    }
+    Ok(text)
+}
+
+/// The entries of BibTeX text with their keys, `@string`/`@comment`/`@preamble` directives
+/// having none.
+fn bib_entries(bibtex: &str) -> Vec<(Option<String>, String)> {
+    let starts: Vec<usize> = BIB_START.find_iter(bibtex).map(|m| m.start()).collect();
+    starts
+        .iter()
+        .enumerate()
+        .map(|(i, &start)| {
+            let end = starts.get(i + 1).copied().unwrap_or(bibtex.len());
+            let entry = bibtex[start..end].trim();
+            let key = BIB_ENTRY
+                .captures(entry)
+                .filter(|c| {
+                    !["string", "comment", "preamble"]
+                        .iter()
+                        .any(|d| c[1].eq_ignore_ascii_case(d))
+                })
+                .map(|c| c[2].to_string());
+            (key, entry.to_string())
+        })
+        .collect()
+}
+
+/// Append the entries of `bibtex` to a `.bib` file, creating it, but those whose key it already
+/// has. Returns the keys added and skipped.
+pub fn add_entries(file: &Path, bibtex: &str) -> Result<(Vec<String>, Vec<String>)> {
+    let entries = bib_entries(bibtex);
+    if entries.iter().all(|(key, _)| key.is_none()) {
+        bail!("No BibTeX entry found");
+    }
+
+    let mut content = fs::read_to_string(file).unwrap_or_default();
+    let mut keys = bib_keys(file);
+    let (mut added, mut skipped) = (Vec::new(), Vec::new());
+    for (key, entry) in entries {
+        if let Some(key) = &key {
+            if keys.contains(key) {
+                skipped.push(key.clone());
+                continue;
+            }
+            keys.push(key.clone());
+            added.push(key.clone());
+        }
+        if !content.trim().is_empty() {
+            content.truncate(content.trim_end().len());
+            content.push_str("\n\n");
+        }
+        content.push_str(&entry);
+        content.push('\n');
+    }
+
+    if !added.is_empty() {
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create directory '{}'", dir.display()))?;
+        }
+        atomic::write(file, content)?;
+    }
+    Ok((added, skipped))
+}
+
+/// The bibliography files shared by the notes of the vault.
+pub fn shared_bib_files(note_root: &Path) -> Vec<PathBuf> {
+    bib_files_under(&note_root.join(SHARED_DIR))
+}
+
+/// The bibliography files of a note itself: see [`note_bib_files`].
+pub fn own_bib_files(note: &Path) -> Result<Vec<PathBuf>> {
+    let main_file = note.main_file_path()?;
+    let note_type = main_file.note_type()?;
+    if !matches!(note_type, NoteType::Md | NoteType::Typ) {
+        return Ok(Vec::new());
+    }
+    let contents: Vec<String> = sources(note, &main_file)
+        .iter()
+        .filter_map(|s| fs::read_to_string(s).ok())
+        .map(|c| strip_code(&c))
+        .collect();
+    Ok(note_bib_files(note, &main_file, note_type, &contents))
+}
+
+/// The bibliography files a note is exported with: those of the first scope of `order` with
+/// any entry. Empty files, like the `refs.bib` of new dirnotes, do not count.
+pub fn bib_files(
+    note_root: &Path,
+    note: &Path,
+    order: &[BibScope],
+) -> Result<Option<(BibScope, Vec<PathBuf>)>> {
+    for &scope in order {
+        let files = match scope {
+            BibScope::Note => own_bib_files(note)?,
+            BibScope::Vault => shared_bib_files(note_root),
+        };
+        if files.iter().any(|f| !bib_keys(f).is_empty()) {
+            return Ok(Some((scope, files)));
+        }
+    }
+    Ok(None)
+}
+
+/// Check that the keys cited by the notes are in the bibliographies they can see (their own
+/// and the shared ones under `bibliography/`), that no key is defined twice, that every entry is
+/// cited, and that the footnotes of markdown notes are both referenced and defined.
+///
+/// Markdown notes cite with pandoc's bracketed `[@key]`; typst notes with `@key`, unless a
+/// `<key>` label of the note is meant, or `#cite(<key>)`.
+pub fn check(note_root: &Path, scope: Scope) -> Result<Vec<Finding>> {
+    let relative = |p: &Path| p.strip_prefix(note_root).unwrap_or(p).to_path_buf();
+    let shared = shared_bib_files(note_root);
+
+    // The keys of each bibliography file, in order
+    let mut bibliographies: BTreeMap<PathBuf, Vec<String>> = BTreeMap::new();
+    let mut cited: HashSet<(PathBuf, String)> = HashSet::new();
+    let mut findings = Vec::new();
+
+    let mut notes = scoped_notes(note_root, note_root, scope)?;
+    notes.sort();
+    for note in notes {
+        let Ok(main_file) = note.main_file_path() else {
+            continue;
+        };
+        let Ok(note_type) = main_file.note_type() else {
+            continue;
+        };
+        if !matches!(note_type, NoteType::Md | NoteType::Typ) {
+            continue;
+        }
+        let sources = sources(&note, &main_file);
+        let contents: Vec<String> = sources
+            .iter()
+            .filter_map(|s| fs::read_to_string(s).ok())
+            .map(|c| strip_code(&c))
+            .collect();
+
+        let mut files = note_bib_files(&note, &main_file, note_type, &contents);
+        files.extend(shared.iter().cloned());
+        for file in &files {
+            if !bibliographies.contains_key(file) {
+                bibliographies.insert(file.clone(), bib_keys(file));
+            }
+        }
+
+        for key in citations(note_type, &contents) {
+            let holders: Vec<&PathBuf> = files
+                .iter()
+                .filter(|f| bibliographies[*f].contains(&key))
+                .collect();
+            if holders.is_empty() {
+                findings.push(Finding::UnknownKey {
+                    note: relative(&note),
+                    key,
+                });
+            } else {
+                for file in holders {
+                    cited.insert((file.clone(), key.clone()));
+                }
+            }
+        }
+
+        if note_type == NoteType::Md {
+            let (references, definitions) = footnotes(&contents);
+            for label in references.difference(&definitions) {
+                findings.push(Finding::UndefinedFootnote {
+                    note: relative(&note),
+                    label: label.clone(),
+                });
+            }
+            for label in definitions.difference(&references) {
+                findings.push(Finding::UnusedFootnote {
+                    note: relative(&note),
+                    label: label.clone(),
+                });
+            }
+        }
+    }
+
+    let mut defined: BTreeMap<&String, Vec<PathBuf>> = BTreeMap::new();
+    for (file, keys) in &bibliographies {
+        for key in keys {
+            defined.entry(key).or_default().push(relative(file));
+        }
+    }
+    for (key, files) in defined {
+        if files.len() > 1 {
+            findings.push(Finding::DuplicateKey {
+                key: key.clone(),
+                files,
+            });
+        }
+    }
+    for (file, keys) in &bibliographies {
+        for key in keys {
+            if !cited.contains(&(file.clone(), key.clone())) {
+                findings.push(Finding::Uncited {
+                    file: relative(file),
+                    key: key.clone(),
+                });
+            }
+        }
+    }
+
+    Ok(findings)
+}
+
+/// The files of a note written in its language: every one of a dirnote (chapters...), the
+/// note itself for a filenote.
+pub(crate) fn sources(note: &Path, main_file: &Path) -> Vec<PathBuf> {
+    if !note.is_dir() {
+        return vec![main_file.to_path_buf()];
+    }
+    let mut sources: Vec<PathBuf> = WalkDir::new(note)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .map(|e| e.into_path())
+        .filter(|p| p.is_file() && p.extension() == main_file.extension())
+        .collect();
+    sources.sort();
+    sources
+}
+
+/// The bibliographies of a note: the `.bib` files of a dirnote, and the files named by the
+/// `bibliography` field of markdown notes or `#bibliography(...)` of typst notes.
+fn note_bib_files(
+    note: &Path,
+    main_file: &Path,
+    note_type: NoteType,
+    contents: &[String],
+) -> Vec<PathBuf> {
+    let mut files = if note.is_dir() {
+        bib_files_under(note)
+    } else {
+        Vec::new()
+    };
+
+    let dir = main_file.parent().unwrap_or(Path::new("."));
+    let named: Vec<String> = match note_type {
+        NoteType::Md => match fs::read_to_string(main_file)
+            .map(|c| parse_metadata(&c, NoteType::Md).get("bibliography"))
+        {
+            Ok(Some(Value::String(file))) => vec![file],
+            Ok(Some(Value::Array(files))) => files
+                .iter()
+                .filter_map(|f| f.as_str().map(str::to_string))
+                .collect(),
+            _ => Vec::new(),
+        },
+        _ => contents
+            .iter()
+            .flat_map(|c| TYPST_BIBLIOGRAPHY.captures_iter(c))
+            .flat_map(|c| {
+                QUOTED
+                    .captures_iter(c.get(1).unwrap().as_str())
+                    .map(|q| q[1].to_string())
+                    .collect::<Vec<_>>()
+            })
+            .collect(),
+    };
+    for name in named {
+        let path = match name.strip_prefix('/') {
+            Some(rooted) => note.join(rooted),
+            None => dir.join(name),
+        };
+        let path = normalize(&path);
+        if !files.contains(&path) {
+            files.push(path);
+        }
+    }
+
+    files
+}
+
+fn bib_files_under(dir: &Path) -> Vec<PathBuf> {
+    if !dir.is_dir() {
+        return Vec::new();
+    }
+    let mut files: Vec<PathBuf> = WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .map(|e| normalize(e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "bib"))
+        .collect();
+    files.sort();
+    files
+}
+
+/// The keys of a bibliography, `.bib` or hayagriva `.yml`, in order.
+pub(crate) fn bib_keys(file: &Path) -> Vec<String> {
+    let Ok(content) = fs::read_to_string(file) else {
+        return Vec::new();
+    };
+    if file
+        .extension()
+        .is_some_and(|ext| ext == "yml" || ext == "yaml")
+    {
+        return YAML_ENTRY
+            .captures_iter(&content)
+            .map(|c| c[1].to_string())
+            .collect();
+    }
+    BIB_ENTRY
+        .captures_iter(&content)
+        .filter(|c| {
+            !["string", "comment", "preamble"]
+                .iter()
+                .any(|d| c[1].eq_ignore_ascii_case(d))
+        })
+        .map(|c| c[2].to_string())
+        .collect()
+}
+
+/// The keys cited by the files of a note, sorted.
+pub(crate) fn citations(note_type: NoteType, contents: &[String]) -> BTreeSet<String> {
+    let mut keys = BTreeSet::new();
+    match note_type {
+        NoteType::Md => {
+            for content in contents {
+                for citation in PANDOC_CITATION.find_iter(content) {
+                    // `[^note]` footnotes and `[text](mailto:a@b)` are not citations
+                    if citation.as_str().starts_with("[^") {
+                        continue;
+                    }
+                    keys.extend(
+                        AT_KEY
+                            .captures_iter(citation.as_str())
+                            .map(|c| trim_key(&c[1])),
+                    );
+                }
+            }
+        }
+        _ => {
+            let labels: HashSet<String> = contents
+                .iter()
+                .map(|c| TYPST_CITE.replace_all(c, ""))
+                .flat_map(|c| {
+                    TYPST_LABEL
+                        .captures_iter(&c)
+                        .map(|l| l[1].to_string())
+                        .collect::<Vec<_>>()
+                })
+                .collect();
+            for content in contents {
+                keys.extend(
+                    AT_KEY
+                        .captures_iter(content)
+                        .map(|c| trim_key(&c[1]))
+                        .filter(|k| !labels.contains(k)),
+                );
+                keys.extend(TYPST_CITE.captures_iter(content).map(|c| c[1].to_string()));
+            }
+        }
+    }
+    keys
+}
+
+/// Keys may contain `.` and `:`, but not end with them, which ends the sentence instead.
+fn trim_key(key: &str) -> String {
+    key.trim_end_matches(['.', ':', '-', '/']).to_string()
+}
+
+/// The referenced and the defined footnote labels.
+fn footnotes(contents: &[String]) -> (BTreeSet<String>, BTreeSet<String>) {
+    let mut references = BTreeSet::new();
+    let mut definitions = BTreeSet::new();
+    for content in contents {
+        for c in FOOTNOTE.captures_iter(content) {
+            let label = c[2].to_string();
+            if c.get(1).is_some() && c.get(3).is_some() {
+                definitions.insert(label);
+            } else {
+                references.insert(label);
+            }
+        }
+    }
+    (references, definitions)
+}
+
+/// Blank out fenced code blocks, code spans and line comments, where `@` is not a citation.
+pub(crate) fn strip_code(content: &str) -> String {
+    let mut stripped = String::new();
+    let mut in_fence = false;
+    for line in content.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+        } else if !in_fence && !trimmed.starts_with("//") {
+            stripped.push_str(&CODE_SPAN.replace_all(line, ""));
+        }
+        stripped.push('\n');
+    }
+    stripped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_check() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        fs::create_dir_all(root.join("bibliography")).unwrap();
+        fs::write(
+            root.join("bibliography/shared.bib"),
+            "@book{knuth84,\n  title = {TeX}\n}\n@string{x = \"y\"}\n@misc{unused, title={u}}\n",
+        )
+        .unwrap();
+
+        fs::create_dir_all(root.join("paper/chapters")).unwrap();
+        fs::write(
+            root.join("paper/main.typ"),
+            "= Intro <intro>\nSee @intro and @knuth84.\nMail a@b.com.\n#include \"chapters/a.typ\"\n#bibliography(\"refs.yml\")\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("paper/chapters/a.typ"),
+            "#cite(<lamport94>) and @missing.\n// @commented\n`@code`\n",
+        )
+        .unwrap();
+        fs::write(root.join("paper/refs.yml"), "lamport94:\n  type: book\n").unwrap();
+        fs::write(root.join("paper/local.bib"), "@article{knuth84,\n}\n").unwrap();
+
+        fs::write(
+            root.join("notes.md"),
+            "---\nbibliography: refs.bib\n---\nAs [see @knuth84, p. 3; @doe20] and @bare.\nA note[^1] and [^2].\n\n[^1]: Defined.\n[^3]: Unused.\n\n```\n[@incode]\n```\n",
+        )
+        .unwrap();
+        fs::write(root.join("refs.bib"), "@misc{doe20, title={d}}\n").unwrap();
+
+        let findings = check(root, Scope::Active).unwrap();
+        let messages: Vec<String> = findings.iter().map(|f| f.to_string()).collect();
+        assert_eq!(
+            messages,
+            [
+                "notes.md: footnote '[^2]' is not defined",
+                "notes.md: footnote '[^3]' is defined but never referenced",
+                "paper: citation key 'missing' is in none of its bibliographies",
+                "bibliography/shared.bib, paper/local.bib: key 'knuth84' is defined more than once",
+                "bibliography/shared.bib: 'unused' is never cited",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bib_files() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        fs::create_dir_all(root.join("bibliography")).unwrap();
+        fs::write(root.join("bibliography/shared.bib"), "@book{knuth84,}\n").unwrap();
+
+        // The empty refs.bib of a new dirnote is no bibliography
+        fs::create_dir_all(root.join("paper/bibliography")).unwrap();
+        fs::write(root.join("paper/main.typ"), "= Paper\n").unwrap();
+        fs::write(root.join("paper/bibliography/refs.bib"), "").unwrap();
+        fs::write(root.join("notes.md"), "---\nbibliography: refs.bib\n---\n").unwrap();
+        fs::write(root.join("refs.bib"), "@misc{doe20,}\n").unwrap();
+
+        let order = [BibScope::Note, BibScope::Vault];
+        let shared = (BibScope::Vault, vec![root.join("bibliography/shared.bib")]);
+        let own = (BibScope::Note, vec![root.join("refs.bib")]);
+        assert_eq!(
+            bib_files(root, &root.join("paper"), &order).unwrap(),
+            Some(shared.clone())
+        );
+        assert_eq!(
+            bib_files(root, &root.join("notes.md"), &order).unwrap(),
+            Some(own)
+        );
+        assert_eq!(
+            bib_files(
+                root,
+                &root.join("notes.md"),
+                &[BibScope::Vault, BibScope::Note]
+            )
+            .unwrap(),
+            Some(shared)
+        );
+        assert_eq!(
+            bib_files(root, &root.join("paper"), &[BibScope::Note]).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_add_entries() {
+        let tmp_dir = tempdir().unwrap();
+        let file = tmp_dir.path().join("paper/bibliography/refs.bib");
+
+        let fetched = " @article{Knuth_1984, title={Literate Programming}, year={1984}}\n";
+        assert_eq!(
+            add_entries(&file, fetched).unwrap(),
+            (vec!["Knuth_1984".to_string()], vec![])
+        );
+        let exported = "@string{acm = \"ACM\"}\n\n@book{Knuth_1984,\n  title = {Again}\n}\n\n\
+            @misc{doe20,\n  publisher = acm\n}\n";
+        assert_eq!(
+            add_entries(&file, exported).unwrap(),
+            (vec!["doe20".to_string()], vec!["Knuth_1984".to_string()])
+        );
+        assert_eq!(
+            fs::read_to_string(&file).unwrap(),
+            "@article{Knuth_1984, title={Literate Programming}, year={1984}}\n\n\
+            @string{acm = \"ACM\"}\n\n@misc{doe20,\n  publisher = acm\n}\n"
+        );
+        assert_eq!(bib_keys(&file), ["Knuth_1984", "doe20"]);
+        assert!(add_entries(&file, "not bibtex").is_err());
+    }
+}
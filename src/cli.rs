@@ -1,10 +1,25 @@
-use std::ffi::OsString;
+use std::{ffi::OsString, path::PathBuf, sync::OnceLock};
 
 use clap::{
-    Parser, ValueEnum, builder::NonEmptyStringValueParser, crate_authors, crate_description,
-    crate_name, crate_version,
+    Parser, Subcommand, ValueEnum,
+    builder::{NonEmptyStringValueParser, PossibleValue},
+    crate_authors, crate_description, crate_name, crate_version,
 };
 
+use serde::{Deserialize, Serialize};
+
+use crate::cite::BibScope;
+use crate::complete::Shell;
+use crate::config;
+use crate::context::ContextItem;
+use crate::doctor::DoctorPass;
+use crate::garden::Tier;
+use crate::graph::GraphFormat;
+use crate::meta::MetaAction;
+use crate::metadata::FieldFilter;
+use crate::sort::SortKey;
+use crate::vault::Scope;
+
 #[derive(Parser, Debug)]
 #[command(
     name = crate_name!(),
@@ -12,10 +27,43 @@ use clap::{
     version = crate_version!(),
     about = crate_description!()
 )]
-pub enum Cli {
+pub struct Cli {
+    /// Print debug logs to stderr. Secrets (e.g. Authorization headers) are redacted.
+    #[arg(short = 'v', long, global = true, default_value = "false")]
+    pub verbose: bool,
+
+    /// Emit machine-readable progress events of long operations (export, ai enrich, semantic
+    /// search)
+    #[arg(long, global = true, value_enum)]
+    pub events: Option<EventFormat>,
+
+    /// Write the events to this file instead of stderr
+    #[arg(long, global = true, requires = "events")]
+    pub events_file: Option<PathBuf>,
+
+    /// Print the time spent in each phase of the startup (config, parsing, index...) to stderr
+    #[arg(long, global = true)]
+    pub profile_startup: bool,
+
+    /// The vault of the config to use instead of the current one (see `noxe vault switch`)
+    #[arg(long, global = true, env = "NOXE_VAULT")]
+    pub vault: Option<String>,
+
+    /// Let note names resolve to the notes of the archive or the trash when no other note has
+    /// the name
+    #[arg(long, global = true)]
+    pub include_archived: bool,
+
+    /// Without a command, a guided setup runs when no vault is configured
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
     #[command(about = "Create a new note")]
     New {
-        /// The path of the note. If the note path includes an extension (e.g., .md or .typ), the note type will be
+        /// The path of the note. If the note path includes an extension (e.g., .md, .typ or .org), the note type will be
         /// automatically inferred and the note will be created as a single file.
         note_path: OsString,
 
@@ -27,7 +75,7 @@ pub enum Cli {
         #[arg(short = 'k', long, value_delimiter = ',')]
         note_keywords: Vec<String>,
 
-        /// Specify the note type (md|typ). Default is 'typ'
+        /// Specify the note type (md|typ|org, or a type of the config). Default is 'typ'
         #[arg(short = 't', long, default_value_t, value_enum, env = "NOXE_TYPE")]
         note_type: NoteType,
 
@@ -37,8 +85,53 @@ pub enum Cli {
         #[arg(short = 'S', long, env = "NOXE_TEMPLATE")]
         note_template: Option<OsString>,
 
+        /// Use the template of this name from the template registry instead of a template file
+        #[arg(short = 'T', long)]
+        template: Option<String>,
+
+        /// Set a template variable, used as `{{key}}` in the template. Built-in variables are
+        /// title, author, keywords, date and category
+        #[arg(long = "var", value_name = "KEY=VALUE", value_parser = parse_var)]
+        vars: Vec<(String, String)>,
+
+        /// The directory where the templates are stored. Default is `~/.config/noxe/templates`
+        #[arg(long, env = "NOXE_TEMPLATE_DIR")]
+        template_dir: Option<PathBuf>,
+
+        /// Capture the environment into the `context` metadata field (comma-separated),
+        /// instead of what the `context` of the template says
+        #[arg(long, value_enum, value_delimiter = ',')]
+        context: Vec<ContextItem>,
+
+        /// The git repository captured by `--context git`. Default is the current directory
+        #[arg(long)]
+        context_repo: Option<PathBuf>,
+
         #[arg(short = 'm', long, default_value = "true")]
         note_with_metadata: bool,
+
+        /// Prefix the note with a timestamp ID, e.g. `202505171230-title.md`, which finds it
+        /// and resolves links to it whatever its title becomes
+        #[arg(long, env = "NOXE_ZETTEL_IDS")]
+        id: bool,
+    },
+
+    #[command(about = "Delete a note", visible_alias = "delete")]
+    Rm {
+        /// The path or name of the note
+        note_path: OsString,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// Do not ask for confirmation
+        #[arg(short = 'y', long)]
+        yes: bool,
+
+        /// Delete the note even if it is read-only
+        #[arg(long = "override")]
+        override_lock: bool,
     },
 
     #[command(about = "Preview note")]
@@ -60,6 +153,131 @@ pub enum Cli {
         /// eg. `glow`
         #[arg(long, value_delimiter = ' ', env = "NOXE_PREVIEW_MARKDOWN")]
         preview_markdown: Vec<OsString>,
+
+        /// Custom org preview command. The note path will automatically be appended to the command.
+        /// eg. `emacs -nw`
+        #[arg(long, value_delimiter = ' ', env = "NOXE_PREVIEW_ORG")]
+        preview_org: Vec<OsString>,
+
+        /// Render the markdown note to HTML on a local web server, reloading the page in the
+        /// browser whenever the note is saved
+        #[arg(short = 's', long)]
+        serve: bool,
+
+        /// The port of the server, a free one by default
+        #[arg(short = 'p', long, default_value = "0", requires = "serve")]
+        port: u16,
+
+        /// Compile the typst note to a PDF under `.noxe/preview` and open it with the PDF viewer
+        #[arg(long, conflicts_with = "serve")]
+        pdf: bool,
+
+        /// Compile the PDF again whenever the note is saved
+        #[arg(short = 'w', long, requires = "pdf")]
+        watch: bool,
+
+        /// The PDF viewer, e.g. `zathura`. The default application by default
+        #[arg(long, value_delimiter = ' ', env = "NOXE_PDF_VIEWER")]
+        pdf_viewer: Vec<OsString>,
+    },
+
+    #[command(about = "Print the content of a note", visible_alias = "cat")]
+    Show {
+        /// The path or name of the note. When it is a name, the note will be searched in the note directory.
+        note_path: OsString,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// Print the body only, without the front matter or typst metadata
+        #[arg(long, conflicts_with = "metadata_only")]
+        no_frontmatter: bool,
+
+        /// Print the metadata only, as JSON
+        #[arg(long)]
+        metadata_only: bool,
+
+        /// Print the whole of a huge note, not only its first `NOXE_PREVIEW_LIMIT` KiB
+        #[arg(long)]
+        full: bool,
+    },
+
+    #[command(
+        about = "Print the outline of a note with the status and tags of its sections, or the annotated sections of every note"
+    )]
+    Outline {
+        /// The path or name of the note. Every note when omitted, showing their annotated
+        /// sections only
+        note_path: Option<OsString>,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// Only show the sections with this status, e.g. draft
+        #[arg(short = 's', long)]
+        status: Option<String>,
+
+        /// Only show the sections with this tag
+        #[arg(short = 'T', long)]
+        tag: Option<String>,
+
+        /// Read the whole of huge notes, not only their first `NOXE_PREVIEW_LIMIT` KiB
+        #[arg(long)]
+        full: bool,
+
+        /// The output format
+        #[arg(long, default_value_t, value_enum, env = "NOXE_FORMAT")]
+        format: OutputFormat,
+    },
+
+    #[command(
+        about = "Export the flashcards of a note or a category as a TSV file that Anki imports"
+    )]
+    Flashcards {
+        /// The path or name of the note, or a category to export all of its notes
+        target: OsString,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// Write the cards to this file rather than to stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// The deck of the cards. Each category gets a subdeck, e.g. `noxe::lang::rust`
+        #[arg(long, default_value = "noxe")]
+        deck: String,
+    },
+
+    #[command(about = "Get, set or delete a metadata field of a note, preserving the rest of it")]
+    Meta {
+        /// The path or name of the note
+        note_path: OsString,
+
+        #[arg(value_enum)]
+        action: MetaAction,
+
+        /// The name of the field, e.g. title or keywords
+        key: String,
+
+        /// The value to set, as YAML: `[rust, cli]` is a list, `42` a number
+        #[arg(required_if_eq("action", "set"))]
+        value: Option<String>,
+
+        /// Set the value as a string, even if it reads as another YAML value
+        #[arg(short = 's', long)]
+        string: bool,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// Change the field even if the note marks it read-only
+        #[arg(long = "override")]
+        override_lock: bool,
     },
 
     #[command(about = "Edit note")]
@@ -77,88 +295,1744 @@ pub enum Cli {
         edit: Vec<OsString>,
     },
 
-    #[command(about = "Search notes")]
-    Search {
-        /// The query to search for
-        #[arg(value_parser = NonEmptyStringValueParser::new())]
-        query: String,
+    #[command(
+        about = "Encrypt a note with age, into `<note>.md.age`, which preview and edit decrypt"
+    )]
+    Encrypt {
+        /// The path or name of the note
+        note_path: OsString,
 
         /// The directory where the notes are stored
         #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
         note_root: OsString,
+
+        /// The age identity file (`age-keygen -o <file>`), whose public key encrypts the note
+        #[arg(short = 'i', long, env = "NOXE_AGE_IDENTITY")]
+        identity: Option<PathBuf>,
     },
 
-    #[command(about = "List notes")]
-    List {
+    #[command(about = "Decrypt a note encrypted by `noxe encrypt` back to plain text")]
+    Decrypt {
+        /// The path or name of the note
+        note_path: OsString,
+
         /// The directory where the notes are stored
         #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
         note_root: OsString,
 
-        /// List categories
-        #[arg(short = 'a', default_value = "false", group = "sort")]
-        category: bool,
+        /// The age identity file
+        #[arg(short = 'i', long, env = "NOXE_AGE_IDENTITY")]
+        identity: Option<PathBuf>,
+    },
 
-        /// List notes by category
-        #[arg(short = 'c', default_value = "false", group = "sort")]
-        sort_by_category: bool,
+    #[command(about = "Open a note, or its compiled PDF, with the default application")]
+    Open {
+        /// The path or name of the note. When it is a name, the note will be searched in the note directory.
+        /// When it is a path, the note will be found in the specified path.
+        note_path: Option<OsString>,
 
-        /// List notes by name
-        #[arg(short = 'n', default_value = "false", group = "sort")]
-        sort_by_name: bool,
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
 
-        /// List notes by created date
-        #[arg(short = 'C', default_value = "false", group = "sort")]
-        sort_by_created_at: bool,
+        /// Open the main file even if a compiled PDF exists
+        #[arg(short = 's', long)]
+        source: bool,
 
-        /// List notes by updated date
-        #[arg(short = 'u', default_value = "false", group = "sort")]
-        sort_by_updated_at: bool,
+        /// Show the note in the file manager instead
+        #[arg(short = 'r', long)]
+        reveal: bool,
+    },
+
+    #[command(about = "Pick a random note, to resurface what was forgotten")]
+    Random {
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// Only pick from the notes of these categories (comma-separated), e.g. `work/projects`
+        #[arg(long = "category", value_name = "CATEGORY", value_delimiter = ',')]
+        categories: Vec<String>,
+
+        /// Only pick from the notes with one of these tags (comma-separated)
+        #[arg(short = 't', long = "tag", value_name = "TAG", value_delimiter = ',')]
+        tags: Vec<String>,
+
+        /// Pick from the active, archived or trashed notes, or all of them
+        #[arg(long, default_value_t, value_enum)]
+        scope: Scope,
+
+        /// Show the first N lines of the note (without its metadata) under its path
+        #[arg(short = 'p', long, default_value = "5", value_name = "N")]
+        preview_lines: usize,
+
+        /// Open the note with the default application instead, like `noxe open`
+        #[arg(short = 'o', long)]
+        open: bool,
+
+        /// Open the main file even if a compiled PDF exists
+        #[arg(short = 's', long, requires = "open")]
+        source: bool,
+    },
+
+    #[command(about = "Pin a note, listed first by `noxe list` and by `noxe pins`")]
+    Pin {
+        /// The path or name of the note
+        note_path: OsString,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+    },
+
+    #[command(about = "Unpin a note")]
+    Unpin {
+        /// The path or name of the note, or its path relative to the note root once it is gone
+        note_path: OsString,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+    },
+
+    #[command(about = "List the pinned notes")]
+    Pins {
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+    },
+
+    #[command(about = "List the notes modified last, the newest first")]
+    Recent {
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
 
         /// The number of notes to list
         #[arg(short = 'N', long, default_value = "10")]
         number: usize,
 
-        /// Only list notes file name
-        #[arg(short = 't', long, default_value = "false")]
-        terse: bool,
+        /// List the active, archived or trashed notes, or all of them
+        #[arg(long, default_value_t, value_enum)]
+        scope: Scope,
+
+        /// Open the most recent note with the default application instead, like `noxe open`
+        #[arg(short = 'o', long)]
+        open: bool,
+
+        /// Open the main file even if a compiled PDF exists
+        #[arg(short = 's', long, requires = "open")]
+        source: bool,
     },
 
-    Grep {
-        pattern: OsString,
+    #[command(
+        about = "Append a timestamped entry to the inbox note, or with a capture template of the config"
+    )]
+    Capture {
+        /// The text to capture, after the name of a capture template of the config to use one.
+        /// Read from stdin, or written in the editor, when omitted
+        text: Vec<String>,
+
+        /// The value of a prompt of the capture template (NAME=VALUE). Can be repeated
+        #[arg(short = 's', long = "set", value_name = "NAME=VALUE")]
+        vars: Vec<String>,
+
+        /// The inbox note, relative to the note root. Created if missing
+        #[arg(short = 'i', long, default_value = crate::capture::DEFAULT_INBOX, env = "NOXE_INBOX")]
+        inbox: String,
 
         /// The directory where the notes are stored
-        #[arg(short = 'd', long, default_value = ".", env = "NOXE_DR")]
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
         note_root: OsString,
+
+        /// The command editing the text when none is given, `$EDITOR` by default. The draft
+        /// path will automatically be appended to it.
+        #[arg(long, env = "NOXE_EDIT")]
+        edit: Vec<OsString>,
     },
 
-    Publish {
-        /// The path or name of the note. When it is a name, the note will be searched in the note directory.
-        /// When it is a path, the note will be found in the specified path.
-        note_path: Option<OsString>,
+    #[command(about = "Append text to a note, creating the note if missing")]
+    Append {
+        /// The path or name of the note. A missing note is created under the note root, as a
+        /// filenote of --note-type unless the name has an extension
+        note_path: OsString,
+
+        /// The text to append. Read from stdin when omitted
+        text: Vec<String>,
+
+        /// Append to the end of the section under this heading, added when missing
+        #[arg(short = 'H', long)]
+        heading: Option<String>,
+
+        /// Append the text as is, not as a list item starting with the time
+        #[arg(long)]
+        no_timestamp: bool,
 
         /// The directory where the notes are stored
         #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
         note_root: OsString,
 
-        // Support PDF, PNG, SVG, HTML
-        #[arg(short = 't', long, default_value = "pdf", value_parser = ["pdf", "png", "svg", "html"])]
-        output_type: String,
-    }
-}
+        /// The type of a created note
+        #[arg(short = 't', long, default_value_t, value_enum, env = "NOXE_TYPE")]
+        note_type: NoteType,
 
-#[derive(Debug, Clone, Copy, Default, ValueEnum)]
-pub enum NoteType {
-    #[default]
-    Typ,
-    Md,
-}
+        /// The author of a created note
+        #[arg(short = 'a', long, env = "NOXE_AUTHOR")]
+        note_author: Option<String>,
 
-impl std::fmt::Display for NoteType {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            NoteType::Typ => write!(f, "typ"),
-            NoteType::Md => write!(f, "md"),
-        }
+        /// The template file of a created note
+        #[arg(short = 'S', long, env = "NOXE_TEMPLATE")]
+        note_template: Option<OsString>,
+
+        /// Create the note from the template of this name from the template registry instead
+        #[arg(short = 'T', long)]
+        template: Option<String>,
+
+        /// The directory where the templates are stored. Default is `~/.config/noxe/templates`
+        #[arg(long, env = "NOXE_TEMPLATE_DIR")]
+        template_dir: Option<PathBuf>,
+    },
+
+    #[command(about = "Show the tasks with a day, reminders and journal notes of a day or a week")]
+    Agenda {
+        /// The day: today, yesterday, tomorrow or YYYY-MM-DD. Default is today
+        #[arg(short = 'D', long)]
+        date: Option<String>,
+
+        /// Show the week, Monday to Sunday, of the day
+        #[arg(short, long)]
+        week: bool,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// The path of journal notes relative to the note root, with strftime-like
+        /// placeholders
+        #[arg(short = 'p', long, default_value = crate::journal::DEFAULT_PATTERN, env = "NOXE_JOURNAL_PATH")]
+        pattern: String,
+
+        /// Browse the agenda in the TUI to mark items done, reschedule them or open their notes
+        #[arg(short = 'i', long)]
+        interactive: bool,
+
+        /// Custom edit command. The note path will automatically be appended to the command.
+        #[arg(long, env = "NOXE_EDIT")]
+        edit: Vec<OsString>,
+    },
+
+    #[command(
+        about = "List the checkboxes and TODO: markers of the notes, by note, or check one",
+        args_conflicts_with_subcommands = true
+    )]
+    Todo {
+        #[command(subcommand)]
+        command: Option<TodoCommand>,
+
+        /// The path or name of a note, or a category, to list only its TODOs
+        target: Option<OsString>,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// Only list the checked boxes and DONE headings
+        #[arg(long, conflicts_with = "open")]
+        done: bool,
+
+        /// Only list what is left to do
+        #[arg(long)]
+        open: bool,
+
+        /// Only list the TODOs in sections with this status, e.g. draft
+        #[arg(short = 's', long)]
+        status: Option<String>,
+
+        /// Only list the TODOs in sections with this tag
+        #[arg(short = 'T', long)]
+        tag: Option<String>,
+
+        /// The output format
+        #[arg(long, default_value_t, value_enum, env = "NOXE_FORMAT")]
+        format: OutputFormat,
+    },
+
+    #[command(about = "Create or open the journal note of a day")]
+    Journal {
+        /// The day: today, yesterday, tomorrow or YYYY-MM-DD. Default is today
+        #[arg(short = 'D', long)]
+        date: Option<String>,
+
+        /// Shift the day by N days, e.g. `--offset -1` for the day before
+        #[arg(short = 'o', long, default_value = "0", allow_negative_numbers = true)]
+        offset: i64,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// The path of journal notes relative to the note root, with strftime-like
+        /// placeholders. The extension decides the note type
+        #[arg(short = 'p', long, default_value = crate::journal::DEFAULT_PATTERN, env = "NOXE_JOURNAL_PATH")]
+        pattern: String,
+
+        /// A template for new journal notes, in which `{{date}}` and `{{weekday}}` are replaced
+        #[arg(short = 'S', long, env = "NOXE_JOURNAL_TEMPLATE")]
+        template: Option<PathBuf>,
+
+        /// Custom edit command. The note path will automatically be appended to the command.
+        #[arg(long, env = "NOXE_EDIT")]
+        edit: Vec<OsString>,
+
+        /// Only print the path of the note instead of opening it
+        #[arg(short = 'n', long, default_value = "false")]
+        no_edit: bool,
+    },
+
+    #[command(about = "Build the index of the vault to speed up list and search")]
+    Index {
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// Delete the index, going back to walking the files on each command
+        #[arg(long)]
+        remove: bool,
+    },
+
+    #[command(
+        about = "Browse the notes, with a second pane to move or copy notes between categories"
+    )]
+    Tui {
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// Start from the note root instead of restoring the last session
+        #[arg(long)]
+        fresh: bool,
+
+        /// Browse the snapshots of the vault (the commits of its git repository, as made by
+        /// `noxe sync`) to diff and restore single notes or directories
+        #[arg(long, conflicts_with = "fresh")]
+        restore: bool,
+    },
+
+    #[command(about = "Score the notes and group them into seedling, budding and evergreen tiers")]
+    #[command(about = "Export the link graph of the notes as DOT (Graphviz) or JSON")]
+    Graph {
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// The output format
+        #[arg(long, default_value_t, value_enum)]
+        format: GraphFormat,
+
+        /// Write the graph to this file instead of stdout
+        #[arg(short = 'o', long)]
+        output: Option<PathBuf>,
+    },
+
+    #[command(about = "Score the notes and list the ones to tend, by maturity tier")]
+    Garden {
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// Only report the notes of this tier
+        #[arg(short = 't', long, value_enum)]
+        tier: Option<Tier>,
+
+        /// Show at most N notes per tier, the ones to tend first
+        #[arg(short = 'N', long, default_value = "10")]
+        number: usize,
+
+        /// The output format. JSON lists the scores of all the notes (of --tier)
+        #[arg(long, default_value_t, value_enum, env = "NOXE_FORMAT")]
+        format: OutputFormat,
+    },
+
+    #[command(about = "Cluster the notes into topics by their links and terms (TF-IDF)")]
+    Topics {
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// The similarity, between 0 and 1, from which two notes are related. Linked notes get
+        /// 0.5 more
+        #[arg(long, default_value = "0.15")]
+        threshold: f64,
+
+        /// Leave out the clusters of fewer notes
+        #[arg(long, default_value = "2")]
+        min_size: usize,
+
+        /// The number of terms labelling a topic
+        #[arg(short = 'N', long, default_value = "3")]
+        terms: usize,
+
+        /// Write an index note (map of content) per topic to this category, replacing those of
+        /// earlier runs. The notes of the category are not clustered
+        #[arg(long)]
+        moc: Option<PathBuf>,
+
+        /// The type of the index notes
+        #[arg(short = 't', long, default_value_t, value_enum, env = "NOXE_TYPE")]
+        note_type: NoteType,
+
+        /// The output format
+        #[arg(long, default_value_t, value_enum, env = "NOXE_FORMAT")]
+        format: OutputFormat,
+    },
+
+    #[command(about = "Rename a note, or many at once with --bulk, rewriting the links to them")]
+    Rename {
+        /// The path or name of the note
+        #[arg(required_unless_present = "bulk")]
+        note_path: Option<OsString>,
+
+        /// The new name of the note. Filenotes keep their extension unless one is given
+        #[arg(required_unless_present = "bulk")]
+        new_name: Option<String>,
+
+        /// Rename the notes of the vault (or of --category) after --pattern or --plan, or after
+        /// a plan edited in the editor
+        #[arg(long, conflicts_with_all = ["note_path", "new_name"])]
+        bulk: bool,
+
+        /// Name the notes after this pattern, e.g. `{date}-{slug}`. Placeholders: {name},
+        /// {title}, {slug}, {date} and {n}
+        #[arg(short = 'p', long, requires = "bulk", conflicts_with = "plan")]
+        pattern: Option<String>,
+
+        /// Read the renames from this plan file, one `old -> new` line per note
+        #[arg(long, requires = "bulk")]
+        plan: Option<PathBuf>,
+
+        /// Only rename the notes of this category
+        #[arg(short = 'c', long, requires = "bulk")]
+        category: Option<PathBuf>,
+
+        /// Print the renames without doing them
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// The command editing the plan. The plan path will automatically be appended to it.
+        #[arg(long, env = "NOXE_EDIT")]
+        edit: Vec<OsString>,
+
+        /// Rename in bulk even the notes marked `readonly: true`, and rewrite the links in them
+        #[arg(long = "override")]
+        override_lock: bool,
+    },
+
+    #[command(
+        about = "Move notes to the archive/ directory of the vault, keeping their category, or back"
+    )]
+    Archive {
+        /// The paths or names of the notes
+        #[arg(required_unless_present = "older_than")]
+        notes: Vec<OsString>,
+
+        /// Archive the notes whose date (from the metadata, or the modification date) is older
+        /// than this age, e.g. 30d, 2w, 6m or 1y
+        #[arg(long, value_name = "AGE", conflicts_with_all = ["notes", "unarchive"])]
+        older_than: Option<String>,
+
+        /// Move archived notes back to their category
+        #[arg(short = 'u', long)]
+        unarchive: bool,
+
+        /// Print the moves without doing them
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// Archive by age even the notes marked `readonly: true`, and rewrite the links in them
+        #[arg(long = "override")]
+        override_lock: bool,
+    },
+
+    #[command(about = "Check the vault for problems")]
+    Doctor {
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// Only run these checks (comma-separated)
+        #[arg(short = 'p', long = "pass", value_enum, value_delimiter = ',')]
+        passes: Vec<DoctorPass>,
+
+        /// Check the active, archived or trashed notes, or all of them
+        #[arg(long, default_value_t, value_enum)]
+        scope: Scope,
+    },
+
+    #[command(about = "Check the notes for broken frontmatter, links and images, and empty notes")]
+    Lint {
+        /// A category or note to check, instead of the whole vault
+        target: Option<PathBuf>,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// Check the active, archived or trashed notes, or all of them
+        #[arg(long, default_value_t, value_enum)]
+        scope: Scope,
+    },
+
+    #[command(about = "Copy a file into a dirnote and print the link to it")]
+    Attach {
+        /// The path or name of the note
+        note_path: OsString,
+
+        /// The file to attach
+        file: PathBuf,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// The directory of the note to copy into. Default is images/ for images, attachments/
+        /// for other files
+        #[arg(long, value_name = "DIR")]
+        dir: Option<String>,
+
+        /// Append the link to the main file instead of printing it
+        #[arg(long)]
+        insert: bool,
+    },
+
+    #[command(about = "Remove the unused files of dirnote images/ and bibliography/")]
+    CleanAssets {
+        /// A category or note to clean, instead of the whole vault
+        target: Option<PathBuf>,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// Clean the active, archived or trashed notes, or all of them
+        #[arg(long, default_value_t, value_enum)]
+        scope: Scope,
+
+        /// List the unused files without removing them
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+
+        /// Move the files to the trash of the vault instead of deleting them
+        #[arg(short = 't', long)]
+        trash: bool,
+
+        /// Do not ask for confirmation
+        #[arg(short = 'y', long)]
+        yes: bool,
+    },
+
+    #[command(about = "Upgrade the vault state (.noxe) left by an older noxe")]
+    Migrate {
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// Report what would change without writing anything
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+    },
+
+    #[command(about = "Search notes")]
+    Search {
+        /// The query to search for
+        #[arg(value_parser = NonEmptyStringValueParser::new(), required_unless_present_any = ["near", "saved"])]
+        query: Option<String>,
+
+        /// Run the saved search NAME, defined under `searches` in the config or saved with
+        /// `--save`. The options given on the command line take precedence, field filters add up
+        #[arg(long, value_name = "NAME", conflicts_with = "near")]
+        saved: Option<String>,
+
+        /// Save the query and filters of this search as NAME, in `searches.yaml` next to the
+        /// config, then run it
+        #[arg(long, value_name = "NAME", conflicts_with_all = ["near", "semantic"])]
+        save: Option<String>,
+
+        /// Search the content of notes for lines close to each other containing all the terms
+        /// (case-insensitive, a term may be a phrase), e.g. `--near "borrow checker" lifetime`
+        #[arg(long, num_args = 1.., value_name = "TERM", conflicts_with_all = ["query", "metadata"])]
+        near: Vec<String>,
+
+        /// The maximum distance in lines between the first and the last term of `--near`
+        #[arg(
+            short = 'w',
+            long,
+            default_value = "3",
+            value_name = "LINES",
+            requires = "near"
+        )]
+        within: usize,
+
+        /// Rank notes by the similarity of their content to the query, using embeddings
+        /// computed by the AI provider. Only new or edited notes are embedded again
+        #[arg(short = 'S', long, requires = "query", conflicts_with_all = ["near", "metadata"])]
+        semantic: bool,
+
+        /// The number of notes shown by `--semantic`
+        #[arg(short = 'n', long, default_value = "10", requires = "semantic")]
+        limit: usize,
+
+        /// The AI provider of `--semantic`
+        #[arg(short = 'P', long, default_value = "openai", env = "NOXE_AI_PROVIDER")]
+        provider: String,
+
+        /// The embedding model of `--semantic`. Default is the provider's default
+        #[arg(long, env = "NOXE_AI_EMBEDDING_MODEL")]
+        embedding_model: Option<String>,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// Only keep notes whose metadata fields match (comma-separated KEY=VALUE), e.g.
+        /// `status=draft,course=CS144`
+        #[arg(short = 'f', long, value_delimiter = ',')]
+        fields: Vec<FieldFilter>,
+
+        /// Also match the query against the title, author and keywords of notes
+        #[arg(short = 'm', long, default_value = "false")]
+        metadata: bool,
+
+        /// Read the query as structured terms rather than a regex on names, e.g.
+        /// `author:alice tag:rust created:>2024-01-01 title:async`. Terms are words matched in
+        /// the name or title, or `title:`, `author:`, `name:`, `tag:`, `type:`, `category:`,
+        /// `created:`, `modified:`, `date:` (with `>`, `>=`, `<` or `<=` before the day) and
+        /// any other metadata field. All terms must match; `-` negates one
+        #[arg(short = 'Q', long, requires = "query", conflicts_with_all = ["near", "semantic", "metadata"])]
+        query_lang: bool,
+
+        /// The output format
+        #[arg(long, default_value_t, value_enum, env = "NOXE_FORMAT")]
+        format: OutputFormat,
+
+        /// Show the first N lines of each note (without its metadata) under its entry
+        #[arg(short = 'p', long, default_value = "0", value_name = "N")]
+        preview_lines: usize,
+
+        /// Search the active, archived or trashed notes, or all of them
+        #[arg(long, default_value_t, value_enum)]
+        scope: Scope,
+
+        /// Only search notes created on or after this day: YYYY-MM-DD, today, yesterday, or an age
+        /// like 7d, 2w, 6m or 1y
+        #[arg(long, value_name = "DATE")]
+        created_after: Option<String>,
+
+        /// Only search notes created on or before this day
+        #[arg(long, value_name = "DATE")]
+        created_before: Option<String>,
+
+        /// Only search notes modified on or after this day
+        #[arg(long, value_name = "DATE")]
+        modified_after: Option<String>,
+
+        /// Only search notes modified on or before this day
+        #[arg(long, value_name = "DATE")]
+        modified_before: Option<String>,
+
+        /// Only search notes in this language, e.g. zh or en: their `lang` metadata field, else the
+        /// detected one
+        #[arg(long)]
+        lang: Option<String>,
+
+        /// Only search the notes of these categories (comma-separated), e.g. `work/projects`.
+        /// Names match case-insensitively
+        #[arg(long = "category", value_name = "CATEGORY", value_delimiter = ',')]
+        categories: Vec<String>,
+    },
+
+    #[command(about = "List notes")]
+    List {
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// List categories
+        #[arg(short = 'a', default_value = "false", group = "order")]
+        category: bool,
+
+        /// List notes by category
+        #[arg(short = 'c', default_value = "false", group = "order")]
+        sort_by_category: bool,
+
+        /// List notes by name, like `--sort name`
+        #[arg(short = 'n', default_value = "false", group = "order")]
+        sort_by_name: bool,
+
+        /// List the newest notes by created date, like `--sort created`
+        #[arg(short = 'C', default_value = "false", group = "order")]
+        sort_by_created_at: bool,
+
+        /// List the newest notes by updated date, like `--sort updated`
+        #[arg(short = 'u', default_value = "false", group = "order")]
+        sort_by_updated_at: bool,
+
+        /// List notes by title in their metadata, like `--sort title`
+        #[arg(short = 'T', default_value = "false", group = "order")]
+        sort_by_title: bool,
+
+        /// List the newest notes by date in their metadata, like `--sort date`
+        #[arg(short = 'D', default_value = "false", group = "order")]
+        sort_by_date: bool,
+
+        /// Sort the notes by these keys (comma-separated), the later ones breaking the ties, e.g.
+        /// `category,updated`. Only the first N notes are listed when a time leads
+        #[arg(short = 's', long, value_enum, value_delimiter = ',', group = "order")]
+        sort: Vec<SortKey>,
+
+        /// Reverse the order of the notes
+        #[arg(short = 'r', long, requires = "order")]
+        reverse: bool,
+
+        /// The number of notes to list
+        #[arg(short = 'N', long, default_value = "10")]
+        number: usize,
+
+        /// Only list notes file name
+        #[arg(short = 't', long, default_value = "false")]
+        terse: bool,
+
+        /// List the notes as a table: name, category, type, size, created and modified times,
+        /// and title
+        #[arg(short = 'l', long, conflicts_with = "terse")]
+        long: bool,
+
+        /// Only list notes whose metadata fields match (comma-separated KEY=VALUE), e.g.
+        /// `status=draft,course=CS144`
+        #[arg(short = 'f', long, value_delimiter = ',')]
+        fields: Vec<FieldFilter>,
+
+        /// The output format
+        #[arg(long, default_value_t, value_enum, env = "NOXE_FORMAT")]
+        format: OutputFormat,
+
+        /// Show the first N lines of each note (without its metadata) under its entry
+        #[arg(short = 'p', long, default_value = "0", value_name = "N")]
+        preview_lines: usize,
+
+        /// Filter or rank the notes with the WebAssembly plugin `.noxe/plugins/<NAME>.wasm`.
+        /// Can be repeated, plugins run in order
+        #[arg(long = "plugin", value_name = "NAME")]
+        plugins: Vec<String>,
+
+        /// List the active, archived or trashed notes, or all of them
+        #[arg(long, default_value_t, value_enum)]
+        scope: Scope,
+
+        /// Only list notes created on or after this day: YYYY-MM-DD, today, yesterday, or an age
+        /// like 7d, 2w, 6m or 1y
+        #[arg(long, value_name = "DATE")]
+        created_after: Option<String>,
+
+        /// Only list notes created on or before this day
+        #[arg(long, value_name = "DATE")]
+        created_before: Option<String>,
+
+        /// Only list notes modified on or after this day
+        #[arg(long, value_name = "DATE")]
+        modified_after: Option<String>,
+
+        /// Only list notes modified on or before this day
+        #[arg(long, value_name = "DATE")]
+        modified_before: Option<String>,
+
+        /// Only list notes in this language, e.g. zh or en: their `lang` metadata field, else the
+        /// detected one
+        #[arg(long)]
+        lang: Option<String>,
+
+        /// Only list the notes of these categories (comma-separated), e.g. `work/projects`.
+        /// Names match case-insensitively
+        #[arg(long = "category", value_name = "CATEGORY", value_delimiter = ',')]
+        categories: Vec<String>,
+
+        /// Only list the notes owned by this person, their `owner` metadata field
+        #[arg(long)]
+        owner: Option<String>,
+
+        /// Only list the notes this person reviews, their `reviewers` metadata field
+        #[arg(long)]
+        reviewer: Option<String>,
+
+        /// Only list the notes waiting for a review
+        #[arg(long)]
+        needs_review: bool,
+    },
+
+    Grep {
+        pattern: OsString,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_DR")]
+        note_root: OsString,
+    },
+
+    Publish {
+        /// The path or name of the note. When it is a name, the note will be searched in the note directory.
+        /// When it is a path, the note will be found in the specified path.
+        note_path: Option<OsString>,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        // Support PDF, PNG, SVG, HTML. Default is the `export.format` of the note, or PDF
+        #[arg(short = 't', long, value_parser = ["pdf", "png", "svg", "html"])]
+        output_type: Option<String>,
+
+        /// Render the whole vault into a static HTML site in this directory, e.g. for GitHub
+        /// Pages, instead of publishing a note
+        #[arg(short = 'o', long, conflicts_with_all = ["note_path", "output_type"])]
+        out_dir: Option<PathBuf>,
+
+        /// The URL the site is published at. Adds an Atom feed of the recently updated notes,
+        /// `feed.xml`, to the site
+        #[arg(short = 'u', long, requires = "out_dir", env = "NOXE_SITE_URL")]
+        base_url: Option<String>,
+    },
+
+    #[command(
+        about = "Print an Atom feed of the recently created or updated notes of a site made with `noxe publish -o`"
+    )]
+    Feed {
+        /// The URL the site is published at, e.g. https://me.github.io/notes
+        #[arg(short = 'u', long, env = "NOXE_SITE_URL")]
+        base_url: String,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// The number of notes of the feed, the most recently updated ones
+        #[arg(short = 'n', long, default_value_t = crate::feed::DEFAULT_LIMIT)]
+        limit: usize,
+
+        /// The author of the notes without one in their metadata
+        #[arg(short = 'a', long, env = "NOXE_AUTHOR")]
+        author: Option<String>,
+
+        /// Write the feed to this file rather than to stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    #[command(about = "Export notes (Typst notes with typst, Markdown notes with pandoc)")]
+    Export {
+        /// The path or name of a note, or a category directory. Export all notes if omitted.
+        target: Option<OsString>,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// The directory where the PDFs are written, mirroring the categories. Default is
+        /// `<note_root>/export`
+        #[arg(short = 'o', long)]
+        output_dir: Option<PathBuf>,
+
+        /// Where the bibliography of a note comes from, the first scope with entries winning
+        /// (comma-separated)
+        #[arg(
+            long,
+            value_enum,
+            value_delimiter = ',',
+            default_value = "note,vault",
+            env = "NOXE_BIBLIOGRAPHY_ORDER"
+        )]
+        bibliography_order: Vec<BibScope>,
+
+        /// Export the active, archived or trashed notes of the vault or category, or all of them
+        #[arg(long, default_value_t, value_enum)]
+        scope: Scope,
+    },
+
+    #[command(about = "Show or add to the bibliographies of a note or of the vault")]
+    Bib {
+        #[command(subcommand)]
+        command: BibCommand,
+    },
+
+    #[command(
+        about = "Write a text file per note with its title and tags, for OS search indexers (Spotlight, Tracker, Everything)"
+    )]
+    Sidecar {
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// The directory of the sidecar files, mirroring the categories. Default is
+        /// `<note_root>/sidecars`
+        #[arg(short = 'o', long, env = "NOXE_SIDECAR_DIR")]
+        output_dir: Option<PathBuf>,
+
+        /// Remove the sidecar files instead
+        #[arg(long)]
+        remove: bool,
+    },
+
+    #[command(about = "List the notes linking to a note with [[...]]")]
+    Backlinks {
+        /// The path or name of the note. When it is a name, the note will be searched in the note directory.
+        /// When it is a path, the note will be found in the specified path.
+        note_path: OsString,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+    },
+
+    #[command(about = "Commit, pull --rebase and push the notes with git")]
+    Sync {
+        /// The directory where the notes are stored, a git repository
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// The commit message. Default is `noxe sync: <timestamp>`
+        #[arg(short = 'm', long)]
+        message: Option<String>,
+
+        /// Only commit the local changes
+        #[arg(long, default_value = "false")]
+        no_push: bool,
+    },
+
+    #[command(
+        about = "Back up the vault into a timestamped .tar.zst archive, or restore a backup",
+        args_conflicts_with_subcommands = true
+    )]
+    Backup {
+        #[command(subcommand)]
+        command: Option<BackupCommand>,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// The directory of the backups, or the path of the archive when it ends with .tar.zst.
+        /// `~/.local/share/noxe/backups` by default
+        #[arg(short = 'o', long, env = "NOXE_BACKUP_DIR")]
+        output: Option<PathBuf>,
+
+        /// Keep only the N latest backups of the vault, deleting older ones
+        #[arg(short = 'k', long, value_name = "N")]
+        keep: Option<usize>,
+    },
+
+    #[command(about = "Pack notes into a single .noxepack archive")]
+    Pack {
+        /// The paths or names of the notes to pack
+        #[arg(required = true)]
+        note_paths: Vec<OsString>,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// The path of the archive
+        #[arg(short = 'o', long, default_value = "notes.noxepack")]
+        output: OsString,
+    },
+
+    #[command(about = "Unpack a .noxepack archive into the note directory")]
+    Unpack {
+        /// The path of the archive
+        pack_path: OsString,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+    },
+
+    #[command(about = "Import notes from other note apps into the note directory")]
+    Import {
+        #[command(subcommand)]
+        command: ImportCommand,
+    },
+
+    #[command(about = "Manage note tags (the keywords in note metadata)")]
+    Tag {
+        #[command(subcommand)]
+        command: TagCommand,
+    },
+
+    #[command(
+        about = "Review the notes due today with spaced repetition, or request and approve reviews of notes",
+        args_conflicts_with_subcommands = true
+    )]
+    Review {
+        #[command(subcommand)]
+        command: Option<ReviewCommand>,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// Only list the notes due today, without opening them
+        #[arg(short, long)]
+        list: bool,
+
+        #[arg(long, env = "NOXE_EDIT")]
+        /// Custom edit command. The note path will automatically be appended to the command.
+        edit: Vec<OsString>,
+    },
+
+    #[command(about = "Manage reusable snippets (code blocks, preambles, tables...)")]
+    Snippet {
+        #[command(subcommand)]
+        command: SnippetCommand,
+    },
+
+    #[command(about = "Navigate folgezettel sequences (notes named `12a3 Title`)")]
+    Zk {
+        #[command(subcommand)]
+        command: ZkCommand,
+    },
+
+    #[command(
+        about = "Browse the #include tree of a typst note, with git status and compile errors"
+    )]
+    Includes {
+        /// The path or name of the note. When it is a name, the note will be searched in the note directory.
+        /// When it is a path, the note will be found in the specified path.
+        note_path: Option<OsString>,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// Do not compile the note for diagnostics
+        #[arg(long)]
+        no_check: bool,
+
+        /// Print the tree instead of opening the TUI
+        #[arg(short = 'p', long)]
+        print: bool,
+
+        /// Custom edit command. The file path will automatically be appended to the command.
+        #[arg(long, env = "NOXE_EDIT")]
+        edit: Vec<OsString>,
+    },
+
+    #[command(about = "Print the shell completion script, e.g. `source <(noxe completions bash)`")]
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+
+    #[command(
+        about = "Print the shell function exporting the last opened note as the current one, e.g. `eval \"$(noxe shell-init bash)\"`"
+    )]
+    ShellInit {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+
+    #[command(
+        about = "Print the current note, which commands given no note run on: the dirnote of the current directory, else the last opened one"
+    )]
+    Current,
+
+    /// Print the completion candidates of a command line, for the completion scripts
+    #[command(name = "__complete", hide = true)]
+    Complete {
+        /// The arguments after `noxe`, the last one being completed
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        words: Vec<String>,
+    },
+
+    #[command(about = "Manage named note templates (paper notes, meeting notes...)")]
+    Template {
+        #[command(subcommand)]
+        command: TemplateCommand,
+    },
+
+    #[command(about = "Inspect the config file")]
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+
+    #[command(about = "List the vaults of the config or switch between them")]
+    Vault {
+        #[command(subcommand)]
+        command: VaultCommand,
+    },
+
+    #[command(about = "AI assistant")]
+    Ai {
+        #[command(subcommand)]
+        command: AiCommand,
+    },
+
+    #[command(about = "Ask a question about the notes")]
+    Ask {
+        /// The question
+        question: String,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// The AI provider
+        #[arg(short = 'P', long, default_value = "openai", env = "NOXE_AI_PROVIDER")]
+        provider: String,
+
+        /// The chat model. Default is the provider's default
+        #[arg(short = 'M', long, env = "NOXE_AI_MODEL")]
+        model: Option<String>,
+    },
+
+    #[command(about = "Summarize a note")]
+    Summarize {
+        /// The path or name of the note
+        note_path: OsString,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// The AI provider
+        #[arg(short = 'P', long, default_value = "openai", env = "NOXE_AI_PROVIDER")]
+        provider: String,
+
+        /// The chat model. Default is the provider's default
+        #[arg(short = 'M', long, env = "NOXE_AI_MODEL")]
+        model: Option<String>,
+    },
+
+    /// Any other command runs the `noxe-<command>` executable found in PATH
+    #[command(external_subcommand)]
+    External(Vec<OsString>),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TodoCommand {
+    #[command(about = "Check a checkbox, or uncheck it when it is checked")]
+    Check {
+        /// The note and line of the checkbox, as listed by `noxe todo`: `<note>:<line>`
+        location: String,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum BackupCommand {
+    #[command(about = "Unpack a backup into a new directory")]
+    Restore {
+        /// The backup archive
+        archive: PathBuf,
+
+        /// The directory to restore into, which must not exist or be empty. Named after the
+        /// archive in the current directory by default
+        into: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ImportCommand {
+    #[command(
+        about = "Copy an Obsidian vault: folder notes and notes with attachments become dirnotes"
+    )]
+    Obsidian {
+        /// The directory of the Obsidian vault
+        vault: PathBuf,
+
+        /// The category to import the notes into, instead of the top of the note directory
+        #[arg(short = 'c', long)]
+        category: Option<PathBuf>,
+
+        /// List the notes to import without writing them
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+    },
+
+    #[command(about = "Import a Notion export zip, dropping page ids and making links wiki-links")]
+    Notion {
+        /// The zip file exported by Notion
+        export: PathBuf,
+
+        /// The category to import the notes into, instead of the top of the note directory
+        #[arg(short = 'c', long)]
+        category: Option<PathBuf>,
+
+        /// List the notes to import without writing them
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ReviewCommand {
+    #[command(about = "Schedule notes for spaced repetition, due today")]
+    Add {
+        /// The paths or names of the notes, or categories to schedule all of their notes
+        #[arg(required = true)]
+        targets: Vec<OsString>,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+    },
+
+    #[command(about = "Stop reviewing a note with spaced repetition")]
+    Remove {
+        /// The path or name of the note
+        note_path: OsString,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+    },
+
+    #[command(
+        about = "Grade the review of a note to schedule the next one, like `noxe review` does"
+    )]
+    Grade {
+        /// The path or name of the note
+        note_path: OsString,
+
+        /// From 0 (forgotten) to 5 (perfect recall); below 3 the note is learned again
+        #[arg(value_parser = clap::value_parser!(u8).range(0..=5))]
+        grade: u8,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+    },
+
+    #[command(about = "Request a review of a note, logged in its review log section")]
+    Request {
+        /// The path or name of the note
+        note_path: OsString,
+
+        /// The reviewers, added to those in the `reviewers` field of the note
+        reviewers: Vec<String>,
+
+        /// Who requests the review, made the owner of a note without one
+        #[arg(long = "as", value_name = "NAME", env = "NOXE_AUTHOR")]
+        by: Option<String>,
+
+        /// A message for the reviewers
+        #[arg(short, long)]
+        message: Option<String>,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// Change the review fields even if the note marks them read-only
+        #[arg(long = "override")]
+        override_lock: bool,
+    },
+
+    #[command(about = "Approve a note under review, once all of its reviewers did it is approved")]
+    Approve {
+        /// The path or name of the note
+        note_path: OsString,
+
+        /// The reviewer approving the note
+        #[arg(long = "as", value_name = "NAME", env = "NOXE_AUTHOR")]
+        by: Option<String>,
+
+        /// A comment logged with the approval
+        #[arg(short, long)]
+        message: Option<String>,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// Change the review fields even if the note marks them read-only
+        #[arg(long = "override")]
+        override_lock: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TagCommand {
+    #[command(about = "List all tags with the number of notes carrying them")]
+    List {
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+    },
+
+    #[command(about = "Add tags to a note")]
+    Add {
+        /// The path or name of the note
+        note_path: OsString,
+
+        /// The tags to add
+        #[arg(required = true)]
+        tags: Vec<String>,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// Change the tags even if the note marks them read-only
+        #[arg(long = "override")]
+        override_lock: bool,
+    },
+
+    #[command(about = "Remove tags from a note")]
+    Rm {
+        /// The path or name of the note
+        note_path: OsString,
+
+        /// The tags to remove
+        #[arg(required = true)]
+        tags: Vec<String>,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// Change the tags even if the note marks them read-only
+        #[arg(long = "override")]
+        override_lock: bool,
+    },
+
+    #[command(about = "Find notes carrying a tag")]
+    Find {
+        /// The tag to find (case-insensitive)
+        tag: String,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+    },
+}
+
+fn parse_var(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.to_string()))
+        }
+        _ => Err(format!("expected KEY=VALUE, got '{}'", s)),
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ZkCommand {
+    #[command(about = "Print the note read after a zettel: its first branch, or the next one")]
+    Next {
+        /// The zettel ID, e.g. `12a3`
+        id: String,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+    },
+
+    #[command(about = "Print the note read before a zettel")]
+    Prev {
+        /// The zettel ID, e.g. `12a3`
+        id: String,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+    },
+
+    #[command(about = "Print the notes branching directly off a zettel")]
+    Children {
+        /// The zettel ID, e.g. `12a3`
+        id: String,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+    },
+
+    #[command(about = "Browse the folgezettel tree")]
+    Tree {
+        /// Only show the branches of this zettel
+        id: Option<String>,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// Print the tree instead of opening the TUI
+        #[arg(short = 'p', long)]
+        print: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TemplateCommand {
+    #[command(about = "List the templates")]
+    List {
+        /// The directory where the templates are stored. Default is `~/.config/noxe/templates`
+        #[arg(long, env = "NOXE_TEMPLATE_DIR")]
+        template_dir: Option<PathBuf>,
+    },
+
+    #[command(about = "Print a template")]
+    Show {
+        /// The name of the template
+        name: String,
+
+        /// The directory where the templates are stored. Default is `~/.config/noxe/templates`
+        #[arg(long, env = "NOXE_TEMPLATE_DIR")]
+        template_dir: Option<PathBuf>,
+    },
+
+    #[command(about = "Save a template, read from a file or stdin")]
+    Add {
+        /// The name of the template
+        name: String,
+
+        /// Read the template from this file instead of stdin
+        #[arg(short = 'f', long)]
+        file: Option<PathBuf>,
+
+        /// Replace an existing template
+        #[arg(long, default_value = "false")]
+        force: bool,
+
+        /// The directory where the templates are stored. Default is `~/.config/noxe/templates`
+        #[arg(long, env = "NOXE_TEMPLATE_DIR")]
+        template_dir: Option<PathBuf>,
+    },
+
+    #[command(
+        about = "Save a template reproducing a note: its files, with its title, author and date as variables"
+    )]
+    From {
+        /// The path or name of the note
+        note_path: OsString,
+
+        /// The name of the template. Default is the name of the note
+        #[arg(short = 'n', long)]
+        name: Option<String>,
+
+        /// Keep only the headings of the notes, leaving the other files empty
+        #[arg(long = "headings")]
+        headings_only: bool,
+
+        /// Print the template instead of saving it
+        #[arg(short = 'p', long, conflicts_with_all = ["name", "force"])]
+        print: bool,
+
+        /// Replace an existing template
+        #[arg(long, default_value = "false")]
+        force: bool,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// The directory where the templates are stored. Default is `~/.config/noxe/templates`
+        #[arg(long, env = "NOXE_TEMPLATE_DIR")]
+        template_dir: Option<PathBuf>,
+    },
+
+    #[command(about = "Remove a template", visible_alias = "remove")]
+    Rm {
+        /// The name of the template
+        name: String,
+
+        /// The directory where the templates are stored. Default is `~/.config/noxe/templates`
+        #[arg(long, env = "NOXE_TEMPLATE_DIR")]
+        template_dir: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SnippetCommand {
+    #[command(about = "Save a snippet, read from a file or stdin")]
+    Add {
+        /// The name of the snippet
+        name: String,
+
+        /// Read the snippet from this file instead of stdin
+        #[arg(short = 'f', long)]
+        file: Option<PathBuf>,
+
+        /// Replace an existing snippet
+        #[arg(long, default_value = "false")]
+        force: bool,
+
+        /// The directory where the snippets are stored. Default is `~/.config/noxe/snippets`
+        #[arg(long, env = "NOXE_SNIPPET_DIR")]
+        snippet_dir: Option<PathBuf>,
+    },
+
+    #[command(about = "List the snippets")]
+    List {
+        /// The directory where the snippets are stored. Default is `~/.config/noxe/snippets`
+        #[arg(long, env = "NOXE_SNIPPET_DIR")]
+        snippet_dir: Option<PathBuf>,
+    },
+
+    #[command(about = "Insert a snippet into a note")]
+    Insert {
+        /// The name of the snippet, fuzzy-matched if no snippet has this exact name
+        name: String,
+
+        /// The path or name of the note
+        note_path: OsString,
+
+        /// Insert at the end of the section under this heading instead of the end of the note
+        #[arg(short = 'H', long)]
+        heading: Option<String>,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// The directory where the snippets are stored. Default is `~/.config/noxe/snippets`
+        #[arg(long, env = "NOXE_SNIPPET_DIR")]
+        snippet_dir: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    #[command(about = "Report unknown keys, deprecated options and missing referenced files")]
+    Doctor,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum BibCommand {
+    #[command(
+        about = "List the citation keys a note can cite, or those of the vault with --shared"
+    )]
+    List {
+        /// The path or name of the note
+        #[arg(required_unless_present = "shared")]
+        note_path: Option<OsString>,
+
+        /// List the shared bibliography of the vault, `bibliography/` under the note root
+        #[arg(long, conflicts_with = "note_path")]
+        shared: bool,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// Where the bibliography of the note comes from, the first scope with entries winning
+        /// (comma-separated)
+        #[arg(
+            long,
+            value_enum,
+            value_delimiter = ',',
+            default_value = "note,vault",
+            env = "NOXE_BIBLIOGRAPHY_ORDER"
+        )]
+        bibliography_order: Vec<BibScope>,
+    },
+    #[command(
+        about = "Append BibTeX entries, fetched by DOI or read from a file, to bibliography/refs.bib"
+    )]
+    Add {
+        /// The path or name of the dirnote
+        note_path: OsString,
+
+        /// A DOI (`10.1145/3290368`, `https://doi.org/...`) or a BibTeX file
+        source: String,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum VaultCommand {
+    #[command(about = "List the vaults of the config, marking the current one with '*'")]
+    List,
+    #[command(about = "Make a vault the current one")]
+    Switch {
+        /// The name of the vault. Without it, the top-level options of the config are used again.
+        name: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AiCommand {
+    #[command(about = "Generate missing summaries, tags and embeddings of notes")]
+    Enrich {
+        /// The path or name of the note to enrich
+        #[arg(required_unless_present = "all")]
+        note_path: Option<OsString>,
+
+        /// Enrich every note that is missing a summary, tags or an embedding
+        #[arg(short = 'A', long, conflicts_with = "note_path")]
+        all: bool,
+
+        /// The directory where the notes are stored
+        #[arg(short = 'd', long, default_value = ".", env = "NOXE_ROOT")]
+        note_root: OsString,
+
+        /// The AI provider
+        #[arg(short = 'P', long, default_value = "openai", env = "NOXE_AI_PROVIDER")]
+        provider: String,
+
+        /// The chat model used for summaries and tags. Default is the provider's default
+        #[arg(short = 'M', long, env = "NOXE_AI_MODEL")]
+        model: Option<String>,
+
+        /// The embedding model. Default is the provider's default
+        #[arg(long, env = "NOXE_AI_EMBEDDING_MODEL")]
+        embedding_model: Option<String>,
+
+        /// The tasks to run (comma-separated)
+        #[arg(
+            short = 't',
+            long,
+            value_enum,
+            value_delimiter = ',',
+            default_value = "summary,tags,embedding"
+        )]
+        tasks: Vec<EnrichTask>,
+
+        /// The maximum number of notes processed concurrently
+        #[arg(short = 'j', long, default_value = "4", value_parser = clap::value_parser!(u16).range(1..))]
+        jobs: u16,
+
+        /// Write the summary and the tags even to notes marking them read-only
+        #[arg(long = "override")]
+        override_lock: bool,
+    },
+
+    #[command(about = "Manage API keys of AI providers")]
+    Auth {
+        #[command(subcommand)]
+        command: AuthCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AuthCommand {
+    #[command(about = "Store the API key of a provider in the OS keychain")]
+    Set {
+        /// The provider name, e.g. `openai`. The key is read from stdin.
+        provider: String,
+    },
+
+    #[command(about = "Show which providers have an API key")]
+    Status {
+        /// The provider name. All known providers are shown when omitted.
+        provider: Option<String>,
+
+        /// Send a request to the provider to check that the key is accepted
+        #[arg(short = 'c', long, default_value = "false")]
+        check: bool,
+    },
+
+    #[command(about = "Remove the API key of a provider from the OS keychain")]
+    Remove {
+        /// The provider name
+        provider: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable output
+    #[default]
+    Plain,
+    /// A JSON array of note records
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum EventFormat {
+    /// One JSON object per line
+    Ndjson,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnrichTask {
+    /// A short summary, stored in the `summary` metadata field
+    Summary,
+    /// Suggested tags, merged into the `keywords` metadata field
+    Tags,
+    /// An embedding vector, stored in the vault state for semantic search
+    Embedding,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NoteType {
+    #[default]
+    Typ,
+    Md,
+    Org,
+    /// A type of the `types` section of the config, by its file extension. Notes of these
+    /// types have no metadata.
+    Custom(&'static str),
+}
+
+impl NoteType {
+    /// The built-in types, then the types of the config.
+    pub fn all() -> Vec<NoteType> {
+        let mut types = vec![NoteType::Typ, NoteType::Md, NoteType::Org];
+        types.extend(
+            config::note_types()
+                .keys()
+                .map(|ext| NoteType::Custom(ext.as_str())),
+        );
+        types
+    }
+
+    /// The name of the main file of dirnotes of this type.
+    pub fn main_file(self) -> String {
+        match self {
+            NoteType::Custom(ext) => config::note_types()
+                .get(ext)
+                .and_then(|t| t.main_file.clone())
+                .unwrap_or_else(|| format!("main.{}", ext)),
+            t => format!("main.{}", t),
+        }
+    }
+}
+
+impl ValueEnum for NoteType {
+    fn value_variants<'a>() -> &'a [Self] {
+        static VARIANTS: OnceLock<Vec<NoteType>> = OnceLock::new();
+        VARIANTS.get_or_init(NoteType::all)
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(PossibleValue::new(<&'static str>::from(*self)))
+    }
+}
+
+impl std::fmt::Display for NoteType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", <&'static str>::from(*self))
     }
 }
 
@@ -169,7 +2043,11 @@ impl TryFrom<&str> for NoteType {
         match value {
             "typ" => Ok(NoteType::Typ),
             "md" => Ok(NoteType::Md),
-            _ => Err(format!("Invalid note type: {}", value)),
+            "org" => Ok(NoteType::Org),
+            _ => match config::note_types().get_key_value(value) {
+                Some((ext, _)) => Ok(NoteType::Custom(ext.as_str())),
+                None => Err(format!("Invalid note type: {}", value)),
+            },
         }
     }
 }
@@ -179,6 +2057,8 @@ impl From<NoteType> for &'static str {
         match val {
             NoteType::Typ => "typ",
             NoteType::Md => "md",
+            NoteType::Org => "org",
+            NoteType::Custom(ext) => ext,
         }
     }
 }
@@ -0,0 +1,233 @@
+use clap::{Arg, CommandFactory, ValueEnum};
+use std::path::Path;
+
+use crate::cli::Cli;
+use crate::external;
+use crate::vault::note_names;
+
+/// The positional arguments naming an existing note.
+pub(crate) const NOTE_ARGS: [&str; 2] = ["note_path", "target"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// The completion script of a shell. The scripts ask `noxe __complete` for the candidates,
+/// and fall back to file names when there is none.
+pub fn script(shell: Shell) -> &'static str {
+    match shell {
+        Shell::Bash => BASH,
+        Shell::Zsh => ZSH,
+        Shell::Fish => FISH,
+    }
+}
+
+const BASH: &str = r#"_noxe() {
+    local cur="${COMP_WORDS[COMP_CWORD]}"
+    local IFS=$'\n'
+    COMPREPLY=($(noxe __complete -- "${COMP_WORDS[@]:1:COMP_CWORD}" 2>/dev/null))
+    if [ ${#COMPREPLY[@]} -eq 0 ]; then
+        COMPREPLY=($(compgen -f -- "$cur"))
+    fi
+    compopt -o filenames 2>/dev/null
+}
+complete -F _noxe noxe
+"#;
+
+const ZSH: &str = r#"#compdef noxe
+_noxe() {
+    local -a candidates
+    candidates=("${(@f)$(noxe __complete -- "${(@)words[2,CURRENT]}" 2>/dev/null)}")
+    if [[ -n "${candidates[1]}" ]]; then
+        compadd -a candidates
+    else
+        _files
+    fi
+}
+if [ "$funcstack[1]" = "_noxe" ]; then
+    _noxe "$@"
+else
+    compdef _noxe noxe
+fi
+"#;
+
+const FISH: &str = r#"function __noxe_complete
+    set -l tokens (commandline -opc) (commandline -ct)
+    noxe __complete -- $tokens[2..-1] 2>/dev/null
+end
+complete -c noxe -a '(__noxe_complete)'
+"#;
+
+/// The candidates for the last of `words`, the arguments after `noxe` up to the cursor:
+/// subcommands, flags, values of enum options, and the names of the notes of the vault for
+/// the arguments naming a note.
+pub fn complete(words: &[String]) -> Vec<String> {
+    let Some((current, before)) = words.split_last() else {
+        return Vec::new();
+    };
+
+    let mut root = Cli::command();
+    root.build();
+    let mut cmd = &root;
+    let mut positionals = 0;
+    let mut note_root = None;
+    // The option waiting for its value
+    let mut pending: Option<&Arg> = None;
+    let mut only_positionals = false;
+
+    for word in before {
+        if let Some(arg) = pending.take() {
+            if arg.get_id() == "note_root" {
+                note_root = Some(word.clone());
+            }
+            continue;
+        }
+        if word == "--" && !only_positionals {
+            only_positionals = true;
+            continue;
+        }
+        if word.starts_with('-') && word.len() > 1 && !only_positionals {
+            let (flag, value) = match word.split_once('=') {
+                Some((flag, value)) => (flag, Some(value)),
+                None => (word.as_str(), None),
+            };
+            if let Some(arg) = find_option(cmd, flag)
+                && arg.get_action().takes_values()
+            {
+                match value {
+                    Some(value) if arg.get_id() == "note_root" => {
+                        note_root = Some(value.to_string())
+                    }
+                    Some(_) => {}
+                    None => pending = Some(arg),
+                }
+            }
+            continue;
+        }
+        if positionals == 0
+            && let Some(sub) = cmd.find_subcommand(word)
+        {
+            cmd = sub;
+            continue;
+        }
+        positionals += 1;
+    }
+
+    let candidates = if let Some(arg) = pending {
+        possible_values(arg)
+    } else if current.starts_with('-') && !only_positionals {
+        cmd.get_arguments()
+            .filter(|a| !a.is_hide_set())
+            .filter_map(|a| a.get_long().map(|l| format!("--{}", l)))
+            .chain(["--help".to_string()])
+            .collect()
+    } else if cmd.has_subcommands() && positionals == 0 {
+        let mut names: Vec<String> = cmd
+            .get_subcommands()
+            .filter(|c| !c.is_hide_set())
+            .map(|c| c.get_name().to_string())
+            .collect();
+        if cmd.get_name() == root.get_name() {
+            names.extend(external::commands());
+        }
+        names
+    } else {
+        let mut args = cmd.get_positionals();
+        let arg = args.nth(positionals).or_else(|| {
+            cmd.get_positionals()
+                .last()
+                .filter(|a| a.get_num_args().is_some_and(|n| n.max_values() > 1))
+        });
+        match arg {
+            Some(arg)
+                if NOTE_ARGS.contains(&arg.get_id().as_str())
+                    && cmd.get_name() != "new"
+                    && !current.contains(['/', '\\']) =>
+            {
+                let note_root = note_root
+                    .or_else(|| std::env::var("NOXE_ROOT").ok())
+                    .unwrap_or_else(|| ".".to_string());
+                note_names(Path::new(&note_root)).unwrap_or_default()
+            }
+            Some(arg) => possible_values(arg),
+            None => Vec::new(),
+        }
+    };
+
+    candidates
+        .into_iter()
+        .filter(|c| c.starts_with(current.as_str()))
+        .collect()
+}
+
+/// The option of `cmd` named by `--long` or `-s`.
+pub(crate) fn find_option<'a>(cmd: &'a clap::Command, flag: &str) -> Option<&'a Arg> {
+    match flag.strip_prefix("--") {
+        Some(long) => cmd.get_arguments().find(|a| a.get_long() == Some(long)),
+        None => {
+            let short = flag.chars().nth(1)?;
+            cmd.get_arguments().find(|a| a.get_short() == Some(short))
+        }
+    }
+}
+
+fn possible_values(arg: &Arg) -> Vec<String> {
+    arg.get_possible_values()
+        .iter()
+        .filter(|v| !v.is_hide_set())
+        .map(|v| v.get_name().to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn complete_line(line: &str) -> Vec<String> {
+        let mut words: Vec<String> = line.split(' ').map(String::from).collect();
+        if words == [""] {
+            words.clear();
+        }
+        // Building the whole command line takes more stack, in debug builds, than the 2 MiB of
+        // the test threads; the main thread of noxe has 8 MiB
+        std::thread::Builder::new()
+            .stack_size(8 << 20)
+            .spawn(move || complete(&words))
+            .unwrap()
+            .join()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_complete() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        fs::create_dir_all(root.join("cat/paper")).unwrap();
+        fs::write(root.join("cat/paper/main.typ"), "").unwrap();
+        fs::write(root.join("rust.md"), "").unwrap();
+        fs::write(root.join("ruby.md"), "").unwrap();
+        let root = root.display();
+
+        assert!(complete_line("ed").contains(&"edit".to_string()));
+        assert!(!complete_line("__").contains(&"__complete".to_string()));
+        assert_eq!(complete_line("zk ch"), ["children"]);
+        assert!(complete_line("edit --no").contains(&"--note-root".to_string()));
+        assert_eq!(complete_line("new -t m"), ["md"]);
+
+        assert_eq!(
+            complete_line(&format!("edit -d {} ru", root)),
+            ["ruby.md", "rust.md"]
+        );
+        assert_eq!(
+            complete_line(&format!("preview --note-root={} ", root)),
+            ["paper", "ruby.md", "rust.md"]
+        );
+        assert!(complete_line(&format!("new -d {} ru", root)).is_empty());
+        assert!(complete_line(&format!("edit -d {} cat/", root)).is_empty());
+    }
+}
@@ -0,0 +1,887 @@
+use anyhow::{Context, Result, anyhow, bail};
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use crate::ai::PROVIDERS;
+use crate::capture::{self, CaptureTemplate};
+use crate::cite::BibScope;
+use crate::cli::{ConfigCommand, VaultCommand};
+use crate::journal;
+use crate::searches::{self, SavedSearch};
+
+const CONFIG_ENV: &str = "NOXE_CONFIG";
+
+const VAULT_ENV: &str = "NOXE_VAULT";
+
+/// The file next to the config remembering the vault of `noxe vault switch`
+const CURRENT_VAULT_FILE: &str = "current-vault";
+
+/// The extensions of the note types built into noxe
+const BUILTIN_TYPES: [&str; 3] = ["typ", "md", "org"];
+
+/// Options that were renamed: (old key, new key)
+const DEPRECATED_KEYS: [(&str, &str); 1] = [("note_dir", "note_root")];
+
+/// The user config, read from `$NOXE_CONFIG` or `~/.config/noxe/config.yaml`.
+/// Every option is a default that the environment and the command line can override.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// The directory where the notes are stored
+    #[serde(alias = "note_dir")]
+    pub note_root: Option<PathBuf>,
+    pub author: Option<String>,
+    /// The default note type, `typ`, `md` or a type of `types`
+    pub note_type: Option<String>,
+    /// The note template file used by `noxe new`
+    pub template: Option<PathBuf>,
+    /// The edit command, e.g. `nvim`
+    pub editor: Option<String>,
+    pub preview: PreviewConfig,
+    pub journal: JournalConfig,
+    /// The inbox note of `noxe capture`, relative to the note root
+    pub inbox: Option<String>,
+    pub ai: AiConfig,
+    pub bibliography: BibliographyConfig,
+    pub hooks: HooksConfig,
+    pub encryption: EncryptionConfig,
+    pub tui: TuiConfig,
+    /// Flush the notes to the disk after each write, for machines that may lose power
+    pub fsync: bool,
+    /// Prefix the notes of `noxe new` with a timestamp ID, e.g. `202505171230-title.md`
+    pub zettel_ids: bool,
+    /// The templates of `noxe capture <template>`, by name
+    pub capture_templates: BTreeMap<String, CaptureTemplate>,
+    /// The searches of `noxe search --saved <name>`, by name. Those saved with `--save` are
+    /// added from `searches.yaml` next to the config
+    pub searches: BTreeMap<String, SavedSearch>,
+    /// More note types, by file extension
+    pub types: BTreeMap<String, TypeConfig>,
+    /// Named vaults, e.g. `work` and `personal`
+    pub vaults: BTreeMap<String, VaultConfig>,
+}
+
+/// A named vault. While it is selected, its options replace the top-level ones.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct VaultConfig {
+    #[serde(alias = "note_dir")]
+    pub note_root: Option<PathBuf>,
+    pub author: Option<String>,
+    pub note_type: Option<String>,
+    pub template: Option<PathBuf>,
+    /// The directory of the templates of `noxe template`
+    pub template_dir: Option<PathBuf>,
+    pub editor: Option<String>,
+    pub inbox: Option<String>,
+}
+
+/// A user-defined note type, e.g. `org` or `adoc`.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct TypeConfig {
+    /// The main file of dirnotes, `main.<extension>` by default
+    pub main_file: Option<String>,
+    /// The preview command. The note path will automatically be appended to the command.
+    pub preview: Option<String>,
+    /// The edit command of notes of this type, used instead of `editor`
+    pub edit: Option<String>,
+}
+
+static NOTE_TYPES: OnceLock<BTreeMap<String, TypeConfig>> = OnceLock::new();
+
+/// The note types of the config, once registered.
+pub fn note_types() -> &'static BTreeMap<String, TypeConfig> {
+    static EMPTY: BTreeMap<String, TypeConfig> = BTreeMap::new();
+    NOTE_TYPES.get().unwrap_or(&EMPTY)
+}
+
+static CAPTURE_TEMPLATES: OnceLock<BTreeMap<String, CaptureTemplate>> = OnceLock::new();
+
+/// The capture templates of the config, once registered.
+pub fn capture_templates() -> &'static BTreeMap<String, CaptureTemplate> {
+    static EMPTY: BTreeMap<String, CaptureTemplate> = BTreeMap::new();
+    CAPTURE_TEMPLATES.get().unwrap_or(&EMPTY)
+}
+
+static SAVED_SEARCHES: OnceLock<BTreeMap<String, SavedSearch>> = OnceLock::new();
+
+/// The saved searches of the config, once registered.
+pub fn saved_searches() -> &'static BTreeMap<String, SavedSearch> {
+    static EMPTY: BTreeMap<String, SavedSearch> = BTreeMap::new();
+    SAVED_SEARCHES.get().unwrap_or(&EMPTY)
+}
+
+static TUI_THEME: OnceLock<ThemeConfig> = OnceLock::new();
+
+/// The TUI theme of the config, once registered.
+pub fn tui_theme() -> &'static ThemeConfig {
+    static DEFAULT: ThemeConfig = ThemeConfig {
+        preset: ThemePreset::Auto,
+        tree: None,
+        category: None,
+        selection: None,
+        border: None,
+        preview: None,
+        status_bar: None,
+    };
+    TUI_THEME.get().unwrap_or(&DEFAULT)
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct TuiConfig {
+    pub theme: ThemeConfig,
+}
+
+/// The colors of `noxe tui`, over those of the preset. A color is a name (`blue`,
+/// `lightgreen`), a hex code (`#1e90ff`) or an index of the 256-color palette (`208`).
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct ThemeConfig {
+    pub preset: ThemePreset,
+    /// The notes of the tree
+    pub tree: Option<String>,
+    /// The categories of the tree
+    pub category: Option<String>,
+    /// The background of the selected entry
+    pub selection: Option<String>,
+    /// The border of the active pane
+    pub border: Option<String>,
+    /// The text of the preview
+    pub preview: Option<String>,
+    /// The status bar
+    pub status_bar: Option<String>,
+}
+
+impl ThemeConfig {
+    /// The colors set, by key.
+    pub fn colors(&self) -> [(&'static str, Option<&str>); 6] {
+        [
+            ("tree", self.tree.as_deref()),
+            ("category", self.category.as_deref()),
+            ("selection", self.selection.as_deref()),
+            ("border", self.border.as_deref()),
+            ("preview", self.preview.as_deref()),
+            ("status_bar", self.status_bar.as_deref()),
+        ]
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemePreset {
+    /// Dark or light, by the background of the terminal when it tells it, dark otherwise
+    #[default]
+    Auto,
+    Dark,
+    Light,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct PreviewConfig {
+    /// The typst preview command, e.g. `tinymist preview`
+    pub typst: Option<String>,
+    /// The markdown preview command, e.g. `glow`
+    pub markdown: Option<String>,
+    /// The org preview command, e.g. `emacs -nw`
+    pub org: Option<String>,
+    /// The PDF viewer of `noxe preview --pdf`, e.g. `zathura`. The default application by
+    /// default
+    pub pdf_viewer: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct JournalConfig {
+    /// The path of journal notes relative to the note root, e.g. `journal/%Y/%m/%Y-%m-%d.md`
+    pub path: Option<String>,
+    /// The template of new journal notes
+    pub template: Option<PathBuf>,
+}
+
+/// Shell commands run at points in the life of notes, with the note in their environment:
+/// `NOXE_NOTE`, `NOXE_NOTE_FILE`, `NOXE_NOTE_TITLE`, `NOXE_NOTE_METADATA`...
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct HooksConfig {
+    /// Before `noxe new` creates a note. Failing aborts the creation
+    pub pre_new: Option<String>,
+    /// After a note is created, e.g. to give it an ID
+    pub post_new: Option<String>,
+    /// After the editor of `noxe edit` exits, e.g. `git commit`
+    pub post_edit: Option<String>,
+    /// Before `noxe rm` deletes a note. Failing aborts the deletion
+    pub pre_delete: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct EncryptionConfig {
+    /// The age identity file of `noxe encrypt`, e.g. `~/.config/noxe/age.key`
+    pub identity: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct BibliographyConfig {
+    /// Where the bibliography of an exported note comes from, in order of precedence, e.g.
+    /// `[note, vault]` (the default) or `[vault]` to always use the shared `bibliography/`
+    pub order: Option<Vec<BibScope>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct AiConfig {
+    /// The default provider, one of the built-in providers
+    pub provider: Option<String>,
+    /// A custom OpenAI-compatible endpoint replacing the provider's, e.g. a local server
+    pub base_url: Option<String>,
+    pub model: Option<String>,
+    pub embedding_model: Option<String>,
+    /// Prices by model, used to estimate the cost of a run
+    pub prices: BTreeMap<String, Price>,
+}
+
+/// The price of a model in USD per million tokens.
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Price {
+    pub input: f64,
+    pub output: f64,
+}
+
+pub fn process_config_command(command: ConfigCommand) -> Result<()> {
+    match command {
+        ConfigCommand::Doctor => {
+            let Some(path) = config_path() else {
+                bail!("Cannot determine the config directory");
+            };
+            println!("Config file: {}", path.display());
+
+            if !path.exists() {
+                println!("Not found, the defaults are used");
+                return Ok(());
+            }
+
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read config '{}'", path.display()))?;
+            let problems = diagnose(&content)
+                .map_err(|e| anyhow!("Invalid config '{}': {}", path.display(), e))?;
+
+            if problems.is_empty() {
+                println!("No problems found");
+                return Ok(());
+            }
+
+            for problem in &problems {
+                println!("- {}", problem);
+            }
+            bail!("{} problem(s) found", problems.len());
+        }
+    }
+}
+
+pub fn process_vault_command(command: VaultCommand) -> Result<()> {
+    let config = Config::load()?;
+    match command {
+        VaultCommand::List => {
+            if config.vaults.is_empty() {
+                println!("No vaults in the config");
+                return Ok(());
+            }
+            let (current, _) = selected_vault(std::env::args_os());
+            let width = config.vaults.keys().map(|n| n.len()).max().unwrap_or(0);
+            for (name, vault) in &config.vaults {
+                let marker = if current.as_ref() == Some(name) {
+                    '*'
+                } else {
+                    ' '
+                };
+                let note_root = vault
+                    .note_root
+                    .as_ref()
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_default();
+                println!("{} {:<width$}  {}", marker, name, note_root);
+            }
+        }
+        VaultCommand::Switch { name } => {
+            let Some(path) = current_vault_path() else {
+                bail!("Cannot determine the config directory");
+            };
+            match name {
+                Some(name) => {
+                    config.vault(&name)?;
+                    if let Some(dir) = path.parent() {
+                        fs::create_dir_all(dir)?;
+                    }
+                    fs::write(&path, format!("{}\n", name))
+                        .with_context(|| format!("Failed to write '{}'", path.display()))?;
+                    println!("Switched to vault '{}'", name);
+                }
+                None => {
+                    if path.exists() {
+                        fs::remove_file(&path)
+                            .with_context(|| format!("Failed to remove '{}'", path.display()))?;
+                    }
+                    println!("Using the top-level options of the config");
+                }
+            }
+            if std::env::var_os(VAULT_ENV).is_some() {
+                eprintln!("Note: ${} is set and takes precedence", VAULT_ENV);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The config file path: `$NOXE_CONFIG`, or `noxe/config.yaml` under the user config directory.
+pub fn config_path() -> Option<PathBuf> {
+    match std::env::var_os(CONFIG_ENV) {
+        Some(path) if !path.is_empty() => Some(PathBuf::from(path)),
+        _ => dirs::config_dir().map(|dir| dir.join("noxe").join("config.yaml")),
+    }
+}
+
+/// The file remembering the vault of `noxe vault switch`, next to the config file.
+fn current_vault_path() -> Option<PathBuf> {
+    config_path().map(|p| p.with_file_name(CURRENT_VAULT_FILE))
+}
+
+/// The selected vault: `--vault`, then `$NOXE_VAULT`, then the vault of `noxe vault switch`.
+/// Also returns whether it was given on the command line, where it beats the environment.
+pub fn selected_vault(args: impl IntoIterator<Item = OsString>) -> (Option<String>, bool) {
+    if let Some(name) = vault_arg(args) {
+        return (Some(name), true);
+    }
+    let name = std::env::var(VAULT_ENV).ok().or_else(|| {
+        current_vault_path()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .map(|name| name.trim().to_string())
+    });
+    (name.filter(|n| !n.is_empty()), false)
+}
+
+/// The value of the global `--vault` option, which is needed before the command line is parsed.
+fn vault_arg(args: impl IntoIterator<Item = OsString>) -> Option<String> {
+    let mut args = args.into_iter().skip(1);
+    while let Some(arg) = args.next() {
+        let arg = arg.to_string_lossy();
+        if arg == "--" {
+            break;
+        }
+        if arg == "--vault" {
+            return args.next().map(|a| a.to_string_lossy().to_string());
+        }
+        if let Some(name) = arg.strip_prefix("--vault=") {
+            return Some(name.to_string());
+        }
+    }
+    None
+}
+
+impl Config {
+    /// Load the config file, with the searches saved next to it. A missing file yields the
+    /// default config.
+    pub fn load() -> Result<Config> {
+        let mut config = match config_path().filter(|p| p.exists()) {
+            Some(path) => {
+                let content = fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read config '{}'", path.display()))?;
+                let (config, unknown_keys) = parse(&content)
+                    .map_err(|e| anyhow!("Invalid config '{}': {}", path.display(), e))?;
+
+                for key in unknown_keys {
+                    tracing::debug!("Unknown config key '{}'", key);
+                }
+                config
+            }
+            None => Config::default(),
+        };
+
+        if let Some(path) = searches::saved_searches_path() {
+            for (name, search) in searches::load(&path)? {
+                config.searches.entry(name).or_insert(search);
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// The vault of the config named `name`.
+    pub fn vault(&self, name: &str) -> Result<&VaultConfig> {
+        match self.vaults.get(name) {
+            Some(vault) => Ok(vault),
+            None if self.vaults.is_empty() => {
+                bail!("Unknown vault '{}': the config has none", name)
+            }
+            None => bail!(
+                "Unknown vault '{}' (expected one of: {})",
+                name,
+                self.vaults.keys().cloned().collect::<Vec<_>>().join(", ")
+            ),
+        }
+    }
+
+    /// Expose the options as the `NOXE_*` environment variables read by the command line,
+    /// unless they are already set. The options of `vault` come first, and with `force` they
+    /// replace the environment too. Must be called before any thread is spawned.
+    pub fn apply_env(&self, vault: Option<&str>, force: bool) -> Result<()> {
+        if let Some(name) = vault {
+            let vault = self.vault(name)?;
+            let path = |p: &Option<PathBuf>| p.as_ref().map(|p| p.display().to_string());
+            let vars = [
+                ("NOXE_ROOT", path(&vault.note_root)),
+                ("NOXE_AUTHOR", vault.author.clone()),
+                ("NOXE_TYPE", vault.note_type.clone()),
+                ("NOXE_TEMPLATE", path(&vault.template)),
+                ("NOXE_TEMPLATE_DIR", path(&vault.template_dir)),
+                ("NOXE_EDIT", vault.editor.clone()),
+                ("NOXE_INBOX", vault.inbox.clone()),
+            ];
+            for (name, value) in vars {
+                if let Some(value) = value
+                    && (force || std::env::var_os(name).is_none())
+                {
+                    // SAFETY: called at startup, before any other thread exists.
+                    unsafe { std::env::set_var(name, value) };
+                }
+            }
+        }
+
+        let vars = [
+            (
+                "NOXE_ROOT",
+                self.note_root.as_ref().map(|p| p.display().to_string()),
+            ),
+            ("NOXE_AUTHOR", self.author.clone()),
+            ("NOXE_TYPE", self.note_type.clone()),
+            (
+                "NOXE_TEMPLATE",
+                self.template.as_ref().map(|p| p.display().to_string()),
+            ),
+            ("NOXE_EDIT", self.editor.clone()),
+            ("NOXE_PREVIEW_TYPST", self.preview.typst.clone()),
+            ("NOXE_PREVIEW_MARKDOWN", self.preview.markdown.clone()),
+            ("NOXE_PREVIEW_ORG", self.preview.org.clone()),
+            ("NOXE_PDF_VIEWER", self.preview.pdf_viewer.clone()),
+            ("NOXE_JOURNAL_PATH", self.journal.path.clone()),
+            (
+                "NOXE_JOURNAL_TEMPLATE",
+                self.journal
+                    .template
+                    .as_ref()
+                    .map(|p| p.display().to_string()),
+            ),
+            ("NOXE_INBOX", self.inbox.clone()),
+            ("NOXE_AI_PROVIDER", self.ai.provider.clone()),
+            ("NOXE_AI_BASE_URL", self.ai.base_url.clone()),
+            ("NOXE_AI_MODEL", self.ai.model.clone()),
+            ("NOXE_AI_EMBEDDING_MODEL", self.ai.embedding_model.clone()),
+            ("NOXE_HOOK_PRE_NEW", self.hooks.pre_new.clone()),
+            ("NOXE_HOOK_POST_NEW", self.hooks.post_new.clone()),
+            ("NOXE_HOOK_POST_EDIT", self.hooks.post_edit.clone()),
+            ("NOXE_HOOK_PRE_DELETE", self.hooks.pre_delete.clone()),
+            (
+                "NOXE_AGE_IDENTITY",
+                self.encryption
+                    .identity
+                    .as_ref()
+                    .map(|p| p.display().to_string()),
+            ),
+            ("NOXE_FSYNC", self.fsync.then(|| "1".to_string())),
+            ("NOXE_ZETTEL_IDS", self.zettel_ids.then(|| "1".to_string())),
+            (
+                "NOXE_BIBLIOGRAPHY_ORDER",
+                self.bibliography.order.as_ref().map(|order| {
+                    order
+                        .iter()
+                        .filter_map(|s| s.to_possible_value())
+                        .map(|v| v.get_name().to_string())
+                        .collect::<Vec<_>>()
+                        .join(",")
+                }),
+            ),
+        ];
+
+        for (name, value) in vars {
+            if let Some(value) = value
+                && std::env::var_os(name).is_none()
+            {
+                // SAFETY: called at startup, before any other thread exists.
+                unsafe { std::env::set_var(name, value) };
+            }
+        }
+        Ok(())
+    }
+
+    /// Make the note types, capture templates, saved searches and TUI theme of the config
+    /// known, before parsing the command line. Only the first call has an effect.
+    pub fn register_types(&self) {
+        let _ = NOTE_TYPES.set(self.types.clone());
+        let _ = CAPTURE_TEMPLATES.set(self.capture_templates.clone());
+        let _ = SAVED_SEARCHES.set(self.searches.clone());
+        let _ = TUI_THEME.set(self.tui.theme.clone());
+    }
+}
+
+/// Parse the config, returning it with the keys that are not part of the schema.
+/// Errors carry the path of the offending option and its line and column.
+pub(crate) fn parse(content: &str) -> Result<(Config, Vec<String>)> {
+    if content.trim().is_empty() {
+        return Ok((Config::default(), Vec::new()));
+    }
+
+    let mut unknown_keys = Vec::new();
+    let mut track = |path: serde_ignored::Path| unknown_keys.push(path.to_string());
+    let deserializer =
+        serde_ignored::Deserializer::new(serde_yml::Deserializer::from_str(content), &mut track);
+    let mut config: Config = serde_path_to_error::deserialize(deserializer).map_err(|e| {
+        let (path, message) = (e.path().to_string(), e.inner().to_string());
+        // serde_yml already names the option in most of its messages
+        if path == "." || message.starts_with(&format!("{}: ", path)) {
+            anyhow!("{}", message)
+        } else {
+            anyhow!("{}: {}", path, message)
+        }
+    })?;
+
+    for (ext, note_type) in &config.types {
+        if ext.is_empty() || ext.contains(['.', '/', '\\']) || BUILTIN_TYPES.contains(&ext.as_str())
+        {
+            bail!("types.{}: not a valid extension for a new note type", ext);
+        }
+        if let Some(main_file) = &note_type.main_file
+            && (main_file.contains(['/', '\\']) || !main_file.ends_with(&format!(".{}", ext)))
+        {
+            bail!(
+                "types.{}.main_file: '{}' must be a file name ending with '.{}'",
+                ext,
+                main_file,
+                ext
+            );
+        }
+    }
+    if let Some(note_type) = &config.note_type
+        && !BUILTIN_TYPES.contains(&note_type.as_str())
+        && !config.types.contains_key(note_type)
+    {
+        bail!("note_type: Invalid note type: {}", note_type);
+    }
+    for (name, vault) in &config.vaults {
+        if let Some(note_type) = &vault.note_type
+            && !BUILTIN_TYPES.contains(&note_type.as_str())
+            && !config.types.contains_key(note_type)
+        {
+            bail!(
+                "vaults.{}.note_type: Invalid note type: {}",
+                name,
+                note_type
+            );
+        }
+    }
+
+    for (name, template) in &config.capture_templates {
+        if let Some(target) = &template.target {
+            capture::expand_target(target, chrono::Local::now())
+                .with_context(|| format!("capture_templates.{}.target", name))?;
+        }
+    }
+
+    for (key, color) in config.tui.theme.colors() {
+        if let Some(color) = color
+            && color.parse::<ratatui::style::Color>().is_err()
+        {
+            bail!("tui.theme.{}: Invalid color: {}", key, color);
+        }
+    }
+
+    config.note_root = config.note_root.map(|p| expand_home(&p));
+    config.template = config.template.map(|p| expand_home(&p));
+    config.journal.template = config.journal.template.map(|p| expand_home(&p));
+    for vault in config.vaults.values_mut() {
+        vault.note_root = vault.note_root.take().map(|p| expand_home(&p));
+        vault.template = vault.template.take().map(|p| expand_home(&p));
+        vault.template_dir = vault.template_dir.take().map(|p| expand_home(&p));
+    }
+
+    Ok((config, unknown_keys))
+}
+
+/// A problem found by `config doctor`.
+#[derive(Debug, PartialEq)]
+pub enum Problem {
+    UnknownKey(String),
+    Deprecated {
+        key: &'static str,
+        replacement: &'static str,
+    },
+    MissingFile {
+        key: String,
+        path: PathBuf,
+    },
+    UnknownProvider(String),
+    Invalid {
+        key: &'static str,
+        reason: String,
+    },
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Problem::UnknownKey(key) => write!(f, "unknown key '{}'", key),
+            Problem::Deprecated { key, replacement } => {
+                write!(f, "'{}' is deprecated, use '{}' instead", key, replacement)
+            }
+            Problem::MissingFile { key, path } => {
+                write!(
+                    f,
+                    "'{}' refers to '{}', which does not exist",
+                    key,
+                    path.display()
+                )
+            }
+            Problem::Invalid { key, reason } => write!(f, "'{}' is invalid: {}", key, reason),
+            Problem::UnknownProvider(provider) => {
+                let known = PROVIDERS.map(|p| p.name);
+                write!(
+                    f,
+                    "unknown AI provider '{}' (expected one of: {})",
+                    provider,
+                    known.join(", ")
+                )
+            }
+        }
+    }
+}
+
+/// Check the config for problems that do not prevent loading it.
+pub fn diagnose(content: &str) -> Result<Vec<Problem>> {
+    let (config, unknown_keys) = parse(content)?;
+    let mut problems: Vec<Problem> = unknown_keys.into_iter().map(Problem::UnknownKey).collect();
+
+    let raw: serde_yml::Value = serde_yml::from_str(content).unwrap_or_default();
+    for (key, replacement) in DEPRECATED_KEYS {
+        if raw.get(key).is_some() {
+            problems.push(Problem::Deprecated { key, replacement });
+        }
+    }
+
+    if let Some(path) = config.note_root
+        && !path.is_dir()
+    {
+        problems.push(Problem::MissingFile {
+            key: "note_root".into(),
+            path,
+        });
+    }
+    if let Some(path) = config.template
+        && !path.is_file()
+    {
+        problems.push(Problem::MissingFile {
+            key: "template".into(),
+            path,
+        });
+    }
+
+    for (name, vault) in &config.vaults {
+        let files = [
+            ("note_root", &vault.note_root, true),
+            ("template", &vault.template, false),
+            ("template_dir", &vault.template_dir, true),
+        ];
+        for (key, path, is_dir) in files {
+            if let Some(path) = path
+                && !(if is_dir {
+                    path.is_dir()
+                } else {
+                    path.is_file()
+                })
+            {
+                problems.push(Problem::MissingFile {
+                    key: format!("vaults.{}.{}", name, key),
+                    path: path.clone(),
+                });
+            }
+        }
+    }
+
+    if let Some(path) = config.journal.template
+        && !path.is_file()
+    {
+        problems.push(Problem::MissingFile {
+            key: "journal.template".into(),
+            path,
+        });
+    }
+    if let Some(pattern) = config.journal.path
+        && let Err(e) = journal::check_pattern(&pattern)
+    {
+        problems.push(Problem::Invalid {
+            key: "journal.path",
+            reason: e.to_string(),
+        });
+    }
+
+    if let Some(provider) = config.ai.provider
+        && !PROVIDERS.iter().any(|p| p.name == provider)
+    {
+        problems.push(Problem::UnknownProvider(provider));
+    }
+
+    Ok(problems)
+}
+
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_error_location() {
+        let err = parse("author: me\npreview:\n  typst: [1, 2]\n")
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("preview.typst: "), "{}", err);
+        assert!(err.contains("line 3"), "{}", err);
+
+        let err = parse("note_type: pdf\n").unwrap_err().to_string();
+        assert!(err.starts_with("note_type: "), "{}", err);
+
+        let err = parse("tui:\n  theme:\n    border: reddish\n")
+            .unwrap_err()
+            .to_string();
+        assert_eq!(err, "tui.theme.border: Invalid color: reddish");
+    }
+
+    #[test]
+    fn test_note_types() {
+        use crate::cli::NoteType;
+        use crate::vault::Note;
+
+        let err = parse("types:\n  md: {}\n").unwrap_err().to_string();
+        assert!(err.starts_with("types.md: "), "{}", err);
+        let err = parse("types:\n  adoc:\n    main_file: index.md\n")
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("types.adoc.main_file: "), "{}", err);
+
+        let (config, _) = parse(
+            "note_type: adoc\ntypes:\n  adoc:\n    main_file: index.adoc\n    preview: asciidoctor-preview\n",
+        )
+        .unwrap();
+        config.register_types();
+        assert_eq!(
+            note_types()["adoc"].preview.as_deref(),
+            Some("asciidoctor-preview")
+        );
+
+        let adoc = NoteType::try_from("adoc").unwrap();
+        assert_eq!(adoc.main_file(), "index.adoc");
+        assert_eq!(NoteType::Md.main_file(), "main.md");
+
+        let tmp_dir = tempdir().unwrap();
+        let dirnote = tmp_dir.path().join("dirnote");
+        std::fs::create_dir(&dirnote).unwrap();
+        std::fs::write(dirnote.join("index.adoc"), "= Title\n").unwrap();
+        let filenote = tmp_dir.path().join("filenote.adoc");
+        std::fs::write(&filenote, "").unwrap();
+
+        assert!(dirnote.is_dirnote());
+        assert_eq!(
+            dirnote.main_file_path().unwrap(),
+            dirnote.join("index.adoc")
+        );
+        assert!(filenote.is_filenote());
+        assert_eq!(filenote.note_type().unwrap(), adoc);
+    }
+
+    #[test]
+    fn test_diagnose() {
+        let tmp_dir = tempdir().unwrap();
+        let content = format!(
+            "note_dir: {}\ntemplate: {}\neditr: nvim\nai:\n  provider: foo\n  modle: x\n",
+            tmp_dir.path().display(),
+            tmp_dir.path().join("missing.yaml").display()
+        );
+
+        let problems = diagnose(&content).unwrap();
+        assert_eq!(
+            problems,
+            [
+                Problem::UnknownKey("editr".into()),
+                Problem::UnknownKey("ai.modle".into()),
+                Problem::Deprecated {
+                    key: "note_dir",
+                    replacement: "note_root"
+                },
+                Problem::MissingFile {
+                    key: "template".into(),
+                    path: tmp_dir.path().join("missing.yaml")
+                },
+                Problem::UnknownProvider("foo".into()),
+            ]
+        );
+
+        assert!(diagnose("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_vaults() {
+        let tmp_dir = tempdir().unwrap();
+        let content = format!(
+            "note_root: {0}\nvaults:\n  work:\n    note_dir: {0}\n    author: Me\n  personal:\n    note_root: {1}\n",
+            tmp_dir.path().display(),
+            tmp_dir.path().join("missing").display()
+        );
+        let (config, unknown_keys) = parse(&content).unwrap();
+        assert!(unknown_keys.is_empty());
+        assert_eq!(config.vault("work").unwrap().author.as_deref(), Some("Me"));
+        let err = config.vault("home").unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "Unknown vault 'home' (expected one of: personal, work)"
+        );
+
+        assert_eq!(
+            diagnose(&content).unwrap(),
+            [Problem::MissingFile {
+                key: "vaults.personal.note_root".into(),
+                path: tmp_dir.path().join("missing")
+            }]
+        );
+
+        let err = parse("vaults:\n  work:\n    note_type: pdf\n")
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("vaults.work.note_type: "), "{}", err);
+
+        let args = |args: &[&str]| vault_arg(args.iter().map(OsString::from));
+        assert_eq!(
+            args(&["noxe", "list", "--vault", "work"]).as_deref(),
+            Some("work")
+        );
+        assert_eq!(
+            args(&["noxe", "--vault=personal", "list"]).as_deref(),
+            Some("personal")
+        );
+        assert_eq!(args(&["noxe", "new", "--", "--vault"]), None);
+    }
+}
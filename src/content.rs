@@ -0,0 +1,171 @@
+use std::{
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
+
+use crate::bounded::{CONTENT_LIMIT, map_head};
+use crate::extract::extractor;
+use crate::vault::Note;
+
+/// The body lines of a set of notes, lowercased for case-insensitive term lookups.
+#[derive(Debug, Default)]
+pub struct ContentIndex {
+    notes: Vec<IndexedNote>,
+}
+
+#[derive(Debug)]
+struct IndexedNote {
+    path: PathBuf,
+    /// The original lines, for display
+    lines: Vec<String>,
+    lowercase: Vec<String>,
+}
+
+/// A window of lines of a note in which all the terms occur.
+#[derive(Debug, PartialEq)]
+pub struct NearMatch<'a> {
+    pub note: &'a Path,
+    /// Line numbers, starting at 1
+    pub lines: RangeInclusive<usize>,
+    pub text: &'a [String],
+}
+
+impl ContentIndex {
+    /// Index the main files of the notes, memory-mapped and up to [`CONTENT_LIMIT`] bytes of
+    /// each. Unreadable notes are skipped.
+    pub fn build(notes: &[PathBuf]) -> Self {
+        let notes = notes
+            .iter()
+            .filter_map(|note| {
+                let main_file = note.main_file_path().ok()?;
+                let extractor = extractor(main_file.note_type().ok()?);
+                let lines = map_head(&main_file, CONTENT_LIMIT, |content| {
+                    extractor
+                        .blank_metadata(content)
+                        .lines()
+                        .map(str::to_string)
+                        .collect::<Vec<_>>()
+                })
+                .ok()?;
+                Some(IndexedNote {
+                    path: note.clone(),
+                    lowercase: lines.iter().map(|l| l.to_lowercase()).collect(),
+                    lines,
+                })
+            })
+            .collect();
+
+        ContentIndex { notes }
+    }
+
+    /// Find, in each note, the smallest window of lines containing every term where the first
+    /// and last lines are at most `within` lines apart. A term may be a phrase; it must occur
+    /// within a single line.
+    pub fn near(&self, terms: &[String], within: usize) -> Vec<NearMatch<'_>> {
+        let terms: Vec<String> = terms.iter().map(|t| t.to_lowercase()).collect();
+        if terms.is_empty() {
+            return Vec::new();
+        }
+
+        self.notes
+            .iter()
+            .filter_map(|note| {
+                let window = smallest_window(note, &terms)?;
+                (window.end() - window.start() <= within).then(|| NearMatch {
+                    note: &note.path,
+                    lines: window.start() + 1..=window.end() + 1,
+                    text: &note.lines[window],
+                })
+            })
+            .collect()
+    }
+}
+
+/// The smallest range of line indexes covering an occurrence of every term.
+fn smallest_window(note: &IndexedNote, terms: &[String]) -> Option<RangeInclusive<usize>> {
+    // (line, term) occurrences, ordered by line
+    let occurrences: Vec<(usize, usize)> = note
+        .lowercase
+        .iter()
+        .enumerate()
+        .flat_map(|(line, text)| {
+            terms
+                .iter()
+                .enumerate()
+                .filter(move |(_, term)| text.contains(term.as_str()))
+                .map(move |(term, _)| (line, term))
+        })
+        .collect();
+
+    let mut counts = vec![0usize; terms.len()];
+    let mut covered = 0;
+    let mut best: Option<RangeInclusive<usize>> = None;
+    let mut start = 0;
+
+    for &(line, term) in &occurrences {
+        if counts[term] == 0 {
+            covered += 1;
+        }
+        counts[term] += 1;
+
+        while covered == terms.len() {
+            let (first, first_term) = occurrences[start];
+            if best
+                .as_ref()
+                .is_none_or(|b| line - first < b.end() - b.start())
+            {
+                best = Some(first..=line);
+            }
+
+            counts[first_term] -= 1;
+            if counts[first_term] == 0 {
+                covered -= 1;
+            }
+            start += 1;
+        }
+    }
+
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_near() {
+        let tmp_dir = tempdir().unwrap();
+        let a = tmp_dir.path().join("a.md");
+        let b = tmp_dir.path().join("b.md");
+        fs::write(
+            &a,
+            "---\ntitle: lifetime\n---\nThe Borrow Checker\n\n\n\n\nrejects this\nlifetime 'a\nborrow checker again\n",
+        )
+        .unwrap();
+        fs::write(&b, "borrow checker\n\n\n\n\nlifetime\n").unwrap();
+
+        let index = ContentIndex::build(&[a.clone(), b.clone()]);
+        let terms = ["borrow checker".to_string(), "lifetime".to_string()];
+
+        let matches = index.near(&terms, 3);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].note, a);
+        assert_eq!(matches[0].lines, 10..=11);
+        assert_eq!(matches[0].text, ["lifetime 'a", "borrow checker again"]);
+
+        assert_eq!(index.near(&terms, 5).len(), 2);
+        assert!(index.near(&["missing".to_string()], 3).is_empty());
+
+        // Typst metadata in the middle of the file keeps the line numbers
+        let c = tmp_dir.path().join("c.typ");
+        fs::write(
+            &c,
+            "= Intro\n#metadata((\n  status: \"draft\",\n)) <info>\nborrow checker\nlifetime\n",
+        )
+        .unwrap();
+        let index = ContentIndex::build(&[c]);
+        assert_eq!(index.near(&terms, 1)[0].lines, 5..=6);
+    }
+}
@@ -0,0 +1,155 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+use serde_json::Value;
+use std::{fs, path::Path, process};
+
+use crate::metadata::Fields;
+use crate::sync::git;
+use crate::vault::NoteTemplate;
+
+/// The metadata field of new notes holding the context they were created in.
+pub const CONTEXT_FIELD: &str = "context";
+
+/// What of the environment is captured when a note is created, for lab-notebook style notes
+/// that tell where they come from.
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ContextItem {
+    /// The repository, commit and dirtiness of a git repository, the current directory's by
+    /// default
+    Git,
+    Hostname,
+    /// The current directory
+    Cwd,
+    /// The noxe command line creating the note
+    Command,
+}
+
+/// The context of a new note: the items given on the command line, else those of its
+/// template.
+pub fn for_template(template: &NoteTemplate, items: &[ContextItem], repo: Option<&Path>) -> Fields {
+    let items = if items.is_empty() {
+        template.context()
+    } else {
+        items
+    };
+    capture(items, repo.or(template.context_repo()))
+}
+
+/// Capture the items. Those that cannot be captured, like `git` outside of a repository, are
+/// left out with a warning.
+pub fn capture(items: &[ContextItem], repo: Option<&Path>) -> Fields {
+    let mut context = Fields::new();
+    for item in items {
+        match item {
+            ContextItem::Git => {
+                let dir = repo.unwrap_or(Path::new("."));
+                match git_context(dir) {
+                    Some(git) => context.extend(git),
+                    None => eprintln!(
+                        "Warning: '{}' is not a git repository, its commit is not captured",
+                        dir.display()
+                    ),
+                }
+            }
+            ContextItem::Hostname => match hostname() {
+                Some(hostname) => {
+                    context.insert("hostname".to_string(), hostname.into());
+                }
+                None => eprintln!("Warning: the hostname is unknown"),
+            },
+            ContextItem::Cwd => {
+                if let Ok(cwd) = std::env::current_dir() {
+                    context.insert("cwd".to_string(), cwd.display().to_string().into());
+                }
+            }
+            ContextItem::Command => {
+                let args: Vec<String> = std::env::args_os()
+                    .map(|a| quote(&a.to_string_lossy()))
+                    .collect();
+                context.insert("command".to_string(), args.join(" ").into());
+            }
+        }
+    }
+    context
+}
+
+fn git_context(dir: &Path) -> Option<Fields> {
+    let run = |args: &[&str]| {
+        git(dir, args)
+            .ok()
+            .filter(|o| o.status.success())
+            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+    };
+
+    let repo = run(&["rev-parse", "--show-toplevel"])?;
+    let mut context = Fields::from([("git_repo".to_string(), Value::from(repo))]);
+    // A repository without commits has no HEAD
+    if let Some(commit) = run(&["rev-parse", "HEAD"]) {
+        context.insert("git_commit".to_string(), commit.into());
+    }
+    if let Some(status) = run(&["status", "--porcelain"]) {
+        context.insert("git_dirty".to_string(), (!status.is_empty()).into());
+    }
+    Some(context)
+}
+
+fn hostname() -> Option<String> {
+    let from_command = || {
+        process::Command::new("hostname")
+            .output()
+            .ok()
+            .filter(|o| o.status.success())
+            .map(|o| String::from_utf8_lossy(&o.stdout).to_string())
+    };
+    std::env::var("COMPUTERNAME")
+        .ok()
+        .or_else(|| fs::read_to_string("/proc/sys/kernel/hostname").ok())
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .or_else(from_command)
+        .map(|h| h.trim().to_string())
+        .filter(|h| !h.is_empty())
+}
+
+/// Quote an argument for a POSIX shell when it needs it.
+fn quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_alphanumeric() || "-_./=:,@%+".contains(c));
+    if plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_capture() {
+        let tmp_dir = tempdir().unwrap();
+        let repo = tmp_dir.path();
+        let context = capture(
+            &[ContextItem::Git, ContextItem::Cwd, ContextItem::Command],
+            Some(repo),
+        );
+        // Not a repository
+        assert!(!context.contains_key("git_repo"));
+        assert!(context.contains_key("cwd"));
+        assert!(context.contains_key("command"));
+
+        if git(repo, &["init", "-q"]).is_ok_and(|o| o.status.success()) {
+            fs::write(repo.join("data.csv"), "1,2\n").unwrap();
+            let context = capture(&[ContextItem::Git], Some(repo));
+            assert_eq!(context["git_dirty"], Value::Bool(true));
+            assert!(context.contains_key("git_repo"));
+        }
+
+        assert_eq!(quote("--context=git,cwd"), "--context=git,cwd");
+        assert_eq!(quote("it's here"), r"'it'\''s here'");
+    }
+}
@@ -0,0 +1,247 @@
+use anyhow::{Context, Result, anyhow, bail};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::atomic;
+use crate::vault::Note;
+
+/// The extension of encrypted files, after the one of the note type: `secrets.md.age`.
+pub const ENCRYPTED_EXT: &str = "age";
+/// The age identity file, set by `encryption.identity` in the config.
+pub const IDENTITY_VAR: &str = "NOXE_AGE_IDENTITY";
+
+/// Whether the file is encrypted.
+pub fn is_encrypted(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == ENCRYPTED_EXT)
+}
+
+/// The encrypted file of `file`: `note.md` gives `note.md.age`.
+pub fn encrypted_path(file: &Path) -> PathBuf {
+    let mut path = file.as_os_str().to_owned();
+    path.push(".");
+    path.push(ENCRYPTED_EXT);
+    PathBuf::from(path)
+}
+
+/// Read the age identity (`AGE-SECRET-KEY-1...`) of `path`, or of `NOXE_AGE_IDENTITY`. Notes
+/// are encrypted to its public key.
+pub fn identity(path: Option<&Path>) -> Result<age::x25519::Identity> {
+    let Some(path) = path
+        .map(Path::to_path_buf)
+        .or_else(|| env::var_os(IDENTITY_VAR).map(PathBuf::from))
+    else {
+        bail!(
+            "No age identity: set `encryption.identity` in the config, or pass --identity. \
+            `age-keygen -o <file>` creates one"
+        );
+    };
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read the identity '{}'", path.display()))?;
+
+    content
+        .lines()
+        .map(str::trim)
+        .find(|line| line.starts_with("AGE-SECRET-KEY-"))
+        .with_context(|| format!("No age secret key in '{}'", path.display()))
+        .and_then(|key| {
+            age::x25519::Identity::from_str(key)
+                .map_err(|e| anyhow!("Invalid identity '{}': {}", path.display(), e))
+        })
+}
+
+/// Encrypt the main file of a note, replacing it. Returns the encrypted file.
+pub fn encrypt_note(note: &Path, identity: &age::x25519::Identity) -> Result<PathBuf> {
+    let main_file = note.main_file_path()?;
+    if is_encrypted(&main_file) {
+        bail!("'{}' is already encrypted", note.display());
+    }
+    let encrypted = encrypted_path(&main_file);
+    if encrypted.exists() {
+        bail!("'{}' already exists", encrypted.display());
+    }
+
+    let plaintext = fs::read(&main_file)
+        .with_context(|| format!("Failed to read '{}'", main_file.display()))?;
+    let ciphertext = age::encrypt(&identity.to_public(), &plaintext)?;
+    atomic::write(&encrypted, ciphertext)?;
+    fs::remove_file(&main_file)
+        .with_context(|| format!("Failed to remove '{}'", main_file.display()))?;
+
+    Ok(encrypted)
+}
+
+/// Decrypt the main file of a note, replacing it. Returns the decrypted file.
+pub fn decrypt_note(note: &Path, identity: &age::x25519::Identity) -> Result<PathBuf> {
+    let main_file = note.main_file_path()?;
+    if !is_encrypted(&main_file) {
+        bail!("'{}' is not encrypted", note.display());
+    }
+    let decrypted = main_file.with_extension("");
+    if decrypted.exists() {
+        bail!("'{}' already exists", decrypted.display());
+    }
+
+    atomic::write(&decrypted, decrypt(&main_file, identity)?)?;
+    fs::remove_file(&main_file)
+        .with_context(|| format!("Failed to remove '{}'", main_file.display()))?;
+
+    Ok(decrypted)
+}
+
+fn decrypt(file: &Path, identity: &age::x25519::Identity) -> Result<Vec<u8>> {
+    let ciphertext =
+        fs::read(file).with_context(|| format!("Failed to read '{}'", file.display()))?;
+    age::decrypt(identity, &ciphertext)
+        .with_context(|| format!("Failed to decrypt '{}'", file.display()))
+}
+
+/// A decrypted copy of an encrypted file, for the previewer and editor to open. It lives in a
+/// private temporary directory, removed when the copy is dropped.
+pub struct Decrypted {
+    /// The copy, named like the file without `.age`
+    pub path: PathBuf,
+    dir: PathBuf,
+    file: PathBuf,
+    plaintext: Vec<u8>,
+    identity: age::x25519::Identity,
+}
+
+impl Decrypted {
+    /// Decrypt `file` when it is encrypted.
+    pub fn open_if_encrypted(file: &Path) -> Result<Option<Decrypted>> {
+        if !is_encrypted(file) {
+            return Ok(None);
+        }
+        Decrypted::open(file, identity(None)?).map(Some)
+    }
+
+    fn open(file: &Path, identity: age::x25519::Identity) -> Result<Decrypted> {
+        let plaintext = decrypt(file, &identity)?;
+
+        let dir = private_dir()?;
+        let path = dir.join(file.with_extension("").file_name().unwrap());
+        let decrypted = Decrypted {
+            path,
+            dir,
+            file: file.to_path_buf(),
+            plaintext,
+            identity,
+        };
+        fs::write(&decrypted.path, &decrypted.plaintext)
+            .with_context(|| format!("Failed to write '{}'", decrypted.path.display()))?;
+
+        Ok(decrypted)
+    }
+
+    /// Encrypt the copy back into the file, when it was changed. Returns whether it was.
+    pub fn save(&self) -> Result<bool> {
+        let plaintext = fs::read(&self.path)
+            .with_context(|| format!("Failed to read '{}'", self.path.display()))?;
+        if plaintext == self.plaintext {
+            return Ok(false);
+        }
+        let ciphertext = age::encrypt(&self.identity.to_public(), &plaintext)?;
+        atomic::write(&self.file, ciphertext)?;
+        Ok(true)
+    }
+}
+
+impl Drop for Decrypted {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.dir) {
+            tracing::warn!(
+                "Failed to remove the decrypted copy '{}': {}",
+                self.dir.display(),
+                e
+            );
+        }
+    }
+}
+
+/// A new directory only the user can read, in memory when `XDG_RUNTIME_DIR` is set.
+fn private_dir() -> Result<PathBuf> {
+    let base = env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_dir())
+        .unwrap_or_else(env::temp_dir);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.subsec_nanos());
+    let dir = base.join(format!("noxe-decrypted-{}-{}", process::id(), nanos));
+
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder
+        .create(&dir)
+        .with_context(|| format!("Failed to create '{}'", dir.display()))?;
+    Ok(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use age::secrecy::ExposeSecret;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_encrypt_note() {
+        let tmp_dir = tempdir().unwrap();
+        let key = tmp_dir.path().join("age.key");
+        let generated = age::x25519::Identity::generate();
+        fs::write(
+            &key,
+            format!(
+                "# created: today\n{}\n",
+                generated.to_string().expose_secret()
+            ),
+        )
+        .unwrap();
+        let identity = identity(Some(&key)).unwrap();
+
+        let note = tmp_dir.path().join("secrets.md");
+        fs::write(&note, "# Secrets\npassword\n").unwrap();
+        let encrypted = encrypt_note(&note, &identity).unwrap();
+        assert_eq!(encrypted, tmp_dir.path().join("secrets.md.age"));
+        assert!(!note.exists());
+        assert!(encrypted.is_filenote());
+        assert_eq!(encrypted.note_name(), "secrets");
+        assert!(encrypt_note(&encrypted, &identity).is_err());
+
+        let decrypted = decrypt_note(&encrypted, &identity).unwrap();
+        assert_eq!(decrypted, note);
+        assert_eq!(fs::read_to_string(&note).unwrap(), "# Secrets\npassword\n");
+        assert!(!encrypted.exists());
+
+        // The main file of a dirnote
+        let dirnote = tmp_dir.path().join("health");
+        fs::create_dir(&dirnote).unwrap();
+        fs::write(dirnote.join("main.md"), "# Health\n").unwrap();
+        encrypt_note(&dirnote, &identity).unwrap();
+        assert!(dirnote.is_dirnote());
+        assert_eq!(
+            dirnote.main_file_path().unwrap(),
+            dirnote.join("main.md.age")
+        );
+
+        // Editing the decrypted copy
+        let copy = Decrypted::open(&dirnote.join("main.md.age"), identity.clone()).unwrap();
+        assert_eq!(copy.path.file_name().unwrap(), "main.md");
+        assert_eq!(fs::read_to_string(&copy.path).unwrap(), "# Health\n");
+        assert!(!copy.save().unwrap());
+        fs::write(&copy.path, "# Health\nAllergies\n").unwrap();
+        assert!(copy.save().unwrap());
+        let dir = copy.dir.clone();
+        drop(copy);
+        assert!(!dir.exists());
+        assert_eq!(
+            decrypt(&dirnote.join("main.md.age"), &identity).unwrap(),
+            b"# Health\nAllergies\n"
+        );
+    }
+}
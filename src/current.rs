@@ -0,0 +1,207 @@
+use anyhow::{Result, bail};
+use clap::{CommandFactory, Parser, error::ErrorKind};
+use std::{
+    env,
+    ffi::{OsStr, OsString},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::cli::Cli;
+use crate::complete::{Shell, find_option};
+use crate::process::find_note_dir;
+use crate::vault::Note;
+
+/// The note last opened in the shell, exported by the function of `noxe shell-init`.
+pub const CURRENT_NOTE_VAR: &str = "NOXE_CURRENT_NOTE";
+/// The file where noxe writes the note it opens, for the function of `noxe shell-init` to
+/// export it.
+pub const LAST_NOTE_FILE_VAR: &str = "NOXE_LAST_NOTE_FILE";
+
+/// The note the user is working in: the dirnote the current directory is in, else the one of
+/// `NOXE_CURRENT_NOTE`.
+pub fn current_note() -> Option<PathBuf> {
+    env::current_dir()
+        .ok()
+        .and_then(|cwd| enclosing_note(&cwd))
+        .or_else(|| {
+            env::var_os(CURRENT_NOTE_VAR)
+                .map(PathBuf::from)
+                .filter(|note| note.is_filenote() || note.is_dirnote())
+        })
+}
+
+/// The innermost dirnote containing `dir`, or `dir` itself.
+fn enclosing_note(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|dir| dir.is_dirnote())
+        .map(Path::to_path_buf)
+}
+
+/// The note named on the command line, else the current note.
+pub fn note_or_current(note_path: Option<&OsStr>, note_root: &OsStr) -> Result<PathBuf> {
+    match note_path {
+        Some(note_path) => find_note_dir(note_path, note_root),
+        None => match current_note() {
+            Some(note) => Ok(note),
+            None => bail!(
+                "No note given, and not in one: pass a note, run the command inside a dirnote, \
+                or open a note after `eval \"$(noxe shell-init bash)\"`"
+            ),
+        },
+    }
+}
+
+/// Tell the function of `noxe shell-init` that `note` was opened, for it to become the current
+/// note of the shell.
+pub fn record_opened(note: &Path) {
+    let Some(file) = env::var_os(LAST_NOTE_FILE_VAR) else {
+        return;
+    };
+    let note = fs::canonicalize(note).unwrap_or_else(|_| note.to_path_buf());
+    if let Err(e) = fs::write(&file, note.as_os_str().as_encoded_bytes()) {
+        tracing::debug!("Failed to record the opened note: {}", e);
+    }
+}
+
+/// Parse the command line. A command given no note where it needs one, like
+/// `noxe tag add rust` or `noxe meta set status done`, runs on the current note.
+pub fn parse_args() -> Cli {
+    let args: Vec<OsString> = env::args_os().collect();
+    let error = match Cli::try_parse_from(&args) {
+        Ok(cli) => return cli,
+        Err(e) => e,
+    };
+    if matches!(
+        error.kind(),
+        ErrorKind::DisplayHelp
+            | ErrorKind::DisplayVersion
+            | ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand
+    ) {
+        error.exit();
+    }
+
+    note_position(&args)
+        .zip(current_note())
+        .and_then(|(position, note)| {
+            let mut args = args.clone();
+            args.insert(position, note.into_os_string());
+            Cli::try_parse_from(args).ok()
+        })
+        .unwrap_or_else(|| error.exit())
+}
+
+/// Where the note goes in `args` when its command takes one first: right after the name of the
+/// command.
+fn note_position(args: &[OsString]) -> Option<usize> {
+    let mut root = Cli::command();
+    root.build();
+    let mut cmd = &root;
+    let mut position = None;
+
+    let mut words = args.iter().enumerate().skip(1);
+    while let Some((i, word)) = words.next() {
+        let word = word.to_str()?;
+        if word == "--" {
+            break;
+        }
+        if word.starts_with('-') && word.len() > 1 {
+            // The value of an option, given separately
+            if !word.contains('=')
+                && find_option(cmd, word).is_some_and(|arg| arg.get_action().takes_values())
+            {
+                words.next();
+            }
+            continue;
+        }
+        match cmd.find_subcommand(word) {
+            Some(sub) => {
+                cmd = sub;
+                position = Some(i + 1);
+            }
+            None => break,
+        }
+    }
+
+    cmd.get_positionals()
+        .next()
+        .filter(|arg| arg.get_id() == "note_path")?;
+    position
+}
+
+/// The shell function wrapping `noxe`, exporting `NOXE_CURRENT_NOTE` once a note is opened.
+pub fn shell_init(shell: Shell) -> &'static str {
+    match shell {
+        Shell::Bash | Shell::Zsh => POSIX_INIT,
+        Shell::Fish => FISH_INIT,
+    }
+}
+
+const POSIX_INIT: &str = r#"noxe() {
+    local last="${TMPDIR:-/tmp}/noxe-last-note-$$"
+    NOXE_LAST_NOTE_FILE="$last" command noxe "$@"
+    local code=$?
+    if [ -s "$last" ]; then
+        export NOXE_CURRENT_NOTE="$(cat "$last")"
+        rm -f "$last"
+    fi
+    return $code
+}
+"#;
+
+const FISH_INIT: &str = r#"function noxe
+    set -l tmp /tmp
+    set -q TMPDIR; and set tmp $TMPDIR
+    set -l last $tmp/noxe-last-note-$fish_pid
+    NOXE_LAST_NOTE_FILE=$last command noxe $argv
+    set -l code $status
+    if test -s $last
+        set -gx NOXE_CURRENT_NOTE (cat $last)
+        rm -f $last
+    end
+    return $code
+end
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn note_position_of(line: &str) -> Option<usize> {
+        let args: Vec<OsString> = line.split(' ').map(OsString::from).collect();
+        // Building the whole command line takes more stack than the test threads have
+        std::thread::Builder::new()
+            .stack_size(8 << 20)
+            .spawn(move || note_position(&args))
+            .unwrap()
+            .join()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_note_position() {
+        assert_eq!(note_position_of("noxe tag add rust"), Some(3));
+        assert_eq!(
+            note_position_of("noxe --vault work tag add -d notes rust"),
+            Some(5)
+        );
+        assert_eq!(note_position_of("noxe meta set status done"), Some(2));
+        assert_eq!(note_position_of("noxe show"), Some(2));
+        // Commands whose first argument is not a note
+        assert_eq!(note_position_of("noxe tag find rust"), None);
+        assert_eq!(note_position_of("noxe list"), None);
+    }
+
+    #[test]
+    fn test_enclosing_note() {
+        let tmp_dir = tempdir().unwrap();
+        let note = tmp_dir.path().join("lang/rust");
+        fs::create_dir_all(note.join("images")).unwrap();
+        fs::write(note.join("main.md"), "# Rust\n").unwrap();
+
+        assert_eq!(enclosing_note(&note.join("images")), Some(note.clone()));
+        assert_eq!(enclosing_note(&note), Some(note));
+        assert_eq!(enclosing_note(&tmp_dir.path().join("lang")), None);
+    }
+}
@@ -0,0 +1,159 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Local, NaiveDate};
+use std::{
+    fs, io,
+    path::Path,
+    sync::Once,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::archive::cutoff;
+use crate::journal::parse_date;
+
+/// Bounds on the creation and modification dates of notes, from the `--created-after`,
+/// `--created-before`, `--modified-after` and `--modified-before` options. Bounds are
+/// inclusive.
+#[derive(Debug, Default, PartialEq)]
+pub struct DateFilter {
+    pub created_after: Option<NaiveDate>,
+    pub created_before: Option<NaiveDate>,
+    pub modified_after: Option<NaiveDate>,
+    pub modified_before: Option<NaiveDate>,
+}
+
+impl DateFilter {
+    pub fn new(
+        created_after: Option<&str>,
+        created_before: Option<&str>,
+        modified_after: Option<&str>,
+        modified_before: Option<&str>,
+        today: NaiveDate,
+    ) -> Result<Self> {
+        let day = |date: Option<&str>| date.map(|d| parse_day(d, today)).transpose();
+        Ok(DateFilter {
+            created_after: day(created_after)?,
+            created_before: day(created_before)?,
+            modified_after: day(modified_after)?,
+            modified_before: day(modified_before)?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == DateFilter::default()
+    }
+
+    /// Whether the note is within the bounds. Where the file system does not record creation
+    /// times, the modification time stands for it.
+    pub fn matches(&self, note: &Path) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+        let Ok(metadata) = note.metadata() else {
+            return false;
+        };
+        within(
+            Ok(created(&metadata)),
+            self.created_after,
+            self.created_before,
+        ) && within(
+            metadata.modified(),
+            self.modified_after,
+            self.modified_before,
+        )
+    }
+}
+
+/// The creation time of a file, else its modification time on file systems (and kernels) that
+/// do not record creation times, else the epoch. The fallback is reported once.
+pub fn created(metadata: &fs::Metadata) -> SystemTime {
+    created_or_fallback(metadata.created(), metadata.modified())
+}
+
+fn created_or_fallback(
+    created: io::Result<SystemTime>,
+    modified: io::Result<SystemTime>,
+) -> SystemTime {
+    static WARNING: Once = Once::new();
+    created.unwrap_or_else(|_| {
+        WARNING.call_once(|| {
+            eprintln!(
+                "Warning: the file system does not record creation times, modification times are used instead"
+            )
+        });
+        modified.unwrap_or(UNIX_EPOCH)
+    })
+}
+
+fn within(
+    time: io::Result<SystemTime>,
+    after: Option<NaiveDate>,
+    before: Option<NaiveDate>,
+) -> bool {
+    if after.is_none() && before.is_none() {
+        return true;
+    }
+    let Ok(time) = time else {
+        return false;
+    };
+    let date = DateTime::<Local>::from(time).date_naive();
+    after.is_none_or(|a| date >= a) && before.is_none_or(|b| date <= b)
+}
+
+/// Parse a day: `YYYY-MM-DD`, `today` or `yesterday`, or an age before today like `7d`, `2w`,
+/// `6m` or `1y`.
+pub fn parse_day(date: &str, today: NaiveDate) -> Result<NaiveDate> {
+    let date = date.trim();
+    if date.ends_with(['d', 'w', 'm', 'y']) && date.starts_with(|c: char| c.is_ascii_digit()) {
+        return cutoff(date, today);
+    }
+    parse_date(Some(date), 0, today).map_err(|_| {
+        anyhow!(
+            "Invalid date '{}', expected YYYY-MM-DD, today, yesterday or an age like 7d, 2w, 6m or 1y",
+            date
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_date_filter() {
+        let today = NaiveDate::from_ymd_opt(2025, 3, 10).unwrap();
+        let day = |s| parse_day(s, today).unwrap();
+        assert_eq!(day("7d"), NaiveDate::from_ymd_opt(2025, 3, 3).unwrap());
+        assert_eq!(day("2w"), NaiveDate::from_ymd_opt(2025, 2, 24).unwrap());
+        assert_eq!(
+            day("2024-12-31"),
+            NaiveDate::from_ymd_opt(2024, 12, 31).unwrap()
+        );
+        assert_eq!(
+            day("yesterday"),
+            NaiveDate::from_ymd_opt(2025, 3, 9).unwrap()
+        );
+        assert!(parse_day("last week", today).is_err());
+
+        let tmp_dir = tempdir().unwrap();
+        let note = tmp_dir.path().join("a.md");
+        fs::write(&note, "").unwrap();
+        let today = crate::journal::today();
+        let filter = |after, before| DateFilter::new(None, None, after, before, today).unwrap();
+        assert!(filter(None, None).is_empty());
+        assert!(filter(Some("7d"), None).matches(&note));
+        assert!(filter(Some("today"), Some("today")).matches(&note));
+        assert!(!filter(None, Some("1d")).matches(&note));
+        assert!(!filter(Some("today"), None).matches(&tmp_dir.path().join("missing.md")));
+
+        let unsupported = || Err(io::Error::from(io::ErrorKind::Unsupported));
+        let now = SystemTime::now();
+        assert_eq!(created_or_fallback(Ok(UNIX_EPOCH), Ok(now)), UNIX_EPOCH);
+        assert_eq!(created_or_fallback(unsupported(), Ok(now)), now);
+        assert_eq!(
+            created_or_fallback(unsupported(), unsupported()),
+            UNIX_EPOCH
+        );
+    }
+}
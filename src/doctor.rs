@@ -0,0 +1,272 @@
+use anyhow::{Result, bail};
+use clap::ValueEnum;
+use colored::Colorize;
+use std::{
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::cite;
+use crate::config::{self, config_path};
+use crate::process::{in_path, split_command};
+use crate::template::TemplateStore;
+use crate::vault::{Scope, load_note_template};
+
+/// A group of checks of `noxe doctor`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DoctorPass {
+    /// The external programs noxe runs, the note root, the config and the templates
+    Environment,
+    /// Citation keys missing from the bibliographies, duplicate or uncited entries, and dangling
+    /// markdown footnotes
+    Citations,
+}
+
+impl DoctorPass {
+    fn title(self) -> &'static str {
+        match self {
+            DoctorPass::Environment => "Environment",
+            DoctorPass::Citations => "Citations and footnotes",
+        }
+    }
+}
+
+/// Run the passes, all of them when none is given, and print what they find. Fails when
+/// something is found, for use in scripts.
+pub fn doctor(note_root: &Path, passes: &[DoctorPass], scope: Scope) -> Result<()> {
+    let passes = if passes.is_empty() {
+        DoctorPass::value_variants()
+    } else {
+        passes
+    };
+
+    let mut problems = 0;
+    for &pass in passes {
+        let findings: Vec<String> = match pass {
+            DoctorPass::Environment => environment(note_root)
+                .iter()
+                .map(|(problem, fix)| format!("{}\n    {} {}", problem, "fix:".cyan(), fix))
+                .collect(),
+            DoctorPass::Citations => cite::check(note_root, scope)?
+                .iter()
+                .map(|f| f.to_string())
+                .collect(),
+        };
+
+        println!("{}", pass.title().bold());
+        if findings.is_empty() {
+            println!("  {}", "ok".green());
+        }
+        for finding in &findings {
+            println!("  {}", finding);
+        }
+        problems += findings.len();
+    }
+
+    if problems > 0 {
+        bail!("{} problem(s) found", problems);
+    }
+    Ok(())
+}
+
+/// A problem of the environment, and how to fix it.
+type Issue = (String, String);
+
+/// The external programs: the command, from the environment variable set by the config or
+/// the default one, what is lost without it, and how to get it.
+fn programs() -> Vec<(Vec<OsString>, &'static str, &'static str)> {
+    let command = |var: &str, default: &str| match std::env::var(var) {
+        Ok(command) if !command.trim().is_empty() => split_command(&command),
+        _ => split_command(default),
+    };
+    let editor = match std::env::var("NOXE_EDIT").or_else(|_| std::env::var("EDITOR")) {
+        Ok(editor) if !editor.trim().is_empty() => split_command(&editor),
+        _ => vec!["vim".into()],
+    };
+
+    vec![
+        (
+            editor,
+            "notes cannot be edited",
+            "install it, or set `editor` in the config or $EDITOR",
+        ),
+        (
+            command("NOXE_PREVIEW_TYPST", "tinymist preview"),
+            "typst notes cannot be previewed",
+            "install tinymist (https://github.com/Myriad-Dreamin/tinymist), or set `preview.typst` in the config",
+        ),
+        (
+            command("NOXE_PREVIEW_MARKDOWN", "glow"),
+            if std::env::var_os("NOXE_PREVIEW_MARKDOWN").is_some() {
+                "markdown notes cannot be previewed"
+            } else {
+                "markdown notes are previewed with the built-in renderer"
+            },
+            "install glow (https://github.com/charmbracelet/glow), or set `preview.markdown` in the config",
+        ),
+        (
+            vec!["typst".into()],
+            "typst notes cannot be exported",
+            "install typst (https://github.com/typst/typst)",
+        ),
+        (
+            vec!["pandoc".into()],
+            "markdown and org notes cannot be exported",
+            "install pandoc (https://pandoc.org/installing.html)",
+        ),
+        (
+            vec!["git".into()],
+            "`noxe sync` cannot run",
+            "install git (https://git-scm.com/downloads)",
+        ),
+    ]
+}
+
+/// Check the environment noxe runs in.
+fn environment(note_root: &Path) -> Vec<Issue> {
+    let mut issues = Vec::new();
+
+    for (command, loss, fix) in programs() {
+        let Some(program) = command.first() else {
+            continue;
+        };
+        let program = program.to_string_lossy();
+        let found = if program.contains(['/', '\\']) {
+            Path::new(program.as_ref()).is_file()
+        } else {
+            in_path(&program)
+        };
+        if !found {
+            issues.push((
+                format!("'{}' is not installed or not in PATH: {}", program, loss),
+                fix.to_string(),
+            ));
+        }
+    }
+
+    issues.extend(check_note_root(note_root));
+    issues.extend(check_config());
+    issues.extend(check_templates(
+        std::env::var_os("NOXE_TEMPLATE").map(PathBuf::from),
+        std::env::var_os("NOXE_TEMPLATE_DIR").map(PathBuf::from),
+    ));
+    issues
+}
+
+fn check_note_root(note_root: &Path) -> Option<Issue> {
+    let fix = "create it, or set `note_root` in the config, $NOXE_ROOT or -d";
+    if !note_root.exists() {
+        return Some((
+            format!("The note root '{}' does not exist", note_root.display()),
+            fix.to_string(),
+        ));
+    }
+    if !note_root.is_dir() {
+        return Some((
+            format!("The note root '{}' is not a directory", note_root.display()),
+            fix.to_string(),
+        ));
+    }
+
+    let probe = note_root.join(format!(".noxe-doctor-{}", std::process::id()));
+    match fs::write(&probe, "") {
+        Ok(()) => {
+            let _ = fs::remove_file(&probe);
+            None
+        }
+        Err(e) => Some((
+            format!(
+                "The note root '{}' is not writable: {}",
+                note_root.display(),
+                e
+            ),
+            format!("check the permissions of '{}'", note_root.display()),
+        )),
+    }
+}
+
+fn check_config() -> Vec<Issue> {
+    let Some(path) = config_path().filter(|p| p.exists()) else {
+        return Vec::new();
+    };
+    let fix = format!("edit '{}'", path.display());
+    let problems = fs::read_to_string(&path)
+        .map_err(anyhow::Error::from)
+        .and_then(|content| config::diagnose(&content));
+    match problems {
+        Ok(problems) => problems
+            .iter()
+            .map(|p| (format!("Config: {}", p), fix.clone()))
+            .collect(),
+        Err(e) => vec![(
+            format!("The config '{}' is invalid: {}", path.display(), e),
+            fix,
+        )],
+    }
+}
+
+/// The template file of `noxe new` and the templates of the registry must all load.
+fn check_templates(template: Option<PathBuf>, template_dir: Option<PathBuf>) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    if let Some(template) = template
+        && template.is_file()
+        && let Err(e) = load_note_template(template.as_os_str())
+    {
+        issues.push((
+            format!("{:#}", e),
+            "fix the template file, or `template` in the config".to_string(),
+        ));
+    }
+
+    let Ok(store) = TemplateStore::new(template_dir) else {
+        return issues;
+    };
+    match store.names() {
+        Ok(names) => {
+            for name in names {
+                if let Err(e) = store.load(&name) {
+                    issues.push((
+                        format!("{:#}", e),
+                        format!(
+                            "fix it and save it again with `noxe template add {} <file> --force`, or remove it with `noxe template rm {}`",
+                            name, name
+                        ),
+                    ));
+                }
+            }
+        }
+        Err(e) => issues.push((
+            format!("{:#}", e),
+            "check the template directory".to_string(),
+        )),
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_environment() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        assert_eq!(check_note_root(root), None);
+        let (problem, _) = check_note_root(&root.join("missing")).unwrap();
+        assert!(problem.ends_with("missing' does not exist"), "{}", problem);
+
+        let templates = root.join("templates");
+        fs::create_dir(&templates).unwrap();
+        fs::write(templates.join("paper.yaml"), "paths:\n  images: {}\n").unwrap();
+        fs::write(templates.join("broken.yaml"), "paths: [").unwrap();
+        fs::write(root.join("default.yaml"), "main_md: 1: 2\n").unwrap();
+
+        let issues = check_templates(Some(root.join("default.yaml")), Some(templates));
+        assert_eq!(issues.len(), 2, "{:?}", issues);
+        assert!(issues[0].0.contains("default.yaml"), "{}", issues[0].0);
+        assert!(issues[1].0.contains("broken.yaml"), "{}", issues[1].0);
+        assert!(issues[1].1.contains("noxe template rm broken"));
+    }
+}
@@ -0,0 +1,460 @@
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+    time::SystemTime,
+};
+
+use crate::ai::{Client, Usage};
+use crate::atomic;
+use crate::cli::EnrichTask;
+use crate::config::{Config, Price};
+use crate::events::Progress;
+use crate::extract::NoteText;
+use crate::metadata::{ensure_writable, set_field};
+use crate::semantic::{self, EMBEDDINGS, Embedding, Embeddings, note_key};
+use crate::state::StateStore;
+use crate::vault::Note;
+
+pub const CHECKPOINT: &str = "enrich";
+
+/// The state is saved after this many notes, and once more at the end.
+const SAVE_EVERY: usize = 16;
+/// Notes are truncated to this many characters before being sent to the provider.
+const MAX_INPUT_CHARS: usize = 12_000;
+
+const SUMMARY_PROMPT: &str = "Summarize the note in one or two sentences, in the language of the note. \
+Reply with the summary only.";
+const TAGS_PROMPT: &str = "Suggest 3 to 5 short lowercase tags for the note. \
+Reply with the tags only, separated by commas.";
+
+#[derive(Debug)]
+pub struct EnrichOptions {
+    pub provider: String,
+    pub model: Option<String>,
+    pub embedding_model: Option<String>,
+    pub tasks: Vec<EnrichTask>,
+    pub jobs: u16,
+    /// Write the summary and the tags even to notes marking them read-only
+    pub override_lock: bool,
+}
+
+/// The tasks done on each note, so that an interrupted run resumes where it stopped.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Checkpoint {
+    notes: BTreeMap<String, CheckpointEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CheckpointEntry {
+    /// The modification time of the main file once enriched. A later edit invalidates the entry.
+    modified: Option<SystemTime>,
+    tasks: BTreeSet<EnrichTask>,
+}
+
+/// The outcome of an enrichment run.
+#[derive(Debug)]
+pub struct Report {
+    provider: &'static str,
+    enriched: usize,
+    skipped: usize,
+    failed: Vec<(PathBuf, String)>,
+    /// Token usage by model
+    usage: BTreeMap<String, Usage>,
+    prices: BTreeMap<String, Price>,
+}
+
+impl Report {
+    pub fn print(&self) {
+        println!(
+            "Enriched {} note(s), {} already up to date, {} failed",
+            self.enriched,
+            self.skipped,
+            self.failed.len()
+        );
+        for (note, error) in &self.failed {
+            println!("  {}: {}", note.display(), error);
+        }
+
+        if self.usage.is_empty() {
+            return;
+        }
+        println!("{}:", self.provider);
+        for (model, usage) in &self.usage {
+            print!(
+                "  {}: {} prompt + {} completion tokens",
+                model, usage.prompt_tokens, usage.completion_tokens
+            );
+            match self.cost(model) {
+                Some(cost) => println!(", ~${:.4}", cost),
+                None => println!(),
+            }
+        }
+    }
+
+    /// The estimated cost in USD, if the price of the model is configured.
+    fn cost(&self, model: &str) -> Option<f64> {
+        let price = self.prices.get(model)?;
+        let usage = self.usage.get(model)?;
+        Some(
+            (usage.prompt_tokens as f64 * price.input
+                + usage.completion_tokens as f64 * price.output)
+                / 1_000_000.0,
+        )
+    }
+}
+
+/// Generate the missing summaries, tags and embeddings of the notes, with at most
+/// `options.jobs` notes in flight.
+pub fn enrich(note_root: &Path, notes: &[PathBuf], options: &EnrichOptions) -> Result<Report> {
+    let client = Client::new(&options.provider)?;
+    let provider = client.provider();
+
+    let chat_model = options
+        .model
+        .clone()
+        .unwrap_or_else(|| provider.chat_model.to_string());
+    let embedding_model = options
+        .embedding_model
+        .clone()
+        .or_else(|| provider.embedding_model.map(str::to_string));
+    if options.tasks.contains(&EnrichTask::Embedding) && embedding_model.is_none() {
+        bail!(
+            "'{}' has no default embedding model, specify one with --embedding-model",
+            provider.name
+        );
+    }
+
+    let store = StateStore::new(note_root);
+    let checkpoint: Checkpoint = store.load(CHECKPOINT)?;
+    let embeddings: Embeddings = store.load(EMBEDDINGS)?;
+
+    let jobs: Vec<(&PathBuf, String, Vec<EnrichTask>)> = notes
+        .iter()
+        .filter_map(|note| {
+            let key = note_key(note_root, note);
+            let tasks = pending_tasks(
+                note,
+                &key,
+                &options.tasks,
+                &checkpoint,
+                &embeddings,
+                embedding_model.as_deref(),
+            )
+            .ok()?;
+            (!tasks.is_empty()).then_some((note, key, tasks))
+        })
+        .collect();
+
+    let state = Mutex::new((checkpoint, embeddings));
+    let usage = Mutex::new(BTreeMap::<String, Usage>::new());
+    let failed = Mutex::new(Vec::new());
+    let next = AtomicUsize::new(0);
+    let events = Progress::start("enrich", jobs.len());
+
+    let record_usage = |model: &str, u: Usage| {
+        *usage.lock().unwrap().entry(model.to_string()).or_default() += u;
+    };
+    let save = |state: &(Checkpoint, Embeddings)| -> Result<()> {
+        store.save(CHECKPOINT, &state.0)?;
+        store.save(EMBEDDINGS, &state.1)
+    };
+
+    thread::scope(|scope| {
+        for _ in 0..(options.jobs as usize).min(jobs.len()) {
+            scope.spawn(|| {
+                while let Some((note, key, tasks)) = jobs.get(next.fetch_add(1, Ordering::Relaxed))
+                {
+                    let models = Models {
+                        chat: &chat_model,
+                        embedding: embedding_model.as_deref(),
+                    };
+                    events.item_started(key);
+                    let result = enrich_note(
+                        &client,
+                        &models,
+                        note,
+                        tasks,
+                        options.override_lock,
+                        &record_usage,
+                    );
+
+                    let count = events.item_finished(key, result.as_ref().err());
+                    progress(count, jobs.len(), note);
+
+                    let (modified, embedding) = match result {
+                        Ok(result) => result,
+                        Err(e) => {
+                            failed
+                                .lock()
+                                .unwrap()
+                                .push((note.to_path_buf(), format!("{:#}", e)));
+                            continue;
+                        }
+                    };
+
+                    let mut state = state.lock().unwrap();
+                    if let Some(embedding) = embedding {
+                        state.1.insert(key.clone(), embedding);
+                    }
+                    let entry =
+                        state
+                            .0
+                            .notes
+                            .entry(key.clone())
+                            .or_insert_with(|| CheckpointEntry {
+                                modified,
+                                tasks: BTreeSet::new(),
+                            });
+                    entry.modified = modified;
+                    entry.tasks.extend(tasks);
+
+                    if count.is_multiple_of(SAVE_EVERY)
+                        && let Err(e) = save(&state)
+                    {
+                        tracing::warn!("Failed to save the checkpoint: {:#}", e);
+                    }
+                }
+            });
+        }
+    });
+
+    events.finish();
+    if !jobs.is_empty() && io::stderr().is_terminal() {
+        eprintln!();
+    }
+    save(&state.into_inner().unwrap())?;
+
+    let failed = failed.into_inner().unwrap();
+    Ok(Report {
+        provider: provider.name,
+        enriched: jobs.len() - failed.len(),
+        skipped: notes.len() - jobs.len(),
+        failed,
+        usage: usage.into_inner().unwrap(),
+        prices: Config::load().map(|c| c.ai.prices).unwrap_or_default(),
+    })
+}
+
+struct Models<'a> {
+    chat: &'a str,
+    embedding: Option<&'a str>,
+}
+
+/// Run the tasks on a note. Metadata is written back after each task so that finished work
+/// survives a later failure, and fields the note marks read-only fail their task. Returns the
+/// new modification time and the embedding, if computed.
+fn enrich_note(
+    client: &Client,
+    models: &Models,
+    note: &Path,
+    tasks: &[EnrichTask],
+    override_lock: bool,
+    record_usage: &(dyn Fn(&str, Usage) + Sync),
+) -> Result<(Option<SystemTime>, Option<Embedding>)> {
+    let main_file = note.main_file_path()?;
+    let note_type = main_file.note_type()?;
+    let mut content = fs::read_to_string(&main_file)
+        .with_context(|| format!("Failed to read note '{}'", main_file.display()))?;
+
+    let text = semantic::embedding_text(&content, note_type);
+    let input: String = text.chars().take(MAX_INPUT_CHARS).collect();
+
+    let mut embedding = None;
+    for task in tasks {
+        match task {
+            EnrichTask::Summary => {
+                // Checked before the request, which costs
+                ensure_writable(&content, note_type, "summary", override_lock)?;
+                let (summary, usage) = client.chat(models.chat, SUMMARY_PROMPT, &input)?;
+                record_usage(models.chat, usage);
+                content = set_field(&content, note_type, "summary", &Value::String(summary))?;
+            }
+            EnrichTask::Tags => {
+                ensure_writable(&content, note_type, "keywords", override_lock)?;
+                let (reply, usage) = client.chat(models.chat, TAGS_PROMPT, &input)?;
+                record_usage(models.chat, usage);
+                let tags = parse_tags(&reply);
+                if tags.is_empty() {
+                    continue;
+                }
+                let tags = Value::Array(tags.into_iter().map(Value::String).collect());
+                content = set_field(&content, note_type, "keywords", &tags)?;
+            }
+            EnrichTask::Embedding => {
+                // Checked by `enrich` before any note is processed
+                let model = models.embedding.unwrap_or_default();
+                let (chunks, usage) = semantic::embed(client, model, &text)?;
+                record_usage(model, usage);
+                embedding = Some(Embedding {
+                    model: model.to_string(),
+                    modified: None,
+                    chunks,
+                });
+                continue;
+            }
+        }
+
+        atomic::write(&main_file, &content)?;
+    }
+
+    // The other tasks may have edited the note after it was embedded
+    let modified = fs::metadata(&main_file).and_then(|m| m.modified()).ok();
+    if let Some(embedding) = &mut embedding {
+        embedding.modified = modified;
+    }
+    Ok((modified, embedding))
+}
+
+/// The tasks still to be done on a note: those not checkpointed since its last edit whose
+/// result is missing.
+fn pending_tasks(
+    note: &Path,
+    key: &str,
+    tasks: &[EnrichTask],
+    checkpoint: &Checkpoint,
+    embeddings: &Embeddings,
+    embedding_model: Option<&str>,
+) -> Result<Vec<EnrichTask>> {
+    let main_file = note.main_file_path()?;
+    let metadata = NoteText::read(&main_file)?.metadata();
+    let modified = fs::metadata(&main_file)?.modified().ok();
+
+    let done = checkpoint
+        .notes
+        .get(key)
+        .filter(|entry| entry.modified == modified);
+
+    Ok(tasks
+        .iter()
+        .copied()
+        .filter(|task| !done.is_some_and(|entry| entry.tasks.contains(task)))
+        .filter(|task| match task {
+            EnrichTask::Summary => metadata.get("summary").is_none(),
+            EnrichTask::Tags => metadata.keywords.is_empty(),
+            EnrichTask::Embedding => !embeddings
+                .get(key)
+                .is_some_and(|e| e.is_fresh(embedding_model.unwrap_or_default(), modified)),
+        })
+        .collect())
+}
+
+/// Parse a reply like `rust, #Async IO, "cli"` into tags.
+fn parse_tags(reply: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in reply.split([',', '\n']) {
+        let tag = tag
+            .trim()
+            .trim_start_matches(['-', '*', '#'])
+            .trim_matches(['"', '\'', '`', '.'])
+            .trim()
+            .to_lowercase();
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags.truncate(5);
+    tags
+}
+
+fn progress(done: usize, total: usize, note: &Path) {
+    if !io::stderr().is_terminal() {
+        return;
+    }
+    eprint!("\r\x1b[2K[{}/{}] {}", done, total, note.display());
+    let _ = io::stderr().flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_tags() {
+        assert_eq!(
+            parse_tags("Rust, #async io, \"cli\"\n- rust, tools., a, b, c"),
+            ["rust", "async io", "cli", "tools", "a"]
+        );
+        assert!(parse_tags("  ").is_empty());
+    }
+
+    #[test]
+    fn test_pending_tasks() {
+        let tmp_dir = tempdir().unwrap();
+        let note = tmp_dir.path().join("a.md");
+        fs::write(&note, "---\nsummary: \"done\"\n---\nBody\n").unwrap();
+
+        let all = [EnrichTask::Summary, EnrichTask::Tags, EnrichTask::Embedding];
+        let mut checkpoint = Checkpoint::default();
+        let mut embeddings = Embeddings::new();
+
+        let pending = |c: &Checkpoint, e: &Embeddings| {
+            pending_tasks(&note, "a.md", &all, c, e, Some("m")).unwrap()
+        };
+        assert_eq!(
+            pending(&checkpoint, &embeddings),
+            [EnrichTask::Tags, EnrichTask::Embedding]
+        );
+
+        embeddings.insert(
+            "a.md".into(),
+            Embedding {
+                model: "m".into(),
+                modified: fs::metadata(&note).unwrap().modified().ok(),
+                chunks: vec![vec![0.0]],
+            },
+        );
+        assert_eq!(pending(&checkpoint, &embeddings), [EnrichTask::Tags]);
+
+        // The model returned no tags for this version of the note
+        let modified = fs::metadata(&note).unwrap().modified().ok();
+        checkpoint.notes.insert(
+            "a.md".into(),
+            CheckpointEntry {
+                modified,
+                tasks: BTreeSet::from([EnrichTask::Tags]),
+            },
+        );
+        assert!(pending(&checkpoint, &embeddings).is_empty());
+
+        // An edit invalidates the checkpoint
+        checkpoint.notes.get_mut("a.md").unwrap().modified = Some(SystemTime::UNIX_EPOCH);
+        assert_eq!(pending(&checkpoint, &embeddings), [EnrichTask::Tags]);
+    }
+
+    #[test]
+    fn test_report_cost() {
+        let report = Report {
+            provider: "openai",
+            enriched: 1,
+            skipped: 0,
+            failed: Vec::new(),
+            usage: BTreeMap::from([(
+                "m".to_string(),
+                Usage {
+                    prompt_tokens: 1_000_000,
+                    completion_tokens: 500_000,
+                },
+            )]),
+            prices: BTreeMap::from([(
+                "m".to_string(),
+                Price {
+                    input: 0.15,
+                    output: 0.6,
+                },
+            )]),
+        };
+        assert_eq!(report.cost("m"), Some(0.45));
+        assert_eq!(report.cost("other"), None);
+    }
+}
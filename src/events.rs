@@ -0,0 +1,183 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::{self, Write},
+    path::Path,
+    sync::{
+        Mutex, OnceLock,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use crate::cli::EventFormat;
+
+/// Bumped on incompatible changes to the events, so that consumers can check what they read.
+pub const EVENTS_VERSION: u32 = 1;
+
+static SINK: OnceLock<Mutex<Box<dyn Write + Send>>> = OnceLock::new();
+
+/// Send the events of long operations to `file`, or to stderr. Without it, events are dropped.
+pub fn init(format: EventFormat, file: Option<&Path>) -> Result<()> {
+    // NDJSON is the only format so far
+    let EventFormat::Ndjson = format;
+    let sink: Box<dyn Write + Send> = match file {
+        Some(path) => Box::new(
+            File::create(path)
+                .with_context(|| format!("Failed to create event file '{}'", path.display()))?,
+        ),
+        None => Box::new(io::stderr()),
+    };
+    let _ = SINK.set(Mutex::new(sink));
+    Ok(())
+}
+
+/// An event of a long operation, written as one JSON object per line with the kind of event
+/// in `event`, e.g.
+///
+/// ```json
+/// {"v":1,"time":"2025-01-01T10:00:00+08:00","event":"item_finished","operation":"export","item":"cat/a.typ","done":1,"total":3}
+/// ```
+///
+/// Items are note paths relative to the note root.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    Started {
+        operation: &'a str,
+        total: usize,
+    },
+    ItemStarted {
+        operation: &'a str,
+        item: &'a str,
+    },
+    ItemFinished {
+        operation: &'a str,
+        item: &'a str,
+        done: usize,
+        total: usize,
+        /// Present when the item failed
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    Finished {
+        operation: &'a str,
+        succeeded: usize,
+        failed: usize,
+    },
+}
+
+#[derive(Serialize)]
+struct Line<'a> {
+    v: u32,
+    time: String,
+    #[serde(flatten)]
+    event: &'a Event<'a>,
+}
+
+fn line(event: &Event, time: String) -> String {
+    serde_json::to_string(&Line {
+        v: EVENTS_VERSION,
+        time,
+        event,
+    })
+    .expect("events always serialize")
+}
+
+pub fn emit(event: &Event) {
+    let Some(sink) = SINK.get() else {
+        return;
+    };
+
+    let line = line(event, chrono::Local::now().to_rfc3339());
+    let mut sink = sink.lock().unwrap();
+    // A consumer going away must not stop the operation
+    let _ = writeln!(sink, "{}", line).and_then(|_| sink.flush());
+}
+
+/// Emits the events of an operation over a known number of items. Items may be processed
+/// from several threads.
+#[derive(Debug)]
+pub struct Progress {
+    operation: &'static str,
+    total: usize,
+    done: AtomicUsize,
+    failed: AtomicUsize,
+}
+
+impl Progress {
+    pub fn start(operation: &'static str, total: usize) -> Self {
+        emit(&Event::Started { operation, total });
+        Progress {
+            operation,
+            total,
+            done: AtomicUsize::new(0),
+            failed: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn item_started(&self, item: &str) {
+        emit(&Event::ItemStarted {
+            operation: self.operation,
+            item,
+        });
+    }
+
+    /// Record the end of an item. Returns the number of items done so far.
+    pub fn item_finished(&self, item: &str, error: Option<&anyhow::Error>) -> usize {
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        if error.is_some() {
+            self.failed.fetch_add(1, Ordering::Relaxed);
+        }
+        emit(&Event::ItemFinished {
+            operation: self.operation,
+            item,
+            done,
+            total: self.total,
+            error: error.map(|e| format!("{:#}", e)),
+        });
+        done
+    }
+
+    pub fn finish(self) {
+        let failed = self.failed.into_inner();
+        emit(&Event::Finished {
+            operation: self.operation,
+            succeeded: self.done.into_inner() - failed,
+            failed,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line() {
+        let time = "2025-01-01T10:00:00+08:00".to_string();
+        assert_eq!(
+            line(
+                &Event::Started {
+                    operation: "export",
+                    total: 2
+                },
+                time.clone()
+            ),
+            r#"{"v":1,"time":"2025-01-01T10:00:00+08:00","event":"started","operation":"export","total":2}"#
+        );
+
+        let finished = |error| Event::ItemFinished {
+            operation: "enrich",
+            item: "cat/a.md",
+            done: 1,
+            total: 2,
+            error,
+        };
+        assert!(!line(&finished(None), time.clone()).contains("error"));
+        assert!(
+            line(&finished(Some("timeout".to_string())), time)
+                .ends_with(r#""done":1,"total":2,"error":"timeout"}"#)
+        );
+    }
+}
@@ -0,0 +1,340 @@
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use std::{
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+};
+
+use crate::cite::{self, BibScope};
+use crate::cli::NoteType;
+use crate::events::Progress;
+use crate::metadata::read_metadata;
+use crate::semantic::note_key;
+use crate::vault::Note;
+
+/// The formats typst can compile to; pandoc accepts any format it knows.
+const TYPST_FORMATS: [&str; 4] = ["pdf", "png", "svg", "html"];
+
+/// Export directives of a note, from the `export` field of its metadata, e.g.
+/// `export: {format: pdf, template: ieee, toc: true}`.
+///
+/// Pandoc gets them as `--template` and `--toc`. Typst has no such options, so they are
+/// passed as `sys.inputs.template` and `sys.inputs.toc` for the note to act on.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExportSettings {
+    /// The output format, pdf by default
+    pub format: Option<String>,
+    pub template: Option<String>,
+    pub toc: bool,
+}
+
+impl ExportSettings {
+    /// Read the export settings of a note's main file.
+    pub fn read(main_file: &Path) -> Result<Self> {
+        match read_metadata(main_file)?.fields.remove("export") {
+            None => Ok(Self::default()),
+            Some(value) => serde_json::from_value(value)
+                .with_context(|| format!("Invalid export settings in '{}'", main_file.display())),
+        }
+    }
+
+    pub fn format(&self) -> &str {
+        self.format.as_deref().unwrap_or("pdf")
+    }
+
+    /// The output format, checked to be one typst can compile to.
+    pub fn typst_format(&self) -> Result<&str> {
+        let format = self.format();
+        if !TYPST_FORMATS.contains(&format) {
+            bail!(
+                "typst cannot export to '{}' (expected one of: {})",
+                format,
+                TYPST_FORMATS.join(", ")
+            );
+        }
+        Ok(format)
+    }
+
+    /// The `--input` arguments of `typst compile`.
+    pub fn typst_inputs(&self) -> Vec<OsString> {
+        let mut args = Vec::new();
+        if let Some(template) = &self.template {
+            args.extend(["--input".into(), format!("template={}", template).into()]);
+        }
+        if self.toc {
+            args.extend(["--input".into(), "toc=true".into()]);
+        }
+        args
+    }
+}
+
+/// Export the notes under `output_dir`, mirroring their paths relative to the note root, in
+/// the format of their export settings. A failing note does not stop the others; the failures
+/// are reported at the end.
+///
+/// Each note is exported with the bibliographies of the first scope of `bibliography_order`
+/// that has any, so that notes without their own use the shared `bibliography/` of the vault.
+pub fn export(
+    note_root: &Path,
+    notes: &[PathBuf],
+    output_dir: &Path,
+    bibliography_order: &[BibScope],
+) -> Result<Vec<PathBuf>> {
+    let mut exported = Vec::new();
+    let mut failed = 0;
+    let events = Progress::start("export", notes.len());
+
+    for note in notes {
+        let key = note_key(note_root, note);
+        events.item_started(&key);
+        let result = export_note(note_root, note, output_dir, bibliography_order);
+        events.item_finished(&key, result.as_ref().err());
+
+        match result {
+            Ok(output) => {
+                println!("{} -> {}", note.display(), output.display());
+                exported.push(output);
+            }
+            Err(e) => {
+                eprintln!("Failed to export '{}': {:#}", note.display(), e);
+                failed += 1;
+            }
+        }
+    }
+    events.finish();
+
+    if failed > 0 {
+        bail!("{} of {} note(s) failed to export", failed, notes.len());
+    }
+
+    Ok(exported)
+}
+
+fn export_note(
+    note_root: &Path,
+    note: &Path,
+    output_dir: &Path,
+    bibliography_order: &[BibScope],
+) -> Result<PathBuf> {
+    let settings = ExportSettings::read(&note.main_file_path()?)?;
+    let bibliography = cite::bib_files(note_root, note, bibliography_order)?
+        .map(|(_, files)| files)
+        .unwrap_or_default();
+    let output = output_path(note_root, note, output_dir, settings.format());
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory '{}'", parent.display()))?;
+    }
+
+    let mut cmd = export_command(note_root, note, &output, &settings, &bibliography)?;
+    let program = cmd.get_program().to_string_lossy().to_string();
+    let status = match cmd.status() {
+        Ok(status) => status,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            bail!("'{}' is not installed or not in PATH", program)
+        }
+        Err(e) => return Err(e).with_context(|| format!("Failed to run '{}'", program)),
+    };
+    if !status.success() {
+        bail!("'{}' exited with {}", program, status);
+    }
+
+    Ok(output)
+}
+
+/// `<output_dir>/<category>/<note name>.<format>`
+pub(crate) fn output_path(
+    note_root: &Path,
+    note: &Path,
+    output_dir: &Path,
+    format: &str,
+) -> PathBuf {
+    let rel_path = note.strip_prefix(note_root).unwrap_or(note);
+    let name = format!("{}.{}", note.note_name(), format);
+
+    match rel_path.parent() {
+        Some(category) => output_dir.join(category).join(name),
+        None => output_dir.join(name),
+    }
+}
+
+/// `typst compile` for Typst notes, `pandoc` for Markdown notes.
+///
+/// Pandoc processes the citations with the `bibliography` files. Typst notes name their
+/// bibliography themselves, so the files are passed as `sys.inputs.bibliography`, separated by
+/// commas and rooted at the note root, e.g.
+/// `#bibliography(sys.inputs.at("bibliography", default: "refs.bib").split(","))`.
+fn export_command(
+    note_root: &Path,
+    note: &Path,
+    output: &Path,
+    settings: &ExportSettings,
+    bibliography: &[PathBuf],
+) -> Result<process::Command> {
+    let main_file = note.main_file_path()?;
+    // Relative assets (images, bibliography) are resolved from the note's own directory.
+    let note_dir = main_file.parent().unwrap_or(Path::new("."));
+
+    let cmd = match main_file.note_type()? {
+        NoteType::Typ => {
+            let format = settings.typst_format()?;
+
+            let mut cmd = process::Command::new("typst");
+            // Allow notes to import shared files from anywhere in the vault.
+            cmd.arg("compile").arg("--root").arg(note_root);
+            if format == "html" {
+                cmd.arg("--features").arg("html");
+            }
+            cmd.args(settings.typst_inputs());
+            if !bibliography.is_empty() {
+                let files: Vec<String> = bibliography
+                    .iter()
+                    .map(|f| {
+                        let rel = f.strip_prefix(note_root).unwrap_or(f);
+                        format!("/{}", rel.display())
+                    })
+                    .collect();
+                cmd.arg("--input")
+                    .arg(format!("bibliography={}", files.join(",")));
+            }
+            cmd.arg(&main_file).arg(output);
+            cmd
+        }
+        NoteType::Md | NoteType::Org => {
+            let mut cmd = process::Command::new("pandoc");
+            cmd.arg(&main_file).arg("--resource-path").arg(note_dir);
+            if let Some(template) = &settings.template {
+                cmd.arg("--template").arg(template);
+            }
+            if settings.toc {
+                cmd.arg("--toc");
+            }
+            if !bibliography.is_empty() {
+                cmd.arg("--citeproc");
+                for file in bibliography {
+                    cmd.arg("--bibliography").arg(file);
+                }
+            }
+            cmd.arg("-o").arg(output);
+            cmd
+        }
+        NoteType::Custom(ext) => bail!("'{}' notes cannot be exported", ext),
+    };
+
+    Ok(cmd)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_export_command() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        let dirnote = root.join("cat").join("dirnote");
+        fs::create_dir_all(&dirnote).unwrap();
+        fs::write(dirnote.join("main.typ"), "= Dir\n").unwrap();
+        fs::write(root.join("file.md"), "# File\n").unwrap();
+
+        let out = root.join("export");
+        assert_eq!(
+            output_path(root, &dirnote, &out, "pdf"),
+            out.join("cat").join("dirnote.pdf")
+        );
+        assert_eq!(
+            output_path(root, &root.join("file.md"), &out, "docx"),
+            out.join("file.docx")
+        );
+
+        let default = ExportSettings::default();
+        let cmd = export_command(root, &dirnote, &out.join("a.pdf"), &default, &[]).unwrap();
+        assert_eq!(cmd.get_program(), "typst");
+        let main_file = dirnote.join("main.typ");
+        let output = out.join("a.pdf");
+        assert_eq!(
+            cmd.get_args().collect::<Vec<_>>(),
+            [
+                "compile".as_ref(),
+                "--root".as_ref(),
+                root.as_os_str(),
+                main_file.as_os_str(),
+                output.as_os_str()
+            ]
+        );
+
+        let cmd = export_command(
+            root,
+            &root.join("file.md"),
+            &out.join("b.pdf"),
+            &default,
+            &[],
+        )
+        .unwrap();
+        assert_eq!(cmd.get_program(), "pandoc");
+        assert_eq!(
+            cmd.get_args().last().unwrap(),
+            out.join("b.pdf").as_os_str()
+        );
+
+        let shared = root.join("bibliography").join("refs.bib");
+        let bibliography = std::slice::from_ref(&shared);
+        let cmd =
+            export_command(root, &root.join("file.md"), &output, &default, bibliography).unwrap();
+        let args: Vec<_> = cmd.get_args().collect();
+        assert!(args.contains(&"--citeproc".as_ref()));
+        assert!(
+            args.windows(2)
+                .any(|w| w == ["--bibliography".as_ref(), shared.as_os_str()])
+        );
+        let cmd = export_command(root, &dirnote, &output, &default, bibliography).unwrap();
+        let args: Vec<_> = cmd.get_args().collect();
+        assert!(
+            args.windows(2)
+                .any(|w| w == ["--input", "bibliography=/bibliography/refs.bib"])
+        );
+    }
+
+    #[test]
+    fn test_export_settings() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        let md = root.join("paper.md");
+        fs::write(
+            &md,
+            "---\nexport:\n  format: html\n  template: ieee\n  toc: true\n---\n# Paper\n",
+        )
+        .unwrap();
+        let typ = root.join("post.typ");
+        fs::write(
+            &typ,
+            "#metadata((export: (format: \"svg\", toc: true)))\n= Post\n",
+        )
+        .unwrap();
+
+        let settings = ExportSettings::read(&md).unwrap();
+        assert_eq!(settings.format(), "html");
+        let cmd = export_command(root, &md, &root.join("paper.html"), &settings, &[]).unwrap();
+        let args: Vec<_> = cmd.get_args().collect();
+        assert!(args.windows(2).any(|w| w == ["--template", "ieee"]));
+        assert!(args.contains(&"--toc".as_ref()));
+
+        let settings = ExportSettings::read(&typ).unwrap();
+        assert_eq!(settings.format(), "svg");
+        assert_eq!(settings.typst_inputs(), ["--input", "toc=true"]);
+
+        let settings = ExportSettings {
+            format: Some("docx".into()),
+            ..Default::default()
+        };
+        assert!(settings.typst_format().is_err());
+        assert!(export_command(root, &typ, &root.join("post.docx"), &settings, &[]).is_err());
+
+        fs::write(&md, "---\nexport:\n  tco: true\n---\n").unwrap();
+        assert!(ExportSettings::read(&md).is_err());
+    }
+}
@@ -0,0 +1,124 @@
+use anyhow::{Context, Result, bail};
+use std::{
+    collections::BTreeSet,
+    ffi::OsString,
+    io,
+    path::{Path, PathBuf},
+    process,
+};
+
+use crate::config::{self, config_path};
+
+/// External subcommands are the executables named `noxe-<command>` in `PATH`, like git's.
+const PREFIX: &str = "noxe-";
+
+/// The names of the external subcommands found in `PATH`, sorted.
+pub fn commands() -> Vec<String> {
+    let Some(paths) = std::env::var_os("PATH") else {
+        return Vec::new();
+    };
+    let mut names = BTreeSet::new();
+    for dir in std::env::split_paths(&paths) {
+        let Ok(entries) = dir.read_dir() else {
+            continue;
+        };
+        for entry in entries.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            if let Some(name) = command_name(&path)
+                && is_executable(&path)
+            {
+                names.insert(name);
+            }
+        }
+    }
+    names.into_iter().collect()
+}
+
+fn command_name(path: &Path) -> Option<String> {
+    let file_name = if cfg!(windows) {
+        path.file_stem()?
+    } else {
+        path.file_name()?
+    };
+    let name = file_name.to_str()?.strip_prefix(PREFIX)?;
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file() && path.extension().is_some_and(|ext| ext == "exe")
+}
+
+/// Run the external subcommand `noxe-<args[0]>` with the rest of the arguments. The settings
+/// resolved from the config and the selected vault reach it as the usual `NOXE_*` variables,
+/// along with the absolute `NOXE_ROOT`, `NOXE_VAULT`, `NOXE_CONFIG` and `NOXE_BIN`, the noxe
+/// executable to call back. A failing command makes noxe exit with its exit code.
+pub fn run(args: &[OsString]) -> Result<()> {
+    let Some((name, args)) = args.split_first() else {
+        bail!("No command given");
+    };
+    let program = format!("{}{}", PREFIX, name.to_string_lossy());
+
+    let mut cmd = process::Command::new(&program);
+    cmd.args(args).envs(settings());
+    let status = match cmd.status() {
+        Ok(status) => status,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            bail!(
+                "Unknown command '{}', and no '{}' in PATH",
+                name.to_string_lossy(),
+                program
+            )
+        }
+        Err(e) => return Err(e).with_context(|| format!("Failed to run '{}'", program)),
+    };
+    if !status.success() {
+        process::exit(status.code().unwrap_or(1));
+    }
+    Ok(())
+}
+
+/// The settings passed to external subcommands, besides those already in the environment.
+fn settings() -> Vec<(&'static str, OsString)> {
+    let note_root = std::env::var_os("NOXE_ROOT").unwrap_or_else(|| ".".into());
+    let note_root = PathBuf::from(&note_root)
+        .canonicalize()
+        .map_or(note_root, PathBuf::into_os_string);
+
+    let mut settings = vec![("NOXE_ROOT", note_root)];
+    if let (Some(vault), _) = config::selected_vault(std::env::args_os()) {
+        settings.push(("NOXE_VAULT", vault.into()));
+    }
+    if let Some(path) = config_path() {
+        settings.push(("NOXE_CONFIG", path.into()));
+    }
+    if let Ok(exe) = std::env::current_exe() {
+        settings.push(("NOXE_BIN", exe.into()));
+    }
+    settings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_name() {
+        let name = |p: &str| command_name(Path::new(p));
+        if cfg!(windows) {
+            assert_eq!(name("C:\\bin\\noxe-stats.exe").as_deref(), Some("stats"));
+        } else {
+            assert_eq!(name("/usr/bin/noxe-stats").as_deref(), Some("stats"));
+        }
+        assert_eq!(name("/usr/bin/noxe-"), None);
+        assert_eq!(name("/usr/bin/noxe"), None);
+        assert_eq!(name("/usr/bin/git-noxe-x"), None);
+    }
+}
@@ -0,0 +1,517 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::{fs, path::Path, sync::LazyLock};
+
+use crate::bounded::read_head;
+use crate::cli::NoteType;
+use crate::links::wiki_links;
+use crate::metadata::{
+    NoteMetadata, blank_typst_metadata, parse_frontmatter, parse_org_keywords,
+    parse_typst_document, parse_typst_metadata, split_frontmatter, split_org_keywords,
+    strip_typst_metadata,
+};
+use crate::vault::Note;
+
+/// Typst calls whose line is code rather than prose.
+const TYPST_STATEMENTS: [&str; 5] = ["set", "show", "let", "import", "include"];
+
+/// `[text](target)` and `[text](<target> "title")`
+static MARKDOWN_LINK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"\]\(\s*(?:<([^>]+)>|([^)\s]+))"#).unwrap());
+
+/// `![alt](path)` and `![alt](<path with spaces>)`
+static MARKDOWN_IMAGE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"!\[[^\]]*\]\(\s*(?:<([^>]+)>|([^)\s]+))"#).unwrap());
+
+/// `image("path")`
+static TYPST_IMAGE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"\bimage\(\s*"([^"]+)""#).unwrap());
+
+/// `[[file:target]]` and `[[file:target][description]]`
+static ORG_FILE_LINK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\[\[file:([^\]]+)\]").unwrap());
+
+/// What noxe reads out of the content of a note: its metadata, text, headings, links and tags.
+/// Each note type has its own extractor, see [`extractor`], so that search, the index, stats
+/// and AI features do not need to know about note types.
+pub trait Extract: Sync {
+    /// The metadata of the note.
+    fn metadata(&self, content: &str) -> NoteMetadata;
+
+    /// The content without its metadata.
+    fn body(&self, content: &str) -> String;
+
+    /// The content with its metadata replaced by empty lines, so that the lines of the body
+    /// keep their line numbers in the file.
+    fn blank_metadata(&self, content: &str) -> String {
+        // The metadata of most types is a leading block
+        let body = self.body(content);
+        let offset = content.lines().count().saturating_sub(body.lines().count());
+        "\n".repeat(offset) + &body
+    }
+
+    /// The prose of the body: code, markup and link targets are left out.
+    fn plain_text(&self, content: &str) -> String;
+
+    /// The line index and level of each heading of the lines of a note, skipping code blocks.
+    fn headings(&self, lines: &[&str]) -> Vec<(usize, usize)>;
+
+    /// The relative path targets of the links of the note, e.g. `[text](other.md)`. URLs,
+    /// absolute paths and anchors are left out, and fragments are dropped.
+    fn path_links(&self, _content: &str) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// The relative paths of the images of the note, left out like those of
+    /// [`Extract::path_links`].
+    fn images(&self, _content: &str) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// The targets of the `[[wiki links]]` of the note.
+    fn wiki_links(&self, content: &str) -> Vec<String> {
+        wiki_links(content)
+    }
+
+    /// The tags of the note, the keywords of its metadata.
+    fn tags(&self, content: &str) -> Vec<String> {
+        self.metadata(content).keywords
+    }
+}
+
+/// The extractor of a note type.
+pub fn extractor(note_type: NoteType) -> &'static dyn Extract {
+    match note_type {
+        NoteType::Md => &Markdown,
+        NoteType::Typ => &Typst,
+        NoteType::Org => &Org,
+        NoteType::Custom(_) => &Plain,
+    }
+}
+
+/// The content of the main file of a note, read with the extractor of its type.
+pub struct NoteText {
+    pub content: String,
+    /// The size of the file, when only its head was read
+    pub truncated: Option<u64>,
+    extractor: &'static dyn Extract,
+}
+
+impl NoteText {
+    pub fn read(main_file: &Path) -> Result<Self> {
+        let content = fs::read_to_string(main_file)
+            .with_context(|| format!("Failed to read note '{}'", main_file.display()))?;
+        Ok(NoteText {
+            content,
+            truncated: None,
+            extractor: extractor(main_file.note_type()?),
+        })
+    }
+
+    /// Read the first `limit` bytes of the note only, for huge notes.
+    pub fn read_head(main_file: &Path, limit: u64) -> Result<Self> {
+        let head = read_head(main_file, limit)?;
+        Ok(NoteText {
+            content: head.text,
+            truncated: head.truncated,
+            extractor: extractor(main_file.note_type()?),
+        })
+    }
+
+    pub fn metadata(&self) -> NoteMetadata {
+        self.extractor.metadata(&self.content)
+    }
+
+    pub fn body(&self) -> String {
+        self.extractor.body(&self.content)
+    }
+
+    pub fn plain_text(&self) -> String {
+        self.extractor.plain_text(&self.content)
+    }
+
+    pub fn path_links(&self) -> Vec<String> {
+        self.extractor.path_links(&self.content)
+    }
+}
+
+/// Markdown notes, with YAML front matter.
+struct Markdown;
+
+impl Extract for Markdown {
+    fn metadata(&self, content: &str) -> NoteMetadata {
+        NoteMetadata::from_fields(parse_frontmatter(content))
+    }
+
+    fn body(&self, content: &str) -> String {
+        split_frontmatter(content)
+            .map_or(content, |(_, body)| body)
+            .to_string()
+    }
+
+    fn plain_text(&self, content: &str) -> String {
+        markdown_prose(&self.body(content))
+    }
+
+    fn headings(&self, lines: &[&str]) -> Vec<(usize, usize)> {
+        marked_headings(lines, '#')
+    }
+
+    fn path_links(&self, content: &str) -> Vec<String> {
+        relative_targets(
+            MARKDOWN_LINK
+                .captures_iter(content)
+                .filter_map(|c| c.get(1).or(c.get(2)))
+                .map(|m| m.as_str()),
+        )
+    }
+
+    fn images(&self, content: &str) -> Vec<String> {
+        relative_targets(
+            MARKDOWN_IMAGE
+                .captures_iter(content)
+                .filter_map(|c| c.get(1).or(c.get(2)))
+                .map(|m| m.as_str()),
+        )
+    }
+}
+
+/// Typst notes, with `#set document(...)` and `#metadata(...)` metadata.
+struct Typst;
+
+impl Extract for Typst {
+    fn metadata(&self, content: &str) -> NoteMetadata {
+        let mut fields = parse_typst_document(content);
+        fields.extend(parse_typst_metadata(content));
+        NoteMetadata::from_fields(fields)
+    }
+
+    fn body(&self, content: &str) -> String {
+        strip_typst_metadata(content)
+    }
+
+    fn blank_metadata(&self, content: &str) -> String {
+        blank_typst_metadata(content)
+    }
+
+    fn plain_text(&self, content: &str) -> String {
+        typst_prose(&self.body(content))
+    }
+
+    fn headings(&self, lines: &[&str]) -> Vec<(usize, usize)> {
+        marked_headings(lines, '=')
+    }
+
+    fn images(&self, content: &str) -> Vec<String> {
+        relative_targets(
+            TYPST_IMAGE
+                .captures_iter(content)
+                .map(|c| c.get(1).unwrap().as_str()),
+        )
+    }
+}
+
+/// Org notes, with `#+KEY: value` metadata lines.
+struct Org;
+
+impl Extract for Org {
+    fn metadata(&self, content: &str) -> NoteMetadata {
+        NoteMetadata::from_fields(parse_org_keywords(content))
+    }
+
+    fn body(&self, content: &str) -> String {
+        split_org_keywords(content).1.to_string()
+    }
+
+    fn plain_text(&self, content: &str) -> String {
+        org_prose(&self.body(content))
+    }
+
+    fn headings(&self, lines: &[&str]) -> Vec<(usize, usize)> {
+        marked_headings(lines, '*')
+    }
+
+    fn path_links(&self, content: &str) -> Vec<String> {
+        relative_targets(
+            ORG_FILE_LINK
+                .captures_iter(content)
+                .map(|c| c.get(1).unwrap().as_str()),
+        )
+    }
+}
+
+/// Notes of the types of the config: plain text, without metadata or known headings.
+struct Plain;
+
+impl Extract for Plain {
+    fn metadata(&self, _content: &str) -> NoteMetadata {
+        NoteMetadata::default()
+    }
+
+    fn body(&self, content: &str) -> String {
+        content.to_string()
+    }
+
+    fn plain_text(&self, content: &str) -> String {
+        content.to_string()
+    }
+
+    fn headings(&self, _lines: &[&str]) -> Vec<(usize, usize)> {
+        Vec::new()
+    }
+}
+
+/// The text of a heading line, without its marker.
+pub(crate) fn heading_text(line: &str) -> &str {
+    line.trim_start_matches(['#', '=', '*']).trim()
+}
+
+/// The headings of lines starting with one or more `marker` and a space, skipping fenced code
+/// blocks.
+fn marked_headings(lines: &[&str], marker: char) -> Vec<(usize, usize)> {
+    let mut headings = Vec::new();
+    let mut in_fence = false;
+    for (i, line) in lines.iter().enumerate() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+
+        let level = line.chars().take_while(|&c| c == marker).count();
+        if level > 0 && line[level..].starts_with(' ') {
+            headings.push((i, level));
+        }
+    }
+
+    headings
+}
+
+/// The relative paths of link targets: URLs, absolute paths and anchors are left out, and
+/// fragments are dropped.
+fn relative_targets<'a>(targets: impl Iterator<Item = &'a str>) -> Vec<String> {
+    targets
+        // URLs, `mailto:`...
+        .filter(|t| {
+            !t.split_once(':')
+                .is_some_and(|(scheme, _)| scheme.chars().all(|c| c.is_ascii_alphanumeric()))
+        })
+        .filter(|t| !t.starts_with(['/', '#']))
+        // `a.md#heading`, and org `a.org::*heading`
+        .map(|t| t.split(['#', ':']).next().unwrap_or(t).replace("%20", " "))
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+/// Drop fenced code blocks, HTML tags and link targets.
+fn markdown_prose(body: &str) -> String {
+    let mut prose = String::new();
+    let mut fence: Option<&str> = None;
+
+    for line in body.lines() {
+        let trimmed = line.trim_start();
+        match fence {
+            Some(f) => {
+                if trimmed.starts_with(f) {
+                    fence = None;
+                }
+                continue;
+            }
+            None => {
+                if let Some(f) = ["```", "~~~"].into_iter().find(|f| trimmed.starts_with(f)) {
+                    fence = Some(f);
+                    continue;
+                }
+            }
+        }
+
+        let mut rest = line;
+        while !rest.is_empty() {
+            if let Some(target) = rest.strip_prefix("](")
+                && let Some(end) = target.find(')')
+            {
+                rest = &target[end + 1..];
+            } else if let Some(tag) = rest.strip_prefix('<')
+                && tag.starts_with(|c: char| c == '/' || c.is_ascii_alphabetic())
+                && let Some(end) = tag.find('>')
+            {
+                rest = &tag[end + 1..];
+            } else {
+                let c = rest.chars().next().unwrap();
+                prose.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+        prose.push('\n');
+    }
+
+    prose
+}
+
+/// Drop blocks (`#+BEGIN_SRC` to `#+END_SRC`, ...), keyword and comment lines and link targets.
+fn org_prose(body: &str) -> String {
+    let mut prose = String::new();
+    let mut in_block = false;
+
+    for line in body.lines() {
+        let trimmed = line.trim_start().to_lowercase();
+        if in_block {
+            in_block = !trimmed.starts_with("#+end_");
+            continue;
+        }
+        if trimmed.starts_with("#+begin_") {
+            // Quotes and verses are prose
+            in_block = !["#+begin_quote", "#+begin_verse"]
+                .iter()
+                .any(|b| trimmed.starts_with(b));
+            continue;
+        }
+        if trimmed.starts_with("#+") || trimmed == "#" || trimmed.starts_with("# ") {
+            continue;
+        }
+
+        // Count the description of `[[target][description]]`, not bare `[[target]]` links
+        let mut rest = line;
+        while let Some(start) = rest.find("[[") {
+            prose.push_str(&rest[..start]);
+            let link = &rest[start + 2..];
+            let Some(end) = link.find("]]") else {
+                rest = link;
+                break;
+            };
+            if let Some((_, description)) = link[..end].split_once("][") {
+                prose.push_str(description);
+            }
+            rest = &link[end + 2..];
+        }
+        prose.push_str(rest);
+        prose.push('\n');
+    }
+
+    prose
+}
+
+/// Drop raw blocks, comments, math, labels, references and code: statements such as `#set` up
+/// to the end of the line, the names and arguments of function calls. The content blocks of
+/// calls, e.g. the text of `#strong[text]`, are kept.
+fn typst_prose(body: &str) -> String {
+    let mut prose = String::new();
+    let mut rest = body;
+
+    while let Some(c) = rest.chars().next() {
+        let after = &rest[c.len_utf8()..];
+        rest = if let Some(raw) = rest.strip_prefix("```") {
+            raw.find("```").map_or("", |end| &raw[end + 3..])
+        } else if rest.starts_with("//") {
+            rest.find('\n').map_or("", |end| &rest[end..])
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            comment.find("*/").map_or("", |end| &comment[end + 2..])
+        } else if c == '\\' {
+            // An escaped character is text
+            let escaped = after.chars().next().map_or(0, char::len_utf8);
+            prose.push_str(&after[..escaped]);
+            &after[escaped..]
+        } else if c == '$' {
+            after.find('$').map_or("", |end| &after[end + 1..])
+        } else if c == '#' {
+            let name_len = after
+                .find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '-' | '.')))
+                .unwrap_or(after.len());
+            let (name, call) = after.split_at(name_len);
+            if TYPST_STATEMENTS.contains(&name) {
+                call.find('\n').map_or("", |end| &call[end..])
+            } else {
+                skip_group(call)
+            }
+        } else if c == '@' {
+            after.trim_start_matches(|c: char| c.is_alphanumeric() || matches!(c, '_' | '-' | ':'))
+        } else if c == '<'
+            && let Some(end) = after.find('>')
+            && !after[..end].is_empty()
+            && !after[..end].contains(char::is_whitespace)
+        {
+            &after[end + 1..]
+        } else {
+            prose.push(c);
+            after
+        };
+    }
+
+    prose
+}
+
+/// Skip the parenthesized arguments at the start of `s`, if any.
+fn skip_group(s: &str) -> &str {
+    if !s.starts_with('(') {
+        return s;
+    }
+
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '(' if !in_string => depth += 1,
+            ')' if !in_string => {
+                depth -= 1;
+                if depth == 0 {
+                    return &s[i + 1..];
+                }
+            }
+            _ => {}
+        }
+    }
+    // Unbalanced: the rest is arguments
+    ""
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extractors() {
+        let md = "---\ntitle: Net\nkeywords: [tcp]\n---\n# Net\n\nSee [TCP](tcp.md#intro), [[udp]] and <b>[docs](https://x.org)</b>.\n\n```\n# not a heading\n```\n";
+        let extract = extractor(NoteType::Md);
+        assert_eq!(extract.metadata(md).title.as_deref(), Some("Net"));
+        assert_eq!(extract.tags(md), ["tcp"]);
+        assert!(extract.body(md).starts_with("# Net"));
+        let text = extract.plain_text(md);
+        assert!(text.contains("TCP") && !text.contains("tcp.md") && !text.contains("<b>"));
+        assert!(!text.contains("not a heading"));
+        let lines: Vec<&str> = md.lines().collect();
+        assert_eq!(extract.headings(&lines), [(4, 1)]);
+        assert_eq!(extract.path_links(md), ["tcp.md"]);
+        assert_eq!(extract.wiki_links(md), ["udp"]);
+        assert_eq!(
+            extract.images("![a](<my logo.png>) ![b](https://x.org/b.png)"),
+            ["my logo.png"]
+        );
+
+        let typ = "#set document(title: \"Net\")\n= Net\n#strong[TCP] and $x$ <intro>\n";
+        let extract = extractor(NoteType::Typ);
+        assert_eq!(extract.metadata(typ).title.as_deref(), Some("Net"));
+        assert_eq!(extract.plain_text(typ).trim(), "= Net\n[TCP] and");
+        assert_eq!(extract.headings(&typ.lines().collect::<Vec<_>>()), [(1, 1)]);
+        assert_eq!(
+            extract.images("#image(\"images/a%20b.png\", width: 50%)"),
+            ["images/a b.png"]
+        );
+
+        let org =
+            "#+TITLE: Net\n* Net\n[[file:tcp.org::*Intro][TCP]]\n#+BEGIN_SRC sh\nls\n#+END_SRC\n";
+        let extract = extractor(NoteType::Org);
+        assert_eq!(extract.metadata(org).title.as_deref(), Some("Net"));
+        assert_eq!(extract.plain_text(org), "* Net\nTCP\n");
+        assert_eq!(extract.path_links(org), ["tcp.org"]);
+
+        let extract = extractor(NoteType::Custom("adoc"));
+        assert_eq!(extract.body("= Net\n"), "= Net\n");
+        assert!(extract.headings(&["= Net"]).is_empty());
+    }
+}
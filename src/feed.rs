@@ -0,0 +1,200 @@
+use chrono::{DateTime, Local};
+use std::{
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::cli::NoteType;
+use crate::dates;
+use crate::extract::NoteText;
+use crate::links::LinkIndex;
+use crate::markdown::{self, Block, escape};
+use crate::site::{Page, resolve_wiki_links};
+use crate::vault::Note;
+
+/// The notes of a feed, unless `--limit` says otherwise.
+pub const DEFAULT_LIMIT: usize = 20;
+/// The blocks of a Markdown note in the excerpt of its entry, headings left out.
+const EXCERPT_BLOCKS: usize = 3;
+/// The characters of the text of other notes in the excerpt of their entry.
+const EXCERPT_CHARS: usize = 280;
+
+/// A note of the feed.
+#[derive(Debug)]
+pub struct Entry {
+    pub page: Page,
+    pub created: DateTime<Local>,
+    pub updated: DateTime<Local>,
+    pub author: Option<String>,
+    /// The start of the note, as HTML
+    pub excerpt: String,
+}
+
+/// The entries of the `limit` most recently updated notes, the latest first. Their links point
+/// to the pages of `noxe publish -o` under `base_url`.
+pub fn entries(note_root: &Path, notes: &[PathBuf], base_url: &str, limit: usize) -> Vec<Entry> {
+    let mut dated: Vec<(&PathBuf, PathBuf, fs::Metadata)> = notes
+        .iter()
+        .filter_map(|note| {
+            let main_file = note.main_file_path().ok()?;
+            let metadata = main_file.metadata().ok()?;
+            Some((note, main_file, metadata))
+        })
+        .collect();
+    dated.sort_by_key(|(_, _, metadata)| std::cmp::Reverse(metadata.modified().ok()));
+    dated.truncate(limit);
+
+    let links = LinkIndex::new(note_root).ok();
+    let link_url = |target: &str| {
+        let note = links.as_ref()?.resolve(target)?;
+        Some(page_url(base_url, &Page::new(note_root, note)))
+    };
+
+    dated
+        .into_iter()
+        .map(|(note, main_file, metadata)| {
+            let text = NoteText::read(&main_file).ok();
+            let excerpt = match (&text, main_file.note_type()) {
+                (Some(text), Ok(NoteType::Md)) => {
+                    let body = resolve_wiki_links(&text.body(), &link_url);
+                    let blocks: Vec<Block> = markdown::parse(&body)
+                        .into_iter()
+                        .filter(|block| !matches!(block, Block::Heading(..)))
+                        .take(EXCERPT_BLOCKS)
+                        .collect();
+                    markdown::to_html(&blocks)
+                }
+                (Some(text), _) => {
+                    let plain = text.plain_text();
+                    let plain = plain.split_whitespace().collect::<Vec<_>>().join(" ");
+                    let mut excerpt: String = plain.chars().take(EXCERPT_CHARS).collect();
+                    if excerpt.len() < plain.len() {
+                        excerpt.push('…');
+                    }
+                    format!("<p>{}</p>\n", escape(&excerpt))
+                }
+                (None, _) => String::new(),
+            };
+
+            Entry {
+                page: Page::new(note_root, note),
+                created: dates::created(&metadata).into(),
+                updated: metadata
+                    .modified()
+                    .map_or_else(|_| Local::now(), Into::into),
+                author: text.and_then(|text| text.metadata().author),
+                excerpt,
+            }
+        })
+        .collect()
+}
+
+/// The absolute URL of a page of the site.
+fn page_url(base_url: &str, page: &Page) -> String {
+    format!("{}/{}", base_url.trim_end_matches('/'), page.url)
+}
+
+/// The Atom feed of the entries. `author` is the author of the notes without one.
+pub fn atom(title: &str, base_url: &str, author: Option<&str>, entries: &[Entry]) -> String {
+    let base_url = base_url.trim_end_matches('/');
+    let updated = entries
+        .iter()
+        .map(|entry| entry.updated)
+        .max()
+        .unwrap_or_else(Local::now);
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    let _ = writeln!(xml, "  <title>{}</title>", escape(title));
+    let _ = writeln!(xml, "  <id>{}/</id>", escape(base_url));
+    let _ = writeln!(xml, "  <link href=\"{}/\"/>", escape(base_url));
+    let _ = writeln!(
+        xml,
+        "  <link rel=\"self\" href=\"{}/feed.xml\"/>",
+        escape(base_url)
+    );
+    let _ = writeln!(xml, "  <updated>{}</updated>", updated.to_rfc3339());
+    let _ = writeln!(
+        xml,
+        "  <author><name>{}</name></author>",
+        escape(author.unwrap_or(title))
+    );
+
+    for entry in entries {
+        let url = page_url(base_url, &entry.page);
+        xml.push_str("  <entry>\n");
+        let _ = writeln!(xml, "    <title>{}</title>", escape(&entry.page.title));
+        let _ = writeln!(xml, "    <id>{}</id>", escape(&url));
+        let _ = writeln!(xml, "    <link href=\"{}\"/>", escape(&url));
+        let _ = writeln!(
+            xml,
+            "    <published>{}</published>",
+            entry.created.to_rfc3339()
+        );
+        let _ = writeln!(xml, "    <updated>{}</updated>", entry.updated.to_rfc3339());
+        if let Some(author) = &entry.author {
+            let _ = writeln!(xml, "    <author><name>{}</name></author>", escape(author));
+        }
+        if !entry.page.category.is_empty() {
+            let _ = writeln!(
+                xml,
+                "    <category term=\"{}\"/>",
+                escape(&entry.page.category)
+            );
+        }
+        let _ = writeln!(
+            xml,
+            "    <summary type=\"html\">{}</summary>",
+            escape(&entry.excerpt)
+        );
+        xml.push_str("  </entry>\n");
+    }
+    xml.push_str("</feed>\n");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_feed() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        fs::create_dir(root.join("lang")).unwrap();
+        fs::write(
+            root.join("lang/rust.md"),
+            "---\ntitle: Rust & Cargo\nauthor: Alice\n---\n\n# Rust\n\nSee [[tokio]].\n\nSecond.\n",
+        )
+        .unwrap();
+        fs::write(root.join("tokio.typ"), "= Tokio\nAn async runtime.\n").unwrap();
+        let notes = [root.join("lang/rust.md"), root.join("tokio.typ")];
+
+        assert_eq!(
+            entries(root, &notes, "https://me.github.io/notes/", 1).len(),
+            1
+        );
+
+        let entries = entries(root, &notes, "https://me.github.io/notes/", 10);
+        let rust = entries
+            .iter()
+            .find(|e| e.page.url == "lang/rust.html")
+            .unwrap();
+        assert_eq!(rust.author.as_deref(), Some("Alice"));
+        assert_eq!(
+            rust.excerpt,
+            "<p>See <a href=\"https://me.github.io/notes/tokio.html\">tokio</a>.</p>\n<p>Second.</p>\n"
+        );
+
+        let xml = atom("notes", "https://me.github.io/notes/", None, &entries);
+        assert!(xml.contains("<title>Rust &amp; Cargo</title>"));
+        assert!(xml.contains("<id>https://me.github.io/notes/lang/rust.html</id>"));
+        assert!(xml.contains("<link rel=\"self\" href=\"https://me.github.io/notes/feed.xml\"/>"));
+        assert!(xml.contains("<category term=\"lang\"/>"));
+        assert!(xml.contains("<summary type=\"html\">&lt;p&gt;See"));
+        assert!(!xml.contains("<p>"));
+    }
+}
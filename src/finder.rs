@@ -0,0 +1,273 @@
+use anyhow::Result;
+use ratatui::{
+    crossterm::event::KeyCode,
+    style::{Color, Modifier, Stylize},
+    text::{Line, Span},
+};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use crate::bounded::{map_head, preview_limit};
+use crate::snippet::fuzzy_score;
+use crate::vault::{Note, all_notes};
+
+/// How a note matches the query.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatchKind {
+    /// Its path, fuzzy-matched
+    Name,
+    /// Its text, which contains the query
+    Content,
+}
+
+/// What the finder asks of the TUI after a key.
+#[derive(Debug, PartialEq)]
+pub enum FinderAction {
+    None,
+    Close,
+    /// Show the note in its category
+    Jump(PathBuf),
+}
+
+/// The `/` search of the TUI: the notes of the vault whose path relative to the note root
+/// fuzzy-matches the query, best first, then those whose text contains it, ignoring case.
+#[derive(Debug)]
+pub struct Finder {
+    notes: Vec<PathBuf>,
+    labels: Vec<String>,
+    pub query: String,
+    /// The indices of the matching notes, with how they match
+    matches: Vec<(usize, MatchKind)>,
+    /// The lowercased heads of the notes, read on the first search of their text. `None` for
+    /// the notes that cannot be read, like encrypted ones, which are not searched.
+    heads: Option<Vec<Option<String>>>,
+    pub selected: usize,
+}
+
+impl Finder {
+    pub fn new(note_root: &Path) -> Result<Self> {
+        let mut notes = all_notes(note_root)?;
+        notes.sort();
+        let labels = notes
+            .iter()
+            .map(|p| {
+                p.strip_prefix(note_root)
+                    .unwrap_or(p)
+                    .to_string_lossy()
+                    .replace('\\', "/")
+            })
+            .collect();
+        let mut finder = Finder {
+            notes,
+            labels,
+            query: String::new(),
+            matches: Vec::new(),
+            heads: None,
+            selected: 0,
+        };
+        finder.refilter();
+        Ok(finder)
+    }
+
+    pub fn handle_code(&mut self, code: KeyCode) -> FinderAction {
+        match code {
+            KeyCode::Esc => return FinderAction::Close,
+            KeyCode::Enter => {
+                if let Some(note) = self.selected_note() {
+                    return FinderAction::Jump(note.to_path_buf());
+                }
+            }
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Tab if self.selected + 1 < self.matches.len() => {
+                self.selected += 1;
+            }
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.refilter();
+            }
+            KeyCode::Char(c) => {
+                self.query.push(c);
+                self.refilter();
+            }
+            _ => {}
+        }
+        FinderAction::None
+    }
+
+    pub fn selected_note(&self) -> Option<&Path> {
+        self.matches
+            .get(self.selected)
+            .map(|&(i, _)| self.notes[i].as_path())
+    }
+
+    /// The title of the list: the query and how many notes match it.
+    pub fn title(&self) -> String {
+        format!(
+            " /{} ({}/{}) ",
+            self.query,
+            self.matches.len(),
+            self.notes.len()
+        )
+    }
+
+    /// The matching notes, those matched by their text marked so.
+    pub fn lines(&self) -> Vec<Line<'static>> {
+        self.matches
+            .iter()
+            .map(|&(i, kind)| {
+                let label = Span::raw(self.labels[i].clone());
+                match kind {
+                    MatchKind::Name => Line::from(label),
+                    MatchKind::Content => Line::from(vec![label, " (content)".dim()]),
+                }
+            })
+            .collect()
+    }
+
+    fn refilter(&mut self) {
+        let mut by_name: Vec<(i64, usize)> = self
+            .labels
+            .iter()
+            .enumerate()
+            .filter_map(|(i, label)| fuzzy_score(&self.query, label).map(|score| (score, i)))
+            .collect();
+        by_name.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        self.matches = by_name
+            .into_iter()
+            .map(|(_, i)| (i, MatchKind::Name))
+            .collect();
+
+        if !self.query.is_empty() {
+            let query = self.query.to_lowercase();
+            let by_name: HashSet<usize> = self.matches.iter().map(|&(i, _)| i).collect();
+            let notes = &self.notes;
+            let heads = self.heads.get_or_insert_with(|| {
+                notes
+                    .iter()
+                    .map(|note| {
+                        let main_file = note.main_file_path().ok()?;
+                        map_head(&main_file, preview_limit(), |text| text.to_lowercase()).ok()
+                    })
+                    .collect()
+            });
+            for (i, head) in heads.iter().enumerate() {
+                if !by_name.contains(&i) && head.as_ref().is_some_and(|h| h.contains(&query)) {
+                    self.matches.push((i, MatchKind::Content));
+                }
+            }
+        }
+        self.selected = 0;
+    }
+}
+
+/// Highlight the occurrences of `query` in the lines, ignoring the case of ASCII letters.
+/// Returns the lines and the index of the first one with an occurrence.
+pub fn highlight_matches(
+    lines: Vec<Line<'static>>,
+    query: &str,
+) -> (Vec<Line<'static>>, Option<usize>) {
+    if query.is_empty() {
+        return (lines, None);
+    }
+    let query = query.to_ascii_lowercase();
+    let mut first = None;
+    let lines = lines
+        .into_iter()
+        .enumerate()
+        .map(|(n, line)| {
+            let mut spans = Vec::new();
+            for span in line.spans {
+                // Same offsets as the content, unlike a full lowercase
+                let lower = span.content.to_ascii_lowercase();
+                let mut start = 0;
+                for (pos, _) in lower.match_indices(&query) {
+                    first.get_or_insert(n);
+                    if pos > start {
+                        spans.push(Span::styled(
+                            span.content[start..pos].to_string(),
+                            span.style,
+                        ));
+                    }
+                    let end = pos + query.len();
+                    spans.push(Span::styled(
+                        span.content[pos..end].to_string(),
+                        span.style
+                            .fg(Color::Black)
+                            .bg(Color::Yellow)
+                            .add_modifier(Modifier::BOLD),
+                    ));
+                    start = end;
+                }
+                if start == 0 {
+                    spans.push(span);
+                } else if start < span.content.len() {
+                    spans.push(Span::styled(span.content[start..].to_string(), span.style));
+                }
+            }
+            Line::from(spans).style(line.style)
+        })
+        .collect();
+    (lines, first)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_finder() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        fs::create_dir_all(root.join("lang")).unwrap();
+        fs::write(
+            root.join("lang/rust.md"),
+            "# Rust\nOwnership and borrowing\n",
+        )
+        .unwrap();
+        fs::write(root.join("lang/go.md"), "# Go\nGoroutines\n").unwrap();
+        fs::write(root.join("memory.md"), "# Memory\nSee the BORROW checker\n").unwrap();
+
+        let mut finder = Finder::new(root).unwrap();
+        assert_eq!(finder.matches.len(), 3);
+        for c in "borrow".chars() {
+            finder.handle_code(KeyCode::Char(c));
+        }
+        assert_eq!(
+            finder.matches,
+            [(1, MatchKind::Content), (2, MatchKind::Content)]
+        );
+        for _ in 0..6 {
+            finder.handle_code(KeyCode::Backspace);
+        }
+        for c in "lgo".chars() {
+            finder.handle_code(KeyCode::Char(c));
+        }
+        assert_eq!(finder.matches, [(0, MatchKind::Name)]);
+        finder.handle_code(KeyCode::Down);
+        assert_eq!(
+            finder.handle_code(KeyCode::Enter),
+            FinderAction::Jump(root.join("lang/go.md"))
+        );
+        assert_eq!(finder.handle_code(KeyCode::Esc), FinderAction::Close);
+
+        let lines = vec![
+            Line::from("# Rust"),
+            Line::from(vec![Span::raw("Borrow "), "and borrow".bold()]),
+        ];
+        let (lines, first) = highlight_matches(lines, "borrow");
+        assert_eq!(first, Some(1));
+        let spans: Vec<&str> = lines[1].spans.iter().map(|s| s.content.as_ref()).collect();
+        assert_eq!(spans, ["Borrow", " ", "and ", "borrow"]);
+        assert_eq!(lines[1].spans[0].style.bg, Some(Color::Yellow));
+        assert!(
+            lines[1].spans[3]
+                .style
+                .add_modifier
+                .contains(Modifier::BOLD)
+        );
+    }
+}
@@ -0,0 +1,285 @@
+use anyhow::{Result, bail};
+use std::path::{Path, PathBuf};
+
+use crate::extract::NoteText;
+use crate::index::NoteIndex;
+use crate::process::find_note_dir;
+use crate::vault::{Note, category_dir, notes_in_categories};
+
+/// The header of the TSV files, telling Anki how to import them (Anki 2.1.55 and later):
+/// the note type, deck and tags of each card are columns.
+const TSV_HEADER: &str =
+    "#separator:tab\n#html:true\n#notetype column:1\n#deck column:2\n#tags column:5\n";
+
+/// A flashcard written in a note.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Card {
+    pub kind: CardKind,
+    pub front: String,
+    /// The answer of basic cards, empty for cloze cards
+    pub back: String,
+    /// The line of the card in the note, from 1
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CardKind {
+    Basic,
+    Cloze,
+}
+
+impl CardKind {
+    /// The name of the Anki note type.
+    fn notetype(self) -> &'static str {
+        match self {
+            CardKind::Basic => "Basic",
+            CardKind::Cloze => "Cloze",
+        }
+    }
+}
+
+/// The cards of a note and where they go.
+#[derive(Debug)]
+pub struct Deck {
+    pub name: String,
+    pub tags: Vec<String>,
+    pub cards: Vec<Card>,
+}
+
+/// The notes of `target`: the notes of a category, or a single note.
+pub fn target_notes(note_root: &Path, target: &Path) -> Result<Vec<PathBuf>> {
+    if let Ok(category) = category_dir(note_root, &target.to_string_lossy()) {
+        let index = NoteIndex::open(note_root)?;
+        return notes_in_categories(index.as_ref(), note_root, &[category]);
+    }
+    Ok(vec![find_note_dir(
+        target.as_os_str(),
+        note_root.as_os_str(),
+    )?])
+}
+
+/// The decks of the notes, one per note with cards. A note goes to the subdeck of `deck`
+/// named after its category, e.g. `noxe::lang::rust`, and its keywords tag its cards.
+pub fn decks(note_root: &Path, notes: &[PathBuf], deck: &str) -> Result<Vec<Deck>> {
+    let mut decks = Vec::new();
+    for note in notes {
+        let text = NoteText::read(&note.main_file_path()?)?;
+        let cards = cards(&text.body(), body_offset(&text));
+        if cards.is_empty() {
+            continue;
+        }
+
+        let name = note
+            .parent()
+            .and_then(|dir| dir.strip_prefix(note_root).ok())
+            .into_iter()
+            .flat_map(|dir| dir.iter())
+            .fold(deck.to_string(), |deck, c| {
+                format!("{}::{}", deck, c.to_string_lossy())
+            });
+        let tags = text
+            .metadata()
+            .keywords
+            .iter()
+            .map(|k| k.split_whitespace().collect::<Vec<_>>().join("_"))
+            .collect();
+        decks.push(Deck { name, tags, cards });
+    }
+    if decks.is_empty() {
+        bail!("No flashcard found: write `Q:`/`A:` lines, `front :: back` or `{{{{c1::cloze}}}}`");
+    }
+    Ok(decks)
+}
+
+/// The number of lines of the metadata before the body.
+fn body_offset(text: &NoteText) -> usize {
+    text.content
+        .lines()
+        .count()
+        .saturating_sub(text.body().lines().count())
+}
+
+/// Read the flashcards of the body of a note:
+/// - `Q: question` then `A: answer` lines, each of which may go on over the following lines,
+///   up to a blank line;
+/// - `front :: back` lines;
+/// - lines with Anki cloze deletions, `{{c1::answer}}` or `{{c1::answer::hint}}`.
+///
+/// Code blocks are skipped. `offset` is the number of lines before the body.
+pub fn cards(body: &str, offset: usize) -> Vec<Card> {
+    let mut cards = Vec::new();
+    let mut question: Option<(usize, Vec<&str>)> = None;
+    let mut answer: Option<Vec<&str>> = None;
+    let mut in_code = false;
+
+    // A question without an answer is dropped
+    fn finish(
+        question: &mut Option<(usize, Vec<&str>)>,
+        answer: &mut Option<Vec<&str>>,
+        cards: &mut Vec<Card>,
+    ) {
+        if let (Some((line, q)), Some(a)) = (question.take(), answer.take()) {
+            cards.push(Card {
+                kind: CardKind::Basic,
+                front: q.join("\n"),
+                back: a.join("\n"),
+                line,
+            });
+        }
+    }
+
+    for (i, line) in body.lines().enumerate() {
+        let trimmed = line.trim();
+        let lower = trimmed.to_lowercase();
+        if trimmed.starts_with("```")
+            || trimmed.starts_with("~~~")
+            || lower.starts_with("#+begin_src")
+            || lower.starts_with("#+end_src")
+        {
+            finish(&mut question, &mut answer, &mut cards);
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+
+        if let Some(q) = trimmed.strip_prefix("Q:") {
+            finish(&mut question, &mut answer, &mut cards);
+            question = Some((offset + i + 1, vec![q.trim()]));
+        } else if let Some(a) = trimmed.strip_prefix("A:")
+            && question.is_some()
+        {
+            answer = Some(vec![a.trim()]);
+        } else if trimmed.is_empty() {
+            finish(&mut question, &mut answer, &mut cards);
+        } else if let Some(a) = &mut answer {
+            a.push(trimmed);
+        } else if let Some((_, q)) = &mut question {
+            q.push(trimmed);
+        } else if is_cloze(trimmed) {
+            cards.push(Card {
+                kind: CardKind::Cloze,
+                front: trimmed.to_string(),
+                back: String::new(),
+                line: offset + i + 1,
+            });
+        } else if let Some((front, back)) = trimmed.split_once(" :: ")
+            && !front.trim().is_empty()
+            && !back.trim().is_empty()
+        {
+            cards.push(Card {
+                kind: CardKind::Basic,
+                front: front.trim().to_string(),
+                back: back.trim().to_string(),
+                line: offset + i + 1,
+            });
+        }
+    }
+    finish(&mut question, &mut answer, &mut cards);
+
+    cards
+}
+
+fn is_cloze(line: &str) -> bool {
+    line.match_indices("{{c").any(|(i, _)| {
+        let rest = &line[i + 3..];
+        let digits = rest.chars().take_while(char::is_ascii_digit).count();
+        digits > 0 && rest[digits..].starts_with("::")
+    })
+}
+
+/// The decks as a TSV file that Anki imports, basic cards as `Basic` notes and cloze cards as
+/// `Cloze` notes.
+pub fn to_tsv(decks: &[Deck]) -> String {
+    let mut tsv = TSV_HEADER.to_string();
+    for deck in decks {
+        let tags = deck.tags.join(" ");
+        for card in &deck.cards {
+            let fields = [
+                card.kind.notetype(),
+                &deck.name,
+                &html(&card.front),
+                &html(&card.back),
+                &tags,
+            ];
+            tsv.push_str(&fields.map(field).join("\t"));
+            tsv.push('\n');
+        }
+    }
+    tsv
+}
+
+fn html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('\n', "<br>")
+}
+
+/// A TSV field: tabs become spaces, and fields with quotes are quoted.
+fn field(text: &str) -> String {
+    let text = text.replace('\t', " ");
+    if text.contains('"') {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_flashcards() {
+        let body = "# TCP\n\nQ: What does SYN do?\nA: Opens a\nconnection\n\nMTU :: Maximum transmission unit\n\nThe handshake has {{c1::three}} steps.\n\n```\nQ: not a card\nA: code\n```\nQ: Unanswered\n";
+        let cards = cards(body, 3);
+        assert_eq!(
+            cards
+                .iter()
+                .map(|c| (c.kind, c.front.as_str(), c.back.as_str(), c.line))
+                .collect::<Vec<_>>(),
+            [
+                (
+                    CardKind::Basic,
+                    "What does SYN do?",
+                    "Opens a\nconnection",
+                    6
+                ),
+                (CardKind::Basic, "MTU", "Maximum transmission unit", 10),
+                (
+                    CardKind::Cloze,
+                    "The handshake has {{c1::three}} steps.",
+                    "",
+                    12
+                ),
+            ]
+        );
+
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        fs::create_dir_all(root.join("net")).unwrap();
+        let note = root.join("net/tcp.md");
+        fs::write(
+            &note,
+            "---\nkeywords: [network protocols]\n---\nQ: Is <TCP> \"reliable\"?\nA: Yes\n",
+        )
+        .unwrap();
+        fs::write(root.join("net/empty.md"), "no cards\n").unwrap();
+
+        let notes = target_notes(root, Path::new("NET")).unwrap();
+        assert_eq!(notes.len(), 2);
+        let decks = decks(root, &notes, "noxe").unwrap();
+        assert_eq!(decks.len(), 1);
+        assert_eq!(decks[0].cards[0].line, 4);
+        let tsv = to_tsv(&decks);
+        assert!(tsv.starts_with(TSV_HEADER));
+        assert!(tsv.ends_with(
+            "Basic\tnoxe::net\t\"Is &lt;TCP&gt; \"\"reliable\"\"?\"\tYes\tnetwork_protocols\n"
+        ));
+        assert!(target_notes(root, Path::new("missing")).is_err());
+    }
+}
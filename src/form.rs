@@ -0,0 +1,278 @@
+use anyhow::{Result, bail};
+use ratatui::crossterm::event::KeyCode;
+use std::{
+    ffi::OsString,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::cli::NoteType;
+use crate::template::TemplateStore;
+use crate::vault::Vault;
+
+/// A field of the form.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Name,
+    Category,
+    Type,
+    Template,
+}
+
+pub const FIELDS: [Field; 4] = [Field::Name, Field::Category, Field::Type, Field::Template];
+
+/// What the form asks of the TUI after a key.
+#[derive(Debug, PartialEq)]
+pub enum FormAction {
+    None,
+    Close,
+    /// Run `noxe new` with these arguments
+    Create(Vec<OsString>),
+}
+
+/// The form of a new note: its name, its category (completed over the categories of the
+/// vault), its type and the template of the registry to use, if any.
+#[derive(Debug)]
+pub struct NewNoteForm {
+    note_root: PathBuf,
+    pub name: String,
+    pub category: String,
+    types: Vec<NoteType>,
+    note_type: usize,
+    pub template: String,
+    /// The categories of the vault, relative to the note root
+    categories: Vec<String>,
+    templates: Vec<String>,
+    pub focus: usize,
+    /// The text completed by Tab, and the completion shown
+    completing: Option<(String, usize)>,
+    pub message: Option<String>,
+}
+
+impl NewNoteForm {
+    /// A form creating the note in `category`, relative to the note root.
+    pub fn new(note_root: &Path, category: &str) -> Result<Self> {
+        let mut categories: Vec<String> = Vault::open(note_root)?
+            .categories()?
+            .iter()
+            .map(|c| {
+                c.strip_prefix(note_root)
+                    .unwrap_or(c)
+                    .to_string_lossy()
+                    .replace('\\', "/")
+            })
+            .collect();
+        categories.sort();
+        // A missing template directory is no reason to fail
+        let templates =
+            TemplateStore::new(std::env::var_os("NOXE_TEMPLATE_DIR").map(PathBuf::from))
+                .and_then(|store| store.names())
+                .unwrap_or_default();
+        let types = NoteType::all();
+        let note_type = std::env::var("NOXE_TYPE")
+            .ok()
+            .and_then(|t| NoteType::try_from(t.as_str()).ok())
+            .and_then(|t| types.iter().position(|&u| u == t))
+            .unwrap_or(0);
+
+        Ok(NewNoteForm {
+            note_root: note_root.to_path_buf(),
+            name: String::new(),
+            category: category.to_string(),
+            types,
+            note_type,
+            template: String::new(),
+            categories,
+            templates,
+            focus: 0,
+            completing: None,
+            message: None,
+        })
+    }
+
+    pub fn note_type(&self) -> NoteType {
+        self.types[self.note_type]
+    }
+
+    pub fn field(&self) -> Field {
+        FIELDS[self.focus]
+    }
+
+    /// The completions of the focused field: the categories or templates starting with its
+    /// text.
+    pub fn completions(&self) -> Vec<&str> {
+        let (options, text) = match self.field() {
+            Field::Category => (&self.categories, &self.category),
+            Field::Template => (&self.templates, &self.template),
+            _ => return Vec::new(),
+        };
+        let prefix = self
+            .completing
+            .as_ref()
+            .map_or(text.as_str(), |(prefix, _)| prefix);
+        options
+            .iter()
+            .map(String::as_str)
+            .filter(|o| o.starts_with(prefix))
+            .collect()
+    }
+
+    pub fn handle_code(&mut self, code: KeyCode) -> FormAction {
+        self.message = None;
+        match code {
+            KeyCode::Esc => return FormAction::Close,
+            KeyCode::Enter => match self.words() {
+                Ok(words) => return FormAction::Create(words),
+                Err(e) => self.message = Some(e.to_string()),
+            },
+            KeyCode::Up | KeyCode::BackTab => self.focus(self.focus.saturating_sub(1)),
+            KeyCode::Down => self.focus((self.focus + 1).min(FIELDS.len() - 1)),
+            KeyCode::Tab => match self.field() {
+                Field::Category | Field::Template => self.complete(),
+                _ => self.focus((self.focus + 1).min(FIELDS.len() - 1)),
+            },
+            KeyCode::Left if self.field() == Field::Type => {
+                self.note_type = (self.note_type + self.types.len() - 1) % self.types.len();
+            }
+            KeyCode::Right | KeyCode::Char(' ') if self.field() == Field::Type => {
+                self.note_type = (self.note_type + 1) % self.types.len();
+            }
+            KeyCode::Backspace => {
+                self.completing = None;
+                if let Some(text) = self.text() {
+                    text.pop();
+                }
+            }
+            KeyCode::Char(c) => {
+                self.completing = None;
+                if let Some(text) = self.text() {
+                    text.push(c);
+                }
+            }
+            _ => {}
+        }
+        FormAction::None
+    }
+
+    fn focus(&mut self, focus: usize) {
+        self.focus = focus;
+        self.completing = None;
+    }
+
+    fn text(&mut self) -> Option<&mut String> {
+        match self.field() {
+            Field::Name => Some(&mut self.name),
+            Field::Category => Some(&mut self.category),
+            Field::Template => Some(&mut self.template),
+            Field::Type => None,
+        }
+    }
+
+    /// Replace the text of the field with its next completion.
+    fn complete(&mut self) {
+        let next = match &self.completing {
+            Some((_, shown)) => shown + 1,
+            None => 0,
+        };
+        let completions: Vec<String> = self.completions().into_iter().map(str::to_string).collect();
+        if completions.is_empty() {
+            return;
+        }
+        let next = next % completions.len();
+        let prefix = match self.completing.take() {
+            Some((prefix, _)) => prefix,
+            None => self.text().cloned().unwrap_or_default(),
+        };
+        if let Some(text) = self.text() {
+            *text = completions[next].clone();
+        }
+        self.completing = Some((prefix, next));
+    }
+
+    /// The arguments of `noxe new` creating the note.
+    fn words(&self) -> Result<Vec<OsString>> {
+        let name = self.name.trim();
+        if name.is_empty() {
+            bail!("The note needs a name");
+        }
+        if name.contains(['/', '\\']) || name.starts_with('.') {
+            bail!("Invalid note name '{}'", name);
+        }
+        let category = Path::new(self.category.trim());
+        if !category
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+        {
+            bail!("Invalid category '{}'", category.display());
+        }
+
+        let mut words: Vec<OsString> = vec![
+            "new".into(),
+            self.note_root.join(category).join(name).into(),
+            "--note-type".into(),
+            self.note_type().to_string().into(),
+        ];
+        let template = self.template.trim();
+        if !template.is_empty() {
+            words.extend(["--template".into(), template.into()]);
+        }
+        Ok(words)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_new_note_form() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        fs::create_dir_all(root.join("lang/rust")).unwrap();
+        fs::write(root.join("lang/rust/main.md"), "").unwrap();
+        fs::create_dir_all(root.join("lang/c")).unwrap();
+        fs::create_dir_all(root.join("life")).unwrap();
+
+        let mut form = NewNoteForm::new(root, "").unwrap();
+        assert_eq!(form.categories, ["lang", "lang/c", "life"]);
+        assert_eq!(
+            form.handle_code(KeyCode::Enter),
+            FormAction::None,
+            "no name"
+        );
+        assert!(form.message.is_some());
+
+        for c in "tokio".chars() {
+            form.handle_code(KeyCode::Char(c));
+        }
+        form.handle_code(KeyCode::Tab);
+        assert_eq!(form.field(), Field::Category);
+        form.handle_code(KeyCode::Char('l'));
+        assert_eq!(form.completions(), ["lang", "lang/c", "life"]);
+        form.handle_code(KeyCode::Char('a'));
+        form.handle_code(KeyCode::Tab);
+        assert_eq!(form.category, "lang");
+        form.handle_code(KeyCode::Tab);
+        assert_eq!(form.category, "lang/c");
+        form.handle_code(KeyCode::Tab);
+        assert_eq!(form.category, "lang");
+
+        form.handle_code(KeyCode::Down);
+        form.handle_code(KeyCode::Right);
+        let note_type = form.note_type().to_string();
+        assert_eq!(
+            form.handle_code(KeyCode::Enter),
+            FormAction::Create(vec![
+                "new".into(),
+                root.join("lang/tokio").into(),
+                "--note-type".into(),
+                note_type.into(),
+            ])
+        );
+
+        form.category = "../out".to_string();
+        assert_eq!(form.handle_code(KeyCode::Enter), FormAction::None);
+        assert_eq!(form.handle_code(KeyCode::Esc), FormAction::Close);
+    }
+}
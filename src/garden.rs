@@ -0,0 +1,239 @@
+use anyhow::Result;
+use chrono::{DateTime, Local, NaiveDate};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::extract::extractor;
+use crate::links::LinkIndex;
+use crate::vault::{Note, all_notes};
+use crate::words::count_words;
+
+/// The metadata field recording when a note was last reviewed, as `YYYY-MM-DD`. Without it,
+/// the modification time of the main file is used.
+pub const REVIEWED_FIELD: &str = "reviewed";
+
+/// The words from which the length of a note earns all its points.
+const FULL_LENGTH: usize = 500;
+
+/// The links (in or out) from which a note earns all the points of the links.
+const FULL_LINKS: usize = 3;
+
+/// How grown a note is, from its score.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Tier {
+    /// Score below 40
+    Seedling,
+    /// Score from 40 to 69
+    Budding,
+    /// Score from 70
+    Evergreen,
+}
+
+impl Tier {
+    fn of(score: u32) -> Self {
+        match score {
+            0..40 => Tier::Seedling,
+            40..70 => Tier::Budding,
+            _ => Tier::Evergreen,
+        }
+    }
+}
+
+/// The quality of a note, scored out of 100:
+///
+/// - length: up to 30 points, full from 500 words
+/// - metadata: 5 points for a title, 5 for keywords
+/// - outgoing and incoming links: up to 15 points each, full from 3 links
+/// - review: 20 points if reviewed within 30 days, 10 within 180 days
+/// - lint: 10 points if all its links resolve
+#[derive(Debug, Serialize)]
+pub struct Assessment {
+    /// Path of the note relative to the note root
+    pub path: String,
+    pub words: usize,
+    pub has_title: bool,
+    pub has_keywords: bool,
+    pub links_out: usize,
+    pub links_in: usize,
+    pub broken_links: usize,
+    /// Days since the last review
+    pub days_since_review: Option<i64>,
+    pub score: u32,
+    pub tier: Tier,
+}
+
+impl Assessment {
+    fn score(&self) -> u32 {
+        let ratio = |n: usize, full: usize, points: usize| (n.min(full) * points / full) as u32;
+
+        let review = match self.days_since_review {
+            Some(days) if days <= 30 => 20,
+            Some(days) if days <= 180 => 10,
+            _ => 0,
+        };
+
+        ratio(self.words, FULL_LENGTH, 30)
+            + 5 * self.has_title as u32
+            + 5 * self.has_keywords as u32
+            + ratio(self.links_out, FULL_LINKS, 15)
+            + ratio(self.links_in, FULL_LINKS, 15)
+            + review
+            + if self.broken_links == 0 { 10 } else { 0 }
+    }
+
+    /// What would raise the score the most, for the report.
+    pub fn hints(&self) -> Vec<&'static str> {
+        let mut hints = Vec::new();
+        if self.words < FULL_LENGTH / 5 {
+            hints.push("short");
+        }
+        if !self.has_title || !self.has_keywords {
+            hints.push("missing metadata");
+        }
+        if self.links_out == 0 {
+            hints.push("no outgoing links");
+        }
+        if self.links_in == 0 {
+            hints.push("orphan");
+        }
+        if self.days_since_review.is_none_or(|d| d > 180) {
+            hints.push("stale");
+        }
+        if self.broken_links > 0 {
+            hints.push("broken links");
+        }
+        hints
+    }
+}
+
+/// Score the notes of the vault, lowest first.
+pub fn assess(note_root: &Path, today: NaiveDate) -> Result<Vec<Assessment>> {
+    let links = LinkIndex::new(note_root)?;
+
+    // (note, words, title, keywords, links out, broken links, last review)
+    let mut notes = Vec::new();
+    let mut links_in: HashMap<PathBuf, usize> = HashMap::new();
+    for note in all_notes(note_root)? {
+        let Ok(main_file) = note.main_file_path() else {
+            continue;
+        };
+        let (Ok(content), Ok(note_type)) = (fs::read_to_string(&main_file), main_file.note_type())
+        else {
+            continue;
+        };
+        let extract = extractor(note_type);
+        let metadata = extract.metadata(&content);
+
+        let mut links_out = 0;
+        let mut broken = 0;
+        for link in extract.wiki_links(&content) {
+            match links.resolve(&link) {
+                Some(target) if target != note => {
+                    links_out += 1;
+                    *links_in.entry(target.to_path_buf()).or_default() += 1;
+                }
+                Some(_) => {}
+                None => broken += 1,
+            }
+        }
+
+        let reviewed = metadata
+            .fields
+            .get(REVIEWED_FIELD)
+            .and_then(|v| v.as_str())
+            .and_then(|d| NaiveDate::parse_from_str(d.get(..10)?, "%Y-%m-%d").ok())
+            .or_else(|| {
+                let modified = fs::metadata(&main_file).ok()?.modified().ok()?;
+                Some(DateTime::<Local>::from(modified).date_naive())
+            });
+
+        notes.push((
+            note,
+            count_words(&content, note_type),
+            metadata.title.is_some(),
+            !metadata.keywords.is_empty(),
+            links_out,
+            broken,
+            reviewed,
+        ));
+    }
+
+    let mut assessments: Vec<Assessment> = notes
+        .into_iter()
+        .map(
+            |(note, words, has_title, has_keywords, links_out, broken, reviewed)| {
+                let mut assessment = Assessment {
+                    path: note
+                        .strip_prefix(note_root)
+                        .unwrap_or(&note)
+                        .to_string_lossy()
+                        .to_string(),
+                    links_in: links_in.get(&note).copied().unwrap_or(0),
+                    words,
+                    has_title,
+                    has_keywords,
+                    links_out,
+                    broken_links: broken,
+                    days_since_review: reviewed.map(|d| (today - d).num_days()),
+                    score: 0,
+                    tier: Tier::Seedling,
+                };
+                assessment.score = assessment.score();
+                assessment.tier = Tier::of(assessment.score);
+                assessment
+            },
+        )
+        .collect();
+    assessments.sort_by(|a, b| a.score.cmp(&b.score).then_with(|| a.path.cmp(&b.path)));
+
+    Ok(assessments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_assess() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        let long_text = "word ".repeat(600);
+        fs::write(
+            root.join("hub.md"),
+            format!(
+                "---\ntitle: \"Hub\"\nkeywords: [a]\nreviewed: \"2025-01-10\"\n---\n[[a]] [[b]] [[c]]\n{}",
+                long_text
+            ),
+        )
+        .unwrap();
+        for name in ["a", "b", "c"] {
+            fs::write(root.join(format!("{}.md", name)), "[[hub]]\n").unwrap();
+        }
+        fs::write(root.join("lonely.md"), "[[missing]]\n").unwrap();
+
+        let today = NaiveDate::from_ymd_opt(2025, 1, 20).unwrap();
+        let assessments = assess(root, today).unwrap();
+        assert_eq!(assessments.len(), 5);
+
+        let hub = assessments.iter().find(|a| a.path == "hub.md").unwrap();
+        assert_eq!((hub.links_out, hub.links_in), (3, 3));
+        assert_eq!(hub.days_since_review, Some(10));
+        assert_eq!(hub.score, 100);
+        assert_eq!(hub.tier, Tier::Evergreen);
+        assert!(hub.hints().is_empty());
+
+        let lonely = &assessments[0];
+        assert_eq!(lonely.path, "lonely.md");
+        assert_eq!(lonely.broken_links, 1);
+        assert_eq!(lonely.tier, Tier::Seedling);
+        assert!(lonely.hints().contains(&"orphan"));
+        assert!(lonely.hints().contains(&"broken links"));
+    }
+}
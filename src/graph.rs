@@ -0,0 +1,212 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::extract::extractor;
+use crate::include::normalize;
+use crate::links::LinkIndex;
+use crate::vault::{Note, all_notes};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum GraphFormat {
+    /// Graphviz
+    #[default]
+    Dot,
+    /// `{"nodes": [...], "links": [...]}`, as read by d3-force style graph viewers
+    Json,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GraphNode {
+    /// The path of the note relative to the note root
+    pub id: String,
+    pub title: String,
+    /// The category of the note, "" at the root
+    pub group: String,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct GraphLink {
+    pub source: String,
+    pub target: String,
+    /// `wiki` for `[[...]]` links, `path` for relative path links
+    pub kind: &'static str,
+}
+
+/// The notes of a vault and the links between them.
+#[derive(Debug, Serialize)]
+pub struct Graph {
+    pub nodes: Vec<GraphNode>,
+    pub links: Vec<GraphLink>,
+}
+
+impl Graph {
+    pub fn build(note_root: &Path) -> Result<Self> {
+        let index = LinkIndex::new(note_root)?;
+        let mut notes = all_notes(note_root)?;
+        notes.sort();
+
+        let id = |note: &Path| {
+            note.strip_prefix(note_root)
+                .unwrap_or(note)
+                .to_string_lossy()
+                .replace('\\', "/")
+        };
+
+        // Path links may point to the note or to the main file of a dirnote
+        let mut by_path: HashMap<PathBuf, usize> = HashMap::new();
+        for (i, note) in notes.iter().enumerate() {
+            by_path.insert(normalize(note), i);
+            if let Ok(main_file) = note.main_file_path() {
+                by_path.insert(normalize(&main_file), i);
+            }
+        }
+        let position: HashMap<&Path, usize> = notes
+            .iter()
+            .enumerate()
+            .map(|(i, n)| (n.as_path(), i))
+            .collect();
+
+        let mut nodes = Vec::new();
+        let mut links = Vec::new();
+        let mut seen = HashSet::new();
+        for (i, note) in notes.iter().enumerate() {
+            let main_file = note.main_file_path().ok();
+            let content = main_file
+                .as_ref()
+                .and_then(|m| fs::read_to_string(m).ok())
+                .unwrap_or_default();
+            let note_type = main_file.as_ref().and_then(|m| m.note_type().ok());
+
+            let extract = note_type.map(extractor);
+            let title = extract
+                .and_then(|e| e.metadata(&content).title)
+                .unwrap_or_else(|| note.note_name());
+            let group = Path::new(&id(note))
+                .parent()
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_default();
+            nodes.push(GraphNode {
+                id: id(note),
+                title,
+                group,
+            });
+
+            let mut targets: Vec<(usize, &'static str)> = extract
+                .map(|e| e.wiki_links(&content))
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|link| index.resolve(link))
+                .filter_map(|target| position.get(target).map(|&j| (j, "wiki")))
+                .collect();
+            if let (Some(main_file), Some(extract)) = (&main_file, extract) {
+                let base = main_file.parent().unwrap_or(Path::new("."));
+                targets.extend(
+                    extract
+                        .path_links(&content)
+                        .iter()
+                        .filter_map(|link| by_path.get(&normalize(&base.join(link))))
+                        .map(|&j| (j, "path")),
+                );
+            }
+
+            for (j, kind) in targets {
+                if i != j && seen.insert((i, j)) {
+                    links.push(GraphLink {
+                        source: id(note),
+                        target: id(&notes[j]),
+                        kind,
+                    });
+                }
+            }
+        }
+
+        Ok(Graph { nodes, links })
+    }
+
+    pub fn to_dot(&self) -> String {
+        let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+
+        let mut dot = String::from("digraph notes {\n");
+        for node in &self.nodes {
+            writeln!(dot, "  {} [label={}];", quote(&node.id), quote(&node.title)).unwrap();
+        }
+        for link in &self.links {
+            let style = if link.kind == "path" {
+                " [style=dashed]"
+            } else {
+                ""
+            };
+            writeln!(
+                dot,
+                "  {} -> {}{};",
+                quote(&link.source),
+                quote(&link.target),
+                style
+            )
+            .unwrap();
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_graph() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        fs::create_dir_all(root.join("lang/paper")).unwrap();
+        fs::write(root.join("lang/paper/main.typ"), "[[rust]]\n").unwrap();
+        fs::write(
+            root.join("rust.md"),
+            "---\ntitle: \"Rust \\\"lang\\\"\"\n---\n[[paper]] [[rust]] [[missing]]\n[main](lang/paper/main.typ#intro) [web](https://a.b/c.md) [self](#top)\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("lang/go.md"),
+            "[Rust](../rust.md \"title\") and [[rust]]\n",
+        )
+        .unwrap();
+        fs::write(root.join("lang/note.org"), "[[file:go.md::*Intro][Go]]\n").unwrap();
+
+        let graph = Graph::build(root).unwrap();
+        let ids: Vec<&str> = graph.nodes.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            ["lang/go.md", "lang/note.org", "lang/paper", "rust.md"]
+        );
+        assert_eq!(graph.nodes[2].group, "lang");
+        assert_eq!(graph.nodes[3].title, "Rust \"lang\"");
+
+        let links: Vec<(&str, &str, &str)> = graph
+            .links
+            .iter()
+            .map(|l| (l.source.as_str(), l.target.as_str(), l.kind))
+            .collect();
+        assert_eq!(
+            links,
+            [
+                ("lang/go.md", "rust.md", "wiki"),
+                ("lang/note.org", "lang/go.md", "path"),
+                ("lang/paper", "rust.md", "wiki"),
+                ("rust.md", "lang/paper", "wiki"),
+            ]
+        );
+
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph notes {\n"));
+        assert!(dot.contains("  \"rust.md\" [label=\"Rust \\\"lang\\\"\"];\n"));
+        assert!(dot.contains("  \"lang/note.org\" -> \"lang/go.md\" [style=dashed];\n"));
+    }
+}
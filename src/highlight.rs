@@ -0,0 +1,462 @@
+//! A small syntax highlighter for code blocks and Typst sources, splitting each line in
+//! tokens: keywords, strings, comments, numbers and calls of the common languages, and the
+//! markup of Typst. Languages it does not know are left as text.
+
+use ratatui::style::{Color, Modifier, Style};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Token {
+    Text,
+    Keyword,
+    String,
+    Comment,
+    Number,
+    /// A function or macro call, or a Typst `#function`
+    Function,
+    /// A Typst `= heading`
+    Heading,
+    /// Typst `*strong*` and `_emphasis_` markers
+    Markup,
+    /// Typst `$math$`
+    Math,
+    /// Typst `<label>` and `@reference`
+    Label,
+}
+
+impl Token {
+    pub fn style(self) -> Style {
+        let style = Style::default();
+        match self {
+            Token::Text => style,
+            Token::Keyword => style.fg(Color::Magenta),
+            Token::String => style.fg(Color::Green),
+            Token::Comment => style.fg(Color::DarkGray),
+            Token::Number | Token::Math => style.fg(Color::Yellow),
+            Token::Function => style.fg(Color::Blue),
+            Token::Heading => style.fg(Color::Cyan).add_modifier(Modifier::BOLD),
+            Token::Markup => style.fg(Color::Red),
+            Token::Label => style.fg(Color::Cyan),
+        }
+    }
+}
+
+/// A line of highlighted text.
+pub type Tokens<'a> = Vec<(Token, &'a str)>;
+
+/// How a language looks.
+struct Syntax {
+    keywords: &'static [&'static str],
+    line_comment: &'static str,
+    block_comment: Option<(&'static str, &'static str)>,
+    /// Whether `'` quotes strings, rather than lifetimes or nothing
+    single_quotes: bool,
+}
+
+const C_LIKE: Syntax = Syntax {
+    keywords: &[
+        "break",
+        "case",
+        "class",
+        "const",
+        "continue",
+        "default",
+        "do",
+        "else",
+        "enum",
+        "extern",
+        "false",
+        "for",
+        "goto",
+        "if",
+        "include",
+        "namespace",
+        "new",
+        "nullptr",
+        "private",
+        "public",
+        "return",
+        "sizeof",
+        "static",
+        "struct",
+        "switch",
+        "template",
+        "this",
+        "true",
+        "typedef",
+        "union",
+        "using",
+        "void",
+        "volatile",
+        "while",
+    ],
+    line_comment: "//",
+    block_comment: Some(("/*", "*/")),
+    single_quotes: true,
+};
+
+fn syntax(lang: &str) -> Option<Syntax> {
+    let syntax = match lang.to_lowercase().as_str() {
+        "rust" | "rs" => Syntax {
+            keywords: &[
+                "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else",
+                "enum", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod",
+                "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super",
+                "trait", "true", "type", "unsafe", "use", "where", "while",
+            ],
+            line_comment: "//",
+            block_comment: Some(("/*", "*/")),
+            single_quotes: false,
+        },
+        "c" | "h" | "cpp" | "c++" | "cc" | "hpp" | "java" | "cs" | "csharp" => C_LIKE,
+        "go" => Syntax {
+            keywords: &[
+                "break",
+                "case",
+                "chan",
+                "const",
+                "continue",
+                "default",
+                "defer",
+                "else",
+                "false",
+                "for",
+                "func",
+                "go",
+                "if",
+                "import",
+                "interface",
+                "map",
+                "nil",
+                "package",
+                "range",
+                "return",
+                "select",
+                "struct",
+                "switch",
+                "true",
+                "type",
+                "var",
+            ],
+            ..C_LIKE
+        },
+        "js" | "javascript" | "ts" | "typescript" | "jsx" | "tsx" => Syntax {
+            keywords: &[
+                "async",
+                "await",
+                "break",
+                "case",
+                "class",
+                "const",
+                "continue",
+                "default",
+                "else",
+                "export",
+                "extends",
+                "false",
+                "for",
+                "from",
+                "function",
+                "if",
+                "import",
+                "in",
+                "interface",
+                "let",
+                "new",
+                "null",
+                "of",
+                "return",
+                "switch",
+                "this",
+                "throw",
+                "true",
+                "try",
+                "catch",
+                "type",
+                "undefined",
+                "var",
+                "while",
+            ],
+            ..C_LIKE
+        },
+        "python" | "py" => Syntax {
+            keywords: &[
+                "and", "as", "async", "await", "break", "class", "continue", "def", "elif", "else",
+                "except", "False", "for", "from", "if", "import", "in", "is", "lambda", "None",
+                "not", "or", "pass", "raise", "return", "True", "try", "while", "with", "yield",
+            ],
+            line_comment: "#",
+            block_comment: None,
+            single_quotes: true,
+        },
+        "sh" | "bash" | "zsh" | "shell" | "fish" => Syntax {
+            keywords: &[
+                "case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function",
+                "if", "in", "local", "return", "then", "while",
+            ],
+            line_comment: "#",
+            block_comment: None,
+            single_quotes: true,
+        },
+        "toml" | "yaml" | "yml" => Syntax {
+            keywords: &["true", "false", "null"],
+            line_comment: "#",
+            block_comment: None,
+            single_quotes: true,
+        },
+        _ => return None,
+    };
+    Some(syntax)
+}
+
+/// Highlight the lines of `text` written in `lang`, a code block language or a note extension
+/// (`typ` for Typst). `None` if the language is unknown.
+pub fn highlight<'a>(lang: &str, text: &'a str) -> Option<Vec<Tokens<'a>>> {
+    if matches!(lang, "typ" | "typst") {
+        return Some(highlight_typst(text));
+    }
+    let syntax = syntax(lang)?;
+
+    let mut in_comment = false;
+    let lines = text
+        .lines()
+        .map(|line| {
+            let mut spans = Vec::new();
+            let mut i = 0;
+            while i < line.len() {
+                let rest = &line[i..];
+                let c = rest.chars().next().unwrap();
+                let (token, len) = if in_comment {
+                    let end = syntax.block_comment.map_or("", |(_, end)| end);
+                    match rest.find(end) {
+                        Some(pos) => {
+                            in_comment = false;
+                            (Token::Comment, pos + end.len())
+                        }
+                        None => (Token::Comment, rest.len()),
+                    }
+                } else if rest.starts_with(syntax.line_comment) {
+                    (Token::Comment, rest.len())
+                } else if let Some((start, _)) = syntax.block_comment
+                    && rest.starts_with(start)
+                {
+                    in_comment = true;
+                    (Token::Comment, start.len())
+                } else if c == '"' || (c == '\'' && syntax.single_quotes) {
+                    (Token::String, quoted(rest, c))
+                } else if c.is_ascii_digit() {
+                    (
+                        Token::Number,
+                        word_len(rest, |c| c.is_alphanumeric() || c == '.'),
+                    )
+                } else if is_ident_start(c) {
+                    let len = word_len(rest, is_ident_char);
+                    let token = if syntax.keywords.contains(&&rest[..len]) {
+                        Token::Keyword
+                    } else if rest[len..].starts_with(['(', '!']) {
+                        Token::Function
+                    } else {
+                        Token::Text
+                    };
+                    (token, len)
+                } else {
+                    (Token::Text, c.len_utf8())
+                };
+                push(&mut spans, token, i, i + len);
+                i += len;
+            }
+            tokens(line, spans)
+        })
+        .collect();
+    Some(lines)
+}
+
+const TYPST_KEYWORDS: [&str; 12] = [
+    "let", "set", "show", "import", "include", "if", "else", "for", "in", "while", "return",
+    "context",
+];
+
+/// Highlight Typst markup: headings, `#` code, math, strings, comments, labels and emphasis.
+fn highlight_typst(text: &str) -> Vec<Tokens<'_>> {
+    let mut in_comment = false;
+    let mut in_math = false;
+    text.lines()
+        .map(|line| {
+            if !in_comment && !in_math && line.trim_start().starts_with('=') {
+                return vec![(Token::Heading, line)];
+            }
+
+            let mut spans = Vec::new();
+            let mut i = 0;
+            while i < line.len() {
+                let rest = &line[i..];
+                let c = rest.chars().next().unwrap();
+                let (token, len) = if in_comment {
+                    match rest.find("*/") {
+                        Some(pos) => {
+                            in_comment = false;
+                            (Token::Comment, pos + 2)
+                        }
+                        None => (Token::Comment, rest.len()),
+                    }
+                } else if in_math {
+                    match rest.find('$') {
+                        Some(pos) => {
+                            in_math = false;
+                            (Token::Math, pos + 1)
+                        }
+                        None => (Token::Math, rest.len()),
+                    }
+                } else if rest.starts_with("//") {
+                    (Token::Comment, rest.len())
+                } else if rest.starts_with("/*") {
+                    in_comment = true;
+                    (Token::Comment, 2)
+                } else if c == '\\' {
+                    // An escaped character
+                    let next = rest[1..].chars().next().map_or(0, char::len_utf8);
+                    (Token::Text, 1 + next)
+                } else if c == '$' {
+                    in_math = true;
+                    (Token::Math, 1)
+                } else if c == '"' {
+                    (Token::String, quoted(rest, c))
+                } else if c == '#' && rest[1..].starts_with(is_ident_start) {
+                    let len =
+                        1 + word_len(&rest[1..], |c| is_ident_char(c) || c == '.' || c == '-');
+                    let token = if TYPST_KEYWORDS.contains(&&rest[1..len]) {
+                        Token::Keyword
+                    } else {
+                        Token::Function
+                    };
+                    (token, len)
+                } else if c == '<'
+                    && let Some(end) = rest.find('>')
+                    && end > 1
+                    && rest[1..end]
+                        .chars()
+                        .all(|c| is_ident_char(c) || ":.-".contains(c))
+                {
+                    (Token::Label, end + 1)
+                } else if c == '@' && rest[1..].starts_with(is_ident_start) {
+                    (
+                        Token::Label,
+                        1 + word_len(&rest[1..], |c| is_ident_char(c) || c == ':' || c == '-'),
+                    )
+                } else if c == '*' || c == '_' {
+                    (Token::Markup, 1)
+                } else {
+                    (Token::Text, c.len_utf8())
+                };
+                push(&mut spans, token, i, i + len);
+                i += len;
+            }
+            tokens(line, spans)
+        })
+        .collect()
+}
+
+/// Add the token of the bytes `start..end` of a line, merged with the previous one when they
+/// are of the same kind.
+fn push(spans: &mut Vec<(Token, usize, usize)>, token: Token, start: usize, end: usize) {
+    match spans.last_mut() {
+        Some((last, _, last_end)) if *last == token => *last_end = end,
+        _ => spans.push((token, start, end)),
+    }
+}
+
+fn tokens(line: &str, spans: Vec<(Token, usize, usize)>) -> Tokens<'_> {
+    spans
+        .into_iter()
+        .map(|(token, start, end)| (token, &line[start..end]))
+        .collect()
+}
+
+/// The length of the string starting `rest` with `quote`, up to its closing quote or the end
+/// of the line.
+fn quoted(rest: &str, quote: char) -> usize {
+    let mut escaped = false;
+    for (i, c) in rest.char_indices().skip(1) {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == quote {
+            return i + 1;
+        }
+    }
+    rest.len()
+}
+
+fn word_len(rest: &str, f: impl Fn(char) -> bool) -> usize {
+    rest.find(|c| !f(c)).unwrap_or(rest.len())
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_highlight() {
+        assert_eq!(
+            highlight("rust", "let s = \"a \\\" b\"; // note\nfoo(1.5) /* x").unwrap(),
+            [
+                vec![
+                    (Token::Keyword, "let"),
+                    (Token::Text, " s = "),
+                    (Token::String, "\"a \\\" b\""),
+                    (Token::Text, "; "),
+                    (Token::Comment, "// note"),
+                ],
+                vec![
+                    (Token::Function, "foo"),
+                    (Token::Text, "("),
+                    (Token::Number, "1.5"),
+                    (Token::Text, ") "),
+                    (Token::Comment, "/* x"),
+                ],
+            ]
+        );
+        assert_eq!(
+            highlight("py", "# done\nx = 'a'").unwrap()[1],
+            [(Token::Text, "x = "), (Token::String, "'a'")]
+        );
+        assert_eq!(highlight("brainfuck", "+++"), None);
+
+        assert_eq!(
+            highlight(
+                "typ",
+                "= Title <intro>\n#let x = \"s\" // c\nSee @intro, *bold* $a +\nb$ \\#x"
+            )
+            .unwrap(),
+            [
+                vec![(Token::Heading, "= Title <intro>")],
+                vec![
+                    (Token::Keyword, "#let"),
+                    (Token::Text, " x = "),
+                    (Token::String, "\"s\""),
+                    (Token::Text, " "),
+                    (Token::Comment, "// c"),
+                ],
+                vec![
+                    (Token::Text, "See "),
+                    (Token::Label, "@intro"),
+                    (Token::Text, ", "),
+                    (Token::Markup, "*"),
+                    (Token::Text, "bold"),
+                    (Token::Markup, "*"),
+                    (Token::Text, " "),
+                    (Token::Math, "$a +"),
+                ],
+                vec![(Token::Math, "b$"), (Token::Text, " \\#x")],
+            ]
+        );
+    }
+}
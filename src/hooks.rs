@@ -0,0 +1,162 @@
+use anyhow::{Context, Result, bail};
+use std::{ffi::OsString, path::Path, process::Command};
+
+use crate::metadata::{NoteMetadata, read_metadata};
+use crate::vault::Note;
+
+/// A point in the life of a note where the user can run a command, set by the `hooks` section
+/// of the config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    /// Before a note is created. Failing aborts the creation
+    PreNew,
+    /// After a note is created, e.g. to give it an ID
+    PostNew,
+    /// After the editor of a note exits, e.g. to commit the change
+    PostEdit,
+    /// Before a note is deleted. Failing aborts the deletion
+    PreDelete,
+}
+
+impl Hook {
+    pub fn name(self) -> &'static str {
+        match self {
+            Hook::PreNew => "pre_new",
+            Hook::PostNew => "post_new",
+            Hook::PostEdit => "post_edit",
+            Hook::PreDelete => "pre_delete",
+        }
+    }
+
+    /// The environment variable holding the command, set from the config.
+    pub fn env_var(self) -> String {
+        format!("NOXE_HOOK_{}", self.name().to_uppercase())
+    }
+
+    fn aborts(self) -> bool {
+        matches!(self, Hook::PreNew | Hook::PreDelete)
+    }
+}
+
+/// Whether a command is set for the hook.
+pub fn is_set(hook: Hook) -> bool {
+    std::env::var_os(hook.env_var()).is_some_and(|c| !c.is_empty())
+}
+
+/// Run the command of a hook, if one is set, with the note in its environment. The metadata
+/// is read from the note, unless given, as for notes that do not exist yet.
+pub fn run(hook: Hook, note: &Path, metadata: Option<&NoteMetadata>) -> Result<()> {
+    let Some(command) = std::env::var_os(hook.env_var()).filter(|c| !c.is_empty()) else {
+        return Ok(());
+    };
+
+    let read;
+    let metadata = match metadata {
+        Some(metadata) => metadata,
+        None => {
+            read = note
+                .main_file_path()
+                .and_then(|main_file| read_metadata(&main_file))
+                .unwrap_or_default();
+            &read
+        }
+    };
+
+    let status = shell(&command)
+        .envs(hook_env(hook, note, metadata))
+        .status()
+        .with_context(|| format!("Failed to run the {} hook", hook.name()))?;
+    if !status.success() {
+        if hook.aborts() {
+            bail!("The {} hook exited with {}, aborting", hook.name(), status);
+        }
+        eprintln!("Warning: the {} hook exited with {}", hook.name(), status);
+    }
+    Ok(())
+}
+
+/// The command run by the shell, so that hooks can use pipes and `$NOXE_NOTE`.
+fn shell(command: &OsString) -> Command {
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C");
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c");
+        cmd
+    };
+    cmd.arg(command);
+    cmd
+}
+
+/// `NOXE_HOOK`, the path of the note as `NOXE_NOTE`, its main file once it exists as
+/// `NOXE_NOTE_FILE` and its metadata as `NOXE_NOTE_*`, all of it in `NOXE_NOTE_METADATA` as
+/// JSON.
+fn hook_env(hook: Hook, note: &Path, metadata: &NoteMetadata) -> Vec<(&'static str, OsString)> {
+    let mut env = vec![
+        ("NOXE_HOOK", hook.name().into()),
+        ("NOXE_NOTE", note.into()),
+        ("NOXE_NOTE_NAME", note.note_name().into()),
+        (
+            "NOXE_NOTE_TITLE",
+            metadata.title.clone().unwrap_or_default().into(),
+        ),
+        (
+            "NOXE_NOTE_AUTHOR",
+            metadata.author.clone().unwrap_or_default().into(),
+        ),
+        ("NOXE_NOTE_KEYWORDS", metadata.keywords.join(",").into()),
+        (
+            "NOXE_NOTE_DATE",
+            metadata.date.clone().unwrap_or_default().into(),
+        ),
+        (
+            "NOXE_NOTE_METADATA",
+            serde_json::to_string(metadata).unwrap_or_default().into(),
+        ),
+    ];
+    if let Ok(main_file) = note.main_file_path()
+        && main_file.exists()
+    {
+        env.push(("NOXE_NOTE_FILE", main_file.into()));
+    }
+    env
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_hook_env() {
+        let tmp_dir = tempdir().unwrap();
+        let note = tmp_dir.path().join("rust.md");
+        fs::write(
+            &note,
+            "---\ntitle: Rust\nkeywords: [lang, systems]\nid: 42\n---\n",
+        )
+        .unwrap();
+
+        assert_eq!(Hook::PostEdit.env_var(), "NOXE_HOOK_POST_EDIT");
+        let metadata = read_metadata(&note).unwrap();
+        let env = hook_env(Hook::PostEdit, &note, &metadata);
+        let get = |name: &str| {
+            env.iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, v)| v.to_string_lossy().to_string())
+        };
+        assert_eq!(get("NOXE_HOOK").as_deref(), Some("post_edit"));
+        assert_eq!(get("NOXE_NOTE_NAME").as_deref(), Some("rust"));
+        assert_eq!(get("NOXE_NOTE_TITLE").as_deref(), Some("Rust"));
+        assert_eq!(get("NOXE_NOTE_KEYWORDS").as_deref(), Some("lang,systems"));
+        assert_eq!(get("NOXE_NOTE_FILE"), Some(note.display().to_string()));
+        assert!(get("NOXE_NOTE_METADATA").unwrap().contains("\"id\":42"));
+
+        // A note to create has no main file yet
+        let env = hook_env(Hook::PreNew, &tmp_dir.path().join("new.md"), &metadata);
+        assert!(!env.iter().any(|(n, _)| *n == "NOXE_NOTE_FILE"));
+    }
+}
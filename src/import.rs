@@ -0,0 +1,820 @@
+use anyhow::{Context, Result, bail};
+use regex::{Captures, Regex};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    io::{Cursor, Read, Seek},
+    path::{Component, Path, PathBuf},
+    sync::LazyLock,
+};
+use walkdir::WalkDir;
+
+use crate::cli::{ImportCommand, NoteType};
+use crate::links::{LinkIndex, rewrite_wiki_links, wiki_links};
+use crate::metadata::{parse_frontmatter, remove_field, set_field};
+use crate::serve::percent_decode;
+
+/// An Obsidian embed or link to a file: `![[photo.png|300]]`, `[[paper.pdf]]`
+static FILE_LINK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(!?)\[\[([^\]\|#]+)(?:\|([^\]]*))?\]\]").unwrap());
+/// A Markdown image: `![alt](path)`
+static MD_IMAGE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"!\[([^\]]*)\]\(([^)\s]+)\)").unwrap());
+/// A Markdown link or image: `[text](url)`, `![alt](url)`
+static MD_LINK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(!?)\[([^\]]*)\]\(([^)\s]+)\)").unwrap());
+/// The id Notion appends to the names of pages: `Projects 0123456789abcdef0123456789abcdef`
+static NOTION_ID: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(.*?)\s*([0-9a-f]{32})$").unwrap());
+/// A link to a page on notion.so, ending with its id
+static NOTION_URL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"notion\.so/[^)\s]*?([0-9a-f]{32})\b").unwrap());
+/// An Obsidian image size, given where the alias goes: `300`, `300x200`
+static IMAGE_SIZE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\d+(x\d+)?$").unwrap());
+
+pub fn process_import_command(command: ImportCommand) -> Result<()> {
+    match command {
+        ImportCommand::Obsidian {
+            vault,
+            category,
+            dry_run,
+            note_root,
+        } => {
+            let note_root = Path::new(&note_root);
+            let category = category.unwrap_or_default();
+            check_relative(&category)?;
+            let import = obsidian(Path::new(&vault), note_root, &category)?;
+            finish(&import, note_root, dry_run)?;
+        }
+        ImportCommand::Notion {
+            export,
+            category,
+            dry_run,
+            note_root,
+        } => {
+            let category = category.unwrap_or_default();
+            check_relative(&category)?;
+            let import = notion(Path::new(&export), &category)?;
+            finish(&import, Path::new(&note_root), dry_run)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Write the import, unless it is a dry run, and report it.
+fn finish(import: &Import, note_root: &Path, dry_run: bool) -> Result<()> {
+    if dry_run {
+        println!("Would import notes:");
+    } else {
+        import.write(note_root)?;
+        println!("Imported notes:");
+    }
+    for note in &import.notes {
+        println!("{}", note.display());
+    }
+    println!(
+        "{} attachments, {} left out as no note links them",
+        import.attachments, import.unlinked
+    );
+
+    if !import.unresolved.is_empty() {
+        eprintln!("Unresolved links:");
+        for (note, target) in &import.unresolved {
+            eprintln!("{}: {}", note.display(), target);
+        }
+    }
+
+    Ok(())
+}
+
+/// What an import writes, relative to the note root.
+#[derive(Debug, Default)]
+pub struct Import {
+    /// The contents of the main files of the notes
+    texts: BTreeMap<PathBuf, String>,
+    /// Attachments, and where they are copied from
+    files: BTreeMap<PathBuf, Source>,
+    /// The imported notes
+    pub notes: Vec<PathBuf>,
+    pub attachments: usize,
+    /// Attachments no note links, which are not imported
+    pub unlinked: usize,
+    /// The notes, and the targets of their links that resolve to nothing
+    pub unresolved: Vec<(PathBuf, String)>,
+}
+
+impl Import {
+    /// Write the notes and their attachments. Nothing is written if any of them already exists.
+    pub fn write(&self, note_root: &Path) -> Result<()> {
+        for path in self.notes.iter().chain(self.texts.keys()) {
+            let target = note_root.join(path);
+            if target.exists() {
+                bail!("'{}' already exists", target.display());
+            }
+        }
+
+        for (path, text) in &self.texts {
+            let target = note_root.join(path);
+            create_parent(&target)?;
+            fs::write(&target, text)
+                .with_context(|| format!("Failed to write '{}'", target.display()))?;
+        }
+        for (path, source) in &self.files {
+            let target = note_root.join(path);
+            create_parent(&target)?;
+            match source {
+                Source::File(file) => fs::copy(file, &target).map(|_| ()),
+                Source::Bytes(data) => fs::write(&target, data),
+            }
+            .with_context(|| format!("Failed to write '{}'", target.display()))?;
+        }
+
+        Ok(())
+    }
+
+    /// Add the note `key`, a dirnote when it has attachments, named in its `images/`.
+    fn add_note(
+        &mut self,
+        key: PathBuf,
+        dirnote: bool,
+        content: String,
+        attachments: BTreeMap<String, Source>,
+        unresolved: Vec<String>,
+    ) -> Result<()> {
+        let dirnote = dirnote || !attachments.is_empty();
+        let main_file = if dirnote {
+            key.join("main.md")
+        } else {
+            let mut file = key.clone().into_os_string();
+            file.push(".md");
+            PathBuf::from(file)
+        };
+
+        for (name, source) in attachments {
+            self.files.insert(key.join("images").join(name), source);
+            self.attachments += 1;
+        }
+        self.unresolved.extend(
+            unresolved
+                .into_iter()
+                .map(|target| (main_file.clone(), target)),
+        );
+        if self.texts.insert(main_file.clone(), content).is_some() {
+            bail!("Two notes would be imported as '{}'", main_file.display());
+        }
+        self.notes.push(if dirnote { key } else { main_file });
+
+        Ok(())
+    }
+}
+
+/// Where an attachment is copied from.
+#[derive(Debug)]
+enum Source {
+    File(PathBuf),
+    Bytes(Vec<u8>),
+}
+
+fn create_parent(path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory '{}'", parent.display()))?;
+    }
+    Ok(())
+}
+
+/// Refuse a category that could escape the note root.
+fn check_relative(path: &Path) -> Result<()> {
+    if !path.components().all(|c| matches!(c, Component::Normal(_))) {
+        bail!("Invalid category '{}'", path.display());
+    }
+    Ok(())
+}
+
+/// Plan the import of an Obsidian vault into `category` of the note root.
+///
+/// - A folder note, `Recipes/Recipes.md` alone in its folder with the other notes of the
+///   folder, becomes the dirnote `Recipes/`.
+/// - A note embedding or linking attachments becomes a dirnote, and its attachments are
+///   copied to its `images/` directory.
+/// - `tags` become `keywords`, `created` becomes `date`, and the title is the file name.
+/// - Wiki-links with a path are rewritten to the paths of the imported notes.
+pub fn obsidian(vault: &Path, note_root: &Path, category: &Path) -> Result<Import> {
+    if !vault.is_dir() {
+        bail!("'{}' is not a directory", vault.display());
+    }
+
+    // Hidden entries are Obsidian's settings (`.obsidian/`) and trash (`.trash/`)
+    let mut pages = Vec::new();
+    let mut files = Vec::new();
+    for entry in WalkDir::new(vault)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| e.depth() == 0 || !e.file_name().to_string_lossy().starts_with('.'))
+    {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let rel_path = entry.path().strip_prefix(vault)?.to_path_buf();
+        if rel_path.extension().is_some_and(|ext| ext == "md") {
+            pages.push(rel_path);
+        } else {
+            files.push(rel_path);
+        }
+    }
+
+    let vault_files = VaultFiles::new(&files);
+    let keys: HashMap<&PathBuf, String> = pages
+        .iter()
+        .map(|page| {
+            let key = if is_folder_note(page, &pages) {
+                page.parent().unwrap().to_path_buf()
+            } else {
+                page.with_extension("")
+            };
+            (page, slash_path(&category.join(key)))
+        })
+        .collect();
+    let links = LinkIndex::new(note_root).ok();
+    let resolve = |target: &str| -> Option<&String> {
+        let target = target.trim_start_matches("./");
+        let target = target.strip_suffix(".md").unwrap_or(target).to_lowercase();
+        pages
+            .iter()
+            .find(|page| {
+                let page = if target.contains('/') {
+                    slash_path(&page.with_extension(""))
+                } else {
+                    page.file_stem().unwrap().to_string_lossy().to_string()
+                };
+                page.to_lowercase() == target
+            })
+            .map(|page| &keys[page])
+    };
+
+    let mut import = Import::default();
+    let mut linked = Vec::new();
+    for page in &pages {
+        let source = vault.join(page);
+        let content = fs::read_to_string(&source)
+            .with_context(|| format!("Failed to read '{}'", source.display()))?;
+        let key = PathBuf::from(&keys[page]);
+        let title = page.file_stem().unwrap().to_string_lossy();
+        let dir = page.parent().unwrap_or(Path::new(""));
+
+        let mut unresolved = Vec::new();
+        let mut attachments = Attachments::default();
+        let content = FILE_LINK.replace_all(&content, |c: &Captures| {
+            let target = c[2].trim();
+            if Path::new(target).extension().is_none_or(|ext| ext == "md") {
+                return c[0].to_string();
+            }
+            let Some(file) = vault_files.find(dir, target) else {
+                // A note with a dot in its name, like `v1.2`
+                if resolve(target).is_none() {
+                    unresolved.push(target.to_string());
+                }
+                return c[0].to_string();
+            };
+            let name = attachments.add(file);
+            let text = c
+                .get(3)
+                .map(|alias| alias.as_str().trim())
+                .filter(|alias| !IMAGE_SIZE.is_match(alias));
+            match &c[1] {
+                "!" => format!("![{}](images/{})", text.unwrap_or(""), name),
+                _ => format!("[{}](images/{})", text.unwrap_or(&name), name),
+            }
+        });
+        let content = MD_IMAGE.replace_all(&content, |c: &Captures| {
+            let target = &c[2];
+            if target.contains("://") || target.starts_with("data:") {
+                return c[0].to_string();
+            }
+            match vault_files.find(dir, &percent_decode(target)) {
+                Some(file) => format!("![{}](images/{})", &c[1], attachments.add(file)),
+                None => c[0].to_string(),
+            }
+        });
+        let content = rewrite_wiki_links(&content, |target| {
+            let key = resolve(target)?;
+            target.contains('/').then(|| key.clone())
+        });
+        unresolved.extend(wiki_links(&content).into_iter().filter(|target| {
+            Path::new(target).extension().is_none_or(|ext| ext == "md")
+                && resolve(target).is_none()
+                && links
+                    .as_ref()
+                    .is_none_or(|links| links.resolve(target).is_none())
+        }));
+        let content = translate_frontmatter(&content, &title)?;
+
+        let attachments = attachments
+            .names
+            .into_iter()
+            .map(|(name, file)| {
+                linked.push(file);
+                (name, Source::File(vault.join(file)))
+            })
+            .collect();
+        let folder_note = is_folder_note(page, &pages);
+        import.add_note(key, folder_note, content, attachments, unresolved)?;
+    }
+
+    linked.sort();
+    linked.dedup();
+    import.unlinked = files.len() - linked.len();
+
+    Ok(import)
+}
+
+/// Whether the note is the folder note of its folder: named after it, and the only note in it.
+fn is_folder_note(page: &Path, pages: &[PathBuf]) -> bool {
+    let Some(dir) = page.parent().filter(|dir| !dir.as_os_str().is_empty()) else {
+        return false;
+    };
+    dir.file_name() == page.file_stem()
+        && pages
+            .iter()
+            .all(|other| other == page || !other.starts_with(dir))
+}
+
+/// Resolve `.` and `..` in a path, without the file system.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normal = PathBuf::new();
+    for c in path.components() {
+        match c {
+            Component::ParentDir => {
+                normal.pop();
+            }
+            Component::CurDir => {}
+            c => normal.push(c),
+        }
+    }
+    normal
+}
+
+fn slash_path(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+/// The attachments of the vault, found as Obsidian does: by path, else by file name.
+struct VaultFiles<'a> {
+    files: &'a [PathBuf],
+    by_name: HashMap<String, Vec<&'a PathBuf>>,
+}
+
+impl<'a> VaultFiles<'a> {
+    fn new(files: &'a [PathBuf]) -> Self {
+        let mut by_name: HashMap<String, Vec<&PathBuf>> = HashMap::new();
+        for file in files {
+            let name = file.file_name().unwrap().to_string_lossy().to_lowercase();
+            by_name.entry(name).or_default().push(file);
+        }
+        VaultFiles { files, by_name }
+    }
+
+    /// Find a file linked from a note of `dir`. Among files of the same name, the one closest
+    /// to the note wins.
+    fn find(&self, dir: &Path, target: &str) -> Option<&'a PathBuf> {
+        let by_path = |path: &Path| {
+            let path = slash_path(&normalize(path)).to_lowercase();
+            self.files
+                .iter()
+                .find(|file| slash_path(file).to_lowercase() == path)
+        };
+        if let Some(file) = by_path(&dir.join(target)).or_else(|| by_path(Path::new(target))) {
+            return Some(file);
+        }
+
+        let name = Path::new(target)
+            .file_name()?
+            .to_string_lossy()
+            .to_lowercase();
+        self.by_name.get(&name)?.iter().copied().max_by_key(|file| {
+            let shared = file
+                .components()
+                .zip(dir.components())
+                .take_while(|(a, b)| a == b)
+                .count();
+            (shared, std::cmp::Reverse(file.components().count()))
+        })
+    }
+}
+
+/// The attachments of a note, by their names in its `images/` directory.
+#[derive(Default)]
+struct Attachments<'a> {
+    names: BTreeMap<String, &'a PathBuf>,
+}
+
+impl<'a> Attachments<'a> {
+    /// The name of the file in `images/`, numbered if another file has its name.
+    fn add(&mut self, file: &'a PathBuf) -> String {
+        if let Some((name, _)) = self.names.iter().find(|(_, f)| **f == file) {
+            return name.clone();
+        }
+        let name = file.file_name().unwrap().to_string_lossy().to_string();
+        let mut unique = name.clone();
+        let mut n = 1;
+        while self.names.contains_key(&unique) {
+            n += 1;
+            unique = format!("{}-{}", n, name);
+        }
+        self.names.insert(unique.clone(), file);
+        unique
+    }
+}
+
+/// Translate the frontmatter of an Obsidian note: `tags` become `keywords`, `created` becomes
+/// `date`, and a title is set. Other fields, like `aliases`, are kept.
+fn translate_frontmatter(content: &str, title: &str) -> Result<String> {
+    let fields = parse_frontmatter(content);
+    let mut content = content.to_string();
+
+    let mut keywords = fields.get("keywords").map(tag_list).unwrap_or_default();
+    let mut tagged = false;
+    for key in ["tags", "tag"] {
+        let Some(tags) = fields.get(key) else {
+            continue;
+        };
+        for tag in tag_list(tags) {
+            if !keywords.contains(&tag) {
+                keywords.push(tag);
+            }
+        }
+        content = remove_field(&content, NoteType::Md, key)?;
+        tagged = true;
+    }
+    if tagged {
+        let keywords = keywords.into_iter().map(Value::String).collect();
+        content = set_field(&content, NoteType::Md, "keywords", &Value::Array(keywords))?;
+    }
+    if !fields.contains_key("date")
+        && let Some(created) = fields.get("created")
+    {
+        content = remove_field(&content, NoteType::Md, "created")?;
+        content = set_field(&content, NoteType::Md, "date", created)?;
+    }
+    if !fields.contains_key("title") {
+        content = set_field(&content, NoteType::Md, "title", &Value::from(title))?;
+    }
+
+    Ok(content)
+}
+
+/// Obsidian tags, given as a list or as a string separated by commas or spaces, without `#`.
+fn tag_list(value: &Value) -> Vec<String> {
+    let tags: Vec<String> = match value {
+        Value::Array(values) => values
+            .iter()
+            .map(|v| v.as_str().map_or_else(|| v.to_string(), str::to_string))
+            .collect(),
+        Value::String(s) => s.split([',', ' ']).map(str::to_string).collect(),
+        _ => Vec::new(),
+    };
+    tags.into_iter()
+        .map(|tag| tag.trim().trim_start_matches('#').to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
+
+/// Plan the import of a Notion export (Markdown & CSV) into `category` of the note root.
+///
+/// - The ids Notion appends to page names are dropped, numbering pages left with the same name.
+/// - A page with subpages becomes a category holding them, the page itself named after it:
+///   `Work/Work`.
+/// - Links between pages, relative or to notion.so, become wiki-links.
+/// - A page with images or files becomes a dirnote, and they are copied to its `images/`.
+pub fn notion(export: &Path, category: &Path) -> Result<Import> {
+    let file =
+        fs::File::open(export).with_context(|| format!("Failed to open '{}'", export.display()))?;
+    let mut entries = BTreeMap::new();
+    read_zip(file, &mut entries)
+        .with_context(|| format!("Failed to read '{}'", export.display()))?;
+    let entries = strip_wrapper(entries);
+    let pages: Vec<&PathBuf> = entries
+        .keys()
+        .filter(|path| path.extension().is_some_and(|ext| ext == "md"))
+        .collect();
+
+    let mut keys: HashMap<&PathBuf, PathBuf> = HashMap::new();
+    let mut by_id: HashMap<&str, &PathBuf> = HashMap::new();
+    let mut taken = HashSet::new();
+    for &page in &pages {
+        // The subpages and files of a page are in the directory named after it
+        let dir = page.with_extension("");
+        let clean: PathBuf = dir
+            .components()
+            .map(|c| split_id(&c.as_os_str().to_string_lossy()).0.to_string())
+            .collect();
+        let name = clean.file_name().unwrap().to_string_lossy().to_string();
+        let base = if pages.iter().any(|other| other.starts_with(&dir)) {
+            category.join(&clean).join(&name)
+        } else {
+            category.join(&clean)
+        };
+
+        let mut key = base.clone();
+        let mut n = 1;
+        while !taken.insert(slash_path(&key).to_lowercase()) {
+            n += 1;
+            key = base.with_file_name(format!("{} {}", name, n));
+        }
+        keys.insert(page, key);
+        if let (_, Some(id)) = split_id(page.file_stem().unwrap().to_str().unwrap_or_default()) {
+            by_id.insert(id, page);
+        }
+    }
+
+    let mut import = Import::default();
+    let mut linked = HashSet::new();
+    for &page in &pages {
+        let content = String::from_utf8_lossy(&entries[page]);
+        let dir = page.parent().unwrap_or(Path::new(""));
+        let mut unresolved = Vec::new();
+        let mut attachments = Attachments::default();
+
+        let content = MD_LINK.replace_all(&content, |c: &Captures| {
+            let (bang, text, url) = (&c[1], &c[2], &c[3]);
+            let wiki_link = |target: &PathBuf| {
+                let key = &keys[target];
+                if text.is_empty() || key.file_name().is_some_and(|name| name == text) {
+                    format!("[[{}]]", slash_path(key))
+                } else {
+                    format!("[[{}|{}]]", slash_path(key), text)
+                }
+            };
+
+            if url.contains("://") {
+                return match NOTION_URL
+                    .captures(url)
+                    .and_then(|id| by_id.get(&id[1]).copied())
+                {
+                    Some(target) => wiki_link(target),
+                    None => c[0].to_string(),
+                };
+            }
+            let target = percent_decode(url);
+            let path = normalize(&dir.join(&target));
+            match entries.get_key_value(&path) {
+                Some((page, _)) if keys.contains_key(page) => wiki_link(page),
+                Some((file, _)) => format!("{}[{}](images/{})", bang, text, attachments.add(file)),
+                None => {
+                    if path.extension().is_some_and(|ext| ext == "md") {
+                        unresolved.push(target);
+                    }
+                    c[0].to_string()
+                }
+            }
+        });
+
+        let attachments = attachments
+            .names
+            .into_iter()
+            .map(|(name, file)| {
+                linked.insert(file);
+                (name, Source::Bytes(entries[file].clone()))
+            })
+            .collect();
+        let key = keys[page].clone();
+        import.add_note(key, false, content.into_owned(), attachments, unresolved)?;
+    }
+    // Databases are exported as a CSV file next to the directory of their pages
+    import.unlinked = entries.len() - pages.len() - linked.len();
+
+    Ok(import)
+}
+
+/// A name without the id Notion appends to it, and the id.
+fn split_id(name: &str) -> (&str, Option<&str>) {
+    match NOTION_ID.captures(name) {
+        Some(c) if !c[1].is_empty() => {
+            (c.get(1).unwrap().as_str(), Some(c.get(2).unwrap().as_str()))
+        }
+        _ => (name, None),
+    }
+}
+
+/// Read the files of a zip archive. Large Notion exports are zips of zips, one per part.
+fn read_zip(reader: impl Read + Seek, entries: &mut BTreeMap<PathBuf, Vec<u8>>) -> Result<()> {
+    let mut archive = zip::ZipArchive::new(reader)?;
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        if file.is_dir() {
+            continue;
+        }
+        let Some(path) = file.enclosed_name() else {
+            bail!("Invalid path '{}' in the archive", file.name());
+        };
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+
+        if path.extension().is_some_and(|ext| ext == "zip") {
+            read_zip(Cursor::new(data), entries)?;
+        } else {
+            entries.insert(path, data);
+        }
+    }
+    Ok(())
+}
+
+/// Drop the directory all the files are in, like the `Export-<id>/` of some exports.
+fn strip_wrapper(entries: BTreeMap<PathBuf, Vec<u8>>) -> BTreeMap<PathBuf, Vec<u8>> {
+    let Some(wrapper) = entries
+        .keys()
+        .next()
+        .and_then(|path| path.components().next())
+        .map(|c| PathBuf::from(c.as_os_str()))
+    else {
+        return entries;
+    };
+    if !entries
+        .keys()
+        .all(|path| path.starts_with(&wrapper) && path != &wrapper)
+    {
+        return entries;
+    }
+    entries
+        .into_iter()
+        .map(|(path, data)| (path.strip_prefix(&wrapper).unwrap().to_path_buf(), data))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn write(path: PathBuf, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_obsidian() {
+        let tmp_dir = tempdir().unwrap();
+        let vault = tmp_dir.path().join("vault");
+        let root = tmp_dir.path().join("notes");
+        fs::create_dir(&root).unwrap();
+        write(vault.join(".obsidian/app.json"), "{}");
+        write(vault.join("Recipes/Recipes.md"), "# Recipes\n");
+        write(
+            vault.join("Daily/2024-01-01.md"),
+            "![[pic.png|300]] ![cake](../attachments/cake%201.jpg)\n[[Recipes]] [[Missing]] [[gone.pdf]]\n",
+        );
+        write(vault.join("attachments/pic.png"), "png");
+        write(vault.join("attachments/cake 1.jpg"), "jpg");
+        write(vault.join("attachments/unused.png"), "png");
+        write(
+            vault.join("Ideas.md"),
+            "---\ntags: [rust, '#async']\ncreated: 2024-01-02\naliases: [thoughts]\n---\n\nSee [[Daily/2024-01-01|the day]].\n",
+        );
+
+        let import = obsidian(&vault, &root, Path::new("")).unwrap();
+        assert_eq!(
+            import.notes,
+            [
+                PathBuf::from("Daily/2024-01-01"),
+                PathBuf::from("Ideas.md"),
+                PathBuf::from("Recipes")
+            ]
+        );
+        assert_eq!((import.attachments, import.unlinked), (2, 1));
+        assert_eq!(
+            import.unresolved,
+            [
+                (PathBuf::from("Daily/2024-01-01/main.md"), "gone.pdf".into()),
+                (PathBuf::from("Daily/2024-01-01/main.md"), "Missing".into())
+            ]
+        );
+
+        import.write(&root).unwrap();
+        let daily = fs::read_to_string(root.join("Daily/2024-01-01/main.md")).unwrap();
+        assert!(daily.contains("![](images/pic.png) ![cake](images/cake 1.jpg)\n"));
+        assert_eq!(
+            fs::read(root.join("Daily/2024-01-01/images/pic.png")).unwrap(),
+            b"png"
+        );
+        assert!(root.join("Recipes/main.md").is_file());
+        let ideas = fs::read_to_string(root.join("Ideas.md")).unwrap();
+        assert!(ideas.contains("[[Daily/2024-01-01|the day]]"));
+        let fields = parse_frontmatter(&ideas);
+        assert_eq!(fields["keywords"], serde_json::json!(["rust", "async"]));
+        assert_eq!(fields["title"], "Ideas");
+        assert!(fields.contains_key("date") && fields.contains_key("aliases"));
+        assert!(!fields.contains_key("tags") && !fields.contains_key("created"));
+
+        // Importing again would overwrite the notes
+        assert!(import.write(&root).is_err());
+
+        let import = obsidian(&vault, &root, Path::new("obsidian")).unwrap();
+        assert!(
+            import
+                .notes
+                .contains(&PathBuf::from("obsidian/Daily/2024-01-01"))
+        );
+        assert!(
+            import.texts[Path::new("obsidian/Ideas.md")]
+                .contains("[[obsidian/Daily/2024-01-01|the day]]")
+        );
+    }
+
+    #[test]
+    fn test_notion() {
+        use std::io::Write;
+        use zip::write::SimpleFileOptions;
+
+        fn zip(files: Vec<(String, Vec<u8>)>) -> Vec<u8> {
+            let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+            for (name, data) in files {
+                writer
+                    .start_file(name, SimpleFileOptions::default())
+                    .unwrap();
+                writer.write_all(&data).unwrap();
+            }
+            writer.finish().unwrap().into_inner()
+        }
+
+        let work = "0123456789abcdef0123456789abcdef";
+        let projects = "fedcba9876543210fedcba9876543210";
+        let part = zip(vec![
+            (
+                format!("Export-1/Work {work}.md"),
+                format!(
+                    "# Work\n\n[Projects](Work%20{work}/Projects%20{projects}.md)\n\n![diagram.png](Work%20{work}/diagram.png)\n"
+                )
+                .into_bytes(),
+            ),
+            (
+                format!("Export-1/Work {work}/Projects {projects}.md"),
+                format!(
+                    "# Projects\n\n[Up](https://www.notion.so/Work-{work}) [Gone](Gone%20page.md)\n"
+                )
+                .into_bytes(),
+            ),
+            (format!("Export-1/Work {work}/diagram.png"), b"png".to_vec()),
+            (
+                "Export-1/Tasks 11111111111111111111111111111111.csv".into(),
+                b"Name\n".to_vec(),
+            ),
+        ]);
+        let tmp_dir = tempdir().unwrap();
+        let export = tmp_dir.path().join("export.zip");
+        fs::write(&export, zip(vec![("Export-1-Part-1.zip".into(), part)])).unwrap();
+
+        let import = notion(&export, Path::new("")).unwrap();
+        assert_eq!(
+            import.notes,
+            [
+                PathBuf::from("Work/Projects.md"),
+                PathBuf::from("Work/Work")
+            ]
+        );
+        assert_eq!((import.attachments, import.unlinked), (1, 1));
+        assert_eq!(
+            import.unresolved,
+            [(PathBuf::from("Work/Projects.md"), "Gone page.md".into())]
+        );
+        assert_eq!(
+            import.texts[Path::new("Work/Work/main.md")],
+            "# Work\n\n[[Work/Projects]]\n\n![diagram.png](images/diagram.png)\n"
+        );
+        assert!(import.texts[Path::new("Work/Projects.md")].contains("[[Work/Work|Up]]"));
+
+        let root = tmp_dir.path().join("notes");
+        import.write(&root).unwrap();
+        assert_eq!(
+            fs::read(root.join("Work/Work/images/diagram.png")).unwrap(),
+            b"png"
+        );
+    }
+
+    #[test]
+    fn test_split_id() {
+        assert_eq!(
+            split_id("My Page 0123456789abcdef0123456789abcdef"),
+            ("My Page", Some("0123456789abcdef0123456789abcdef"))
+        );
+        assert_eq!(split_id("diagram.png"), ("diagram.png", None));
+    }
+
+    #[test]
+    fn test_tag_list() {
+        assert_eq!(tag_list(&Value::from("a, #b c")), ["a", "b", "c"]);
+        assert_eq!(tag_list(&serde_json::json!(["#x", 2])), ["x", "2"]);
+    }
+
+    #[test]
+    fn test_translate_frontmatter() {
+        let content = "---\nkeywords: [a]\ntags: [b, c]\ntag: d\n---\nText\n";
+        let translated = translate_frontmatter(content, "Note").unwrap();
+        let fields = parse_frontmatter(&translated);
+        assert_eq!(fields["keywords"], serde_json::json!(["a", "b", "c", "d"]));
+        assert!(!fields.contains_key("tags") && !fields.contains_key("tag"));
+        assert_eq!(fields["title"], "Note");
+    }
+}
@@ -0,0 +1,384 @@
+use anyhow::{Result, bail};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Component, Path, PathBuf},
+    process,
+};
+
+use crate::cli::NoteType;
+use crate::sync::git;
+use crate::vault::Note;
+
+/// The git status of a file of the tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileStatus {
+    Clean,
+    Modified,
+    Added,
+    Untracked,
+}
+
+impl FileStatus {
+    pub fn marker(self) -> &'static str {
+        match self {
+            FileStatus::Clean => " ",
+            FileStatus::Modified => "M",
+            FileStatus::Added => "A",
+            FileStatus::Untracked => "?",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub column: usize,
+    /// `error` or `warning`
+    pub severity: String,
+    pub message: String,
+}
+
+/// A file of the tree. Nodes are stored in depth-first order.
+#[derive(Debug)]
+pub struct IncludeNode {
+    pub path: PathBuf,
+    pub depth: usize,
+    pub parent: Option<usize>,
+    /// The first heading of the file
+    pub heading: Option<String>,
+    pub missing: bool,
+    /// Included by one of its own includes; its includes are not followed again
+    pub cycle: bool,
+    pub status: Option<FileStatus>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// The `#include` structure of a typst note, from its main file.
+#[derive(Debug)]
+pub struct IncludeTree {
+    /// The directory of the main file, the root of absolute include paths
+    pub root: PathBuf,
+    pub nodes: Vec<IncludeNode>,
+}
+
+impl IncludeTree {
+    pub fn build(note: &Path) -> Result<Self> {
+        let main_file = note.main_file_path()?;
+        if main_file.note_type()? != NoteType::Typ {
+            bail!("'{}' is not a typst note", note.display());
+        }
+
+        let root = main_file.parent().unwrap_or(Path::new(".")).to_path_buf();
+        let mut tree = IncludeTree {
+            root,
+            nodes: Vec::new(),
+        };
+        tree.add(normalize(&main_file), None, &mut Vec::new());
+        Ok(tree)
+    }
+
+    fn add(&mut self, path: PathBuf, parent: Option<usize>, ancestors: &mut Vec<PathBuf>) {
+        let content = fs::read_to_string(&path).ok();
+        let cycle = ancestors.contains(&path);
+        let index = self.nodes.len();
+        self.nodes.push(IncludeNode {
+            depth: ancestors.len(),
+            parent,
+            heading: content.as_deref().and_then(first_heading),
+            missing: content.is_none(),
+            cycle,
+            status: None,
+            diagnostics: Vec::new(),
+            path: path.clone(),
+        });
+
+        let Some(content) = content.filter(|_| !cycle) else {
+            return;
+        };
+        let dir = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+        ancestors.push(path);
+        for include in includes(&content) {
+            let child = match include.strip_prefix('/') {
+                Some(rooted) => self.root.join(rooted),
+                None => dir.join(&include),
+            };
+            self.add(normalize(&child), Some(index), ancestors);
+        }
+        ancestors.pop();
+    }
+
+    pub fn relative<'a>(&self, path: &'a Path) -> &'a Path {
+        path.strip_prefix(&self.root).unwrap_or(path)
+    }
+
+    pub fn children(&self, index: usize) -> impl Iterator<Item = usize> + '_ {
+        (index + 1..self.nodes.len()).filter(move |&i| self.nodes[i].parent == Some(index))
+    }
+
+    /// Fill the git status of the files, if the note is in a git repository.
+    pub fn read_status(&mut self) -> Result<()> {
+        let toplevel = git(&self.root, &["rev-parse", "--show-toplevel"])?;
+        if !toplevel.status.success() {
+            return Ok(());
+        }
+        let toplevel = PathBuf::from(String::from_utf8_lossy(&toplevel.stdout).trim());
+        let output = git(
+            &self.root,
+            &["status", "--porcelain", "-z", "--untracked-files=all", "."],
+        )?;
+        let statuses = parse_status(&String::from_utf8_lossy(&output.stdout));
+
+        for node in &mut self.nodes {
+            if node.missing {
+                continue;
+            }
+            let path = fs::canonicalize(&node.path).unwrap_or(node.path.clone());
+            let toplevel = fs::canonicalize(&toplevel).unwrap_or(toplevel.clone());
+            node.status = Some(
+                path.strip_prefix(&toplevel)
+                    .ok()
+                    .and_then(|rel| statuses.get(rel).copied())
+                    .unwrap_or(FileStatus::Clean),
+            );
+        }
+        Ok(())
+    }
+
+    /// Compile the main file with typst and attach the diagnostics to the files.
+    pub fn check(&mut self) -> Result<()> {
+        let Some(main_file) = self.nodes.first().map(|n| n.path.clone()) else {
+            return Ok(());
+        };
+        let output = std::env::temp_dir().join(format!("noxe-check-{}.pdf", process::id()));
+        let result = process::Command::new("typst")
+            .arg("compile")
+            .arg("--diagnostic-format")
+            .arg("short")
+            .arg("--root")
+            .arg(&self.root)
+            .arg(&main_file)
+            .arg(&output)
+            .current_dir(&self.root)
+            .output();
+        let _ = fs::remove_file(&output);
+        let result = match result {
+            Ok(result) => result,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                bail!("'typst' is not installed or not in PATH")
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        for node in &mut self.nodes {
+            node.diagnostics.clear();
+        }
+        for (path, diagnostic) in parse_diagnostics(&String::from_utf8_lossy(&result.stderr)) {
+            let path = normalize(&self.root.join(path));
+            if let Some(node) = self.nodes.iter_mut().find(|n| n.path == path) {
+                node.diagnostics.push(diagnostic);
+            } else {
+                // Diagnostics of packages or files outside the tree go to the main file
+                self.nodes[0].diagnostics.push(diagnostic);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The paths of the `#include "..."` statements of a typst file, outside comments.
+fn includes(content: &str) -> Vec<String> {
+    let mut paths = Vec::new();
+    let mut in_comment = false;
+
+    for line in content.lines() {
+        let mut code = String::new();
+        let mut rest = line;
+        loop {
+            if in_comment {
+                let Some(end) = rest.find("*/") else {
+                    break;
+                };
+                rest = &rest[end + 2..];
+                in_comment = false;
+                continue;
+            }
+            match (rest.find("//"), rest.find("/*")) {
+                (Some(line_comment), block) if block.is_none_or(|b| line_comment < b) => {
+                    code.push_str(&rest[..line_comment]);
+                    break;
+                }
+                (_, Some(block)) => {
+                    code.push_str(&rest[..block]);
+                    rest = &rest[block + 2..];
+                    in_comment = true;
+                }
+                _ => {
+                    code.push_str(rest);
+                    break;
+                }
+            }
+        }
+
+        for (start, _) in code.match_indices("#include") {
+            let arg = code[start + "#include".len()..].trim_start();
+            if let Some(quoted) = arg.strip_prefix('"')
+                && let Some(end) = quoted.find('"')
+            {
+                paths.push(quoted[..end].to_string());
+            }
+        }
+    }
+
+    paths
+}
+
+/// The text of the first `=` heading.
+fn first_heading(content: &str) -> Option<String> {
+    content.lines().find_map(|line| {
+        let line = line.trim_start();
+        let text = line.trim_start_matches('=');
+        (text.len() < line.len() && text.starts_with(' ')).then(|| text.trim().to_string())
+    })
+}
+
+/// Resolve `.` and `..` without touching the filesystem, so missing files have a path too.
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if normalized.file_name().is_some() => {
+                normalized.pop();
+            }
+            c => normalized.push(c),
+        }
+    }
+    normalized
+}
+
+/// `git status --porcelain -z`: `XY path`, renames followed by their source.
+fn parse_status(output: &str) -> HashMap<PathBuf, FileStatus> {
+    let mut statuses = HashMap::new();
+    let mut entries = output.split('\0');
+    while let Some(entry) = entries.next() {
+        if entry.len() < 4 {
+            continue;
+        }
+        let (code, path) = entry.split_at(3);
+        let status = match code.trim() {
+            "??" => FileStatus::Untracked,
+            "A" | "AM" => FileStatus::Added,
+            _ => FileStatus::Modified,
+        };
+        if code.starts_with('R') || code.starts_with('C') {
+            entries.next();
+        }
+        statuses.insert(PathBuf::from(path), status);
+    }
+    statuses
+}
+
+/// `typst compile --diagnostic-format short` lines: `path:line:column: severity: message`.
+fn parse_diagnostics(stderr: &str) -> Vec<(PathBuf, Diagnostic)> {
+    stderr
+        .lines()
+        .filter_map(|line| {
+            let (location, rest) = [": error: ", ": warning: "]
+                .iter()
+                .find_map(|sep| line.split_once(sep).map(|(l, m)| (l, (sep, m))))?;
+            let mut parts = location.rsplitn(3, ':');
+            let column = parts.next()?.parse().ok()?;
+            let line_number = parts.next()?.parse().ok()?;
+            let path = parts.next()?;
+
+            Some((
+                PathBuf::from(path),
+                Diagnostic {
+                    line: line_number,
+                    column,
+                    severity: rest.0.trim_matches([':', ' ']).to_string(),
+                    message: rest.1.to_string(),
+                },
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_include_tree() {
+        let tmp_dir = tempdir().unwrap();
+        let note = tmp_dir.path().join("book");
+        fs::create_dir_all(note.join("chapters")).unwrap();
+        fs::write(
+            note.join("main.typ"),
+            "#include \"chapters/intro.typ\"\n// #include \"old.typ\"\n/* #include \"old.typ\" */\n#include \"/chapters/body.typ\"\n",
+        )
+        .unwrap();
+        fs::write(note.join("chapters/intro.typ"), "= Introduction\n").unwrap();
+        fs::write(
+            note.join("chapters/body.typ"),
+            "== Body\n#include \"../missing.typ\"\n#include \"../main.typ\"\n",
+        )
+        .unwrap();
+
+        let tree = IncludeTree::build(&note).unwrap();
+        let nodes: Vec<(usize, String, Option<&str>)> = tree
+            .nodes
+            .iter()
+            .map(|n| {
+                (
+                    n.depth,
+                    tree.relative(&n.path).display().to_string(),
+                    n.heading.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            nodes,
+            [
+                (0, "main.typ".into(), None),
+                (1, "chapters/intro.typ".into(), Some("Introduction")),
+                (1, "chapters/body.typ".into(), Some("Body")),
+                (2, "missing.typ".into(), None),
+                (2, "main.typ".into(), None),
+            ]
+        );
+        assert!(tree.nodes[3].missing);
+        assert!(tree.nodes[4].cycle);
+        assert_eq!(tree.children(0).collect::<Vec<_>>(), [1, 2]);
+
+        assert!(IncludeTree::build(&tmp_dir.path().join("none")).is_err());
+    }
+
+    #[test]
+    fn test_parse_output() {
+        let diagnostics = parse_diagnostics(
+            "chapters/a.typ:3:5: error: unknown variable: x\nmain.typ:1:1: warning: unused\n  hint: something\n",
+        );
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].0, PathBuf::from("chapters/a.typ"));
+        assert_eq!(
+            diagnostics[0].1,
+            Diagnostic {
+                line: 3,
+                column: 5,
+                severity: "error".into(),
+                message: "unknown variable: x".into(),
+            }
+        );
+        assert_eq!(diagnostics[1].1.severity, "warning");
+
+        let statuses = parse_status(" M a.typ\0?? b c.typ\0R  new.typ\0old.typ\0A  d.typ\0");
+        assert_eq!(statuses[Path::new("a.typ")], FileStatus::Modified);
+        assert_eq!(statuses[Path::new("b c.typ")], FileStatus::Untracked);
+        assert_eq!(statuses[Path::new("new.typ")], FileStatus::Modified);
+        assert_eq!(statuses[Path::new("d.typ")], FileStatus::Added);
+        assert!(!statuses.contains_key(Path::new("old.typ")));
+    }
+}
@@ -0,0 +1,266 @@
+use anyhow::{Context, Result};
+use ignore::WalkBuilder;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::lang::note_lang;
+use crate::metadata::{NoteMetadata, read_metadata};
+use crate::profile;
+use crate::semantic::note_key;
+use crate::state::StateStore;
+use crate::vault::Note;
+
+pub const INDEX: &str = "index";
+
+/// A persistent list of the notes of a vault with their metadata, kept in `.noxe/index.json`.
+///
+/// Adding or removing an entry changes the modification time of its directory, so only the
+/// directories are checked to find new and removed notes, and only the main files whose
+/// modification time changed are read again.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct NoteIndex {
+    /// Categories (and the vault root, as ""), with their modification time
+    dirs: BTreeMap<String, Option<SystemTime>>,
+    notes: BTreeMap<String, IndexEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexEntry {
+    /// The modification time of the main file
+    modified: Option<SystemTime>,
+    metadata: NoteMetadata,
+    /// The language of the note, detected when it is read
+    #[serde(default)]
+    lang: Option<String>,
+}
+
+impl NoteIndex {
+    /// Index every note under the note root and save the index.
+    pub fn rebuild(note_root: &Path) -> Result<Self> {
+        // Created first, so that it does not change the modification time of the root later
+        let store = StateStore::new(note_root);
+        store.init()?;
+
+        let mut index = NoteIndex::default();
+        index.scan(note_root, note_root)?;
+        store.save(INDEX, &index)?;
+        Ok(index)
+    }
+
+    /// Delete the index of the vault. Returns whether there was one.
+    pub fn remove(note_root: &Path) -> Result<bool> {
+        StateStore::new(note_root).remove(INDEX)
+    }
+
+    /// Load the index of the vault if it has one, bringing it up to date with the files.
+    pub fn open(note_root: &Path) -> Result<Option<Self>> {
+        let store = StateStore::new(note_root);
+        if !store.exists(INDEX) {
+            return Ok(None);
+        }
+
+        let mut index: NoteIndex = store.load(INDEX)?;
+        profile::mark("index load");
+        if index.update(note_root)? {
+            store.save(INDEX, &index)?;
+        }
+        profile::mark("index update");
+        Ok(Some(index))
+    }
+
+    /// The paths of the notes, sorted.
+    pub fn notes(&self, note_root: &Path) -> Vec<PathBuf> {
+        self.notes.keys().map(|key| note_root.join(key)).collect()
+    }
+
+    /// The paths of the categories, sorted.
+    pub fn categories(&self, note_root: &Path) -> Vec<PathBuf> {
+        self.dirs
+            .keys()
+            .filter(|key| !key.is_empty())
+            .map(|key| note_root.join(key))
+            .collect()
+    }
+
+    pub fn metadata(&self, note_root: &Path, note: &Path) -> Option<&NoteMetadata> {
+        self.notes
+            .get(&note_key(note_root, note))
+            .map(|entry| &entry.metadata)
+    }
+
+    /// The language of a note. `None` for notes without one, or not indexed.
+    pub fn lang(&self, note_root: &Path, note: &Path) -> Option<&str> {
+        self.notes
+            .get(&note_key(note_root, note))
+            .and_then(|entry| entry.lang.as_deref())
+    }
+
+    /// Rescan the directories whose content changed and re-read the edited notes. Returns
+    /// whether anything changed.
+    fn update(&mut self, note_root: &Path) -> Result<bool> {
+        let changed: Vec<String> = self
+            .dirs
+            .iter()
+            .filter(|(dir, modified)| dir_modified(&note_root.join(dir)) != **modified)
+            .map(|(dir, _)| dir.clone())
+            .collect();
+
+        // Rescanning a directory covers its subdirectories
+        let mut rescanned: Vec<&String> = Vec::new();
+        for dir in &changed {
+            if rescanned.iter().any(|d| is_within(dir, d)) {
+                continue;
+            }
+            self.dirs.retain(|d, _| !is_within(d, dir));
+            self.notes.retain(|n, _| !is_within(n, dir));
+
+            let path = note_root.join(dir);
+            if path.is_dir() {
+                self.scan(note_root, &path)?;
+            }
+            rescanned.push(dir);
+        }
+
+        let mut edited = false;
+        for (key, entry) in self.notes.iter_mut() {
+            let Ok(main_file) = note_root.join(key).main_file_path() else {
+                continue;
+            };
+            let modified = file_modified(&main_file);
+            if modified != entry.modified {
+                entry.modified = modified;
+                entry.metadata = read_metadata(&main_file).unwrap_or_default();
+                entry.lang = note_lang(&note_root.join(key));
+                edited = true;
+            }
+        }
+
+        Ok(!changed.is_empty() || edited)
+    }
+
+    /// Add the notes and categories under `dir`, which is the note root or a category.
+    fn scan(&mut self, note_root: &Path, dir: &Path) -> Result<()> {
+        self.dirs
+            .insert(note_key(note_root, dir), dir_modified(dir));
+
+        let mut it = WalkBuilder::new(dir).build();
+        it.next();
+        while let Some(entry) = it.next() {
+            let entry = entry.with_context(|| format!("Failed to walk '{}'", dir.display()))?;
+            let path = entry.path();
+
+            if path.is_filenote() || path.is_dirnote() {
+                if path.is_dirnote() {
+                    it.skip_current_dir();
+                }
+                let main_file = path.main_file_path()?;
+                self.notes.insert(
+                    note_key(note_root, path),
+                    IndexEntry {
+                        modified: file_modified(&main_file),
+                        metadata: read_metadata(&main_file).unwrap_or_default(),
+                        lang: note_lang(path),
+                    },
+                );
+            } else if path.is_category() {
+                self.dirs
+                    .insert(note_key(note_root, path), dir_modified(path));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether the relative path `path` is `dir` or lies under it. Everything is within the root "".
+fn is_within(path: &str, dir: &str) -> bool {
+    dir.is_empty()
+        || path == dir
+        || path
+            .strip_prefix(dir)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+fn dir_modified(dir: &Path) -> Option<SystemTime> {
+    fs::metadata(dir).and_then(|m| m.modified()).ok()
+}
+
+fn file_modified(file: &Path) -> Option<SystemTime> {
+    fs::metadata(file).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    /// Give files a distinct modification time, which may be coarse on some file systems.
+    fn touch(path: &Path, time: u64) {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(time);
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(time)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_index_update() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        fs::create_dir_all(root.join("cat/dirnote")).unwrap();
+        fs::write(root.join("cat/dirnote/main.typ"), "= Dir\n").unwrap();
+        fs::write(root.join("a.md"), "---\ntitle: A\n---\n").unwrap();
+
+        let index = NoteIndex::rebuild(root).unwrap();
+        assert_eq!(
+            index.notes(root),
+            [root.join("a.md"), root.join("cat/dirnote")]
+        );
+        assert_eq!(index.categories(root), [root.join("cat")]);
+        assert_eq!(
+            index.metadata(root, &root.join("a.md")).unwrap().title,
+            Some("A".to_string())
+        );
+
+        // New note in a category, edited note at the root
+        fs::write(root.join("cat/b.md"), "# B\n").unwrap();
+        fs::write(root.join("a.md"), "---\ntitle: A2\n---\n").unwrap();
+        touch(&root.join("a.md"), 1);
+        let dir = fs::File::open(root.join("cat")).unwrap();
+        dir.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(2))
+            .unwrap();
+
+        let index = NoteIndex::open(root).unwrap().unwrap();
+        assert_eq!(index.notes(root).len(), 3);
+        assert!(index.notes(root).contains(&root.join("cat/b.md")));
+        assert_eq!(
+            index.metadata(root, &root.join("a.md")).unwrap().title,
+            Some("A2".to_string())
+        );
+
+        // Removed category
+        fs::remove_dir_all(root.join("cat")).unwrap();
+        let index = NoteIndex::open(root).unwrap().unwrap();
+        assert_eq!(index.notes(root), [root.join("a.md")]);
+        assert!(index.categories(root).is_empty());
+
+        assert!(NoteIndex::remove(root).unwrap());
+        assert!(NoteIndex::open(root).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_is_within() {
+        assert!(is_within("cat/a.md", "cat"));
+        assert!(is_within("cat", "cat"));
+        assert!(!is_within("category/a.md", "cat"));
+        assert!(is_within("a.md", ""));
+    }
+}
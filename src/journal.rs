@@ -0,0 +1,205 @@
+use anyhow::{Context, Result, bail};
+use chrono::{
+    Datelike, Days, Local, NaiveDate,
+    format::{Item, StrftimeItems},
+};
+use std::{
+    ffi::OsString,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::cli::NoteType;
+use crate::process::exec_with;
+use crate::vault::{Note, TemplateVars, render_template};
+
+pub const DEFAULT_PATTERN: &str = "journal/%Y/%m/%Y-%m-%d.md";
+
+/// Create the journal note of a day if needed, then open it with the edit command.
+pub fn journal(
+    note_root: &Path,
+    pattern: &str,
+    template: Option<&Path>,
+    date: NaiveDate,
+    edit: Option<Vec<OsString>>,
+) -> Result<()> {
+    let note_path = note_root.join(journal_path(pattern, date)?);
+
+    if !note_path.exists() {
+        let note_type = note_path.note_type().with_context(|| {
+            format!(
+                "The journal path pattern '{}' must end with .md, .typ or .org",
+                pattern
+            )
+        })?;
+        let content = match template {
+            Some(template) => {
+                let template = fs::read_to_string(template).with_context(|| {
+                    format!("Failed to read journal template '{}'", template.display())
+                })?;
+                fill_template(&template, date)
+            }
+            None => default_content(note_type, date),
+        };
+
+        if let Some(parent) = note_path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory '{}'", parent.display()))?;
+        }
+        fs::write(&note_path, content)
+            .with_context(|| format!("Failed to create note '{}'", note_path.display()))?;
+        println!("Note '{}' created successfully!", note_path.display());
+    }
+
+    match edit {
+        Some(edit) if edit.is_empty() => exec_with(&note_path, &["vim".into()]),
+        Some(edit) => exec_with(&note_path, &edit),
+        None => {
+            println!("{}", note_path.display());
+            Ok(())
+        }
+    }
+}
+
+/// Parse `today`, `yesterday`, `tomorrow` or `YYYY-MM-DD`, then shift it by `offset` days.
+pub fn parse_date(date: Option<&str>, offset: i64, today: NaiveDate) -> Result<NaiveDate> {
+    let date = match date.map(str::trim) {
+        None | Some("today") => today,
+        Some("yesterday") => today - Days::new(1),
+        Some("tomorrow") => today + Days::new(1),
+        Some(date) => NaiveDate::parse_from_str(date, "%Y-%m-%d").with_context(|| {
+            format!(
+                "Invalid date '{}', expected today, yesterday, tomorrow or YYYY-MM-DD",
+                date
+            )
+        })?,
+    };
+
+    let days = Days::new(offset.unsigned_abs());
+    let date = if offset >= 0 {
+        date.checked_add_days(days)
+    } else {
+        date.checked_sub_days(days)
+    };
+    date.context("Date out of range")
+}
+
+pub fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
+
+/// Expand the strftime-like pattern, e.g. `journal/%Y/%m/%Y-%m-%d.md`. Fails on an invalid
+/// placeholder, or one a date cannot fill like `%H`.
+pub(crate) fn journal_path(pattern: &str, date: NaiveDate) -> Result<PathBuf> {
+    if StrftimeItems::new(pattern).any(|item| item == Item::Error) {
+        bail!(
+            "Invalid placeholder in the journal path pattern '{}'",
+            pattern
+        );
+    }
+    let mut path = String::new();
+    write!(path, "{}", date.format(pattern)).with_context(|| {
+        format!(
+            "The journal path pattern '{}' has a placeholder a date cannot fill",
+            pattern
+        )
+    })?;
+    Ok(PathBuf::from(path))
+}
+
+/// Replace `{{date}}` and `{{weekday}}` in a journal template.
+fn fill_template(template: &str, date: NaiveDate) -> String {
+    let vars = TemplateVars::from([
+        ("date".to_string(), date.format("%Y-%m-%d").to_string()),
+        ("weekday".to_string(), date.format("%A").to_string()),
+    ]);
+    render_template(template, &vars)
+}
+
+fn default_content(note_type: NoteType, date: NaiveDate) -> String {
+    let title = date.format("%Y-%m-%d").to_string();
+    let weekday = date.format("%A");
+
+    match note_type {
+        NoteType::Md => format!(
+            "---\ntitle: \"{}\"\ndate: \"{}\"\n---\n\n# {} {}\n\n",
+            title, title, title, weekday
+        ),
+        NoteType::Typ => format!(
+            "#set document(title: \"{}\", date: datetime(year: {}, month: {}, day: {}))\n\n= {} {}\n\n",
+            title,
+            date.year(),
+            date.month(),
+            date.day(),
+            title,
+            weekday
+        ),
+        NoteType::Org => format!(
+            "#+TITLE: {}\n#+DATE: {}\n\n* {} {}\n\n",
+            title, title, title, weekday
+        ),
+        NoteType::Custom(_) => format!("{} {}\n\n", title, weekday),
+    }
+}
+
+/// Check that a journal pattern is usable: it must produce `.md`, `.typ` or `.org` paths.
+pub fn check_pattern(pattern: &str) -> Result<()> {
+    let path = journal_path(pattern, today())?;
+    if path.note_type().is_err() {
+        bail!(
+            "The journal path pattern '{}' must end with .md, .typ or .org",
+            pattern
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_date() {
+        let today = NaiveDate::from_ymd_opt(2025, 3, 1).unwrap();
+        let date = |d: Option<&str>, offset| parse_date(d, offset, today).unwrap().to_string();
+
+        assert_eq!(date(None, 0), "2025-03-01");
+        assert_eq!(date(Some("yesterday"), 0), "2025-02-28");
+        assert_eq!(date(Some("tomorrow"), 0), "2025-03-02");
+        assert_eq!(date(Some("2024-12-31"), 1), "2025-01-01");
+        assert_eq!(date(None, -2), "2025-02-27");
+        assert!(parse_date(Some("someday"), 0, today).is_err());
+    }
+
+    #[test]
+    fn test_journal() {
+        let tmp_dir = tempdir().unwrap();
+        let date = NaiveDate::from_ymd_opt(2025, 5, 17).unwrap();
+
+        journal(tmp_dir.path(), DEFAULT_PATTERN, None, date, None).unwrap();
+        let note = tmp_dir.path().join("journal/2025/05/2025-05-17.md");
+        assert_eq!(
+            fs::read_to_string(&note).unwrap(),
+            "---\ntitle: \"2025-05-17\"\ndate: \"2025-05-17\"\n---\n\n# 2025-05-17 Saturday\n\n"
+        );
+
+        // An existing note is left untouched
+        fs::write(&note, "edited").unwrap();
+        journal(tmp_dir.path(), DEFAULT_PATTERN, None, date, None).unwrap();
+        assert_eq!(fs::read_to_string(&note).unwrap(), "edited");
+
+        let template = tmp_dir.path().join("template.typ");
+        fs::write(&template, "= {{date}} ({{weekday}})\n").unwrap();
+        journal(tmp_dir.path(), "days/%d.typ", Some(&template), date, None).unwrap();
+        assert_eq!(
+            fs::read_to_string(tmp_dir.path().join("days/17.typ")).unwrap(),
+            "= 2025-05-17 (Saturday)\n"
+        );
+
+        assert!(check_pattern("journal/%Y").is_err());
+        assert!(check_pattern("journal/%Y-%H.md").is_err());
+        assert!(check_pattern("journal/100%.md").is_err());
+    }
+}
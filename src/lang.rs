@@ -0,0 +1,168 @@
+use std::path::Path;
+
+use crate::bounded::METADATA_LIMIT;
+use crate::extract::NoteText;
+use crate::vault::Note;
+
+/// Notes with fewer letters than this have no language.
+const MIN_LETTERS: usize = 12;
+/// A CJK character weighs as much as a few latin letters, as it is a word or syllable.
+const CJK_WEIGHT: usize = 3;
+
+/// Frequent short words of the languages written in latin script, to tell them apart.
+const STOP_WORDS: &[(&str, &[&str])] = &[
+    (
+        "en",
+        &[
+            "the", "and", "of", "to", "is", "in", "that", "it", "with", "for", "this", "are",
+        ],
+    ),
+    (
+        "fr",
+        &[
+            "le", "la", "les", "et", "des", "est", "une", "dans", "que", "pour", "pas", "sur",
+        ],
+    ),
+    (
+        "de",
+        &[
+            "der", "die", "das", "und", "ist", "nicht", "mit", "ein", "eine", "auf", "für", "sich",
+        ],
+    ),
+    (
+        "es",
+        &[
+            "el", "los", "las", "y", "es", "una", "por", "con", "para", "del", "que", "como",
+        ],
+    ),
+];
+
+/// The dominant language of a text, as an ISO 639-1 code: `zh`, `ja`, `ko`, `ru`, or for latin
+/// script `en`, `fr`, `de` or `es`, telling them apart by their frequent words.
+pub fn detect(text: &str) -> Option<&'static str> {
+    let (mut han, mut kana, mut hangul, mut cyrillic, mut latin) = (0, 0, 0, 0, 0);
+    for c in text.chars() {
+        match c {
+            '\u{3040}'..='\u{30ff}' => kana += 1,
+            '\u{3400}'..='\u{4dbf}' | '\u{4e00}'..='\u{9fff}' | '\u{f900}'..='\u{faff}' => han += 1,
+            '\u{ac00}'..='\u{d7af}' | '\u{1100}'..='\u{11ff}' => hangul += 1,
+            '\u{0400}'..='\u{04ff}' => cyrillic += 1,
+            c if c.is_alphabetic() && (c.is_ascii() || ('\u{00c0}'..='\u{024f}').contains(&c)) => {
+                latin += 1
+            }
+            _ => {}
+        }
+    }
+
+    let scripts = [
+        // Japanese mixes kanji and kana
+        (
+            if kana * 5 >= han { "ja" } else { "zh" },
+            (han + kana) * CJK_WEIGHT,
+        ),
+        ("ko", hangul * CJK_WEIGHT),
+        ("ru", cyrillic),
+        ("latin", latin),
+    ];
+    let (script, weight) = scripts.into_iter().max_by_key(|&(_, w)| w)?;
+    if weight < MIN_LETTERS {
+        return None;
+    }
+    if script != "latin" {
+        return Some(script);
+    }
+
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let score = |stop_words: &[&str]| {
+        words
+            .iter()
+            .filter(|w| stop_words.contains(&w.as_str()))
+            .count()
+    };
+    // English when no frequent word is found, as for lists of terms
+    let mut best = ("en", 0);
+    for &(lang, stop_words) in STOP_WORDS {
+        let score = score(stop_words);
+        if score > best.1 {
+            best = (lang, score);
+        }
+    }
+    Some(best.0)
+}
+
+/// The language of a note: its `lang` (or `language`) metadata field, else the one detected
+/// in its title and body.
+pub fn note_lang(note: &Path) -> Option<String> {
+    let text = NoteText::read_head(&note.main_file_path().ok()?, METADATA_LIMIT).ok()?;
+
+    let metadata = text.metadata();
+    let field = ["lang", "language"]
+        .into_iter()
+        .find_map(|key| metadata.get(key)?.as_str().map(str::to_string));
+    if let Some(lang) = field.filter(|l| !l.trim().is_empty()) {
+        return Some(lang.trim().to_string());
+    }
+
+    let text = format!(
+        "{}\n{}",
+        metadata.title.unwrap_or_default(),
+        text.plain_text()
+    );
+    detect(&text).map(str::to_string)
+}
+
+/// Whether the language `lang` is `wanted`, comparing their primary subtag, so that `zh-CN`
+/// is `zh`.
+pub fn is_lang(lang: &str, wanted: &str) -> bool {
+    let primary = |l: &str| {
+        l.split(['-', '_'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase()
+    };
+    primary(lang) == primary(wanted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            detect("Rust 的所有权系统保证了内存安全, 不需要垃圾回收"),
+            Some("zh")
+        );
+        assert_eq!(
+            detect("所有権とは、メモリを安全に管理するための仕組みです"),
+            Some("ja")
+        );
+        assert_eq!(
+            detect("The borrow checker makes sure that references are valid"),
+            Some("en")
+        );
+        assert_eq!(
+            detect("Le vérificateur d'emprunts garantit que les références sont valides"),
+            Some("fr")
+        );
+        // Mostly Chinese, with some English terms
+        assert_eq!(
+            detect("借用检查器 (borrow checker) 在编译期检查引用是否有效"),
+            Some("zh")
+        );
+        assert_eq!(detect("ok"), None);
+
+        let tmp_dir = tempdir().unwrap();
+        let note = tmp_dir.path().join("a.md");
+        fs::write(&note, "---\nlang: zh-CN\n---\nThis is written in English\n").unwrap();
+        assert_eq!(note_lang(&note).as_deref(), Some("zh-CN"));
+        assert!(is_lang("zh-CN", "zh"));
+        assert!(!is_lang("en", "zh"));
+    }
+}
@@ -0,0 +1,91 @@
+//! Manage typst and markdown notes.
+//!
+//! [`Vault`] is the entry point for using noxe as a library: listing, searching and creating
+//! notes. The `noxe` binary is a thin CLI over [`process::process_command`].
+#![feature(let_chains)]
+#![feature(os_str_display)]
+
+mod agenda;
+mod ai;
+mod append;
+mod archive;
+mod assets;
+mod atomic;
+mod attach;
+mod backup;
+mod bounded;
+mod capture;
+mod cite;
+pub mod cli;
+mod complete;
+pub mod config;
+mod content;
+mod context;
+mod crypt;
+pub mod current;
+mod dates;
+mod doctor;
+mod enrich;
+pub mod events;
+mod export;
+mod external;
+mod extract;
+mod feed;
+mod finder;
+mod flashcards;
+mod form;
+mod garden;
+mod graph;
+mod highlight;
+mod hooks;
+mod import;
+mod include;
+mod index;
+mod journal;
+mod lang;
+mod links;
+mod lint;
+mod llm;
+mod markdown;
+mod meta;
+pub mod metadata;
+mod migrate;
+mod open;
+mod ops;
+mod pack;
+mod palette;
+mod picker;
+mod pins;
+mod plugin;
+pub mod process;
+pub mod profile;
+mod query;
+mod random;
+mod recent;
+mod record;
+mod rename;
+mod review;
+mod searches;
+mod sections;
+mod semantic;
+mod serve;
+mod setup;
+mod sidecar;
+mod site;
+mod snapshot;
+mod snippet;
+mod sort;
+mod srs;
+mod state;
+mod sync;
+mod tag;
+mod template;
+mod theme;
+mod todo;
+mod topics;
+mod tui;
+pub mod vault;
+mod words;
+mod zk;
+
+pub use vault::{NewNote, Note, TemplateVars, Vault};
@@ -0,0 +1,322 @@
+use anyhow::{Context, Result};
+use regex::{Captures, Regex};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+
+use crate::vault::{Note, all_notes};
+use crate::zk::timestamp_id;
+
+static WIKI_LINK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\[\[([^\]\|#]+)[^\]]*\]\]").unwrap());
+
+/// Extract the targets of `[[Target]]`, `[[Target|alias]]` and `[[Target#heading]]` links.
+pub fn wiki_links(content: &str) -> Vec<String> {
+    let mut links: Vec<String> = WIKI_LINK
+        .captures_iter(content)
+        .map(|c| c[1].trim().to_string())
+        .collect();
+    links.sort();
+    links.dedup();
+    links
+}
+
+/// Replace the targets of the wiki-links of `content`. `rewrite` gets each (trimmed) target and
+/// returns the new one, or `None` to leave the link alone. Aliases and headings are kept.
+pub fn rewrite_wiki_links(content: &str, rewrite: impl Fn(&str) -> Option<String>) -> String {
+    WIKI_LINK
+        .replace_all(content, |c: &Captures| {
+            let (link, target) = (c.get(0).unwrap(), c.get(1).unwrap());
+            match rewrite(target.as_str().trim()) {
+                Some(new) => format!("[[{}{}", new, &content[target.end()..link.end()]),
+                None => link.as_str().to_string(),
+            }
+        })
+        .into_owned()
+}
+
+/// A change of the main file of a note, kept to be reverted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rewrite {
+    pub file: PathBuf,
+    pub old: String,
+    pub new: String,
+}
+
+/// The notes of a vault, used to resolve wiki-links to paths.
+#[derive(Debug)]
+pub struct LinkIndex {
+    note_root: PathBuf,
+    notes: Vec<PathBuf>,
+    /// The canonical path of a note to the notes linking to it
+    backlinks: HashMap<PathBuf, Vec<PathBuf>>,
+}
+
+impl LinkIndex {
+    pub fn new(note_root: &Path) -> Result<Self> {
+        let mut notes = all_notes(note_root)?;
+        notes.sort();
+
+        let mut index = LinkIndex {
+            note_root: note_root.to_path_buf(),
+            notes,
+            backlinks: HashMap::new(),
+        };
+        index.backlinks = index.reverse_links();
+        Ok(index)
+    }
+
+    /// Resolve a link target to a note. A target containing `/` is matched against the path
+    /// relative to the note root, otherwise against the note name. Matching ignores case and
+    /// the `.md`/`.typ`/`.org` extension. A target starting with a timestamp ID
+    /// (`202505171230`) falls back to the note with that ID.
+    pub fn resolve(&self, target: &str) -> Option<&Path> {
+        let target = target.trim().trim_start_matches("./");
+        let target = target
+            .strip_suffix(".md")
+            .or_else(|| target.strip_suffix(".typ"))
+            .or_else(|| target.strip_suffix(".org"))
+            .unwrap_or(target)
+            .to_lowercase();
+
+        self.notes
+            .iter()
+            .find(|note| {
+                let key = if target.contains('/') {
+                    self.link_key(note)
+                } else {
+                    note.note_name()
+                };
+                key.to_lowercase() == target
+            })
+            .or_else(|| {
+                // A link by timestamp ID, alone or with a title since changed, still finds the
+                // note
+                let id = timestamp_id(target.rsplit('/').next()?)?;
+                self.notes
+                    .iter()
+                    .find(|note| timestamp_id(&note.note_name()) == Some(id))
+            })
+            .map(PathBuf::as_path)
+    }
+
+    /// The notes containing a link that resolves to `note`.
+    pub fn backlinks(&self, note: &Path) -> Vec<PathBuf> {
+        fs::canonicalize(note)
+            .ok()
+            .and_then(|note| self.backlinks.get(&note))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// The rewrites keeping the links to `from` working once it is moved to `to`. Links by path
+    /// get the new path; links by name only change if the name does. To be called before the
+    /// move, while the links still resolve. The links of `from` itself are left alone.
+    pub fn plan_move(&self, from: &Path, to: &Path) -> Result<Vec<Rewrite>> {
+        let mut rewrites = self.plan_moves(&[(from.to_path_buf(), to.to_path_buf())])?;
+        rewrites.retain(|r| !r.file.starts_with(from));
+        Ok(rewrites)
+    }
+
+    /// The rewrites keeping the links to the notes working once they are all moved, including
+    /// the links between the moved notes. The rewritten files are the ones before the moves.
+    pub fn plan_moves(&self, moves: &[(PathBuf, PathBuf)]) -> Result<Vec<Rewrite>> {
+        // (canonical source, new name, new key, old name)
+        let mut targets = Vec::new();
+        for (from, to) in moves {
+            let from_canonical = fs::canonicalize(from)
+                .with_context(|| format!("Failed to access '{}'", from.display()))?;
+            // `to` does not exist yet, so its name is derived from the kind of `from`
+            let to_name = if from.is_filenote() {
+                to.file_stem()
+            } else {
+                to.file_name()
+            };
+            let to_name = to_name
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let to_key = self.key_of(to, &to_name);
+            targets.push((from_canonical, to_name, to_key, from.note_name()));
+        }
+
+        let mut rewrites = Vec::new();
+        for source in &self.notes {
+            let Ok(file) = source.main_file_path() else {
+                continue;
+            };
+            let Ok(old) = fs::read_to_string(&file) else {
+                continue;
+            };
+
+            let new = rewrite_wiki_links(&old, |target| {
+                let resolved = fs::canonicalize(self.resolve(target)?).ok()?;
+                let (_, to_name, to_key, from_name) =
+                    targets.iter().find(|(from, ..)| *from == resolved)?;
+                if target.contains('/') {
+                    Some(to_key.clone())
+                } else {
+                    (to_name != from_name).then(|| to_name.clone())
+                }
+            });
+            if new != old {
+                rewrites.push(Rewrite { file, old, new });
+            }
+        }
+
+        Ok(rewrites)
+    }
+
+    /// Resolve the links of every note once, canonicalizing each note a single time.
+    fn reverse_links(&self) -> HashMap<PathBuf, Vec<PathBuf>> {
+        let canonical: Vec<Option<PathBuf>> = self
+            .notes
+            .iter()
+            .map(|n| fs::canonicalize(n).ok())
+            .collect();
+        let canonical_of = |note: &Path| {
+            let i = self
+                .notes
+                .binary_search_by(|n| n.as_path().cmp(note))
+                .ok()?;
+            canonical[i].clone()
+        };
+
+        let mut backlinks: HashMap<PathBuf, Vec<PathBuf>> = HashMap::new();
+        for (source, source_canonical) in self.notes.iter().zip(&canonical) {
+            let Some(source_canonical) = source_canonical else {
+                continue;
+            };
+            let Ok(content) = source
+                .main_file_path()
+                .and_then(|m| Ok(fs::read_to_string(m)?))
+            else {
+                continue;
+            };
+
+            for link in wiki_links(&content) {
+                let Some(target) = self.resolve(&link).and_then(canonical_of) else {
+                    continue;
+                };
+                if target == *source_canonical {
+                    continue;
+                }
+                let sources = backlinks.entry(target).or_default();
+                // The notes are sorted, so a source linking twice is the last one
+                if sources.last() != Some(source) {
+                    sources.push(source.clone());
+                }
+            }
+        }
+
+        backlinks
+    }
+
+    /// The path of the note relative to the note root, without the extension of a filenote.
+    fn link_key(&self, note: &Path) -> String {
+        self.key_of(note, &note.note_name())
+    }
+
+    fn key_of(&self, note: &Path, name: &str) -> String {
+        let rel_path = note.strip_prefix(&self.note_root).unwrap_or(note);
+        let key = match rel_path.parent() {
+            Some(parent) => parent.join(name),
+            None => PathBuf::from(name),
+        };
+        key.to_string_lossy().replace('\\', "/")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_wiki_links() {
+        assert_eq!(
+            wiki_links("see [[b]], [[ Other Note |alias]] and [[b#intro]]; [not a link]"),
+            ["Other Note", "b"]
+        );
+    }
+
+    #[test]
+    fn test_resolve_and_backlinks() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        let dirnote = root.join("cat").join("Dir Note");
+        fs::create_dir_all(&dirnote).unwrap();
+        fs::write(dirnote.join("main.typ"), "= Dir\nsee [[a]]\n").unwrap();
+        fs::write(root.join("a.md"), "link to [[cat/dir note|dir]]\n").unwrap();
+        fs::write(root.join("cat").join("b.md"), "[[A.md]] and [[missing]]\n").unwrap();
+
+        let index = LinkIndex::new(root).unwrap();
+        assert_eq!(index.notes.len(), 3);
+        assert_eq!(index.resolve("dir note"), Some(dirnote.as_path()));
+        assert_eq!(
+            index.resolve("cat/b"),
+            Some(root.join("cat/b.md").as_path())
+        );
+        assert_eq!(index.resolve("missing"), None);
+
+        assert_eq!(
+            index.backlinks(&root.join("a.md")),
+            [root.join("cat/Dir Note"), root.join("cat/b.md")]
+        );
+        assert_eq!(index.backlinks(&dirnote), [root.join("a.md")]);
+    }
+
+    #[test]
+    fn test_resolve_timestamp_id() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        fs::create_dir_all(root.join("zk")).unwrap();
+        let note = root.join("zk/202505171230-Ownership rules.md");
+        fs::write(&note, "# Ownership\n").unwrap();
+
+        let index = LinkIndex::new(root).unwrap();
+        assert_eq!(index.resolve("202505171230"), Some(note.as_path()));
+        // Renamed since the link was written
+        assert_eq!(
+            index.resolve("202505171230-Ownership"),
+            Some(note.as_path())
+        );
+        assert_eq!(index.resolve("zk/202505171230"), Some(note.as_path()));
+        assert_eq!(index.resolve("202505171231"), None);
+    }
+
+    #[test]
+    fn test_rewrite_wiki_links() {
+        let content = "[[a]], [[ cat/a |alias]] and [[a#intro]], [[b]]";
+        assert_eq!(
+            rewrite_wiki_links(content, |t| (t != "b").then(|| format!("x/{}", t))),
+            "[[x/a]], [[x/cat/a|alias]] and [[x/a#intro]], [[b]]"
+        );
+    }
+
+    #[test]
+    fn test_plan_move() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        fs::create_dir_all(root.join("cat")).unwrap();
+        fs::create_dir_all(root.join("other")).unwrap();
+        fs::write(root.join("cat/a.md"), "# A\n").unwrap();
+        fs::write(root.join("b.md"), "[[cat/a|A]], [[a]] and [[cat/a#x]]\n").unwrap();
+        fs::write(root.join("c.typ"), "nothing\n").unwrap();
+
+        let index = LinkIndex::new(root).unwrap();
+        let rewrites = index
+            .plan_move(&root.join("cat/a.md"), &root.join("other/a.md"))
+            .unwrap();
+        assert_eq!(rewrites.len(), 1);
+        assert_eq!(rewrites[0].file, root.join("b.md"));
+        assert_eq!(rewrites[0].new, "[[other/a|A]], [[a]] and [[other/a#x]]\n");
+
+        let rewrites = index
+            .plan_move(&root.join("cat/a.md"), &root.join("cat/z.md"))
+            .unwrap();
+        assert_eq!(rewrites[0].new, "[[cat/z|A]], [[z]] and [[cat/z#x]]\n");
+    }
+}
@@ -0,0 +1,283 @@
+use anyhow::Result;
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+use crate::cite::strip_code;
+use crate::cli::NoteType;
+use crate::extract::extractor;
+use crate::flashcards::target_notes;
+use crate::include::normalize;
+use crate::links::LinkIndex;
+use crate::metadata::{split_frontmatter, strip_metadata};
+use crate::serve::percent_decode;
+use crate::vault::{Note, Scope, category_dir, scoped_notes, search};
+
+/// A problem of the vault. Paths are relative to the note root.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    pub path: PathBuf,
+    /// The line of the file, counting from 1
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.path.display(), line, self.message),
+            None => write!(f, "{}: {}", self.path.display(), self.message),
+        }
+    }
+}
+
+/// Check the notes of `target`, a category or a note, or of the whole vault: their frontmatter
+/// parses, their wiki-links and relative images resolve and they are not empty. The chapter
+/// files of dirnotes are checked too, for their links and images. Categories
+/// holding files but no note are reported as dirnotes missing their main file.
+pub fn lint(note_root: &Path, target: Option<&Path>, scope: Scope) -> Result<Vec<Problem>> {
+    let (mut notes, dir) = match target {
+        Some(target) => (
+            target_notes(note_root, target)?,
+            category_dir(note_root, &target.to_string_lossy()).ok(),
+        ),
+        None => (
+            scoped_notes(note_root, note_root, scope)?,
+            Some(note_root.to_path_buf()),
+        ),
+    };
+    notes.sort();
+
+    let links = LinkIndex::new(note_root)?;
+    let relative = |p: &Path| p.strip_prefix(note_root).unwrap_or(p).to_path_buf();
+
+    let mut problems = Vec::new();
+    for note in &notes {
+        let Ok(main_file) = note.main_file_path() else {
+            continue;
+        };
+        let Ok(note_type) = main_file.note_type() else {
+            continue;
+        };
+        let mut files = vec![(main_file.clone(), note_type)];
+        if note.is_dirnote() {
+            files.extend(chapters(note, &main_file));
+        }
+
+        for (file, note_type) in files {
+            let path = relative(&file);
+            let content = match fs::read_to_string(&file) {
+                Ok(content) => content,
+                Err(e) => {
+                    problems.push(Problem {
+                        path,
+                        line: None,
+                        message: format!("cannot be read: {}", e),
+                    });
+                    continue;
+                }
+            };
+            let dir = file.parent().unwrap_or(note_root);
+
+            let found = if file == main_file {
+                lint_content(&content, note_type, dir, &links)
+            } else {
+                lint_references(&content, note_type, dir, &links)
+            };
+            problems.extend(found.into_iter().map(|(line, message)| Problem {
+                path: path.clone(),
+                line,
+                message,
+            }));
+        }
+    }
+
+    if let Some(dir) = dir {
+        let mut categories = search(&dir, false, false, true, &|_| true)?.concat();
+        // The folders of a dirnote, `images/` or chapters, belong to it
+        categories.retain(|c| {
+            scope.contains(note_root, c.path())
+                && !c
+                    .path()
+                    .ancestors()
+                    .skip(1)
+                    .take_while(|a| a.starts_with(&dir))
+                    .any(|a| a.is_dirnote())
+        });
+        for category in categories {
+            let category = category.path();
+            let holds_files = fs::read_dir(category)
+                .into_iter()
+                .flatten()
+                .flatten()
+                .any(|e| e.path().is_file() && !e.file_name().to_string_lossy().starts_with('.'));
+            if holds_files && !notes.iter().any(|note| note.starts_with(category)) {
+                problems.push(Problem {
+                    path: relative(category),
+                    line: None,
+                    message: "holds files but no note: a dirnote missing its main file?"
+                        .to_string(),
+                });
+            }
+        }
+    }
+
+    Ok(problems)
+}
+
+/// The problems of the main file of a note in `dir`, with their lines.
+fn lint_content(
+    content: &str,
+    note_type: NoteType,
+    dir: &Path,
+    links: &LinkIndex,
+) -> Vec<(Option<usize>, String)> {
+    let mut problems = Vec::new();
+
+    if note_type == NoteType::Md
+        && content
+            .lines()
+            .next()
+            .is_some_and(|l| l.trim_end() == "---")
+    {
+        match split_frontmatter(content) {
+            None => problems.push((Some(1), "the frontmatter is not closed by `---`".into())),
+            Some((yaml, _)) => match serde_yml::from_str::<serde_yml::Value>(yaml) {
+                Err(e) => problems.push((
+                    // The frontmatter starts on the second line
+                    e.location().map_or(Some(1), |l| Some(l.line() + 1)),
+                    format!("the frontmatter is invalid YAML: {}", e),
+                )),
+                Ok(value) if !value.is_mapping() && !value.is_null() => {
+                    problems.push((Some(1), "the frontmatter is not a mapping".into()))
+                }
+                Ok(_) => {}
+            },
+        }
+    }
+
+    if strip_metadata(content, note_type).trim().is_empty() {
+        problems.push((None, "the note is empty".into()));
+    }
+
+    problems.extend(lint_references(content, note_type, dir, links));
+    problems
+}
+
+/// The wiki-links and relative images of a file in `dir` that resolve to nothing, with their
+/// lines.
+fn lint_references(
+    content: &str,
+    note_type: NoteType,
+    dir: &Path,
+    links: &LinkIndex,
+) -> Vec<(Option<usize>, String)> {
+    let extract = extractor(note_type);
+    let mut problems = Vec::new();
+
+    for (i, line) in strip_code(content).lines().enumerate() {
+        for target in extract.wiki_links(line) {
+            let is_file = Path::new(&target)
+                .extension()
+                .is_some_and(|ext| NoteType::try_from(ext.to_string_lossy().as_ref()).is_err());
+            let resolved = if is_file {
+                normalize(&dir.join(&target)).exists()
+            } else {
+                links.resolve(&target).is_some()
+            };
+            if !resolved {
+                problems.push((Some(i + 1), format!("[[{}]] resolves to nothing", target)));
+            }
+        }
+
+        for image in extract.images(line) {
+            if !normalize(&dir.join(percent_decode(&image))).exists() {
+                problems.push((Some(i + 1), format!("image '{}' does not exist", image)));
+            }
+        }
+    }
+
+    problems
+}
+
+/// The files of a dirnote other than its main file that are notes, like its chapters.
+fn chapters(note: &Path, main_file: &Path) -> Vec<(PathBuf, NoteType)> {
+    WalkDir::new(note)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| e.depth() == 0 || !e.file_name().to_string_lossy().starts_with('.'))
+        .flatten()
+        .filter(|e| e.file_type().is_file() && e.path() != main_file)
+        .filter_map(|e| {
+            let note_type = e.path().note_type().ok()?;
+            Some((e.into_path(), note_type))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_lint() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        fs::create_dir_all(root.join("lang/rust/images")).unwrap();
+        fs::write(root.join("lang/rust/images/logo.png"), "png").unwrap();
+        fs::write(
+            root.join("lang/rust/main.md"),
+            "---\ntitle: Rust\n---\n\n![logo](images/logo.png) ![gone](images/gone%201.png)\n\
+            See [[tokio]] and [[missing]].\n\n```\n[[in code]]\n```\n",
+        )
+        .unwrap();
+        fs::write(root.join("tokio.md"), "# Tokio\n").unwrap();
+        fs::write(
+            root.join("empty.typ"),
+            "#set document(title: \"Empty\")\n\n",
+        )
+        .unwrap();
+        fs::create_dir_all(root.join("lang/rust/chapters")).unwrap();
+        fs::write(
+            root.join("lang/rust/chapters/intro.md"),
+            "# Intro\n\n![logo](../images/logo.png) [[tokio]] [[gone]]\n",
+        )
+        .unwrap();
+        fs::create_dir_all(root.join("broken/images")).unwrap();
+        fs::write(root.join("broken/Main.txt"), "text").unwrap();
+
+        let problems: Vec<String> = lint(root, None, Scope::Active)
+            .unwrap()
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        assert_eq!(
+            problems,
+            [
+                "empty.typ: the note is empty",
+                "lang/rust/main.md:5: image 'images/gone 1.png' does not exist",
+                "lang/rust/main.md:6: [[missing]] resolves to nothing",
+                "lang/rust/chapters/intro.md:3: [[gone]] resolves to nothing",
+                "broken: holds files but no note: a dirnote missing its main file?",
+            ][..],
+            "{:#?}",
+            problems
+        );
+        // The YAML error is reported too
+        let tokio = lint_content(
+            "---\ntitle: [a\n---\n\nTokio\n",
+            NoteType::Md,
+            root,
+            &LinkIndex::new(root).unwrap(),
+        );
+        assert_eq!(tokio.len(), 1);
+        assert!(tokio[0].1.starts_with("the frontmatter is invalid YAML"));
+
+        // A category
+        let problems = lint(root, Some(Path::new("lang")), Scope::Active).unwrap();
+        assert_eq!(problems.len(), 3);
+    }
+}
@@ -0,0 +1,139 @@
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+use crate::ai::Client;
+use crate::extract::NoteText;
+use crate::vault::Note;
+
+/// At most this many notes are sent as context of a question.
+const CONTEXT_NOTES: usize = 5;
+/// Each note is truncated to this many characters before being sent to the provider.
+const MAX_NOTE_CHARS: usize = 6_000;
+
+const ASK_PROMPT: &str = "You answer questions about the user's notes. Base the answer on the \
+notes given below the question, and say so when they do not contain the answer. \
+Reply in the language of the question.";
+const SUMMARIZE_PROMPT: &str = "Summarize the note in a short paragraph followed by its key \
+points as a bullet list, in the language of the note.";
+
+#[derive(Debug)]
+pub struct LlmOptions {
+    pub provider: String,
+    /// The chat model, the provider's default if `None`
+    pub model: Option<String>,
+}
+
+/// The reply of the model, with the notes it was given.
+#[derive(Debug)]
+pub struct Answer {
+    pub text: String,
+    pub sources: Vec<PathBuf>,
+}
+
+/// Answer a question using the notes most relevant to it as context.
+pub fn ask(notes: &[PathBuf], question: &str, options: &LlmOptions) -> Result<Answer> {
+    let client = Client::new(&options.provider)?;
+
+    let sources = relevant_notes(notes, question, CONTEXT_NOTES);
+    let mut input = format!("Question: {}\n", question);
+    for note in &sources {
+        input.push_str(&format!(
+            "\n--- Note '{}' ---\n{}\n",
+            note.note_name(),
+            note_body(note)?
+        ));
+    }
+
+    let (text, _) = client.chat(&chat_model(&client, options), ASK_PROMPT, &input)?;
+    Ok(Answer { text, sources })
+}
+
+/// Summarize a single note.
+pub fn summarize(note: &Path, options: &LlmOptions) -> Result<Answer> {
+    let client = Client::new(&options.provider)?;
+
+    let (text, _) = client.chat(
+        &chat_model(&client, options),
+        SUMMARIZE_PROMPT,
+        &note_body(note)?,
+    )?;
+    Ok(Answer {
+        text,
+        sources: vec![note.to_path_buf()],
+    })
+}
+
+fn chat_model(client: &Client, options: &LlmOptions) -> String {
+    options
+        .model
+        .clone()
+        .unwrap_or_else(|| client.provider().chat_model.to_string())
+}
+
+/// The content of the main file without metadata, truncated to `MAX_NOTE_CHARS`.
+fn note_body(note: &Path) -> Result<String> {
+    let body = NoteText::read(&note.main_file_path()?)?.body();
+
+    Ok(body.chars().take(MAX_NOTE_CHARS).collect())
+}
+
+/// The notes sharing the most words with the question, best first. Notes without any
+/// shared word are left out.
+fn relevant_notes(notes: &[PathBuf], question: &str, limit: usize) -> Vec<PathBuf> {
+    let words: Vec<String> = question
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.chars().count() >= 3)
+        .map(str::to_lowercase)
+        .collect();
+
+    let mut scored: Vec<(usize, &PathBuf)> = notes
+        .iter()
+        .filter_map(|note| {
+            let text = format!("{}\n{}", note.note_name(), note_body(note).ok()?).to_lowercase();
+            let score = words.iter().filter(|w| text.contains(w.as_str())).count();
+            (score > 0).then_some((score, note))
+        })
+        .collect();
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(b.1)));
+
+    scored
+        .into_iter()
+        .take(limit)
+        .map(|(_, note)| note.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_relevant_notes() {
+        let tmp_dir = tempdir().unwrap();
+        let write = |name: &str, content: &str| {
+            let path = tmp_dir.path().join(name);
+            fs::write(&path, content).unwrap();
+            path
+        };
+        let rust = write(
+            "rust.md",
+            "---\ntitle: rust\n---\nOwnership and borrowing\n",
+        );
+        let lifetimes = write("lifetimes.md", "Lifetimes make borrowing safe\n");
+        let cooking = write("cooking.typ", "= Pasta\nBoil water\n");
+        let notes = [rust.clone(), lifetimes.clone(), cooking];
+
+        assert_eq!(
+            relevant_notes(&notes, "How does borrowing relate to lifetimes?", 5),
+            [lifetimes.clone(), rust.clone()]
+        );
+        assert_eq!(
+            relevant_notes(&notes, "How does borrowing relate to lifetimes?", 1),
+            [lifetimes]
+        );
+        // Words in the metadata alone do not count, short words are ignored
+        assert!(relevant_notes(&notes, "title of a", 5).is_empty());
+    }
+}
@@ -0,0 +1,867 @@
+//! A small Markdown parser for rendering notes without external tools: the common blocks
+//! (headings, paragraphs, lists, quotes, fenced code, tables, rules) and inlines (code,
+//! emphasis, links, images). Anything else is kept as text.
+
+use colored::Colorize;
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+};
+
+use crate::highlight::highlight;
+
+/// A block of a Markdown document.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Block {
+    Heading(usize, Vec<Inline>),
+    Paragraph(Vec<Inline>),
+    Code {
+        lang: String,
+        text: String,
+    },
+    Quote(Vec<Block>),
+    List {
+        ordered: bool,
+        items: Vec<Vec<Block>>,
+    },
+    Table {
+        header: Vec<Vec<Inline>>,
+        rows: Vec<Vec<Vec<Inline>>>,
+    },
+    Rule,
+}
+
+/// An inline element of a block.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Inline {
+    Text(String),
+    Code(String),
+    Strong(Vec<Inline>),
+    Emphasis(Vec<Inline>),
+    Link {
+        text: Vec<Inline>,
+        url: String,
+    },
+    Image {
+        alt: String,
+        url: String,
+    },
+    /// A hard line break, a line ending with two spaces or a backslash
+    Break,
+}
+
+pub fn parse(text: &str) -> Vec<Block> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut blocks = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        let trimmed = line.trim_start();
+
+        if trimmed.is_empty() {
+            i += 1;
+        } else if let Some(fence) = fence(trimmed) {
+            let lang = trimmed[fence.len()..].trim().to_string();
+            let mut code = Vec::new();
+            i += 1;
+            while i < lines.len() && !lines[i].trim_start().starts_with(fence) {
+                code.push(lines[i]);
+                i += 1;
+            }
+            // The closing fence
+            i += 1;
+            blocks.push(Block::Code {
+                lang,
+                text: code.join("\n"),
+            });
+        } else if let Some((level, text)) = heading(trimmed) {
+            blocks.push(Block::Heading(level, parse_inline(text)));
+            i += 1;
+        } else if is_rule(trimmed) {
+            blocks.push(Block::Rule);
+            i += 1;
+        } else if trimmed.starts_with('>') {
+            let mut quoted = Vec::new();
+            while i < lines.len() {
+                let Some(rest) = lines[i].trim_start().strip_prefix('>') else {
+                    break;
+                };
+                quoted.push(rest.strip_prefix(' ').unwrap_or(rest));
+                i += 1;
+            }
+            blocks.push(Block::Quote(parse(&quoted.join("\n"))));
+        } else if let Some((ordered, _, _)) = list_marker(line) {
+            let (list, next) = parse_list(&lines, i, ordered);
+            blocks.push(list);
+            i = next;
+        } else if line.contains('|') && lines.get(i + 1).is_some_and(|l| is_table_separator(l)) {
+            let header = table_cells(line);
+            let mut rows = Vec::new();
+            i += 2;
+            while i < lines.len() && lines[i].contains('|') {
+                rows.push(table_cells(lines[i]));
+                i += 1;
+            }
+            blocks.push(Block::Table { header, rows });
+        } else {
+            let mut paragraph = vec![trimmed];
+            i += 1;
+            while i < lines.len() && !starts_block(lines[i]) {
+                paragraph.push(lines[i].trim_start());
+                i += 1;
+            }
+            blocks.push(Block::Paragraph(parse_inline(&paragraph.join("\n"))));
+        }
+    }
+
+    blocks
+}
+
+/// The items of the list starting at `lines[start]`, and the index of the line after it.
+fn parse_list(lines: &[&str], start: usize, ordered: bool) -> (Block, usize) {
+    let base = indent(lines[start]);
+    let mut items = Vec::new();
+    let mut i = start;
+
+    while let Some((item_ordered, offset, content)) = lines.get(i).and_then(|l| list_marker(l)) {
+        if item_ordered != ordered || indent(lines[i]) > base {
+            break;
+        }
+        let mut item = vec![content.to_string()];
+        i += 1;
+        while i < lines.len() {
+            let line = lines[i];
+            if line.trim().is_empty() {
+                // Blank lines belong to the item if it goes on after them
+                let next = lines[i..].iter().position(|l| !l.trim().is_empty());
+                match next.map(|n| lines[i + n]) {
+                    Some(next) if indent(next) >= offset => item.push(String::new()),
+                    _ => break,
+                }
+            } else if indent(line) >= offset {
+                item.push(line[offset..].to_string());
+            } else if !starts_block(line) {
+                // A lazy continuation of the paragraph
+                item.push(line.trim_start().to_string());
+            } else {
+                break;
+            }
+            i += 1;
+        }
+        items.push(parse(&item.join("\n")));
+    }
+
+    (Block::List { ordered, items }, i)
+}
+
+fn indent(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+fn fence(trimmed: &str) -> Option<&'static str> {
+    ["```", "~~~"].into_iter().find(|f| trimmed.starts_with(f))
+}
+
+fn heading(trimmed: &str) -> Option<(usize, &str)> {
+    let level = trimmed.len() - trimmed.trim_start_matches('#').len();
+    let text = &trimmed[level..];
+    ((1..=6).contains(&level) && (text.is_empty() || text.starts_with(' ')))
+        .then(|| (level, text.trim().trim_end_matches('#').trim_end()))
+}
+
+fn is_rule(trimmed: &str) -> bool {
+    ['-', '*', '_'].iter().any(|&c| {
+        trimmed.chars().filter(|&x| x == c).count() >= 3
+            && trimmed.chars().all(|x| x == c || x == ' ')
+    })
+}
+
+/// `- item`, `* item`, `+ item` or `1. item`: whether the list is ordered, the column of the
+/// content, and the content.
+fn list_marker(line: &str) -> Option<(bool, usize, &str)> {
+    let trimmed = line.trim_start();
+    let indent = line.len() - trimmed.len();
+    let digits = trimmed.len()
+        - trimmed
+            .trim_start_matches(|c: char| c.is_ascii_digit())
+            .len();
+
+    let (ordered, marker_len) = if trimmed.starts_with(['-', '*', '+']) {
+        (false, 1)
+    } else if (1..=9).contains(&digits) && trimmed[digits..].starts_with(['.', ')']) {
+        (true, digits + 1)
+    } else {
+        return None;
+    };
+    let rest = &trimmed[marker_len..];
+    if rest.is_empty() {
+        return Some((ordered, indent + marker_len + 1, ""));
+    }
+    let content = rest.strip_prefix(' ')?;
+    Some((ordered, indent + marker_len + 1, content.trim_start()))
+}
+
+fn is_table_separator(line: &str) -> bool {
+    let trimmed = line.trim();
+    trimmed.contains('-')
+        && trimmed.contains('|')
+        && trimmed.chars().all(|c| matches!(c, '|' | '-' | ':' | ' '))
+}
+
+fn table_cells(line: &str) -> Vec<Vec<Inline>> {
+    let trimmed = line.trim().trim_start_matches('|');
+    let trimmed = trimmed.strip_suffix('|').unwrap_or(trimmed);
+    trimmed.split('|').map(|c| parse_inline(c.trim())).collect()
+}
+
+/// Whether a line interrupts a paragraph.
+fn starts_block(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    trimmed.is_empty()
+        || fence(trimmed).is_some()
+        || heading(trimmed).is_some()
+        || trimmed.starts_with('>')
+        || is_rule(trimmed)
+        || list_marker(line).is_some_and(|(_, _, content)| !content.is_empty())
+}
+
+pub fn parse_inline(text: &str) -> Vec<Inline> {
+    let mut inlines = Vec::new();
+    let mut buffer = String::new();
+    let mut rest = text;
+
+    while let Some(c) = rest.chars().next() {
+        let parsed = match c {
+            '`' => code_span(rest),
+            '*' | '_' => emphasis(rest, buffer.chars().last()),
+            '!' if rest.starts_with("![") => image(rest),
+            '[' => link(rest),
+            '<' => autolink(rest),
+            '\\' => match rest[1..].chars().next() {
+                Some('\n') => Some((Inline::Break, 2)),
+                Some(c) if c.is_ascii_punctuation() => {
+                    buffer.push(c);
+                    rest = &rest[1 + c.len_utf8()..];
+                    continue;
+                }
+                _ => None,
+            },
+            '\n' if buffer.ends_with("  ") => {
+                buffer.truncate(buffer.trim_end_matches(' ').len());
+                Some((Inline::Break, 1))
+            }
+            _ => None,
+        };
+
+        match parsed {
+            Some((inline, len)) => {
+                if !buffer.is_empty() {
+                    inlines.push(Inline::Text(std::mem::take(&mut buffer)));
+                }
+                inlines.push(inline);
+                rest = &rest[len..];
+            }
+            None => {
+                buffer.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    if !buffer.is_empty() {
+        inlines.push(Inline::Text(buffer));
+    }
+    inlines
+}
+
+fn code_span(rest: &str) -> Option<(Inline, usize)> {
+    let ticks = rest.len() - rest.trim_start_matches('`').len();
+    let delimiter = &rest[..ticks];
+    let end = rest[ticks..].find(delimiter)?;
+    let code = &rest[ticks..ticks + end];
+    Some((Inline::Code(code.trim().to_string()), ticks * 2 + end))
+}
+
+fn emphasis(rest: &str, before: Option<char>) -> Option<(Inline, usize)> {
+    let c = rest.chars().next()?;
+    // `snake_case` words are not emphasized
+    if c == '_' && before.is_some_and(|b| b.is_alphanumeric()) {
+        return None;
+    }
+    let run = (rest.len() - rest.trim_start_matches(c).len()).min(2);
+    let delimiter = &rest[..run];
+    let inner = &rest[run..];
+    if inner.starts_with(char::is_whitespace) {
+        return None;
+    }
+    // Escaped or preceded by a space, a delimiter does not close
+    let end = inner
+        .match_indices(delimiter)
+        .map(|(e, _)| e)
+        .find(|&e| e > 0 && !inner[..e].ends_with(['\\', ' ', '\t', '\n']))?;
+    let children = parse_inline(&inner[..end]);
+    let inline = if run == 2 {
+        Inline::Strong(children)
+    } else {
+        Inline::Emphasis(children)
+    };
+    Some((inline, run * 2 + end))
+}
+
+/// `[text](url)`: the text, the url and the length.
+fn bracket_and_url(rest: &str) -> Option<(&str, String, usize)> {
+    let mut depth = 0;
+    let close = rest.char_indices().find_map(|(i, c)| {
+        match c {
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+        None
+    })?;
+    let after = rest[close + 1..].strip_prefix('(')?;
+    let end = after.find(')')?;
+    // `[text](url "title")`
+    let url = after[..end].split_whitespace().next().unwrap_or_default();
+    let url = url
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .to_string();
+    Some((&rest[1..close], url, close + 2 + end + 1))
+}
+
+fn link(rest: &str) -> Option<(Inline, usize)> {
+    let (text, url, len) = bracket_and_url(rest)?;
+    Some((
+        Inline::Link {
+            text: parse_inline(text),
+            url,
+        },
+        len,
+    ))
+}
+
+fn image(rest: &str) -> Option<(Inline, usize)> {
+    let (alt, url, len) = bracket_and_url(&rest[1..])?;
+    Some((
+        Inline::Image {
+            alt: alt.to_string(),
+            url,
+        },
+        len + 1,
+    ))
+}
+
+fn autolink(rest: &str) -> Option<(Inline, usize)> {
+    let end = rest.find('>')?;
+    let url = &rest[1..end];
+    (url.starts_with("http://") || url.starts_with("https://")).then(|| {
+        (
+            Inline::Link {
+                text: vec![Inline::Text(url.to_string())],
+                url: url.to_string(),
+            },
+            end + 1,
+        )
+    })
+}
+
+/* HTML */
+
+pub fn to_html(blocks: &[Block]) -> String {
+    let mut html = String::new();
+    for block in blocks {
+        block_html(block, &mut html);
+    }
+    html
+}
+
+fn block_html(block: &Block, html: &mut String) {
+    match block {
+        Block::Heading(level, text) => {
+            html.push_str(&format!("<h{0}>{1}</h{0}>\n", level, inline_html(text)))
+        }
+        Block::Paragraph(text) => html.push_str(&format!("<p>{}</p>\n", inline_html(text))),
+        Block::Code { lang, text } => {
+            let class = if lang.is_empty() {
+                String::new()
+            } else {
+                format!(" class=\"language-{}\"", escape(lang))
+            };
+            html.push_str(&format!(
+                "<pre><code{}>{}</code></pre>\n",
+                class,
+                escape(text)
+            ));
+        }
+        Block::Quote(blocks) => {
+            html.push_str(&format!("<blockquote>\n{}</blockquote>\n", to_html(blocks)))
+        }
+        Block::List { ordered, items } => {
+            let tag = if *ordered { "ol" } else { "ul" };
+            html.push_str(&format!("<{}>\n", tag));
+            for item in items {
+                // Tight items are not wrapped in paragraphs
+                match item.as_slice() {
+                    [Block::Paragraph(text)] => {
+                        html.push_str(&format!("<li>{}</li>\n", inline_html(text)))
+                    }
+                    blocks => html.push_str(&format!("<li>\n{}</li>\n", to_html(blocks))),
+                }
+            }
+            html.push_str(&format!("</{}>\n", tag));
+        }
+        Block::Table { header, rows } => {
+            html.push_str("<table>\n<thead><tr>");
+            for cell in header {
+                html.push_str(&format!("<th>{}</th>", inline_html(cell)));
+            }
+            html.push_str("</tr></thead>\n<tbody>\n");
+            for row in rows {
+                html.push_str("<tr>");
+                for cell in row {
+                    html.push_str(&format!("<td>{}</td>", inline_html(cell)));
+                }
+                html.push_str("</tr>\n");
+            }
+            html.push_str("</tbody>\n</table>\n");
+        }
+        Block::Rule => html.push_str("<hr>\n"),
+    }
+}
+
+fn inline_html(inlines: &[Inline]) -> String {
+    inlines
+        .iter()
+        .map(|inline| match inline {
+            Inline::Text(text) => escape(text),
+            Inline::Code(code) => format!("<code>{}</code>", escape(code)),
+            Inline::Strong(children) => format!("<strong>{}</strong>", inline_html(children)),
+            Inline::Emphasis(children) => format!("<em>{}</em>", inline_html(children)),
+            Inline::Link { text, url } => {
+                format!("<a href=\"{}\">{}</a>", escape(url), inline_html(text))
+            }
+            Inline::Image { alt, url } => {
+                format!("<img src=\"{}\" alt=\"{}\">", escape(url), escape(alt))
+            }
+            Inline::Break => "<br>\n".to_string(),
+        })
+        .collect()
+}
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/* Terminal */
+
+/// A rendered line: pieces of text with their style.
+type Spans = Vec<(String, Style)>;
+
+/// The text in `style` for a terminal, with colors unless disabled.
+fn ansi(text: &str, style: Style) -> String {
+    let mut styled = text.normal();
+    if let Some(color) = style.fg {
+        styled = styled.color(match color {
+            Color::Red => colored::Color::Red,
+            Color::Green => colored::Color::Green,
+            Color::Yellow => colored::Color::Yellow,
+            Color::Blue => colored::Color::Blue,
+            Color::Magenta => colored::Color::Magenta,
+            Color::Cyan => colored::Color::Cyan,
+            Color::DarkGray => colored::Color::BrightBlack,
+            _ => colored::Color::White,
+        });
+    }
+    let modifier = style.add_modifier;
+    if modifier.contains(Modifier::BOLD) {
+        styled = styled.bold();
+    }
+    if modifier.contains(Modifier::ITALIC) {
+        styled = styled.italic();
+    }
+    if modifier.contains(Modifier::UNDERLINED) {
+        styled = styled.underline();
+    }
+    if modifier.contains(Modifier::DIM) {
+        styled = styled.dimmed();
+    }
+    styled.to_string()
+}
+
+/// A word of a paragraph, the unit of wrapping.
+#[derive(Debug)]
+enum Piece {
+    Word {
+        text: String,
+        style: Style,
+        /// Preceded by a space
+        space: bool,
+        /// Cannot be moved to the next line apart from the previous word, as in `**a**,`
+        glue: bool,
+    },
+    Break,
+}
+
+/// Render the document for a terminal `width` columns wide, with colors unless disabled.
+pub fn to_terminal(blocks: &[Block], width: usize) -> String {
+    blocks_terminal(blocks, width.max(20))
+        .iter()
+        .map(|line| {
+            line.iter()
+                .map(|(text, style)| ansi(text, *style))
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+        + "\n"
+}
+
+/// Render the document as the lines of a TUI `width` columns wide.
+pub fn to_lines(blocks: &[Block], width: usize) -> Vec<Line<'static>> {
+    blocks_terminal(blocks, width.max(20))
+        .into_iter()
+        .map(|line| {
+            Line::from(
+                line.into_iter()
+                    .map(|(text, style)| Span::styled(text, style))
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .collect()
+}
+
+/// The lines of the blocks, with a blank line between blocks.
+fn blocks_terminal(blocks: &[Block], width: usize) -> Vec<Spans> {
+    let mut lines = Vec::new();
+    for (i, block) in blocks.iter().enumerate() {
+        if i > 0 {
+            lines.push(Spans::new());
+        }
+        lines.extend(block_terminal(block, width));
+    }
+    lines
+}
+
+/// The line with `prefix` before it.
+fn prefixed(prefix: &str, style: Style, mut line: Spans) -> Spans {
+    line.insert(0, (prefix.to_string(), style));
+    line
+}
+
+fn block_terminal(block: &Block, width: usize) -> Vec<Spans> {
+    match block {
+        Block::Heading(level, text) => {
+            let style = Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD);
+            let marker = format!("{} ", "#".repeat(*level));
+            wrap(&pieces(text, style), width - marker.len())
+                .into_iter()
+                .map(|line| prefixed(&marker, style, line))
+                .collect()
+        }
+        Block::Paragraph(text) => wrap(&pieces(text, Style::default()), width),
+        Block::Code { lang, text } => match highlight(lang, text) {
+            Some(lines) => lines
+                .into_iter()
+                .map(|tokens| {
+                    let line = tokens
+                        .into_iter()
+                        .map(|(token, text)| (text.to_string(), token.style()))
+                        .collect();
+                    prefixed("  ", Style::default(), line)
+                })
+                .collect(),
+            None => text
+                .lines()
+                .map(|line| {
+                    vec![
+                        ("  ".to_string(), Style::default()),
+                        (line.to_string(), Style::default().fg(Color::Green)),
+                    ]
+                })
+                .collect(),
+        },
+        Block::Quote(blocks) => blocks_terminal(blocks, width - 2)
+            .into_iter()
+            .map(|line| prefixed("│ ", Style::default().add_modifier(Modifier::DIM), line))
+            .collect(),
+        Block::List { ordered, items } => {
+            let mut lines = Vec::new();
+            for (n, item) in items.iter().enumerate() {
+                let marker = if *ordered {
+                    format!("{}. ", n + 1)
+                } else {
+                    "• ".to_string()
+                };
+                let indent = display_width(&marker);
+                for (i, line) in blocks_terminal(item, width - indent)
+                    .into_iter()
+                    .enumerate()
+                {
+                    if i == 0 {
+                        lines.push(prefixed(&marker, Style::default(), line));
+                    } else if line.is_empty() {
+                        lines.push(line);
+                    } else {
+                        lines.push(prefixed(&" ".repeat(indent), Style::default(), line));
+                    }
+                }
+            }
+            lines
+        }
+        Block::Table { header, rows } => {
+            let plain = |cell: &[Inline]| plain_text(cell);
+            let columns = header
+                .len()
+                .max(rows.iter().map(Vec::len).max().unwrap_or(0));
+            let widths: Vec<usize> = (0..columns)
+                .map(|c| {
+                    std::iter::once(header)
+                        .chain(rows)
+                        .filter_map(|row| row.get(c))
+                        .map(|cell| display_width(&plain(cell)))
+                        .max()
+                        .unwrap_or(0)
+                })
+                .collect();
+            let render = |row: &[Vec<Inline>], style: Style| {
+                let mut line = Spans::new();
+                for (c, width) in widths.iter().enumerate() {
+                    let text = row.get(c).map(|cell| plain(cell)).unwrap_or_default();
+                    let padding = " ".repeat(width - display_width(&text));
+                    if c > 0 {
+                        line.push(("  ".to_string(), Style::default()));
+                    }
+                    line.push((text, style));
+                    line.push((padding, Style::default()));
+                }
+                // No trailing spaces
+                while line.last().is_some_and(|(text, _)| text.trim().is_empty()) {
+                    line.pop();
+                }
+                line
+            };
+
+            let mut lines = vec![render(
+                header,
+                Style::default().add_modifier(Modifier::BOLD),
+            )];
+            let rule: Vec<String> = widths.iter().map(|w| "─".repeat(*w)).collect();
+            lines.push(vec![(
+                rule.join("  "),
+                Style::default().add_modifier(Modifier::DIM),
+            )]);
+            lines.extend(rows.iter().map(|row| render(row, Style::default())));
+            lines
+        }
+        Block::Rule => vec![vec![(
+            "─".repeat(width.min(40)),
+            Style::default().add_modifier(Modifier::DIM),
+        )]],
+    }
+}
+
+fn pieces(inlines: &[Inline], style: Style) -> Vec<Piece> {
+    let mut pieces = Vec::new();
+    add_pieces(inlines, style, &mut pieces, &mut false);
+    pieces
+}
+
+/// `space` tells whether a space is pending before the next word.
+fn add_pieces(inlines: &[Inline], style: Style, pieces: &mut Vec<Piece>, space: &mut bool) {
+    let push_text = |text: &str, style: Style, pieces: &mut Vec<Piece>, space: &mut bool| {
+        let mut word = String::new();
+        let flush = |word: &mut String, pieces: &mut Vec<Piece>, space: &mut bool| {
+            if !word.is_empty() {
+                pieces.push(Piece::Word {
+                    text: std::mem::take(word),
+                    style,
+                    space: *space,
+                    glue: !*space && matches!(pieces.last(), Some(Piece::Word { .. })),
+                });
+                *space = false;
+            }
+        };
+        for c in text.chars() {
+            if c.is_whitespace() {
+                flush(&mut word, pieces, space);
+                *space = true;
+            } else if is_wide(c) {
+                // Lines can break between wide characters, which are not spaced
+                flush(&mut word, pieces, space);
+                pieces.push(Piece::Word {
+                    text: c.to_string(),
+                    style,
+                    space: std::mem::take(space),
+                    glue: false,
+                });
+            } else {
+                word.push(c);
+            }
+        }
+        flush(&mut word, pieces, space);
+    };
+
+    let dimmed = style.add_modifier(Modifier::DIM);
+    for inline in inlines {
+        match inline {
+            Inline::Text(text) => push_text(text, style, pieces, space),
+            Inline::Code(code) => push_text(code, style.fg(Color::Yellow), pieces, space),
+            Inline::Strong(children) => {
+                add_pieces(children, style.add_modifier(Modifier::BOLD), pieces, space)
+            }
+            Inline::Emphasis(children) => add_pieces(
+                children,
+                style.add_modifier(Modifier::ITALIC),
+                pieces,
+                space,
+            ),
+            Inline::Link { text, url } => {
+                let link = style.fg(Color::Blue).add_modifier(Modifier::UNDERLINED);
+                add_pieces(text, link, pieces, space);
+                if plain_text(text) != *url {
+                    *space = true;
+                    push_text(&format!("({})", url), dimmed, pieces, space);
+                }
+            }
+            Inline::Image { alt, url } => {
+                push_text(&format!("[image: {}]({})", alt, url), dimmed, pieces, space);
+            }
+            Inline::Break => {
+                pieces.push(Piece::Break);
+                *space = false;
+            }
+        }
+    }
+}
+
+/// Lay the words out in lines of at most `width` columns, unless a word is longer.
+fn wrap(pieces: &[Piece], width: usize) -> Vec<Spans> {
+    let mut lines = Vec::new();
+    let mut line = Spans::new();
+    let mut used = 0;
+
+    for piece in pieces {
+        match piece {
+            Piece::Word {
+                text,
+                style,
+                space,
+                glue,
+            } => {
+                let word_width = display_width(text);
+                let space = *space && used > 0;
+                if used > 0 && !glue && used + usize::from(space) + word_width > width {
+                    lines.push(std::mem::take(&mut line));
+                    used = 0;
+                } else if space {
+                    line.push((" ".to_string(), Style::default()));
+                    used += 1;
+                }
+                line.push((text.clone(), *style));
+                used += word_width;
+            }
+            Piece::Break => {
+                lines.push(std::mem::take(&mut line));
+                used = 0;
+            }
+        }
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+fn plain_text(inlines: &[Inline]) -> String {
+    inlines
+        .iter()
+        .map(|inline| match inline {
+            Inline::Text(text) | Inline::Code(text) => text.clone(),
+            Inline::Strong(children) | Inline::Emphasis(children) => plain_text(children),
+            Inline::Link { text, .. } => plain_text(text),
+            Inline::Image { alt, .. } => alt.clone(),
+            Inline::Break => " ".to_string(),
+        })
+        .collect()
+}
+
+/// The number of columns taken by the text, wide characters (CJK, emojis) taking two.
+fn display_width(text: &str) -> usize {
+    text.chars().map(|c| if is_wide(c) { 2 } else { 1 }).sum()
+}
+
+fn is_wide(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x1100..=0x115F
+            | 0x2E80..=0xA4CF
+            | 0xAC00..=0xD7A3
+            | 0xF900..=0xFAFF
+            | 0xFE30..=0xFE4F
+            | 0xFF00..=0xFF60
+            | 0xFFE0..=0xFFE6
+            | 0x1F300..=0x1F64F
+            | 0x1F900..=0x1F9FF
+            | 0x20000..=0x3FFFD
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_html() {
+        let text = "# Title #\n\nSome *em* and **strong** `a<b` text,\nsnake_case [link](a.md \"t\") ![img](i.png)\n\n- one\n- two\n  1. nested\n\n> quoted\n> **text**\n\n```rust\nfn main() {}\n```\n\n| a | b |\n|---|:-:|\n| 1 | 2 |\n\n---\n";
+        assert_eq!(
+            to_html(&parse(text)),
+            "<h1>Title</h1>\n\
+             <p>Some <em>em</em> and <strong>strong</strong> <code>a&lt;b</code> text,\nsnake_case <a href=\"a.md\">link</a> <img src=\"i.png\" alt=\"img\"></p>\n\
+             <ul>\n<li>one</li>\n<li>\n<p>two</p>\n<ol>\n<li>nested</li>\n</ol>\n</li>\n</ul>\n\
+             <blockquote>\n<p>quoted\n<strong>text</strong></p>\n</blockquote>\n\
+             <pre><code class=\"language-rust\">fn main() {}</code></pre>\n\
+             <table>\n<thead><tr><th>a</th><th>b</th></tr></thead>\n<tbody>\n<tr><td>1</td><td>2</td></tr>\n</tbody>\n</table>\n\
+             <hr>\n"
+        );
+
+        // Not emphasis, not links
+        assert_eq!(
+            parse_inline("a * b [[wiki]] 2*3 \\*x\\*"),
+            [Inline::Text("a * b [[wiki]] 2*3 *x*".into())]
+        );
+        assert_eq!(
+            parse_inline("a  \nb"),
+            [
+                Inline::Text("a".into()),
+                Inline::Break,
+                Inline::Text("b".into())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_to_terminal() {
+        colored::control::set_override(false);
+        let text = "## Notes\n\nA **bold** word, `code` and [a link](https://a.b) in a long line.\n\n- first item\n- second\n\n> 引用中文内容\n\n| k | value |\n|---|---|\n| a | 1 |\n";
+        assert_eq!(
+            to_terminal(&parse(text), 20),
+            "## Notes\n\n\
+             A bold word, code\nand a link\n(https://a.b) in a\nlong line.\n\n\
+             • first item\n• second\n\n\
+             │ 引用中文内容\n\n\
+             k  value\n─  ─────\na  1\n"
+        );
+        colored::control::unset_override();
+    }
+}
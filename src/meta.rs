@@ -0,0 +1,132 @@
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use serde_json::Value;
+use std::{fs, path::Path};
+
+use crate::atomic;
+use crate::metadata::{ensure_writable, parse_metadata, remove_field, set_field};
+use crate::vault::Note;
+
+/// What `noxe meta` does with a metadata field.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MetaAction {
+    Get,
+    Set,
+    #[value(alias = "rm")]
+    Del,
+}
+
+/// Read a metadata field of a note. Fails when the note has no such field, so that scripts
+/// can tell.
+pub fn get(note: &Path, key: &str) -> Result<Value> {
+    let main_file = note.main_file_path()?;
+    let content = fs::read_to_string(&main_file)
+        .with_context(|| format!("Failed to read note '{}'", main_file.display()))?;
+    match parse_metadata(&content, main_file.note_type()?).get(key) {
+        Some(value) if !value.is_null() => Ok(value),
+        _ => bail!("'{}' has no field '{}'", note.display(), key),
+    }
+}
+
+/// Set a metadata field of a note in place, or remove it when `value` is `None`.
+pub fn update(note: &Path, key: &str, value: Option<&Value>, override_lock: bool) -> Result<()> {
+    update_fields(note, &[(key, value)], override_lock)
+}
+
+/// Set or remove several metadata fields of a note at once. Nothing is written when any of
+/// them is read-only.
+pub fn update_fields(
+    note: &Path,
+    fields: &[(&str, Option<&Value>)],
+    override_lock: bool,
+) -> Result<()> {
+    let main_file = note.main_file_path()?;
+    let note_type = main_file.note_type()?;
+    let content = fs::read_to_string(&main_file)
+        .with_context(|| format!("Failed to read note '{}'", main_file.display()))?;
+    for (key, _) in fields {
+        ensure_writable(&content, note_type, key, override_lock)?;
+    }
+
+    let mut updated = content.clone();
+    for (key, value) in fields {
+        updated = match value {
+            Some(value) => set_field(&updated, note_type, key, value)?,
+            None => remove_field(&updated, note_type, key)?,
+        };
+    }
+    if updated != content {
+        atomic::write(&main_file, updated)?;
+    }
+    Ok(())
+}
+
+/// The value to set: YAML, like `[rust, cli]`, `42` or `true`, unless `string` is set. Text
+/// that is not valid YAML is a string.
+pub fn parse_value(value: &str, string: bool) -> Value {
+    if string {
+        return Value::String(value.to_string());
+    }
+    match serde_yml::from_str::<Value>(value) {
+        Ok(Value::Null) if !value.trim().is_empty() && value.trim() != "null" => {
+            Value::String(value.to_string())
+        }
+        Ok(value) => value,
+        Err(_) => Value::String(value.to_string()),
+    }
+}
+
+/// The value as printed by `noxe meta get`: strings as they are, the rest as JSON.
+pub fn format_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_meta() {
+        let tmp_dir = tempdir().unwrap();
+        let md = tmp_dir.path().join("rust.md");
+        fs::write(&md, "---\ntitle: Rust\nstatus: draft\n---\n\nBody\n").unwrap();
+
+        assert_eq!(get(&md, "title").unwrap(), Value::from("Rust"));
+        assert!(get(&md, "course").is_err());
+
+        update(
+            &md,
+            "keywords",
+            Some(&parse_value("[lang, cli]", false)),
+            false,
+        )
+        .unwrap();
+        update(&md, "status", None, false).unwrap();
+        assert_eq!(
+            fs::read_to_string(&md).unwrap(),
+            "---\ntitle: Rust\nkeywords: [lang, cli]\n---\n\nBody\n"
+        );
+        assert_eq!(
+            format_value(&get(&md, "keywords").unwrap()),
+            r#"["lang","cli"]"#
+        );
+
+        let typ = tmp_dir.path().join("net.typ");
+        fs::write(&typ, "#set document(title: \"Net\")\n\nBody\n").unwrap();
+        update(&typ, "title", Some(&parse_value("TCP", false)), false).unwrap();
+        assert_eq!(format_value(&get(&typ, "title").unwrap()), "TCP");
+        assert!(fs::read_to_string(&typ).unwrap().ends_with("\n\nBody\n"));
+
+        fs::write(&md, "---\ntitle: Rust\nreadonly: [title]\n---\n").unwrap();
+        assert!(update(&md, "title", Some(&Value::from("Go")), false).is_err());
+        update(&md, "title", Some(&Value::from("Go")), true).unwrap();
+
+        assert_eq!(parse_value("42", false), Value::from(42));
+        assert_eq!(parse_value("42", true), Value::from("42"));
+        assert_eq!(parse_value("a: b: c", false), Value::from("a: b: c"));
+    }
+}
@@ -0,0 +1,1165 @@
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+use std::{collections::BTreeMap, ops::Range, path::Path, str::FromStr};
+
+use crate::bounded::{METADATA_LIMIT, preview_limit, read_head};
+use crate::cli::NoteType;
+use crate::extract::{NoteText, extractor};
+use crate::vault::Note;
+
+/// The field protecting a note, or some of its fields, from being changed by noxe.
+pub const READONLY: &str = "readonly";
+
+/// Structured fields of a note, keyed by field name.
+pub type Fields = BTreeMap<String, Value>;
+
+/// Metadata of a note, parsed from the YAML front matter of markdown notes, from
+/// `#set document(...)` and `#metadata((...))` of typst notes or from the `#+KEY: value` lines
+/// heading org notes.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct NoteMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub keywords: Vec<String>,
+    pub date: Option<String>,
+    /// All other fields
+    pub fields: Fields,
+}
+
+impl NoteMetadata {
+    /// Build the metadata from raw fields, lifting the well-known keys out of them.
+    pub fn from_fields(mut fields: Fields) -> Self {
+        let author = fields.remove("author").and_then(|v| match v {
+            Value::Array(items) => Some(
+                items
+                    .iter()
+                    .map(value_to_string)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ),
+            Value::Null => None,
+            v => Some(value_to_string(&v)),
+        });
+
+        let keywords = match fields.remove("keywords") {
+            Some(Value::Array(items)) => items.iter().map(value_to_string).collect(),
+            Some(Value::String(s)) => s
+                .split(',')
+                .map(|k| k.trim().to_string())
+                .filter(|k| !k.is_empty())
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        let title = fields.remove("title").filter(|v| !v.is_null());
+        let date = fields.remove("date").filter(|v| !v.is_null());
+
+        NoteMetadata {
+            title: title.as_ref().map(value_to_string),
+            author,
+            keywords,
+            date: date.as_ref().map(value_to_string),
+            fields,
+        }
+    }
+
+    /// Look up a field by name, including the well-known ones.
+    pub fn get(&self, key: &str) -> Option<Value> {
+        match key {
+            "title" => self.title.clone().map(Value::String),
+            "author" => self.author.clone().map(Value::String),
+            "keywords" => Some(Value::Array(
+                self.keywords.iter().cloned().map(Value::String).collect(),
+            )),
+            "date" => self.date.clone().map(Value::String),
+            _ => self.fields.get(key).cloned(),
+        }
+    }
+
+    /// Check whether the note is locked as a whole, with `readonly: true`.
+    pub fn is_readonly(&self) -> bool {
+        match self.fields.get(READONLY) {
+            Some(Value::Bool(locked)) => *locked,
+            // Org keywords are strings
+            Some(Value::String(s)) => s.trim() == "true",
+            _ => false,
+        }
+    }
+
+    /// Check whether a field is protected by `readonly`: `true` locks every field, a list (or a
+    /// comma-separated string) of names locks those. The lock protects itself too.
+    pub fn is_locked(&self, key: &str) -> bool {
+        if self.is_readonly() {
+            return true;
+        }
+        match self.fields.get(READONLY) {
+            Some(Value::Array(keys)) => {
+                key == READONLY || keys.iter().any(|k| value_to_string(k) == key)
+            }
+            Some(Value::String(keys)) => {
+                key == READONLY || keys.split(',').any(|k| k.trim() == key)
+            }
+            _ => false,
+        }
+    }
+
+    /// Check whether the title, author or any keyword matches the pattern.
+    pub fn is_match(&self, pattern: &regex::Regex) -> bool {
+        self.title.as_deref().is_some_and(|t| pattern.is_match(t))
+            || self.author.as_deref().is_some_and(|a| pattern.is_match(a))
+            || self.keywords.iter().any(|k| pattern.is_match(k))
+    }
+}
+
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+/// Read the metadata of a note's main file.
+pub fn read_metadata(main_file: &Path) -> Result<NoteMetadata> {
+    Ok(NoteText::read_head(main_file, METADATA_LIMIT)?.metadata())
+}
+
+/// Parse the metadata of a note from its content.
+pub fn parse_metadata(content: &str, note_type: NoteType) -> NoteMetadata {
+    extractor(note_type).metadata(content)
+}
+
+/// Split a markdown note into its YAML front matter (without the `---` fences) and body.
+pub fn split_frontmatter(content: &str) -> Option<(&str, &str)> {
+    let rest = content
+        .strip_prefix("---\n")
+        .or_else(|| content.strip_prefix("---\r\n"))?;
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            return Some((&rest[..offset], &rest[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+
+    None
+}
+
+/// Split an org note into its leading `#+KEY: value` lines and body.
+pub fn split_org_keywords(content: &str) -> (&str, &str) {
+    let mut offset = 0;
+    for line in content.split_inclusive('\n') {
+        if org_keyword(line).is_none() {
+            break;
+        }
+        offset += line.len();
+    }
+
+    content.split_at(offset)
+}
+
+/// The key, lowercased, and value of a `#+KEY: value` line.
+fn org_keyword(line: &str) -> Option<(String, &str)> {
+    let (key, value) = line.trim_end().strip_prefix("#+")?.split_once(':')?;
+    if key.is_empty() || key.contains(char::is_whitespace) {
+        return None;
+    }
+    Some((key.to_lowercase(), value.trim()))
+}
+
+/// Parse the `#+KEY: value` lines heading an org note.
+pub fn parse_org_keywords(content: &str) -> Fields {
+    split_org_keywords(content)
+        .0
+        .lines()
+        .filter_map(org_keyword)
+        .map(|(key, value)| (key, Value::String(value.to_string())))
+        .collect()
+}
+
+/// Remove the metadata of a note (front matter, `#set document(...)`, `#metadata(...)`,
+/// `#+KEY: value` lines), leaving the body.
+pub fn strip_metadata(content: &str, note_type: NoteType) -> String {
+    extractor(note_type).body(content)
+}
+
+/// Remove the `#set document(...)` and `#metadata(...)` calls of a typst note.
+pub fn strip_typst_metadata(content: &str) -> String {
+    remove_typst_metadata(content, false)
+}
+
+/// Like [`strip_typst_metadata`], but keep the line breaks of the calls, so that the lines of
+/// the body have the line numbers of the file.
+pub fn blank_typst_metadata(content: &str) -> String {
+    remove_typst_metadata(content, true)
+}
+
+fn remove_typst_metadata(content: &str, keep_lines: bool) -> String {
+    const CALLS: [&str; 2] = ["#set document", "#metadata"];
+
+    let mut body = String::new();
+    let mut rest = content;
+
+    while let Some((pos, call)) = CALLS
+        .iter()
+        .filter_map(|c| rest.find(c).map(|pos| (pos, c)))
+        .min()
+    {
+        body.push_str(&rest[..pos]);
+        let removed = &rest[pos..];
+        rest = &rest[pos + call.len()..];
+
+        let args = rest.trim_start_matches([' ', '\t']);
+        let mut parser = TypstParser::new(args);
+        if parser.peek() != Some('(') || parser.raw_group().is_none() {
+            // Not a call, or unbalanced: keep the text as is.
+            body.push_str(call);
+            continue;
+        }
+        rest = &args[parser.pos..];
+
+        // Drop the label of `#metadata(...) <label>`
+        if let Some(label) = rest.trim_start_matches([' ', '\t']).strip_prefix('<')
+            && let Some(end) = label.find('>')
+            && !label[..end].contains('\n')
+        {
+            rest = &label[end + 1..];
+        }
+
+        if keep_lines {
+            let removed = &removed[..removed.len() - rest.len()];
+            body.extend(removed.matches('\n'));
+        }
+    }
+    body.push_str(rest);
+
+    body
+}
+
+/// Read the first `n` non-empty lines of a note's body, skipping its metadata and the
+/// typst preamble (`#import`, `#show`, `#set`).
+pub fn preview_lines(main_file: &Path, n: usize) -> Result<Vec<String>> {
+    let content = read_head(main_file, preview_limit())?.text;
+    let note_type = main_file.note_type()?;
+
+    let lines = strip_metadata(&content, note_type)
+        .lines()
+        .map(str::trim_end)
+        .filter(|l| !l.trim().is_empty())
+        .filter(|l| {
+            !matches!(note_type, NoteType::Typ)
+                || !["#import", "#show", "#set"]
+                    .iter()
+                    .any(|p| l.trim_start().starts_with(p))
+        })
+        .take(n)
+        .map(str::to_string)
+        .collect();
+
+    Ok(lines)
+}
+
+/// Parse the YAML front matter of a markdown note.
+pub fn parse_frontmatter(content: &str) -> Fields {
+    split_frontmatter(content)
+        .and_then(|(yaml, _)| serde_yml::from_str::<serde_yml::Value>(yaml).ok())
+        .and_then(|yaml| serde_json::to_value(yaml).ok())
+        .and_then(|json| match json {
+            Value::Object(map) => Some(map.into_iter().collect()),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+/// Parse the arguments of the first `#set document(...)` rule of a typst note.
+pub fn parse_typst_document(content: &str) -> Fields {
+    const OPEN: &str = "#set document";
+
+    let Some(pos) = content.find(OPEN) else {
+        return Fields::new();
+    };
+
+    let mut parser = TypstParser::new(&content[pos + OPEN.len()..]);
+    match parser.parse_value() {
+        Some(Value::Object(map)) => map.into_iter().collect(),
+        _ => Fields::new(),
+    }
+}
+
+/// Collect the dictionaries of every `#metadata((...))` block in a typst file.
+///
+/// Later blocks override earlier ones when they define the same key. Non-dictionary
+/// metadata values (e.g. `#metadata("x") <label>`) are ignored.
+pub fn parse_typst_metadata(content: &str) -> Fields {
+    const OPEN: &str = "#metadata(";
+
+    let mut fields = Fields::new();
+    let mut rest = content;
+
+    while let Some(pos) = rest.find(OPEN) {
+        rest = &rest[pos + OPEN.len()..];
+
+        let mut parser = TypstParser::new(rest);
+        if let Some(Value::Object(map)) = parser.parse_value() {
+            fields.extend(map);
+        }
+    }
+
+    fields
+}
+
+/// A `key: value` entry of a Typst argument list, with its span in the source.
+type Entry = (String, Range<usize>);
+
+/// A minimal parser for typst literal values (strings, numbers, booleans, `none`,
+/// arrays, dictionaries and `datetime(...)`). Anything else is kept as raw text.
+pub struct TypstParser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl<'a> TypstParser<'a> {
+    pub fn new(src: &'a str) -> Self {
+        Self { src, pos: 0 }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_ws(&mut self) {
+        loop {
+            let rest = self.rest();
+            if rest.starts_with("//") {
+                self.pos += rest.find('\n').unwrap_or(rest.len());
+            } else if rest.starts_with("/*") {
+                self.pos += rest.find("*/").map_or(rest.len(), |i| i + 2);
+            } else if self.peek().is_some_and(char::is_whitespace) {
+                self.bump();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn ident(&mut self) -> Option<&'a str> {
+        let rest = self.rest();
+        let len = rest
+            .char_indices()
+            .find(|&(i, c)| !(c.is_alphanumeric() || c == '_' || (i > 0 && c == '-')))
+            .map_or(rest.len(), |(i, _)| i);
+
+        if len == 0 || rest.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+
+        self.pos += len;
+        Some(&rest[..len])
+    }
+
+    fn string(&mut self) -> Option<String> {
+        if !self.eat('"') {
+            return None;
+        }
+
+        let mut s = String::new();
+        loop {
+            match self.bump()? {
+                '"' => return Some(s),
+                '\\' => match self.bump()? {
+                    'n' => s.push('\n'),
+                    't' => s.push('\t'),
+                    c => s.push(c),
+                },
+                c => s.push(c),
+            }
+        }
+    }
+
+    /// Skip a balanced `(...)` group and return its raw text (including parentheses).
+    fn raw_group(&mut self) -> Option<&'a str> {
+        let start = self.pos;
+        let mut depth = 0usize;
+
+        loop {
+            match self.peek()? {
+                '"' => {
+                    self.string()?;
+                    continue;
+                }
+                '(' => depth += 1,
+                ')' => {
+                    depth = depth.checked_sub(1)?;
+                    if depth == 0 {
+                        self.bump();
+                        return Some(&self.src[start..self.pos]);
+                    }
+                }
+                _ => {}
+            }
+            self.bump();
+        }
+    }
+
+    fn number(&mut self) -> Value {
+        let rest = self.rest();
+        let len = rest
+            .char_indices()
+            .find(|&(i, c)| !(c.is_ascii_digit() || c == '.' || (i == 0 && c == '-')))
+            .map_or(rest.len(), |(i, _)| i);
+        self.pos += len;
+        let digits = &rest[..len];
+
+        // Lengths, angles, ratios... (`1pt`, `50%`) are kept as raw text.
+        let unit_len = self
+            .rest()
+            .find(|c: char| !(c.is_alphabetic() || c == '%'))
+            .unwrap_or(self.rest().len());
+        if unit_len > 0 {
+            self.pos += unit_len;
+            return Value::String(rest[..len + unit_len].to_string());
+        }
+
+        if let Ok(i) = digits.parse::<i64>() {
+            Value::Number(i.into())
+        } else if let Some(n) = digits.parse::<f64>().ok().and_then(Number::from_f64) {
+            Value::Number(n)
+        } else {
+            Value::String(digits.to_string())
+        }
+    }
+
+    /// Parse a single value. Returns `None` on malformed input.
+    pub fn parse_value(&mut self) -> Option<Value> {
+        self.skip_ws();
+
+        match self.peek()? {
+            '"' => self.string().map(Value::String),
+            '(' => self.group(),
+            c if c.is_ascii_digit() || c == '-' => Some(self.number()),
+            _ => {
+                let ident = self.ident()?;
+                match ident {
+                    "true" => Some(Value::Bool(true)),
+                    "false" => Some(Value::Bool(false)),
+                    "none" | "auto" => Some(Value::Null),
+                    "datetime" if self.peek() == Some('(') => {
+                        let Value::Object(args) = self.group()? else {
+                            return None;
+                        };
+                        Some(Value::String(format_datetime(&args)))
+                    }
+                    _ if self.peek() == Some('(') => {
+                        let raw = self.raw_group()?;
+                        Some(Value::String(format!("{}{}", ident, raw)))
+                    }
+                    _ => Some(Value::String(ident.to_string())),
+                }
+            }
+        }
+    }
+
+    /// Parse a key of a dictionary entry, leaving the cursor after the `:`.
+    fn key(&mut self) -> Option<String> {
+        let start = self.pos;
+        self.skip_ws();
+
+        let key = if self.peek() == Some('"') {
+            self.string()
+        } else {
+            self.ident().map(str::to_string)
+        };
+
+        self.skip_ws();
+        if let Some(key) = key
+            && self.eat(':')
+        {
+            return Some(key);
+        }
+
+        self.pos = start;
+        None
+    }
+
+    /// Parse the `key: value` entries of an argument list or dictionary, returning the span
+    /// of each entry and the position of the closing parenthesis.
+    fn entries(&mut self) -> Option<(Vec<Entry>, usize)> {
+        self.skip_ws();
+        if !self.eat('(') {
+            return None;
+        }
+        let mut entries = Vec::new();
+        loop {
+            self.skip_ws();
+            if self.peek() == Some(')') {
+                let close = self.pos;
+                self.bump();
+                return Some((entries, close));
+            }
+
+            let start = self.pos;
+            let key = self.key()?;
+            self.parse_value()?;
+            entries.push((key, start..self.pos));
+
+            self.skip_ws();
+            if !self.eat(',') && self.peek() != Some(')') {
+                return None;
+            }
+        }
+    }
+
+    /// Parse a parenthesized group: a dictionary, an array, or a parenthesized value.
+    fn group(&mut self) -> Option<Value> {
+        if !self.eat('(') {
+            return None;
+        }
+        self.skip_ws();
+
+        // `(:)` is the empty dictionary, `()` the empty array.
+        if self.eat(':') {
+            self.skip_ws();
+            return self.eat(')').then(|| Value::Object(Map::new()));
+        }
+        if self.eat(')') {
+            return Some(Value::Array(Vec::new()));
+        }
+
+        if let Some(first_key) = self.key() {
+            return self.dict(first_key);
+        }
+
+        let mut items = Vec::new();
+        let mut trailing_comma = false;
+        loop {
+            self.skip_ws();
+            if self.eat(')') {
+                break;
+            }
+            items.push(self.parse_value()?);
+            self.skip_ws();
+            trailing_comma = self.eat(',');
+            if !trailing_comma {
+                self.skip_ws();
+                if !self.eat(')') {
+                    return None;
+                }
+                break;
+            }
+        }
+
+        if items.len() == 1 && !trailing_comma {
+            items.pop()
+        } else {
+            Some(Value::Array(items))
+        }
+    }
+
+    /// Parse the remaining entries of a dictionary whose first key has been consumed.
+    fn dict(&mut self, first_key: String) -> Option<Value> {
+        let mut map = Map::new();
+        let mut key = first_key;
+
+        loop {
+            let value = self.parse_value()?;
+            map.insert(key, value);
+
+            self.skip_ws();
+            if !self.eat(',') {
+                self.skip_ws();
+                return self.eat(')').then_some(Value::Object(map));
+            }
+
+            self.skip_ws();
+            if self.eat(')') {
+                return Some(Value::Object(map));
+            }
+            key = self.key()?;
+        }
+    }
+}
+
+fn format_datetime(args: &Map<String, Value>) -> String {
+    let get = |key: &str| args.get(key).and_then(Value::as_i64);
+
+    let date = match (get("year"), get("month"), get("day")) {
+        (Some(y), Some(m), Some(d)) => Some(format!("{:04}-{:02}-{:02}", y, m, d)),
+        _ => None,
+    };
+    let time = match (get("hour"), get("minute"), get("second")) {
+        (Some(h), Some(m), Some(s)) => Some(format!("{:02}:{:02}:{:02}", h, m, s)),
+        _ => None,
+    };
+
+    match (date, time) {
+        (Some(date), Some(time)) => format!("{} {}", date, time),
+        (Some(date), None) => date,
+        (None, Some(time)) => time,
+        (None, None) => String::new(),
+    }
+}
+
+/* Editing */
+
+/// Arguments of typst's `document` element, which are kept in `#set document(...)`. Other
+/// fields go to a `#metadata((...))` block.
+const TYPST_DOCUMENT_FIELDS: [&str; 5] = ["title", "author", "description", "keywords", "date"];
+
+/// Set a metadata field in place, preserving the rest of the note.
+pub fn set_field(content: &str, note_type: NoteType, key: &str, value: &Value) -> Result<String> {
+    match note_type {
+        NoteType::Md => Ok(edit_frontmatter(content, key, Some(value))),
+        NoteType::Typ => edit_typst(content, key, Some(value)),
+        NoteType::Org => Ok(edit_org(content, key, Some(value))),
+        NoteType::Custom(ext) => bail!("'{}' notes have no metadata", ext),
+    }
+}
+
+/// Fail if `readonly` protects the field, unless the lock is overridden.
+pub fn ensure_writable(
+    content: &str,
+    note_type: NoteType,
+    key: &str,
+    override_lock: bool,
+) -> Result<()> {
+    if !override_lock && parse_metadata(content, note_type).is_locked(key) {
+        bail!(
+            "'{}' is read-only (see `{}` in the metadata), use --override to change it",
+            key,
+            READONLY
+        );
+    }
+    Ok(())
+}
+
+/// Remove a metadata field in place, preserving the rest of the note.
+pub fn remove_field(content: &str, note_type: NoteType, key: &str) -> Result<String> {
+    match note_type {
+        NoteType::Md => Ok(edit_frontmatter(content, key, None)),
+        NoteType::Typ => edit_typst(content, key, None),
+        NoteType::Org => Ok(edit_org(content, key, None)),
+        NoteType::Custom(ext) => bail!("'{}' notes have no metadata", ext),
+    }
+}
+
+fn edit_frontmatter(content: &str, key: &str, value: Option<&Value>) -> String {
+    let new_line = value.map(|v| match to_yaml(v) {
+        block if block.starts_with('\n') => format!("{}:{}\n", key, block),
+        inline => format!("{}: {}\n", key, inline),
+    });
+
+    let Some((yaml, body)) = split_frontmatter(content) else {
+        return match new_line {
+            Some(line) => format!("---\n{}---\n\n{}", line, content),
+            None => content.to_string(),
+        };
+    };
+
+    let is_key_line = |l: &str| l.strip_prefix(key).is_some_and(|r| r.starts_with(':'));
+
+    let mut out = String::from("---\n");
+    let mut found = false;
+    let mut skipping = false;
+    for line in yaml.split_inclusive('\n') {
+        // Skip the indented or list continuation lines of the replaced key
+        if skipping && (line.starts_with([' ', '\t', '-']) || line.trim().is_empty()) {
+            continue;
+        }
+        skipping = false;
+
+        if is_key_line(line) {
+            if !found && let Some(new_line) = &new_line {
+                out.push_str(new_line);
+            }
+            found = true;
+            skipping = true;
+            continue;
+        }
+        out.push_str(line);
+    }
+    if !found && let Some(new_line) = new_line {
+        if !out.ends_with('\n') {
+            out.push('\n');
+        }
+        out.push_str(&new_line);
+    }
+    out.push_str("---\n");
+    out.push_str(body);
+
+    out
+}
+
+fn edit_org(content: &str, key: &str, value: Option<&Value>) -> String {
+    let new_line = value.map(|v| {
+        let value = match v {
+            Value::Array(items) => items
+                .iter()
+                .map(value_to_string)
+                .collect::<Vec<_>>()
+                .join(", "),
+            v => value_to_string(v),
+        };
+        format!("#+{}: {}\n", key.to_uppercase(), value)
+    });
+
+    let (keywords, body) = split_org_keywords(content);
+    if keywords.is_empty() {
+        return match new_line {
+            Some(line) => format!("{}\n{}", line, content),
+            None => content.to_string(),
+        };
+    }
+
+    let mut out = String::new();
+    let mut found = false;
+    for line in keywords.split_inclusive('\n') {
+        if org_keyword(line).is_some_and(|(k, _)| k == key.to_lowercase()) {
+            if !found && let Some(new_line) = &new_line {
+                out.push_str(new_line);
+            }
+            found = true;
+        } else {
+            out.push_str(line);
+            if !line.ends_with('\n') {
+                out.push('\n');
+            }
+        }
+    }
+    if !found && let Some(new_line) = &new_line {
+        out.push_str(new_line);
+    }
+    out.push_str(body);
+
+    out
+}
+
+fn edit_typst(content: &str, key: &str, value: Option<&Value>) -> Result<String> {
+    // For `#metadata(...)`, the arguments parsed below are the entries of its dictionary.
+    let open = if TYPST_DOCUMENT_FIELDS.contains(&key) {
+        "#set document"
+    } else {
+        "#metadata("
+    };
+
+    let Some(pos) = content.find(open) else {
+        let Some(value) = value else {
+            return Ok(content.to_string());
+        };
+        let block = if open == "#set document" {
+            format!("#set document({}: {})\n", key, to_typst(value))
+        } else {
+            format!("#metadata(({}: {}))\n", key, to_typst(value))
+        };
+        // Keep `#set document(...)` first
+        return Ok(match content.find("#set document") {
+            Some(doc) if open != "#set document" => {
+                let line_end = content[doc..]
+                    .find('\n')
+                    .map_or(content.len(), |i| doc + i + 1);
+                let mut out = content[..line_end].to_string();
+                if !out.ends_with('\n') {
+                    out.push('\n');
+                }
+                out.push_str(&block);
+                out.push_str(&content[line_end..]);
+                out
+            }
+            _ => format!("{}{}", block, content),
+        });
+    };
+
+    let args_start = pos + open.len();
+    let mut parser = TypstParser::new(&content[args_start..]);
+    let Some((entries, close)) = parser.entries() else {
+        bail!("Failed to parse '{}...)'", open);
+    };
+    let offset = |r: &Range<usize>| args_start + r.start..args_start + r.end;
+    let close = args_start + close;
+
+    let index = entries.iter().position(|(k, _)| k == key);
+    let mut out = content.to_string();
+
+    match (index, value) {
+        (Some(i), Some(value)) => {
+            out.replace_range(
+                offset(&entries[i].1),
+                &format!("{}: {}", key, to_typst(value)),
+            );
+        }
+        (Some(i), None) => {
+            let range = if i + 1 < entries.len() {
+                offset(&entries[i].1).start..offset(&entries[i + 1].1).start
+            } else if i > 0 {
+                offset(&entries[i - 1].1).end..offset(&entries[i].1).end
+            } else {
+                offset(&entries[i].1)
+            };
+            out.replace_range(range, "");
+        }
+        (None, Some(value)) => {
+            let entry = format!("{}: {}", key, to_typst(value));
+            match entries.last() {
+                Some((_, last)) => out.insert_str(offset(last).end, &format!(", {}", entry)),
+                None => out.insert_str(close, &entry),
+            }
+        }
+        (None, None) => {}
+    }
+
+    Ok(out)
+}
+
+/// Format a value as inline YAML, following `key: `. Mappings without nested mappings are
+/// indented blocks on the next lines.
+fn to_yaml(value: &Value) -> String {
+    fn scalar(s: &str) -> String {
+        let plain = !s.is_empty()
+            && s.chars()
+                .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '/' | ' '))
+            && !s.starts_with([' ', '-'])
+            && !s.ends_with(' ')
+            && serde_yml::from_str::<serde_yml::Value>(s).is_ok_and(|v| v.is_string());
+        if plain {
+            s.to_string()
+        } else {
+            Value::String(s.to_string()).to_string()
+        }
+    }
+
+    match value {
+        Value::Array(items) => {
+            let items = items
+                .iter()
+                .map(|v| match v {
+                    Value::String(s) => scalar(s),
+                    v => to_yaml(v),
+                })
+                .collect::<Vec<_>>();
+            format!("[{}]", items.join(", "))
+        }
+        Value::Object(map) if !map.is_empty() && !map.values().any(Value::is_object) => map
+            .iter()
+            .map(|(k, v)| format!("\n  {}: {}", k, to_yaml(v)))
+            .collect(),
+        v => v.to_string(),
+    }
+}
+
+/// Format a value as a typst literal.
+fn to_typst(value: &Value) -> String {
+    match value {
+        Value::Null => "none".to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        // JSON string escapes are valid typst string escapes
+        Value::String(s) => Value::String(s.clone()).to_string(),
+        Value::Array(items) => {
+            let items = items.iter().map(to_typst).collect::<Vec<_>>();
+            match items.len() {
+                1 => format!("({},)", items[0]),
+                _ => format!("({})", items.join(", ")),
+            }
+        }
+        Value::Object(map) if map.is_empty() => "(:)".to_string(),
+        Value::Object(map) => {
+            let entries = map
+                .iter()
+                .map(|(k, v)| format!("{}: {}", k, to_typst(v)))
+                .collect::<Vec<_>>();
+            format!("({})", entries.join(", "))
+        }
+    }
+}
+
+/// A `key=value` filter over note fields.
+#[derive(Debug, Clone)]
+pub struct FieldFilter {
+    pub key: String,
+    pub value: String,
+}
+
+impl FromStr for FieldFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() => Ok(FieldFilter {
+                key: key.trim().to_string(),
+                value: value.trim().to_string(),
+            }),
+            _ => Err(format!("Invalid field filter '{}', expected KEY=VALUE", s)),
+        }
+    }
+}
+
+impl FieldFilter {
+    /// Check the filter against the fields of a note. Values are compared by type:
+    /// numbers numerically, booleans and `none` by keyword, strings case-insensitively,
+    /// and arrays match when any of their elements matches.
+    pub fn matches(&self, metadata: &NoteMetadata) -> bool {
+        metadata
+            .get(&self.key)
+            .is_some_and(|v| value_matches(&v, &self.value))
+    }
+}
+
+fn value_matches(value: &Value, expected: &str) -> bool {
+    match value {
+        Value::String(s) => s.eq_ignore_ascii_case(expected),
+        Value::Number(n) => expected.parse::<f64>().is_ok_and(|e| n.as_f64() == Some(e)),
+        Value::Bool(b) => expected.parse::<bool>() == Ok(*b),
+        Value::Null => expected == "none",
+        Value::Array(items) => items.iter().any(|v| value_matches(v, expected)),
+        Value::Object(_) => false,
+    }
+}
+
+/// Check whether a note matches all field filters.
+pub fn matches_fields(note_path: &Path, filters: &[FieldFilter]) -> bool {
+    if filters.is_empty() {
+        return true;
+    }
+
+    let Ok(metadata) = note_path.main_file_path().and_then(|p| read_metadata(&p)) else {
+        return false;
+    };
+
+    filters.iter().all(|f| f.matches(&metadata))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn parse_typst_value(src: &str) -> Option<Value> {
+        TypstParser::new(src).parse_value()
+    }
+
+    #[test]
+    fn test_parse_typst_metadata() {
+        let content = r#"
+#set document(title: "Note")
+#metadata((status: "draft", course: "CS144", week: 3, done: false)) <info>
+= Heading
+#metadata((tags: ("net", "tcp"), due: datetime(year: 2025, month: 5, day: 17)))
+"#;
+        let fields = parse_typst_metadata(content);
+
+        assert_eq!(fields["status"], json!("draft"));
+        assert_eq!(fields["course"], json!("CS144"));
+        assert_eq!(fields["week"], json!(3));
+        assert_eq!(fields["done"], json!(false));
+        assert_eq!(fields["tags"], json!(["net", "tcp"]));
+        assert_eq!(fields["due"], json!("2025-05-17"));
+    }
+
+    #[test]
+    fn test_parse_typst_value() {
+        assert_eq!(parse_typst_value("(:)").unwrap(), json!({}));
+        assert_eq!(parse_typst_value("()").unwrap(), json!([]));
+        assert_eq!(parse_typst_value("(1,)").unwrap(), json!([1]));
+        assert_eq!(parse_typst_value("(1)").unwrap(), json!(1));
+        assert_eq!(parse_typst_value("1.5").unwrap(), json!(1.5));
+        assert_eq!(parse_typst_value("12pt").unwrap(), json!("12pt"));
+        assert_eq!(parse_typst_value("none").unwrap(), Value::Null);
+        assert_eq!(
+            parse_typst_value(r#"("a": (b: "c\"d"))"#).unwrap(),
+            json!({"a": {"b": "c\"d"}})
+        );
+        assert!(parse_typst_value("(a: ").is_none());
+    }
+
+    #[test]
+    fn test_field_filter() {
+        let metadata = parse_metadata(
+            r#"#set document(title: "Net")
+#metadata((status: "Draft", week: 3, done: true, tags: ("a", "b")))"#,
+            NoteType::Typ,
+        );
+
+        let matches = |s: &str| s.parse::<FieldFilter>().unwrap().matches(&metadata);
+        assert!(matches("status=draft"));
+        assert!(matches("week=3"));
+        assert!(matches("week=3.0"));
+        assert!(matches("done=true"));
+        assert!(matches("tags=b"));
+        assert!(matches("title=net"));
+        assert!(!matches("status=final"));
+        assert!(!matches("missing=x"));
+        assert!("novalue".parse::<FieldFilter>().is_err());
+    }
+
+    #[test]
+    fn test_parse_markdown_metadata() {
+        let content = "---\ntitle: \"MyNote\"\nauthor: \"Me\"\nkeywords: [kw1, kw2]\ndate: \"2025-05-17 12:00:00\"\nstatus: draft\n---\n\n# Body\n";
+        let metadata = parse_metadata(content, NoteType::Md);
+
+        assert_eq!(metadata.title.as_deref(), Some("MyNote"));
+        assert_eq!(metadata.author.as_deref(), Some("Me"));
+        assert_eq!(metadata.keywords, ["kw1", "kw2"]);
+        assert_eq!(metadata.date.as_deref(), Some("2025-05-17 12:00:00"));
+        assert_eq!(metadata.fields["status"], json!("draft"));
+
+        let (_, body) = split_frontmatter(content).unwrap();
+        assert_eq!(body, "\n# Body\n");
+        assert!(split_frontmatter("# No front matter").is_none());
+        assert_eq!(strip_metadata(content, NoteType::Md), "\n# Body\n");
+    }
+
+    #[test]
+    fn test_org_metadata() {
+        let content = "#+TITLE: My Note\n#+author: Me\n#+KEYWORDS: kw1, kw2\n#+STATUS: draft\n\n* Body\n#+BEGIN_SRC rust\n";
+        let metadata = parse_metadata(content, NoteType::Org);
+
+        assert_eq!(metadata.title.as_deref(), Some("My Note"));
+        assert_eq!(metadata.author.as_deref(), Some("Me"));
+        assert_eq!(metadata.keywords, ["kw1", "kw2"]);
+        assert_eq!(metadata.fields["status"], json!("draft"));
+        assert_eq!(
+            strip_metadata(content, NoteType::Org),
+            "\n* Body\n#+BEGIN_SRC rust\n"
+        );
+
+        let org = "#+TITLE: a\n#+STATUS: draft\n* A\n";
+        assert_eq!(
+            set_field(org, NoteType::Org, "status", &json!("done")).unwrap(),
+            "#+TITLE: a\n#+STATUS: done\n* A\n"
+        );
+        assert_eq!(
+            set_field(org, NoteType::Org, "keywords", &json!(["x", "y"])).unwrap(),
+            "#+TITLE: a\n#+STATUS: draft\n#+KEYWORDS: x, y\n* A\n"
+        );
+        assert_eq!(
+            remove_field(org, NoteType::Org, "title").unwrap(),
+            "#+STATUS: draft\n* A\n"
+        );
+        assert_eq!(
+            set_field("* A\n", NoteType::Org, "title", &json!("t")).unwrap(),
+            "#+TITLE: t\n\n* A\n"
+        );
+    }
+
+    #[test]
+    fn test_strip_typst_metadata() {
+        let content = "#set document(title: \"a (b)\",\n  author: \"me\")\n#metadata((status: \"draft\")) <info>\n= Heading\nText #metadata(1)here\n";
+        assert_eq!(
+            strip_metadata(content, NoteType::Typ),
+            "\n\n= Heading\nText here\n"
+        );
+        assert_eq!(
+            blank_typst_metadata(content),
+            "\n\n\n= Heading\nText here\n"
+        );
+
+        // Prose mentioning the calls is kept
+        let content = "Write #metadata) like this.\n";
+        assert_eq!(blank_typst_metadata(content), content);
+        assert_eq!(strip_typst_metadata(content), content);
+        let content = "Use #set document to (set) the title.\n";
+        assert_eq!(strip_typst_metadata(content), content);
+        assert_eq!(
+            strip_typst_metadata("#metadata (1) <a>\nText\n"),
+            "\nText\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_typst_document_metadata() {
+        // The format written by `noxe new`
+        let content = "#set document(title: \"TestNote\", author: \"AuthorName\", keywords: (kw1, kw2), date: datetime(year: 2025, month: 5, day: 17, hour: 9, minute: 3, second: 0))\n\n";
+        let metadata = parse_metadata(content, NoteType::Typ);
+
+        assert_eq!(metadata.title.as_deref(), Some("TestNote"));
+        assert_eq!(metadata.author.as_deref(), Some("AuthorName"));
+        assert_eq!(metadata.keywords, ["kw1", "kw2"]);
+        assert_eq!(metadata.date.as_deref(), Some("2025-05-17 09:03:00"));
+    }
+
+    #[test]
+    fn test_edit_fields() {
+        let md = "---\ntitle: \"a\"\ntags:\n  - x\n  - y\nstatus: draft\n---\nBody\n";
+        assert_eq!(
+            set_field(md, NoteType::Md, "tags", &json!(["z"])).unwrap(),
+            "---\ntitle: \"a\"\ntags: [z]\nstatus: draft\n---\nBody\n"
+        );
+        assert_eq!(
+            set_field(md, NoteType::Md, "week", &json!(3)).unwrap(),
+            "---\ntitle: \"a\"\ntags:\n  - x\n  - y\nstatus: draft\nweek: 3\n---\nBody\n"
+        );
+        assert_eq!(
+            remove_field(md, NoteType::Md, "title").unwrap(),
+            "---\ntags:\n  - x\n  - y\nstatus: draft\n---\nBody\n"
+        );
+        assert_eq!(
+            set_field("Body\n", NoteType::Md, "title", &json!("t")).unwrap(),
+            "---\ntitle: \"t\"\n---\n\nBody\n"
+        );
+
+        let typ =
+            "#set document(title: \"a\")\n#metadata((status: \"draft\", week: 1)) <info>\n= A\n";
+        assert_eq!(
+            set_field(typ, NoteType::Typ, "author", &json!("me")).unwrap(),
+            "#set document(title: \"a\", author: \"me\")\n#metadata((status: \"draft\", week: 1)) <info>\n= A\n"
+        );
+        assert_eq!(
+            set_field(typ, NoteType::Typ, "status", &json!("done")).unwrap(),
+            "#set document(title: \"a\")\n#metadata((status: \"done\", week: 1)) <info>\n= A\n"
+        );
+        assert_eq!(
+            remove_field(typ, NoteType::Typ, "status").unwrap(),
+            "#set document(title: \"a\")\n#metadata((week: 1)) <info>\n= A\n"
+        );
+        assert_eq!(
+            set_field(
+                "#set document(title: \"a\")\n= A\n",
+                NoteType::Typ,
+                "status",
+                &json!("x")
+            )
+            .unwrap(),
+            "#set document(title: \"a\")\n#metadata((status: \"x\"))\n= A\n"
+        );
+    }
+
+    #[test]
+    fn test_readonly() {
+        let md = "---\ntitle: \"a\"\nreadonly: [title, keywords]\n---\n";
+        let metadata = parse_metadata(md, NoteType::Md);
+        assert!(!metadata.is_readonly());
+        assert!(metadata.is_locked("title"));
+        assert!(metadata.is_locked("readonly"));
+        assert!(!metadata.is_locked("summary"));
+        assert!(ensure_writable(md, NoteType::Md, "keywords", false).is_err());
+        assert!(ensure_writable(md, NoteType::Md, "keywords", true).is_ok());
+        assert!(ensure_writable(md, NoteType::Md, "status", false).is_ok());
+
+        let typ = "#metadata((readonly: true)) <info>\n= A\n";
+        assert!(parse_metadata(typ, NoteType::Typ).is_locked("status"));
+        let org = "#+READONLY: true\n* A\n";
+        assert!(parse_metadata(org, NoteType::Org).is_readonly());
+        let org = "#+READONLY: summary, date\n* A\n";
+        let metadata = parse_metadata(org, NoteType::Org);
+        assert!(metadata.is_locked("date") && !metadata.is_locked("title"));
+    }
+}
@@ -0,0 +1,203 @@
+use anyhow::{Context, Result, bail};
+use serde_json::{Map, Value, json};
+use std::{fs, path::PathBuf};
+
+use crate::enrich::CHECKPOINT;
+use crate::semantic::EMBEDDINGS;
+use crate::state::{LAYOUT, LAYOUT_VERSION, Layout, StateStore};
+
+/// The directory of `.noxe` where the state is backed up before migrating.
+const BACKUPS: &str = "backups";
+
+/// A step upgrading the state from layout version `from` to `from + 1`. It returns a line per
+/// change, and only writes when `apply` is set. Steps are idempotent.
+struct Migration {
+    from: u32,
+    description: &'static str,
+    run: fn(&StateStore, bool) -> Result<Vec<String>>,
+}
+
+const MIGRATIONS: &[Migration] = &[Migration {
+    from: 0,
+    description: "Split embeddings into chunks",
+    run: chunk_embeddings,
+}];
+
+/// What a migration did, or would do in a dry run.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub from: u32,
+    pub to: u32,
+    pub backup: Option<PathBuf>,
+    /// The description of each step run, with its changes
+    pub steps: Vec<(&'static str, Vec<String>)>,
+}
+
+/// Upgrade the state of the vault to the current layout, backing it up first. Running it on
+/// an up-to-date vault does nothing.
+pub fn migrate(store: &StateStore, dry_run: bool) -> Result<Report> {
+    if !store.dir().is_dir() {
+        // Nothing to migrate, the state will be created with the current layout
+        return Ok(Report {
+            from: LAYOUT_VERSION,
+            to: LAYOUT_VERSION,
+            ..Default::default()
+        });
+    }
+
+    let from = store.load::<Layout>(LAYOUT)?.version;
+    if from > LAYOUT_VERSION {
+        bail!(
+            "The vault state has layout version {}, but this noxe only knows up to {}. Upgrade noxe",
+            from,
+            LAYOUT_VERSION
+        );
+    }
+
+    let mut report = Report {
+        from,
+        to: LAYOUT_VERSION,
+        ..Default::default()
+    };
+    if from == LAYOUT_VERSION {
+        return Ok(report);
+    }
+
+    if !dry_run {
+        report.backup = Some(backup(store, from)?);
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| m.from >= from) {
+        let changes = (migration.run)(store, !dry_run)
+            .with_context(|| format!("Failed to migrate: {}", migration.description))?;
+        report.steps.push((migration.description, changes));
+
+        if !dry_run {
+            // Stamped after each step, so that an interrupted migration resumes where it stopped
+            store.save(
+                LAYOUT,
+                &Layout {
+                    version: migration.from + 1,
+                },
+            )?;
+        }
+    }
+
+    Ok(report)
+}
+
+/// Copy the state files to `.noxe/backups/v<version>-<time>`.
+fn backup(store: &StateStore, version: u32) -> Result<PathBuf> {
+    let dir = store.dir().join(BACKUPS).join(format!(
+        "v{}-{}",
+        version,
+        chrono::Local::now().format("%Y%m%d%H%M%S")
+    ));
+    fs::create_dir_all(&dir)
+        .with_context(|| format!("Failed to create directory '{}'", dir.display()))?;
+
+    for entry in fs::read_dir(store.dir())? {
+        let path = entry?.path();
+        if path.is_file() {
+            fs::copy(&path, dir.join(path.file_name().unwrap()))
+                .with_context(|| format!("Failed to back up '{}'", path.display()))?;
+        }
+    }
+
+    Ok(dir)
+}
+
+/// Embeddings used to be a single `vector` per note. It becomes the only chunk, and the
+/// embedding is dated from the enrichment checkpoint so that unchanged notes are not embedded
+/// again.
+fn chunk_embeddings(store: &StateStore, apply: bool) -> Result<Vec<String>> {
+    let mut embeddings: Map<String, Value> = store.load(EMBEDDINGS)?;
+    let checkpoint: Value = store.load(CHECKPOINT)?;
+
+    let mut changes = Vec::new();
+    for (key, embedding) in embeddings.iter_mut() {
+        let Some(embedding) = embedding.as_object_mut() else {
+            continue;
+        };
+        let Some(vector) = embedding.remove("vector") else {
+            continue;
+        };
+        embedding.insert("chunks".to_string(), json!([vector]));
+
+        let entry = &checkpoint["notes"][key];
+        let embedded = entry["tasks"]
+            .as_array()
+            .is_some_and(|tasks| tasks.contains(&json!("embedding")));
+        if embedded && !embedding.contains_key("modified") {
+            embedding.insert("modified".to_string(), entry["modified"].clone());
+        }
+
+        changes.push(format!("Chunked the embedding of '{}'", key));
+    }
+
+    if apply && !changes.is_empty() {
+        store.save(EMBEDDINGS, &embeddings)?;
+    }
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::semantic::Embeddings;
+    use crate::state::STATE_DIR;
+    use std::time::{Duration, SystemTime};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_migrate() {
+        let tmp_dir = tempdir().unwrap();
+        let store = StateStore::new(tmp_dir.path());
+        assert_eq!(migrate(&store, false).unwrap().from, LAYOUT_VERSION);
+        assert!(!store.dir().exists());
+
+        // A vault enriched before embeddings were chunked
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1);
+        fs::create_dir_all(tmp_dir.path().join(STATE_DIR)).unwrap();
+        fs::write(
+            store.dir().join("embeddings.json"),
+            r#"{"a.md": {"model": "m", "vector": [1.0, 0.0]}}"#,
+        )
+        .unwrap();
+        fs::write(
+            store.dir().join("enrich.json"),
+            serde_json::to_string(&json!({
+                "notes": {"a.md": {"modified": time, "tasks": ["embedding"]}}
+            }))
+            .unwrap(),
+        )
+        .unwrap();
+
+        let report = migrate(&store, true).unwrap();
+        assert_eq!((report.from, report.to), (0, LAYOUT_VERSION));
+        assert_eq!(report.steps[0].1, ["Chunked the embedding of 'a.md'"]);
+        assert!(!store.exists(LAYOUT));
+
+        let report = migrate(&store, false).unwrap();
+        let backup = report.backup.unwrap();
+        assert!(backup.join("embeddings.json").is_file());
+        let embeddings: Embeddings = store.load(EMBEDDINGS).unwrap();
+        assert_eq!(embeddings["a.md"].chunks, [vec![1.0, 0.0]]);
+        assert!(embeddings["a.md"].is_fresh("m", Some(time)));
+
+        // Already up to date
+        let report = migrate(&store, false).unwrap();
+        assert!(report.steps.is_empty());
+        assert!(report.backup.is_none());
+
+        store
+            .save(
+                LAYOUT,
+                &Layout {
+                    version: LAYOUT_VERSION + 1,
+                },
+            )
+            .unwrap();
+        assert!(migrate(&store, false).is_err());
+    }
+}
@@ -0,0 +1,228 @@
+use anyhow::{Context, Result, bail};
+use std::{
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+};
+
+use crate::cli::NoteType;
+use crate::export::output_path;
+use crate::state::STATE_DIR;
+use crate::vault::Note;
+
+/// The file to open for a note: its most recent compiled PDF if there is one, else its
+/// main file.
+pub fn open_target(note_root: &Path, note: &Path, source: bool) -> Result<PathBuf> {
+    let main_file = note.main_file_path()?;
+    if source {
+        return Ok(main_file);
+    }
+    Ok(compiled_pdf(note_root, note, &main_file).unwrap_or(main_file))
+}
+
+/// The newest of the PDFs compiled from a note: next to its main file (as `typst watch`
+/// writes it), exported by `noxe export` or published by `noxe publish`.
+fn compiled_pdf(note_root: &Path, note: &Path, main_file: &Path) -> Option<PathBuf> {
+    let mut candidates = vec![
+        main_file.with_extension("pdf"),
+        output_path(note_root, note, &note_root.join("export"), "pdf"),
+    ];
+
+    let stem = note.file_stem()?.to_string_lossy().to_string();
+    if let Ok(entries) = fs::read_dir(note_root.join("publish")) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            // `<name>-YYYY-MM-DD.pdf`
+            if path.extension().is_some_and(|ext| ext == "pdf")
+                && let Some(name) = path.file_stem().and_then(|s| s.to_str())
+                && let Some(date) = name.strip_prefix(&stem).and_then(|s| s.strip_prefix('-'))
+                && chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").is_ok()
+            {
+                candidates.push(path);
+            }
+        }
+    }
+
+    candidates
+        .into_iter()
+        .filter_map(|path| {
+            let modified = fs::metadata(&path).ok()?.modified().ok()?;
+            Some((modified, path))
+        })
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, path)| path)
+}
+
+/// Where `noxe preview --pdf` compiles a note: under `.noxe/preview` for notes of the vault,
+/// mirroring their category, else in the temporary directory.
+pub fn preview_pdf_path(note_root: &Path, note: &Path) -> PathBuf {
+    match note.strip_prefix(note_root) {
+        Ok(rel_path) if rel_path.is_relative() && !rel_path.as_os_str().is_empty() => note_root
+            .join(STATE_DIR)
+            .join("preview")
+            .join(rel_path)
+            .with_extension("pdf"),
+        _ => std::env::temp_dir()
+            .join("noxe-preview")
+            .join(Path::new(note.file_name().unwrap_or_default()).with_extension("pdf")),
+    }
+}
+
+/// Compile a typst note to a PDF and open it with the viewer, else the default application.
+/// With `watch`, the PDF is compiled again whenever the note changes, until interrupted.
+pub fn preview_pdf(note_root: &Path, note: &Path, viewer: &[OsString], watch: bool) -> Result<()> {
+    let main_file = note.main_file_path()?;
+    if main_file.note_type()? != NoteType::Typ {
+        bail!("--pdf only compiles typst notes");
+    }
+    let pdf = preview_pdf_path(note_root, note);
+    if let Some(dir) = pdf.parent() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create directory '{}'", dir.display()))?;
+    }
+
+    let typst = |subcommand: &str| -> Vec<OsString> {
+        vec![
+            "typst".into(),
+            subcommand.into(),
+            "--root".into(),
+            note_root.into(),
+            main_file.as_os_str().into(),
+            pdf.as_os_str().into(),
+        ]
+    };
+    run(&typst("compile"), true)?;
+
+    if viewer.is_empty() {
+        open(&pdf, false)?;
+    } else {
+        let mut args = viewer.to_vec();
+        args.push(pdf.as_os_str().into());
+        // The viewer keeps running while the note is watched
+        run(&args, !watch)?;
+    }
+
+    if watch {
+        run(&typst("watch"), true)?;
+    }
+    Ok(())
+}
+
+/// The command opening a path with the default application of the OS.
+fn opener() -> Vec<OsString> {
+    if cfg!(target_os = "macos") {
+        vec!["open".into()]
+    } else if cfg!(windows) {
+        vec!["cmd".into(), "/C".into(), "start".into(), "".into()]
+    } else {
+        vec!["xdg-open".into()]
+    }
+}
+
+/// The command showing a path in the file manager, selecting it where the OS supports it.
+fn revealer(path: &Path) -> Vec<OsString> {
+    if cfg!(target_os = "macos") {
+        vec!["open".into(), "-R".into(), path.into()]
+    } else if cfg!(windows) {
+        let mut select = OsString::from("/select,");
+        select.push(path);
+        vec!["explorer".into(), select]
+    } else {
+        let dir = path.parent().filter(|p| !p.as_os_str().is_empty());
+        vec!["xdg-open".into(), dir.unwrap_or(Path::new(".")).into()]
+    }
+}
+
+/// Open a path with the default application, or show it in the file manager.
+pub fn open(path: &Path, reveal: bool) -> Result<()> {
+    let args = if reveal {
+        revealer(path)
+    } else {
+        let mut args = opener();
+        args.push(path.into());
+        args
+    };
+
+    run(&args, true)
+}
+
+/// Run a command, waiting for it to succeed unless `wait` is false.
+fn run(args: &[OsString], wait: bool) -> Result<()> {
+    let program = args[0].to_string_lossy().to_string();
+    let mut cmd = process::Command::new(&args[0]);
+    cmd.args(&args[1..]);
+    let result = if wait {
+        cmd.status()
+    } else {
+        cmd.spawn().map(|_| process::ExitStatus::default())
+    };
+    let status = match result {
+        Ok(status) => status,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            bail!("'{}' is not installed or not in PATH", program)
+        }
+        Err(e) => return Err(e).with_context(|| format!("Failed to run '{}'", program)),
+    };
+    // explorer exits with 1 even when it succeeds
+    if !status.success() && !cfg!(windows) {
+        bail!("'{}' exited with {}", program, status);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{thread, time::Duration};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_open_target() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        let note = root.join("cat").join("paper");
+        fs::create_dir_all(&note).unwrap();
+        fs::write(note.join("main.typ"), "").unwrap();
+
+        assert_eq!(
+            open_target(root, &note, false).unwrap(),
+            note.join("main.typ")
+        );
+
+        fs::write(note.join("main.pdf"), "").unwrap();
+        assert_eq!(
+            open_target(root, &note, false).unwrap(),
+            note.join("main.pdf")
+        );
+        assert_eq!(
+            open_target(root, &note, true).unwrap(),
+            note.join("main.typ")
+        );
+
+        thread::sleep(Duration::from_millis(20));
+        fs::create_dir_all(root.join("publish")).unwrap();
+        let published = root.join("publish").join("paper-2025-01-02.pdf");
+        fs::write(&published, "").unwrap();
+        fs::write(root.join("publish").join("paper-notes-2025-01-03.pdf"), "").unwrap();
+        assert_eq!(open_target(root, &note, false).unwrap(), published);
+    }
+
+    #[test]
+    fn test_preview_pdf_path() {
+        let root = Path::new("/notes");
+        assert_eq!(
+            preview_pdf_path(root, &root.join("cat").join("paper")),
+            root.join(".noxe/preview/cat/paper.pdf")
+        );
+        assert_eq!(
+            preview_pdf_path(root, &root.join("net.typ")),
+            root.join(".noxe/preview/net.pdf")
+        );
+        assert_eq!(
+            preview_pdf_path(root, Path::new("/elsewhere/net.typ")),
+            std::env::temp_dir().join("noxe-preview").join("net.pdf")
+        );
+    }
+}
@@ -0,0 +1,290 @@
+use anyhow::{Context, Result, bail};
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+use crate::atomic::{self, Transaction};
+use crate::links::{LinkIndex, Rewrite};
+use crate::rename;
+
+/// A file operation on a note.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operation {
+    /// Move a note, rewriting the links to it
+    Move {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    Copy {
+        from: PathBuf,
+        to: PathBuf,
+    },
+}
+
+impl Operation {
+    pub fn moving(note: &Path, dir: &Path) -> Self {
+        Operation::Move {
+            from: note.to_path_buf(),
+            to: dir.join(note.file_name().unwrap_or_default()),
+        }
+    }
+
+    pub fn copying(note: &Path, dir: &Path) -> Self {
+        Operation::Copy {
+            from: note.to_path_buf(),
+            to: dir.join(note.file_name().unwrap_or_default()),
+        }
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operation::Move { from, to } => {
+                write!(f, "move {} -> {}", from.display(), to.display())
+            }
+            Operation::Copy { from, to } => {
+                write!(f, "copy {} -> {}", from.display(), to.display())
+            }
+        }
+    }
+}
+
+/// An operation that has been run, with what is needed to revert it.
+#[derive(Debug)]
+struct Applied {
+    operation: Operation,
+    rewrites: Vec<Rewrite>,
+}
+
+/// Operations waiting to be run, and those run so far, which can be undone in reverse order.
+#[derive(Debug)]
+pub struct OperationQueue {
+    note_root: PathBuf,
+    pending: Vec<Operation>,
+    done: Vec<Applied>,
+}
+
+impl OperationQueue {
+    pub fn new(note_root: &Path) -> Self {
+        OperationQueue {
+            note_root: note_root.to_path_buf(),
+            pending: Vec::new(),
+            done: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, operation: Operation) {
+        self.pending.push(operation);
+    }
+
+    /// Drop the last pending operation.
+    pub fn pop(&mut self) -> Option<Operation> {
+        self.pending.pop()
+    }
+
+    pub fn pending(&self) -> &[Operation] {
+        &self.pending
+    }
+
+    /// Run the pending operations in order. On failure, the failed operation and the following
+    /// ones stay pending. Returns the number of operations run.
+    pub fn run(&mut self) -> Result<usize> {
+        let mut count = 0;
+        while !self.pending.is_empty() {
+            let operation = self.pending[0].clone();
+            let rewrites = apply(&self.note_root, &operation)
+                .with_context(|| format!("Failed to {}", operation))?;
+
+            self.pending.remove(0);
+            self.done.push(Applied {
+                operation,
+                rewrites,
+            });
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Revert the last operation run. Link rewrites are only reverted in notes that have not
+    /// been edited since.
+    pub fn undo(&mut self) -> Result<Option<Operation>> {
+        let Some(applied) = self.done.pop() else {
+            return Ok(None);
+        };
+
+        let result = match &applied.operation {
+            Operation::Move { from, to } => revert_move(from, to, &applied.rewrites),
+            Operation::Copy { to, .. } => remove(to),
+        };
+        if let Err(e) = result {
+            let context = format!("Failed to undo {}", applied.operation);
+            self.done.push(applied);
+            return Err(e).context(context);
+        }
+
+        Ok(Some(applied.operation))
+    }
+}
+
+/// Run an operation, returning the link rewrites it made.
+fn apply(note_root: &Path, operation: &Operation) -> Result<Vec<Rewrite>> {
+    match operation {
+        Operation::Move { from, to } => {
+            check_destination(from, to)?;
+            let rewrites = LinkIndex::new(note_root)?.plan_move(from, to)?;
+            rename::ensure_rewritable(note_root, &rewrites)?;
+
+            fs::rename(from, to)?;
+            let mut transaction = Transaction::new();
+            for rewrite in &rewrites {
+                transaction.write(&rewrite.file, rewrite.new.as_str());
+            }
+            if let Err(e) = transaction.commit(note_root) {
+                // Not to leave the note moved with only some of its links rewritten
+                let e = e.context("Failed to rewrite the links");
+                return Err(match revert_move(from, to, &rewrites) {
+                    Ok(()) => e.context("The move was reverted"),
+                    Err(revert) => e.context(format!("Failed to revert the move: {:#}", revert)),
+                });
+            }
+            Ok(rewrites)
+        }
+        Operation::Copy { from, to } => {
+            check_destination(from, to)?;
+            copy(from, to)?;
+            Ok(Vec::new())
+        }
+    }
+}
+
+fn revert_move(from: &Path, to: &Path, rewrites: &[Rewrite]) -> Result<()> {
+    if from.exists() {
+        bail!("'{}' already exists", from.display());
+    }
+    fs::rename(to, from)?;
+
+    for rewrite in rewrites {
+        if fs::read_to_string(&rewrite.file).is_ok_and(|c| c == rewrite.new) {
+            atomic::write(&rewrite.file, &rewrite.old).with_context(|| {
+                format!("Failed to restore links in '{}'", rewrite.file.display())
+            })?;
+        }
+    }
+    Ok(())
+}
+
+fn check_destination(from: &Path, to: &Path) -> Result<()> {
+    if !from.exists() {
+        bail!("'{}' does not exist", from.display());
+    }
+    if to.exists() {
+        bail!("'{}' already exists", to.display());
+    }
+    if to.starts_with(from) {
+        bail!("Cannot put '{}' inside itself", from.display());
+    }
+    Ok(())
+}
+
+/// Copy a filenote, or a dirnote with all its files.
+fn copy(from: &Path, to: &Path) -> Result<()> {
+    if from.is_file() {
+        fs::copy(from, to)?;
+        return Ok(());
+    }
+
+    for entry in WalkDir::new(from) {
+        let entry = entry?;
+        let target = to.join(entry.path().strip_prefix(from)?);
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+fn remove(path: &Path) -> Result<()> {
+    if path.is_dir() {
+        fs::remove_dir_all(path)?;
+    } else {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_operation_queue() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        let (cat, other) = (root.join("cat"), root.join("other"));
+        fs::create_dir_all(cat.join("dir")).unwrap();
+        fs::create_dir_all(&other).unwrap();
+        fs::write(cat.join("dir/main.typ"), "= Dir\n").unwrap();
+        fs::write(cat.join("a.md"), "# A\n").unwrap();
+        fs::write(root.join("b.md"), "[[cat/a]]\n").unwrap();
+
+        let mut queue = OperationQueue::new(root);
+        queue.push(Operation::moving(&cat.join("a.md"), &other));
+        queue.push(Operation::copying(&cat.join("dir"), &other));
+        queue.push(Operation::copying(&cat.join("missing.md"), &other));
+        assert!(queue.run().is_err());
+        assert_eq!(queue.pending().len(), 1);
+        assert_eq!(
+            queue.pop(),
+            Some(Operation::copying(&cat.join("missing.md"), &other))
+        );
+
+        assert!(other.join("a.md").is_file());
+        assert_eq!(
+            fs::read_to_string(root.join("b.md")).unwrap(),
+            "[[other/a]]\n"
+        );
+        assert!(other.join("dir/main.typ").is_file());
+        assert!(cat.join("dir/main.typ").is_file());
+
+        // Undone in reverse order
+        assert!(matches!(
+            queue.undo().unwrap(),
+            Some(Operation::Copy { .. })
+        ));
+        assert!(!other.join("dir").exists());
+        assert!(matches!(
+            queue.undo().unwrap(),
+            Some(Operation::Move { .. })
+        ));
+        assert!(cat.join("a.md").is_file());
+        assert_eq!(
+            fs::read_to_string(root.join("b.md")).unwrap(),
+            "[[cat/a]]\n"
+        );
+        assert_eq!(queue.undo().unwrap(), None);
+
+        queue.push(Operation::moving(&cat, &cat.join("dir")));
+        assert!(queue.run().is_err());
+        queue.pop();
+
+        // A rewrite failing, here as the name of its temporary file is too long: the move and
+        // the rewrites already written are reverted
+        let long = root.join(format!("{}.md", "l".repeat(245)));
+        fs::write(&long, "[[cat/a]]\n").unwrap();
+        queue.push(Operation::moving(&cat.join("a.md"), &other));
+        assert!(queue.run().is_err());
+        assert!(cat.join("a.md").is_file());
+        assert!(!other.join("a.md").exists());
+        assert_eq!(
+            fs::read_to_string(root.join("b.md")).unwrap(),
+            "[[cat/a]]\n"
+        );
+        assert_eq!(queue.pending().len(), 1);
+    }
+}
@@ -0,0 +1,319 @@
+use anyhow::{Context, Result, bail};
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::Read,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::links::wiki_links;
+use crate::metadata::{NoteMetadata, read_metadata};
+use crate::vault::Note;
+
+/// Version of the `.noxepack` layout, bumped on incompatible changes.
+const PACK_VERSION: u32 = 1;
+const MANIFEST_NAME: &str = "manifest.json";
+const NOTES_DIR: &str = "notes";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    pub created: String,
+    pub notes: Vec<PackedNote>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PackedNote {
+    /// Path of the note relative to the note root
+    pub path: PathBuf,
+    pub dirnote: bool,
+    pub metadata: NoteMetadata,
+    /// Targets of the `[[...]]` links found in the main file
+    pub links: Vec<String>,
+    /// Files of a dirnote other than its main file, relative to the note
+    pub assets: Vec<PathBuf>,
+}
+
+/// Pack the notes into a single gzip-compressed tar archive with a manifest.
+pub fn pack(note_root: &Path, notes: &[PathBuf], output: &Path) -> Result<Manifest> {
+    let mut manifest = Manifest {
+        version: PACK_VERSION,
+        created: chrono::Local::now().to_rfc3339(),
+        notes: Vec::new(),
+    };
+    // (path in the archive, path on disk)
+    let mut files = Vec::new();
+
+    for note in notes {
+        let rel_path = note
+            .strip_prefix(note_root)
+            .ok()
+            .filter(|p| !p.as_os_str().is_empty())
+            .map(Path::to_path_buf)
+            .or_else(|| note.file_name().map(PathBuf::from))
+            .with_context(|| format!("Invalid note path '{}'", note.display()))?;
+
+        let main_file = note.main_file_path()?;
+        let content = fs::read_to_string(&main_file)
+            .with_context(|| format!("Failed to read note '{}'", main_file.display()))?;
+
+        let mut assets = Vec::new();
+        if note.is_dirnote() {
+            for entry in walkdir::WalkDir::new(note).sort_by_file_name() {
+                let entry = entry?;
+                if entry.depth() == 0 {
+                    continue;
+                }
+                // Directories are packed too so that empty ones (e.g. `images/`) survive.
+                let asset = entry.path().strip_prefix(note)?.to_path_buf();
+                files.push((rel_path.join(&asset), entry.path().to_path_buf()));
+                if entry.file_type().is_file() && entry.path() != main_file {
+                    assets.push(asset);
+                }
+            }
+        } else {
+            files.push((rel_path.clone(), note.clone()));
+        }
+
+        manifest.notes.push(PackedNote {
+            path: rel_path,
+            dirnote: note.is_dirnote(),
+            metadata: read_metadata(&main_file)?,
+            links: wiki_links(&content),
+            assets,
+        });
+    }
+
+    let file = fs::File::create(output)
+        .with_context(|| format!("Failed to create pack '{}'", output.display()))?;
+    let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+
+    // The manifest goes first so that `unpack` can check for conflicts before writing.
+    let manifest_data = serde_json::to_vec_pretty(&manifest)?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(manifest_data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(chrono::Local::now().timestamp() as u64);
+    header.set_cksum();
+    builder.append_data(&mut header, MANIFEST_NAME, manifest_data.as_slice())?;
+
+    for (name, path) in files {
+        builder
+            .append_path_with_name(&path, Path::new(NOTES_DIR).join(name))
+            .with_context(|| format!("Failed to pack '{}'", path.display()))?;
+    }
+
+    builder.into_inner()?.finish()?;
+
+    Ok(manifest)
+}
+
+/// Unpack a `.noxepack` into the note root. Nothing is written if any packed file already
+/// exists, or if the pack holds anything but the files and directories of its notes.
+pub fn unpack(pack: &Path, note_root: &Path) -> Result<Manifest> {
+    let mut archive = open_pack(pack)?;
+    let mut entries = archive.entries()?;
+    let manifest: Manifest = match entries.next() {
+        Some(entry) => {
+            let mut entry = entry?;
+            if entry.path()? != Path::new(MANIFEST_NAME) {
+                bail!("'{}' is not a noxe pack", pack.display());
+            }
+            let mut data = String::new();
+            entry.read_to_string(&mut data)?;
+            serde_json::from_str(&data)
+                .with_context(|| format!("Failed to parse manifest of '{}'", pack.display()))?
+        }
+        None => bail!("'{}' is empty", pack.display()),
+    };
+
+    if manifest.version > PACK_VERSION {
+        bail!(
+            "'{}' was created by a newer noxe (pack version {})",
+            pack.display(),
+            manifest.version
+        );
+    }
+
+    for note in &manifest.notes {
+        check_relative(&note.path)?;
+        let target = note_root.join(&note.path);
+        if target.exists() {
+            bail!("Note '{}' already exists", target.display());
+        }
+    }
+
+    // Check every entry before writing anything
+    for entry in entries {
+        let entry = entry?;
+        let rel_path = entry_path(&entry)?;
+        let entry_type = entry.header().entry_type();
+        // Links could point out of the note root
+        if !entry_type.is_file() && !entry_type.is_dir() {
+            bail!("Unsupported entry '{}' in pack", rel_path.display());
+        }
+        if !manifest
+            .notes
+            .iter()
+            .any(|note| rel_path.starts_with(&note.path))
+        {
+            bail!(
+                "Entry '{}' in pack belongs to no note of its manifest",
+                rel_path.display()
+            );
+        }
+        let target = note_root.join(&rel_path);
+        if target.exists() {
+            bail!("'{}' already exists", target.display());
+        }
+    }
+
+    let mut archive = open_pack(pack)?;
+    for entry in archive.entries()?.skip(1) {
+        let mut entry = entry?;
+        let target = note_root.join(entry_path(&entry)?);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory '{}'", parent.display()))?;
+        }
+        entry
+            .unpack(&target)
+            .with_context(|| format!("Failed to unpack '{}'", target.display()))?;
+    }
+
+    Ok(manifest)
+}
+
+fn open_pack(pack: &Path) -> Result<tar::Archive<GzDecoder<fs::File>>> {
+    let file =
+        fs::File::open(pack).with_context(|| format!("Failed to open '{}'", pack.display()))?;
+    Ok(tar::Archive::new(GzDecoder::new(file)))
+}
+
+/// The path of a note entry, relative to the note root.
+fn entry_path(entry: &tar::Entry<impl Read>) -> Result<PathBuf> {
+    let path = entry.path()?;
+    let rel_path = path
+        .strip_prefix(NOTES_DIR)
+        .with_context(|| format!("Unexpected entry '{}' in pack", path.display()))?;
+    check_relative(rel_path)?;
+    Ok(rel_path.to_path_buf())
+}
+
+/// Refuse paths that could escape the note root.
+fn check_relative(path: &Path) -> Result<()> {
+    if path.as_os_str().is_empty() || !path.components().all(|c| matches!(c, Component::Normal(_)))
+    {
+        bail!("Invalid path '{}' in pack", path.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_pack_unpack_roundtrip() {
+        let src = tempdir().unwrap();
+        let dirnote = src.path().join("cat").join("dirnote");
+        fs::create_dir_all(dirnote.join("images")).unwrap();
+        fs::create_dir_all(dirnote.join("chapter")).unwrap();
+        fs::write(
+            dirnote.join("main.md"),
+            "---\ntitle: \"Dir\"\n---\nsee [[file]] and [[Other|alias]]\n",
+        )
+        .unwrap();
+        fs::write(dirnote.join("images").join("a.png"), [0u8, 1, 2]).unwrap();
+        let filenote = src.path().join("file.typ");
+        fs::write(&filenote, "#set document(title: \"File\")\n").unwrap();
+
+        let pack_path = src.path().join("out.noxepack");
+        let manifest = pack(src.path(), &[dirnote.clone(), filenote.clone()], &pack_path).unwrap();
+        assert_eq!(manifest.notes.len(), 2);
+        assert_eq!(manifest.notes[0].links, ["Other", "file"]);
+        assert_eq!(manifest.notes[0].assets, [PathBuf::from("images/a.png")]);
+
+        let dst = tempdir().unwrap();
+        let manifest = unpack(&pack_path, dst.path()).unwrap();
+        assert_eq!(manifest.notes[0].metadata.title.as_deref(), Some("Dir"));
+        assert!(dst.path().join("cat/dirnote/chapter").is_dir());
+        assert_eq!(
+            fs::read(dst.path().join("cat/dirnote/images/a.png")).unwrap(),
+            [0u8, 1, 2]
+        );
+        assert_eq!(
+            fs::read_to_string(dst.path().join("file.typ")).unwrap(),
+            "#set document(title: \"File\")\n"
+        );
+
+        // Unpacking again must not overwrite the existing notes
+        assert!(unpack(&pack_path, dst.path()).is_err());
+    }
+
+    #[test]
+    fn test_unpack_rejects_unsafe_entries() {
+        let src = tempdir().unwrap();
+        let manifest = |paths: &[&str]| Manifest {
+            version: PACK_VERSION,
+            created: String::new(),
+            notes: paths
+                .iter()
+                .map(|p| PackedNote {
+                    path: PathBuf::from(p),
+                    dirnote: true,
+                    metadata: NoteMetadata::default(),
+                    links: Vec::new(),
+                    assets: Vec::new(),
+                })
+                .collect(),
+        };
+        let write_pack = |name: &str, manifest: &Manifest, add: &dyn Fn(&mut tar::Builder<_>)| {
+            let path = src.path().join(name);
+            let file = fs::File::create(&path).unwrap();
+            let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+            let data = serde_json::to_vec(manifest).unwrap();
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, MANIFEST_NAME, data.as_slice())
+                .unwrap();
+            add(&mut builder);
+            builder.into_inner().unwrap().finish().unwrap();
+            path
+        };
+        let file = |builder: &mut tar::Builder<_>, name: &str| {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(1);
+            header.set_cksum();
+            builder.append_data(&mut header, name, &b"x"[..]).unwrap();
+        };
+
+        // A link to `/`, then a file through it
+        let link_pack = write_pack("link.noxepack", &manifest(&["x"]), &|builder| {
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(tar::EntryType::Symlink);
+            header.set_size(0);
+            builder.append_link(&mut header, "notes/x", "/").unwrap();
+        });
+        let dst = tempdir().unwrap();
+        assert!(unpack(&link_pack, dst.path()).is_err());
+        assert!(fs::symlink_metadata(dst.path().join("x")).is_err());
+
+        // A file of no note of the manifest, which exists in the vault
+        fs::write(dst.path().join("todo.md"), "mine").unwrap();
+        let extra_pack = write_pack("extra.noxepack", &manifest(&["a"]), &|builder| {
+            file(builder, "notes/a/main.md");
+            file(builder, "notes/todo.md");
+        });
+        assert!(unpack(&extra_pack, dst.path()).is_err());
+        assert!(!dst.path().join("a").exists());
+        assert_eq!(
+            fs::read_to_string(dst.path().join("todo.md")).unwrap(),
+            "mine"
+        );
+    }
+}
@@ -0,0 +1,416 @@
+use anyhow::{Result, bail};
+use clap::CommandFactory;
+use ratatui::crossterm::event::KeyCode;
+use std::path::Path;
+
+use crate::cli::Cli;
+use crate::complete::NOTE_ARGS;
+use crate::external;
+use crate::snippet::fuzzy_score;
+use crate::state::StateStore;
+
+/// The state file of the commands run from the palette.
+const HISTORY: &str = "tui-palette-history";
+/// How many commands the history keeps.
+const HISTORY_LEN: usize = 20;
+/// The subcommands not offered: the TUI itself, and the help.
+const SKIPPED: [&str; 2] = ["tui", "help"];
+
+/// A command of the palette: a subcommand of noxe, `tag add` for those of a group.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaletteCommand {
+    pub name: String,
+    pub about: String,
+    pub args: Vec<ArgPrompt>,
+}
+
+/// A positional argument of a command, prompted for once the command is picked.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArgPrompt {
+    pub name: String,
+    pub help: String,
+    pub required: bool,
+    /// Whether it takes several values, separated by spaces
+    pub multiple: bool,
+    /// Whether it names a note, the selected one being the default
+    pub note: bool,
+}
+
+/// The commands of noxe, and the external `noxe-*` commands on the `PATH`.
+pub fn noxe_commands() -> Vec<PaletteCommand> {
+    let mut cli = Cli::command();
+    cli.build();
+    let mut commands = commands(&cli);
+    commands.extend(external::commands().into_iter().map(|name| PaletteCommand {
+        name,
+        about: "External command".to_string(),
+        args: Vec::new(),
+    }));
+    commands
+}
+
+/// The commands of a command line, the subcommands of groups flattened.
+pub fn commands(cli: &clap::Command) -> Vec<PaletteCommand> {
+    let mut commands = Vec::new();
+    for sub in cli.get_subcommands() {
+        if sub.is_hide_set() || SKIPPED.contains(&sub.get_name()) {
+            continue;
+        }
+        if sub.has_subcommands() {
+            commands.extend(self::commands(sub).into_iter().map(|mut c| {
+                c.name = format!("{} {}", sub.get_name(), c.name);
+                c
+            }));
+            // A group may also run on its own, like `noxe review`
+            if sub.is_subcommand_required_set() {
+                continue;
+            }
+        }
+        commands.push(PaletteCommand {
+            name: sub.get_name().to_string(),
+            about: sub.get_about().map(|a| a.to_string()).unwrap_or_default(),
+            args: sub
+                .get_positionals()
+                .map(|arg| ArgPrompt {
+                    name: arg.get_id().to_string(),
+                    help: arg.get_help().map(|h| h.to_string()).unwrap_or_default(),
+                    required: arg.is_required_set(),
+                    multiple: arg.get_num_args().is_some_and(|n| n.max_values() > 1),
+                    note: NOTE_ARGS.contains(&arg.get_id().as_str()) && sub.get_name() != "new",
+                })
+                .collect(),
+        });
+    }
+    commands
+}
+
+/// The commands run from the palette, the most recent first.
+pub fn load_history(note_root: &Path) -> Vec<String> {
+    // A history that cannot be read is not worth failing for
+    StateStore::new(note_root).load(HISTORY).unwrap_or_default()
+}
+
+/// Put a command line at the top of the history.
+pub fn record(note_root: &Path, history: &mut Vec<String>, line: &str) -> Result<()> {
+    history.retain(|l| l != line);
+    history.insert(0, line.to_string());
+    history.truncate(HISTORY_LEN);
+    StateStore::new(note_root).save(HISTORY, history)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Choice {
+    Recent(usize),
+    Command(usize),
+}
+
+/// The arguments of the command being prompted for.
+#[derive(Debug)]
+struct Prompting {
+    command: usize,
+    words: Vec<String>,
+    /// The argument prompted for, the extra options after the last one
+    arg: usize,
+}
+
+/// What the palette asks of the TUI after a key.
+#[derive(Debug, PartialEq)]
+pub enum PaletteAction {
+    None,
+    Close,
+    /// Run noxe with these arguments
+    Run(Vec<String>),
+}
+
+/// The command palette: the recent commands and the commands matching the input, then
+/// prompts for the arguments of the picked command.
+#[derive(Debug)]
+pub struct Palette {
+    commands: Vec<PaletteCommand>,
+    history: Vec<String>,
+    pub input: String,
+    matches: Vec<Choice>,
+    pub selected: usize,
+    prompting: Option<Prompting>,
+    /// The selected note, the default of the arguments naming a note
+    note: Option<String>,
+    pub message: Option<String>,
+}
+
+impl Palette {
+    pub fn new(commands: Vec<PaletteCommand>, history: Vec<String>, note: Option<String>) -> Self {
+        let mut palette = Palette {
+            commands,
+            history,
+            input: String::new(),
+            matches: Vec::new(),
+            selected: 0,
+            prompting: None,
+            note,
+            message: None,
+        };
+        palette.refilter();
+        palette
+    }
+
+    /// The title of the palette: the argument prompted for, if any.
+    pub fn title(&self) -> String {
+        let Some(prompting) = &self.prompting else {
+            return " Command ".to_string();
+        };
+        let command = &self.commands[prompting.command];
+        match command.args.get(prompting.arg) {
+            Some(arg) => {
+                let optional = if arg.required { "" } else { ", optional" };
+                let multiple = if arg.multiple {
+                    ", space-separated"
+                } else {
+                    ""
+                };
+                format!(" {}: {}{}{} ", command.name, arg.name, optional, multiple)
+            }
+            None => format!(" {}: options, e.g. --help ", command.name),
+        }
+    }
+
+    /// The lines listed under the input: the matching commands, or the help of the argument.
+    pub fn lines(&self) -> Vec<String> {
+        if let Some(prompting) = &self.prompting {
+            let command = &self.commands[prompting.command];
+            let mut lines = vec![format!("noxe {}", join(&prompting.words))];
+            if let Some(arg) = command.args.get(prompting.arg) {
+                lines.push(arg.help.clone());
+            }
+            return lines;
+        }
+        self.matches
+            .iter()
+            .map(|choice| match *choice {
+                Choice::Recent(i) => format!("↺ {}", self.history[i]),
+                Choice::Command(i) => {
+                    let command = &self.commands[i];
+                    format!("{:<16} {}", command.name, command.about)
+                }
+            })
+            .collect()
+    }
+
+    pub fn is_prompting(&self) -> bool {
+        self.prompting.is_some()
+    }
+
+    pub fn handle_code(&mut self, code: KeyCode) -> PaletteAction {
+        self.message = None;
+        match code {
+            KeyCode::Esc => return PaletteAction::Close,
+            KeyCode::Down if self.selected + 1 < self.matches.len() => self.selected += 1,
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Backspace => {
+                self.input.pop();
+                self.refilter();
+            }
+            KeyCode::Char(c) => {
+                self.input.push(c);
+                self.refilter();
+            }
+            KeyCode::Enter => match self.enter() {
+                Ok(action) => return action,
+                Err(e) => self.message = Some(format!("{:#}", e)),
+            },
+            _ => {}
+        }
+        PaletteAction::None
+    }
+
+    fn enter(&mut self) -> Result<PaletteAction> {
+        let Some(prompting) = &mut self.prompting else {
+            match self.matches.get(self.selected).copied() {
+                Some(Choice::Recent(i)) => return Ok(PaletteAction::Run(split(&self.history[i])?)),
+                Some(Choice::Command(i)) => {
+                    self.prompting = Some(Prompting {
+                        command: i,
+                        words: split(&self.commands[i].name)?,
+                        arg: 0,
+                    });
+                    self.prefill();
+                    return Ok(self.next_prompt());
+                }
+                None => bail!("No command matches '{}'", self.input),
+            }
+        };
+
+        let command = &self.commands[prompting.command];
+        let values = split(&self.input)?;
+        match command.args.get(prompting.arg) {
+            Some(arg) if values.is_empty() && arg.required => bail!("{} is required", arg.name),
+            Some(arg) if values.len() > 1 && !arg.multiple => {
+                bail!("{} takes one value, quote it if it has spaces", arg.name)
+            }
+            _ => {}
+        }
+        prompting.words.extend(values);
+        prompting.arg += 1;
+        self.input.clear();
+        self.prefill();
+        Ok(self.next_prompt())
+    }
+
+    /// Skip to the extra options once the arguments are given, and run the command after them.
+    fn next_prompt(&mut self) -> PaletteAction {
+        match &self.prompting {
+            Some(prompting) if prompting.arg > self.commands[prompting.command].args.len() => {
+                PaletteAction::Run(prompting.words.clone())
+            }
+            _ => PaletteAction::None,
+        }
+    }
+
+    /// Fill the input with the selected note when the argument names a note.
+    fn prefill(&mut self) {
+        if let Some(prompting) = &self.prompting
+            && let Some(arg) = self.commands[prompting.command].args.get(prompting.arg)
+            && arg.note
+            && let Some(note) = &self.note
+        {
+            self.input = join(std::slice::from_ref(note));
+        }
+    }
+
+    fn refilter(&mut self) {
+        if self.prompting.is_some() {
+            return;
+        }
+        let mut scored: Vec<(i64, Choice)> = self
+            .history
+            .iter()
+            .enumerate()
+            .filter_map(|(i, line)| fuzzy_score(&self.input, line).map(|s| (s, Choice::Recent(i))))
+            .chain(self.commands.iter().enumerate().filter_map(|(i, command)| {
+                fuzzy_score(&self.input, &command.name).map(|s| (s, Choice::Command(i)))
+            }))
+            .collect();
+        // Recent commands first on ties, the sort being stable
+        scored.sort_by_key(|s| std::cmp::Reverse(s.0));
+        self.matches = scored.into_iter().map(|(_, choice)| choice).collect();
+        self.selected = 0;
+    }
+}
+
+/// Split a command line on spaces outside of double quotes.
+pub fn split(line: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_quotes = false;
+    let mut started = false;
+
+    for c in line.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                started = true;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if started {
+                    words.push(std::mem::take(&mut word));
+                }
+                started = false;
+            }
+            c => {
+                word.push(c);
+                started = true;
+            }
+        }
+    }
+    if in_quotes {
+        bail!("Unterminated quote in '{}'", line);
+    }
+    if started {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+/// Join words into a command line, quoting those with spaces.
+pub fn join(words: &[String]) -> String {
+    words
+        .iter()
+        .map(|w| {
+            if w.is_empty() || w.contains(char::is_whitespace) {
+                format!("\"{}\"", w)
+            } else {
+                w.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Arg, Command};
+
+    #[test]
+    fn test_palette() {
+        let cli = Command::new("noxe")
+            .subcommand(
+                Command::new("edit")
+                    .about("Edit a note")
+                    .arg(Arg::new("note_path").required(true)),
+            )
+            .subcommand(
+                Command::new("tag").subcommand_required(true).subcommand(
+                    Command::new("add")
+                        .about("Tag a note")
+                        .arg(Arg::new("note_path").required(true))
+                        .arg(Arg::new("tags").num_args(1..).required(true)),
+                ),
+            )
+            .subcommand(Command::new("tui"))
+            .subcommand(Command::new("__complete").hide(true));
+        let commands = commands(&cli);
+        assert_eq!(
+            commands.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(),
+            ["edit", "tag add"]
+        );
+        assert!(commands[1].args[1].multiple && !commands[1].args[1].note);
+
+        let mut palette = Palette::new(
+            commands,
+            vec!["edit todo.md".to_string()],
+            Some("/notes/my note.md".to_string()),
+        );
+        assert_eq!(palette.lines()[0], "↺ edit todo.md");
+        let press = |palette: &mut Palette, keys: &str| {
+            keys.chars()
+                .map(|c| {
+                    palette.handle_code(match c {
+                        '\n' => KeyCode::Enter,
+                        c => KeyCode::Char(c),
+                    })
+                })
+                .last()
+        };
+
+        // The selected note is the default, the tags are required
+        assert_eq!(press(&mut palette, "tgad\n"), Some(PaletteAction::None));
+        assert_eq!(palette.input, "\"/notes/my note.md\"");
+        press(&mut palette, "\n\n");
+        assert_eq!(palette.message.as_deref(), Some("tags is required"));
+        press(&mut palette, "rust \"async io\"\n");
+        assert_eq!(
+            press(&mut palette, "\n"),
+            Some(PaletteAction::Run(vec![
+                "tag".into(),
+                "add".into(),
+                "/notes/my note.md".into(),
+                "rust".into(),
+                "async io".into()
+            ]))
+        );
+
+        let line = join(&split("tag add \"my note\" rust").unwrap());
+        assert_eq!(line, "tag add \"my note\" rust");
+        assert!(split("edit \"oops").is_err());
+    }
+}
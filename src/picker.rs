@@ -0,0 +1,216 @@
+use anyhow::{Result, bail};
+use ratatui::{
+    Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout},
+    style::Stylize,
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+};
+use std::path::{Path, PathBuf};
+
+use crate::metadata::preview_lines;
+use crate::snippet::fuzzy_score;
+use crate::theme::theme;
+use crate::vault::Note;
+
+/// Let the user pick one of the candidate notes: typing filters them, fuzzy-matched on their
+/// path relative to the note root, and the first lines of the selected one are shown beside
+/// the list. Returns the index of the picked note.
+pub fn pick(note_root: &Path, candidates: &[PathBuf]) -> Result<usize> {
+    let labels: Vec<String> = candidates
+        .iter()
+        .map(|p| {
+            p.strip_prefix(note_root)
+                .unwrap_or(p)
+                .to_string_lossy()
+                .replace('\\', "/")
+        })
+        .collect();
+    let preview = |i: usize, height: usize| {
+        candidates[i]
+            .main_file_path()
+            .ok()
+            .and_then(|main_file| preview_lines(&main_file, height).ok())
+            .unwrap_or_default()
+    };
+
+    match pick_with(" Notes ", &labels, "", preview)? {
+        Some(index) => Ok(index),
+        None => bail!("No note picked"),
+    }
+}
+
+/// Let the user pick one of the labels, fuzzy-matched on what is typed, starting with `query`,
+/// with the lines `preview` gives for the selected one beside the list. Returns the index of
+/// the picked label, or `None` when cancelled.
+pub fn pick_with(
+    title: &str,
+    labels: &[String],
+    query: &str,
+    preview: impl Fn(usize, usize) -> Vec<String>,
+) -> Result<Option<usize>> {
+    let mut picker = Picker::new(labels, query);
+
+    let mut terminal = ratatui::init();
+    let result = (|| -> Result<Option<usize>> {
+        loop {
+            terminal.draw(|frame| picker.draw(frame, title, &preview))?;
+            if let Event::Key(key) = event::read()?
+                && let Some(done) = picker.handle_key(key)
+            {
+                return Ok(done);
+            }
+        }
+    })();
+    ratatui::restore();
+    result
+}
+
+struct Picker<'a> {
+    labels: &'a [String],
+    query: String,
+    /// Indices of the labels matching the query, best first
+    matches: Vec<usize>,
+    selected: usize,
+}
+
+impl<'a> Picker<'a> {
+    fn new(labels: &'a [String], query: &str) -> Self {
+        Picker {
+            labels,
+            query: query.to_string(),
+            matches: filter(query, labels),
+            selected: 0,
+        }
+    }
+
+    /// `Some` once done: the picked candidate, or `None` when cancelled.
+    fn handle_key(&mut self, key: KeyEvent) -> Option<Option<usize>> {
+        if key.kind != KeyEventKind::Press {
+            return None;
+        }
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Enter => return self.matches.get(self.selected).map(|&i| Some(i)),
+            KeyCode::Esc => return Some(None),
+            KeyCode::Char('c') if ctrl => return Some(None),
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Char('p') if ctrl => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Tab => self.select_next(),
+            KeyCode::Char('n') if ctrl => self.select_next(),
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.refilter();
+            }
+            KeyCode::Char(c) if !ctrl => {
+                self.query.push(c);
+                self.refilter();
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn select_next(&mut self) {
+        if self.selected + 1 < self.matches.len() {
+            self.selected += 1;
+        }
+    }
+
+    fn refilter(&mut self) {
+        self.matches = filter(&self.query, self.labels);
+        self.selected = 0;
+    }
+
+    fn draw(&self, frame: &mut Frame, title: &str, preview: &dyn Fn(usize, usize) -> Vec<String>) {
+        let [input, main] =
+            Layout::vertical([Constraint::Length(3), Constraint::Min(3)]).areas(frame.area());
+        let [list_area, preview_area] =
+            Layout::horizontal([Constraint::Percentage(50); 2]).areas(main);
+
+        frame.render_widget(
+            Paragraph::new(format!("> {}", self.query)).block(
+                Block::default().borders(Borders::ALL).title(format!(
+                    " {}/{} ",
+                    self.matches.len(),
+                    self.labels.len()
+                )),
+            ),
+            input,
+        );
+
+        let items: Vec<ListItem> = self
+            .matches
+            .iter()
+            .map(|&i| ListItem::new(self.labels[i].as_str()))
+            .collect();
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .highlight_style(theme().selection);
+        let mut state = ListState::default().with_selected(Some(self.selected));
+        frame.render_stateful_widget(list, list_area, &mut state);
+
+        let preview = self
+            .matches
+            .get(self.selected)
+            .map(|&i| preview(i, preview_area.height.saturating_sub(2).into()))
+            .unwrap_or_default()
+            .join("\n");
+        frame.render_widget(
+            Paragraph::new(preview)
+                .dim()
+                .block(Block::default().borders(Borders::ALL).title(" Preview ")),
+            preview_area,
+        );
+    }
+}
+
+/// The indices of the labels matching the query, best first. All of them, in order, for an
+/// empty query.
+fn filter(query: &str, labels: &[String]) -> Vec<usize> {
+    let mut scored: Vec<(i64, usize)> = labels
+        .iter()
+        .enumerate()
+        .filter_map(|(i, label)| fuzzy_score(query, label).map(|score| (score, i)))
+        .collect();
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    scored.into_iter().map(|(_, i)| i).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_picker() {
+        let labels = [
+            "lang/rust.md".to_string(),
+            "archive/lang/rust.md".to_string(),
+            "rust-book".to_string(),
+        ];
+        let mut picker = Picker::new(&labels, "");
+        assert_eq!(picker.matches, [0, 1, 2]);
+
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        for c in "book".chars() {
+            assert_eq!(picker.handle_key(key(KeyCode::Char(c))), None);
+        }
+        assert_eq!(picker.matches, [2]);
+        for _ in 0..4 {
+            picker.handle_key(key(KeyCode::Backspace));
+        }
+        assert_eq!(picker.matches, [0, 1, 2]);
+        for c in "arch".chars() {
+            picker.handle_key(key(KeyCode::Char(c)));
+        }
+        assert_eq!(picker.matches, [1]);
+        assert_eq!(picker.handle_key(key(KeyCode::Enter)), Some(Some(1)));
+
+        picker.handle_key(key(KeyCode::Char('z')));
+        assert_eq!(picker.handle_key(key(KeyCode::Enter)), None);
+        assert_eq!(picker.handle_key(key(KeyCode::Esc)), Some(None));
+
+        // Starting with a query
+        assert_eq!(Picker::new(&labels, "book").matches, [2]);
+    }
+}
@@ -0,0 +1,99 @@
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+use crate::state::StateStore;
+
+/// The state file of the pinned notes, `.noxe/pins.json`.
+const PINS: &str = "pins";
+
+/// The pinned notes, relative to the note root, in the order they were pinned.
+pub type Pins = Vec<PathBuf>;
+
+pub fn load(note_root: &Path) -> Result<Pins> {
+    StateStore::new(note_root).load(PINS)
+}
+
+fn save(note_root: &Path, pins: &Pins) -> Result<()> {
+    StateStore::new(note_root).save(PINS, pins)
+}
+
+fn relative(note_root: &Path, note: &Path) -> PathBuf {
+    note.strip_prefix(note_root).unwrap_or(note).to_path_buf()
+}
+
+/// Pin a note. Returns whether it was not pinned yet.
+pub fn pin(note_root: &Path, note: &Path) -> Result<bool> {
+    let mut pins = load(note_root)?;
+    let note = relative(note_root, note);
+    if pins.contains(&note) {
+        return Ok(false);
+    }
+    pins.push(note);
+    save(note_root, &pins)?;
+    Ok(true)
+}
+
+/// Unpin a note, given by its path or by its path relative to the note root, which still works
+/// once it is gone. Returns whether it was pinned.
+pub fn unpin(note_root: &Path, note: &Path) -> Result<bool> {
+    let mut pins = load(note_root)?;
+    let note = relative(note_root, note);
+    let len = pins.len();
+    pins.retain(|pin| *pin != note);
+    if pins.len() == len {
+        return Ok(false);
+    }
+    save(note_root, &pins)?;
+    Ok(true)
+}
+
+/// The notes of `notes` that are pinned, in the order they were pinned.
+pub fn pinned<'a>(note_root: &Path, pins: &Pins, notes: &[&'a Path]) -> Vec<&'a Path> {
+    pins.iter()
+        .filter_map(|pin| {
+            notes
+                .iter()
+                .find(|note| relative(note_root, note) == *pin)
+                .copied()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_pins() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        let todo = root.join("todo.md");
+        let rust = root.join("lang/rust");
+        fs::create_dir_all(&rust).unwrap();
+        fs::write(&todo, "").unwrap();
+        fs::write(rust.join("main.md"), "").unwrap();
+
+        assert!(pin(root, &rust).unwrap());
+        assert!(pin(root, &todo).unwrap());
+        assert!(!pin(root, &todo).unwrap());
+        assert_eq!(
+            load(root).unwrap(),
+            [PathBuf::from("lang/rust"), PathBuf::from("todo.md")]
+        );
+
+        let other = root.join("other.md");
+        let notes = [other.as_path(), todo.as_path(), rust.as_path()];
+        assert_eq!(
+            pinned(root, &load(root).unwrap(), &notes),
+            [rust.as_path(), todo.as_path()]
+        );
+
+        // A note gone is unpinned by its relative path
+        fs::remove_file(&todo).unwrap();
+        assert!(unpin(root, Path::new("todo.md")).unwrap());
+        assert!(!unpin(root, &todo).unwrap());
+        assert_eq!(load(root).unwrap(), [PathBuf::from("lang/rust")]);
+    }
+}
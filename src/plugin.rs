@@ -0,0 +1,237 @@
+use anyhow::{Context, Result, anyhow, bail};
+use std::path::{Path, PathBuf};
+use wasmtime::{Config, Engine, Instance, Memory, Module, Store, TypedFunc};
+
+use crate::record::NoteRecord;
+use crate::state::STATE_DIR;
+
+/// The directory of `.noxe` where plugins are looked up.
+pub const PLUGIN_DIR: &str = "plugins";
+
+/// The instructions a plugin may run per call, so that a looping plugin fails instead of
+/// hanging `noxe list`.
+const FUEL_PER_CALL: u64 = 10_000_000;
+
+/// A WebAssembly module filtering or ranking notes, loaded from `.noxe/plugins/<name>.wasm`
+/// (or `.wat`).
+///
+/// A plugin exports its `memory`, `alloc(len: i32) -> i32` returning where noxe may write
+/// `len` bytes, and at least one of:
+///
+/// - `filter(ptr: i32, len: i32) -> i32`: non-zero to keep the note
+/// - `score(ptr: i32, len: i32) -> f64`: notes are listed by descending score
+///
+/// Both receive the note as the UTF-8 JSON of a note record, as printed by
+/// `noxe list --format json`. The written bytes are only valid during the call.
+pub struct Plugin {
+    name: String,
+    store: Store<()>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    filter: Option<TypedFunc<(i32, i32), i32>>,
+    score: Option<TypedFunc<(i32, i32), f64>>,
+}
+
+impl Plugin {
+    pub fn load(note_root: &Path, name: &str) -> Result<Self> {
+        let path = plugin_path(note_root, name)?;
+
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config)?;
+        let module = Module::from_file(&engine, &path)
+            .with_context(|| format!("Failed to load plugin '{}'", path.display()))?;
+
+        let mut store = Store::new(&engine, ());
+        let instance = Instance::new(&mut store, &module, &[])
+            .with_context(|| format!("Failed to instantiate plugin '{}'", name))?;
+
+        let plugin = Plugin {
+            name: name.to_string(),
+            memory: instance
+                .get_memory(&mut store, "memory")
+                .ok_or_else(|| anyhow!("Plugin '{}' does not export its memory", name))?,
+            alloc: instance
+                .get_typed_func(&mut store, "alloc")
+                .with_context(|| format!("Plugin '{}' does not export alloc", name))?,
+            filter: optional_func(&instance, &mut store, "filter", name)?,
+            score: optional_func(&instance, &mut store, "score", name)?,
+            store,
+        };
+        if plugin.filter.is_none() && plugin.score.is_none() {
+            bail!("Plugin '{}' exports neither filter nor score", name);
+        }
+
+        Ok(plugin)
+    }
+
+    /// Whether the plugin keeps the note. Notes are kept when it has no filter.
+    pub fn filter(&mut self, record: &NoteRecord) -> Result<bool> {
+        let Some(filter) = self.filter.clone() else {
+            return Ok(true);
+        };
+        let (ptr, len) = self.write(record)?;
+        let keep = filter.call(&mut self.store, (ptr, len)).with_context(|| {
+            format!("Plugin '{}' failed to filter '{}'", self.name, record.path)
+        })?;
+        Ok(keep != 0)
+    }
+
+    /// The score of the note, if the plugin ranks notes.
+    pub fn score(&mut self, record: &NoteRecord) -> Result<Option<f64>> {
+        let Some(score) = self.score.clone() else {
+            return Ok(None);
+        };
+        let (ptr, len) = self.write(record)?;
+        let score = score
+            .call(&mut self.store, (ptr, len))
+            .with_context(|| format!("Plugin '{}' failed to score '{}'", self.name, record.path))?;
+        Ok(Some(score))
+    }
+
+    pub fn ranks(&self) -> bool {
+        self.score.is_some()
+    }
+
+    /// Copy the record into the plugin memory, refilling the fuel for the coming call.
+    fn write(&mut self, record: &NoteRecord) -> Result<(i32, i32)> {
+        self.store.set_fuel(FUEL_PER_CALL)?;
+
+        let json = serde_json::to_vec(record)?;
+        let len = i32::try_from(json.len())?;
+        let ptr = self
+            .alloc
+            .call(&mut self.store, len)
+            .with_context(|| format!("Plugin '{}' failed to allocate", self.name))?;
+        self.memory
+            .write(&mut self.store, ptr as u32 as usize, &json)
+            .with_context(|| format!("Plugin '{}' returned an invalid address", self.name))?;
+
+        self.store.set_fuel(FUEL_PER_CALL)?;
+        Ok((ptr, len))
+    }
+}
+
+fn optional_func<Params, Results>(
+    instance: &Instance,
+    store: &mut Store<()>,
+    func: &str,
+    name: &str,
+) -> Result<Option<TypedFunc<Params, Results>>>
+where
+    Params: wasmtime::WasmParams,
+    Results: wasmtime::WasmResults,
+{
+    match instance.get_func(&mut *store, func) {
+        Some(f) => Ok(Some(f.typed(&*store).with_context(|| {
+            format!("Plugin '{}' exports {} with a wrong signature", name, func)
+        })?)),
+        None => Ok(None),
+    }
+}
+
+fn plugin_path(note_root: &Path, name: &str) -> Result<PathBuf> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        bail!("Invalid plugin name '{}'", name);
+    }
+
+    let dir = note_root.join(STATE_DIR).join(PLUGIN_DIR);
+    ["wasm", "wat"]
+        .iter()
+        .map(|ext| dir.join(format!("{}.{}", name, ext)))
+        .find(|p| p.is_file())
+        .ok_or_else(|| anyhow!("No plugin '{}' in '{}'", name, dir.display()))
+}
+
+/// Run the notes through the plugins in order. Each filters the notes, and the last plugin
+/// exporting `score` orders them, best first. Returns whether the notes were ranked.
+pub fn apply<'a>(
+    note_root: &Path,
+    notes: Vec<&'a Path>,
+    plugins: &[String],
+) -> Result<(Vec<&'a Path>, bool)> {
+    let mut notes: Vec<(&Path, NoteRecord)> = notes
+        .into_iter()
+        .map(|n| (n, NoteRecord::new(note_root, n)))
+        .collect();
+    let mut ranked = false;
+
+    for name in plugins {
+        let mut plugin = Plugin::load(note_root, name)?;
+
+        let mut kept = Vec::with_capacity(notes.len());
+        for (note, record) in notes {
+            if plugin.filter(&record)? {
+                kept.push((note, record));
+            }
+        }
+        notes = kept;
+
+        if plugin.ranks() {
+            let mut scored = Vec::with_capacity(notes.len());
+            for (note, record) in notes {
+                let score = plugin.score(&record)?.unwrap_or(f64::NEG_INFINITY);
+                scored.push((score, note, record));
+            }
+            scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+            notes = scored.into_iter().map(|(_, n, r)| (n, r)).collect();
+            ranked = true;
+        }
+    }
+
+    Ok((notes.into_iter().map(|(n, _)| n).collect(), ranked))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    /// Scores notes by the length of their record, and drops those whose path starts with `b`.
+    const PLUGIN: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (func (export "alloc") (param $len i32) (result i32)
+    (i32.const 1024))
+  (func (export "filter") (param $ptr i32) (param $len i32) (result i32)
+    ;; The record starts with {"path":"
+    (i32.ne (i32.load8_u (i32.add (local.get $ptr) (i32.const 9))) (i32.const 98)))
+  (func (export "score") (param $ptr i32) (param $len i32) (result f64)
+    (f64.convert_i32_u (local.get $len))))
+"#;
+
+    #[test]
+    fn test_apply() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        let plugin_dir = root.join(STATE_DIR).join(PLUGIN_DIR);
+        fs::create_dir_all(&plugin_dir).unwrap();
+        fs::write(plugin_dir.join("rank.wat"), PLUGIN).unwrap();
+        fs::write(
+            plugin_dir.join("loop.wat"),
+            r#"(module (memory (export "memory") 1)
+                (func (export "alloc") (param i32) (result i32) (loop (br 0)) (i32.const 0))
+                (func (export "filter") (param i32 i32) (result i32) (i32.const 1)))"#,
+        )
+        .unwrap();
+
+        let (a, b, long) = (
+            root.join("a.md"),
+            root.join("b.md"),
+            root.join("long-name.md"),
+        );
+        for note in [&a, &b, &long] {
+            fs::write(note, "").unwrap();
+        }
+        let notes = vec![a.as_path(), b.as_path(), long.as_path()];
+
+        let (result, ranked) = apply(root, notes.clone(), &["rank".to_string()]).unwrap();
+        assert!(ranked);
+        assert_eq!(result, [long.as_path(), a.as_path()]);
+
+        assert!(apply(root, notes.clone(), &["loop".to_string()]).is_err());
+        assert!(apply(root, notes.clone(), &["missing".to_string()]).is_err());
+        assert!(apply(root, notes, &["../rank".to_string()]).is_err());
+    }
+}
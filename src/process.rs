@@ -1,261 +1,1393 @@
-use crate::cli::{Cli, NoteType};
+use crate::agenda;
+use crate::ai;
+use crate::append;
+use crate::archive;
+use crate::assets;
+use crate::attach;
+use crate::backup;
+use crate::bounded::{preview_limit, truncation_notice};
+use crate::capture;
+use crate::cite;
+use crate::cli::{Command, NoteType, OutputFormat};
+use crate::complete;
+use crate::config;
+use crate::content::ContentIndex;
+use crate::context;
+use crate::crypt::{self, Decrypted};
+use crate::current;
+use crate::dates::DateFilter;
+use crate::doctor;
+use crate::export::{self, ExportSettings};
+use crate::external;
+use crate::extract::NoteText;
+use crate::feed;
+use crate::flashcards;
+use crate::garden::{self, Tier};
+use crate::graph::{Graph, GraphFormat};
+use crate::hooks::{self, Hook};
+use crate::import;
+use crate::include::IncludeTree;
+use crate::index::NoteIndex;
+use crate::journal;
+use crate::lang::is_lang;
+use crate::links::LinkIndex;
+use crate::lint;
+use crate::llm::{self, LlmOptions};
+use crate::markdown;
+use crate::meta::{self, MetaAction};
+use crate::metadata::{NoteMetadata, preview_lines as read_preview_lines, read_metadata};
+use crate::migrate;
+use crate::open;
+use crate::pack;
+use crate::picker;
+use crate::pins;
+use crate::plugin;
+use crate::profile;
+use crate::query::Query;
+use crate::random;
+use crate::recent;
+use crate::record::{self, NoteRecord};
+use crate::rename;
+use crate::review;
+use crate::searches::{self, SavedSearch};
+use crate::sections::{self, NoteSections};
+use crate::semantic::{self, SemanticOptions};
+use crate::serve;
+use crate::setup;
+use crate::sidecar;
+use crate::site;
+use crate::snippet;
+use crate::sort::{self, SortKey};
+use crate::srs;
+use crate::state::StateStore;
+use crate::sync;
+use crate::tag;
+use crate::template::{self, TemplateStore};
+use crate::todo;
+use crate::topics::{self, TopicOptions};
+use crate::tui;
+use crate::vault::{
+    NewNote, Note, NoteTemplate, Scope, all_notes, category_dir, create_note, load_note_template,
+    note_lang_in, note_matches_fields, note_metadata, notes_in_categories, scoped_notes, search,
+};
+use crate::zk;
 use anyhow::{Context, Result, bail};
-use chrono::{Datelike, Timelike};
-use ignore::{DirEntry, WalkBuilder};
-use serde::Deserialize;
+use colored::Colorize;
+use ignore::DirEntry;
 use std::{
-    collections::{BTreeMap, HashMap},
-    env::current_dir,
+    collections::{BTreeMap, HashMap, HashSet},
     ffi::{OsStr, OsString},
     fs,
-    io::{self, Write},
-    ops::Deref,
+    io::{self, IsTerminal, Write},
     path::{Component, Path, PathBuf},
-    process::Command,
+    process,
+    sync::atomic::{AtomicBool, Ordering},
+    time::SystemTime,
 };
 
-// TODO: 改为NewType
-trait Note {
-    fn note_type(&self) -> Result<NoteType>;
-
-    fn main_file_path(&self) -> Result<PathBuf>;
+pub fn process_command(command: Command) -> Result<()> {
+    match command {
+        command @ Command::New { .. } => {
+            let note_path = new_note(command)?;
+            println!("Note '{}' created successfully!", note_path.display());
+        }
+        Command::Preview {
+            note_path,
+            note_root,
+            mut preview_typst,
+            mut preview_markdown,
+            mut preview_org,
+            serve,
+            port,
+            pdf,
+            watch,
+            pdf_viewer,
+        } => {
+            let note = current::note_or_current(note_path.as_deref(), &note_root)?;
+            current::record_opened(&note);
+            if pdf {
+                return open::preview_pdf(Path::new(&note_root), &note, &pdf_viewer, watch);
+            }
+            let note_path = note.main_file_path()?;
+            let decrypted = Decrypted::open_if_encrypted(&note_path)?;
+            let note_path = decrypted.as_ref().map_or(note_path, |d| d.path.clone());
+            let note_type = note_path.note_type()?;
 
-    fn is_filenote(&self) -> bool;
+            if serve {
+                if note_type != NoteType::Md {
+                    bail!("--serve only renders markdown notes");
+                }
+                return serve::serve(&note_path, port);
+            }
 
-    fn is_dirnote(&self) -> bool;
+            if preview_typst.is_empty() {
+                let root = note_path.parent().unwrap();
+                preview_typst = vec![
+                    "tinymist".into(),
+                    "preview".into(),
+                    "--root".into(),
+                    root.into(),
+                ];
+            }
+            // Without a markdown previewer, the built-in renderer is used if glow is missing
+            let builtin_markdown = preview_markdown.is_empty();
+            if builtin_markdown {
+                preview_markdown = vec!["glow".into()];
+            }
+            if preview_org.is_empty() {
+                preview_org = vec!["emacs".into(), "-nw".into()];
+            }
 
-    fn is_category(&self) -> bool;
+            match note_type {
+                NoteType::Typ => exec_with(&note_path, &preview_typst)?,
+                NoteType::Md if builtin_markdown && !in_path("glow") => {
+                    let body = NoteText::read(&note_path)?.body();
+                    let width = ratatui::crossterm::terminal::size()
+                        .map(|(w, _)| w as usize)
+                        .unwrap_or(80);
+                    print!(
+                        "{}",
+                        markdown::to_terminal(&markdown::parse(&body), width.min(100))
+                    );
+                    return Ok(());
+                }
+                NoteType::Md => exec_with(&note_path, &preview_markdown)?,
+                NoteType::Org => exec_with(&note_path, &preview_org)?,
+                NoteType::Custom(ext) => {
+                    let Some(preview) = config::note_types()
+                        .get(ext)
+                        .and_then(|t| t.preview.as_ref())
+                    else {
+                        bail!(
+                            "No preview command for '{}' notes, set `types.{}.preview` in the config",
+                            ext,
+                            ext
+                        );
+                    };
+                    exec_with(&note_path, &split_command(preview))?
+                }
+            }
 
-    fn is_note_name(&self) -> bool;
+            println!("Previewing note '{}'", note_path.display());
+        }
+        Command::Show {
+            note_path,
+            note_root,
+            no_frontmatter,
+            metadata_only,
+            full,
+        } => {
+            let main_file = find_note_dir(&note_path, &note_root)?.main_file_path()?;
+            let limit = if full { u64::MAX } else { preview_limit() };
+            let (content, notice) = show_content(&main_file, no_frontmatter, metadata_only, limit)?;
+            print!("{}", content);
+            if let Some(notice) = notice {
+                eprintln!("{}", notice.dimmed());
+            }
+        }
+        Command::Flashcards {
+            target,
+            note_root,
+            output,
+            deck,
+        } => {
+            let note_root = Path::new(&note_root);
+            let notes = flashcards::target_notes(note_root, Path::new(&target))?;
+            let decks = flashcards::decks(note_root, &notes, &deck)?;
+            let tsv = flashcards::to_tsv(&decks);
+
+            match output {
+                Some(output) => {
+                    fs::write(&output, tsv).with_context(|| {
+                        format!("Failed to write flashcards to '{}'", output.display())
+                    })?;
+                    let count: usize = decks.iter().map(|d| d.cards.len()).sum();
+                    println!("Exported {} cards to '{}'", count, output.display());
+                }
+                None => print!("{}", tsv),
+            }
+        }
+        Command::Outline {
+            note_path,
+            note_root,
+            status,
+            tag,
+            full,
+            format,
+        } => {
+            let note_root = Path::new(&note_root);
+            let notes = match &note_path {
+                Some(note_path) => vec![find_note_dir(note_path, note_root.as_os_str())?],
+                None => all_notes(note_root)?,
+            };
+            let limit = if full { u64::MAX } else { preview_limit() };
+
+            let mut outlines = Vec::new();
+            for note in notes {
+                let Ok((sections, notice)) = sections::note_sections(&note, limit) else {
+                    continue;
+                };
+                if let Some(notice) = notice {
+                    eprintln!("{}: {}", note.display(), notice.dimmed());
+                }
+                let sections: Vec<_> = sections
+                    .into_iter()
+                    .filter(|s| note_path.is_some() || s.is_annotated())
+                    .filter(|s| s.matches(status.as_deref(), tag.as_deref()))
+                    .collect();
+                if !sections.is_empty() {
+                    let rel_path = note.strip_prefix(note_root).unwrap_or(&note);
+                    outlines.push(NoteSections {
+                        note: rel_path.display().to_string(),
+                        sections,
+                    });
+                }
+            }
 
-    // fn note_name
-}
+            if format == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&outlines)?);
+                return Ok(());
+            }
+            for outline in &outlines {
+                if note_path.is_none() {
+                    println!("{}", outline.note.bold());
+                }
+                for section in &outline.sections {
+                    let mut line = format!(
+                        "{}{}",
+                        "  ".repeat(section.level.saturating_sub(1)),
+                        section.heading
+                    );
+                    if let Some(status) = &section.status {
+                        line.push_str(&format!(" {}", format!("[{}]", status).yellow()));
+                    }
+                    for tag in &section.tags {
+                        line.push_str(&format!(" {}", format!("#{}", tag).dimmed()));
+                    }
+                    println!("{}", line);
+                }
+            }
+        }
+        Command::Meta {
+            note_path,
+            action,
+            key,
+            value,
+            string,
+            note_root,
+            override_lock,
+        } => {
+            let note = find_note_dir(&note_path, &note_root)?;
+            match (action, value) {
+                (MetaAction::Get, None) => {
+                    println!("{}", meta::format_value(&meta::get(&note, &key)?))
+                }
+                (MetaAction::Set, Some(value)) => {
+                    let value = meta::parse_value(&value, string);
+                    meta::update(&note, &key, Some(&value), override_lock)?;
+                }
+                (MetaAction::Del, None) => meta::update(&note, &key, None, override_lock)?,
+                (_, _) => bail!("Only `set` takes a value"),
+            }
+        }
+        Command::Edit {
+            note_path,
+            note_root,
+            mut edit,
+        } => {
+            let note = current::note_or_current(note_path.as_deref(), &note_root)?;
+            current::record_opened(&note);
+            let note_path = note.main_file_path()?;
+            let decrypted = Decrypted::open_if_encrypted(&note_path)?;
+            let note_path = decrypted.as_ref().map_or(note_path, |d| d.path.clone());
+
+            if let Ok(NoteType::Custom(ext)) = note_path.note_type()
+                && let Some(command) = config::note_types().get(ext).and_then(|t| t.edit.as_ref())
+            {
+                edit = split_command(command);
+            }
+            if edit.is_empty() {
+                edit = vec!["vim".into()];
+            }
 
-impl<T: Deref<Target = Path>> Note for T {
-    fn note_type(&self) -> Result<NoteType> {
-        if let Some(ext) = self.extension().and_then(|ext| ext.to_str())
-            && let Ok(note_type) = NoteType::try_from(ext)
-        {
-            Ok(note_type)
-        } else {
-            bail!("Failed to parse note type from '{}'", self.display());
+            exec_with(&note_path, &edit)?;
+            if let Some(decrypted) = &decrypted
+                && decrypted.save()?
+            {
+                println!("Encrypted the changes of '{}'", note.display());
+            }
+            hooks::run(Hook::PostEdit, &note, None)?;
         }
-    }
+        Command::Encrypt {
+            note_path,
+            note_root,
+            identity,
+        } => {
+            let note = find_note_dir(&note_path, &note_root)?;
+            let identity = crypt::identity(identity.as_deref())?;
+            let encrypted = crypt::encrypt_note(&note, &identity)?;
+            println!("Encrypted '{}'", encrypted.display());
+        }
+        Command::Decrypt {
+            note_path,
+            note_root,
+            identity,
+        } => {
+            let note = find_note_dir(&note_path, &note_root)?;
+            let identity = crypt::identity(identity.as_deref())?;
+            let decrypted = crypt::decrypt_note(&note, &identity)?;
+            println!("Decrypted '{}'", decrypted.display());
+        }
+        Command::Rm {
+            note_path,
+            note_root,
+            yes,
+            override_lock,
+        } => {
+            let note = find_note_dir(&note_path, &note_root)?;
+            if !override_lock && read_metadata(&note.main_file_path()?)?.is_readonly() {
+                bail!(
+                    "'{}' is read-only, use --override to delete it anyway",
+                    note.display()
+                );
+            }
+            if !yes && !confirm(&format!("Delete '{}'?", note.display()))? {
+                bail!("Aborted");
+            }
 
-    fn main_file_path(&self) -> Result<PathBuf> {
-        let note_path = if self.is_dir() {
-            if self.join("main.typ").is_file() {
-                self.join("main.typ")
-            } else if self.join("main.md").is_file() {
-                self.join("main.md")
+            hooks::run(Hook::PreDelete, &note, None)?;
+            if note.is_dir() {
+                fs::remove_dir_all(&note)
             } else {
-                bail!("No main file found in '{}'", self.display())
+                fs::remove_file(&note)
             }
-        } else {
-            self.to_path_buf()
-        };
-
-        Ok(note_path)
-    }
-
-    fn is_filenote(&self) -> bool {
-        self.is_file()
-            && self
-                .extension()
-                .and_then(|ext| ext.to_str())
-                .and_then(|ext| NoteType::try_from(ext).ok())
-                .is_some()
-    }
+            .with_context(|| format!("Failed to delete '{}'", note.display()))?;
+            println!("Deleted '{}'", note.display());
+        }
+        Command::Includes {
+            note_path,
+            note_root,
+            no_check,
+            print,
+            mut edit,
+        } => {
+            let note = current::note_or_current(note_path.as_deref(), &note_root)?;
+            let mut tree = IncludeTree::build(&note)?;
 
-    fn is_dirnote(&self) -> bool {
-        self.is_dir() && (self.join("main.md").is_file() || self.join("main.typ").is_file())
-    }
+            if print {
+                tree.read_status()?;
+                if !no_check {
+                    tree.check()?;
+                }
+                for node in &tree.nodes {
+                    let indent = "  ".repeat(node.depth);
+                    let mut line = format!(
+                        "{} {}{}",
+                        node.status.map_or(" ", |s| s.marker()),
+                        indent,
+                        tree.relative(&node.path).display()
+                    );
+                    if node.missing {
+                        line.push_str(" (missing)");
+                    } else if node.cycle {
+                        line.push_str(" (cycle)");
+                    }
+                    println!("{}", line);
+                    for d in &node.diagnostics {
+                        println!(
+                            "  {}  {}:{}: {}: {}",
+                            indent, d.line, d.column, d.severity, d.message
+                        );
+                    }
+                }
+            } else {
+                if edit.is_empty() {
+                    edit = vec!["vim".into()];
+                }
+                tui::run_includes(tree, &edit, !no_check)?;
+            }
+        }
+        Command::External(args) => external::run(&args)?,
+        Command::Completions { shell } => print!("{}", complete::script(shell)),
+        Command::ShellInit { shell } => print!("{}", current::shell_init(shell)),
+        Command::Current => match current::current_note() {
+            Some(note) => println!("{}", note.display()),
+            None => bail!("Not in a note, and no note was opened in this shell"),
+        },
+        Command::Complete { words } => {
+            for candidate in complete::complete(&words) {
+                println!("{}", candidate);
+            }
+        }
+        Command::Open {
+            note_path,
+            note_root,
+            source,
+            reveal,
+        } => {
+            let note = current::note_or_current(note_path.as_deref(), &note_root)?;
+            current::record_opened(&note);
 
-    fn is_category(&self) -> bool {
-        self.is_dir() && !self.join("main.md").is_file() && !self.join("main.typ").is_file()
-    }
+            if reveal {
+                open::open(&note, true)?;
+            } else {
+                open::open(
+                    &open::open_target(Path::new(&note_root), &note, source)?,
+                    false,
+                )?;
+            }
+        }
+        Command::Random {
+            note_root,
+            categories,
+            tags,
+            scope,
+            preview_lines,
+            open,
+            source,
+        } => {
+            let note_root = Path::new(&note_root);
+            let notes = random::candidates(note_root, &categories, &tags, scope)?;
+            let Some(note) = random::pick(&notes) else {
+                bail!("No note to pick from");
+            };
+            current::record_opened(note);
 
-    fn is_note_name(&self) -> bool {
-        self.components().count() == 1
-    }
-}
+            if open {
+                open::open(&open::open_target(note_root, note, source)?, false)?;
+            } else {
+                println!("{}", note.display());
+                for line in note_preview(note, preview_lines) {
+                    println!("    {}", line.dimmed());
+                }
+            }
+        }
+        Command::Pin {
+            note_path,
+            note_root,
+        } => {
+            let note = find_note_dir(&note_path, &note_root)?;
+            if pins::pin(Path::new(&note_root), &note)? {
+                println!("Pinned '{}'", note.display());
+            } else {
+                println!("'{}' is already pinned", note.display());
+            }
+        }
+        Command::Unpin {
+            note_path,
+            note_root,
+        } => {
+            let note =
+                find_note_dir(&note_path, &note_root).unwrap_or_else(|_| PathBuf::from(&note_path));
+            if pins::unpin(Path::new(&note_root), &note)? {
+                println!("Unpinned '{}'", note.display());
+            } else {
+                bail!("'{}' is not pinned", note.display());
+            }
+        }
+        Command::Pins { note_root } => {
+            let note_root = Path::new(&note_root);
+            for pin in pins::load(note_root)? {
+                if note_root.join(&pin).exists() {
+                    println!("{}", pin.display());
+                } else {
+                    println!("{} {}", pin.display(), "(missing)".red());
+                }
+            }
+        }
+        Command::Recent {
+            note_root,
+            number,
+            scope,
+            open,
+            source,
+        } => {
+            let note_root = Path::new(&note_root);
+            let notes = recent::recent(note_root, scope, if open { 1 } else { number })?;
+
+            if open {
+                let Some((note, _)) = notes.first() else {
+                    bail!("No note in '{}'", note_root.display());
+                };
+                current::record_opened(note);
+                open::open(&open::open_target(note_root, note, source)?, false)?;
+                return Ok(());
+            }
 
-pub fn process_command(args: Cli) -> Result<()> {
-    match args {
-        Cli::New {
+            let now = SystemTime::now();
+            let ages: Vec<String> = notes
+                .iter()
+                .map(|(_, time)| recent::time_ago(*time, now))
+                .collect();
+            let width = ages.iter().map(String::len).max().unwrap_or(0);
+            for ((note, _), age) in notes.iter().zip(&ages) {
+                println!(
+                    "{}  {}",
+                    format!("{:>width$}", age).dimmed(),
+                    note.strip_prefix(note_root).unwrap_or(note).display()
+                );
+            }
+        }
+        Command::Capture {
+            text,
+            vars,
+            inbox,
+            note_root,
+            edit,
+        } => {
+            let note_root = Path::new(&note_root);
+            let template = text
+                .first()
+                .and_then(|name| config::capture_templates().get(name));
+            let note = match template {
+                Some(template) => {
+                    let vars = capture::parse_vars(&vars)?;
+                    let text = if text.len() == 1 && !template.takes_text() {
+                        String::new()
+                    } else {
+                        capture::read_text(note_root, &text[1..], &edit)?
+                    };
+                    capture::capture_with(
+                        note_root,
+                        template,
+                        &inbox,
+                        &text,
+                        vars,
+                        chrono::Local::now(),
+                    )?
+                }
+                None => {
+                    let text = capture::read_text(note_root, &text, &edit)?;
+                    capture::capture(note_root, &inbox, &text, chrono::Local::now())?
+                }
+            };
+            println!("Captured to '{}'", note.display());
+        }
+        Command::Append {
             note_path,
+            text,
+            heading,
+            no_timestamp,
+            note_root,
+            note_type,
             note_author,
-            note_keywords,
-            mut note_type,
-            mut single_file,
             note_template,
-            note_with_metadata,
+            template,
+            template_dir,
         } => {
-            let note_path = Path::new(&note_path);
-
-            // 如果note_path包含扩展名，则表明是单文件
-            if let Some(ext) = note_path.extension().and_then(|ext| ext.to_str())
-                && let Ok(t) = NoteType::try_from(ext)
-            {
-                note_type = t;
-                single_file = true;
+            let (note, exists) = append::resolve(&note_path, &note_root, note_type)?;
+            let text = append::read_text(&text)?;
+            if !exists {
+                if let Some(parent) = note.parent() {
+                    fs::create_dir_all(parent).with_context(|| {
+                        format!("Failed to create directory '{}'", parent.display())
+                    })?;
+                }
+                let template = note_template_of(template, template_dir, note_template)?;
+                let context = context::for_template(&template, &[], None);
+                create_note_with_hooks(
+                    &note,
+                    &NewNote {
+                        note_type,
+                        single_file: true,
+                        author: note_author,
+                        keywords: Vec::new(),
+                        template,
+                        with_metadata: true,
+                        vars: Default::default(),
+                        context,
+                    },
+                )?;
             }
+            append::append(
+                &note.main_file_path()?,
+                &text,
+                heading.as_deref(),
+                (!no_timestamp).then(chrono::Local::now),
+            )?;
+            println!("Appended to '{}'", note.display());
+        }
+        Command::Journal {
+            date,
+            offset,
+            note_root,
+            pattern,
+            template,
+            edit,
+            no_edit,
+        } => {
+            let date = journal::parse_date(date.as_deref(), offset, journal::today())?;
+            journal::journal(
+                Path::new(&note_root),
+                &pattern,
+                template.as_deref(),
+                date,
+                (!no_edit).then_some(edit),
+            )?;
+        }
+        Command::Agenda {
+            date,
+            week,
+            note_root,
+            pattern,
+            interactive,
+            mut edit,
+        } => {
+            let today = journal::today();
+            let date = journal::parse_date(date.as_deref(), 0, today)?;
+            let note_root = Path::new(&note_root);
+            if interactive {
+                if edit.is_empty() {
+                    edit = vec!["vim".into()];
+                }
+                tui::run_agenda(note_root, &pattern, date, week, &edit)?;
+            } else {
+                let (from, to) = agenda::range(date, week);
+                let items = agenda::collect(note_root, &pattern, from, to, today)?;
+                print!("{}", agenda::render(&items, note_root, from, to, today));
+            }
+        }
+        Command::Todo {
+            command: Some(command),
+            ..
+        } => todo::process_todo_command(command)?,
+        Command::Todo {
+            command: None,
+            target,
+            note_root,
+            done,
+            open,
+            status,
+            tag,
+            format,
+        } => {
+            let note_root = Path::new(&note_root);
+            let notes = match &target {
+                Some(target) => flashcards::target_notes(note_root, Path::new(target))?,
+                None => all_notes(note_root)?,
+            };
+            let filter = match (done, open) {
+                (true, _) => Some(true),
+                (_, true) => Some(false),
+                _ => None,
+            };
+            let todos = todo::collect(note_root, &notes, filter, status.as_deref(), tag.as_deref());
 
-            let note_name = note_path
-                .file_stem()
-                .and_then(|s| s.to_str())
-                .ok_or_else(|| anyhow::anyhow!("Failed to parse note name"))?;
+            if format == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&todos)?);
+            } else {
+                print!("{}", todo::render(&todos));
+            }
+        }
+        Command::Tui {
+            note_root,
+            fresh,
+            restore,
+        } => {
+            if restore {
+                tui::run_restore(Path::new(&note_root))?
+            } else {
+                tui::run(Path::new(&note_root), fresh)?
+            }
+        }
+        Command::Index { note_root, remove } => {
+            let note_root = Path::new(&note_root);
+            if remove {
+                if !NoteIndex::remove(note_root)? {
+                    bail!("No index in '{}'", note_root.display());
+                }
+                println!("Index removed");
+            } else {
+                let index = NoteIndex::rebuild(note_root)?;
+                println!("Indexed {} note(s)", index.notes(note_root).len());
+            }
+        }
+        Command::Graph {
+            note_root,
+            format,
+            output,
+        } => {
+            let graph = Graph::build(Path::new(&note_root))?;
+            let text = match format {
+                GraphFormat::Dot => graph.to_dot(),
+                GraphFormat::Json => format!("{}\n", serde_json::to_string_pretty(&graph)?),
+            };
+            match output {
+                Some(output) => {
+                    fs::write(&output, text)
+                        .with_context(|| format!("Failed to write '{}'", output.display()))?;
+                    println!(
+                        "{} note(s) and {} link(s) written to '{}'",
+                        graph.nodes.len(),
+                        graph.links.len(),
+                        output.display()
+                    );
+                }
+                None => print!("{}", text),
+            }
+        }
+        Command::Garden {
+            note_root,
+            tier,
+            number,
+            format,
+        } => {
+            let mut assessments = garden::assess(Path::new(&note_root), journal::today())?;
+            assessments.retain(|a| tier.is_none_or(|t| a.tier == t));
 
-            // Check if the note already exists
-            if fs::metadata(note_path).is_ok() {
-                bail!("Note '{}' already exists", note_path.display());
+            if format == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&assessments)?);
+                return Ok(());
             }
 
-            let main_path = if single_file {
-                note_path.to_path_buf()
-            } else {
-                note_path.join(format!("main.{}", note_type))
+            for tier in [Tier::Seedling, Tier::Budding, Tier::Evergreen] {
+                let notes: Vec<_> = assessments.iter().filter(|a| a.tier == tier).collect();
+                if notes.is_empty() {
+                    continue;
+                }
+                let name = format!("{:?}", tier);
+                println!("{} ({})", name.bold(), notes.len());
+                for assessment in notes.iter().take(number) {
+                    println!(
+                        "{:>4}  {}  {}",
+                        assessment.score,
+                        assessment.path,
+                        assessment.hints().join(", ").dimmed()
+                    );
+                }
+            }
+        }
+        Command::Topics {
+            note_root,
+            threshold,
+            min_size,
+            terms,
+            moc,
+            note_type,
+            format,
+        } => {
+            let note_root = Path::new(&note_root);
+            let options = TopicOptions {
+                threshold,
+                min_size,
+                terms,
+                exclude: moc.clone(),
             };
+            let topics = topics::topics(note_root, &options)?;
 
-            let mut main_file_data = String::new();
+            if format == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&topics)?);
+            } else {
+                for topic in &topics {
+                    println!("{} ({})", topic.label().bold(), topic.notes.len());
+                    for note in &topic.notes {
+                        println!("  {}", note);
+                    }
+                }
+            }
 
-            // Optionally add metadata
-            if note_with_metadata {
-                main_file_data.push_str(&metadata(
-                    note_name,
-                    note_author.as_ref(),
-                    note_type,
-                    &note_keywords,
-                ));
+            if let Some(moc) = moc {
+                let written = topics::write_mocs(note_root, &moc, note_type, &topics)?;
+                eprintln!(
+                    "{} index note(s) written to '{}'",
+                    written.len(),
+                    note_root.join(&moc).display()
+                );
             }
+        }
+        Command::Rename {
+            note_path,
+            new_name,
+            bulk,
+            pattern,
+            plan,
+            category,
+            dry_run,
+            note_root,
+            mut edit,
+            override_lock,
+        } => {
+            let note_root = Path::new(&note_root);
 
-            let note_template = if let Some(path) = note_template {
-                load_note_template(&path)?
+            let plan = if bulk {
+                let dir = match &category {
+                    Some(category) => note_root.join(category),
+                    None => note_root.to_path_buf(),
+                };
+                let mut notes = all_notes(&dir)?;
+                notes.sort();
+
+                match (pattern, plan) {
+                    (Some(pattern), _) => rename::pattern_plan(&notes, &pattern)?,
+                    (None, Some(plan)) => {
+                        let text = fs::read_to_string(&plan)
+                            .with_context(|| format!("Failed to read '{}'", plan.display()))?;
+                        rename::parse_plan(note_root, &text)?
+                    }
+                    (None, None) => {
+                        if edit.is_empty() {
+                            edit = vec!["vim".into()];
+                        }
+                        let unchanged = notes.iter().map(|n| (n.clone(), n.clone())).collect();
+                        rename::edit_plan(
+                            &StateStore::new(note_root),
+                            note_root,
+                            &unchanged,
+                            &edit,
+                        )?
+                    }
+                }
             } else {
-                Default::default()
+                let (Some(note_path), Some(new_name)) = (note_path, new_name) else {
+                    unreachable!("clap requires the note and its new name without --bulk");
+                };
+                let note = find_note_dir(&note_path, note_root.as_os_str())?;
+                vec![(note.clone(), rename::sibling(&note, &new_name))]
             };
+            let plan: rename::Plan = plan.into_iter().filter(|(from, to)| from != to).collect();
+            if bulk && !override_lock {
+                rename::ensure_unlocked(&plan)?;
+            }
 
-            // Create the note template
-            if !single_file {
-                create_note_template(note_path, &note_template)?;
+            if plan.is_empty() {
+                println!("Nothing to rename");
+                return Ok(());
+            }
+            for (from, to) in &plan {
+                println!("{} -> {}", from.display(), to.display());
             }
 
-            // Add main file data
-            if matches!(note_type, NoteType::Typ)
-                && let Some(main_typ) = &note_template.main_typ
-            {
-                main_file_data.push_str(main_typ);
-            } else if matches!(note_type, NoteType::Md)
-                && let Some(main_md) = &note_template.main_md
-            {
-                main_file_data.push_str(main_md);
+            if dry_run {
+                rename::validate(&plan)?;
+            } else {
+                let rewritten = rename::execute(note_root, &plan, override_lock)?;
+                println!(
+                    "Renamed {} note(s), rewrote the links of {} note(s)",
+                    plan.len(),
+                    rewritten
+                );
             }
+        }
+        Command::Archive {
+            notes,
+            older_than,
+            unarchive,
+            dry_run,
+            note_root,
+            override_lock,
+        } => {
+            let note_root = Path::new(&note_root);
 
-            // Create the main file and write data
-            fs::write(&main_path, main_file_data)
-                .with_context(|| format!("Failed to create main file '{}'", main_path.display()))?;
+            let plan = match older_than {
+                Some(age) => {
+                    let cutoff = archive::cutoff(&age, chrono::Local::now().date_naive())?;
+                    let mut old = archive::older_than(note_root, &all_notes(note_root)?, cutoff);
+                    old.sort();
+                    let plan = archive::archive_plan(note_root, &old)?;
+                    if !override_lock {
+                        rename::ensure_unlocked(&plan)?;
+                    }
+                    plan
+                }
+                None if unarchive => {
+                    // Names are looked up in the archive only
+                    let archive_dir = note_root.join(archive::ARCHIVE_DIR);
+                    let notes = notes
+                        .iter()
+                        .map(|n| find_note_dir(n, archive_dir.as_os_str()))
+                        .collect::<Result<Vec<_>>>()?;
+                    archive::unarchive_plan(note_root, &notes)?
+                }
+                None => {
+                    let notes = notes
+                        .iter()
+                        .map(|n| find_note_dir(n, note_root.as_os_str()))
+                        .collect::<Result<Vec<_>>>()?;
+                    archive::archive_plan(note_root, &notes)?
+                }
+            };
 
-            println!("Note '{}' created successfully!", note_path.display());
+            if plan.is_empty() {
+                println!("Nothing to archive");
+                return Ok(());
+            }
+            for (from, to) in &plan {
+                println!("{} -> {}", from.display(), to.display());
+            }
+
+            if dry_run {
+                rename::validate(&plan)?;
+            } else {
+                let rewritten = rename::execute(note_root, &plan, override_lock)?;
+                println!(
+                    "{} {} note(s), rewrote the links of {} note(s)",
+                    if unarchive { "Unarchived" } else { "Archived" },
+                    plan.len(),
+                    rewritten
+                );
+            }
+        }
+        Command::Doctor {
+            note_root,
+            passes,
+            scope,
+        } => {
+            doctor::doctor(Path::new(&note_root), &passes, scope)?;
         }
-        Cli::Preview {
+        Command::Lint {
+            target,
+            note_root,
+            scope,
+        } => {
+            let problems = lint::lint(Path::new(&note_root), target.as_deref(), scope)?;
+            for problem in &problems {
+                println!("{}", problem);
+            }
+            if !problems.is_empty() {
+                bail!("{} problem(s) found", problems.len());
+            }
+        }
+        Command::Attach {
             note_path,
+            file,
             note_root,
-            mut preview_typst,
-            mut preview_markdown,
+            dir,
+            insert,
         } => {
-            let note_path = if let Some(s) = note_path {
-                s
+            let note = find_note_dir(&note_path, &note_root)?;
+            let main_file = note.main_file_path()?;
+            let note_type = main_file.note_type()?;
+            let attachment = attach::attach(&note, &file, dir.as_deref())?;
+            let link = attachment.link(note_type);
+            eprintln!(
+                "{} '{}'",
+                if attachment.existed {
+                    "Already attached as"
+                } else {
+                    "Attached"
+                },
+                note.join(&attachment.path).display()
+            );
+
+            if insert {
+                attach::insert_link(&main_file, &link)?;
+                println!("Inserted {} into '{}'", link, main_file.display());
             } else {
-                current_dir()?.into_os_string()
+                println!("{}", link);
+            }
+        }
+        Command::CleanAssets {
+            target,
+            note_root,
+            scope,
+            dry_run,
+            trash,
+            yes,
+        } => {
+            let note_root = Path::new(&note_root);
+            let notes = match &target {
+                Some(target) => flashcards::target_notes(note_root, target)?,
+                None => scoped_notes(note_root, note_root, scope)?,
             };
+            let unused = assets::unused(&notes)?;
+            if unused.is_empty() {
+                println!("No unused assets");
+                return Ok(());
+            }
 
-            let note_path = find_note_dir(&note_path, &note_root)?.main_file_path()?;
-            let note_type = note_path.note_type()?;
-
-            if preview_typst.is_empty() {
-                let root = note_path.parent().unwrap();
-                preview_typst = vec![
-                    "tinymist".into(),
-                    "preview".into(),
-                    "--root".into(),
-                    root.into(),
-                ];
+            let mut size = 0;
+            for file in &unused {
+                let len = fs::metadata(file).map_or(0, |m| m.len());
+                size += len;
+                println!(
+                    "{} ({})",
+                    file.strip_prefix(note_root).unwrap_or(file).display(),
+                    record::human_size(len)
+                );
             }
-            if preview_markdown.is_empty() {
-                preview_markdown = vec!["glow".into()];
+            println!(
+                "{} unused file(s), {}",
+                unused.len(),
+                record::human_size(size)
+            );
+            if dry_run {
+                return Ok(());
             }
 
-            match note_type {
-                NoteType::Typ => exec_with(&note_path, &preview_typst)?,
-                NoteType::Md => exec_with(&note_path, &preview_markdown)?,
+            let action = if trash { "Move to the trash" } else { "Delete" };
+            if !yes && !confirm(&format!("{} {} file(s)?", action, unused.len()))? {
+                bail!("Aborted");
+            }
+            if trash {
+                assets::trash(note_root, &unused)?;
+                println!(
+                    "Moved {} file(s) to '{}'",
+                    unused.len(),
+                    note_root.join(archive::TRASH_DIR).display()
+                );
+            } else {
+                assets::delete(&unused)?;
+                println!("Deleted {} file(s)", unused.len());
+            }
+        }
+        Command::Migrate { note_root, dry_run } => {
+            let report = migrate::migrate(&StateStore::new(Path::new(&note_root)), dry_run)?;
+            if report.from == report.to {
+                println!("Vault state is up to date (layout version {})", report.to);
+                return Ok(());
             }
 
-            println!("Previewing note '{}'", note_path.display());
+            if let Some(backup) = &report.backup {
+                println!("Backed up the state to '{}'", backup.display());
+            }
+            for (description, changes) in &report.steps {
+                println!("{}", description.bold());
+                if changes.is_empty() {
+                    println!("  Nothing to change");
+                }
+                for change in changes {
+                    println!("  {}", change);
+                }
+            }
+            if dry_run {
+                println!(
+                    "{}",
+                    format!(
+                        "Dry run: layout version {} would be upgraded to {}",
+                        report.from, report.to
+                    )
+                    .dimmed()
+                );
+            } else {
+                println!("Upgraded layout version {} to {}", report.from, report.to);
+            }
         }
-        Cli::Edit {
-            note_path,
+        Command::Search {
+            query,
+            saved,
+            save,
+            near,
+            within,
+            semantic,
+            limit,
+            provider,
+            embedding_model,
             note_root,
-            mut edit,
+            fields,
+            metadata,
+            query_lang,
+            format,
+            preview_lines,
+            scope,
+            created_after,
+            created_before,
+            modified_after,
+            modified_before,
+            lang,
+            categories,
         } => {
-            let note_path = if let Some(s) = note_path {
-                s
-            } else {
-                current_dir()?.into_os_string()
+            let mut search = SavedSearch {
+                query,
+                query_lang,
+                metadata,
+                fields: fields
+                    .iter()
+                    .map(|f| format!("{}={}", f.key, f.value))
+                    .collect(),
+                categories,
+                created_after,
+                created_before,
+                modified_after,
+                modified_before,
+                lang,
+            };
+            if let Some(name) = &saved {
+                search.fill_from(searches::get(name)?);
+            }
+            if let Some(name) = &save {
+                let path = searches::save(name, &search)?;
+                eprintln!("Saved the search '{}' in '{}'", name, path.display());
+            }
+            let fields = search.field_filters()?;
+            let SavedSearch {
+                query,
+                query_lang,
+                metadata,
+                categories,
+                created_after,
+                created_before,
+                modified_after,
+                modified_before,
+                lang,
+                ..
+            } = search;
+            if query.is_none() && near.is_empty() {
+                bail!(
+                    "The saved search '{}' has no query",
+                    saved.unwrap_or_default()
+                );
+            }
+
+            let note_root = Path::new(&note_root);
+            let dates = DateFilter::new(
+                created_after.as_deref(),
+                created_before.as_deref(),
+                modified_after.as_deref(),
+                modified_before.as_deref(),
+                journal::today(),
+            )?;
+            let categories = categories
+                .iter()
+                .map(|c| category_dir(note_root, c))
+                .collect::<Result<Vec<_>>>()?;
+            let index = NoteIndex::open(note_root)?;
+            let listed = |p: &Path| {
+                scope.contains(note_root, p)
+                    && dates.matches(p)
+                    && lang.as_ref().is_none_or(|lang| {
+                        note_lang_in(index.as_ref(), note_root, p)
+                            .is_some_and(|l| is_lang(&l, lang))
+                    })
             };
 
-            let note_path = find_note_dir(&note_path, &note_root)?.main_file_path()?;
+            if semantic {
+                let notes = notes_in_categories(index.as_ref(), note_root, &categories)?
+                    .into_iter()
+                    .filter(|p| listed(p))
+                    .filter(|p| note_matches_fields(index.as_ref(), note_root, p, &fields))
+                    .collect::<Vec<_>>();
+                let options = SemanticOptions {
+                    provider,
+                    embedding_model,
+                };
+                let mut ranked =
+                    semantic::search(note_root, &notes, &query.unwrap_or_default(), &options)?;
+                ranked.truncate(limit);
+
+                if format == OutputFormat::Json {
+                    let paths = ranked.iter().map(|(p, _)| p.as_path()).collect::<Vec<_>>();
+                    return print_records(note_root, &paths);
+                }
+
+                if ranked.is_empty() {
+                    bail!("No note found in '{}'", note_root.display());
+                }
+
+                for (note, score) in ranked {
+                    println!("{:.3}  {}", score, note.display());
+                    for line in note_preview(&note, preview_lines) {
+                        println!("    {}", line.dimmed());
+                    }
+                }
+                return Ok(());
+            }
+
+            if !near.is_empty() {
+                let notes = notes_in_categories(index.as_ref(), note_root, &categories)?
+                    .into_iter()
+                    .filter(|p| listed(p))
+                    .filter(|p| note_matches_fields(index.as_ref(), note_root, p, &fields))
+                    .collect::<Vec<_>>();
+                let index = ContentIndex::build(&notes);
+                let matches = index.near(&near, within);
+
+                if format == OutputFormat::Json {
+                    let paths = matches.iter().map(|m| m.note).collect::<Vec<_>>();
+                    return print_records(note_root, &paths);
+                }
+
+                if matches.is_empty() {
+                    bail!("No note found in '{}'", note_root.display());
+                }
 
-            if edit.is_empty() {
-                edit = vec!["vim".into()];
+                for m in matches {
+                    println!("{}:{}-{}", m.note.display(), m.lines.start(), m.lines.end());
+                    for line in m.text {
+                        println!("    {}", line.dimmed());
+                    }
+                }
+                return Ok(());
             }
 
-            exec_with(&note_path, &edit)?;
-        }
-        Cli::Search { query, note_root } => {
-            let pattern = regex::RegexBuilder::new(&query)
-                .case_insensitive(true)
-                .build()
-                .with_context(|| format!("Failed to build regex from '{}'", query))?;
+            // `query` is required without `--near`
+            let query = query.unwrap_or_default();
+            let notes = notes_in_categories(index.as_ref(), note_root, &categories)?
+                .into_iter()
+                .filter(|p| listed(p));
+            let result = if query_lang {
+                let query = Query::parse(&query, journal::today())?;
+                notes
+                    .filter(|p| query.matches(index.as_ref(), note_root, p))
+                    .filter(|p| note_matches_fields(index.as_ref(), note_root, p, &fields))
+                    .collect::<Vec<_>>()
+            } else {
+                let pattern = regex::RegexBuilder::new(&query)
+                    .case_insensitive(true)
+                    .build()
+                    .with_context(|| format!("Failed to build regex from '{}'", query))?;
+                let name_matches = |s: &OsStr| s.to_str().is_some_and(|s| pattern.is_match(s));
+
+                notes
+                    .filter(|p| {
+                        p.file_name().is_some_and(name_matches)
+                            || metadata
+                                && note_metadata(index.as_ref(), note_root, p)
+                                    .is_some_and(|m| m.is_match(&pattern))
+                    })
+                    .filter(|p| note_matches_fields(index.as_ref(), note_root, p, &fields))
+                    .collect::<Vec<_>>()
+            };
 
-            let note_root = Path::new(&note_root);
-            let result = search(note_root, true, true, false, &|s| {
-                s.to_str().is_some_and(|s| pattern.is_match(s))
-            })?
-            .concat();
+            if format == OutputFormat::Json {
+                let paths = result.iter().map(|p| p.as_path()).collect::<Vec<_>>();
+                return print_records(note_root, &paths);
+            }
 
             if result.is_empty() {
                 bail!("No note found in '{}'", note_root.display());
             }
 
             println!("Found notes:");
-            for entry in result {
-                println!("{}", entry.path().display());
+            for note in result {
+                println!("{}", note.display());
+                for line in note_preview(&note, preview_lines) {
+                    println!("    {}", line.dimmed());
+                }
             }
         }
-        Cli::List {
+        Command::List {
             note_root,
             category,
             sort_by_category,
             sort_by_name,
             sort_by_created_at,
             sort_by_updated_at,
+            sort_by_title,
+            sort_by_date,
+            sort,
+            reverse,
             number,
             terse,
+            long,
+            fields,
+            format,
+            preview_lines,
+            plugins,
+            scope,
+            created_after,
+            created_before,
+            modified_after,
+            modified_before,
+            lang,
+            categories,
+            owner,
+            reviewer,
+            needs_review,
         } => {
             let note_root_path = Path::new(&note_root);
+            let mut fields = fields;
+            fields.extend(review::filters(
+                owner.as_deref(),
+                reviewer.as_deref(),
+                needs_review,
+            ));
+            let dates = DateFilter::new(
+                created_after.as_deref(),
+                created_before.as_deref(),
+                modified_after.as_deref(),
+                modified_before.as_deref(),
+                journal::today(),
+            )?;
+
+            let categories = categories
+                .iter()
+                .map(|c| category_dir(note_root_path, c))
+                .collect::<Result<Vec<_>>>()?;
+            let index = NoteIndex::open(note_root_path)?;
+            let result = match (&index, category) {
+                (Some(index), true) => index.categories(note_root_path),
+                (None, true) => search(note_root_path, false, false, true, &|_| true)?
+                    .concat()
+                    .into_iter()
+                    .map(|e| e.into_path())
+                    .collect(),
+                (_, false) => notes_in_categories(index.as_ref(), note_root_path, &categories)?,
+            };
+            profile::mark("notes");
+
+            let mut notes = result
+                .iter()
+                .map(|p| p.as_path())
+                .filter(|p| scope.contains(note_root_path, p) && dates.matches(p))
+                .filter(|p| categories.is_empty() || categories.iter().any(|c| p.starts_with(c)))
+                .filter(|p| {
+                    lang.as_ref().is_none_or(|lang| {
+                        note_lang_in(index.as_ref(), note_root_path, p)
+                            .is_some_and(|l| is_lang(&l, lang))
+                    })
+                })
+                .filter(|p| note_matches_fields(index.as_ref(), note_root_path, p, &fields))
+                .collect::<Vec<_>>();
+            profile::mark("filter");
+            let mut print_tree_flag = false;
 
-            let result = if category {
-                search(note_root_path, false, false, true, &|_| true)?.concat()
-            } else {
-                search(note_root_path, true, true, false, &|_| true)?.concat()
+            let mut ranked = false;
+            if !plugins.is_empty() {
+                (notes, ranked) = plugin::apply(note_root_path, notes, &plugins)?;
+            }
+
+            // Pinned notes come first, before the list is cut to `number`
+            let pinned = pins::pinned(note_root_path, &pins::load(note_root_path)?, &notes);
+            let pin_first = |notes: &mut Vec<&Path>| notes.sort_by_key(|n| !pinned.contains(n));
+            let print_pinned = || -> Result<()> {
+                if pinned.is_empty() {
+                    return Ok(());
+                }
+                for note in &pinned {
+                    println!(
+                        "{} {}",
+                        "*".yellow(),
+                        note.strip_prefix(note_root_path)?.display()
+                    );
+                }
+                println!();
+                Ok(())
             };
 
-            let mut notes = result.iter().map(|e| e.path()).collect::<Vec<_>>();
-            let mut print_tree_flag = false;
+            // The single-key flags are shorthands of --sort
+            let keys = [
+                (sort_by_name, SortKey::Name),
+                (sort_by_created_at, SortKey::Created),
+                (sort_by_updated_at, SortKey::Updated),
+                (sort_by_title, SortKey::Title),
+                (sort_by_date, SortKey::Date),
+            ]
+            .into_iter()
+            .filter(|(flag, _)| *flag)
+            .map(|(_, key)| key)
+            .chain(sort)
+            .collect::<Vec<_>>();
+
+            if ranked {
+                // The plugins decide the order
+                pin_first(&mut notes);
+                notes.truncate(number);
+            } else if sort_by_category && (format == OutputFormat::Json || long) {
+                sort::sort_notes(
+                    index.as_ref(),
+                    note_root_path,
+                    &mut notes,
+                    &[SortKey::Category],
+                    reverse,
+                );
+                pin_first(&mut notes);
+            } else if sort_by_category {
+                print_pinned()?;
 
-            if sort_by_category {
                 // 按分类分组逻辑
                 let mut categories: HashMap<String, Vec<PathBuf>> = HashMap::new();
+                // 分类下的相对路径 -> 笔记路径
+                let mut originals: HashMap<PathBuf, &Path> = HashMap::new();
 
                 // 遍历所有笔记路径
                 for note_path in &notes {
@@ -275,6 +1407,8 @@ pub fn process_command(args: Cli) -> Result<()> {
                     // 构造分类下的相对路径 (分类名/文件名)
                     let categorized_path = Path::new(&category_name).join(file_name);
 
+                    originals.insert(categorized_path.clone(), *note_path);
+
                     // 按分类分组
                     categories
                         .entry(category_name)
@@ -288,36 +1422,43 @@ pub fn process_command(args: Cli) -> Result<()> {
 
                 // 为每个分类生成树
                 for (_, notes) in sorted_categories {
-                    print_tree(&notes);
+                    print_tree(&notes, &|p| {
+                        originals
+                            .get(p)
+                            .map(|o| note_preview(o, preview_lines))
+                            .unwrap_or_default()
+                    });
                 }
 
                 return Ok(());
-            } else if sort_by_name {
-                notes.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
-            } else if sort_by_created_at {
-                notes.sort_by(|a, b| {
-                    b.metadata()
-                        .unwrap()
-                        .created()
-                        .unwrap()
-                        .cmp(&a.metadata().unwrap().created().unwrap())
-                });
-                // 只显示最新的number个笔记
-                notes.truncate(number);
-            } else if sort_by_updated_at {
-                notes.sort_by(|a, b| {
-                    b.metadata()
-                        .unwrap()
-                        .modified()
-                        .unwrap()
-                        .cmp(&a.metadata().unwrap().modified().unwrap())
-                });
-                // 只显示最新的number个笔记
-                notes.truncate(number);
+            } else if !keys.is_empty() {
+                sort::sort_notes(index.as_ref(), note_root_path, &mut notes, &keys, reverse);
+                pin_first(&mut notes);
+                if keys[0].is_recency() {
+                    // 只显示最新的number个笔记
+                    notes.truncate(number);
+                }
+            } else if format == OutputFormat::Json || long {
+                pin_first(&mut notes);
             } else {
                 print_tree_flag = true;
+                print_pinned()?;
+            }
+
+            if format == OutputFormat::Json {
+                return print_records(note_root_path, &notes);
+            }
+            if long {
+                let records: Vec<NoteRecord> = notes
+                    .iter()
+                    .map(|p| NoteRecord::new(note_root_path, p))
+                    .collect();
+                print!("{}", record::format_table(&records));
+                return Ok(());
             }
 
+            let originals = notes.clone();
+
             if terse {
                 notes.iter_mut().for_each(|n| {
                     *n = Path::new(n.file_name().unwrap());
@@ -329,33 +1470,87 @@ pub fn process_command(args: Cli) -> Result<()> {
             }
 
             if print_tree_flag {
-                print_tree(&notes);
+                let originals: HashMap<&Path, &Path> =
+                    notes.iter().copied().zip(originals).collect();
+                print_tree(&notes, &|p| {
+                    originals
+                        .get(p)
+                        .map(|o| note_preview(o, preview_lines))
+                        .unwrap_or_default()
+                });
             } else {
-                for note in notes {
-                    println!("{}", note.display());
+                for (note, original) in notes.iter().zip(originals) {
+                    if pinned.contains(&original) && !terse {
+                        println!("{} {}", note.display(), "(pinned)".yellow());
+                    } else {
+                        println!("{}", note.display());
+                    }
+                    for line in note_preview(original, preview_lines) {
+                        println!("    {}", line.dimmed());
+                    }
                 }
             }
         }
-        Cli::Grep { note_root, pattern } => {
-            std::process::Command::new("rg")
+        Command::Grep { note_root, pattern } => {
+            process::Command::new("rg")
                 .arg("-g")
                 .arg("*.{md,typ}")
                 .arg(&pattern)
                 .arg(&note_root)
                 .status()?;
         }
-        Cli::Publish {
+        Command::Publish {
+            note_root,
+            out_dir: Some(out_dir),
+            base_url,
+            ..
+        } => {
+            let root = Path::new(&note_root);
+            let notes = scoped_notes(root, root, Scope::Active)?;
+            let pages = site::publish(root, &notes, &out_dir)?;
+            if let Some(base_url) = base_url {
+                let entries = feed::entries(root, &notes, &base_url, feed::DEFAULT_LIMIT);
+                let xml = feed::atom(&site::site_title(root), &base_url, None, &entries);
+                let path = out_dir.join("feed.xml");
+                fs::write(&path, xml)
+                    .with_context(|| format!("Failed to write '{}'", path.display()))?;
+            }
+            println!(
+                "Published {} notes to '{}'",
+                pages.len(),
+                out_dir.join("index.html").display()
+            );
+        }
+        Command::Feed {
+            base_url,
+            note_root,
+            limit,
+            author,
+            output,
+        } => {
+            let root = Path::new(&note_root);
+            let notes = scoped_notes(root, root, Scope::Active)?;
+            let entries = feed::entries(root, &notes, &base_url, limit);
+            let xml = feed::atom(
+                &site::site_title(root),
+                &base_url,
+                author.as_deref(),
+                &entries,
+            );
+            match output {
+                Some(output) => fs::write(&output, xml)
+                    .with_context(|| format!("Failed to write '{}'", output.display()))?,
+                None => print!("{}", xml),
+            }
+        }
+        Command::Publish {
             note_path,
             note_root,
             output_type,
+            out_dir: None,
+            ..
         } => {
-            let note_path = if let Some(s) = note_path {
-                s
-            } else {
-                current_dir()?.into_os_string()
-            };
-
-            let note_dir = find_note_dir(&note_path, &note_root)?;
+            let note_dir = current::note_or_current(note_path.as_deref(), &note_root)?;
             let note_path = note_dir.main_file_path()?;
             let note_type = note_path.note_type()?;
 
@@ -364,9 +1559,23 @@ pub fn process_command(args: Cli) -> Result<()> {
                     println!("No need to publish markdown note");
                     return Ok(());
                 }
+                NoteType::Org => {
+                    println!("No need to publish org note");
+                    return Ok(());
+                }
                 NoteType::Typ => {}
+                NoteType::Custom(ext) => bail!("'{}' notes cannot be published", ext),
             }
 
+            let settings = ExportSettings::read(&note_path)?;
+            let output_type = match output_type {
+                Some(output_type) => output_type,
+                None => settings
+                    .typst_format()
+                    .with_context(|| format!("Invalid export format in '{}'", note_path.display()))?
+                    .to_string(),
+            };
+
             let mut publish_name = note_dir.file_stem().unwrap().to_os_string();
             let now = chrono::Local::now();
             publish_name.push(now.format("-%Y-%m-%d.").to_string());
@@ -374,235 +1583,392 @@ pub fn process_command(args: Cli) -> Result<()> {
 
             let publish_path = PathBuf::from(note_root).join("publish").join(publish_name);
 
-            Command::new("typst")
+            process::Command::new("typst")
                 .arg("compile")
+                .args(settings.typst_inputs())
                 .arg(note_path)
                 .arg(publish_path)
                 .arg("--features")
                 .arg("html")
                 .status()?;
         }
-    }
+        Command::Export {
+            target,
+            note_root,
+            output_dir,
+            bibliography_order,
+            scope,
+        } => {
+            let root = Path::new(&note_root);
+            let notes = match target {
+                None => scoped_notes(root, root, scope)?,
+                Some(target) => {
+                    match [PathBuf::from(&target), root.join(&target)]
+                        .into_iter()
+                        .find(|p| p.is_category())
+                    {
+                        Some(category) => scoped_notes(root, &category, scope)?,
+                        None => vec![find_note_dir(&target, &note_root)?],
+                    }
+                }
+            };
+            if notes.is_empty() {
+                bail!("No note to export");
+            }
 
-    Ok(())
-}
+            let output_dir = output_dir.unwrap_or_else(|| root.join("export"));
+            export::export(root, &notes, &output_dir, &bibliography_order)?;
+        }
+        Command::Bib { command } => cite::process_bib_command(command)?,
+        Command::Sidecar {
+            note_root,
+            output_dir,
+            remove,
+        } => {
+            let root = Path::new(&note_root);
+            let output_dir = output_dir.unwrap_or_else(|| root.join(sidecar::DEFAULT_DIR));
+            if remove {
+                let removed = sidecar::remove_sidecars(&output_dir, &HashSet::new())?;
+                println!("Removed {} sidecar(s)", removed);
+            } else {
+                let report = sidecar::sync_sidecars(root, &output_dir)?;
+                println!(
+                    "{} sidecar(s) written, {} unchanged, {} removed in '{}'",
+                    report.written,
+                    report.unchanged,
+                    report.removed,
+                    output_dir.display()
+                );
+            }
+        }
+        Command::Backlinks {
+            note_path,
+            note_root,
+        } => {
+            let note = find_note_dir(&note_path, &note_root)?;
+            let index = LinkIndex::new(Path::new(&note_root))?;
 
-/* `New` command helper */
+            for source in index.backlinks(&note) {
+                println!("{}", source.display());
+            }
+        }
+        Command::Sync {
+            note_root,
+            message,
+            no_push,
+        } => sync::sync(Path::new(&note_root), message, !no_push)?,
+        Command::Pack {
+            note_paths,
+            note_root,
+            output,
+        } => {
+            let notes = note_paths
+                .iter()
+                .map(|p| find_note_dir(p, &note_root))
+                .collect::<Result<Vec<_>>>()?;
 
-#[derive(Debug, Deserialize)]
-#[serde(untagged)]
-enum PathContent {
-    Directory(HashMap<String, PathContent>), // 子目录
-    File(String),                            // 文件内容
-}
+            let manifest = pack::pack(Path::new(&note_root), &notes, Path::new(&output))?;
 
-#[derive(Debug, Deserialize)]
-struct NoteTemplate {
-    paths: HashMap<String, PathContent>, // 顶层路径
-    #[serde(rename = "main.typ")]
-    main_typ: Option<String>,
-    #[serde(rename = "main.md")]
-    main_md: Option<String>,
-}
+            println!(
+                "Packed {} note(s) into '{}'",
+                manifest.notes.len(),
+                output.display()
+            );
+        }
+        Command::Backup {
+            command: Some(command),
+            ..
+        } => backup::process_backup_command(command)?,
+        Command::Backup {
+            command: None,
+            note_root,
+            output,
+            keep,
+        } => {
+            let note_root = Path::new(&note_root);
+            let output = match output {
+                Some(output) => output,
+                None => backup::default_dir()?,
+            };
+            let archive = if output.to_string_lossy().ends_with(backup::BACKUP_EXT) {
+                output
+            } else {
+                output.join(backup::archive_name(note_root, chrono::Local::now()))
+            };
 
-impl Default for NoteTemplate {
-    fn default() -> Self {
-        let mut paths = HashMap::new();
+            let files = backup::create(note_root, &archive)?;
+            println!("{}", backup::summary(&archive, files));
+            if let Some(keep) = keep {
+                let dir = archive.parent().unwrap_or(Path::new("."));
+                for old in backup::prune(dir, note_root, keep)? {
+                    println!("Deleted old backup '{}'", old.display());
+                }
+            }
+        }
+        Command::Unpack {
+            pack_path,
+            note_root,
+        } => {
+            let manifest = pack::unpack(Path::new(&pack_path), Path::new(&note_root))?;
 
-        paths.insert("images".to_string(), PathContent::Directory(HashMap::new()));
-        paths.insert(
-            "chapter".to_string(),
-            PathContent::Directory(HashMap::new()),
-        );
-        paths.insert(
-            "bibliography".to_string(),
-            PathContent::Directory(HashMap::new()),
-        );
+            println!("Unpacked notes:");
+            for note in manifest.notes {
+                println!("{}", note.path.display());
+            }
+        }
+        Command::Review {
+            command: Some(command),
+            ..
+        } => review::process_review_command(command)?,
+        Command::Review {
+            command: None,
+            note_root,
+            list,
+            edit,
+        } => srs::review(Path::new(&note_root), list, &edit)?,
+        Command::Import { command } => import::process_import_command(command)?,
+        Command::Tag { command } => tag::process_tag_command(command)?,
+        Command::Snippet { command } => snippet::process_snippet_command(command)?,
+        Command::Template { command } => template::process_template_command(command)?,
+        Command::Zk { command } => zk::process_zk_command(command)?,
+        Command::Config { command } => config::process_config_command(command)?,
+        Command::Vault { command } => config::process_vault_command(command)?,
+        Command::Ai { command } => ai::process_ai_command(command)?,
+        Command::Ask {
+            question,
+            note_root,
+            provider,
+            model,
+        } => {
+            let notes = all_notes(Path::new(&note_root))?;
 
-        NoteTemplate {
-            paths,
-            main_typ: None,
-            main_md: None,
+            let answer = llm::ask(&notes, &question, &LlmOptions { provider, model })?;
+            println!("{}", answer.text);
+            if !answer.sources.is_empty() {
+                let sources: Vec<String> = answer.sources.iter().map(|n| n.note_name()).collect();
+                println!("\n{}", format!("Sources: {}", sources.join(", ")).dimmed());
+            }
+        }
+        Command::Summarize {
+            note_path,
+            note_root,
+            provider,
+            model,
+        } => {
+            let note = find_note_dir(&note_path, &note_root)?;
+            let answer = llm::summarize(&note, &LlmOptions { provider, model })?;
+            println!("{}", answer.text);
         }
     }
-}
-
-fn find_note_dir(note_path_str: &OsStr, note_root: &OsStr) -> Result<PathBuf> {
-    let mut note_path = Path::new(note_path_str).to_path_buf();
 
-    if note_path.is_note_name() {
-        // note_path是note name而非路径
-        let note_root = Path::new(&note_root);
+    Ok(())
+}
 
-        let mut result = search(note_root, true, true, false, &|s| {
-            s.eq_ignore_ascii_case(note_path_str)
-        })?
-        .concat();
+/// Create the note of a `noxe new` command, with its hooks. Returns the path of the note,
+/// which starts with a timestamp ID when asked for one.
+pub(crate) fn new_note(command: Command) -> Result<PathBuf> {
+    let Command::New {
+        note_path,
+        note_author,
+        note_keywords,
+        note_type,
+        single_file,
+        note_template,
+        template,
+        template_dir,
+        vars,
+        context,
+        context_repo,
+        note_with_metadata,
+        id,
+    } = command
+    else {
+        bail!("Not a `new` command");
+    };
 
-        note_path = match result.len() {
-            0 => bail!("No note found in '{}'", note_root.display()),
-            1 => result.pop().unwrap().path().to_path_buf(),
-            _ => prompt_user_choice(&result)?.path().to_path_buf(),
-        };
+    let note_path = if id {
+        zk::with_timestamp_id(Path::new(&note_path), chrono::Local::now().naive_local())
+    } else {
+        PathBuf::from(&note_path)
     };
+    let template = note_template_of(template, template_dir, note_template)?;
+    let context = context::for_template(&template, &context, context_repo.as_deref());
 
+    create_note_with_hooks(
+        &note_path,
+        &NewNote {
+            note_type,
+            single_file,
+            author: note_author,
+            keywords: note_keywords,
+            template,
+            with_metadata: note_with_metadata,
+            vars: vars.into_iter().collect(),
+            context,
+        },
+    )?;
     Ok(note_path)
 }
 
-fn create_note_template(note_path: &Path, template: &NoteTemplate) -> Result<()> {
-    // 递归创建目录和文件
-    fn create_paths(dir: &Path, content: &HashMap<String, PathContent>) -> Result<()> {
-        for (name, path_content) in content {
-            let current_path = dir.join(name);
+/// Create a note between the `pre_new` and `post_new` hooks.
+fn create_note_with_hooks(note_path: &Path, options: &NewNote) -> Result<PathBuf> {
+    let metadata = NoteMetadata {
+        title: Some(note_path.note_name()),
+        author: options.author.clone(),
+        keywords: options.keywords.clone(),
+        ..Default::default()
+    };
+    hooks::run(Hook::PreNew, note_path, Some(&metadata))?;
+    let main_file = create_note(note_path, options)?;
+    hooks::run(Hook::PostNew, note_path, None)?;
+    Ok(main_file)
+}
 
-            match path_content {
-                PathContent::Directory(sub_content) => {
-                    fs::create_dir_all(&current_path).with_context(|| {
-                        format!("Failed to create directory '{}'", current_path.display())
-                    })?;
-                    create_paths(&current_path, sub_content)?;
-                }
-                PathContent::File(file_content) => {
-                    if let Some(parent) = current_path.parent() {
-                        fs::create_dir_all(parent).with_context(|| {
-                            format!("Failed to create parent directory '{}'", parent.display())
-                        })?;
-                    }
-                    let mut file = fs::OpenOptions::new()
-                        .create(true)
-                        .append(true)
-                        .open(&current_path)
-                        .with_context(|| {
-                            format!("Failed to create file '{}'", current_path.display())
-                        })?;
-                    file.write_all(file_content.as_bytes()).with_context(|| {
-                        format!("Failed to write to file '{}'", current_path.display())
-                    })?;
-                }
-            }
-        }
-        Ok(())
+/// Ask a yes/no question on the terminal, no being the default.
+fn confirm(question: &str) -> Result<bool> {
+    if !io::stdin().is_terminal() {
+        bail!("Not a terminal, pass --yes to confirm");
     }
+    eprint!("{} [y/N] ", question);
+    let mut input = String::new();
+    io::stdin()
+        .read_line(&mut input)
+        .with_context(|| "Failed to read user input")?;
+    Ok(matches!(input.trim(), "y" | "Y" | "yes"))
+}
 
-    create_paths(note_path, &template.paths)?;
+/// The template of a new note: a template of the registry named on the command line wins over
+/// the template file of the config.
+fn note_template_of(
+    template: Option<String>,
+    template_dir: Option<PathBuf>,
+    note_template: Option<OsString>,
+) -> Result<NoteTemplate> {
+    Ok(match (template, note_template) {
+        (Some(name), _) => TemplateStore::new(template_dir)?.load(&name)?,
+        (None, Some(path)) => load_note_template(&path)?,
+        (None, None) => Default::default(),
+    })
+}
 
-    Ok(())
+/// Whether a name may resolve to an archived or trashed note, set by `--include-archived`.
+static INCLUDE_ARCHIVED: AtomicBool = AtomicBool::new(false);
+
+pub fn set_include_archived(include: bool) {
+    INCLUDE_ARCHIVED.store(include, Ordering::Relaxed);
 }
 
-fn load_note_template(file_path: &OsStr) -> Result<NoteTemplate> {
-    let content = fs::read_to_string(file_path)
-        .with_context(|| format!("Failed to read template file '{}'", file_path.display()))?;
-    let template: NoteTemplate = serde_yml::from_str(&content)
-        .with_context(|| format!("Failed to parse template file '{}'", file_path.display()))?;
-    Ok(template)
+pub(crate) fn find_note_dir(note_path_str: &OsStr, note_root: &OsStr) -> Result<PathBuf> {
+    let root = Path::new(note_root);
+    match resolve_note(note_path_str, note_root)? {
+        Resolved::Note(note_path) => Ok(note_path),
+        Resolved::Hidden(note_path) if archive::is_trashed(root, &note_path) => bail!(
+            "No note found in '{}', found in trash: {}; use --include-archived or move it out of {}/",
+            root.display(),
+            note_path.strip_prefix(root).unwrap_or(&note_path).display(),
+            archive::TRASH_DIR
+        ),
+        Resolved::Hidden(note_path) => bail!(
+            "No note found in '{}', found in archive: {}; use --include-archived or `noxe archive --unarchive`",
+            root.display(),
+            note_path.strip_prefix(root).unwrap_or(&note_path).display()
+        ),
+        Resolved::None => bail!("No note found in '{}'", root.display()),
+    }
 }
 
-fn metadata(
-    note_name: &str,
-    note_author: Option<&String>,
-    note_type: NoteType,
-    keywords: &[String],
-) -> String {
-    let keywords = keywords.join(", ");
-    let now = chrono::Local::now();
-
-    match note_type {
-        NoteType::Md => {
-            let mut md_metadata = String::from("---\n");
-            md_metadata.push_str(&format!("title: \"{}\"\n", note_name));
-            if let Some(author) = note_author {
-                md_metadata.push_str(&format!("author: \"{}\"\n", author));
-            }
-            if !keywords.is_empty() {
-                md_metadata.push_str(&format!("keywords: [{}]\n", keywords));
-            }
-            md_metadata.push_str(&format!(
-                "date: \"{}\"\n---\n\n",
-                now.format("%Y-%m-%d %H:%M:%S")
-            ));
-            md_metadata
-        }
-        NoteType::Typ => {
-            let mut typ_metadata = format!("#set document(title: \"{}\"", note_name);
-            if let Some(author) = note_author {
-                typ_metadata.push_str(&format!(", author: \"{}\"", author));
-            }
-            if !keywords.is_empty() {
-                typ_metadata.push_str(&format!(", keywords: ({})", keywords));
-            }
-            typ_metadata.push_str(&format!(
-                ", date: datetime(year: {}, month: {}, day: {}, hour: {}, minute: {}, second: {}))\n\n",
-                now.year(),
-                now.month(),
-                now.day(),
-                now.hour(),
-                now.minute(),
-                now.second()
-            ));
-            typ_metadata
-        }
+/// Like [`find_note_dir`], but a name matching no note is not an error.
+pub(crate) fn lookup_note(note_path_str: &OsStr, note_root: &OsStr) -> Result<Option<PathBuf>> {
+    match resolve_note(note_path_str, note_root)? {
+        Resolved::Note(note_path) => Ok(Some(note_path)),
+        Resolved::Hidden(_) | Resolved::None => Ok(None),
     }
 }
 
-fn search(
-    note_root: &Path,
-    search_filenote: bool,
-    search_dirnote: bool,
-    search_category: bool,
-    eq: &dyn Fn(&OsStr) -> bool,
-) -> Result<[Vec<DirEntry>; 3]> {
-    let mut filenotes = Vec::new();
-    let mut dirnotes = Vec::new();
-    let mut categories = Vec::new();
-
-    let mut handle_filenote = if search_filenote {
-        Some(|entry: DirEntry| {
-            if eq(entry.file_name()) {
-                filenotes.push(entry);
-            }
-            Ok(())
-        })
-    } else {
-        None
-    };
-    let mut handle_dirnote = if search_dirnote {
-        Some(|entry: DirEntry| {
-            if eq(entry.file_name()) {
-                dirnotes.push(entry);
-            }
-            Ok(())
-        })
-    } else {
-        None
-    };
-    let mut handle_category = if search_category {
-        Some(|entry: DirEntry| {
-            if eq(entry.file_name()) {
-                categories.push(entry);
-            }
-            Ok(())
-        })
-    } else {
-        None
-    };
+enum Resolved {
+    Note(PathBuf),
+    /// The name only matches a note of the archive or the trash
+    Hidden(PathBuf),
+    None,
+}
 
-    handle_notes(
-        note_root,
-        handle_filenote
-            .as_mut()
-            .map(|f| f as &mut dyn FnMut(DirEntry) -> Result<()>),
-        handle_dirnote
-            .as_mut()
-            .map(|f| f as &mut dyn FnMut(DirEntry) -> Result<()>),
-        handle_category
-            .as_mut()
-            .map(|f| f as &mut dyn FnMut(DirEntry) -> Result<()>),
-    )?;
+fn resolve_note(note_path_str: &OsStr, note_root: &OsStr) -> Result<Resolved> {
+    let note_path = Path::new(note_path_str);
+    if !note_path.is_note_name() {
+        return Ok(Resolved::Note(note_path.to_path_buf()));
+    }
 
-    Ok([filenotes, dirnotes, categories])
+    // note_path是note name而非路径
+    let note_root = Path::new(&note_root);
+
+    // An encrypted filenote is also found by its name before encryption
+    let encrypted = crypt::encrypted_path(note_path).into_os_string();
+    // And a note with a timestamp ID by the ID alone
+    let id = note_path_str
+        .to_str()
+        .filter(|s| zk::timestamp_id(s) == Some(s));
+    let mut result = search(note_root, true, true, false, &|s| {
+        s.eq_ignore_ascii_case(note_path_str)
+            || s.eq_ignore_ascii_case(&encrypted)
+            || id.is_some_and(|id| zk::timestamp_id(&s.to_string_lossy()) == Some(id))
+    })?
+    .concat();
+    // Archived and trashed notes are only picked when there is no other, and when asked for
+    if result
+        .iter()
+        .any(|e| Scope::Active.contains(note_root, e.path()))
+    {
+        result.retain(|e| Scope::Active.contains(note_root, e.path()));
+    } else if !INCLUDE_ARCHIVED.load(Ordering::Relaxed)
+        && let Some(hidden) = result.first()
+    {
+        return Ok(Resolved::Hidden(hidden.path().to_path_buf()));
+    }
+
+    Ok(match result.len() {
+        0 => Resolved::None,
+        1 => Resolved::Note(result.pop().unwrap().into_path()),
+        _ => Resolved::Note(prompt_user_choice(note_root, &result)?.path().to_path_buf()),
+    })
+}
+
+/// The content of a note as printed by `show`, read up to `limit` bytes, and the notice to
+/// print when the note is longer.
+fn show_content(
+    main_file: &Path,
+    no_frontmatter: bool,
+    metadata_only: bool,
+    limit: u64,
+) -> Result<(String, Option<String>)> {
+    if metadata_only {
+        let metadata = read_metadata(main_file)?;
+        return Ok((
+            format!("{}\n", serde_json::to_string_pretty(&metadata)?),
+            None,
+        ));
+    }
+
+    let text = NoteText::read_head(main_file, limit)?;
+    let notice = text
+        .truncated
+        .map(|total| truncation_notice(text.content.len(), total));
+    if no_frontmatter {
+        return Ok((
+            text.body().trim_start_matches(['\r', '\n']).to_string(),
+            notice,
+        ));
+    }
+    Ok((text.content, notice))
 }
 
-fn prompt_user_choice(candidates: &[DirEntry]) -> Result<DirEntry> {
+/// Ask the user which of the candidates to use, with the fuzzy picker when run in a terminal,
+/// else with a numbered list read from stdin.
+fn prompt_user_choice(note_root: &Path, candidates: &[DirEntry]) -> Result<DirEntry> {
+    if io::stdin().is_terminal() && io::stdout().is_terminal() {
+        let paths: Vec<PathBuf> = candidates.iter().map(|c| c.path().to_path_buf()).collect();
+        return Ok(candidates[picker::pick(note_root, &paths)?].clone());
+    }
+
     eprintln!("Multiple matches found:");
     for (i, candidate) in candidates.iter().enumerate() {
         eprintln!("{}. {}", i + 1, candidate.path().display());
@@ -625,8 +1991,39 @@ fn prompt_user_choice(candidates: &[DirEntry]) -> Result<DirEntry> {
     Ok(candidates[choice - 1].clone())
 }
 
-fn exec_with(note_path: &Path, args: &[OsString]) -> Result<()> {
-    let mut cmd = Command::new(&args[0]);
+fn print_records(note_root: &Path, paths: &[&Path]) -> Result<()> {
+    let records = paths
+        .iter()
+        .map(|p| NoteRecord::new(note_root, p))
+        .collect::<Vec<_>>();
+
+    println!("{}", serde_json::to_string_pretty(&records)?);
+
+    Ok(())
+}
+
+/// `noxe` without a command.
+pub fn first_run() -> Result<()> {
+    setup::first_run()
+}
+
+/// Split a command of the config on spaces, as the command options of the CLI are.
+pub(crate) fn split_command(command: &str) -> Vec<OsString> {
+    command.split_whitespace().map(OsString::from).collect()
+}
+
+/// Check whether a program can be found in `PATH`.
+pub(crate) fn in_path(program: &str) -> bool {
+    std::env::var_os("PATH").is_some_and(|paths| {
+        std::env::split_paths(&paths).any(|dir| {
+            let path = dir.join(program);
+            path.is_file() || path.with_extension("exe").is_file()
+        })
+    })
+}
+
+pub(crate) fn exec_with(note_path: &Path, args: &[OsString]) -> Result<()> {
+    let mut cmd = process::Command::new(&args[0]);
     for arg in &args[1..] {
         cmd.arg(arg);
     }
@@ -639,40 +2036,6 @@ fn exec_with(note_path: &Path, args: &[OsString]) -> Result<()> {
     Ok(())
 }
 
-fn handle_notes(
-    root: &Path,
-    mut handle_filenote: Option<&mut dyn FnMut(DirEntry) -> Result<()>>,
-    mut handle_dirnote: Option<&mut dyn FnMut(DirEntry) -> Result<()>>,
-    mut handle_category: Option<&mut dyn FnMut(DirEntry) -> Result<()>>,
-) -> Result<()> {
-    let mut it = WalkBuilder::new(root).build();
-
-    it.next();
-    loop {
-        let entry = match it.next() {
-            Some(entry) => entry,
-            None => break,
-        }?;
-
-        if let Some(handle) = handle_filenote.as_mut()
-            && entry.path().is_filenote()
-        {
-            handle(entry)?;
-        } else if let Some(handle) = handle_dirnote.as_mut()
-            && entry.path().is_dirnote()
-        {
-            handle(entry)?;
-            it.skip_current_dir();
-        } else if let Some(handle) = handle_category.as_mut()
-            && entry.path().is_category()
-        {
-            handle(entry)?;
-        }
-    }
-
-    Ok(())
-}
-
 // fn print_filenote(entry: &DirEntry) {
 //     println!("{}", entry.file_name().display());
 // }
@@ -697,7 +2060,20 @@ fn handle_notes(
 //     println!("{}", entry.path().display());
 // }
 
-fn print_tree(paths: &[impl AsRef<Path>]) {
+/// Read the first `n` lines of a note's body for previewing in search/list output.
+fn note_preview(note_path: &Path, n: usize) -> Vec<String> {
+    if n == 0 {
+        return Vec::new();
+    }
+
+    note_path
+        .main_file_path()
+        .and_then(|p| read_preview_lines(&p, n))
+        .unwrap_or_default()
+}
+
+/// Print paths as a tree, with extra (dimmed) lines printed under each leaf.
+fn print_tree(paths: &[impl AsRef<Path>], leaf_lines: &dyn Fn(&Path) -> Vec<String>) {
     #[derive(Debug)]
     struct PathNode {
         children: BTreeMap<String, PathNode>,
@@ -738,95 +2114,160 @@ fn print_tree(paths: &[impl AsRef<Path>]) {
     }
 
     fn print_subtree(
-        node_map: &BTreeMap<String, PathNode>,
+        node: &PathNode,
+        path: &Path,
         prefix: &str,
         is_last: bool,
         node_name: Option<&str>,
+        leaf_lines: &dyn Fn(&Path) -> Vec<String>,
     ) {
-        if let Some(name) = node_name {
-            let branch = if is_last { "└── " } else { "├── " };
-            println!("{}{}{}", prefix, branch, name);
-        }
-
         let new_prefix = if is_last {
             format!("{}    ", prefix)
         } else {
             format!("{}│   ", prefix)
         };
 
-        let len = node_map.len();
-        for (i, (child_name, child_node)) in node_map.iter().enumerate() {
+        if let Some(name) = node_name {
+            let branch = if is_last { "└── " } else { "├── " };
+            println!("{}{}{}", prefix, branch, name);
+
+            if node.is_file {
+                let child_prefix = if node.children.is_empty() {
+                    format!("{}  ", new_prefix)
+                } else {
+                    format!("{}│   ", new_prefix)
+                };
+                for line in leaf_lines(path) {
+                    println!("{}{}", child_prefix, line.dimmed());
+                }
+            }
+        }
+
+        let len = node.children.len();
+        for (i, (child_name, child_node)) in node.children.iter().enumerate() {
             let child_is_last = i == (len - 1);
             print_subtree(
-                &child_node.children,
+                child_node,
+                &path.join(child_name),
                 &new_prefix,
                 child_is_last,
                 Some(child_name),
+                leaf_lines,
             );
         }
     }
 
-    let mut root = BTreeMap::new();
+    let mut root = PathNode::new();
 
     // 先把所有路径插入 tree
     for p in paths {
-        add_path(&mut root, p.as_ref());
+        add_path(&mut root.children, p.as_ref());
     }
 
     // 再写一个递归函数去打印
-    print_subtree(&root, "", true, None);
+    print_subtree(&root, Path::new(""), "", true, None, leaf_lines);
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::cli::{Cli, NoteType};
-    use std::fs::{self, File};
-    use std::io::Write;
+    use crate::cli::{Command, NoteType, OutputFormat};
+    use std::fs;
     use tempfile::tempdir;
 
-    /// Helper to build Cli::New arguments quickly
-    fn cli_new_args(note_path: &str, single_file: bool, note_type: NoteType) -> Cli {
-        Cli::New {
+    /// Helper to build Command::New arguments quickly
+    fn cli_new_args(note_path: &str, single_file: bool, note_type: NoteType) -> Command {
+        Command::New {
             note_path: note_path.to_string().into(),
             note_author: Some("TestAuthor".to_string()),
             note_keywords: ["keyword1".to_string(), "keyword2".to_string()].into(),
             note_type,
             single_file,
             note_template: None,
+            template: None,
+            template_dir: None,
+            vars: vec![],
+            context: vec![],
+            context_repo: None,
             note_with_metadata: true,
+            id: false,
         }
     }
 
-    /// Helper to build Cli::Preview arguments quickly
-    fn cli_preview_args(note_path: &str, note_root: &str) -> Cli {
-        Cli::Preview {
+    /// Helper to build Command::Preview arguments quickly
+    fn cli_preview_args(note_path: &str, note_root: &str) -> Command {
+        Command::Preview {
             note_path: Some(note_path.to_string().into()),
             note_root: note_root.to_string().into(),
             preview_typst: vec![],
             preview_markdown: vec![],
+            preview_org: vec![],
+            serve: false,
+            port: 0,
+            pdf: false,
+            watch: false,
+            pdf_viewer: vec![],
         }
     }
 
-    /// Helper to build Cli::Search arguments quickly
-    fn cli_search_args(query: &str, note_root: &str) -> Cli {
-        Cli::Search {
-            query: query.to_string(),
+    /// Helper to build Command::Search arguments quickly
+    fn cli_search_args(query: &str, note_root: &str) -> Command {
+        Command::Search {
+            query: Some(query.to_string()),
+            saved: None,
+            save: None,
+            near: vec![],
+            within: 3,
+            semantic: false,
+            limit: 10,
+            provider: "openai".to_string(),
+            embedding_model: None,
             note_root: note_root.to_string().into(),
+            fields: vec![],
+            metadata: false,
+            query_lang: false,
+            format: OutputFormat::Plain,
+            preview_lines: 0,
+            scope: Scope::Active,
+            created_after: None,
+            created_before: None,
+            modified_after: None,
+            modified_before: None,
+            lang: None,
+            categories: vec![],
         }
     }
 
-    /// Helper to build Cli::List arguments quickly
-    fn cli_list_args(note_root: &str) -> Cli {
-        Cli::List {
+    /// Helper to build Command::List arguments quickly
+    fn cli_list_args(note_root: &str) -> Command {
+        Command::List {
             note_root: note_root.to_string().into(),
             category: false,
             sort_by_category: true,
             sort_by_name: false,
             sort_by_created_at: false,
             sort_by_updated_at: false,
+            sort_by_title: false,
+            sort_by_date: false,
+            sort: vec![],
+            reverse: false,
             number: 10,
             terse: false,
+            long: false,
+            fields: vec![],
+            format: OutputFormat::Plain,
+            preview_lines: 0,
+            plugins: vec![],
+            scope: Scope::Active,
+            created_after: None,
+            created_before: None,
+            modified_after: None,
+            modified_before: None,
+            lang: None,
+            categories: vec![],
+            owner: None,
+            reviewer: None,
+            needs_review: false,
         }
     }
 
@@ -968,6 +2409,15 @@ mod tests {
         let args = cli_search_args("SearchedNote", note_root.to_str().unwrap());
         let result = process_command(args);
         assert!(result.is_ok(), "Failed to search notes");
+
+        // no match is an error, except for JSON output, which is an empty list
+        let args = cli_search_args("Missing", note_root.to_str().unwrap());
+        assert!(process_command(args).is_err());
+        let mut args = cli_search_args("Missing", note_root.to_str().unwrap());
+        if let Command::Search { format, .. } = &mut args {
+            *format = OutputFormat::Json;
+        }
+        assert!(process_command(args).is_ok());
     }
 
     #[test]
@@ -1006,169 +2456,6 @@ mod tests {
         assert!(result.is_ok() || result.is_err());
     }
 
-    #[test]
-    fn test_create_note_template_function() {
-        let tmp_dir = tempdir().unwrap();
-        let note_root = tmp_dir.path().join("templated_note");
-
-        let mut sub_paths = HashMap::new();
-        sub_paths.insert(
-            "subfile.md".to_string(),
-            PathContent::File("content".to_string()),
-        );
-        let mut example_paths = HashMap::new();
-        example_paths.insert("subdir".to_string(), PathContent::Directory(sub_paths));
-
-        let template = NoteTemplate {
-            paths: example_paths,
-            main_typ: Some("Typ content".into()),
-            main_md: Some("Md content".into()),
-        };
-
-        let result = create_note_template(&note_root, &template);
-        assert!(result.is_ok(), "Failed to create note template");
-
-        let subdir = note_root.join("subdir");
-        let subfile = subdir.join("subfile.md");
-        assert!(subdir.is_dir(), "Subdirectory was not created");
-        assert!(subfile.is_file(), "Subfile.md was not created");
-        let content = fs::read_to_string(subfile).unwrap();
-        assert_eq!(&content, "content", "Wrong content in subfile.md");
-    }
-
-    #[test]
-    fn test_load_note_template_function() {
-        // We'll write a sample YAML file to a temp location, then load it.
-        let tmp_dir = tempdir().unwrap();
-        let template_file = tmp_dir.path().join("template.yml");
-
-        // Example template in YAML
-        let yaml_content = r#"
-paths:
-  images: {}
-  chapter: {}
-  bibliography: {}
-"main.typ": "Typ content here"
-"main.md": "Markdown content here"
-"#;
-
-        {
-            let mut f = File::create(&template_file).unwrap();
-            writeln!(f, "{}", yaml_content).unwrap();
-        }
-
-        let loaded_template = load_note_template(template_file.as_os_str());
-        assert!(loaded_template.is_ok(), "Failed to load note template");
-        let loaded_template = loaded_template.unwrap();
-
-        assert!(
-            loaded_template.paths.contains_key("images"),
-            "images folder missing"
-        );
-        assert!(
-            loaded_template.paths.contains_key("chapter"),
-            "chapter folder missing"
-        );
-        assert!(
-            loaded_template.paths.contains_key("bibliography"),
-            "bibliography folder missing"
-        );
-        assert_eq!(
-            loaded_template.main_typ.as_deref(),
-            Some("Typ content here"),
-            "Wrong main_typ"
-        );
-        assert_eq!(
-            loaded_template.main_md.as_deref(),
-            Some("Markdown content here"),
-            "Wrong main_md"
-        );
-    }
-
-    #[test]
-    fn test_note_trait_methods() {
-        let tmp_dir = tempdir().unwrap();
-
-        // Test filenote
-        let filenote_md = tmp_dir.path().join("note.md");
-        fs::File::create(&filenote_md).unwrap();
-        assert!(filenote_md.is_filenote());
-        assert!(!filenote_md.is_dirnote());
-        assert!(!filenote_md.is_category());
-
-        let filenote_typ = tmp_dir.path().join("note.typ");
-        fs::File::create(&filenote_typ).unwrap();
-        assert!(filenote_typ.is_filenote());
-        assert!(!filenote_typ.is_dirnote());
-        assert!(!filenote_typ.is_category());
-
-        // Test dirnote
-        let dirnote = tmp_dir.path().join("dirnote");
-        fs::create_dir(&dirnote).unwrap();
-        fs::File::create(dirnote.join("main.md")).unwrap();
-        assert!(dirnote.is_dirnote());
-        assert!(!dirnote.is_filenote());
-        assert!(!dirnote.is_category());
-
-        // Test category
-        let category = tmp_dir.path().join("category");
-        fs::create_dir(&category).unwrap();
-        assert!(category.is_category());
-        assert!(!category.is_filenote());
-        assert!(!category.is_dirnote());
-    }
-
-    #[test]
-    fn test_metadata_generation() {
-        let note_name = "TestNote";
-        let author = Some("AuthorName".to_string());
-        let keywords = ["kw1".to_string(), "kw2".to_string()];
-
-        // Test Markdown metadata
-        let md_meta = metadata(note_name, author.as_ref(), NoteType::Md, &keywords);
-        assert!(md_meta.contains("title: \"TestNote\""));
-        assert!(md_meta.contains("author: \"AuthorName\""));
-        assert!(md_meta.contains("keywords: [kw1, kw2]"));
-        assert!(md_meta.starts_with("---\n"));
-
-        // Test Typst metadata
-        let typ_meta = metadata(note_name, author.as_ref(), NoteType::Typ, &keywords);
-        assert!(typ_meta.contains("#set document(title: \"TestNote\""));
-        assert!(typ_meta.contains("author: \"AuthorName\""));
-        assert!(typ_meta.contains("keywords: (kw1, kw2)"));
-        assert!(typ_meta.contains("date: datetime"));
-    }
-
-    #[test]
-    fn test_search_function() {
-        let tmp_dir = tempdir().unwrap();
-        let note_root = tmp_dir.path();
-
-        // Create test data
-        let filenote = note_root.join("file.md");
-        fs::File::create(&filenote).unwrap();
-
-        let dirnote = note_root.join("dirnote");
-        fs::create_dir(&dirnote).unwrap();
-        fs::File::create(dirnote.join("main.md")).unwrap();
-
-        let category = note_root.join("category");
-        fs::create_dir(&category).unwrap();
-
-        // Search filenotes
-        let [filenotes, _, _] = search(note_root, true, false, false, &|s| s == "file.md").unwrap();
-        assert_eq!(filenotes.len(), 1);
-
-        // Search dirnotes
-        let [_, dirnotes, _] = search(note_root, false, true, false, &|s| s == "dirnote").unwrap();
-        assert_eq!(dirnotes.len(), 1);
-
-        // Search categories
-        let [_, _, categories] =
-            search(note_root, false, false, true, &|s| s == "category").unwrap();
-        assert_eq!(categories.len(), 1);
-    }
-
     #[test]
     fn test_tree_printing() {
         let paths = vec![
@@ -1178,7 +2465,7 @@ paths:
         ];
 
         let result = std::panic::catch_unwind(|| {
-            print_tree(&paths);
+            print_tree(&paths, &|_| Vec::new());
         });
         assert!(result.is_ok());
     }
@@ -1189,11 +2476,17 @@ paths:
         let invalid_file = tmp_dir.path().join("invalid.txt");
         fs::File::create(&invalid_file).unwrap();
 
-        let args = Cli::Preview {
+        let args = Command::Preview {
             note_path: Some(invalid_file.into()),
             note_root: tmp_dir.path().into(),
             preview_typst: vec![],
             preview_markdown: vec![],
+            preview_org: vec![],
+            serve: false,
+            port: 0,
+            pdf: false,
+            watch: false,
+            pdf_viewer: vec![],
         };
 
         let result = process_command(args);
@@ -1203,54 +2496,64 @@ paths:
     }
 
     #[test]
-    fn test_note_path_resolution() {
+    fn test_find_note_dir_hidden() {
         let tmp_dir = tempdir().unwrap();
-
-        // Test filenote path
-        let filenote = tmp_dir.path().join("note.md");
-        fs::File::create(&filenote).unwrap();
-        assert_eq!(filenote.main_file_path().unwrap(), filenote);
-
-        // Test dirnote path
-        let dirnote = tmp_dir.path().join("dirnote");
-        fs::create_dir(&dirnote).unwrap();
-        fs::File::create(dirnote.join("main.md")).unwrap();
-        assert_eq!(dirnote.main_file_path().unwrap(), dirnote.join("main.md"));
-
-        // Test invalid dirnote
-        let invalid_dirnote = tmp_dir.path().join("invalid_dirnote");
-        fs::create_dir(&invalid_dirnote).unwrap();
-        assert!(invalid_dirnote.main_file_path().is_err());
+        let root = tmp_dir.path();
+        fs::create_dir_all(root.join("archive/lang")).unwrap();
+        fs::create_dir_all(root.join("trash")).unwrap();
+        fs::write(root.join("archive/lang/rust.md"), "").unwrap();
+        fs::write(root.join("trash/go.md"), "").unwrap();
+        fs::write(root.join("go.md"), "").unwrap();
+
+        let err = find_note_dir(OsStr::new("rust.md"), root.as_os_str())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("found in archive: archive/lang/rust.md"));
+        assert!(err.contains("--include-archived"));
+        assert_eq!(
+            lookup_note(OsStr::new("rust.md"), root.as_os_str()).unwrap(),
+            None
+        );
+        // The active note wins over the trashed one
+        assert_eq!(
+            find_note_dir(OsStr::new("go.md"), root.as_os_str()).unwrap(),
+            root.join("go.md")
+        );
+        fs::remove_file(root.join("go.md")).unwrap();
+        let err = find_note_dir(OsStr::new("go.md"), root.as_os_str())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("found in trash: trash/go.md"));
     }
 
     #[test]
-    fn test_template_creation() {
+    fn test_show_content() {
         let tmp_dir = tempdir().unwrap();
-        let note_root = tmp_dir.path().join("templated_note");
+        let md = tmp_dir.path().join("note.md");
+        fs::write(&md, "---\ntitle: Note\n---\n\n# Body\n").unwrap();
+        let typ = tmp_dir.path().join("note.typ");
+        fs::write(&typ, "#set document(title: \"Typ\")\n= Body\n").unwrap();
 
-        let mut sub_paths = HashMap::new();
-        sub_paths.insert(
-            "subfile.md".to_string(),
-            PathContent::File("content".to_string()),
+        assert_eq!(
+            show_content(&md, false, false, u64::MAX).unwrap(),
+            ("---\ntitle: Note\n---\n\n# Body\n".to_string(), None)
+        );
+        assert_eq!(
+            show_content(&md, true, false, u64::MAX).unwrap().0,
+            "# Body\n"
+        );
+        assert_eq!(
+            show_content(&typ, true, false, u64::MAX).unwrap().0,
+            "= Body\n"
         );
-        let mut example_paths = HashMap::new();
-        example_paths.insert("subdir".to_string(), PathContent::Directory(sub_paths));
-
-        let template = NoteTemplate {
-            paths: example_paths,
-            main_typ: Some("Typ content".into()),
-            main_md: Some("Md content".into()),
-        };
-
-        create_note_template(&note_root, &template).unwrap();
 
-        // Verify directory structure
-        let subdir = note_root.join("subdir");
-        assert!(subdir.is_dir());
+        // A huge note is cut, with a notice
+        let (content, notice) = show_content(&md, false, false, 20).unwrap();
+        assert_eq!(content, "---\ntitle: Note\n---\n");
+        assert!(notice.unwrap().starts_with("[truncated"));
 
-        // Verify file content
-        let subfile = subdir.join("subfile.md");
-        assert!(subfile.is_file());
-        assert_eq!(fs::read_to_string(subfile).unwrap(), "content");
+        let metadata: serde_json::Value =
+            serde_json::from_str(&show_content(&typ, false, true, u64::MAX).unwrap().0).unwrap();
+        assert_eq!(metadata["title"], "Typ");
     }
 }
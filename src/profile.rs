@@ -0,0 +1,67 @@
+use std::{
+    fmt::Write,
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+static START: OnceLock<Instant> = OnceLock::new();
+
+/// The phases that ended so far, with the time elapsed since the start when they did.
+static PHASES: Mutex<Vec<(&'static str, Duration)>> = Mutex::new(Vec::new());
+
+/// Start the clock of the startup profile. Called first thing in `main`.
+pub fn start() {
+    let _ = START.set(Instant::now());
+}
+
+/// Record the end of a phase of the startup. Cheap, so phases are recorded whether or not the
+/// profile is printed, which is only known once the command line is parsed.
+pub fn mark(phase: &'static str) {
+    let Some(start) = START.get() else {
+        return;
+    };
+    PHASES.lock().unwrap().push((phase, start.elapsed()));
+}
+
+/// The time spent in each phase, and the total, as printed by `--profile-startup`.
+pub fn report() -> String {
+    format_phases(&PHASES.lock().unwrap())
+}
+
+fn format_phases(phases: &[(&str, Duration)]) -> String {
+    let width = phases
+        .iter()
+        .map(|(p, _)| p.len())
+        .max()
+        .unwrap_or(0)
+        .max(5);
+    let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+
+    let mut report = String::from("Startup profile:\n");
+    let mut previous = Duration::ZERO;
+    for &(phase, elapsed) in phases {
+        let spent = elapsed.saturating_sub(previous);
+        writeln!(report, "  {:<width$}  {:>8.3} ms", phase, ms(spent)).unwrap();
+        previous = elapsed;
+    }
+    writeln!(report, "  {:<width$}  {:>8.3} ms", "total", ms(previous)).unwrap();
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_phases() {
+        let phases = [
+            ("config", Duration::from_micros(500)),
+            ("parse", Duration::from_micros(1500)),
+            ("index", Duration::from_micros(1750)),
+        ];
+        assert_eq!(
+            format_phases(&phases),
+            "Startup profile:\n  config     0.500 ms\n  parse      1.000 ms\n  index      0.250 ms\n  total      1.750 ms\n"
+        );
+    }
+}
@@ -0,0 +1,398 @@
+use anyhow::{Result, bail};
+use chrono::{DateTime, Local, NaiveDate};
+use serde_json::Value;
+use std::{cell::OnceCell, cmp::Ordering, path::Path};
+
+use crate::bounded::preview_limit;
+use crate::dates::{created, parse_day};
+use crate::index::NoteIndex;
+use crate::metadata::{FieldFilter, NoteMetadata};
+use crate::sections::{Section, note_sections};
+use crate::vault::{Note, note_metadata};
+
+/// A structured query of `noxe search --query-lang`, like
+/// `author:alice tag:rust created:>2024-01-01 title:async`.
+///
+/// A query is a list of terms, all of which must hold. A term is a word matched against the
+/// name and the title of notes, or `KEY:VALUE`:
+/// - `title:`, `author:` and `name:` match when the value is part of the field;
+/// - `tag:` (or `keyword:`) matches one of the keywords, `type:` the extension of the main
+///   file, `category:` the category of the note or one of its parents;
+/// - `created:`, `modified:` (or `updated:`) and `date:` compare days, with `>`, `>=`, `<`,
+///   `<=` or `=` (the default) before the day, e.g. `created:>=7d`;
+/// - `section-status:` and `section-tag:` match when a section of the note is annotated with
+///   the status or the tag, e.g. `section-status:draft`;
+/// - any other key is a metadata field, compared like `--fields`, or ordered with the
+///   operators, numerically when both sides are numbers.
+///
+/// A term starting with `-` is negated, and a value with spaces is quoted:
+/// `title:"async rust"`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    terms: Vec<Term>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Term {
+    negated: bool,
+    predicate: Predicate,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Predicate {
+    Text(String),
+    Title(String),
+    Author(String),
+    Name(String),
+    Tag(String),
+    Type(String),
+    Category(String),
+    SectionStatus(String),
+    SectionTag(String),
+    Created(Op, NaiveDate),
+    Modified(Op, NaiveDate),
+    Date(Op, NaiveDate),
+    Field { key: String, op: Op, value: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Op {
+    /// Split the operator off a value, `=` when there is none.
+    fn split(value: &str) -> (Op, &str) {
+        for (prefix, op) in [
+            (">=", Op::Ge),
+            ("<=", Op::Le),
+            (">", Op::Gt),
+            ("<", Op::Lt),
+            ("=", Op::Eq),
+        ] {
+            if let Some(rest) = value.strip_prefix(prefix) {
+                return (op, rest);
+            }
+        }
+        (Op::Eq, value)
+    }
+
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            Op::Eq => ordering.is_eq(),
+            Op::Lt => ordering.is_lt(),
+            Op::Le => ordering.is_le(),
+            Op::Gt => ordering.is_gt(),
+            Op::Ge => ordering.is_ge(),
+        }
+    }
+}
+
+impl Query {
+    /// Parse a query. Relative days, like `7d`, are counted back from `today`.
+    pub fn parse(query: &str, today: NaiveDate) -> Result<Self> {
+        let terms = tokens(query)?
+            .into_iter()
+            .map(|(token, quoted)| term(&token, quoted, today))
+            .collect::<Result<Vec<_>>>()?;
+        if terms.is_empty() {
+            bail!("The query is empty");
+        }
+        Ok(Query { terms })
+    }
+
+    /// Whether the note matches all the terms. The metadata is taken from the index when
+    /// given, and only read when a term needs it.
+    pub fn matches(&self, index: Option<&NoteIndex>, note_root: &Path, note: &Path) -> bool {
+        let facts = NoteFacts {
+            index,
+            note_root,
+            note,
+            metadata: OnceCell::new(),
+            sections: OnceCell::new(),
+        };
+        self.terms
+            .iter()
+            .all(|term| term.predicate.holds(&facts) != term.negated)
+    }
+}
+
+/// A note, with its metadata read on demand.
+struct NoteFacts<'a> {
+    index: Option<&'a NoteIndex>,
+    note_root: &'a Path,
+    note: &'a Path,
+    metadata: OnceCell<Option<NoteMetadata>>,
+    sections: OnceCell<Vec<Section>>,
+}
+
+impl NoteFacts<'_> {
+    fn metadata(&self) -> Option<&NoteMetadata> {
+        self.metadata
+            .get_or_init(|| note_metadata(self.index, self.note_root, self.note))
+            .as_ref()
+    }
+
+    fn sections(&self) -> &[Section] {
+        self.sections.get_or_init(|| {
+            note_sections(self.note, preview_limit())
+                .map(|(sections, _)| sections)
+                .unwrap_or_default()
+        })
+    }
+}
+
+impl Predicate {
+    fn holds(&self, facts: &NoteFacts) -> bool {
+        let metadata = || facts.metadata();
+        let contains = |text: Option<&str>, part: &str| {
+            text.is_some_and(|t| t.to_lowercase().contains(&part.to_lowercase()))
+        };
+        let day = |time| DateTime::<Local>::from(time).date_naive();
+
+        match self {
+            Predicate::Text(text) => {
+                contains(Some(&facts.note.note_name()), text)
+                    || contains(metadata().and_then(|m| m.title.as_deref()), text)
+            }
+            Predicate::Title(title) => contains(metadata().and_then(|m| m.title.as_deref()), title),
+            Predicate::Author(author) => {
+                contains(metadata().and_then(|m| m.author.as_deref()), author)
+            }
+            Predicate::Name(name) => contains(Some(&facts.note.note_name()), name),
+            Predicate::Tag(tag) => {
+                metadata().is_some_and(|m| m.keywords.iter().any(|k| k.eq_ignore_ascii_case(tag)))
+            }
+            Predicate::Type(ext) => facts.note.main_file_path().is_ok_and(|main_file| {
+                main_file
+                    .extension()
+                    .is_some_and(|e| e.eq_ignore_ascii_case(ext))
+            }),
+            Predicate::Category(category) => {
+                let category = category.trim_matches('/').to_lowercase();
+                facts
+                    .note
+                    .parent()
+                    .and_then(|dir| dir.strip_prefix(facts.note_root).ok())
+                    .is_some_and(|dir| {
+                        let dir = dir.to_string_lossy().replace('\\', "/").to_lowercase();
+                        dir == category || dir.starts_with(&format!("{}/", category))
+                    })
+            }
+            Predicate::SectionStatus(status) => facts
+                .sections()
+                .iter()
+                .any(|s| s.matches(Some(status), None)),
+            Predicate::SectionTag(tag) => {
+                facts.sections().iter().any(|s| s.matches(None, Some(tag)))
+            }
+            Predicate::Created(op, date) => facts
+                .note
+                .metadata()
+                .is_ok_and(|m| op.holds(day(created(&m)).cmp(date))),
+            Predicate::Modified(op, date) => facts
+                .note
+                .metadata()
+                .and_then(|m| m.modified())
+                .is_ok_and(|time| op.holds(day(time).cmp(date))),
+            Predicate::Date(op, date) => metadata()
+                .and_then(|m| m.date.as_deref())
+                .and_then(|d| NaiveDate::parse_from_str(d.get(..10)?, "%Y-%m-%d").ok())
+                .is_some_and(|d| op.holds(d.cmp(date))),
+            Predicate::Field { key, op, value } => {
+                let Some(metadata) = metadata() else {
+                    return false;
+                };
+                match op {
+                    Op::Eq => FieldFilter {
+                        key: key.clone(),
+                        value: value.clone(),
+                    }
+                    .matches(metadata),
+                    op => metadata
+                        .get(key)
+                        .and_then(|field| compare(&field, value))
+                        .is_some_and(|ordering| op.holds(ordering)),
+                }
+            }
+        }
+    }
+}
+
+/// Order a field and a value: numerically when both are numbers, else as text.
+fn compare(field: &Value, value: &str) -> Option<Ordering> {
+    match field {
+        Value::Number(n) => n.as_f64()?.partial_cmp(&value.parse::<f64>().ok()?),
+        Value::String(s) => match (s.parse::<f64>(), value.parse::<f64>()) {
+            (Ok(a), Ok(b)) => a.partial_cmp(&b),
+            _ => Some(s.as_str().cmp(value)),
+        },
+        _ => None,
+    }
+}
+
+/// Split a query on whitespace outside of quotes. Each token tells whether it starts with a
+/// quote, so that a quoted `"a:b"` is text.
+fn tokens(query: &str) -> Result<Vec<(String, bool)>> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut quoted = false;
+    let mut in_quotes = false;
+    let mut started = false;
+
+    for c in query.chars() {
+        match c {
+            '"' => {
+                if !started {
+                    quoted = true;
+                }
+                in_quotes = !in_quotes;
+                started = true;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if started {
+                    tokens.push((std::mem::take(&mut token), quoted));
+                }
+                quoted = false;
+                started = false;
+            }
+            c => {
+                token.push(c);
+                started = true;
+            }
+        }
+    }
+    if in_quotes {
+        bail!("Unterminated quote in query '{}'", query);
+    }
+    if started {
+        tokens.push((token, quoted));
+    }
+    Ok(tokens)
+}
+
+fn term(token: &str, quoted: bool, today: NaiveDate) -> Result<Term> {
+    let (negated, token) = match token.strip_prefix('-') {
+        Some(rest) if !quoted && !rest.is_empty() => (true, rest),
+        _ => (false, token),
+    };
+
+    let field = token.split_once(':').filter(|(key, _)| {
+        !quoted
+            && key.chars().next().is_some_and(|c| c.is_ascii_alphabetic())
+            && key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    });
+    let Some((key, value)) = field else {
+        return Ok(Term {
+            negated,
+            predicate: Predicate::Text(token.to_string()),
+        });
+    };
+    if value.is_empty() {
+        bail!("'{}' has no value, expected {}:VALUE", token, key);
+    }
+
+    let date = |value: &str| -> Result<(Op, NaiveDate)> {
+        let (op, day) = Op::split(value);
+        Ok((op, parse_day(day, today)?))
+    };
+    let value = value.to_string();
+    let predicate = match key.to_lowercase().as_str() {
+        "title" => Predicate::Title(value),
+        "author" => Predicate::Author(value),
+        "name" => Predicate::Name(value),
+        "tag" | "keyword" => Predicate::Tag(value),
+        "type" => Predicate::Type(value.trim_start_matches('.').to_string()),
+        "category" => Predicate::Category(value),
+        "section-status" => Predicate::SectionStatus(value),
+        "section-tag" => Predicate::SectionTag(value),
+        "created" => {
+            let (op, day) = date(&value)?;
+            Predicate::Created(op, day)
+        }
+        "modified" | "updated" => {
+            let (op, day) = date(&value)?;
+            Predicate::Modified(op, day)
+        }
+        "date" => {
+            let (op, day) = date(&value)?;
+            Predicate::Date(op, day)
+        }
+        _ => {
+            let (op, value) = Op::split(&value);
+            Predicate::Field {
+                key: key.to_string(),
+                op,
+                value: value.to_string(),
+            }
+        }
+    };
+    Ok(Term { negated, predicate })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_query() {
+        let today = NaiveDate::from_ymd_opt(2025, 3, 10).unwrap();
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        fs::create_dir_all(root.join("lang/rust")).unwrap();
+        let async_rust = root.join("lang/rust/async.md");
+        fs::write(
+            &async_rust,
+            "---\ntitle: Async Rust\nauthor: Alice\nkeywords: [rust, async]\ndate: 2024-06-01\npriority: 3\n---\n## Pinning <!-- status: draft, tags: [unsafe] -->\n",
+        )
+        .unwrap();
+        let go = root.join("go.md");
+        fs::write(
+            &go,
+            "---\ntitle: Goroutines\nauthor: Bob\nkeywords: [go]\ndate: 2023-01-01\n---\n",
+        )
+        .unwrap();
+
+        let matching = |query: &str| {
+            let query = Query::parse(query, today).unwrap();
+            [&async_rust, &go]
+                .into_iter()
+                .filter(|note| query.matches(None, root, note))
+                .map(|note| note.note_name())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            matching("author:alice tag:rust created:>2024-01-01 title:async"),
+            ["async"]
+        );
+        assert_eq!(matching("date:<2024-01-01"), ["go"]);
+        assert_eq!(matching("-tag:rust"), ["go"]);
+        assert_eq!(matching("category:lang type:md"), ["async"]);
+        assert_eq!(matching("priority:>=3 priority:<10"), ["async"]);
+        assert_eq!(matching("title:\"async rust\""), ["async"]);
+        assert_eq!(matching("goroutines"), ["go"]);
+        assert_eq!(
+            matching("section-status:draft section-tag:unsafe"),
+            ["async"]
+        );
+        assert_eq!(matching("-section-status:draft"), ["go"]);
+        assert!(matching("modified:<2000-01-01").is_empty());
+
+        assert!(Query::parse("", today).is_err());
+        assert!(Query::parse("tag:", today).is_err());
+        assert!(Query::parse("title:\"async", today).is_err());
+        assert!(Query::parse("created:>last-week", today).is_err());
+        assert_eq!(
+            Query::parse("\"a:b\"", today).unwrap().terms[0].predicate,
+            Predicate::Text("a:b".to_string())
+        );
+    }
+}
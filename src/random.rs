@@ -0,0 +1,97 @@
+use anyhow::Result;
+use std::{
+    hash::{BuildHasher, RandomState},
+    path::{Path, PathBuf},
+};
+
+use crate::index::NoteIndex;
+use crate::vault::{Scope, category_dir, note_metadata, notes_in_categories};
+
+/// The notes `noxe random` picks from: those of the categories (all of them when none is
+/// given) in the scope, carrying one of the tags when any is given. Tags match
+/// case-insensitively.
+pub fn candidates(
+    note_root: &Path,
+    categories: &[String],
+    tags: &[String],
+    scope: Scope,
+) -> Result<Vec<PathBuf>> {
+    let categories = categories
+        .iter()
+        .map(|c| category_dir(note_root, c))
+        .collect::<Result<Vec<_>>>()?;
+    let index = NoteIndex::open(note_root)?;
+
+    let mut notes = notes_in_categories(index.as_ref(), note_root, &categories)?;
+    notes.retain(|note| scope.contains(note_root, note));
+    if !tags.is_empty() {
+        notes.retain(|note| {
+            note_metadata(index.as_ref(), note_root, note).is_some_and(|m| {
+                m.keywords
+                    .iter()
+                    .any(|k| tags.iter().any(|t| t.eq_ignore_ascii_case(k)))
+            })
+        });
+    }
+    notes.sort();
+    Ok(notes)
+}
+
+/// Pick one of the notes at random.
+pub fn pick(notes: &[PathBuf]) -> Option<&PathBuf> {
+    if notes.is_empty() {
+        return None;
+    }
+    // Seeded randomly for each process
+    let n = RandomState::new().hash_one(notes.len());
+    notes.get(n as usize % notes.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_candidates() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        fs::create_dir_all(root.join("lang")).unwrap();
+        fs::create_dir_all(root.join("archive")).unwrap();
+        fs::write(
+            root.join("lang/rust.md"),
+            "---\nkeywords: [Systems, memory]\n---\n",
+        )
+        .unwrap();
+        fs::write(root.join("lang/go.md"), "---\nkeywords: [systems]\n---\n").unwrap();
+        fs::write(root.join("cooking.md"), "---\nkeywords: [food]\n---\n").unwrap();
+        fs::write(
+            root.join("archive/old.md"),
+            "---\nkeywords: [memory]\n---\n",
+        )
+        .unwrap();
+
+        let all = candidates(root, &[], &[], Scope::Active).unwrap();
+        assert_eq!(
+            all,
+            [
+                root.join("cooking.md"),
+                root.join("lang/go.md"),
+                root.join("lang/rust.md"),
+            ]
+        );
+        assert_eq!(
+            candidates(root, &["lang".into()], &["SYSTEMS".into()], Scope::Active).unwrap(),
+            [root.join("lang/go.md"), root.join("lang/rust.md")]
+        );
+        assert_eq!(
+            candidates(root, &[], &["memory".into()], Scope::Active).unwrap(),
+            [root.join("lang/rust.md")]
+        );
+        assert!(candidates(root, &["missing".into()], &[], Scope::Active).is_err());
+
+        assert!(all.contains(pick(&all).unwrap()));
+        assert_eq!(pick(&[]), None);
+    }
+}
@@ -0,0 +1,98 @@
+use anyhow::Result;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::vault::{Note, Scope, scoped_notes};
+
+/// The `n` notes modified last, the newest first, with their modification time.
+pub fn recent(note_root: &Path, scope: Scope, n: usize) -> Result<Vec<(PathBuf, SystemTime)>> {
+    let mut notes: Vec<(PathBuf, SystemTime)> = scoped_notes(note_root, note_root, scope)?
+        .into_iter()
+        .filter_map(|note| {
+            let modified = modified(&note)?;
+            Some((note, modified))
+        })
+        .collect();
+    notes.sort_by(|(a, a_time), (b, b_time)| b_time.cmp(a_time).then_with(|| a.cmp(b)));
+    notes.truncate(n);
+    Ok(notes)
+}
+
+/// When a note was modified: its main file is, unlike the directory of a dirnote, written on
+/// every edit.
+fn modified(note: &Path) -> Option<SystemTime> {
+    let main_file = note.main_file_path().unwrap_or_else(|_| note.to_path_buf());
+    fs::metadata(main_file)
+        .or_else(|_| fs::metadata(note))
+        .and_then(|m| m.modified())
+        .ok()
+}
+
+/// How long before `now` `time` is, roughly: `just now`, `5m ago`, `2h ago`, `3d ago`,
+/// `2w ago`, `4mo ago`, `1y ago`.
+pub fn time_ago(time: SystemTime, now: SystemTime) -> String {
+    const UNITS: [(u64, &str); 6] = [
+        (365 * 86400, "y"),
+        (30 * 86400, "mo"),
+        (7 * 86400, "w"),
+        (86400, "d"),
+        (3600, "h"),
+        (60, "m"),
+    ];
+    let secs = now.duration_since(time).unwrap_or(Duration::ZERO).as_secs();
+    UNITS
+        .iter()
+        .find(|(unit, _)| secs >= *unit)
+        .map_or("just now".to_string(), |(unit, name)| {
+            format!("{}{} ago", secs / unit, name)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_recent() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        fs::create_dir_all(root.join("rust")).unwrap();
+        fs::create_dir_all(root.join("trash")).unwrap();
+        let now = SystemTime::now();
+        let touch = |path: &Path, ago: u64| {
+            fs::write(path, "").unwrap();
+            fs::File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(now - Duration::from_secs(ago))
+                .unwrap();
+        };
+        touch(&root.join("old.md"), 3 * 86400);
+        touch(&root.join("rust/main.md"), 2 * 3600);
+        touch(&root.join("new.md"), 30);
+        touch(&root.join("trash/gone.md"), 0);
+
+        let notes = recent(root, Scope::Active, 2).unwrap();
+        let names: Vec<&Path> = notes.iter().map(|(note, _)| note.as_path()).collect();
+        assert_eq!(names, [root.join("new.md"), root.join("rust")]);
+        assert_eq!(time_ago(notes[1].1, now), "2h ago");
+
+        assert_eq!(time_ago(now, now), "just now");
+        assert_eq!(time_ago(now - Duration::from_secs(90), now), "1m ago");
+        assert_eq!(
+            time_ago(now - Duration::from_secs(10 * 86400), now),
+            "1w ago"
+        );
+        assert_eq!(
+            time_ago(now - Duration::from_secs(400 * 86400), now),
+            "1y ago"
+        );
+        // A time in the future, from another clock
+        assert_eq!(time_ago(now + Duration::from_secs(60), now), "just now");
+    }
+}
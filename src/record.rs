@@ -0,0 +1,208 @@
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::{fmt::Write, path::Path, time::SystemTime};
+use walkdir::WalkDir;
+
+use crate::lang::note_lang;
+use crate::metadata::read_metadata;
+use crate::vault::Note;
+use crate::words::note_words;
+
+/// A structured description of a note (or category), used for machine-readable output.
+#[derive(Debug, Clone, Serialize)]
+pub struct NoteRecord {
+    /// Path of the note relative to the note root
+    pub path: String,
+    pub name: String,
+    /// `filenote`, `dirnote` or `category`
+    pub kind: &'static str,
+    /// The note type (`md`, `typ`, `org`)
+    #[serde(rename = "type")]
+    pub note_type: Option<String>,
+    /// The category path of the note, e.g. `cat1/sub_cat1`
+    pub category: Option<String>,
+    pub created: Option<String>,
+    pub modified: Option<String>,
+    pub title: Option<String>,
+    /// The words of the note, without markup and code
+    pub words: Option<usize>,
+    /// The size in bytes of the file of a filenote, or of the files of a dirnote
+    pub size: Option<u64>,
+    /// The language of the note, e.g. `zh` or `en`
+    pub lang: Option<String>,
+}
+
+impl NoteRecord {
+    pub fn new(note_root: &Path, note_path: &Path) -> Self {
+        let rel_path = note_path.strip_prefix(note_root).unwrap_or(note_path);
+
+        let kind = if note_path.is_dirnote() {
+            "dirnote"
+        } else if note_path.is_filenote() {
+            "filenote"
+        } else {
+            "category"
+        };
+
+        let main_file = note_path
+            .main_file_path()
+            .ok()
+            .filter(|_| kind != "category");
+        let note_type = main_file
+            .as_ref()
+            .and_then(|m| m.note_type().ok())
+            .map(|t| t.to_string());
+        let title = main_file
+            .as_ref()
+            .and_then(|m| read_metadata(m).ok())
+            .and_then(|m| m.title);
+
+        let category = rel_path
+            .parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .map(|p| p.to_string_lossy().to_string());
+
+        let metadata = note_path.metadata().ok();
+        let format_time = |t: std::io::Result<SystemTime>| {
+            t.ok().map(|t| DateTime::<Local>::from(t).to_rfc3339())
+        };
+
+        NoteRecord {
+            path: rel_path.to_string_lossy().to_string(),
+            name: note_path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            kind,
+            note_type,
+            category,
+            created: metadata.as_ref().and_then(|m| format_time(m.created())),
+            modified: metadata.as_ref().and_then(|m| format_time(m.modified())),
+            title,
+            words: main_file.as_ref().and_then(|_| note_words(note_path).ok()),
+            size: main_file.as_ref().map(|_| size(note_path)),
+            lang: main_file.as_ref().and_then(|_| note_lang(note_path)),
+        }
+    }
+}
+
+pub(crate) fn size(note_path: &Path) -> u64 {
+    WalkDir::new(note_path)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.metadata().ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+        .sum()
+}
+
+/// The records as a table with aligned columns, for `noxe list --long`.
+pub fn format_table(records: &[NoteRecord]) -> String {
+    let time = |t: &Option<String>| {
+        t.as_deref()
+            .and_then(|t| t.get(..16))
+            .map(|t| t.replacen('T', " ", 1))
+            .unwrap_or_else(|| "-".to_string())
+    };
+    let dash = |s: Option<&str>| s.filter(|s| !s.is_empty()).unwrap_or("-").to_string();
+
+    let header = [
+        "NAME", "CATEGORY", "TYPE", "SIZE", "CREATED", "MODIFIED", "LANG", "TITLE",
+    ];
+    let rows: Vec<[String; 8]> = records
+        .iter()
+        .map(|r| {
+            [
+                r.name.clone(),
+                dash(r.category.as_deref()),
+                dash(r.note_type.as_deref()),
+                r.size.map_or("-".to_string(), human_size),
+                time(&r.created),
+                time(&r.modified),
+                dash(r.lang.as_deref()),
+                dash(r.title.as_deref()),
+            ]
+        })
+        .collect();
+
+    let mut widths = header.map(|h| h.chars().count());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut table = String::new();
+    for row in std::iter::once(header.map(str::to_string)).chain(rows) {
+        let mut line = String::new();
+        for (i, (cell, width)) in row.iter().zip(widths).enumerate() {
+            if i == 3 {
+                // Sizes are right-aligned
+                write!(line, "{:>width$}  ", cell).unwrap();
+            } else {
+                write!(line, "{:<width$}  ", cell).unwrap();
+            }
+        }
+        table.push_str(line.trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+/// `512`, `1.5K`, `12M`...
+pub(crate) fn human_size(bytes: u64) -> String {
+    let mut size = bytes as f64;
+    for unit in ["", "K", "M", "G"] {
+        if size < 1024.0 {
+            return if unit.is_empty() || size >= 10.0 {
+                format!("{:.0}{}", size, unit)
+            } else {
+                format!("{:.1}{}", size, unit)
+            };
+        }
+        size /= 1024.0;
+    }
+    format!("{:.0}T", size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_note_record() {
+        let tmp_dir = tempdir().unwrap();
+        let dirnote = tmp_dir.path().join("cat").join("sub").join("dirnote");
+        fs::create_dir_all(&dirnote).unwrap();
+        fs::write(dirnote.join("main.md"), "---\ntitle: \"Dir Note\"\n---\n").unwrap();
+
+        let record = NoteRecord::new(tmp_dir.path(), &dirnote);
+        assert_eq!(record.path, "cat/sub/dirnote");
+        assert_eq!(record.name, "dirnote");
+        assert_eq!(record.kind, "dirnote");
+        assert_eq!(record.note_type.as_deref(), Some("md"));
+        assert_eq!(record.category.as_deref(), Some("cat/sub"));
+        assert_eq!(record.title.as_deref(), Some("Dir Note"));
+        assert_eq!(record.words, Some(0));
+        assert_eq!(record.size, Some(26));
+        assert!(record.modified.is_some());
+
+        let table = format_table(std::slice::from_ref(&record));
+        let lines: Vec<&str> = table.lines().collect();
+        assert!(lines[0].starts_with("NAME     CATEGORY  TYPE  SIZE  CREATED"));
+        assert!(lines[1].starts_with("dirnote  cat/sub   md      26  "));
+        assert!(lines[1].ends_with("  Dir Note"));
+        assert_eq!(human_size(1536), "1.5K");
+        assert_eq!(human_size(20 * 1024 * 1024), "20M");
+
+        let record = NoteRecord::new(tmp_dir.path(), &tmp_dir.path().join("cat"));
+        assert_eq!(record.kind, "category");
+        assert_eq!(record.category, None);
+        assert_eq!(record.note_type, None);
+        assert_eq!(record.words, None);
+        assert_eq!(record.size, None);
+        assert_eq!(record.lang, None);
+    }
+}
@@ -0,0 +1,466 @@
+use anyhow::{Context, Result, bail};
+use std::{
+    collections::HashSet,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::atomic::{self, Transaction};
+use crate::links::{LinkIndex, Rewrite};
+use crate::metadata::read_metadata;
+use crate::process::exec_with;
+use crate::state::StateStore;
+use crate::vault::Note;
+
+/// The file of the state directory where the plan is edited.
+const PLAN_FILE: &str = "rename-plan.txt";
+
+const PLAN_HEADER: &str = "\
+# Rename plan: one `old -> new` line per note, paths relative to the note root.
+# Edit the new paths, remove the lines of the notes to keep as they are.
+";
+
+/// A set of renames, from the current path to the new one.
+pub type Plan = Vec<(PathBuf, PathBuf)>;
+
+/// Name the notes after `pattern`, keeping them in their category. Placeholders:
+///
+/// - `{name}`: the current name of the note
+/// - `{title}`: the title of the metadata, or the name
+/// - `{slug}`: the title in lowercase, with runs of other characters than letters and digits
+///   replaced by `-`
+/// - `{date}`: the date of the metadata, or the modification date, as `YYYY-MM-DD`
+/// - `{n}`: the position of the note in the list, from 1
+pub fn pattern_plan(notes: &[PathBuf], pattern: &str) -> Result<Plan> {
+    notes
+        .iter()
+        .enumerate()
+        .map(|(i, note)| {
+            let name = expand_pattern(pattern, note, i + 1)?;
+            Ok((note.clone(), sibling(note, &name)))
+        })
+        .collect()
+}
+
+/// The path of a note renamed to `name` in the same category. Filenotes keep their extension
+/// unless `name` has a note extension.
+pub fn sibling(note: &Path, name: &str) -> PathBuf {
+    let has_note_ext = Path::new(name).note_type().is_ok();
+    let name = match note.extension() {
+        Some(ext) if note.is_filenote() && !has_note_ext => {
+            format!("{}.{}", name, ext.to_string_lossy())
+        }
+        _ => name.to_string(),
+    };
+    note.with_file_name(name)
+}
+
+fn expand_pattern(pattern: &str, note: &Path, n: usize) -> Result<String> {
+    let name = note.note_name();
+    let metadata = note
+        .main_file_path()
+        .and_then(|m| read_metadata(&m))
+        .unwrap_or_default();
+    let title = metadata.title.unwrap_or_else(|| name.clone());
+
+    let date = match metadata
+        .date
+        .as_deref()
+        .and_then(|d| d.get(..10))
+        .filter(|d| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").is_ok())
+    {
+        Some(date) => date.to_string(),
+        None => {
+            let modified = fs::metadata(note)
+                .and_then(|m| m.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            chrono::DateTime::<chrono::Local>::from(modified)
+                .format("%Y-%m-%d")
+                .to_string()
+        }
+    };
+
+    let mut result = String::new();
+    let mut rest = pattern;
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}') else {
+            bail!("Unclosed placeholder in pattern '{}'", pattern);
+        };
+        match &rest[start + 1..start + end] {
+            "name" => result.push_str(&name),
+            "title" => result.push_str(&title),
+            "slug" => result.push_str(&slugify(&title)),
+            "date" => result.push_str(&date),
+            "n" => result.push_str(&n.to_string()),
+            other => bail!(
+                "Unknown placeholder '{{{}}}' in pattern '{}'",
+                other,
+                pattern
+            ),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    result.push_str(rest);
+
+    Ok(result)
+}
+
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+/// Write the plan as editable text.
+pub fn format_plan(note_root: &Path, plan: &Plan) -> String {
+    let rel = |p: &Path| {
+        p.strip_prefix(note_root)
+            .unwrap_or(p)
+            .to_string_lossy()
+            .replace('\\', "/")
+    };
+
+    let mut text = PLAN_HEADER.to_string();
+    for (from, to) in plan {
+        text.push_str(&format!("{} -> {}\n", rel(from), rel(to)));
+    }
+    text
+}
+
+/// Read a plan written by [`format_plan`]. Comments, blank lines and unchanged notes are
+/// skipped.
+pub fn parse_plan(note_root: &Path, text: &str) -> Result<Plan> {
+    let mut plan = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((from, to)) = line.split_once(" -> ") else {
+            bail!("Line {} of the plan is not `old -> new`: {}", i + 1, line);
+        };
+        let (from, to) = (from.trim(), to.trim());
+        if to.is_empty() {
+            bail!("Line {} of the plan has no new path", i + 1);
+        }
+        if Path::new(to).is_absolute()
+            || Path::new(to)
+                .components()
+                .any(|c| c == std::path::Component::ParentDir)
+        {
+            bail!("Line {} of the plan leaves the note root: {}", i + 1, to);
+        }
+        if from != to {
+            plan.push((note_root.join(from), note_root.join(to)));
+        }
+    }
+    Ok(plan)
+}
+
+/// Let the user edit the plan in their editor, and read it back.
+pub fn edit_plan(
+    store: &StateStore,
+    note_root: &Path,
+    plan: &Plan,
+    edit: &[OsString],
+) -> Result<Plan> {
+    store.init()?;
+    let path = store.dir().join(PLAN_FILE);
+    fs::write(&path, format_plan(note_root, plan))
+        .with_context(|| format!("Failed to write the plan '{}'", path.display()))?;
+
+    exec_with(&path, edit)?;
+
+    let text = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read the plan '{}'", path.display()))?;
+    let _ = fs::remove_file(&path);
+    parse_plan(note_root, &text)
+}
+
+/// Check that the renames can all be done: the sources are distinct notes, no two sources
+/// map to the same target, and the targets are free or renamed away by the plan.
+pub fn validate(plan: &Plan) -> Result<()> {
+    let sources: HashSet<&Path> = plan.iter().map(|(from, _)| from.as_path()).collect();
+    if sources.len() != plan.len() {
+        let mut seen = HashSet::new();
+        let duplicate = plan.iter().find(|(from, _)| !seen.insert(from)).unwrap();
+        bail!("'{}' is renamed more than once", duplicate.0.display());
+    }
+
+    let mut targets: HashSet<String> = HashSet::new();
+    for (from, to) in plan {
+        if !from.is_filenote() && !from.is_dirnote() {
+            bail!("'{}' is not a note", from.display());
+        }
+        if to
+            .file_name()
+            .is_none_or(|n| n.to_string_lossy().starts_with('.'))
+        {
+            bail!("'{}' is not a valid note name", to.display());
+        }
+        if from.is_filenote() && to.note_type().is_err() {
+            bail!(
+                "'{}' must keep a note extension, as '{}' is a filenote",
+                to.display(),
+                from.display()
+            );
+        }
+        // Case-insensitive file systems would merge targets differing only by case
+        if !targets.insert(to.to_string_lossy().to_lowercase()) {
+            bail!("Several notes would be renamed to '{}'", to.display());
+        }
+        if to.exists() && !sources.contains(to.as_path()) {
+            bail!("'{}' already exists", to.display());
+        }
+        if to.starts_with(from) {
+            bail!("Cannot put '{}' inside itself", from.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Fail if a note of the plan is locked with `readonly: true`, for the renames done in bulk.
+pub fn ensure_unlocked(plan: &Plan) -> Result<()> {
+    for (from, _) in plan {
+        if from
+            .main_file_path()
+            .and_then(|m| read_metadata(&m))
+            .is_ok_and(|m| m.is_readonly())
+        {
+            bail!(
+                "'{}' is read-only (see `readonly` in its metadata), use --override to move it",
+                from.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Fail if rewriting the links would change a note locked with `readonly: true`, or a file of
+/// a locked dirnote.
+pub fn ensure_rewritable(note_root: &Path, rewrites: &[Rewrite]) -> Result<()> {
+    for rewrite in rewrites {
+        let locked = rewrite
+            .file
+            .ancestors()
+            .take_while(|a| a.starts_with(note_root) && *a != note_root)
+            .filter(|a| a.is_filenote() || a.is_dirnote())
+            .find(|note| {
+                note.main_file_path()
+                    .and_then(|m| read_metadata(&m))
+                    .is_ok_and(|m| m.is_readonly())
+            });
+        if let Some(note) = locked {
+            bail!(
+                "'{}' is read-only (see `readonly` in its metadata) and links to a moved note, use --override to rewrite it",
+                note.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Do the renames and rewrite the links to the renamed notes. The notes are first moved to
+/// temporary names, so that swaps and chains work. If anything fails, everything done so far
+/// is reverted. The links of read-only notes are only rewritten when the lock is overridden.
+pub fn execute(note_root: &Path, plan: &Plan, override_lock: bool) -> Result<usize> {
+    validate(plan)?;
+    let rewrites = LinkIndex::new(note_root)?.plan_moves(plan)?;
+    if !override_lock {
+        ensure_rewritable(note_root, &rewrites)?;
+    }
+
+    let mut journal = Journal::default();
+    let result = run(note_root, plan, &rewrites, &mut journal);
+    if let Err(e) = result {
+        return Err(match journal.rollback() {
+            Ok(()) => e.context("The renames were reverted"),
+            Err(rollback) => e.context(format!("Failed to revert the renames: {:#}", rollback)),
+        });
+    }
+
+    Ok(rewrites.len())
+}
+
+fn run(note_root: &Path, plan: &Plan, rewrites: &[Rewrite], journal: &mut Journal) -> Result<()> {
+    let temporary: Vec<PathBuf> = plan
+        .iter()
+        .enumerate()
+        .map(|(i, (from, _))| {
+            let name = from.file_name().unwrap_or_default().to_string_lossy();
+            from.with_file_name(format!(".noxe-rename-{}-{}", i, name))
+        })
+        .collect();
+
+    for ((from, _), tmp) in plan.iter().zip(&temporary) {
+        journal.rename(from, tmp)?;
+    }
+    for ((_, to), tmp) in plan.iter().zip(&temporary) {
+        if let Some(parent) = to.parent()
+            && !parent.exists()
+        {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create '{}'", parent.display()))?;
+            journal.created_dirs.push(parent.to_path_buf());
+        }
+        journal.rename(tmp, to)?;
+    }
+
+    let mut transaction = Transaction::new();
+    for rewrite in rewrites {
+        // Rewrites of the renamed notes are written to their new place
+        let file = plan
+            .iter()
+            .find_map(|(from, to)| {
+                let rest = rewrite.file.strip_prefix(from).ok()?;
+                // A filenote is its own main file
+                Some(if rest.as_os_str().is_empty() {
+                    to.clone()
+                } else {
+                    to.join(rest)
+                })
+            })
+            .unwrap_or_else(|| rewrite.file.clone());
+
+        transaction.write(&file, rewrite.new.as_str());
+        journal.rewrites.push((file, rewrite.old.clone()));
+    }
+
+    transaction
+        .commit(note_root)
+        .with_context(|| "Failed to rewrite the links")
+}
+
+/// What has been done, to be reverted on failure.
+#[derive(Debug, Default)]
+struct Journal {
+    renames: Vec<(PathBuf, PathBuf)>,
+    created_dirs: Vec<PathBuf>,
+    rewrites: Vec<(PathBuf, String)>,
+}
+
+impl Journal {
+    fn rename(&mut self, from: &Path, to: &Path) -> Result<()> {
+        fs::rename(from, to).with_context(|| {
+            format!(
+                "Failed to rename '{}' to '{}'",
+                from.display(),
+                to.display()
+            )
+        })?;
+        self.renames.push((from.to_path_buf(), to.to_path_buf()));
+        Ok(())
+    }
+
+    fn rollback(self) -> Result<()> {
+        for (file, old) in self.rewrites.iter().rev() {
+            atomic::write(file, old)?;
+        }
+        for (from, to) in self.renames.iter().rev() {
+            fs::rename(to, from)?;
+        }
+        for dir in self.created_dirs.iter().rev() {
+            let _ = fs::remove_dir(dir);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_pattern_plan() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        let a = root.join("a.md");
+        fs::write(
+            &a,
+            "---\ntitle: \"Rust: Lifetimes!\"\ndate: \"2025-05-17 12:00:00\"\n---\n",
+        )
+        .unwrap();
+        let dir = root.join("dir");
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("main.typ"), "= Dir\n").unwrap();
+
+        let plan = pattern_plan(&[a.clone(), dir.clone()], "{date}-{slug}").unwrap();
+        assert_eq!(plan[0].1, root.join("2025-05-17-rust-lifetimes.md"));
+        assert!(plan[1].1.to_string_lossy().ends_with("-dir"));
+        assert_eq!(
+            pattern_plan(std::slice::from_ref(&a), "{n}-{name}.typ").unwrap()[0].1,
+            root.join("1-a.typ")
+        );
+        assert!(pattern_plan(std::slice::from_ref(&a), "{nope}").is_err());
+
+        let text = format_plan(root, &plan);
+        assert!(text.contains("a.md -> 2025-05-17-rust-lifetimes.md\n"));
+        assert_eq!(parse_plan(root, &text).unwrap(), plan);
+        assert!(parse_plan(root, "a.md => b.md\n").is_err());
+        assert!(parse_plan(root, "a.md -> a.md\n").unwrap().is_empty());
+        assert!(parse_plan(root, "a.md -> ../a.md\n").is_err());
+    }
+
+    #[test]
+    fn test_execute() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        let (a, b) = (root.join("a.md"), root.join("b.md"));
+        fs::write(&a, "A links [[b]]\n").unwrap();
+        fs::write(&b, "B links [[a]]\n").unwrap();
+        fs::write(root.join("c.md"), "[[a]] and [[b]]\n").unwrap();
+
+        // Two notes mapped to the same target
+        let clash = vec![
+            (a.clone(), root.join("x.md")),
+            (b.clone(), root.join("X.md")),
+        ];
+        assert!(validate(&clash).is_err());
+        assert!(validate(&vec![(a.clone(), root.join("c.md"))]).is_err());
+        assert!(validate(&vec![(a.clone(), root.join("x"))]).is_err());
+
+        // A swap
+        let plan = vec![(a.clone(), b.clone()), (b.clone(), a.clone())];
+        assert_eq!(execute(root, &plan, false).unwrap(), 3);
+        assert_eq!(fs::read_to_string(&a).unwrap(), "B links [[b]]\n");
+        assert_eq!(fs::read_to_string(&b).unwrap(), "A links [[a]]\n");
+        assert_eq!(
+            fs::read_to_string(root.join("c.md")).unwrap(),
+            "[[b]] and [[a]]\n"
+        );
+
+        // Into a new category
+        let plan = vec![(a.clone(), root.join("cat/new.md"))];
+        execute(root, &plan, false).unwrap();
+        assert!(!a.exists());
+        assert_eq!(
+            fs::read_to_string(root.join("c.md")).unwrap(),
+            "[[b]] and [[new]]\n"
+        );
+
+        // A read-only note linking to the renamed one is left alone without --override
+        let locked = "---\nreadonly: true\n---\n[[b]]\n";
+        fs::write(root.join("c.md"), locked).unwrap();
+        let plan = vec![(b.clone(), root.join("d.md"))];
+        assert!(execute(root, &plan, false).is_err());
+        assert!(b.exists());
+        assert_eq!(fs::read_to_string(root.join("c.md")).unwrap(), locked);
+        execute(root, &plan, true).unwrap();
+        assert!(
+            fs::read_to_string(root.join("c.md"))
+                .unwrap()
+                .contains("[[d]]")
+        );
+    }
+}
@@ -0,0 +1,331 @@
+use anyhow::{Result, bail};
+use chrono::{DateTime, Local};
+use serde_json::Value;
+use std::path::Path;
+
+use crate::append;
+use crate::cli::ReviewCommand;
+use crate::meta;
+use crate::metadata::{FieldFilter, NoteMetadata, read_metadata};
+use crate::process::find_note_dir;
+use crate::srs;
+use crate::vault::Note;
+
+/// The metadata field holding who is responsible for a note.
+pub const OWNER_FIELD: &str = "owner";
+/// The metadata field holding who reviews a note.
+pub const REVIEWERS_FIELD: &str = "reviewers";
+/// The metadata field holding the review status of a note: `requested` or `approved`.
+pub const REVIEW_FIELD: &str = "review";
+/// The metadata field holding the reviewers who approved the note.
+pub const APPROVED_BY_FIELD: &str = "approved_by";
+/// The section of a note logging its reviews.
+pub const REVIEW_LOG_HEADING: &str = "Review log";
+
+pub const REQUESTED: &str = "requested";
+pub const APPROVED: &str = "approved";
+
+pub fn process_review_command(command: ReviewCommand) -> Result<()> {
+    let now = Local::now();
+    let today = now.date_naive();
+    match command {
+        ReviewCommand::Add { targets, note_root } => {
+            let added = srs::add(Path::new(&note_root), &targets, today)?;
+            println!("{} note(s) scheduled for review", added.len());
+        }
+        ReviewCommand::Remove {
+            note_path,
+            note_root,
+        } => {
+            let note = find_note_dir(&note_path, &note_root)?;
+            if srs::remove(Path::new(&note_root), &note)? {
+                println!("'{}' is no longer reviewed", note.display());
+            } else {
+                println!("'{}' was not scheduled for review", note.display());
+            }
+        }
+        ReviewCommand::Grade {
+            note_path,
+            grade,
+            note_root,
+        } => {
+            let note = find_note_dir(&note_path, &note_root)?;
+            let schedule = srs::grade(Path::new(&note_root), &note, grade, today)?;
+            println!("Next review of '{}' on {}", note.display(), schedule.due);
+        }
+        ReviewCommand::Request {
+            note_path,
+            reviewers,
+            by,
+            message,
+            note_root,
+            override_lock,
+        } => {
+            let note = find_note_dir(&note_path, &note_root)?;
+            let reviewers = request(
+                &note,
+                &reviewers,
+                by.as_deref(),
+                message.as_deref(),
+                override_lock,
+                now,
+            )?;
+
+            println!(
+                "Review of '{}' requested from {}",
+                note.display(),
+                reviewers.join(", ")
+            );
+        }
+        ReviewCommand::Approve {
+            note_path,
+            by,
+            message,
+            note_root,
+            override_lock,
+        } => {
+            let Some(by) = by else {
+                bail!("Who approves? Give --as NAME or set `author` in the config");
+            };
+            let note = find_note_dir(&note_path, &note_root)?;
+            let pending = approve(&note, &by, message.as_deref(), override_lock, now)?;
+
+            if pending.is_empty() {
+                println!("'{}' is approved", note.display());
+            } else {
+                println!(
+                    "'{}' approved by {}, waiting for {}",
+                    note.display(),
+                    by,
+                    pending.join(", ")
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The filters of `noxe list --owner`, `--reviewer` and `--needs-review`.
+pub fn filters(
+    owner: Option<&str>,
+    reviewer: Option<&str>,
+    needs_review: bool,
+) -> Vec<FieldFilter> {
+    let filter = |key: &str, value: &str| FieldFilter {
+        key: key.to_string(),
+        value: value.to_string(),
+    };
+
+    let mut filters = Vec::new();
+    if let Some(owner) = owner {
+        filters.push(filter(OWNER_FIELD, owner));
+    }
+    if let Some(reviewer) = reviewer {
+        filters.push(filter(REVIEWERS_FIELD, reviewer));
+    }
+    if needs_review {
+        filters.push(filter(REVIEW_FIELD, REQUESTED));
+    }
+    filters
+}
+
+/// Request a review of a note from the reviewers, added to those it already has. Earlier
+/// approvals are dropped, the note is reviewed anew. The note is given to `by` when it has no
+/// owner yet. Returns the reviewers.
+pub fn request(
+    note: &Path,
+    reviewers: &[String],
+    by: Option<&str>,
+    message: Option<&str>,
+    override_lock: bool,
+    now: DateTime<Local>,
+) -> Result<Vec<String>> {
+    let main_file = note.main_file_path()?;
+    let metadata = read_metadata(&main_file)?;
+
+    let mut all = names(&metadata, REVIEWERS_FIELD);
+    for reviewer in reviewers {
+        if !contains(&all, reviewer) {
+            all.push(reviewer.clone());
+        }
+    }
+    if all.is_empty() {
+        bail!(
+            "'{}' has no reviewers, give them or set its `{}` field",
+            note.display(),
+            REVIEWERS_FIELD
+        );
+    }
+
+    let owner = by
+        .filter(|_| metadata.get(OWNER_FIELD).is_none_or(|v| v.is_null()))
+        .map(Value::from);
+    let reviewers_value = Value::from(all.clone());
+    let requested = Value::from(REQUESTED);
+    let mut fields = vec![
+        (REVIEWERS_FIELD, Some(&reviewers_value)),
+        (REVIEW_FIELD, Some(&requested)),
+        (APPROVED_BY_FIELD, None),
+    ];
+    if let Some(owner) = &owner {
+        fields.insert(0, (OWNER_FIELD, Some(owner)));
+    }
+    meta::update_fields(note, &fields, override_lock)?;
+
+    let mut entry = match by {
+        Some(by) => format!("{} requested a review from {}", by, all.join(", ")),
+        None => format!("Review requested from {}", all.join(", ")),
+    };
+    if let Some(message) = message {
+        entry = format!("{}: {}", entry, message);
+    }
+    append::append(&main_file, &entry, Some(REVIEW_LOG_HEADING), Some(now))?;
+
+    Ok(all)
+}
+
+/// Approve a note under review as `by`. The note is approved once all of its reviewers
+/// approved it. Returns the reviewers yet to approve.
+pub fn approve(
+    note: &Path,
+    by: &str,
+    message: Option<&str>,
+    override_lock: bool,
+    now: DateTime<Local>,
+) -> Result<Vec<String>> {
+    let main_file = note.main_file_path()?;
+    let metadata = read_metadata(&main_file)?;
+
+    if metadata
+        .get(REVIEW_FIELD)
+        .is_none_or(|v| v.as_str() != Some(REQUESTED))
+    {
+        bail!("No review of '{}' is requested", note.display());
+    }
+    let reviewers = names(&metadata, REVIEWERS_FIELD);
+    if !contains(&reviewers, by) {
+        bail!(
+            "'{}' is not a reviewer of '{}', its reviewers are {}",
+            by,
+            note.display(),
+            reviewers.join(", ")
+        );
+    }
+
+    let mut approved_by = names(&metadata, APPROVED_BY_FIELD);
+    if !contains(&approved_by, by) {
+        approved_by.push(by.to_string());
+    }
+    let pending: Vec<String> = reviewers
+        .into_iter()
+        .filter(|r| !contains(&approved_by, r))
+        .collect();
+
+    let approved_by = Value::from(approved_by);
+    let approved = Value::from(APPROVED);
+    let mut fields = vec![(APPROVED_BY_FIELD, Some(&approved_by))];
+    if pending.is_empty() {
+        fields.push((REVIEW_FIELD, Some(&approved)));
+    }
+    meta::update_fields(note, &fields, override_lock)?;
+
+    let entry = match message {
+        Some(message) => format!("{} approved: {}", by, message),
+        None => format!("{} approved", by),
+    };
+    append::append(&main_file, &entry, Some(REVIEW_LOG_HEADING), Some(now))?;
+
+    Ok(pending)
+}
+
+/// The names of a field holding people, as a list or comma-separated.
+fn names(metadata: &NoteMetadata, key: &str) -> Vec<String> {
+    match metadata.get(key) {
+        Some(Value::Array(items)) => items
+            .iter()
+            .filter_map(|v| v.as_str())
+            .map(str::to_string)
+            .collect(),
+        Some(Value::String(s)) => s
+            .split(',')
+            .map(|n| n.trim().to_string())
+            .filter(|n| !n.is_empty())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn contains(names: &[String], name: &str) -> bool {
+    names.iter().any(|n| n.eq_ignore_ascii_case(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_review() {
+        let tmp_dir = tempdir().unwrap();
+        let note = tmp_dir.path().join("design.md");
+        fs::write(
+            &note,
+            "---\ntitle: Design\nreviewers: bob\n---\n\n# Design\n",
+        )
+        .unwrap();
+        let now = Local::now();
+
+        // Nothing to approve before a review is requested
+        assert!(approve(&note, "bob", None, false, now).is_err());
+
+        let reviewers = request(
+            &note,
+            &["carol".to_string(), "Bob".to_string()],
+            Some("alice"),
+            Some("please check the API"),
+            false,
+            now,
+        )
+        .unwrap();
+        assert_eq!(reviewers, ["bob", "carol"]);
+        let metadata = read_metadata(&note).unwrap();
+        assert!(
+            filters(Some("Alice"), Some("carol"), true)
+                .iter()
+                .all(|f| f.matches(&metadata))
+        );
+
+        assert!(approve(&note, "dave", None, false, now).is_err());
+        assert_eq!(
+            approve(&note, "bob", Some("LGTM"), false, now).unwrap(),
+            ["carol"]
+        );
+        assert!(
+            approve(&note, "carol", None, false, now)
+                .unwrap()
+                .is_empty()
+        );
+
+        let metadata = read_metadata(&note).unwrap();
+        assert_eq!(metadata.get(REVIEW_FIELD), Some(APPROVED.into()));
+        assert_eq!(
+            metadata.get(APPROVED_BY_FIELD),
+            Some(serde_json::json!(["bob", "carol"]))
+        );
+        assert!(!filters(None, None, true)[0].matches(&metadata));
+
+        let content = fs::read_to_string(&note).unwrap();
+        let log = &content[content.find("## Review log").unwrap()..];
+        assert!(log.contains("alice requested a review from bob, carol: please check the API"));
+        assert!(log.contains("bob approved: LGTM"));
+        assert!(log.contains("carol approved"));
+
+        // A read-only field leaves the note untouched
+        let locked = "---\ntitle: Design\nreviewers: bob\nreadonly: [approved_by]\n---\n";
+        fs::write(&note, locked).unwrap();
+        assert!(request(&note, &[], Some("alice"), None, false, now).is_err());
+        assert_eq!(fs::read_to_string(&note).unwrap(), locked);
+    }
+}
@@ -0,0 +1,188 @@
+use anyhow::{Context, Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::atomic;
+use crate::config::{self, config_path};
+use crate::metadata::FieldFilter;
+
+/// The file next to the config holding the searches of `noxe search --save`, so that saving
+/// does not rewrite the config and its comments.
+const SAVED_SEARCHES_FILE: &str = "searches.yaml";
+
+/// A named search of `noxe search --saved <name>`: the query and the filters of a search,
+/// defined under `searches` in the config or saved with `noxe search --save <name>`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SavedSearch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+    /// Read the query as structured terms, like `--query-lang`
+    #[serde(skip_serializing_if = "is_false")]
+    pub query_lang: bool,
+    /// Also match the query against the metadata, like `--metadata`
+    #[serde(skip_serializing_if = "is_false")]
+    pub metadata: bool,
+    /// Metadata field filters, `KEY=VALUE`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<String>,
+    #[serde(alias = "category", skip_serializing_if = "Vec::is_empty")]
+    pub categories: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_after: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_before: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modified_after: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modified_before: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lang: Option<String>,
+}
+
+fn is_false(b: &bool) -> bool {
+    !b
+}
+
+impl SavedSearch {
+    /// Complete the options of the command line with those of a saved search. Field filters
+    /// add up, the other options given on the command line win.
+    pub fn fill_from(&mut self, saved: &SavedSearch) {
+        let or = |option: &mut Option<String>, saved: &Option<String>| {
+            if option.is_none() {
+                *option = saved.clone();
+            }
+        };
+        or(&mut self.query, &saved.query);
+        self.query_lang |= saved.query_lang;
+        self.metadata |= saved.metadata;
+        self.fields.splice(0..0, saved.fields.iter().cloned());
+        if self.categories.is_empty() {
+            self.categories = saved.categories.clone();
+        }
+        or(&mut self.created_after, &saved.created_after);
+        or(&mut self.created_before, &saved.created_before);
+        or(&mut self.modified_after, &saved.modified_after);
+        or(&mut self.modified_before, &saved.modified_before);
+        or(&mut self.lang, &saved.lang);
+    }
+
+    pub fn field_filters(&self) -> Result<Vec<FieldFilter>> {
+        self.fields
+            .iter()
+            .map(|f| f.parse().map_err(|e: String| anyhow!(e)))
+            .collect()
+    }
+}
+
+/// The saved search named `name`.
+pub fn get(name: &str) -> Result<&'static SavedSearch> {
+    let searches = config::saved_searches();
+    match searches.get(name) {
+        Some(search) => Ok(search),
+        None if searches.is_empty() => bail!(
+            "Unknown saved search '{}': there is none, save one with `noxe search --save <name>`",
+            name
+        ),
+        None => bail!(
+            "Unknown saved search '{}' (expected one of: {})",
+            name,
+            searches.keys().cloned().collect::<Vec<_>>().join(", ")
+        ),
+    }
+}
+
+/// The file of the searches saved with `--save`, next to the config file.
+pub fn saved_searches_path() -> Option<PathBuf> {
+    config_path().map(|p| p.with_file_name(SAVED_SEARCHES_FILE))
+}
+
+/// Read the saved searches of a file. A missing file has none.
+pub fn load(path: &Path) -> Result<BTreeMap<String, SavedSearch>> {
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read saved searches '{}'", path.display()))?;
+    if content.trim().is_empty() {
+        return Ok(BTreeMap::new());
+    }
+    serde_yml::from_str(&content)
+        .with_context(|| format!("Invalid saved searches '{}'", path.display()))
+}
+
+/// Save a search under `name`, replacing a search saved before with that name. Searches
+/// defined in the config are not replaced: they are edited there.
+pub fn save(name: &str, search: &SavedSearch) -> Result<PathBuf> {
+    let Some(path) = saved_searches_path() else {
+        bail!("Cannot find the config directory to save the search in");
+    };
+    let mut searches = load(&path)?;
+    if !searches.contains_key(name) && config::saved_searches().contains_key(name) {
+        bail!(
+            "The search '{}' is defined in the config, edit it there",
+            name
+        );
+    }
+    searches.insert(name.to_string(), search.clone());
+    write(&path, &searches)?;
+    Ok(path)
+}
+
+fn write(path: &Path, searches: &BTreeMap<String, SavedSearch>) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create directory '{}'", dir.display()))?;
+    }
+    atomic::write(path, serde_yml::to_string(searches)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_saved_searches() {
+        let saved = SavedSearch {
+            query: Some("review".to_string()),
+            fields: vec!["status=draft".to_string()],
+            categories: vec!["work".to_string()],
+            created_after: Some("7d".to_string()),
+            ..Default::default()
+        };
+
+        let mut search = SavedSearch {
+            query_lang: true,
+            fields: vec!["course=CS144".to_string()],
+            created_after: Some("2w".to_string()),
+            ..Default::default()
+        };
+        search.fill_from(&saved);
+        assert_eq!(search.query.as_deref(), Some("review"));
+        assert!(search.query_lang);
+        assert_eq!(search.fields, ["status=draft", "course=CS144"]);
+        assert_eq!(search.categories, ["work"]);
+        assert_eq!(search.created_after.as_deref(), Some("2w"));
+        assert_eq!(search.field_filters().unwrap()[1].key, "course");
+
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("noxe").join(SAVED_SEARCHES_FILE);
+        assert!(load(&path).unwrap().is_empty());
+        write(
+            &path,
+            &BTreeMap::from([("weekly".to_string(), saved.clone())]),
+        )
+        .unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(!content.contains("query_lang") && !content.contains("lang:"));
+        assert_eq!(load(&path).unwrap()["weekly"], saved);
+
+        fs::write(&path, "inbox:\n  query: inbox\n  category: [work]\n").unwrap();
+        assert_eq!(load(&path).unwrap()["inbox"].categories, ["work"]);
+    }
+}
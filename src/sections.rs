@@ -0,0 +1,282 @@
+use anyhow::Result;
+use regex::Regex;
+use serde::Serialize;
+use serde_json::Value;
+use std::{path::Path, sync::LazyLock};
+
+use crate::bounded::read_head;
+use crate::cli::NoteType;
+use crate::extract::{extractor, heading_text};
+use crate::vault::Note;
+
+/// The status and tags of a section of a typst note, as labels on its heading:
+/// `= TCP <status:draft> <tag:net>`.
+static TYPST_LABEL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<(status|tags?):([\w.:\-]+)>").unwrap());
+
+/// A trailing org tag list, like `:net:tcp:`.
+static ORG_TAGS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\s+:([\w@#%:]+):\s*$").unwrap());
+
+/// The org keywords taken as the status of a heading, like `* TODO Handshake`.
+const ORG_KEYWORDS: [&str; 7] = [
+    "TODO",
+    "NEXT",
+    "WAIT",
+    "WAITING",
+    "DONE",
+    "CANCELED",
+    "CANCELLED",
+];
+
+/// A section of a note and its annotations, so that large living documents can be tracked
+/// section by section.
+///
+/// A markdown section is annotated with a comment on its heading or on the line after it:
+/// `<!-- status: draft, tags: [net, tcp] -->`. Typst sections use labels on the heading,
+/// `<status:draft>` and `<tag:net>`, or a `// status: draft` comment after it; org sections use
+/// their TODO keyword and tags.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Section {
+    pub heading: String,
+    pub level: usize,
+    /// The line of the heading, from 1
+    pub line: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl Section {
+    pub fn is_annotated(&self) -> bool {
+        self.status.is_some() || !self.tags.is_empty()
+    }
+
+    /// Whether the section has the status and the tag, when given (case-insensitive).
+    pub fn matches(&self, status: Option<&str>, tag: Option<&str>) -> bool {
+        status.is_none_or(|s| {
+            self.status
+                .as_deref()
+                .is_some_and(|st| st.eq_ignore_ascii_case(s))
+        }) && tag.is_none_or(|t| self.tags.iter().any(|tg| tg.eq_ignore_ascii_case(t)))
+    }
+}
+
+/// The sections of a note with their annotations.
+#[derive(Debug, Serialize)]
+pub struct NoteSections {
+    pub note: String,
+    pub sections: Vec<Section>,
+}
+
+/// Read the sections of a note, in its first `limit` bytes. Also returns the notice to print
+/// when the note is longer.
+pub fn note_sections(note: &Path, limit: u64) -> Result<(Vec<Section>, Option<String>)> {
+    let main_file = note.main_file_path()?;
+    let head = read_head(&main_file, limit)?;
+    Ok((sections(&head.text, main_file.note_type()?), head.notice()))
+}
+
+/// The sections of the content, in order.
+pub fn sections(content: &str, note_type: NoteType) -> Vec<Section> {
+    let lines: Vec<&str> = content.lines().collect();
+    extractor(note_type)
+        .headings(&lines)
+        .into_iter()
+        .map(|(i, level)| {
+            let next = lines[i + 1..].iter().find(|l| !l.trim().is_empty());
+            let mut section = Section {
+                heading: heading_text(lines[i]).to_string(),
+                level,
+                line: i + 1,
+                status: None,
+                tags: Vec::new(),
+            };
+            match note_type {
+                NoteType::Md => annotate_md(&mut section, next.copied()),
+                NoteType::Typ => annotate_typ(&mut section, next.copied()),
+                NoteType::Org => annotate_org(&mut section),
+                NoteType::Custom(_) => {}
+            }
+            section
+        })
+        .collect()
+}
+
+/// The section a line (from 1) is in: the innermost section above it, with the status and
+/// tags it inherits from the sections around it. `None` before the first heading.
+pub fn enclosing(sections: &[Section], line: usize) -> Option<Section> {
+    let mut path: Vec<&Section> = Vec::new();
+    for section in sections.iter().take_while(|s| s.line <= line) {
+        while path.last().is_some_and(|s| s.level >= section.level) {
+            path.pop();
+        }
+        path.push(section);
+    }
+
+    let (innermost, parents) = path.split_last()?;
+    let mut section = (*innermost).clone();
+    for parent in parents.iter().rev() {
+        if section.status.is_none() {
+            section.status = parent.status.clone();
+        }
+        for tag in &parent.tags {
+            if !section.tags.contains(tag) {
+                section.tags.push(tag.clone());
+            }
+        }
+    }
+    Some(section)
+}
+
+fn annotate_md(section: &mut Section, next: Option<&str>) {
+    let comment = |line: &str| {
+        let start = line.find("<!--")?;
+        let end = line[start..].find("-->")? + start;
+        Some((start, line[start + 4..end].to_string()))
+    };
+
+    if let Some((start, inner)) = comment(&section.heading.clone()) {
+        section.heading = section.heading[..start].trim_end().to_string();
+        apply(section, &inner);
+    } else if let Some(next) = next.map(str::trim)
+        && next.starts_with("<!--")
+        && let Some((_, inner)) = comment(next)
+    {
+        apply(section, &inner);
+    }
+}
+
+fn annotate_typ(section: &mut Section, next: Option<&str>) {
+    let heading = section.heading.clone();
+    for label in TYPST_LABEL.captures_iter(&heading) {
+        match &label[1] {
+            "status" => section.status = Some(label[2].to_string()),
+            _ => section.tags.push(label[2].to_string()),
+        }
+    }
+    section.heading = TYPST_LABEL.replace_all(&heading, "").trim().to_string();
+
+    if !section.is_annotated()
+        && let Some(comment) = next.and_then(|l| l.trim().strip_prefix("//"))
+    {
+        apply(section, comment);
+    }
+}
+
+fn annotate_org(section: &mut Section) {
+    let mut heading = section.heading.clone();
+    if let Some(tags) = ORG_TAGS.captures(&heading) {
+        section.tags = tags[1]
+            .split(':')
+            .filter(|t| !t.is_empty())
+            .map(str::to_string)
+            .collect();
+        heading = ORG_TAGS.replace(&heading, "").to_string();
+    }
+    if let Some((keyword, rest)) = heading.split_once(' ')
+        && ORG_KEYWORDS.contains(&keyword)
+    {
+        section.status = Some(keyword.to_string());
+        heading = rest.to_string();
+    }
+    section.heading = heading.trim().to_string();
+}
+
+/// Apply an annotation written as the inside of a YAML flow mapping, like
+/// `status: draft, tags: [net, tcp]`. Anything else, like an ordinary comment, is ignored.
+fn apply(section: &mut Section, annotation: &str) {
+    let Ok(Value::Object(fields)) = serde_yml::from_str::<Value>(&format!("{{{}}}", annotation))
+    else {
+        return;
+    };
+    let text = |v: &Value| match v {
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    };
+
+    if let Some(status) = fields.get("status").filter(|v| !v.is_null()) {
+        section.status = Some(text(status));
+    }
+    match fields.get("tags").or_else(|| fields.get("tag")) {
+        Some(Value::Array(tags)) => section.tags = tags.iter().map(text).collect(),
+        Some(Value::String(tags)) => {
+            section.tags = tags
+                .split(',')
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty())
+                .collect()
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn annotations(sections: &[Section]) -> Vec<(&str, Option<&str>, Vec<&str>)> {
+        sections
+            .iter()
+            .map(|s| {
+                (
+                    s.heading.as_str(),
+                    s.status.as_deref(),
+                    s.tags.iter().map(String::as_str).collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sections() {
+        let md = "# Net\n\n## TCP <!-- status: draft, tags: [net, tcp] -->\n\n## UDP\n\n<!-- status: done -->\ntext\n\n## QUIC\n<!-- not an annotation -->\n";
+        let sections = sections(md, NoteType::Md);
+        assert_eq!(
+            annotations(&sections),
+            [
+                ("Net", None, vec![]),
+                ("TCP", Some("draft"), vec!["net", "tcp"]),
+                ("UDP", Some("done"), vec![]),
+                ("QUIC", None, vec![]),
+            ]
+        );
+        assert_eq!((sections[1].level, sections[1].line), (2, 3));
+        assert!(sections[1].matches(Some("Draft"), Some("tcp")));
+        assert!(!sections[2].matches(None, Some("tcp")));
+
+        let typ =
+            "= TCP <status:review> <tag:net>\n== Handshake\n// status: draft, tags: [syn, ack]\n";
+        assert_eq!(
+            annotations(&super::sections(typ, NoteType::Typ)),
+            [
+                ("TCP", Some("review"), vec!["net"]),
+                ("Handshake", Some("draft"), vec!["syn", "ack"]),
+            ]
+        );
+
+        let org = "* TODO Handshake :net:tcp:\n** Notes\n";
+        assert_eq!(
+            annotations(&super::sections(org, NoteType::Org)),
+            [
+                ("Handshake", Some("TODO"), vec!["net", "tcp"]),
+                ("Notes", None, vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_enclosing() {
+        let md = "intro\n# Net <!-- tags: [net] -->\n## TCP <!-- status: draft, tags: [tcp] -->\ntext\n### Handshake\ntext\n## UDP\n";
+        let sections = sections(md, NoteType::Md);
+
+        assert_eq!(enclosing(&sections, 1), None);
+        let handshake = enclosing(&sections, 6).unwrap();
+        assert_eq!(handshake.heading, "Handshake");
+        assert_eq!(handshake.status.as_deref(), Some("draft"));
+        assert_eq!(handshake.tags, ["tcp", "net"]);
+        let udp = enclosing(&sections, 7).unwrap();
+        assert_eq!((udp.heading.as_str(), udp.status), ("UDP", None));
+        assert_eq!(udp.tags, ["net"]);
+    }
+}
@@ -0,0 +1,295 @@
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::ai::{Client, Usage};
+use crate::cli::NoteType;
+use crate::events::Progress;
+use crate::extract::extractor;
+use crate::state::StateStore;
+use crate::vault::Note;
+
+pub const EMBEDDINGS: &str = "embeddings";
+
+/// Notes are split into chunks of about this many characters, at paragraph boundaries.
+const CHUNK_CHARS: usize = 2_000;
+/// Only the beginning of huge notes is embedded.
+const MAX_CHUNKS: usize = 32;
+/// The number of chunks sent in one request, the lowest limit of the built-in providers.
+const BATCH_SIZE: usize = 10;
+
+/// The embedding of a note, one vector per chunk. Embeddings are keyed by the note path
+/// relative to the note root.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Embedding {
+    pub model: String,
+    /// The modification time of the main file when it was embedded
+    #[serde(default)]
+    pub modified: Option<SystemTime>,
+    #[serde(default)]
+    pub chunks: Vec<Vec<f32>>,
+}
+
+impl Embedding {
+    /// Whether the embedding was computed by `model` from the current version of the note.
+    pub fn is_fresh(&self, model: &str, modified: Option<SystemTime>) -> bool {
+        self.model == model
+            && modified.is_some()
+            && self.modified == modified
+            && !self.chunks.is_empty()
+    }
+}
+
+pub type Embeddings = BTreeMap<String, Embedding>;
+
+#[derive(Debug)]
+pub struct SemanticOptions {
+    pub provider: String,
+    /// The embedding model, the provider's default if `None`
+    pub embedding_model: Option<String>,
+}
+
+/// Rank the notes by similarity to the query, best first. Notes edited since they were last
+/// embedded are embedded again first, and the cache in the state store is updated.
+pub fn search(
+    note_root: &Path,
+    notes: &[PathBuf],
+    query: &str,
+    options: &SemanticOptions,
+) -> Result<Vec<(PathBuf, f32)>> {
+    if query.trim().is_empty() {
+        bail!("The query is empty");
+    }
+    let client = Client::new(&options.provider)?;
+    let Some(model) = options
+        .embedding_model
+        .clone()
+        .or_else(|| client.provider().embedding_model.map(str::to_string))
+    else {
+        bail!(
+            "'{}' has no default embedding model, specify one with --embedding-model",
+            client.provider().name
+        );
+    };
+
+    let store = StateStore::new(note_root);
+    let mut embeddings: Embeddings = store.load(EMBEDDINGS)?;
+
+    let stale: Vec<(&PathBuf, String, Option<SystemTime>)> = notes
+        .iter()
+        .filter_map(|note| {
+            let key = note_key(note_root, note);
+            let modified = modified(note);
+            let fresh = embeddings
+                .get(&key)
+                .is_some_and(|e| e.is_fresh(&model, modified));
+            (!fresh).then_some((note, key, modified))
+        })
+        .collect();
+
+    if !stale.is_empty() {
+        eprintln!("Embedding {} new or changed note(s)...", stale.len());
+    }
+    let events = Progress::start("embed", stale.len());
+    for (note, key, modified) in stale {
+        events.item_started(&key);
+        let result = embed_note(&client, &model, note);
+        events.item_finished(&key, result.as_ref().err());
+
+        match result {
+            Ok((chunks, _)) => {
+                embeddings.insert(
+                    key,
+                    Embedding {
+                        model: model.clone(),
+                        modified,
+                        chunks,
+                    },
+                );
+            }
+            Err(e) => {
+                // Keep what has been computed so far
+                store.save(EMBEDDINGS, &embeddings)?;
+                return Err(e).with_context(|| format!("Failed to embed '{}'", note.display()));
+            }
+        }
+    }
+    events.finish();
+    store.save(EMBEDDINGS, &embeddings)?;
+
+    let (query, _) = embed(&client, &model, query)?;
+    let query = query.first().context("The query is empty")?;
+    let candidates = notes
+        .iter()
+        .filter_map(|note| Some((note, embeddings.get(&note_key(note_root, note))?)));
+
+    Ok(rank(candidates, query))
+}
+
+/// Score the candidates by the best cosine similarity of their chunks to the query.
+fn rank<'a>(
+    candidates: impl Iterator<Item = (&'a PathBuf, &'a Embedding)>,
+    query: &[f32],
+) -> Vec<(PathBuf, f32)> {
+    let mut ranked: Vec<(PathBuf, f32)> = candidates
+        .filter_map(|(note, embedding)| {
+            let score = embedding
+                .chunks
+                .iter()
+                .map(|chunk| cosine_similarity(chunk, query))
+                .reduce(f32::max)?;
+            Some((note.clone(), score))
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    ranked
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 { 0.0 } else { dot / norms }
+}
+
+fn embed_note(client: &Client, model: &str, note: &Path) -> Result<(Vec<Vec<f32>>, Usage)> {
+    let main_file = note.main_file_path()?;
+    let content = fs::read_to_string(&main_file)
+        .with_context(|| format!("Failed to read note '{}'", main_file.display()))?;
+
+    embed(
+        client,
+        model,
+        &embedding_text(&content, main_file.note_type()?),
+    )
+}
+
+/// Embed a text chunk by chunk, returning one vector per chunk.
+pub fn embed(client: &Client, model: &str, text: &str) -> Result<(Vec<Vec<f32>>, Usage)> {
+    let mut vectors = Vec::new();
+    let mut usage = Usage::default();
+
+    for batch in chunks(text).chunks(BATCH_SIZE) {
+        let (batch, u) = client.embed(model, batch)?;
+        vectors.extend(batch);
+        usage += u;
+    }
+
+    Ok((vectors, usage))
+}
+
+/// The title and body of a note, as embedded.
+pub fn embedding_text(content: &str, note_type: NoteType) -> String {
+    let extract = extractor(note_type);
+    let body = extract.body(content);
+    match extract.metadata(content).title {
+        Some(title) => format!("{}\n\n{}", title, body),
+        None => body,
+    }
+}
+
+/// Split a text into chunks of at most `CHUNK_CHARS` characters, keeping paragraphs together
+/// when they fit.
+fn chunks(text: &str) -> Vec<String> {
+    let mut chunks: Vec<String> = Vec::new();
+    let mut current = String::new();
+
+    for paragraph in text.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+        let len = paragraph.chars().count();
+        if !current.is_empty() && current.chars().count() + len + 2 > CHUNK_CHARS {
+            chunks.push(std::mem::take(&mut current));
+        }
+
+        if len > CHUNK_CHARS {
+            let chars: Vec<char> = paragraph.chars().collect();
+            chunks.extend(chars.chunks(CHUNK_CHARS).map(|c| c.iter().collect()));
+            continue;
+        }
+
+        if !current.is_empty() {
+            current.push_str("\n\n");
+        }
+        current.push_str(paragraph);
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    if chunks.is_empty() {
+        // Providers reject empty inputs, but an empty note still gets an embedding
+        chunks.push(" ".to_string());
+    }
+
+    chunks.truncate(MAX_CHUNKS);
+    chunks
+}
+
+/// The path of the note relative to the note root, as used in the state store.
+pub fn note_key(note_root: &Path, note: &Path) -> String {
+    note.strip_prefix(note_root)
+        .unwrap_or(note)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+/// The modification time of the main file of a note.
+pub fn modified(note: &Path) -> Option<SystemTime> {
+    let main_file = note.main_file_path().ok()?;
+    fs::metadata(main_file).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunks() {
+        assert_eq!(chunks("a\n\n\n\nb\n"), ["a\n\nb"]);
+        assert_eq!(chunks(""), [" "]);
+
+        let long = "x".repeat(CHUNK_CHARS + 1);
+        let text = format!(
+            "intro\n\n{}\n\n{}\n\nend",
+            "y".repeat(CHUNK_CHARS - 4),
+            long
+        );
+        let chunks = chunks(&text);
+        assert_eq!(chunks.len(), 5);
+        assert_eq!(chunks[0], "intro");
+        assert_eq!(chunks[2].len(), CHUNK_CHARS);
+        assert_eq!(chunks[3], "x");
+        assert_eq!(chunks[4], "end");
+    }
+
+    #[test]
+    fn test_rank() {
+        let embedding = |chunks: Vec<Vec<f32>>| Embedding {
+            model: "m".into(),
+            modified: None,
+            chunks,
+        };
+        let a = PathBuf::from("a.md");
+        let b = PathBuf::from("b.md");
+        let ea = embedding(vec![vec![0.0, 1.0], vec![1.0, 1.0]]);
+        let eb = embedding(vec![vec![1.0, 0.0]]);
+
+        let ranked = rank([(&a, &ea), (&b, &eb)].into_iter(), &[1.0, 0.0]);
+        assert_eq!(ranked[0].0, b);
+        assert!((ranked[0].1 - 1.0).abs() < 1e-6);
+        assert_eq!(ranked[1].0, a);
+        assert!((ranked[1].1 - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-6);
+
+        let time = SystemTime::UNIX_EPOCH;
+        let fresh = Embedding {
+            modified: Some(time),
+            ..embedding(vec![vec![1.0]])
+        };
+        assert!(fresh.is_fresh("m", Some(time)));
+        assert!(!fresh.is_fresh("other", Some(time)));
+        assert!(!fresh.is_fresh("m", None));
+    }
+}
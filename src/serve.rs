@@ -0,0 +1,271 @@
+use anyhow::{Context, Result};
+use std::{
+    fs,
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    path::{Component, Path, PathBuf},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::cli::NoteType;
+use crate::markdown::{self, escape};
+use crate::metadata::{parse_metadata, strip_metadata};
+
+/// How often the note is checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Comments sent on idle event streams, so that closed connections are noticed.
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+pub(crate) const STYLE: &str = "body{max-width:46em;margin:2em auto;padding:0 1em;font:16px/1.6 sans-serif;color:#222}\
+pre{background:#f5f5f5;padding:.8em;overflow:auto}code{background:#f5f5f5;padding:0 .2em}\
+pre code{padding:0}blockquote{margin:0;padding-left:1em;border-left:4px solid #ddd;color:#555}\
+table{border-collapse:collapse}th,td{border:1px solid #ccc;padding:.3em .6em}img{max-width:100%}";
+
+/// Serve a markdown note as HTML on localhost, reloading the page in the browser whenever the
+/// note is saved. Files next to the note (images...) are served too. Runs until interrupted.
+pub fn serve(main_file: &Path, port: u16) -> Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .with_context(|| format!("Failed to listen on port {}", port))?;
+    println!(
+        "Serving '{}' at http://{}/ (Ctrl-C to stop)",
+        main_file.display(),
+        listener.local_addr()?
+    );
+    run(listener, main_file)
+}
+
+fn run(listener: TcpListener, main_file: &Path) -> Result<()> {
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let main_file = main_file.to_path_buf();
+        // Event streams stay open, so each connection has its own thread
+        thread::spawn(move || {
+            if let Err(e) = handle(stream, &main_file) {
+                tracing::debug!("Preview connection failed: {:#}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+fn handle(mut stream: TcpStream, main_file: &Path) -> Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // The headers are not needed
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && !header.trim().is_empty() {
+        header.clear();
+    }
+
+    let target = request.split_whitespace().nth(1).unwrap_or("/");
+    let path = target.split(['?', '#']).next().unwrap_or_default();
+    match path {
+        "/" => respond(
+            &mut stream,
+            "200 OK",
+            "text/html; charset=utf-8",
+            page(main_file).as_bytes(),
+        ),
+        "/events" => events(&mut stream, main_file),
+        _ => {
+            let dir = main_file.parent().unwrap_or(Path::new("."));
+            match asset_path(dir, path).and_then(|p| fs::read(&p).ok().map(|b| (p, b))) {
+                Some((asset, body)) => respond(&mut stream, "200 OK", content_type(&asset), &body),
+                None => respond(&mut stream, "404 Not Found", "text/plain", b"Not found"),
+            }
+        }
+    }
+}
+
+fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &[u8]) -> Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    Ok(())
+}
+
+/// Server-sent events: a `reload` event once the note is modified, after which the page
+/// reloads and opens a new stream.
+fn events(stream: &mut TcpStream, main_file: &Path) -> Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\n\r\n"
+    )?;
+    stream.flush()?;
+
+    let modified = |p: &Path| fs::metadata(p).and_then(|m| m.modified()).ok();
+    let initial: Option<SystemTime> = modified(main_file);
+    let mut last_write = Instant::now();
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        if modified(main_file) != initial {
+            write!(stream, "data: reload\n\n")?;
+            stream.flush()?;
+            return Ok(());
+        }
+        if last_write.elapsed() >= KEEP_ALIVE {
+            // Fails once the page is closed
+            write!(stream, ": ping\n\n")?;
+            stream.flush()?;
+            last_write = Instant::now();
+        }
+    }
+}
+
+/// The HTML page of the note.
+fn page(main_file: &Path) -> String {
+    let (title, body) = match fs::read_to_string(main_file) {
+        Ok(content) => {
+            let title = parse_metadata(&content, NoteType::Md).title;
+            let body = strip_metadata(&content, NoteType::Md);
+            (title, markdown::to_html(&markdown::parse(&body)))
+        }
+        Err(e) => (
+            None,
+            format!(
+                "<p>Failed to read '{}': {}</p>\n",
+                escape(&main_file.display().to_string()),
+                escape(&e.to_string())
+            ),
+        ),
+    };
+    let title = title.unwrap_or_else(|| {
+        main_file
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string()
+    });
+
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n<article>\n{}</article>\n<script>new EventSource(\"/events\").onmessage = () => location.reload();</script>\n</body>\n</html>\n",
+        escape(&title),
+        STYLE,
+        body
+    )
+}
+
+/// The file under `dir` requested by `url_path`, refusing paths leaving `dir`.
+fn asset_path(dir: &Path, url_path: &str) -> Option<PathBuf> {
+    let relative = PathBuf::from(percent_decode(url_path.trim_start_matches('/')));
+    if relative
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_)))
+    {
+        return None;
+    }
+    Some(dir.join(relative))
+}
+
+pub(crate) fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = (bytes[i] == b'%')
+            .then(|| text.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match hex {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+fn content_type(path: &Path) -> &'static str {
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "html" => "text/html; charset=utf-8",
+        "css" => "text/css",
+        "js" => "text/javascript",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        "pdf" => "application/pdf",
+        "txt" | "md" => "text/plain; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use tempfile::tempdir;
+
+    fn get(addr: &str, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_serve() {
+        let tmp_dir = tempdir().unwrap();
+        let note = tmp_dir.path().join("my note.md");
+        fs::write(&note, "---\ntitle: \"A <b>\"\n---\n# Hello\n").unwrap();
+        fs::write(tmp_dir.path().join("logo.svg"), "<svg/>").unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let main_file = note.clone();
+        thread::spawn(move || run(listener, &main_file));
+
+        let page = get(&addr, "/");
+        assert!(page.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(page.contains("<title>A &lt;b&gt;</title>"));
+        assert!(page.contains("<h1>Hello</h1>"));
+        assert!(page.contains("new EventSource(\"/events\")"));
+
+        let asset = get(&addr, "/logo.svg?v=1");
+        assert!(asset.contains("Content-Type: image/svg+xml\r\n"));
+        assert!(asset.ends_with("<svg/>"));
+        assert!(get(&addr, "/../secret").starts_with("HTTP/1.1 404"));
+        assert!(get(&addr, "/%2e%2e/secret").starts_with("HTTP/1.1 404"));
+
+        // Saving the note sends a reload event
+        let mut stream = TcpStream::connect(&addr).unwrap();
+        write!(stream, "GET /events HTTP/1.1\r\n\r\n").unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+            line.clear();
+        }
+        thread::sleep(POLL_INTERVAL * 2);
+        let later = SystemTime::now() + Duration::from_secs(5);
+        fs::File::options()
+            .append(true)
+            .open(&note)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line, "data: reload\n");
+    }
+}
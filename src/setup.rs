@@ -0,0 +1,232 @@
+use anyhow::{Context, Result, bail};
+use std::{
+    fs,
+    io::{self, BufRead, IsTerminal, Write},
+    path::{Path, PathBuf},
+};
+
+use clap::CommandFactory;
+
+use crate::cli::{Cli, NoteType};
+use crate::config::config_path;
+use crate::state::StateStore;
+use crate::template::TemplateStore;
+
+/// Templates offered by the setup, in the format of `noxe template add`.
+const STARTER_TEMPLATES: [(&str, &str); 2] = [
+    (
+        "meeting",
+        "paths: {}\n\
+         main.md: \"## Attendees\\n\\n## Agenda\\n\\n## Decisions\\n\\n## Action items\\n\"\n\
+         main.typ: \"== Attendees\\n\\n== Agenda\\n\\n== Decisions\\n\\n== Action items\\n\"\n",
+    ),
+    (
+        "paper",
+        "paths:\n  images: {}\n  bibliography:\n    refs.bib: \"\"\n\
+         main.md: \"## Summary\\n\\n## Method\\n\\n## Critique\\n\"\n\
+         main.typ: \"== Summary\\n\\n== Method\\n\\n== Critique\\n\\n#bibliography(\\\"bibliography/refs.bib\\\")\\n\"\n",
+    ),
+];
+
+const CHEAT_SHEET: &str = "\
+Core commands:
+  noxe new rust.md         create a note (a typst dirnote without an extension)
+  noxe list                list the notes
+  noxe search ownership    search the names and metadata of the notes
+  noxe edit rust.md        open a note in the editor
+  noxe preview rust.md     preview a note
+  noxe journal             open today's journal note
+  noxe capture some idea   append to the inbox note
+  noxe tui                 browse the vault
+Run `noxe help <command>` for the options of a command.
+";
+
+/// `noxe` without a command: the guided setup when no vault is configured and a user is at
+/// the terminal, the help otherwise.
+pub fn first_run() -> Result<()> {
+    let configured = std::env::var_os("NOXE_ROOT").is_some();
+    if configured || !io::stdin().is_terminal() {
+        Cli::command().print_help()?;
+        return Ok(());
+    }
+
+    let Some(config) = config_path() else {
+        bail!("Cannot determine the config directory");
+    };
+    let home = dirs::home_dir().context("Cannot determine the home directory")?;
+    setup(
+        &mut io::stdin().lock(),
+        &mut io::stdout(),
+        &config,
+        &home,
+        &TemplateStore::new(None)?,
+    )
+}
+
+/// Ask for the vault directory, the author and the default note type, then write them to the
+/// config file, keeping what it already has.
+fn setup(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    config: &Path,
+    home: &Path,
+    templates: &TemplateStore,
+) -> Result<()> {
+    writeln!(
+        output,
+        "Welcome to noxe! No vault is configured yet, let's set one up.\n"
+    )?;
+
+    let default_root = home.join("notes");
+    let root = ask(
+        input,
+        output,
+        "Vault directory",
+        &default_root.display().to_string(),
+    )?;
+    let root = match root.strip_prefix("~/") {
+        Some(rest) => home.join(rest),
+        None => PathBuf::from(root),
+    };
+    fs::create_dir_all(&root)
+        .with_context(|| format!("Failed to create directory '{}'", root.display()))?;
+    let root = fs::canonicalize(&root).unwrap_or(root);
+    StateStore::new(&root).init()?;
+
+    let default_author = std::env::var("USER").unwrap_or_default();
+    let author = ask(input, output, "Author", &default_author)?;
+
+    let note_type = loop {
+        let answer = ask(input, output, "Default note type (typ, md or org)", "typ")?;
+        match NoteType::try_from(answer.as_str()) {
+            Ok(_) => break answer,
+            Err(_) => writeln!(output, "Unknown note type '{}'", answer)?,
+        }
+    };
+
+    let install = ask(
+        input,
+        output,
+        "Install the starter templates (meeting, paper)? [y/n]",
+        "y",
+    )?;
+    if install.eq_ignore_ascii_case("y") || install.eq_ignore_ascii_case("yes") {
+        let existing = templates.names()?;
+        for (name, content) in STARTER_TEMPLATES {
+            // Keep the user's own template of the same name
+            if existing.iter().any(|n| n == name) {
+                writeln!(output, "Template '{}' exists, kept", name)?;
+            } else {
+                templates.add(name, content, false)?;
+            }
+        }
+    }
+
+    let mut lines = format!("note_root: {}\n", quote(&root.display().to_string()));
+    if !author.is_empty() {
+        lines.push_str(&format!("author: {}\n", quote(&author)));
+    }
+    lines.push_str(&format!("note_type: {}\n", quote(&note_type)));
+    append_config(config, &lines)?;
+
+    writeln!(
+        output,
+        "\nVault '{}' is ready, settings saved in '{}'.\n\n{}",
+        root.display(),
+        config.display(),
+        CHEAT_SHEET
+    )?;
+    Ok(())
+}
+
+/// Ask a question, returning the default on an empty answer.
+fn ask(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    question: &str,
+    default: &str,
+) -> Result<String> {
+    if default.is_empty() {
+        write!(output, "{}: ", question)?;
+    } else {
+        write!(output, "{} [{}]: ", question, default)?;
+    }
+    output.flush()?;
+
+    let mut answer = String::new();
+    if input
+        .read_line(&mut answer)
+        .with_context(|| "Failed to read user input")?
+        == 0
+    {
+        bail!("Setup aborted");
+    }
+    let answer = answer.trim();
+    Ok(if answer.is_empty() { default } else { answer }.to_string())
+}
+
+/// A YAML string. JSON strings are valid YAML.
+fn quote(s: &str) -> String {
+    serde_json::to_string(s).unwrap()
+}
+
+/// Add top-level keys to the config file, creating it if needed. The setup only runs when the
+/// vault is not configured, so the keys are not in the file yet.
+fn append_config(config: &Path, lines: &str) -> Result<()> {
+    if let Some(parent) = config.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory '{}'", parent.display()))?;
+    }
+    let mut content = fs::read_to_string(config).unwrap_or_default();
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    content.push_str(lines);
+    fs::write(config, content)
+        .with_context(|| format!("Failed to write config '{}'", config.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_setup() {
+        let tmp_dir = tempdir().unwrap();
+        let home = tmp_dir.path();
+        let config = home.join("config/noxe/config.yaml");
+        fs::create_dir_all(config.parent().unwrap()).unwrap();
+        fs::write(&config, "editor: nvim").unwrap();
+        let templates = TemplateStore::new(Some(home.join("templates"))).unwrap();
+        templates.add("paper", "paths: {}\n", false).unwrap();
+
+        // The vault under the home, the author "Ann", an invalid then the default type
+        let mut input = io::Cursor::new("~/vault\nAnn\ntxt\n\n\n");
+        let mut output = Vec::new();
+        setup(&mut input, &mut output, &config, home, &templates).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Unknown note type 'txt'"));
+        assert!(output.contains("Template 'paper' exists, kept"));
+        assert!(output.contains("noxe new"));
+
+        let root = fs::canonicalize(home.join("vault")).unwrap();
+        assert!(root.join(".noxe").is_dir());
+        assert_eq!(templates.names().unwrap(), ["meeting", "paper"]);
+        assert_eq!(templates.get("paper").unwrap(), "paths: {}\n");
+        assert!(templates.load("meeting").is_ok());
+
+        let content = fs::read_to_string(&config).unwrap();
+        let (parsed, _) = parse(&content).unwrap();
+        assert_eq!(parsed.note_root, Some(root));
+        assert_eq!(parsed.author.as_deref(), Some("Ann"));
+        assert_eq!(parsed.note_type.as_deref(), Some("typ"));
+        assert_eq!(parsed.editor.as_deref(), Some("nvim"));
+
+        // End of input aborts
+        let mut input = io::Cursor::new("");
+        assert!(setup(&mut input, &mut Vec::new(), &config, home, &templates).is_err());
+    }
+}
@@ -0,0 +1,156 @@
+use anyhow::{Context, Result};
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+use crate::export::output_path;
+use crate::index::NoteIndex;
+use crate::metadata::NoteMetadata;
+use crate::vault::{Note, note_metadata, notes_in};
+
+/// The first line of sidecar files, so that only them are ever removed.
+const MARKER: &str = "noxe sidecar";
+
+pub const DEFAULT_DIR: &str = "sidecars";
+
+#[derive(Debug, Default, PartialEq)]
+pub struct SidecarReport {
+    pub written: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+}
+
+/// Write a plain text file per note under `output_dir`, mirroring the categories, with the
+/// title and keywords of the note and its path, for OS indexers (Spotlight, Tracker,
+/// Everything) that index file content but do not understand the note formats.
+///
+/// Only the sidecars whose content changed are written, and those of deleted notes are
+/// removed, so running it again is cheap, all the more with the index of the vault.
+pub fn sync_sidecars(note_root: &Path, output_dir: &Path) -> Result<SidecarReport> {
+    let index = NoteIndex::open(note_root)?;
+    let mut report = SidecarReport::default();
+    let mut expected = HashSet::new();
+
+    for note in notes_in(index.as_ref(), note_root)? {
+        let Some(metadata) = note_metadata(index.as_ref(), note_root, &note) else {
+            continue;
+        };
+        let path = output_path(note_root, &note, output_dir, "txt");
+        let content = sidecar_content(&note, &metadata);
+
+        if fs::read_to_string(&path).is_ok_and(|old| old == content) {
+            report.unchanged += 1;
+        } else {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).with_context(|| {
+                    format!("Failed to create directory '{}'", parent.display())
+                })?;
+            }
+            fs::write(&path, content)
+                .with_context(|| format!("Failed to write sidecar '{}'", path.display()))?;
+            report.written += 1;
+        }
+        expected.insert(path);
+    }
+
+    report.removed = remove_sidecars(output_dir, &expected)?;
+    Ok(report)
+}
+
+fn sidecar_content(note: &Path, metadata: &NoteMetadata) -> String {
+    let path = fs::canonicalize(note).unwrap_or(note.to_path_buf());
+    let mut content = format!(
+        "{}\nTitle: {}\nTags: {}\n",
+        MARKER,
+        metadata.title.clone().unwrap_or_else(|| note.note_name()),
+        metadata.keywords.join(", ")
+    );
+    if let Some(author) = &metadata.author {
+        content.push_str(&format!("Author: {}\n", author));
+    }
+    if let Some(date) = &metadata.date {
+        content.push_str(&format!("Date: {}\n", date));
+    }
+    content.push_str(&format!("Note: {}\n", path.display()));
+    content
+}
+
+/// Remove the sidecars under `output_dir` not in `keep`, and the directories left empty.
+/// Returns the number of removed sidecars.
+pub fn remove_sidecars(output_dir: &Path, keep: &HashSet<PathBuf>) -> Result<usize> {
+    if !output_dir.is_dir() {
+        return Ok(0);
+    }
+
+    let mut removed = 0;
+    // Children first, so that emptied directories can be removed
+    for entry in WalkDir::new(output_dir).min_depth(1).contents_first(true) {
+        let entry = entry.with_context(|| format!("Failed to walk '{}'", output_dir.display()))?;
+        let path = entry.path();
+        if entry.file_type().is_dir() {
+            // Fails if not empty
+            let _ = fs::remove_dir(path);
+        } else if path.extension().is_some_and(|ext| ext == "txt")
+            && !keep.contains(path)
+            && fs::read_to_string(path).is_ok_and(|c| c.starts_with(MARKER))
+        {
+            fs::remove_file(path)
+                .with_context(|| format!("Failed to remove sidecar '{}'", path.display()))?;
+            removed += 1;
+        }
+    }
+
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_sync_sidecars() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path().join("notes");
+        let out = tmp_dir.path().join("sidecars");
+        fs::create_dir_all(root.join("lang")).unwrap();
+        fs::write(
+            root.join("lang/rust.md"),
+            "---\ntitle: \"Ownership\"\nkeywords: [rust, memory]\n---\n",
+        )
+        .unwrap();
+        fs::write(root.join("plain.md"), "text\n").unwrap();
+
+        let report = sync_sidecars(&root, &out).unwrap();
+        assert_eq!(report.written, 2);
+        let sidecar = fs::read_to_string(out.join("lang/rust.txt")).unwrap();
+        assert!(sidecar.starts_with("noxe sidecar\nTitle: Ownership\nTags: rust, memory\n"));
+        assert!(sidecar.contains("rust.md\n"));
+        assert!(
+            fs::read_to_string(out.join("plain.txt"))
+                .unwrap()
+                .contains("Title: plain\n")
+        );
+
+        // Unchanged notes are not written again; the sidecars of deleted notes are removed,
+        // other files are left alone
+        fs::write(out.join("mine.txt"), "my own file\n").unwrap();
+        fs::remove_file(root.join("lang/rust.md")).unwrap();
+        let report = sync_sidecars(&root, &out).unwrap();
+        assert_eq!(
+            report,
+            SidecarReport {
+                written: 0,
+                unchanged: 1,
+                removed: 1,
+            }
+        );
+        assert!(!out.join("lang").exists());
+        assert!(out.join("mine.txt").exists());
+
+        assert_eq!(remove_sidecars(&out, &HashSet::new()).unwrap(), 1);
+    }
+}
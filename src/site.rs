@@ -0,0 +1,418 @@
+use anyhow::{Context, Result, bail};
+use regex::{Captures, Regex};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write as _,
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+    sync::LazyLock,
+};
+
+use crate::cli::NoteType;
+use crate::events::Progress;
+use crate::export::ExportSettings;
+use crate::extract::NoteText;
+use crate::links::LinkIndex;
+use crate::markdown::{self, escape};
+use crate::semantic::note_key;
+use crate::serve::STYLE;
+use crate::vault::Note;
+
+/// `[[target]]`, `[[target#heading]]` and `[[target|alias]]`
+static WIKI_LINK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\[\[([^\]\|#]+)(?:#[^\]\|]*)?(?:\|([^\]]*))?\]\]").unwrap());
+
+/// A note of the site.
+#[derive(Debug, Clone)]
+pub struct Page {
+    pub note: PathBuf,
+    /// The path of the page relative to the site root, with `/` separators: `lang/rust.html`
+    /// for a filenote, `lang/tokio/index.html` for a dirnote
+    pub url: String,
+    pub title: String,
+    /// The category of the note, `""` at the note root
+    pub category: String,
+}
+
+impl Page {
+    pub fn new(note_root: &Path, note: &Path) -> Self {
+        let key = note_key(note_root, note);
+        let url = if note.is_dirnote() {
+            format!("{}/index.html", key)
+        } else {
+            Path::new(&key)
+                .with_extension("html")
+                .to_string_lossy()
+                .replace('\\', "/")
+        };
+        let category = key.rsplit_once('/').map_or("", |(c, _)| c).to_string();
+        let title = note
+            .main_file_path()
+            .and_then(|main_file| NoteText::read(&main_file))
+            .ok()
+            .and_then(|text| text.metadata().title)
+            .unwrap_or_else(|| note.note_name());
+
+        Page {
+            note: note.to_path_buf(),
+            url,
+            title,
+            category,
+        }
+    }
+}
+
+/// Render the notes into a static HTML site under `out_dir`: a page per note mirroring its path
+/// relative to the note root, with the files it links to, and an `index.html` listing them by
+/// category. Markdown notes are rendered to HTML with their wiki-links resolved, typst notes
+/// compiled to one SVG image per page. A failing note does not stop the others; the failures
+/// are reported at the end.
+pub fn publish(note_root: &Path, notes: &[PathBuf], out_dir: &Path) -> Result<Vec<Page>> {
+    let links = LinkIndex::new(note_root)?;
+    let pages: Vec<Page> = notes
+        .iter()
+        .map(|note| Page::new(note_root, note))
+        .collect();
+    let urls: HashMap<String, &str> = pages
+        .iter()
+        .map(|page| (note_key(note_root, &page.note), page.url.as_str()))
+        .collect();
+    let site_title = site_title(note_root);
+
+    let mut published = Vec::new();
+    let mut failed = 0;
+    let events = Progress::start("publish", pages.len());
+    for page in &pages {
+        let key = note_key(note_root, &page.note);
+        events.item_started(&key);
+        let result = publish_page(note_root, page, out_dir, &site_title, &|target: &str| {
+            let note = links.resolve(target)?;
+            urls.get(&note_key(note_root, note))
+                .map(|url| relative_url(&page.url, url))
+        });
+        events.item_finished(&key, result.as_ref().err());
+
+        match result {
+            Ok(()) => published.push(page.clone()),
+            Err(e) => {
+                eprintln!("Failed to publish '{}': {:#}", page.note.display(), e);
+                failed += 1;
+            }
+        }
+    }
+    events.finish();
+
+    write_file(
+        &out_dir.join("index.html"),
+        &index_html(&site_title, &published),
+    )?;
+    if failed > 0 {
+        bail!("{} of {} note(s) failed to publish", failed, pages.len());
+    }
+    Ok(published)
+}
+
+/// The title of the site: the name of the note root.
+pub fn site_title(note_root: &Path) -> String {
+    fs::canonicalize(note_root)
+        .ok()
+        .and_then(|root| root.file_name().map(|n| n.to_string_lossy().to_string()))
+        .unwrap_or_else(|| "Notes".to_string())
+}
+
+fn publish_page(
+    note_root: &Path,
+    page: &Page,
+    out_dir: &Path,
+    site_title: &str,
+    link_url: &dyn Fn(&str) -> Option<String>,
+) -> Result<()> {
+    let main_file = page.note.main_file_path()?;
+    let output = out_dir.join(&page.url);
+    let text = NoteText::read(&main_file)?;
+
+    let body = match main_file.note_type()? {
+        NoteType::Md => {
+            let body = resolve_wiki_links(&text.body(), link_url);
+            markdown::to_html(&markdown::parse(&body))
+        }
+        NoteType::Typ => typst_pages(&main_file, &output)?,
+        NoteType::Org | NoteType::Custom(_) => {
+            format!("<pre>{}</pre>\n", escape(text.body().trim_matches('\n')))
+        }
+    };
+    copy_linked_files(note_root, &main_file, &text, out_dir)?;
+
+    write_file(&output, &page_html(site_title, page, &body))
+}
+
+/// Turn the wiki-links of Markdown into links to the pages of their notes. Unresolved links are
+/// left as their text.
+pub fn resolve_wiki_links(content: &str, link_url: &dyn Fn(&str) -> Option<String>) -> String {
+    WIKI_LINK
+        .replace_all(content, |c: &Captures| {
+            let target = c[1].trim();
+            let text = c.get(2).map_or(target, |alias| alias.as_str().trim());
+            match link_url(target) {
+                Some(url) => format!("[{}]({})", text, url.replace(' ', "%20")),
+                None => text.to_string(),
+            }
+        })
+        .into_owned()
+}
+
+/// Compile a typst note to an SVG image per page next to its page, returning the HTML showing
+/// them.
+fn typst_pages(main_file: &Path, output: &Path) -> Result<String> {
+    let dir = output.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create directory '{}'", dir.display()))?;
+    let stem = output
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+
+    let settings = ExportSettings::read(main_file)?;
+    let status = match process::Command::new("typst")
+        .arg("compile")
+        .args(settings.typst_inputs())
+        .args(["--format", "svg"])
+        .arg(main_file)
+        .arg(dir.join(format!("{}-{{p}}.svg", stem)))
+        .status()
+    {
+        Ok(status) => status,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            bail!("'typst' is not installed or not in PATH")
+        }
+        Err(e) => return Err(e).context("Failed to run 'typst'"),
+    };
+    if !status.success() {
+        bail!("'typst' exited with {}", status);
+    }
+
+    let mut html = String::new();
+    for page in (1..).take_while(|p| dir.join(format!("{}-{}.svg", stem, p)).is_file()) {
+        let _ = writeln!(
+            html,
+            "<img class=\"page\" src=\"{}-{}.svg\" alt=\"Page {}\">",
+            escape(&stem),
+            page,
+            page
+        );
+    }
+    Ok(html)
+}
+
+/// Copy the files under the note root that a note links to, images or PDFs, to the same place
+/// under `out_dir`, so that the relative links of its page keep working.
+fn copy_linked_files(
+    note_root: &Path,
+    main_file: &Path,
+    text: &NoteText,
+    out_dir: &Path,
+) -> Result<()> {
+    let root = fs::canonicalize(note_root)
+        .with_context(|| format!("Failed to read '{}'", note_root.display()))?;
+    let dir = main_file.parent().unwrap_or(Path::new("."));
+
+    for target in text.path_links() {
+        let Ok(source) = fs::canonicalize(dir.join(&target)) else {
+            continue;
+        };
+        let Ok(relative) = source.strip_prefix(&root) else {
+            continue;
+        };
+        if !source.is_file() || source.note_type().is_ok() {
+            continue;
+        }
+
+        let dest = out_dir.join(relative);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory '{}'", parent.display()))?;
+        }
+        fs::copy(&source, &dest).with_context(|| {
+            format!(
+                "Failed to copy '{}' to '{}'",
+                source.display(),
+                dest.display()
+            )
+        })?;
+    }
+    Ok(())
+}
+
+/// The URL of `to` from the page at `from`, both relative to the site root.
+pub fn relative_url(from: &str, to: &str) -> String {
+    format!("{}{}", "../".repeat(from.matches('/').count()), to)
+}
+
+/// The id of the section of a category on the index.
+fn category_id(category: &str) -> String {
+    if category.is_empty() {
+        "notes".to_string()
+    } else {
+        format!("category-{}", category.replace('/', "-"))
+    }
+}
+
+fn page_html(site_title: &str, page: &Page, body: &str) -> String {
+    let index = relative_url(&page.url, "index.html");
+    let mut nav = format!("<a href=\"{}\">{}</a>", escape(&index), escape(site_title));
+    let mut category = String::new();
+    for part in page.category.split('/').filter(|p| !p.is_empty()) {
+        if !category.is_empty() {
+            category.push('/');
+        }
+        category.push_str(part);
+        let _ = write!(
+            nav,
+            " / <a href=\"{}#{}\">{}</a>",
+            escape(&index),
+            escape(&category_id(&category)),
+            escape(part)
+        );
+    }
+
+    html_document(
+        &format!("{} - {}", page.title, site_title),
+        &format!("<nav>{}</nav>\n<article>\n{}</article>\n", nav, body),
+    )
+}
+
+/// The index of the site: the pages by category, the notes of the note root first.
+fn index_html(site_title: &str, pages: &[Page]) -> String {
+    let mut categories: BTreeMap<&str, Vec<&Page>> = BTreeMap::new();
+    for page in pages {
+        categories.entry(&page.category).or_default().push(page);
+    }
+
+    let mut body = format!("<h1>{}</h1>\n", escape(site_title));
+    if categories.len() > 1 {
+        body.push_str("<nav><ul>\n");
+        for category in categories.keys().filter(|c| !c.is_empty()) {
+            let _ = writeln!(
+                body,
+                "<li><a href=\"#{}\">{}</a></li>",
+                escape(&category_id(category)),
+                escape(category)
+            );
+        }
+        body.push_str("</ul></nav>\n");
+    }
+    for (category, pages) in &mut categories {
+        pages.sort_by_key(|page| page.title.to_lowercase());
+        let _ = writeln!(body, "<section id=\"{}\">", escape(&category_id(category)));
+        if !category.is_empty() {
+            let _ = writeln!(body, "<h2>{}</h2>", escape(category));
+        }
+        body.push_str("<ul>\n");
+        for page in pages.iter() {
+            let _ = writeln!(
+                body,
+                "<li><a href=\"{}\">{}</a></li>",
+                escape(&page.url),
+                escape(&page.title)
+            );
+        }
+        body.push_str("</ul>\n</section>\n");
+    }
+
+    html_document(site_title, &body)
+}
+
+fn html_document(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}nav{{margin-bottom:1em}}img.page{{display:block;margin:1em 0;border:1px solid #ddd}}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape(title),
+        STYLE,
+        body
+    )
+}
+
+fn write_file(path: &Path, content: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory '{}'", parent.display()))?;
+    }
+    fs::write(path, content).with_context(|| format!("Failed to write '{}'", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_resolve_wiki_links() {
+        let link_url = |target: &str| (target == "rust").then(|| "../lang/rust.html".to_string());
+        assert_eq!(
+            resolve_wiki_links(
+                "See [[rust]], [[rust#traits|traits]] and [[missing]]",
+                &link_url
+            ),
+            "See [rust](../lang/rust.html), [traits](../lang/rust.html) and missing"
+        );
+    }
+
+    #[test]
+    fn test_relative_url() {
+        assert_eq!(
+            relative_url("index.html", "lang/rust.html"),
+            "lang/rust.html"
+        );
+        assert_eq!(
+            relative_url("lang/tokio/index.html", "lang/rust.html"),
+            "../../lang/rust.html"
+        );
+    }
+
+    #[test]
+    fn test_publish() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path().join("notes");
+        fs::create_dir_all(root.join("lang/tokio")).unwrap();
+        fs::write(
+            root.join("lang/rust.md"),
+            "---\ntitle: Rust\n---\n\nUses [[tokio]] ![logo](logo.png)\n",
+        )
+        .unwrap();
+        fs::write(root.join("lang/logo.png"), "png").unwrap();
+        fs::write(
+            root.join("lang/tokio/main.md"),
+            "# Tokio\n\nBack to [[rust]]\n",
+        )
+        .unwrap();
+        fs::write(root.join("todo.org"), "* TODO <publish>\n").unwrap();
+        let notes = [
+            root.join("lang/rust.md"),
+            root.join("lang/tokio"),
+            root.join("todo.org"),
+        ];
+
+        let out = tmp_dir.path().join("site");
+        let pages = publish(&root, &notes, &out).unwrap();
+        assert_eq!(pages.len(), 3);
+
+        let rust = fs::read_to_string(out.join("lang/rust.html")).unwrap();
+        assert!(rust.contains("<title>Rust - notes</title>"));
+        assert!(rust.contains("href=\"../lang/tokio/index.html\""));
+        assert!(rust.contains("href=\"../index.html#category-lang\""));
+        assert_eq!(
+            fs::read_to_string(out.join("lang/logo.png")).unwrap(),
+            "png"
+        );
+
+        let tokio = fs::read_to_string(out.join("lang/tokio/index.html")).unwrap();
+        assert!(tokio.contains("href=\"../../lang/rust.html\""));
+        let org = fs::read_to_string(out.join("todo.html")).unwrap();
+        assert!(org.contains("<pre>* TODO &lt;publish&gt;</pre>"));
+
+        let index = fs::read_to_string(out.join("index.html")).unwrap();
+        assert!(index.contains("<a href=\"lang/rust.html\">Rust</a>"));
+        assert!(index.contains("<section id=\"category-lang\">"));
+    }
+}
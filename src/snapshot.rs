@@ -0,0 +1,188 @@
+use anyhow::{Result, bail};
+use std::path::{Path, PathBuf};
+
+use crate::sync::{git, run_git};
+
+/// A snapshot of the vault: a commit of its git repository, like those of `noxe sync`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub commit: String,
+    /// The commit date, `YYYY-MM-DD HH:MM`
+    pub date: String,
+    pub message: String,
+}
+
+impl Snapshot {
+    pub fn short(&self) -> &str {
+        &self.commit[..self.commit.len().min(8)]
+    }
+}
+
+/// A file or directory of a snapshot, relative to the note root.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotEntry {
+    pub path: PathBuf,
+    pub is_dir: bool,
+}
+
+/// The snapshots of the vault, the newest first.
+pub fn snapshots(note_root: &Path) -> Result<Vec<Snapshot>> {
+    if !git(note_root, &["rev-parse", "--is-inside-work-tree"])?
+        .status
+        .success()
+    {
+        bail!(
+            "'{}' is not a git repository, its snapshots are the commits of `noxe sync`",
+            note_root.display()
+        );
+    }
+    // A repository without commits has no HEAD
+    if !git(note_root, &["rev-parse", "--verify", "--quiet", "HEAD"])?
+        .status
+        .success()
+    {
+        return Ok(Vec::new());
+    }
+
+    let log = run_git(note_root, &["log", "--format=%H%x1f%ci%x1f%s", "--", "."])?;
+    Ok(log
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\x1f');
+            let commit = fields.next()?.to_string();
+            let date = fields.next()?.get(..16)?.to_string();
+            let message = fields.next().unwrap_or_default().to_string();
+            Some(Snapshot {
+                commit,
+                date,
+                message,
+            })
+        })
+        .collect())
+}
+
+/// The files and directories of `dir` in a snapshot, directories first. Hidden ones are left
+/// out, like in the browser.
+pub fn entries(note_root: &Path, commit: &str, dir: &Path) -> Result<Vec<SnapshotEntry>> {
+    let dir = if dir.as_os_str().is_empty() {
+        "./".to_string()
+    } else {
+        format!("{}/", dir.display())
+    };
+    let tree = run_git(note_root, &["ls-tree", "-z", commit, "--", &dir])?;
+
+    let mut entries: Vec<SnapshotEntry> = tree
+        .split('\0')
+        .filter_map(|line| {
+            let (info, path) = line.split_once('\t')?;
+            let path = PathBuf::from(path);
+            if path
+                .file_name()
+                .is_some_and(|n| n.to_string_lossy().starts_with('.'))
+            {
+                return None;
+            }
+            Some(SnapshotEntry {
+                path,
+                is_dir: info.split(' ').nth(1) == Some("tree"),
+            })
+        })
+        .collect();
+    entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.path.cmp(&b.path)));
+    Ok(entries)
+}
+
+/// The changes of a file or directory since the snapshot, as a unified diff. Empty when
+/// nothing changed.
+pub fn diff(note_root: &Path, commit: &str, path: &Path) -> Result<String> {
+    let path = path.to_string_lossy();
+    run_git(note_root, &["diff", "--no-color", commit, "--", &path])
+}
+
+/// Restore a file or directory as it was in the snapshot. Files created since are kept.
+pub fn restore(note_root: &Path, commit: &str, path: &Path) -> Result<()> {
+    let source = format!("--source={}", commit);
+    let path = path.to_string_lossy();
+    run_git(note_root, &["restore", &source, "--worktree", "--", &path])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_snapshots() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        assert!(snapshots(root).is_err());
+        if !git(root, &["init", "--quiet"]).is_ok_and(|o| o.status.success()) {
+            return;
+        }
+        assert!(snapshots(root).unwrap().is_empty());
+
+        let commit = |message: &str| {
+            run_git(root, &["add", "--all"]).unwrap();
+            run_git(
+                root,
+                &[
+                    "-c",
+                    "user.name=noxe",
+                    "-c",
+                    "user.email=noxe@example.com",
+                    "commit",
+                    "--quiet",
+                    "-m",
+                    message,
+                ],
+            )
+            .unwrap();
+        };
+        fs::create_dir_all(root.join("cat")).unwrap();
+        fs::write(root.join("cat/a.md"), "one\n").unwrap();
+        fs::write(root.join("b.md"), "b\n").unwrap();
+        commit("first");
+        fs::write(root.join("cat/a.md"), "two\n").unwrap();
+        commit("second");
+
+        let snapshots = snapshots(root).unwrap();
+        assert_eq!(
+            snapshots
+                .iter()
+                .map(|s| s.message.as_str())
+                .collect::<Vec<_>>(),
+            ["second", "first"]
+        );
+        let first = &snapshots[1].commit;
+        assert_eq!(
+            entries(root, first, Path::new("")).unwrap(),
+            [
+                SnapshotEntry {
+                    path: "cat".into(),
+                    is_dir: true
+                },
+                SnapshotEntry {
+                    path: "b.md".into(),
+                    is_dir: false
+                },
+            ]
+        );
+        assert_eq!(
+            entries(root, first, Path::new("cat")).unwrap()[0].path,
+            Path::new("cat/a.md")
+        );
+
+        fs::write(root.join("cat/a.md"), "three\n").unwrap();
+        fs::remove_file(root.join("b.md")).unwrap();
+        let changes = diff(root, first, Path::new("cat/a.md")).unwrap();
+        assert!(changes.contains("-one") && changes.contains("+three"));
+
+        restore(root, first, Path::new("cat")).unwrap();
+        restore(root, first, Path::new("b.md")).unwrap();
+        assert_eq!(fs::read_to_string(root.join("cat/a.md")).unwrap(), "one\n");
+        assert_eq!(fs::read_to_string(root.join("b.md")).unwrap(), "b\n");
+        assert!(diff(root, first, Path::new("b.md")).unwrap().is_empty());
+    }
+}
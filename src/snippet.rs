@@ -0,0 +1,328 @@
+use anyhow::{Context, Result, bail};
+use std::{
+    fs,
+    io::{self, IsTerminal, Read, Write},
+    path::PathBuf,
+};
+
+use crate::cli::{NoteType, SnippetCommand};
+use crate::extract::{extractor, heading_text};
+use crate::picker;
+use crate::process::find_note_dir;
+use crate::vault::Note;
+
+pub fn process_snippet_command(command: SnippetCommand) -> Result<()> {
+    match command {
+        SnippetCommand::Add {
+            name,
+            file,
+            force,
+            snippet_dir,
+        } => {
+            let store = SnippetStore::new(snippet_dir)?;
+
+            let content = match file {
+                Some(file) => fs::read_to_string(&file)
+                    .with_context(|| format!("Failed to read '{}'", file.display()))?,
+                None => {
+                    let mut content = String::new();
+                    io::stdin()
+                        .read_to_string(&mut content)
+                        .with_context(|| "Failed to read the snippet from stdin")?;
+                    content
+                }
+            };
+
+            store.add(&name, &content, force)?;
+            println!("Snippet '{}' saved", name);
+        }
+        SnippetCommand::List { snippet_dir } => {
+            let store = SnippetStore::new(snippet_dir)?;
+
+            for name in store.names()? {
+                let first_line = store.get(&name)?.lines().next().unwrap_or("").to_string();
+                println!("{:<20} {}", name, first_line);
+            }
+        }
+        SnippetCommand::Insert {
+            name,
+            note_path,
+            heading,
+            note_root,
+            snippet_dir,
+        } => {
+            let store = SnippetStore::new(snippet_dir)?;
+            let name = store.select(&name)?;
+            let snippet = store.get(&name)?;
+
+            let main_file = find_note_dir(&note_path, &note_root)?.main_file_path()?;
+            let content = fs::read_to_string(&main_file)
+                .with_context(|| format!("Failed to read note '{}'", main_file.display()))?;
+            let content = insert_snippet(
+                &content,
+                main_file.note_type()?,
+                &snippet,
+                heading.as_deref(),
+            )?;
+            fs::write(&main_file, content)
+                .with_context(|| format!("Failed to write note '{}'", main_file.display()))?;
+
+            println!("Inserted '{}' into '{}'", name, main_file.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Reusable blocks of text, one file per snippet, shared by all vaults.
+#[derive(Debug)]
+pub struct SnippetStore {
+    dir: PathBuf,
+}
+
+impl SnippetStore {
+    /// Open the store at `dir`, or `noxe/snippets` under the user config directory.
+    pub fn new(dir: Option<PathBuf>) -> Result<Self> {
+        let dir = match dir {
+            Some(dir) => dir,
+            None => dirs::config_dir()
+                .context("Cannot determine the config directory")?
+                .join("noxe")
+                .join("snippets"),
+        };
+        Ok(SnippetStore { dir })
+    }
+
+    pub fn add(&self, name: &str, content: &str, force: bool) -> Result<()> {
+        if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+            bail!("Invalid snippet name '{}'", name);
+        }
+
+        let path = self.dir.join(name);
+        if path.exists() && !force {
+            bail!(
+                "Snippet '{}' already exists, use --force to replace it",
+                name
+            );
+        }
+
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create directory '{}'", self.dir.display()))?;
+        fs::write(&path, content)
+            .with_context(|| format!("Failed to write snippet '{}'", path.display()))
+    }
+
+    pub fn get(&self, name: &str) -> Result<String> {
+        let path = self.dir.join(name);
+        fs::read_to_string(&path).with_context(|| format!("No snippet named '{}'", name))
+    }
+
+    pub fn names(&self) -> Result<Vec<String>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&self.dir)
+            .with_context(|| format!("Failed to read directory '{}'", self.dir.display()))?
+        {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if entry.file_type()?.is_file() && !name.starts_with('.') {
+                names.push(name);
+            }
+        }
+        names.sort();
+
+        Ok(names)
+    }
+
+    /// Resolve `query` to a snippet name: an exact name wins, otherwise the snippets are
+    /// fuzzy-matched and the user picks one if several match, with the fuzzy picker when run in
+    /// a terminal.
+    pub fn select(&self, query: &str) -> Result<String> {
+        let names = self.names()?;
+        if names.iter().any(|n| n == query) {
+            return Ok(query.to_string());
+        }
+
+        let mut matches: Vec<(i64, &String)> = names
+            .iter()
+            .filter_map(|name| fuzzy_score(query, name).map(|score| (score, name)))
+            .collect();
+        matches.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(b.1)));
+
+        match matches.as_slice() {
+            [] => bail!("No snippet matches '{}'", query),
+            [(_, name)] => Ok(name.to_string()),
+            _ if io::stdin().is_terminal() && io::stderr().is_terminal() => {
+                let preview = |i: usize, height: usize| {
+                    self.get(&names[i])
+                        .map(|s| s.lines().take(height).map(str::to_string).collect())
+                        .unwrap_or_default()
+                };
+                match picker::pick_with(" Snippets ", &names, query, preview)? {
+                    Some(i) => Ok(names[i].clone()),
+                    None => bail!("No snippet picked"),
+                }
+            }
+            _ => {
+                let candidates: Vec<&String> = matches.iter().map(|(_, n)| *n).collect();
+                prompt_choice(&candidates).map(|n| n.to_string())
+            }
+        }
+    }
+}
+
+/// Score `candidate` if it contains the characters of `query` in order (case-insensitive).
+/// Consecutive characters and matches at word starts score higher.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut pos = 0;
+    let mut prev_match: Option<usize> = None;
+
+    for q in query.to_lowercase().chars() {
+        let i = pos + candidate[pos..].iter().position(|&c| c == q)?;
+
+        score += 1;
+        if prev_match.is_some_and(|p| p + 1 == i) {
+            score += 5;
+        }
+        if i == 0 || !candidate[i - 1].is_alphanumeric() {
+            score += 3;
+        }
+        // Prefer compact matches, without letting a long gap outweigh a word start
+        score -= (i - pos).min(3) as i64;
+
+        prev_match = Some(i);
+        pos = i + 1;
+    }
+
+    Some(score)
+}
+
+fn prompt_choice<'a>(candidates: &[&'a String]) -> Result<&'a String> {
+    eprintln!("Multiple snippets match:");
+    for (i, candidate) in candidates.iter().enumerate() {
+        eprintln!("{}. {}", i + 1, candidate);
+    }
+    eprint!("Enter the number of the snippet (default is 1): ");
+    io::stderr().flush()?;
+
+    let mut input = String::new();
+    io::stdin()
+        .read_line(&mut input)
+        .with_context(|| "Failed to read user input")?;
+    let choice = input.trim().parse::<usize>().unwrap_or(1);
+
+    if choice == 0 || choice > candidates.len() {
+        bail!("Choice out of range");
+    }
+
+    Ok(candidates[choice - 1])
+}
+
+/// Insert the snippet at the end of the section under `heading`, or at the end of the note.
+pub fn insert_snippet(
+    content: &str,
+    note_type: NoteType,
+    snippet: &str,
+    heading: Option<&str>,
+) -> Result<String> {
+    let lines: Vec<&str> = content.lines().collect();
+
+    let at = match heading {
+        None => lines.len(),
+        Some(heading) => match section_end(&lines, note_type, heading) {
+            Some(at) => at,
+            None => bail!("No heading '{}' in the note", heading),
+        },
+    };
+
+    let mut result: Vec<&str> = lines[..at].to_vec();
+    if result.last().is_some_and(|l| !l.trim().is_empty()) {
+        result.push("");
+    }
+    result.extend(snippet.trim_end_matches('\n').lines());
+    if at < lines.len() && !lines[at].trim().is_empty() {
+        result.push("");
+    }
+    result.extend(&lines[at..]);
+
+    Ok(result.join("\n") + "\n")
+}
+
+/// The index of the line after the last non-blank line of the section under `heading`, if the
+/// note has the heading. Blank lines before the next heading are left after it.
+pub fn section_end(lines: &[&str], note_type: NoteType, heading: &str) -> Option<usize> {
+    let headings = extractor(note_type).headings(lines);
+    let &(start, level) = headings
+        .iter()
+        .find(|(i, _)| heading_text(lines[*i]).eq_ignore_ascii_case(heading.trim()))?;
+
+    let end = headings
+        .iter()
+        .find(|(i, l)| *i > start && *l <= level)
+        .map_or(lines.len(), |(i, _)| *i);
+    Some(
+        (start + 1..end)
+            .rev()
+            .find(|i| !lines[*i].trim().is_empty())
+            .map_or(start + 1, |i| i + 1),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_insert_snippet() {
+        let md = "# A\n\ntext\n\n## B\n\n```\n# not a heading\n```\n\n# C\n";
+        assert_eq!(
+            insert_snippet(md, NoteType::Md, "| x |\n", Some("a")).unwrap(),
+            "# A\n\ntext\n\n## B\n\n```\n# not a heading\n```\n\n| x |\n\n# C\n"
+        );
+        assert_eq!(
+            insert_snippet(md, NoteType::Md, "| x |\n", Some("B")).unwrap(),
+            "# A\n\ntext\n\n## B\n\n```\n# not a heading\n```\n\n| x |\n\n# C\n"
+        );
+        assert_eq!(
+            insert_snippet("= T\nbody", NoteType::Typ, "#import \"x\"", None).unwrap(),
+            "= T\nbody\n\n#import \"x\"\n"
+        );
+        assert_eq!(
+            insert_snippet("= T\n== S\nx\n", NoteType::Typ, "y", Some("T")).unwrap(),
+            "= T\n== S\nx\n\ny\n"
+        );
+        assert!(insert_snippet(md, NoteType::Md, "x", Some("missing")).is_err());
+    }
+
+    #[test]
+    fn test_snippet_store() {
+        let tmp_dir = tempdir().unwrap();
+        let store = SnippetStore::new(Some(tmp_dir.path().join("snippets"))).unwrap();
+        assert!(store.names().unwrap().is_empty());
+
+        store.add("rust-block", "```rust\n```\n", false).unwrap();
+        store
+            .add("typst-preamble", "#set text(lang: \"zh\")\n", false)
+            .unwrap();
+        assert!(store.add("rust-block", "", false).is_err());
+        assert!(store.add("../x", "", false).is_err());
+
+        assert_eq!(store.names().unwrap(), ["rust-block", "typst-preamble"]);
+        assert_eq!(store.select("rust-block").unwrap(), "rust-block");
+        assert_eq!(store.select("tpre").unwrap(), "typst-preamble");
+        assert!(store.select("zzz").is_err());
+    }
+
+    #[test]
+    fn test_fuzzy_score() {
+        assert!(fuzzy_score("rb", "rust-block").unwrap() > fuzzy_score("rb", "rubber").unwrap());
+        assert!(fuzzy_score("tab", "md-table").unwrap() > fuzzy_score("tab", "t-a-b").unwrap());
+        assert_eq!(fuzzy_score("x", "rust"), None);
+    }
+}
@@ -0,0 +1,145 @@
+use clap::ValueEnum;
+use std::{cmp::Reverse, path::Path, time::SystemTime};
+
+use crate::dates::created;
+use crate::index::NoteIndex;
+use crate::record;
+use crate::vault::note_metadata;
+
+/// A key of `noxe list --sort`. The times and the size put the newest and largest notes
+/// first, the others sort alphabetically.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortKey {
+    Name,
+    /// The directory of the note
+    Category,
+    Created,
+    #[value(alias = "modified")]
+    Updated,
+    Size,
+    /// The title in the metadata, else the name
+    Title,
+    /// The date in the metadata. Notes without one come last
+    Date,
+}
+
+impl SortKey {
+    /// Whether the key orders the notes from the newest, so that only the first of them are
+    /// listed when it leads.
+    pub fn is_recency(self) -> bool {
+        matches!(self, SortKey::Created | SortKey::Updated | SortKey::Date)
+    }
+}
+
+/// The value of a note for a key. A key always gives the same variant, so that the values
+/// compare key by key.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum SortValue {
+    Text(String),
+    Time(Reverse<Option<SystemTime>>),
+    Size(Reverse<u64>),
+    Date(Reverse<Option<String>>),
+}
+
+/// Sort the notes by the keys, the later keys breaking the ties of the earlier ones.
+/// `reverse` reverses the whole order. The values are computed once per note, so that each file
+/// is read or stat'ed once rather than on every comparison.
+pub fn sort_notes(
+    index: Option<&NoteIndex>,
+    note_root: &Path,
+    notes: &mut [&Path],
+    keys: &[SortKey],
+    reverse: bool,
+) {
+    notes.sort_by_cached_key(|note| {
+        keys.iter()
+            .map(|key| value(index, note_root, note, *key))
+            .collect::<Vec<_>>()
+    });
+    if reverse {
+        notes.reverse();
+    }
+}
+
+fn value(index: Option<&NoteIndex>, note_root: &Path, note: &Path, key: SortKey) -> SortValue {
+    let name = || {
+        note.file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string()
+    };
+    match key {
+        SortKey::Name => SortValue::Text(name()),
+        SortKey::Category => SortValue::Text(
+            note.strip_prefix(note_root)
+                .unwrap_or(note)
+                .parent()
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_default(),
+        ),
+        SortKey::Created => SortValue::Time(Reverse(note.metadata().ok().map(|m| created(&m)))),
+        SortKey::Updated => {
+            SortValue::Time(Reverse(note.metadata().and_then(|m| m.modified()).ok()))
+        }
+        SortKey::Size => SortValue::Size(Reverse(record::size(note))),
+        SortKey::Title => SortValue::Text(
+            note_metadata(index, note_root, note)
+                .and_then(|m| m.title)
+                .unwrap_or_else(|| {
+                    note.file_stem()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .to_string()
+                })
+                .to_lowercase(),
+        ),
+        SortKey::Date => SortValue::Date(Reverse(
+            note_metadata(index, note_root, note).and_then(|m| m.date),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_sort_notes() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        fs::create_dir_all(root.join("rust")).unwrap();
+        fs::create_dir_all(root.join("go")).unwrap();
+        let write = |path: &str, content: &str| {
+            fs::write(root.join(path), content).unwrap();
+            root.join(path)
+        };
+        let a = write("rust/a.md", "---\ndate: 2025-01-02\n---\nshort\n");
+        let b = write(
+            "rust/b.md",
+            "---\ndate: 2025-03-04\n---\nmuch longer content\n",
+        );
+        let c = write("go/c.md", "---\ntitle: Alpha\n---\n");
+
+        let mut notes = vec![a.as_path(), b.as_path(), c.as_path()];
+        sort_notes(
+            None,
+            root,
+            &mut notes,
+            &[SortKey::Category, SortKey::Name],
+            false,
+        );
+        assert_eq!(notes, [c.as_path(), a.as_path(), b.as_path()]);
+
+        sort_notes(None, root, &mut notes, &[SortKey::Date], false);
+        assert_eq!(notes, [b.as_path(), a.as_path(), c.as_path()]);
+
+        sort_notes(None, root, &mut notes, &[SortKey::Size], true);
+        assert_eq!(notes, [c.as_path(), a.as_path(), b.as_path()]);
+
+        sort_notes(None, root, &mut notes, &[SortKey::Title], false);
+        assert_eq!(notes, [a.as_path(), c.as_path(), b.as_path()]);
+        assert!(SortKey::Updated.is_recency() && !SortKey::Size.is_recency());
+    }
+}
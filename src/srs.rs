@@ -0,0 +1,268 @@
+use anyhow::{Context, Result, bail};
+use chrono::{Days, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::flashcards::target_notes;
+use crate::process::exec_with;
+use crate::state::StateStore;
+use crate::vault::Note;
+
+/// The state file of the review schedules, `.noxe/review.json`.
+const REVIEW: &str = "review";
+/// The ease of a note never reviewed, and the lowest one, as in SM-2.
+const INITIAL_EASE: f64 = 2.5;
+const MIN_EASE: f64 = 1.3;
+
+/// The review schedule of a note, following the SM-2 algorithm.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Schedule {
+    /// The day of the next review, `YYYY-MM-DD`
+    pub due: String,
+    /// The days between the last review and the next
+    pub interval: u64,
+    /// The reviews recalled in a row
+    pub repetitions: u32,
+    pub ease: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_review: Option<String>,
+}
+
+/// The schedules by note, relative to the note root.
+pub type Schedules = BTreeMap<PathBuf, Schedule>;
+
+impl Schedule {
+    /// A note to review first today.
+    pub fn new(today: NaiveDate) -> Self {
+        Schedule {
+            due: today.to_string(),
+            interval: 0,
+            repetitions: 0,
+            ease: INITIAL_EASE,
+            last_review: None,
+        }
+    }
+
+    pub fn due(&self) -> Option<NaiveDate> {
+        self.due.parse().ok()
+    }
+
+    /// Record a review graded from 0 (forgotten) to 5 (perfect recall), and schedule the next
+    /// one. Below 3 the note is learned again from a day later.
+    pub fn grade(&mut self, grade: u8, today: NaiveDate) -> Result<()> {
+        if grade > 5 {
+            bail!("Invalid grade {}, expected 0 to 5", grade);
+        }
+
+        if grade < 3 {
+            self.repetitions = 0;
+            self.interval = 1;
+        } else {
+            self.repetitions += 1;
+            self.interval = match self.repetitions {
+                1 => 1,
+                2 => 6,
+                _ => (self.interval as f64 * self.ease).round() as u64,
+            };
+        }
+        let miss = (5 - grade) as f64;
+        self.ease = (self.ease + 0.1 - miss * (0.08 + miss * 0.02)).max(MIN_EASE);
+
+        self.last_review = Some(today.to_string());
+        self.due = (today + Days::new(self.interval)).to_string();
+        Ok(())
+    }
+}
+
+pub fn load(note_root: &Path) -> Result<Schedules> {
+    StateStore::new(note_root).load(REVIEW)
+}
+
+pub fn save(note_root: &Path, schedules: &Schedules) -> Result<()> {
+    StateStore::new(note_root).save(REVIEW, schedules)
+}
+
+fn relative(note_root: &Path, note: &Path) -> PathBuf {
+    note.strip_prefix(note_root).unwrap_or(note).to_path_buf()
+}
+
+/// Schedule the notes of the targets (notes or categories) for review, due today. Notes
+/// already scheduled keep their schedule. Returns the notes added.
+pub fn add(note_root: &Path, targets: &[OsString], today: NaiveDate) -> Result<Vec<PathBuf>> {
+    let mut schedules = load(note_root)?;
+    let mut added = Vec::new();
+    for target in targets {
+        for note in target_notes(note_root, Path::new(target))? {
+            let note = relative(note_root, &note);
+            if !schedules.contains_key(&note) {
+                schedules.insert(note.clone(), Schedule::new(today));
+                added.push(note);
+            }
+        }
+    }
+    save(note_root, &schedules)?;
+    Ok(added)
+}
+
+/// Stop reviewing a note. Returns whether it was scheduled.
+pub fn remove(note_root: &Path, note: &Path) -> Result<bool> {
+    let mut schedules = load(note_root)?;
+    let removed = schedules.remove(&relative(note_root, note)).is_some();
+    save(note_root, &schedules)?;
+    Ok(removed)
+}
+
+/// Grade the review of a scheduled note. Returns its new schedule.
+pub fn grade(note_root: &Path, note: &Path, grade: u8, today: NaiveDate) -> Result<Schedule> {
+    let mut schedules = load(note_root)?;
+    let Some(schedule) = schedules.get_mut(&relative(note_root, note)) else {
+        bail!(
+            "'{}' is not scheduled for review, add it with `noxe review add`",
+            note.display()
+        );
+    };
+    schedule.grade(grade, today)?;
+    let schedule = schedule.clone();
+    save(note_root, &schedules)?;
+    Ok(schedule)
+}
+
+/// The notes due by `today`, the most overdue first. Notes removed since they were scheduled
+/// are left out.
+pub fn due(note_root: &Path, schedules: &Schedules, today: NaiveDate) -> Vec<(PathBuf, NaiveDate)> {
+    let mut due: Vec<(PathBuf, NaiveDate)> = schedules
+        .iter()
+        .filter_map(|(note, schedule)| Some((note, schedule.due()?)))
+        .filter(|(note, day)| *day <= today && note_root.join(note).exists())
+        .map(|(note, day)| (note.clone(), day))
+        .collect();
+    due.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+    due
+}
+
+/// Review the notes due today: open each with `edit`, then ask for a grade to schedule the
+/// next review. The grades are saved as they are given, so that a session can be stopped.
+pub fn review(note_root: &Path, list: bool, edit: &[OsString]) -> Result<()> {
+    let today = Local::now().date_naive();
+    let schedules = load(note_root)?;
+    let due = due(note_root, &schedules, today);
+
+    if list || due.is_empty() {
+        for (note, day) in &due {
+            println!("{}  {}", day, note.display());
+        }
+        if due.is_empty() {
+            match schedules.values().filter_map(Schedule::due).min() {
+                Some(next) => println!("Nothing to review today, the next review is on {}", next),
+                None => println!("No note is scheduled, add some with `noxe review add`"),
+            }
+        }
+        return Ok(());
+    }
+    if !io::stdin().is_terminal() {
+        bail!("Not a terminal, list the notes due with --list");
+    }
+
+    let edit = if edit.is_empty() {
+        vec!["vim".into()]
+    } else {
+        edit.to_vec()
+    };
+    for (i, (note, _)) in due.iter().enumerate() {
+        let path = note_root.join(note);
+        println!("[{}/{}] {}", i + 1, due.len(), note.display());
+        exec_with(&path.main_file_path()?, &edit)?;
+
+        let Some(grade) = ask_grade()? else {
+            println!("Stopped, {} note(s) left for today", due.len() - i);
+            return Ok(());
+        };
+        if let Some(grade) = grade {
+            let schedule = self::grade(note_root, &path, grade, today)?;
+            println!("Next review on {}", schedule.due);
+        }
+    }
+    println!("Done, {} note(s) reviewed", due.len());
+    Ok(())
+}
+
+/// Ask for the grade of a review: `None` to stop, `Some(None)` to skip the note.
+fn ask_grade() -> Result<Option<Option<u8>>> {
+    loop {
+        eprint!("Grade 0-5 (0: forgotten, 5: perfect), s: skip, q: stop: ");
+        io::stderr().flush()?;
+        let mut input = String::new();
+        if io::stdin()
+            .read_line(&mut input)
+            .with_context(|| "Failed to read user input")?
+            == 0
+        {
+            return Ok(None);
+        }
+        match input.trim() {
+            "q" => return Ok(None),
+            "s" => return Ok(Some(None)),
+            input => match input.parse::<u8>() {
+                Ok(grade) if grade <= 5 => return Ok(Some(Some(grade))),
+                _ => eprintln!("Invalid grade '{}'", input),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_srs() {
+        let today = NaiveDate::from_ymd_opt(2025, 3, 10).unwrap();
+        let day = |n| (today + Days::new(n)).to_string();
+
+        let mut schedule = Schedule::new(today);
+        schedule.grade(5, today).unwrap();
+        assert_eq!((schedule.interval, schedule.due.clone()), (1, day(1)));
+        schedule.grade(4, today).unwrap();
+        assert_eq!(schedule.interval, 6);
+        schedule.grade(4, today).unwrap();
+        assert_eq!(schedule.interval, (6.0 * 2.6_f64).round() as u64);
+        schedule.grade(1, today).unwrap();
+        assert_eq!((schedule.interval, schedule.repetitions), (1, 0));
+        assert!(schedule.ease < INITIAL_EASE);
+        assert!(schedule.grade(6, today).is_err());
+
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        fs::create_dir_all(root.join("lang")).unwrap();
+        fs::write(root.join("lang/rust.md"), "# Rust\n").unwrap();
+        fs::write(root.join("lang/go.md"), "# Go\n").unwrap();
+
+        let added = add(root, &["lang".into()], today).unwrap();
+        assert_eq!(added.len(), 2);
+        assert!(add(root, &["lang".into()], today).unwrap().is_empty());
+        assert_eq!(due(root, &load(root).unwrap(), today).len(), 2);
+
+        let rust = root.join("lang/rust.md");
+        assert_eq!(grade(root, &rust, 5, today).unwrap().due, day(1));
+        let due_today = due(root, &load(root).unwrap(), today);
+        assert_eq!(due_today, [(PathBuf::from("lang/go.md"), today)]);
+        assert_eq!(
+            due(root, &load(root).unwrap(), today + Days::new(1)).len(),
+            2
+        );
+
+        // Removed notes are not due, and unscheduled notes cannot be graded
+        fs::remove_file(root.join("lang/go.md")).unwrap();
+        assert!(due(root, &load(root).unwrap(), today).is_empty());
+        assert!(remove(root, &rust).unwrap());
+        assert!(grade(root, &rust, 3, today).is_err());
+        assert!(root.join(".noxe/review.json").is_file());
+    }
+}
@@ -0,0 +1,124 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::atomic;
+
+/// The directory of the vault-local state. Being hidden, it is skipped when walking notes.
+pub const STATE_DIR: &str = ".noxe";
+
+/// The version of the state layout, stamped in `.noxe/layout.json`. Bumped when older state
+/// needs `noxe migrate`.
+pub const LAYOUT_VERSION: u32 = 1;
+pub const LAYOUT: &str = "layout";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Layout {
+    pub version: u32,
+}
+
+/// Vault-local state (checkpoints, embeddings, ...) kept as JSON files in `<note_root>/.noxe`.
+#[derive(Debug, Clone)]
+pub struct StateStore {
+    dir: PathBuf,
+}
+
+impl StateStore {
+    pub fn new(note_root: &Path) -> Self {
+        StateStore {
+            dir: note_root.join(STATE_DIR),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Create the state directory. A new one is stamped with the current layout version, as
+    /// its state needs no migration.
+    pub fn init(&self) -> Result<()> {
+        if self.dir.is_dir() {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create directory '{}'", self.dir.display()))?;
+        self.save(
+            LAYOUT,
+            &Layout {
+                version: LAYOUT_VERSION,
+            },
+        )
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", name))
+    }
+
+    pub fn exists(&self, name: &str) -> bool {
+        self.path(name).is_file()
+    }
+
+    /// Remove a state file. Returns whether it existed.
+    pub fn remove(&self, name: &str) -> Result<bool> {
+        let path = self.path(name);
+        if !path.exists() {
+            return Ok(false);
+        }
+        fs::remove_file(&path)
+            .with_context(|| format!("Failed to remove state '{}'", path.display()))?;
+        Ok(true)
+    }
+
+    /// Load a state file, or its default if it does not exist yet.
+    pub fn load<T: DeserializeOwned + Default>(&self, name: &str) -> Result<T> {
+        let path = self.path(name);
+        if !path.exists() {
+            return Ok(T::default());
+        }
+
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read state '{}'", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse state '{}'", path.display()))
+    }
+
+    /// Save a state file. The file is replaced at once so that an interrupted run never leaves
+    /// it half-written.
+    pub fn save<T: Serialize>(&self, name: &str, value: &T) -> Result<()> {
+        self.init()?;
+
+        let path = self.path(name);
+        atomic::write(&path, serde_json::to_vec(value)?)
+            .with_context(|| format!("Failed to write state '{}'", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_state_store() {
+        let tmp_dir = tempdir().unwrap();
+        let store = StateStore::new(tmp_dir.path());
+
+        let state: BTreeMap<String, u32> = store.load("test").unwrap();
+        assert!(state.is_empty());
+
+        store.save("test", &BTreeMap::from([("a", 1)])).unwrap();
+        let state: BTreeMap<String, u32> = store.load("test").unwrap();
+        assert_eq!(state["a"], 1);
+        assert!(store.exists("test"));
+
+        let layout: Layout = store.load(LAYOUT).unwrap();
+        assert_eq!(layout.version, LAYOUT_VERSION);
+
+        assert!(store.remove("test").unwrap());
+        assert!(!store.remove("test").unwrap());
+    }
+}
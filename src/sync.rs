@@ -0,0 +1,173 @@
+use anyhow::{Context, Result, bail};
+use std::{
+    path::Path,
+    process::{self, Output},
+};
+
+/// Commit local changes, rebase them onto the upstream and push, like running the usual
+/// git commands by hand in the note root.
+pub fn sync(note_root: &Path, message: Option<String>, push: bool) -> Result<()> {
+    if !git(note_root, &["rev-parse", "--is-inside-work-tree"])?
+        .status
+        .success()
+    {
+        bail!(
+            "'{}' is not a git repository, run `git init` there first",
+            note_root.display()
+        );
+    }
+
+    // Only the notes are committed when the note root is a folder of a larger repository
+    run_git(note_root, &["add", "--all", "--", "."])?;
+    let changes = run_git(note_root, &["diff", "--cached", "--name-only", "--", "."])?;
+    let changes = changes.lines().count();
+    if changes > 0 {
+        let message = message.unwrap_or_else(|| {
+            format!(
+                "noxe sync: {}",
+                chrono::Local::now().format("%Y-%m-%d %H:%M:%S")
+            )
+        });
+        run_git(note_root, &["commit", "--quiet", "-m", &message, "--", "."])?;
+        println!("Committed {} changed file(s)", changes);
+    } else {
+        println!("No local changes");
+    }
+
+    if !push {
+        return Ok(());
+    }
+
+    let remotes = run_git(note_root, &["remote"])?;
+    let Some(remote) = remotes.lines().next() else {
+        println!("No remote configured, nothing to push");
+        return Ok(());
+    };
+
+    let has_upstream = git(
+        note_root,
+        &["rev-parse", "--abbrev-ref", "--symbolic-full-name", "@{u}"],
+    )?
+    .status
+    .success();
+
+    if has_upstream {
+        let output = git(note_root, &["pull", "--rebase", "--quiet"])?;
+        if !output.status.success() {
+            let conflicts = run_git(note_root, &["diff", "--name-only", "--diff-filter=U"])?;
+            if conflicts.trim().is_empty() {
+                bail!("git pull failed: {}", stderr(&output));
+            }
+
+            let files = conflicts
+                .lines()
+                .map(|f| format!("  {}", f))
+                .collect::<Vec<_>>();
+            bail!(
+                "Conflicts while rebasing onto the remote notes:\n{}\n\
+                 Resolve them, run `git rebase --continue` in '{}', then sync again \
+                 (or `git rebase --abort` to give up)",
+                files.join("\n"),
+                note_root.display()
+            );
+        }
+        run_git(note_root, &["push", "--quiet"])?;
+    } else {
+        run_git(
+            note_root,
+            &["push", "--quiet", "--set-upstream", remote, "HEAD"],
+        )?;
+    }
+    println!("Synced with '{}'", remote);
+
+    Ok(())
+}
+
+pub(crate) fn git(note_root: &Path, args: &[&str]) -> Result<Output> {
+    process::Command::new("git")
+        .arg("-C")
+        .arg(note_root)
+        .args(args)
+        .output()
+        .with_context(|| "Failed to run git, is it installed?")
+}
+
+/// Run git and return its stdout, failing with its stderr.
+pub(crate) fn run_git(note_root: &Path, args: &[&str]) -> Result<String> {
+    let output = git(note_root, args)?;
+    if !output.status.success() {
+        bail!("git {} failed: {}", args.join(" "), stderr(&output));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn configure(dir: &Path) {
+        run_git(dir, &["config", "user.name", "noxe"]).unwrap();
+        run_git(dir, &["config", "user.email", "noxe@example.com"]).unwrap();
+    }
+
+    #[test]
+    fn test_sync() {
+        let tmp_dir = tempdir().unwrap();
+        let remote = tmp_dir.path().join("remote.git");
+        let a = tmp_dir.path().join("a");
+        let b = tmp_dir.path().join("b");
+        fs::create_dir_all(&remote).unwrap();
+        fs::create_dir_all(&a).unwrap();
+        run_git(&remote, &["init", "--quiet", "--bare"]).unwrap();
+
+        run_git(&a, &["init", "--quiet"]).unwrap();
+        configure(&a);
+        run_git(&a, &["remote", "add", "origin", remote.to_str().unwrap()]).unwrap();
+        fs::write(a.join("note.md"), "v1\n").unwrap();
+        sync(&a, None, true).unwrap();
+
+        // Another machine gets the note, edits it and syncs back
+        run_git(
+            tmp_dir.path(),
+            &["clone", "--quiet", remote.to_str().unwrap(), "b"],
+        )
+        .unwrap();
+        configure(&b);
+        fs::write(b.join("note.md"), "v2\n").unwrap();
+        sync(&b, Some("edit".into()), true).unwrap();
+
+        // Conflicting edits are reported
+        fs::write(a.join("note.md"), "v3\n").unwrap();
+        let err = sync(&a, None, true).unwrap_err().to_string();
+        assert!(err.contains("Conflicts"), "{}", err);
+        assert!(err.contains("note.md"), "{}", err);
+        run_git(&a, &["rebase", "--abort"]).unwrap();
+
+        assert!(sync(tmp_dir.path(), None, false).is_err());
+    }
+
+    #[test]
+    fn test_sync_vault_in_repo() {
+        let tmp_dir = tempdir().unwrap();
+        let repo = tmp_dir.path();
+        let notes = repo.join("notes");
+        fs::create_dir_all(&notes).unwrap();
+        run_git(repo, &["init", "--quiet"]).unwrap();
+        configure(repo);
+
+        fs::write(repo.join("other.txt"), "other\n").unwrap();
+        fs::write(notes.join("note.md"), "note\n").unwrap();
+        sync(&notes, None, false).unwrap();
+
+        let committed = run_git(repo, &["show", "--name-only", "--format=", "HEAD"]).unwrap();
+        assert_eq!(committed.trim(), "notes/note.md");
+        let untracked = run_git(repo, &["status", "--porcelain"]).unwrap();
+        assert_eq!(untracked.trim(), "?? other.txt");
+    }
+}
@@ -0,0 +1,189 @@
+use anyhow::{Context, Result, bail};
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::atomic;
+use crate::cli::TagCommand;
+use crate::extract::extractor;
+use crate::metadata::{ensure_writable, parse_metadata, remove_field, set_field};
+use crate::process::find_note_dir;
+use crate::vault::{Note, all_notes};
+
+pub fn process_tag_command(command: TagCommand) -> Result<()> {
+    match command {
+        TagCommand::List { note_root } => {
+            let index = tag_index(Path::new(&note_root))?;
+
+            let mut tags = index.into_values().collect::<Vec<_>>();
+            tags.sort_by(|a, b| b.notes.len().cmp(&a.notes.len()).then(a.name.cmp(&b.name)));
+
+            for tag in tags {
+                println!("{} ({})", tag.name, tag.notes.len());
+            }
+        }
+        TagCommand::Add {
+            note_path,
+            tags,
+            note_root,
+            override_lock,
+        } => {
+            let note = find_note_dir(&note_path, &note_root)?;
+            let keywords = update_keywords(&note, override_lock, |keywords| {
+                for tag in tags {
+                    if !keywords.iter().any(|k| k.eq_ignore_ascii_case(&tag)) {
+                        keywords.push(tag);
+                    }
+                }
+            })?;
+
+            println!("Tags of '{}': {}", note.display(), keywords.join(", "));
+        }
+        TagCommand::Rm {
+            note_path,
+            tags,
+            note_root,
+            override_lock,
+        } => {
+            let note = find_note_dir(&note_path, &note_root)?;
+            let keywords = update_keywords(&note, override_lock, |keywords| {
+                keywords.retain(|k| !tags.iter().any(|t| t.eq_ignore_ascii_case(k)));
+            })?;
+
+            println!("Tags of '{}': {}", note.display(), keywords.join(", "));
+        }
+        TagCommand::Find { tag, note_root } => {
+            let index = tag_index(Path::new(&note_root))?;
+            let Some(tag) = index.get(&tag.to_lowercase()) else {
+                bail!("No note tagged '{}'", tag);
+            };
+
+            for note in &tag.notes {
+                println!("{}", note.display());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// A tag and the notes carrying it.
+#[derive(Debug)]
+pub struct Tag {
+    /// The tag as first seen in the vault
+    pub name: String,
+    pub notes: Vec<PathBuf>,
+}
+
+/// Aggregate the keywords of every note, keyed by the lowercased tag.
+pub fn tag_index(note_root: &Path) -> Result<BTreeMap<String, Tag>> {
+    let mut index: BTreeMap<String, Tag> = BTreeMap::new();
+
+    for note in all_notes(note_root)? {
+        let Ok(main_file) = note.main_file_path() else {
+            continue;
+        };
+        let Ok(content) = fs::read_to_string(&main_file) else {
+            continue;
+        };
+
+        for keyword in extractor(main_file.note_type()?).tags(&content) {
+            index
+                .entry(keyword.to_lowercase())
+                .or_insert_with(|| Tag {
+                    name: keyword.clone(),
+                    notes: Vec::new(),
+                })
+                .notes
+                .push(note.clone());
+        }
+    }
+
+    Ok(index)
+}
+
+/// Rewrite the keywords of a note in place, returning the new keywords.
+fn update_keywords(
+    note: &Path,
+    override_lock: bool,
+    f: impl FnOnce(&mut Vec<String>),
+) -> Result<Vec<String>> {
+    let main_file = note.main_file_path()?;
+    let note_type = main_file.note_type()?;
+    let content = fs::read_to_string(&main_file)
+        .with_context(|| format!("Failed to read note '{}'", main_file.display()))?;
+    ensure_writable(&content, note_type, "keywords", override_lock)?;
+
+    let mut keywords = parse_metadata(&content, note_type).keywords;
+    f(&mut keywords);
+
+    let content = if keywords.is_empty() {
+        remove_field(&content, note_type, "keywords")?
+    } else {
+        let value = Value::Array(keywords.iter().cloned().map(Value::String).collect());
+        set_field(&content, note_type, "keywords", &value)?
+    };
+
+    atomic::write(&main_file, content)?;
+
+    Ok(keywords)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_update_keywords() {
+        let tmp_dir = tempdir().unwrap();
+
+        let md = tmp_dir.path().join("a.md");
+        fs::write(
+            &md,
+            "---\ntitle: \"a\"\nkeywords: [rust, cli]\n---\n\nBody\n",
+        )
+        .unwrap();
+        update_keywords(&md, false, |k| k.push("async io".into())).unwrap();
+        assert_eq!(
+            fs::read_to_string(&md).unwrap(),
+            "---\ntitle: \"a\"\nkeywords: [rust, cli, async io]\n---\n\nBody\n"
+        );
+
+        let typ = tmp_dir.path().join("b.typ");
+        fs::write(
+            &typ,
+            "#set document(title: \"b\", keywords: (rust, cli))\n\n= B\n",
+        )
+        .unwrap();
+        update_keywords(&typ, false, |k| k.retain(|k| k != "cli")).unwrap();
+        assert_eq!(
+            fs::read_to_string(&typ).unwrap(),
+            "#set document(title: \"b\", keywords: (\"rust\",))\n\n= B\n"
+        );
+        update_keywords(&typ, false, |k| k.clear()).unwrap();
+        assert_eq!(
+            fs::read_to_string(&typ).unwrap(),
+            "#set document(title: \"b\")\n\n= B\n"
+        );
+
+        let index = tag_index(tmp_dir.path()).unwrap();
+        assert_eq!(
+            index.keys().collect::<Vec<_>>(),
+            ["async io", "cli", "rust"]
+        );
+        assert_eq!(index["rust"].notes, [md]);
+
+        let locked = tmp_dir.path().join("c.md");
+        fs::write(&locked, "---\nreadonly: true\nkeywords: [x]\n---\n").unwrap();
+        assert!(update_keywords(&locked, false, |k| k.clear()).is_err());
+        assert!(
+            update_keywords(&locked, true, |k| k.clear())
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
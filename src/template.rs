@@ -0,0 +1,346 @@
+use anyhow::{Context, Result, bail};
+use serde_yml::{Mapping, Value};
+use std::{
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use crate::cli::{NoteType, TemplateCommand};
+use crate::extract::{NoteText, extractor};
+use crate::process::find_note_dir;
+use crate::vault::{Note, NoteTemplate, load_note_template};
+
+/// The extension of template files in the registry.
+const TEMPLATE_EXT: &str = "yaml";
+
+pub fn process_template_command(command: TemplateCommand) -> Result<()> {
+    match command {
+        TemplateCommand::List { template_dir } => {
+            let store = TemplateStore::new(template_dir)?;
+
+            for name in store.names()? {
+                println!("{}", name);
+            }
+        }
+        TemplateCommand::Show { name, template_dir } => {
+            let store = TemplateStore::new(template_dir)?;
+            print!("{}", store.get(&name)?);
+        }
+        TemplateCommand::Add {
+            name,
+            file,
+            force,
+            template_dir,
+        } => {
+            let store = TemplateStore::new(template_dir)?;
+
+            let content = match file {
+                Some(file) => fs::read_to_string(&file)
+                    .with_context(|| format!("Failed to read '{}'", file.display()))?,
+                None => {
+                    let mut content = String::new();
+                    io::stdin()
+                        .read_to_string(&mut content)
+                        .with_context(|| "Failed to read the template from stdin")?;
+                    content
+                }
+            };
+
+            store.add(&name, &content, force)?;
+            println!("Template '{}' saved", name);
+        }
+        TemplateCommand::From {
+            note_path,
+            name,
+            headings_only,
+            print,
+            force,
+            note_root,
+            template_dir,
+        } => {
+            let note = find_note_dir(&note_path, &note_root)?;
+            let content = template_from(&note, headings_only)?;
+            if print {
+                print!("{}", content);
+                return Ok(());
+            }
+
+            let name = name.unwrap_or_else(|| note.note_name());
+            TemplateStore::new(template_dir)?.add(&name, &content, force)?;
+            println!("Template '{}' saved", name);
+        }
+        TemplateCommand::Rm { name, template_dir } => {
+            let store = TemplateStore::new(template_dir)?;
+            store.remove(&name)?;
+            println!("Template '{}' removed", name);
+        }
+    }
+
+    Ok(())
+}
+
+/// A template, in the format of `noxe new --note-template`, reproducing a note: the files and
+/// directories of a dirnote, and its main file without the metadata (which `noxe new` writes).
+/// The title, author and date of the note become the `{{title}}`, `{{author}}` and `{{date}}`
+/// variables. With `headings_only`, the notes keep their headings only and other files are
+/// left empty.
+pub fn template_from(note: &Path, headings_only: bool) -> Result<String> {
+    let main_file = note.main_file_path()?;
+    let note_type = main_file.note_type()?;
+    if !matches!(note_type, NoteType::Typ | NoteType::Md) {
+        bail!("Templates only hold typst and markdown main files");
+    }
+    let text = NoteText::read(&main_file)?;
+    let metadata = text.metadata();
+
+    // Longer values first, so that a title containing the date keeps it whole
+    let mut vars: Vec<(String, &str)> = [
+        (metadata.title.clone(), "title"),
+        (Some(note.note_name()), "title"),
+        (metadata.author.clone(), "author"),
+        (metadata.date.clone(), "date"),
+    ]
+    .into_iter()
+    .filter_map(|(value, name)| Some((value?.trim().to_string(), name)))
+    .filter(|(value, _)| !value.is_empty())
+    .collect();
+    vars.sort_by_key(|(value, _)| std::cmp::Reverse(value.len()));
+    let parameterize = |text: &str| {
+        vars.iter().fold(text.to_string(), |text, (value, name)| {
+            text.replace(value.as_str(), &format!("{{{{{}}}}}", name))
+        })
+    };
+    let body = |text: &str, path: &Path| match path.note_type() {
+        Ok(note_type) if headings_only => {
+            let lines: Vec<&str> = text.lines().collect();
+            extractor(note_type)
+                .headings(&lines)
+                .into_iter()
+                .map(|(i, _)| format!("{}\n", parameterize(lines[i])))
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+        Err(_) if headings_only => String::new(),
+        _ => parameterize(text),
+    };
+
+    let mut template = Mapping::new();
+    let paths = if note.is_dir() {
+        dir_paths(note, &main_file, &|text, path| body(text, path))?
+    } else {
+        Mapping::new()
+    };
+    template.insert("paths".into(), Value::Mapping(paths));
+    let main = text.body();
+    template.insert(
+        format!("main.{}", note_type).into(),
+        body(main.trim_start_matches(['\r', '\n']), &main_file).into(),
+    );
+
+    Ok(serde_yml::to_string(&template)?)
+}
+
+/// The files and directories of `dir` as template paths, leaving out the main file, hidden
+/// files and files that are not text, like images and compiled PDFs.
+fn dir_paths(
+    dir: &Path,
+    main_file: &Path,
+    body: &dyn Fn(&str, &Path) -> String,
+) -> Result<Mapping> {
+    let mut entries = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read directory '{}'", dir.display()))?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+
+    let mut paths = Mapping::new();
+    for path in entries {
+        let name = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        if name.starts_with('.') || path == main_file {
+            continue;
+        }
+        if path.is_dir() {
+            paths.insert(
+                name.into(),
+                Value::Mapping(dir_paths(&path, main_file, body)?),
+            );
+        } else if let Ok(text) = fs::read_to_string(&path) {
+            paths.insert(name.into(), body(&text, &path).into());
+        } else {
+            eprintln!("Skipping '{}', not a text file", path.display());
+        }
+    }
+    Ok(paths)
+}
+
+/// Named note templates (`<name>.yaml`, in the format of `noxe new --note-template`), shared
+/// by all vaults.
+#[derive(Debug)]
+pub struct TemplateStore {
+    dir: PathBuf,
+}
+
+impl TemplateStore {
+    /// Open the store at `dir`, or `noxe/templates` under the user config directory.
+    pub fn new(dir: Option<PathBuf>) -> Result<Self> {
+        let dir = match dir {
+            Some(dir) => dir,
+            None => dirs::config_dir()
+                .context("Cannot determine the config directory")?
+                .join("noxe")
+                .join("templates"),
+        };
+        Ok(TemplateStore { dir })
+    }
+
+    fn path(&self, name: &str) -> Result<PathBuf> {
+        if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+            bail!("Invalid template name '{}'", name);
+        }
+        Ok(self.dir.join(format!("{}.{}", name, TEMPLATE_EXT)))
+    }
+
+    /// Save a template, checking that it is a valid note template first.
+    pub fn add(&self, name: &str, content: &str, force: bool) -> Result<()> {
+        let path = self.path(name)?;
+        if path.exists() && !force {
+            bail!(
+                "Template '{}' already exists, use --force to replace it",
+                name
+            );
+        }
+        serde_yml::from_str::<NoteTemplate>(content)
+            .with_context(|| format!("Template '{}' is not a valid note template", name))?;
+
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create directory '{}'", self.dir.display()))?;
+        fs::write(&path, content)
+            .with_context(|| format!("Failed to write template '{}'", path.display()))
+    }
+
+    pub fn get(&self, name: &str) -> Result<String> {
+        let path = self.path(name)?;
+        fs::read_to_string(&path).with_context(|| format!("No template named '{}'", name))
+    }
+
+    pub fn load(&self, name: &str) -> Result<NoteTemplate> {
+        let path = self.path(name)?;
+        if !path.is_file() {
+            bail!("No template named '{}' in '{}'", name, self.dir.display());
+        }
+        load_note_template(path.as_os_str())
+    }
+
+    pub fn remove(&self, name: &str) -> Result<()> {
+        let path = self.path(name)?;
+        if !path.is_file() {
+            bail!("No template named '{}'", name);
+        }
+        fs::remove_file(&path)
+            .with_context(|| format!("Failed to remove template '{}'", path.display()))
+    }
+
+    pub fn names(&self) -> Result<Vec<String>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&self.dir)
+            .with_context(|| format!("Failed to read directory '{}'", self.dir.display()))?
+        {
+            let path = entry?.path();
+            if path.is_file()
+                && path.extension().is_some_and(|ext| ext == TEMPLATE_EXT)
+                && let Some(name) = path.file_stem().and_then(|s| s.to_str())
+                && !name.starts_with('.')
+            {
+                names.push(name.to_string());
+            }
+        }
+        names.sort();
+
+        Ok(names)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_template_store() {
+        let tmp_dir = tempdir().unwrap();
+        let store = TemplateStore::new(Some(tmp_dir.path().join("templates"))).unwrap();
+        assert!(store.names().unwrap().is_empty());
+
+        let meeting = "paths:\n  attachments: {}\nmain.md: \"## Attendees\\n\"\n";
+        store.add("meeting", meeting, false).unwrap();
+        store.add("paper", "paths: {}\n", false).unwrap();
+        assert!(store.add("meeting", meeting, false).is_err());
+        assert!(store.add("book", "paths: [x]\n", false).is_err());
+        assert!(store.add("../x", meeting, false).is_err());
+
+        assert_eq!(store.names().unwrap(), ["meeting", "paper"]);
+        assert_eq!(store.get("meeting").unwrap(), meeting);
+        assert!(store.load("meeting").is_ok());
+        assert!(store.load("book").is_err());
+
+        store.remove("paper").unwrap();
+        assert!(store.remove("paper").is_err());
+        assert_eq!(store.names().unwrap(), ["meeting"]);
+    }
+
+    #[test]
+    fn test_template_from() {
+        let tmp_dir = tempdir().unwrap();
+        let note = tmp_dir.path().join("Rust Notes");
+        fs::create_dir_all(note.join("chapters")).unwrap();
+        fs::create_dir_all(note.join("images")).unwrap();
+        fs::write(
+            note.join("main.md"),
+            "---\ntitle: Rust Notes\nauthor: Ferris\ndate: 2025-01-02\n---\n\n# Rust Notes\n\nBy Ferris.\n\n## Ownership\n\nMoves.\n",
+        )
+        .unwrap();
+        fs::write(
+            note.join("chapters/intro.md"),
+            "# Intro\n\nWritten 2025-01-02.\n",
+        )
+        .unwrap();
+        fs::write(note.join("images/logo.png"), [0xff, 0xfe, 0x00]).unwrap();
+
+        let template = template_from(&note, false).unwrap();
+        assert!(serde_yml::from_str::<NoteTemplate>(&template).is_ok());
+        let rendered: Value = serde_yml::from_str(&template).unwrap();
+        assert_eq!(
+            rendered["main.md"].as_str().unwrap(),
+            "# {{title}}\n\nBy {{author}}.\n\n## Ownership\n\nMoves.\n"
+        );
+        assert_eq!(
+            rendered["paths"]["chapters"]["intro.md"].as_str().unwrap(),
+            "# Intro\n\nWritten {{date}}.\n"
+        );
+        // Binary files are left out, their directory is kept
+        assert_eq!(rendered["paths"]["images"], Value::Mapping(Mapping::new()));
+
+        let skeleton: Value = serde_yml::from_str(&template_from(&note, true).unwrap()).unwrap();
+        assert_eq!(
+            skeleton["main.md"].as_str().unwrap(),
+            "# {{title}}\n\n## Ownership\n"
+        );
+        assert_eq!(
+            skeleton["paths"]["chapters"]["intro.md"].as_str().unwrap(),
+            "# Intro\n"
+        );
+
+        let org = tmp_dir.path().join("todo.org");
+        fs::write(&org, "* Tasks\n").unwrap();
+        assert!(template_from(&org, false).is_err());
+    }
+}
@@ -0,0 +1,129 @@
+use ratatui::style::{Color, Modifier, Style};
+use std::sync::OnceLock;
+
+use crate::config::{self, ThemeConfig, ThemePreset};
+
+/// The styles of the TUI.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    pub tree: Style,
+    pub category: Style,
+    pub selection: Style,
+    pub border: Style,
+    pub preview: Style,
+    pub status_bar: Style,
+}
+
+/// The background of a terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Background {
+    Dark,
+    Light,
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Theme {
+            tree: Style::default(),
+            category: Style::default().fg(Color::Blue),
+            selection: Style::default().add_modifier(Modifier::REVERSED),
+            border: Style::default().fg(Color::Cyan),
+            preview: Style::default(),
+            status_bar: Style::default().add_modifier(Modifier::DIM),
+        }
+    }
+
+    pub fn light() -> Self {
+        Theme {
+            tree: Style::default(),
+            category: Style::default().fg(Color::Blue),
+            selection: Style::default().fg(Color::Black).bg(Color::LightBlue),
+            border: Style::default().fg(Color::Blue),
+            preview: Style::default(),
+            status_bar: Style::default().fg(Color::DarkGray),
+        }
+    }
+
+    /// The preset of the config, with its colors over it. `background` tells the background
+    /// of the terminal for the `auto` preset, when it can.
+    pub fn from_config(
+        config: &ThemeConfig,
+        background: impl FnOnce() -> Option<Background>,
+    ) -> Self {
+        let light = match config.preset {
+            ThemePreset::Dark => false,
+            ThemePreset::Light => true,
+            ThemePreset::Auto => background() == Some(Background::Light),
+        };
+        let mut theme = if light { Theme::light() } else { Theme::dark() };
+
+        // The colors were checked when the config was loaded
+        let color = |c: Option<&str>| c.and_then(|c| c.parse::<Color>().ok());
+        let [tree, category, selection, border, preview, status_bar] =
+            config.colors().map(|(_, c)| color(c));
+        for (style, color) in [
+            (&mut theme.tree, tree),
+            (&mut theme.category, category),
+            (&mut theme.border, border),
+            (&mut theme.preview, preview),
+            (&mut theme.status_bar, status_bar),
+        ] {
+            if let Some(color) = color {
+                *style = style.fg(color);
+            }
+        }
+        if let Some(color) = selection {
+            theme.selection = Style::default().bg(color);
+        }
+        theme
+    }
+}
+
+/// The theme of the config, for the background of the terminal.
+pub fn theme() -> &'static Theme {
+    static THEME: OnceLock<Theme> = OnceLock::new();
+    THEME.get_or_init(|| Theme::from_config(config::tui_theme(), detect_background))
+}
+
+/// The background of the terminal, from `COLORFGBG` which some terminals (rxvt, Konsole,
+/// iTerm2) set to `<foreground>;<background>`.
+pub fn detect_background() -> Option<Background> {
+    parse_colorfgbg(&std::env::var("COLORFGBG").ok()?)
+}
+
+/// The background of a `COLORFGBG` value, from the ANSI index of its last color: white (7)
+/// and the bright colors but dark gray (9 to 15) are light.
+fn parse_colorfgbg(value: &str) -> Option<Background> {
+    let background: u8 = value.rsplit(';').next()?.trim().parse().ok()?;
+    Some(if matches!(background, 7 | 9..=15) {
+        Background::Light
+    } else {
+        Background::Dark
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theme() {
+        assert_eq!(parse_colorfgbg("15;0"), Some(Background::Dark));
+        assert_eq!(parse_colorfgbg("0;default;15"), Some(Background::Light));
+        assert_eq!(parse_colorfgbg("default"), None);
+
+        let config: ThemeConfig = serde_yml::from_str("preset: auto").unwrap();
+        assert_eq!(Theme::from_config(&config, || None), Theme::dark());
+        assert_eq!(
+            Theme::from_config(&config, || Some(Background::Light)),
+            Theme::light()
+        );
+
+        let config: ThemeConfig =
+            serde_yml::from_str("preset: light\ncategory: '#ff8800'\nselection: '240'").unwrap();
+        let theme = Theme::from_config(&config, || Some(Background::Dark));
+        assert_eq!(theme.category.fg, Some(Color::Rgb(0xff, 0x88, 0x00)));
+        assert_eq!(theme.selection, Style::default().bg(Color::Indexed(240)));
+        assert_eq!(theme.border, Theme::light().border);
+    }
+}
@@ -0,0 +1,355 @@
+use anyhow::{Context, Result, bail};
+use colored::Colorize;
+use regex::Regex;
+use serde::Serialize;
+use std::{
+    ffi::OsStr,
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+
+use crate::agenda::{ORG_TODO_REGEX, TASK_REGEX};
+use crate::atomic;
+use crate::cli::TodoCommand;
+use crate::process::find_note_dir;
+use crate::sections::{self, Section};
+use crate::vault::Note;
+
+/// A `TODO:` marker, in prose or in a comment: `TODO: ask Bob`, `// TODO: cite this`
+static MARKER_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\bTODO:\s*(.*)$").unwrap());
+
+pub fn process_todo_command(command: TodoCommand) -> Result<()> {
+    match command {
+        TodoCommand::Check {
+            location,
+            note_root,
+        } => {
+            let (note_path, line) = parse_location(&location)?;
+            let note = find_note_dir(OsStr::new(note_path), &note_root)?;
+            let done = toggle(&note.main_file_path()?, line)?;
+            let rel_path = note.strip_prefix(&note_root).unwrap_or(&note);
+            println!(
+                "{} {}:{}",
+                if done { "Checked" } else { "Unchecked" },
+                rel_path.display(),
+                line
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TodoKind {
+    /// A checkbox list item: `- [ ] task`
+    Checkbox,
+    /// An org heading with a TODO or DONE keyword
+    Heading,
+    /// A `TODO:` marker, never done
+    Marker,
+}
+
+/// A TODO of a note.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Todo {
+    /// From 1
+    pub line: usize,
+    pub kind: TodoKind,
+    pub text: String,
+    pub done: bool,
+    /// The section the TODO is in, with the annotations it inherits
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub section: Option<Section>,
+}
+
+/// The TODOs of a note, as listed by `noxe todo`.
+#[derive(Debug, Serialize)]
+pub struct NoteTodos {
+    /// The path of the note relative to the note root
+    pub note: String,
+    /// The main file of the note, relative to the note root
+    pub file: String,
+    pub todos: Vec<Todo>,
+}
+
+/// The TODOs of a content: checkbox items, org TODO and DONE headings, and `TODO:` markers.
+/// Code blocks are skipped.
+pub fn todos(content: &str) -> Vec<Todo> {
+    let mut todos = Vec::new();
+    let mut in_code = false;
+
+    for (i, line) in content.lines().enumerate() {
+        let trimmed = line.trim().to_lowercase();
+        if trimmed.starts_with("```")
+            || trimmed.starts_with("#+begin_src")
+            || trimmed.starts_with("#+end_src")
+        {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+
+        let (kind, text, done) = if let Some(caps) = TASK_REGEX.captures(line) {
+            (TodoKind::Checkbox, caps[2].to_string(), &caps[1] != " ")
+        } else if let Some(caps) = ORG_TODO_REGEX.captures(line) {
+            (TodoKind::Heading, caps[2].to_string(), &caps[1] == "DONE")
+        } else if let Some(caps) = MARKER_REGEX.captures(line) {
+            (TodoKind::Marker, caps[1].to_string(), false)
+        } else {
+            continue;
+        };
+        todos.push(Todo {
+            line: i + 1,
+            kind,
+            text: text.trim().to_string(),
+            done,
+            section: None,
+        });
+    }
+    todos
+}
+
+/// The TODOs of the notes, done ones only when `done` is `Some(true)`, open ones only when it
+/// is `Some(false)`, and only those in a section with the `status` and `tag` when given. Notes
+/// without any are left out, as are unreadable notes.
+pub fn collect(
+    note_root: &Path,
+    notes: &[PathBuf],
+    done: Option<bool>,
+    status: Option<&str>,
+    tag: Option<&str>,
+) -> Vec<NoteTodos> {
+    notes
+        .iter()
+        .filter_map(|note| {
+            let file = note.main_file_path().ok()?;
+            let content = fs::read_to_string(&file).ok()?;
+            let sections = sections::sections(&content, file.note_type().ok()?);
+            let todos: Vec<Todo> = todos(&content)
+                .into_iter()
+                .map(|todo| Todo {
+                    section: sections::enclosing(&sections, todo.line),
+                    ..todo
+                })
+                .filter(|todo| done.is_none_or(|done| todo.done == done))
+                .filter(|todo| {
+                    status.is_none() && tag.is_none()
+                        || todo
+                            .section
+                            .as_ref()
+                            .is_some_and(|s| s.matches(status, tag))
+                })
+                .collect();
+            let relative = |path: &Path| {
+                path.strip_prefix(note_root)
+                    .unwrap_or(path)
+                    .display()
+                    .to_string()
+            };
+            (!todos.is_empty()).then(|| NoteTodos {
+                note: relative(note),
+                file: relative(&file),
+                todos,
+            })
+        })
+        .collect()
+}
+
+/// The TODOs grouped by note, each with its line number.
+pub fn render(notes: &[NoteTodos]) -> String {
+    let mut out = String::new();
+    for note in notes {
+        let _ = writeln!(out, "{}", note.note.bold());
+        for todo in &note.todos {
+            let status = match (todo.kind, todo.done) {
+                (TodoKind::Marker, _) => "TODO".yellow(),
+                (_, true) => "[x] ".green(),
+                (_, false) => "[ ] ".normal(),
+            };
+            let line = format!("{:>5}", todo.line).dimmed();
+            let mut annotations = String::new();
+            if let Some(section) = &todo.section {
+                if let Some(status) = &section.status {
+                    annotations.push_str(&format!(" {}", format!("[{}]", status).yellow()));
+                }
+                for tag in &section.tags {
+                    annotations.push_str(&format!(" {}", format!("#{}", tag).dimmed()));
+                }
+            }
+            if todo.done {
+                let _ = writeln!(
+                    out,
+                    "{} {} {}{}",
+                    line,
+                    status,
+                    todo.text.dimmed(),
+                    annotations
+                );
+            } else {
+                let _ = writeln!(out, "{} {} {}{}", line, status, todo.text, annotations);
+            }
+        }
+    }
+    out
+}
+
+/// `<note>:<line>`, split on the last colon so that the note may contain some.
+fn parse_location(location: &str) -> Result<(&str, usize)> {
+    let Some((note, line)) = location.rsplit_once(':') else {
+        bail!("Expected <note>:<line>, got '{}'", location);
+    };
+    let line = line
+        .parse()
+        .ok()
+        .filter(|line| *line > 0)
+        .with_context(|| format!("Invalid line number '{}'", line))?;
+    Ok((note, line))
+}
+
+/// Check the checkbox (or mark the org TODO heading done) on a line of a file, or uncheck it
+/// when it is checked. Returns whether it is done now.
+pub fn toggle(file: &Path, line_number: usize) -> Result<bool> {
+    let content = fs::read_to_string(file)
+        .with_context(|| format!("Failed to read note '{}'", file.display()))?;
+    let mut lines: Vec<&str> = content.split('\n').collect();
+    let Some(line) = line_number.checked_sub(1).and_then(|i| lines.get(i)) else {
+        bail!("'{}' has no line {}", file.display(), line_number);
+    };
+
+    let (updated, done) = if let Some(caps) = TASK_REGEX.captures(line) {
+        let mark = caps.get(1).unwrap();
+        let done = mark.as_str() == " ";
+        let check = if done { "x" } else { " " };
+        (
+            format!("{}{}{}", &line[..mark.start()], check, &line[mark.end()..]),
+            done,
+        )
+    } else if let Some(caps) = ORG_TODO_REGEX.captures(line) {
+        let keyword = caps.get(1).unwrap();
+        let done = keyword.as_str() == "TODO";
+        let to = if done { "DONE" } else { "TODO" };
+        (
+            format!(
+                "{}{}{}",
+                &line[..keyword.start()],
+                to,
+                &line[keyword.end()..]
+            ),
+            done,
+        )
+    } else {
+        bail!(
+            "Line {} of '{}' is not a checkbox: {}",
+            line_number,
+            file.display(),
+            line.trim()
+        );
+    };
+
+    lines[line_number - 1] = &updated;
+    atomic::write(file, lines.join("\n"))?;
+    Ok(done)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_todos() {
+        let content = "# Plan\n\n- [ ] write the intro\n* [x] pick a title\nTODO: ask Bob\n\
+            ```\n- [ ] not a task\n// TODO: nor this\n```\n* DONE release\n";
+        let todos = todos(content);
+        let found: Vec<_> = todos
+            .iter()
+            .map(|t| (t.line, t.kind, t.text.as_str(), t.done))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (3, TodoKind::Checkbox, "write the intro", false),
+                (4, TodoKind::Checkbox, "pick a title", true),
+                (5, TodoKind::Marker, "ask Bob", false),
+                (10, TodoKind::Heading, "release", true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_collect() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        fs::write(root.join("a.md"), "- [ ] open\n- [x] done\n").unwrap();
+        fs::write(root.join("b.md"), "nothing to do\n").unwrap();
+        let notes = [root.join("a.md"), root.join("b.md")];
+
+        let all = collect(root, &notes, None, None, None);
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].note, "a.md");
+        assert_eq!(all[0].todos.len(), 2);
+
+        let open = collect(root, &notes, Some(false), None, None);
+        assert_eq!(open[0].todos[0].text, "open");
+        assert_eq!(open[0].todos.len(), 1);
+        assert!(collect(root, &notes[1..], Some(true), None, None).is_empty());
+
+        // TODOs carry the annotations of their section
+        fs::write(
+            root.join("c.typ"),
+            "= Net <tag:net>\n== TCP <status:draft>\n- [ ] handshake\n= Misc\n- [ ] other\n",
+        )
+        .unwrap();
+        let notes = [root.join("c.typ")];
+        let todos = collect(root, &notes, None, None, None);
+        let section = todos[0].todos[0].section.as_ref().unwrap();
+        assert_eq!(section.heading, "TCP");
+        assert_eq!(section.status.as_deref(), Some("draft"));
+        assert_eq!(section.tags, ["net"]);
+        assert!(todos[0].todos[1].section.as_ref().unwrap().status.is_none());
+
+        let drafts = collect(root, &notes, None, Some("draft"), None);
+        assert_eq!(drafts[0].todos.len(), 1);
+        assert_eq!(drafts[0].todos[0].text, "handshake");
+        assert!(collect(root, &notes, None, None, Some("web")).is_empty());
+    }
+
+    #[test]
+    fn test_toggle() {
+        let tmp_dir = tempdir().unwrap();
+        let note = tmp_dir.path().join("a.org");
+        fs::write(&note, "* TODO release\n- [ ] tag it\nTODO: later\n").unwrap();
+
+        assert!(toggle(&note, 2).unwrap());
+        assert!(toggle(&note, 1).unwrap());
+        assert_eq!(
+            fs::read_to_string(&note).unwrap(),
+            "* DONE release\n- [x] tag it\nTODO: later\n"
+        );
+        assert!(!toggle(&note, 2).unwrap());
+        assert!(fs::read_to_string(&note).unwrap().contains("- [ ] tag it"));
+
+        assert!(toggle(&note, 3).is_err());
+        assert!(toggle(&note, 10).is_err());
+    }
+
+    #[test]
+    fn test_parse_location() {
+        assert_eq!(
+            parse_location("lang/rust.md:12").unwrap(),
+            ("lang/rust.md", 12)
+        );
+        assert_eq!(
+            parse_location("c:/notes/a.md:3").unwrap(),
+            ("c:/notes/a.md", 3)
+        );
+        assert!(parse_location("rust.md").is_err());
+        assert!(parse_location("rust.md:0").is_err());
+    }
+}
@@ -0,0 +1,431 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::cli::NoteType;
+use crate::extract::NoteText;
+use crate::graph::Graph;
+use crate::metadata::set_field;
+use crate::rename::slugify;
+use crate::vault::{NewNote, Note, create_note};
+use crate::words::is_cjk;
+
+/// The similarity added between two notes when one links to the other.
+const LINK_WEIGHT: f64 = 0.5;
+/// Label propagation usually settles in a few rounds.
+const MAX_ROUNDS: usize = 20;
+/// The keyword of the index notes written by `noxe topics --moc`.
+pub const MOC_KEYWORD: &str = "moc";
+
+const STOP_WORDS: &[&str] = &[
+    "about", "after", "all", "also", "and", "any", "are", "because", "been", "before", "but",
+    "can", "could", "did", "does", "each", "for", "from", "had", "has", "have", "her", "here",
+    "his", "how", "into", "its", "just", "like", "more", "most", "not", "now", "only", "other",
+    "our", "out", "over", "same", "see", "she", "should", "some", "such", "than", "that", "the",
+    "their", "them", "then", "there", "these", "they", "this", "those", "through", "too", "use",
+    "used", "very", "was", "way", "were", "what", "when", "where", "which", "while", "who", "why",
+    "will", "with", "would", "you", "your",
+];
+
+#[derive(Debug)]
+pub struct TopicOptions {
+    /// The similarity from which two notes are neighbours, between 0 and 1
+    pub threshold: f64,
+    /// Smaller clusters are not topics
+    pub min_size: usize,
+    /// The number of terms labelling a topic
+    pub terms: usize,
+    /// Leave out the notes under this category, where the index notes are written
+    pub exclude: Option<PathBuf>,
+}
+
+/// A cluster of related notes.
+#[derive(Debug, Serialize)]
+pub struct Topic {
+    /// The terms weighing the most in its notes, by TF-IDF
+    pub terms: Vec<String>,
+    /// The paths of its notes relative to the note root
+    pub notes: Vec<String>,
+}
+
+impl Topic {
+    pub fn label(&self) -> String {
+        self.terms.join(", ")
+    }
+}
+
+/// Cluster the notes of a vault into topics, largest first. Notes are neighbours when the
+/// cosine similarity of their TF-IDF vectors, plus `LINK_WEIGHT` when they are linked, reaches
+/// the threshold, and clusters are found by label propagation over the neighbours.
+pub fn topics(note_root: &Path, options: &TopicOptions) -> Result<Vec<Topic>> {
+    let graph = Graph::build(note_root)?;
+    let excluded = |id: &str| {
+        options
+            .exclude
+            .as_ref()
+            .is_some_and(|dir| Path::new(id).starts_with(dir))
+    };
+    let ids: Vec<&str> = graph
+        .nodes
+        .iter()
+        .map(|n| n.id.as_str())
+        .filter(|id| !excluded(id))
+        .collect();
+    let position: HashMap<&str, usize> = ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+
+    let documents: Vec<Vec<String>> = ids
+        .iter()
+        .map(|id| note_terms(&note_root.join(id)))
+        .collect();
+    let vectors = tf_idf(&documents);
+
+    let mut weights: Vec<BTreeMap<usize, f64>> = vec![BTreeMap::new(); ids.len()];
+    for i in 0..ids.len() {
+        for j in i + 1..ids.len() {
+            let similarity = cosine(&vectors[i], &vectors[j]);
+            if similarity > 0.0 {
+                weights[i].insert(j, similarity);
+                weights[j].insert(i, similarity);
+            }
+        }
+    }
+    for link in &graph.links {
+        if let (Some(&i), Some(&j)) = (
+            position.get(link.source.as_str()),
+            position.get(link.target.as_str()),
+        ) {
+            // Links both ways count once
+            if weights[i].get(&j).is_none_or(|&w| w < LINK_WEIGHT) {
+                let w = weights[i].get(&j).copied().unwrap_or_default() + LINK_WEIGHT;
+                weights[i].insert(j, w);
+                weights[j].insert(i, w);
+            }
+        }
+    }
+    let neighbours: Vec<Vec<(usize, f64)>> = weights
+        .into_iter()
+        .map(|w| {
+            w.into_iter()
+                .filter(|&(_, w)| w >= options.threshold)
+                .collect()
+        })
+        .collect();
+
+    let mut clusters: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for (i, label) in propagate_labels(&neighbours).into_iter().enumerate() {
+        clusters.entry(label).or_default().push(i);
+    }
+
+    let mut topics: Vec<Topic> = clusters
+        .into_values()
+        .filter(|members| members.len() >= options.min_size.max(1))
+        .map(|members| Topic {
+            terms: top_terms(members.iter().map(|&i| &vectors[i]), options.terms),
+            notes: members.iter().map(|&i| ids[i].to_string()).collect(),
+        })
+        .collect();
+    topics.sort_by_key(|t| std::cmp::Reverse(t.notes.len()));
+    Ok(topics)
+}
+
+/// The terms of a note: its title, keywords and body. Words are lowercased and CJK text is
+/// split into bigrams, as it has no spaces.
+fn note_terms(note: &Path) -> Vec<String> {
+    let Ok(main_file) = note.main_file_path() else {
+        return Vec::new();
+    };
+    let Ok(note_text) = NoteText::read(&main_file) else {
+        return Vec::new();
+    };
+    let metadata = note_text.metadata();
+    let mut text = metadata.title.unwrap_or_default();
+    for keyword in &metadata.keywords {
+        text.push('\n');
+        text.push_str(keyword);
+    }
+    text.push('\n');
+    text.push_str(&note_text.body());
+    tokenize(&text)
+}
+
+fn tokenize(text: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    let mut word = String::new();
+    let mut cjk: Vec<char> = Vec::new();
+    let mut flush = |word: &mut String, cjk: &mut Vec<char>| {
+        if word.chars().count() >= 3
+            && !word.chars().all(|c| c.is_ascii_digit())
+            && !STOP_WORDS.contains(&word.as_str())
+        {
+            terms.push(word.clone());
+        }
+        word.clear();
+        match cjk.len() {
+            0 => {}
+            1 => terms.push(cjk[0].to_string()),
+            _ => terms.extend(cjk.windows(2).map(|w| w.iter().collect())),
+        }
+        cjk.clear();
+    };
+
+    for c in text.chars() {
+        if is_cjk(c) {
+            if !word.is_empty() {
+                let mut no_cjk = Vec::new();
+                flush(&mut word, &mut no_cjk);
+            }
+            cjk.push(c);
+        } else if c.is_alphanumeric() {
+            if !cjk.is_empty() {
+                let mut no_word = String::new();
+                flush(&mut no_word, &mut cjk);
+            }
+            word.extend(c.to_lowercase());
+        } else {
+            flush(&mut word, &mut cjk);
+        }
+    }
+    flush(&mut word, &mut cjk);
+    terms
+}
+
+/// The TF-IDF vectors of the documents, normalized. Terms of a single document cannot relate
+/// notes, but they label topics all the same.
+fn tf_idf(documents: &[Vec<String>]) -> Vec<HashMap<String, f64>> {
+    let mut document_frequency: HashMap<&str, usize> = HashMap::new();
+    for terms in documents {
+        let mut unique: Vec<&str> = terms.iter().map(String::as_str).collect();
+        unique.sort_unstable();
+        unique.dedup();
+        for term in unique {
+            *document_frequency.entry(term).or_default() += 1;
+        }
+    }
+
+    let n = documents.len() as f64;
+    documents
+        .iter()
+        .map(|terms| {
+            let mut vector: HashMap<String, f64> = HashMap::new();
+            for term in terms {
+                *vector.entry(term.clone()).or_default() += 1.0;
+            }
+            for (term, weight) in vector.iter_mut() {
+                let idf = (n / document_frequency[term.as_str()] as f64).ln() + 1.0;
+                *weight = *weight / terms.len() as f64 * idf;
+            }
+            let norm = vector.values().map(|w| w * w).sum::<f64>().sqrt();
+            if norm > 0.0 {
+                vector.values_mut().for_each(|w| *w /= norm);
+            }
+            vector
+        })
+        .collect()
+}
+
+fn cosine(a: &HashMap<String, f64>, b: &HashMap<String, f64>) -> f64 {
+    let (small, large) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    small
+        .iter()
+        .filter_map(|(term, w)| large.get(term).map(|v| w * v))
+        .sum()
+}
+
+/// Give each note the label weighing the most among its neighbours, until no label changes.
+/// Ties keep the current label, else go to the smallest one, so that the result does not
+/// depend on hashing.
+fn propagate_labels(neighbours: &[Vec<(usize, f64)>]) -> Vec<usize> {
+    let mut labels: Vec<usize> = (0..neighbours.len()).collect();
+    for _ in 0..MAX_ROUNDS {
+        let mut changed = false;
+        for (i, edges) in neighbours.iter().enumerate() {
+            let mut scores: BTreeMap<usize, f64> = BTreeMap::new();
+            for &(j, w) in edges {
+                *scores.entry(labels[j]).or_default() += w;
+            }
+            let Some(best) = scores.values().copied().reduce(f64::max) else {
+                continue;
+            };
+            if scores.get(&labels[i]).is_some_and(|&s| s >= best) {
+                continue;
+            }
+            if let Some((&label, _)) = scores.iter().find(|&(_, &s)| s >= best) {
+                labels[i] = label;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    labels
+}
+
+fn top_terms<'a>(vectors: impl Iterator<Item = &'a HashMap<String, f64>>, n: usize) -> Vec<String> {
+    let mut sums: HashMap<&str, f64> = HashMap::new();
+    for vector in vectors {
+        for (term, w) in vector {
+            *sums.entry(term).or_default() += w;
+        }
+    }
+    let mut terms: Vec<(&str, f64)> = sums.into_iter().collect();
+    terms.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(b.0)));
+    terms
+        .into_iter()
+        .take(n)
+        .map(|(t, _)| t.to_string())
+        .collect()
+}
+
+/// Write an index note (a map of content) per topic under `category`, linking its notes. The
+/// index notes are named after the terms of their topic, and those of earlier runs with the
+/// same name are overwritten. Returns their paths.
+pub fn write_mocs(
+    note_root: &Path,
+    category: &Path,
+    note_type: NoteType,
+    topics: &[Topic],
+) -> Result<Vec<PathBuf>> {
+    let dir = note_root.join(category);
+    fs::create_dir_all(&dir)
+        .with_context(|| format!("Failed to create directory '{}'", dir.display()))?;
+
+    let mut written = Vec::new();
+    for topic in topics {
+        let mut name = slugify(&topic.terms.join(" "));
+        if name.is_empty() {
+            name = format!("topic-{}", written.len() + 1);
+        }
+        let path = dir.join(format!("{}.{}", name, note_type));
+        if path.exists() {
+            fs::remove_file(&path)
+                .with_context(|| format!("Failed to replace '{}'", path.display()))?;
+        }
+
+        let keywords = std::iter::once(MOC_KEYWORD.to_string())
+            .chain(topic.terms.iter().cloned())
+            .collect();
+        let main_file = create_note(
+            &path,
+            &NewNote {
+                note_type,
+                single_file: true,
+                keywords,
+                with_metadata: true,
+                ..Default::default()
+            },
+        )?;
+        let content = fs::read_to_string(&main_file)?;
+        let mut content = set_field(&content, note_type, "title", &Value::from(topic.label()))?;
+        if !content.is_empty() && !content.ends_with("\n\n") {
+            content.push('\n');
+        }
+        content.push_str(&moc_body(note_type, topic));
+        fs::write(&main_file, content)
+            .with_context(|| format!("Failed to write '{}'", main_file.display()))?;
+        written.push(main_file);
+    }
+    Ok(written)
+}
+
+/// A heading and a list of wiki-links to the notes of the topic, by their path without
+/// extension so that those in categories resolve to a single note.
+fn moc_body(note_type: NoteType, topic: &Topic) -> String {
+    let heading = match note_type {
+        NoteType::Typ => "=",
+        NoteType::Org => "*",
+        NoteType::Md | NoteType::Custom(_) => "#",
+    };
+    let mut body = format!("{} {}\n\n", heading, topic.label());
+    for note in &topic.notes {
+        let target = Path::new(note).with_extension("");
+        let target = target.to_string_lossy().replace('\\', "/");
+        body.push_str(&format!("- [[{}]]\n", target));
+    }
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::links::LinkIndex;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_topics() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        fs::create_dir_all(root.join("lang")).unwrap();
+        fs::write(
+            root.join("lang/rust.md"),
+            "---\ntitle: Rust ownership\n---\nOwnership and borrowing: the borrow checker.\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("lang/borrow.md"),
+            "The borrow checker enforces ownership rules.\n",
+        )
+        .unwrap();
+        fs::write(root.join("lifetimes.md"), "Lifetimes, see [[rust]].\n").unwrap();
+        fs::write(
+            root.join("bread.md"),
+            "Sourdough bread needs flour, water, salt.\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("pizza.md"),
+            "Pizza dough: flour, water, salt, yeast.\n",
+        )
+        .unwrap();
+        fs::write(root.join("chess.md"), "Openings of chess.\n").unwrap();
+
+        let options = TopicOptions {
+            threshold: 0.15,
+            min_size: 2,
+            terms: 3,
+            exclude: Some(PathBuf::from("topics")),
+        };
+        let topics = topics(root, &options).unwrap();
+        let notes: Vec<&[String]> = topics.iter().map(|t| t.notes.as_slice()).collect();
+        assert_eq!(
+            notes,
+            [
+                &["lang/borrow.md", "lang/rust.md", "lifetimes.md"][..],
+                &["bread.md", "pizza.md"][..],
+            ]
+        );
+        assert!(topics[0].terms.contains(&"ownership".to_string()));
+        assert!(topics[1].terms.contains(&"flour".to_string()));
+
+        assert_eq!(tokenize("The Rust 2024 笔记本"), ["rust", "笔记", "记本"]);
+
+        let written = write_mocs(root, Path::new("topics"), NoteType::Md, &topics).unwrap();
+        assert_eq!(written.len(), 2);
+        let content = fs::read_to_string(&written[1]).unwrap();
+        assert!(content.contains(&format!("title: \"{}\"", topics[1].label())));
+        assert!(
+            content.contains("- [[bread]]\n- [[pizza]]\n"),
+            "{}",
+            content
+        );
+        let index = LinkIndex::new(root).unwrap();
+        assert_eq!(
+            index.resolve("lang/borrow"),
+            Some(root.join("lang/borrow.md").as_path())
+        );
+        assert_eq!(
+            index.resolve("bread"),
+            Some(root.join("bread.md").as_path())
+        );
+
+        // Index notes are not clustered, and are rewritten
+        let again = self::topics(root, &options).unwrap();
+        assert_eq!(again.len(), 2);
+        write_mocs(root, Path::new("topics"), NoteType::Md, &again).unwrap();
+    }
+}
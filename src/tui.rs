@@ -1,190 +1,2116 @@
-// //! ## Model
-// //!
-// //! app model
-//
-// use std::time::{Duration, SystemTime};
-//
-// use tuirealm::event::NoUserEvent;
-// use tuirealm::props::{Alignment, Color, TextModifiers};
-// use tuirealm::ratatui::layout::{Constraint, Direction, Layout};
-// use tuirealm::terminal::{CrosstermTerminalAdapter, TerminalAdapter, TerminalBridge};
-// use tuirealm::{
-//     Application, AttrValue, Attribute, EventListenerCfg, Sub, SubClause, SubEventClause, Update,
-// };
-//
-// use super::components::{Clock, DigitCounter, Label, LetterCounter};
-// use super::{Id, Msg};
-//
-// pub struct Model<T>
-// where
-//     T: TerminalAdapter,
-// {
-//     /// Application
-//     pub app: Application<Id, Msg, NoUserEvent>,
-//     /// Indicates that the application must quit
-//     pub quit: bool,
-//     /// Tells whether to redraw interface
-//     pub redraw: bool,
-//     /// Used to draw to terminal
-//     pub terminal: TerminalBridge<T>,
-// }
-//
-// impl Default for Model<CrosstermTerminalAdapter> {
-//     fn default() -> Self {
-//         Self {
-//             app: Self::init_app(),
-//             quit: false,
-//             redraw: true,
-//             terminal: TerminalBridge::init_crossterm().expect("Cannot initialize terminal"),
-//         }
-//     }
-// }
-//
-// impl<T> Model<T>
-// where
-//     T: TerminalAdapter,
-// {
-//     pub fn view(&mut self) {
-//         assert!(self
-//             .terminal
-//             .draw(|f| {
-//                 let chunks = Layout::default()
-//                     .direction(Direction::Vertical)
-//                     .margin(1)
-//                     .constraints(
-//                         [
-//                             Constraint::Length(3), // Clock
-//                             Constraint::Length(3), // Letter Counter
-//                             Constraint::Length(3), // Digit Counter
-//                             Constraint::Length(1), // Label
-//                         ]
-//                         .as_ref(),
-//                     )
-//                     .split(f.area());
-//                 self.app.view(&Id::Clock, f, chunks[0]);
-//                 self.app.view(&Id::LetterCounter, f, chunks[1]);
-//                 self.app.view(&Id::DigitCounter, f, chunks[2]);
-//                 self.app.view(&Id::Label, f, chunks[3]);
-//             })
-//             .is_ok());
-//     }
-//
-//     fn init_app() -> Application<Id, Msg, NoUserEvent> {
-//         // Setup application
-//         // NOTE: NoUserEvent is a shorthand to tell tui-realm we're not going to use any custom user event
-//         // NOTE: the event listener is configured to use the default crossterm input listener and to raise a Tick event each second
-//         // which we will use to update the clock
-//
-//         let mut app: Application<Id, Msg, NoUserEvent> = Application::init(
-//             EventListenerCfg::default()
-//                 .crossterm_input_listener(Duration::from_millis(20), 3)
-//                 .poll_timeout(Duration::from_millis(10))
-//                 .tick_interval(Duration::from_secs(1)),
-//         );
-//         // Mount components
-//         assert!(app
-//             .mount(
-//                 Id::Label,
-//                 Box::new(
-//                     Label::default()
-//                         .text("Waiting for a Msg...")
-//                         .alignment(Alignment::Left)
-//                         .background(Color::Reset)
-//                         .foreground(Color::LightYellow)
-//                         .modifiers(TextModifiers::BOLD),
-//                 ),
-//                 Vec::default(),
-//             )
-//             .is_ok());
-//         // Mount clock, subscribe to tick
-//         assert!(app
-//             .mount(
-//                 Id::Clock,
-//                 Box::new(
-//                     Clock::new(SystemTime::now())
-//                         .alignment(Alignment::Center)
-//                         .background(Color::Reset)
-//                         .foreground(Color::Cyan)
-//                         .modifiers(TextModifiers::BOLD)
-//                 ),
-//                 vec![Sub::new(SubEventClause::Tick, SubClause::Always)]
-//             )
-//             .is_ok());
-//         // Mount counters
-//         assert!(app
-//             .mount(
-//                 Id::LetterCounter,
-//                 Box::new(LetterCounter::new(0)),
-//                 Vec::new()
-//             )
-//             .is_ok());
-//         assert!(app
-//             .mount(
-//                 Id::DigitCounter,
-//                 Box::new(DigitCounter::new(5)),
-//                 Vec::default()
-//             )
-//             .is_ok());
-//         // Active letter counter
-//         assert!(app.active(&Id::LetterCounter).is_ok());
-//         app
-//     }
-// }
-//
-// // Let's implement Update for model
-//
-// impl<T> Update<Msg> for Model<T>
-// where
-//     T: TerminalAdapter,
-// {
-//     fn update(&mut self, msg: Option<Msg>) -> Option<Msg> {
-//         if let Some(msg) = msg {
-//             // Set redraw
-//             self.redraw = true;
-//             // Match message
-//             match msg {
-//                 Msg::AppClose => {
-//                     self.quit = true; // Terminate
-//                     None
-//                 }
-//                 Msg::Clock => None,
-//                 Msg::DigitCounterBlur => {
-//                     // Give focus to letter counter
-//                     assert!(self.app.active(&Id::LetterCounter).is_ok());
-//                     None
-//                 }
-//                 Msg::DigitCounterChanged(v) => {
-//                     // Update label
-//                     assert!(self
-//                         .app
-//                         .attr(
-//                             &Id::Label,
-//                             Attribute::Text,
-//                             AttrValue::String(format!("DigitCounter has now value: {}", v))
-//                         )
-//                         .is_ok());
-//                     None
-//                 }
-//                 Msg::LetterCounterBlur => {
-//                     // Give focus to digit counter
-//                     assert!(self.app.active(&Id::DigitCounter).is_ok());
-//                     None
-//                 }
-//                 Msg::LetterCounterChanged(v) => {
-//                     // Update label
-//                     assert!(self
-//                         .app
-//                         .attr(
-//                             &Id::Label,
-//                             Attribute::Text,
-//                             AttrValue::String(format!("LetterCounter has now value: {}", v))
-//                         )
-//                         .is_ok());
-//                     None
-//                 }
-//             }
-//         } else {
-//             None
-//         }
-//     }
-// }
+use anyhow::{Context, Result, bail};
+use chrono::{Days, NaiveDate};
+use clap::Parser;
+use ratatui::{
+    Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashSet,
+    ffi::OsString,
+    fs,
+    path::{Component, Path, PathBuf},
+    process::{Command, ExitStatus},
+};
+
+use crate::agenda::{self, AgendaItem, ItemKind};
+use crate::bounded::preview_limit;
+use crate::cli::{Cli, NoteType};
+use crate::extract::NoteText;
+use crate::finder::{self, Finder, FinderAction};
+use crate::form::{FIELDS, Field, FormAction, NewNoteForm};
+use crate::highlight::highlight;
+use crate::hooks::{self, Hook};
+use crate::include::IncludeTree;
+use crate::markdown;
+use crate::ops::{Operation, OperationQueue};
+use crate::palette::{self, Palette, PaletteAction};
+use crate::process::{exec_with, new_note};
+use crate::record::human_size;
+use crate::snapshot::{self, Snapshot, SnapshotEntry};
+use crate::state::StateStore;
+use crate::theme::theme;
+use crate::vault::Note;
+use crate::zk::{Folgezettel, ZettelId};
+
+/// Browse the notes of a vault, with a preview of the selected note. A second pane can be
+/// opened, in place of the preview, to move or copy notes between categories. The session
+/// (directories, selected entries and scroll positions of the panes) is saved in the vault
+/// state on exit and restored on the next launch, unless `fresh`.
+///
+/// `:` opens a palette running any noxe command, with the selected note as its default note.
+/// `/` searches the notes by name and content, and jumps to the one picked. `n` creates a note
+/// as `noxe new` does, and jumps to it.
+pub fn run(note_root: &Path, fresh: bool) -> Result<()> {
+    let mut app = App::new(note_root)?;
+    let store = StateStore::new(note_root);
+    if !fresh {
+        // A session that cannot be read is not worth failing for
+        app.restore(&store.load(SESSION).unwrap_or_default());
+    }
+
+    let mut terminal = ratatui::init();
+    let result = (|| -> Result<()> {
+        while !app.quit {
+            terminal.draw(|frame| app.draw(frame))?;
+            if let Event::Key(key) = event::read()? {
+                app.handle_key(key);
+            }
+            if let Some(words) = app.new_note.take() {
+                // The hooks of the note may write to the terminal, or read from it
+                let hooked = hooks::is_set(Hook::PreNew) || hooks::is_set(Hook::PostNew);
+                if hooked {
+                    ratatui::restore();
+                }
+                app.create(words);
+                if hooked {
+                    terminal = ratatui::init();
+                }
+            }
+
+            if let Some(words) = app.run.take() {
+                ratatui::restore();
+                let result = run_noxe(&app.note_root, &words);
+                terminal = ratatui::init();
+                let line = palette::join(&words);
+                app.message = match result {
+                    Ok(status) if status.success() => format!("Ran: noxe {}", line),
+                    Ok(status) => format!("`noxe {}` failed: {}", line, status),
+                    Err(e) => format!("Error: {:#}", e),
+                };
+                // The command may have removed the directory of a pane
+                if let Err(e) = app.reload() {
+                    app.message = format!("Error: {:#}", e);
+                }
+            }
+        }
+        Ok(())
+    })();
+    ratatui::restore();
+
+    result.and_then(|_| store.save(SESSION, &app.session()))
+}
+
+/// Run noxe on the vault, then wait for Enter so that its output can be read.
+fn run_noxe(note_root: &Path, words: &[String]) -> Result<ExitStatus> {
+    println!("$ noxe {}", palette::join(words));
+    let status = Command::new(std::env::current_exe()?)
+        .args(words)
+        .env("NOXE_ROOT", note_root)
+        .status()
+        .context("Failed to run noxe")?;
+
+    println!("\nPress Enter to go back");
+    std::io::stdin().read_line(&mut String::new())?;
+    Ok(status)
+}
+
+/// The state file of the TUI session.
+const SESSION: &str = "tui-session";
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+struct Session {
+    panes: Vec<PaneSession>,
+    dual: bool,
+    active: usize,
+}
+
+/// Paths are relative to the note root.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+struct PaneSession {
+    dir: PathBuf,
+    selected: Option<PathBuf>,
+    offset: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    path: PathBuf,
+    is_category: bool,
+}
+
+/// A directory listing: the categories first, then the notes.
+#[derive(Debug)]
+struct Pane {
+    dir: PathBuf,
+    entries: Vec<Entry>,
+    selected: usize,
+    /// The first entry shown, kept from one frame to the next
+    offset: Cell<usize>,
+}
+
+impl Pane {
+    fn new(dir: &Path) -> Result<Self> {
+        let mut pane = Pane {
+            dir: dir.to_path_buf(),
+            entries: Vec::new(),
+            selected: 0,
+            offset: Cell::new(0),
+        };
+        pane.reload()?;
+        Ok(pane)
+    }
+
+    fn reload(&mut self) -> Result<()> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&self.dir)
+            .with_context(|| format!("Failed to read directory '{}'", self.dir.display()))?
+        {
+            let path = entry?.path();
+            if path
+                .file_name()
+                .is_some_and(|n| n.to_string_lossy().starts_with('.'))
+            {
+                continue;
+            }
+
+            if path.is_category() {
+                entries.push(Entry {
+                    path,
+                    is_category: true,
+                });
+            } else if path.is_filenote() || path.is_dirnote() {
+                entries.push(Entry {
+                    path,
+                    is_category: false,
+                });
+            }
+        }
+        entries.sort_by(|a, b| {
+            b.is_category
+                .cmp(&a.is_category)
+                .then_with(|| a.path.cmp(&b.path))
+        });
+
+        self.entries = entries;
+        self.selected = self.selected.min(self.entries.len().saturating_sub(1));
+        Ok(())
+    }
+
+    fn selected(&self) -> Option<&Entry> {
+        self.entries.get(self.selected)
+    }
+
+    fn open(&mut self, dir: &Path) -> Result<()> {
+        let previous = std::mem::replace(&mut self.dir, dir.to_path_buf());
+        if let Err(e) = self.reload() {
+            self.dir = previous;
+            return Err(e);
+        }
+        self.selected = 0;
+        self.offset.set(0);
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct App {
+    note_root: PathBuf,
+    panes: [Pane; 2],
+    /// The index of the pane receiving the keys
+    active: usize,
+    dual: bool,
+    /// Whether the preview is shown, when there is a single pane
+    preview: bool,
+    /// The lines of the last preview, for its note and width
+    preview_cache: RefCell<Option<(PathBuf, u16, Vec<Line<'static>>)>>,
+    queue: OperationQueue,
+    palette: Option<Palette>,
+    /// The `/` search, shown in place of the active pane
+    finder: Option<Finder>,
+    /// The form of `n`, creating a note
+    form: Option<NewNoteForm>,
+    /// The arguments of `noxe new` from the form, run by the run loop which owns the terminal
+    new_note: Option<Vec<OsString>>,
+    /// The command of the palette to run, by the run loop which owns the terminal
+    run: Option<Vec<String>>,
+    message: String,
+    quit: bool,
+}
+
+impl App {
+    fn new(note_root: &Path) -> Result<Self> {
+        Ok(App {
+            note_root: note_root.to_path_buf(),
+            panes: [Pane::new(note_root)?, Pane::new(note_root)?],
+            active: 0,
+            dual: false,
+            preview: true,
+            preview_cache: RefCell::new(None),
+            queue: OperationQueue::new(note_root),
+            palette: None,
+            finder: None,
+            form: None,
+            new_note: None,
+            run: None,
+            message: "Press ? for help".to_string(),
+            quit: false,
+        })
+    }
+
+    fn session(&self) -> Session {
+        let relative = |p: &Path| p.strip_prefix(&self.note_root).unwrap_or(p).to_path_buf();
+        Session {
+            panes: self
+                .panes
+                .iter()
+                .map(|pane| PaneSession {
+                    dir: relative(&pane.dir),
+                    selected: pane.selected().map(|e| relative(&e.path)),
+                    offset: pane.offset.get(),
+                })
+                .collect(),
+            dual: self.dual,
+            active: self.active,
+        }
+    }
+
+    /// Go back to a saved session, as far as the vault still matches it.
+    fn restore(&mut self, session: &Session) {
+        for (pane, saved) in self.panes.iter_mut().zip(&session.panes) {
+            let dir = self.note_root.join(&saved.dir);
+            if saved
+                .dir
+                .components()
+                .all(|c| matches!(c, Component::Normal(_)))
+                && dir.is_category()
+                && pane.open(&dir).is_err()
+            {
+                continue;
+            }
+            if let Some(selected) = &saved.selected {
+                let selected = self.note_root.join(selected);
+                if let Some(pos) = pane.entries.iter().position(|e| e.path == selected) {
+                    pane.selected = pos;
+                    pane.offset.set(saved.offset.min(pos));
+                }
+            }
+        }
+        self.dual = session.dual;
+        self.active = if session.dual {
+            session.active.min(1)
+        } else {
+            0
+        };
+    }
+
+    fn pane(&mut self) -> &mut Pane {
+        &mut self.panes[self.active]
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        if key.kind != KeyEventKind::Press {
+            return;
+        }
+        if let Err(e) = self.handle_code(key.code) {
+            self.message = format!("Error: {:#}", e);
+        }
+    }
+
+    fn handle_code(&mut self, code: KeyCode) -> Result<()> {
+        if let Some(palette) = &mut self.palette {
+            match palette.handle_code(code) {
+                PaletteAction::None => {}
+                PaletteAction::Close => self.palette = None,
+                PaletteAction::Run(words) => {
+                    self.palette = None;
+                    let mut history = palette::load_history(&self.note_root);
+                    palette::record(&self.note_root, &mut history, &palette::join(&words))?;
+                    self.run = Some(words);
+                }
+            }
+            return Ok(());
+        }
+        if let Some(form) = &mut self.form {
+            match form.handle_code(code) {
+                FormAction::None => {}
+                FormAction::Close => self.form = None,
+                FormAction::Create(words) => self.new_note = Some(words),
+            }
+            return Ok(());
+        }
+        if let Some(finder) = &mut self.finder {
+            match finder.handle_code(code) {
+                FinderAction::None => {}
+                FinderAction::Close => self.finder = None,
+                FinderAction::Jump(note) => {
+                    self.finder = None;
+                    self.jump(&note)?;
+                }
+            }
+            return Ok(());
+        }
+
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Char('j') | KeyCode::Down => {
+                let pane = self.pane();
+                if pane.selected + 1 < pane.entries.len() {
+                    pane.selected += 1;
+                }
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                let pane = self.pane();
+                pane.selected = pane.selected.saturating_sub(1);
+            }
+            KeyCode::Char('l') | KeyCode::Right | KeyCode::Enter => {
+                if let Some(entry) = self.pane().selected().filter(|e| e.is_category).cloned() {
+                    self.pane().open(&entry.path)?;
+                }
+            }
+            KeyCode::Char('h') | KeyCode::Left | KeyCode::Backspace => {
+                let dir = self.pane().dir.clone();
+                if dir != self.note_root
+                    && let Some(parent) = dir.parent()
+                {
+                    self.pane().open(parent)?;
+                    // Keep the category we came from selected
+                    let pane = self.pane();
+                    pane.selected = pane.entries.iter().position(|e| e.path == dir).unwrap_or(0);
+                }
+            }
+            KeyCode::Char('2') => {
+                self.dual = !self.dual;
+                self.active = 0;
+            }
+            KeyCode::Tab if self.dual => self.active = 1 - self.active,
+            KeyCode::Char('p') => self.preview = !self.preview,
+            KeyCode::Char(c @ ('m' | 'c')) if self.dual => {
+                let Some(entry) = self.pane().selected().filter(|e| !e.is_category).cloned() else {
+                    self.message = "Select a note to move or copy".to_string();
+                    return Ok(());
+                };
+                let target = &self.panes[1 - self.active].dir;
+                let operation = if c == 'm' {
+                    Operation::moving(&entry.path, target)
+                } else {
+                    Operation::copying(&entry.path, target)
+                };
+                self.message = format!("Queued: {}", self.relative(&operation));
+                self.queue.push(operation);
+            }
+            KeyCode::Char('d') => {
+                if let Some(operation) = self.queue.pop() {
+                    self.message = format!("Dropped: {}", self.relative(&operation));
+                }
+            }
+            KeyCode::Char('x') => {
+                let result = self.queue.run();
+                self.reload()?;
+                self.message = format!("Done {} operation(s)", result?);
+            }
+            KeyCode::Char('u') => {
+                let result = self.queue.undo();
+                self.reload()?;
+                self.message = match result? {
+                    Some(operation) => format!("Undone: {}", self.relative(&operation)),
+                    None => "Nothing to undo".to_string(),
+                };
+            }
+            KeyCode::Char(':') => {
+                let note = self
+                    .pane()
+                    .selected()
+                    .filter(|e| !e.is_category)
+                    .map(|e| e.path.display().to_string());
+                self.palette = Some(Palette::new(
+                    palette::noxe_commands(),
+                    palette::load_history(&self.note_root),
+                    note,
+                ));
+            }
+            KeyCode::Char('/') => self.finder = Some(Finder::new(&self.note_root)?),
+            KeyCode::Char('n') => {
+                let dir = self.pane().dir.clone();
+                let category = dir.strip_prefix(&self.note_root).unwrap_or(&dir);
+                self.form = Some(NewNoteForm::new(
+                    &self.note_root,
+                    &category.to_string_lossy().replace('\\', "/"),
+                )?);
+            }
+            KeyCode::Char('?') => {
+                self.message = "j/k: move, l/h: open/leave category, p: preview, 2: dual pane, \
+                    Tab: switch pane, m/c: queue move/copy to the other pane, d: drop queued, \
+                    x: run queue, u: undo, /: search, n: new note, :: command palette, q: quit"
+                    .to_string();
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Create a note with the arguments of `noxe new`, then jump to it. On failure, the form
+    /// stays open to fix what was wrong.
+    fn create(&mut self, words: Vec<OsString>) {
+        if let Err(e) = self.try_create(words)
+            && let Some(form) = &mut self.form
+        {
+            form.message = Some(format!("Error: {:#}", e));
+        }
+    }
+
+    fn try_create(&mut self, words: Vec<OsString>) -> Result<()> {
+        let cli = Cli::try_parse_from(std::iter::once(OsString::from("noxe")).chain(words))?;
+        let Some(command) = cli.command else {
+            bail!("No command");
+        };
+        let note = new_note(command)?;
+        self.form = None;
+        self.reload()?;
+        self.jump(&note)?;
+        self.message = format!(
+            "Created {}",
+            note.strip_prefix(&self.note_root)
+                .unwrap_or(&note)
+                .display()
+        );
+        Ok(())
+    }
+
+    /// Open the category of the note in the active pane, with the note selected.
+    fn jump(&mut self, note: &Path) -> Result<()> {
+        let dir = note.parent().unwrap_or(&self.note_root).to_path_buf();
+        let pane = self.pane();
+        pane.open(&dir)?;
+        pane.selected = pane
+            .entries
+            .iter()
+            .position(|e| e.path == note)
+            .unwrap_or(0);
+        self.message = format!(
+            "Jumped to {}",
+            note.strip_prefix(&self.note_root).unwrap_or(note).display()
+        );
+        Ok(())
+    }
+
+    fn reload(&mut self) -> Result<()> {
+        // The notes may have changed too
+        self.preview_cache.take();
+        for pane in &mut self.panes {
+            pane.reload()?;
+        }
+        Ok(())
+    }
+
+    /// Describe an operation with paths relative to the note root.
+    fn relative(&self, operation: &Operation) -> String {
+        let rel = |p: &Path| {
+            p.strip_prefix(&self.note_root)
+                .unwrap_or(p)
+                .display()
+                .to_string()
+        };
+        match operation {
+            Operation::Move { from, to } => format!("move {} -> {}", rel(from), rel(to)),
+            Operation::Copy { from, to } => format!("copy {} -> {}", rel(from), rel(to)),
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let queue_height = if self.queue.pending().is_empty() {
+            0
+        } else {
+            self.queue.pending().len().min(5) as u16 + 2
+        };
+        let [main, queue, status] = Layout::vertical([
+            Constraint::Min(3),
+            Constraint::Length(queue_height),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        if self.dual {
+            let [left, right] = Layout::horizontal([Constraint::Percentage(50); 2]).areas(main);
+            self.draw_pane(frame, 0, left);
+            self.draw_pane(frame, 1, right);
+        } else if self.preview {
+            let [left, right] =
+                Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
+                    .areas(main);
+            self.draw_pane(frame, 0, left);
+            self.draw_preview(frame, right);
+        } else {
+            self.draw_pane(frame, 0, main);
+        }
+
+        if queue_height > 0 {
+            let items: Vec<ListItem> = self
+                .queue
+                .pending()
+                .iter()
+                .map(|op| ListItem::new(self.relative(op)))
+                .collect();
+            frame.render_widget(
+                List::new(items).block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(" Queue (x: run, d: drop) "),
+                ),
+                queue,
+            );
+        }
+
+        if let Some(form) = &self.form {
+            self.draw_form(frame, form, main);
+        }
+
+        let message = match &self.palette {
+            Some(palette) => {
+                self.draw_palette(frame, palette, main);
+                palette.message.as_deref().unwrap_or(
+                    "Enter: pick/accept, Up/Down: select, Esc: close; arguments are quoted \"like this\"",
+                )
+            }
+            None => match &self.form {
+                Some(form) => form.message.as_deref().unwrap_or(
+                    "Tab: next field/complete, Up/Down: field, Left/Right: type, Enter: create, Esc: cancel",
+                ),
+                None if self.finder.is_some() => {
+                    "Type to search names and contents, Enter: jump, Up/Down: select, Esc: close"
+                }
+                None => self.message.as_str(),
+            },
+        };
+        frame.render_widget(Paragraph::new(message).style(theme().status_bar), status);
+    }
+
+    /// The palette over the top of the panes: the input, then the matching commands.
+    fn draw_palette(&self, frame: &mut Frame, palette: &Palette, area: Rect) {
+        let lines = palette.lines();
+        let height = (lines.len() as u16 + 3).min(area.height);
+        let width = (area.width * 4 / 5).max(area.width.min(40));
+        let area = Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + 1.min(area.height - height),
+            width,
+            height,
+        };
+        frame.render_widget(Clear, area);
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(theme().border)
+            .title(palette.title());
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+        let [input, list] =
+            Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(inner);
+
+        frame.render_widget(Paragraph::new(format!("> {}", palette.input)), input);
+        let list_widget =
+            List::new(lines.into_iter().map(ListItem::new)).highlight_style(theme().selection);
+        let mut state = ListState::default()
+            .with_selected((!palette.is_prompting()).then_some(palette.selected));
+        frame.render_stateful_widget(list_widget, list, &mut state);
+    }
+
+    fn draw_preview(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::default().borders(Borders::ALL).title(" Preview ");
+        let width = block.inner(area).width;
+        let selected = match &self.finder {
+            Some(finder) => finder.selected_note(),
+            None => self.panes[0]
+                .selected()
+                .filter(|e| !e.is_category)
+                .map(|e| e.path.as_path()),
+        };
+        let lines = match selected {
+            Some(note) => {
+                let mut cache = self.preview_cache.borrow_mut();
+                if !cache
+                    .as_ref()
+                    .is_some_and(|(path, w, _)| path == note && *w == width)
+                {
+                    let lines = preview_lines(note, width as usize)
+                        .unwrap_or_else(|e| vec![Line::from(format!("{:#}", e)).dim()]);
+                    *cache = Some((note.to_path_buf(), width, lines));
+                }
+                cache
+                    .as_ref()
+                    .map(|(_, _, lines)| lines.clone())
+                    .unwrap_or_default()
+            }
+            None => Vec::new(),
+        };
+
+        // Show the first match of the search, with a little context
+        let (lines, first) = match &self.finder {
+            Some(finder) => finder::highlight_matches(lines, &finder.query),
+            None => (lines, None),
+        };
+        let scroll = first.unwrap_or(0).saturating_sub(2) as u16;
+        frame.render_widget(
+            Paragraph::new(lines)
+                .style(theme().preview)
+                .block(block)
+                .wrap(Wrap { trim: false })
+                .scroll((scroll, 0)),
+            area,
+        );
+    }
+
+    /// The form over the top of the panes: the fields, then the completions of the focused one.
+    fn draw_form(&self, frame: &mut Frame, form: &NewNoteForm, area: Rect) {
+        let completions = form.completions();
+        let height = (FIELDS.len() as u16 + completions.len().min(8) as u16 + 2).min(area.height);
+        let width = (area.width * 3 / 5).max(area.width.min(40));
+        let area = Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + 1.min(area.height - height),
+            width,
+            height,
+        };
+        frame.render_widget(Clear, area);
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(theme().border)
+            .title(" New note ");
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let mut lines: Vec<Line> = FIELDS
+            .iter()
+            .enumerate()
+            .map(|(i, field)| {
+                let (label, value) = match field {
+                    Field::Name => ("Name", form.name.clone()),
+                    Field::Category => ("Category", form.category.clone()),
+                    Field::Type => ("Type", format!("< {} >", form.note_type())),
+                    Field::Template => ("Template", form.template.clone()),
+                };
+                let line = Line::from(format!("{:>8}: {}", label, value));
+                if i == form.focus {
+                    line.style(theme().selection)
+                } else {
+                    line
+                }
+            })
+            .collect();
+        lines.extend(
+            completions
+                .into_iter()
+                .take(8)
+                .map(|c| Line::from(format!("{:>10}{}", "", c)).dim()),
+        );
+        frame.render_widget(Paragraph::new(lines), inner);
+    }
+
+    fn draw_finder(&self, frame: &mut Frame, finder: &Finder, area: Rect) {
+        let list = List::new(finder.lines())
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(theme().border)
+                    .title(finder.title()),
+            )
+            .highlight_style(theme().selection);
+        let mut state = ListState::default().with_selected(Some(finder.selected));
+        frame.render_stateful_widget(list, area, &mut state);
+    }
+
+    fn draw_pane(&self, frame: &mut Frame, index: usize, area: Rect) {
+        if let Some(finder) = &self.finder
+            && index == self.active
+        {
+            self.draw_finder(frame, finder, area);
+            return;
+        }
+        let pane = &self.panes[index];
+        let title = pane
+            .dir
+            .strip_prefix(&self.note_root)
+            .ok()
+            .filter(|p| !p.as_os_str().is_empty())
+            .map_or("/".to_string(), |p| format!("/{}", p.display()));
+
+        let items: Vec<ListItem> = pane
+            .entries
+            .iter()
+            .map(|entry| {
+                let name = entry
+                    .path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string();
+                if entry.is_category {
+                    ListItem::new(format!("{}/", name)).style(theme().category)
+                } else {
+                    ListItem::new(name).style(theme().tree)
+                }
+            })
+            .collect();
+
+        let active = !self.dual || index == self.active;
+        let border = if active {
+            theme().border
+        } else {
+            Style::default()
+        };
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(border)
+                    .title(format!(" {} ", title)),
+            )
+            .highlight_style(theme().selection);
+
+        let mut state = ListState::default()
+            .with_offset(pane.offset.get())
+            .with_selected(active.then_some(pane.selected));
+        frame.render_stateful_widget(list, area, &mut state);
+        pane.offset.set(state.offset());
+    }
+}
+
+/// The preview of a note `width` columns wide: Markdown rendered, the source of Typst
+/// highlighted, and other notes as they are. Only the head of a huge note is read.
+fn preview_lines(note: &Path, width: usize) -> Result<Vec<Line<'static>>> {
+    let main_file = note.main_file_path()?;
+    let text = NoteText::read_head(&main_file, preview_limit())?;
+    let mut lines = match main_file.note_type()? {
+        NoteType::Md => markdown::to_lines(&markdown::parse(&text.body()), width),
+        NoteType::Typ => highlight("typ", &text.content)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|tokens| {
+                Line::from(
+                    tokens
+                        .into_iter()
+                        .map(|(token, text)| Span::styled(text.to_string(), token.style()))
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect(),
+        _ => text
+            .content
+            .lines()
+            .map(|line| Line::from(line.to_string()))
+            .collect(),
+    };
+    if let Some(total) = text.truncated {
+        lines.push(Line::default());
+        lines.push(
+            Line::from(format!(
+                "[truncated: showing the first {} of {}]",
+                human_size(text.content.len() as u64),
+                human_size(total)
+            ))
+            .dim(),
+        );
+    }
+    Ok(lines)
+}
+
+/// Browse the folgezettel tree under `root` (or the whole tree). Returns the note picked with
+/// Enter, if any.
+pub fn run_folgezettel(chain: &Folgezettel, root: Option<&ZettelId>) -> Result<Option<PathBuf>> {
+    let mut view = ZkView::new(chain, root)?;
+
+    let mut terminal = ratatui::init();
+    let result = (|| -> Result<()> {
+        while !view.quit {
+            terminal.draw(|frame| view.draw(frame))?;
+            if let Event::Key(key) = event::read()? {
+                view.handle_key(key);
+            }
+        }
+        Ok(())
+    })();
+    ratatui::restore();
+
+    result.map(|_| view.picked)
+}
+
+#[derive(Debug)]
+struct ZkEntry {
+    depth: usize,
+    id: ZettelId,
+    label: String,
+    path: PathBuf,
+}
+
+#[derive(Debug)]
+struct ZkView {
+    entries: Vec<ZkEntry>,
+    selected: usize,
+    picked: Option<PathBuf>,
+    message: String,
+    quit: bool,
+}
+
+impl ZkView {
+    fn new(chain: &Folgezettel, root: Option<&ZettelId>) -> Result<Self> {
+        let entries = chain
+            .tree(root)?
+            .into_iter()
+            .map(|(depth, (id, path))| ZkEntry {
+                depth,
+                id: id.clone(),
+                label: format!("{}  {}", id, path.note_name()),
+                path: chain.relative(path).to_path_buf(),
+            })
+            .collect();
+
+        Ok(ZkView {
+            entries,
+            selected: 0,
+            picked: None,
+            message: "Press ? for help".to_string(),
+            quit: false,
+        })
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        if key.kind != KeyEventKind::Press {
+            return;
+        }
+
+        let selected = self.entries.get(self.selected);
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Char('j') | KeyCode::Down if self.selected + 1 < self.entries.len() => {
+                self.selected += 1
+            }
+            KeyCode::Char('k') | KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Char('h') | KeyCode::Left => {
+                let parent = selected.and_then(|e| e.id.parent());
+                match self
+                    .entries
+                    .iter()
+                    .position(|e| Some(&e.id) == parent.as_ref())
+                {
+                    Some(pos) => self.selected = pos,
+                    None => self.message = "No parent in the tree".to_string(),
+                }
+            }
+            KeyCode::Char('l') | KeyCode::Right => {
+                let id = selected.map(|e| e.id.clone());
+                match self
+                    .entries
+                    .iter()
+                    .position(|e| e.id.parent() == id && id.is_some())
+                {
+                    Some(pos) => self.selected = pos,
+                    None => self.message = "No branch off this note".to_string(),
+                }
+            }
+            KeyCode::Enter => {
+                self.picked = selected.map(|e| e.path.clone());
+                self.quit = true;
+            }
+            KeyCode::Char('?') => {
+                self.message = "j/k: next/previous note, h: parent, l: first branch, \
+                    Enter: print the note and quit, q: quit"
+                    .to_string();
+            }
+            _ => {}
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [main, status] =
+            Layout::vertical([Constraint::Min(3), Constraint::Length(1)]).areas(frame.area());
+
+        let items: Vec<ListItem> = self
+            .entries
+            .iter()
+            .map(|e| ListItem::new(format!("{}{}", "  ".repeat(e.depth), e.label)))
+            .collect();
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(theme().border)
+                    .title(" Folgezettel "),
+            )
+            .highlight_style(theme().selection);
+
+        let mut state = ListState::default().with_selected(Some(self.selected));
+        frame.render_stateful_widget(list, main, &mut state);
+        frame.render_widget(
+            Paragraph::new(self.message.as_str()).style(theme().status_bar),
+            status,
+        );
+    }
+}
+
+/// Browse the `#include` tree of a typst note. Files are opened with `edit`; the git status
+/// and, when `check` is set, the compile diagnostics are refreshed when coming back.
+pub fn run_includes(tree: IncludeTree, edit: &[OsString], check: bool) -> Result<()> {
+    let mut view = IncludeView::new(tree, check);
+    view.refresh();
+
+    let mut terminal = ratatui::init();
+    let result = (|| -> Result<()> {
+        while !view.quit {
+            terminal.draw(|frame| view.draw(frame))?;
+            if let Event::Key(key) = event::read()? {
+                view.handle_key(key);
+            }
+
+            if let Some(path) = view.open.take() {
+                ratatui::restore();
+                let result = exec_with(&path, edit);
+                terminal = ratatui::init();
+                result?;
+                view.refresh();
+            }
+        }
+        Ok(())
+    })();
+    ratatui::restore();
+
+    result
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum IncludeRow {
+    File(usize),
+    /// A diagnostic of a file, by file and diagnostic index
+    Diagnostic(usize, usize),
+}
+
+#[derive(Debug)]
+struct IncludeView {
+    tree: IncludeTree,
+    check: bool,
+    collapsed: HashSet<usize>,
+    rows: Vec<IncludeRow>,
+    selected: usize,
+    open: Option<PathBuf>,
+    message: String,
+    quit: bool,
+}
+
+impl IncludeView {
+    fn new(tree: IncludeTree, check: bool) -> Self {
+        let mut view = IncludeView {
+            tree,
+            check,
+            collapsed: HashSet::new(),
+            rows: Vec::new(),
+            selected: 0,
+            open: None,
+            message: "Press ? for help".to_string(),
+            quit: false,
+        };
+        view.update_rows();
+        view
+    }
+
+    fn refresh(&mut self) {
+        let mut result = self.tree.read_status();
+        if self.check {
+            result = result.and_then(|_| self.tree.check());
+        }
+        self.message = match result {
+            Ok(()) => {
+                let errors = self
+                    .tree
+                    .nodes
+                    .iter()
+                    .flat_map(|n| &n.diagnostics)
+                    .filter(|d| d.severity == "error")
+                    .count();
+                match (self.check, errors) {
+                    (false, _) => "Press ? for help".to_string(),
+                    (true, 0) => "Compiled without errors".to_string(),
+                    (true, n) => format!("{} compile error(s)", n),
+                }
+            }
+            Err(e) => format!("Error: {:#}", e),
+        };
+        self.update_rows();
+    }
+
+    /// The visible rows: the files whose ancestors are all expanded, each followed by its
+    /// diagnostics while expanded.
+    fn update_rows(&mut self) {
+        let selected = self.rows.get(self.selected).copied();
+        self.rows.clear();
+        for (i, node) in self.tree.nodes.iter().enumerate() {
+            let mut parent = node.parent;
+            let mut hidden = false;
+            while let Some(p) = parent {
+                hidden |= self.collapsed.contains(&p);
+                parent = self.tree.nodes[p].parent;
+            }
+            if hidden {
+                continue;
+            }
+            self.rows.push(IncludeRow::File(i));
+            if !self.collapsed.contains(&i) {
+                self.rows
+                    .extend((0..node.diagnostics.len()).map(|j| IncludeRow::Diagnostic(i, j)));
+            }
+        }
+        self.selected = selected
+            .and_then(|row| self.rows.iter().position(|r| *r == row))
+            .unwrap_or(self.selected.min(self.rows.len().saturating_sub(1)));
+    }
+
+    fn selected_file(&self) -> Option<usize> {
+        self.rows.get(self.selected).map(|row| match row {
+            IncludeRow::File(i) | IncludeRow::Diagnostic(i, _) => *i,
+        })
+    }
+
+    fn select_file(&mut self, index: usize) {
+        if let Some(pos) = self.rows.iter().position(|r| *r == IncludeRow::File(index)) {
+            self.selected = pos;
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        if key.kind != KeyEventKind::Press {
+            return;
+        }
+
+        let file = self.selected_file();
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Char('j') | KeyCode::Down if self.selected + 1 < self.rows.len() => {
+                self.selected += 1
+            }
+            KeyCode::Char('k') | KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Char('l') | KeyCode::Right => {
+                let Some(i) = file else { return };
+                let first_child = self.tree.children(i).next();
+                if self.collapsed.remove(&i) {
+                    self.update_rows();
+                } else if let Some(child) = first_child {
+                    self.select_file(child);
+                } else {
+                    self.message = "No include in this file".to_string();
+                }
+            }
+            KeyCode::Char('h') | KeyCode::Left => {
+                let Some(i) = file else { return };
+                let has_content = self.tree.children(i).next().is_some()
+                    || !self.tree.nodes[i].diagnostics.is_empty();
+                if self.rows[self.selected] == IncludeRow::File(i)
+                    && has_content
+                    && self.collapsed.insert(i)
+                {
+                    self.update_rows();
+                } else if let Some(parent) = self.tree.nodes[i].parent {
+                    self.select_file(parent);
+                } else {
+                    self.select_file(i);
+                }
+            }
+            KeyCode::Enter => {
+                let Some(node) = file.map(|i| &self.tree.nodes[i]) else {
+                    return;
+                };
+                if node.missing {
+                    self.message = format!("'{}' does not exist", node.path.display());
+                } else {
+                    self.open = Some(node.path.clone());
+                }
+            }
+            KeyCode::Char('r') => self.refresh(),
+            KeyCode::Char('?') => {
+                self.message = "j/k: move, l: expand / first include, h: collapse / parent, \
+                    Enter: edit the file, r: refresh the status and diagnostics, q: quit"
+                    .to_string();
+            }
+            _ => {}
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [main, status] =
+            Layout::vertical([Constraint::Min(3), Constraint::Length(1)]).areas(frame.area());
+
+        let items: Vec<ListItem> = self
+            .rows
+            .iter()
+            .map(|row| match *row {
+                IncludeRow::File(i) => {
+                    let node = &self.tree.nodes[i];
+                    let fold = if self.tree.children(i).next().is_none() {
+                        " "
+                    } else if self.collapsed.contains(&i) {
+                        "+"
+                    } else {
+                        "-"
+                    };
+                    let mut label = format!(
+                        "{} {}{} {}",
+                        node.status.map_or(" ", |s| s.marker()),
+                        "  ".repeat(node.depth),
+                        fold,
+                        self.tree.relative(&node.path).display()
+                    );
+                    if let Some(heading) = &node.heading {
+                        label.push_str(&format!("  {}", heading));
+                    }
+                    let item = ListItem::new(label);
+                    if node.missing {
+                        item.red().add_modifier(Modifier::CROSSED_OUT)
+                    } else if node.cycle {
+                        item.yellow()
+                    } else if node.diagnostics.iter().any(|d| d.severity == "error") {
+                        item.red()
+                    } else {
+                        item
+                    }
+                }
+                IncludeRow::Diagnostic(i, j) => {
+                    let node = &self.tree.nodes[i];
+                    let d = &node.diagnostics[j];
+                    let item = ListItem::new(format!(
+                        "  {}    {}:{}: {}: {}",
+                        "  ".repeat(node.depth),
+                        d.line,
+                        d.column,
+                        d.severity,
+                        d.message
+                    ));
+                    if d.severity == "error" {
+                        item.red().dim()
+                    } else {
+                        item.yellow().dim()
+                    }
+                }
+            })
+            .collect();
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(theme().border)
+                    .title(format!(" Includes of {} ", self.tree.root.display())),
+            )
+            .highlight_style(theme().selection);
+
+        let mut state = ListState::default().with_selected(Some(self.selected));
+        frame.render_stateful_widget(list, main, &mut state);
+        frame.render_widget(
+            Paragraph::new(self.message.as_str()).style(theme().status_bar),
+            status,
+        );
+    }
+}
+
+/// Browse the snapshots of the vault, diff their notes against the current ones and restore
+/// single notes or directories.
+pub fn run_restore(note_root: &Path) -> Result<()> {
+    let mut view = RestoreView::new(note_root)?;
+
+    let mut terminal = ratatui::init();
+    let result = (|| -> Result<()> {
+        while !view.quit {
+            terminal.draw(|frame| view.draw(frame))?;
+            if let Event::Key(key) = event::read()? {
+                view.handle_key(key);
+            }
+        }
+        Ok(())
+    })();
+    ratatui::restore();
+
+    result
+}
+
+/// The snapshot being browsed and its directory, relative to the note root.
+#[derive(Debug)]
+struct SnapshotDir {
+    snapshot: usize,
+    dir: PathBuf,
+    entries: Vec<SnapshotEntry>,
+    selected: usize,
+}
+
+#[derive(Debug)]
+struct RestoreView {
+    note_root: PathBuf,
+    snapshots: Vec<Snapshot>,
+    selected: usize,
+    /// The snapshot drilled into, if any
+    browsing: Option<SnapshotDir>,
+    /// The diff of the selected entry against the current version, shown next to the list
+    diff: Option<Vec<String>>,
+    scroll: u16,
+    /// The entry to restore once confirmed
+    confirm: Option<PathBuf>,
+    message: String,
+    quit: bool,
+}
+
+impl RestoreView {
+    fn new(note_root: &Path) -> Result<Self> {
+        let snapshots = snapshot::snapshots(note_root)?;
+        let message = if snapshots.is_empty() {
+            "No snapshot yet, `noxe sync` makes one".to_string()
+        } else {
+            "Press ? for help".to_string()
+        };
+        Ok(RestoreView {
+            note_root: note_root.to_path_buf(),
+            snapshots,
+            selected: 0,
+            browsing: None,
+            diff: None,
+            scroll: 0,
+            confirm: None,
+            message,
+            quit: false,
+        })
+    }
+
+    fn open(&mut self, snapshot: usize, dir: PathBuf) {
+        let commit = &self.snapshots[snapshot].commit;
+        match snapshot::entries(&self.note_root, commit, &dir) {
+            Ok(entries) => {
+                // Coming back up, the directory left stays selected
+                let selected = self
+                    .browsing
+                    .as_ref()
+                    .and_then(|b| entries.iter().position(|e| e.path == b.dir))
+                    .unwrap_or(0);
+                self.browsing = Some(SnapshotDir {
+                    snapshot,
+                    dir,
+                    entries,
+                    selected,
+                });
+            }
+            Err(e) => self.message = format!("Error: {:#}", e),
+        }
+    }
+
+    fn selected_entry(&self) -> Option<(&Snapshot, &SnapshotEntry)> {
+        let browsing = self.browsing.as_ref()?;
+        let entry = browsing.entries.get(browsing.selected)?;
+        Some((&self.snapshots[browsing.snapshot], entry))
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        if key.kind != KeyEventKind::Press {
+            return;
+        }
+
+        if let Some(path) = self.confirm.take() {
+            if key.code == KeyCode::Char('y')
+                && let Some((snapshot, _)) = self.selected_entry()
+            {
+                let commit = snapshot.commit.clone();
+                self.message = match snapshot::restore(&self.note_root, &commit, &path) {
+                    Ok(()) => {
+                        self.diff = None;
+                        format!("Restored '{}'", path.display())
+                    }
+                    Err(e) => format!("Error: {:#}", e),
+                };
+            } else {
+                self.message = "Not restored".to_string();
+            }
+            return;
+        }
+
+        match (&mut self.browsing, key.code) {
+            (_, KeyCode::Char('q')) => self.quit = true,
+            (_, KeyCode::Esc) if self.diff.is_some() => self.diff = None,
+            (_, KeyCode::Char('J')) => self.scroll = self.scroll.saturating_add(1),
+            (_, KeyCode::Char('K')) => self.scroll = self.scroll.saturating_sub(1),
+            (None, KeyCode::Esc) => self.quit = true,
+            (None, KeyCode::Char('j') | KeyCode::Down)
+                if self.selected + 1 < self.snapshots.len() =>
+            {
+                self.selected += 1
+            }
+            (None, KeyCode::Char('k') | KeyCode::Up) => {
+                self.selected = self.selected.saturating_sub(1)
+            }
+            (None, KeyCode::Enter | KeyCode::Char('l') | KeyCode::Right)
+                if self.selected < self.snapshots.len() =>
+            {
+                self.open(self.selected, PathBuf::new())
+            }
+            (Some(b), KeyCode::Char('j') | KeyCode::Down) if b.selected + 1 < b.entries.len() => {
+                b.selected += 1
+            }
+            (Some(b), KeyCode::Char('k') | KeyCode::Up) => {
+                b.selected = b.selected.saturating_sub(1)
+            }
+            (Some(b), KeyCode::Enter | KeyCode::Char('l') | KeyCode::Right) => {
+                if let Some(entry) = b.entries.get(b.selected).filter(|e| e.is_dir) {
+                    let (snapshot, dir) = (b.snapshot, entry.path.clone());
+                    self.open(snapshot, dir);
+                }
+            }
+            (Some(b), KeyCode::Char('h') | KeyCode::Left | KeyCode::Backspace | KeyCode::Esc) => {
+                match b.dir.parent() {
+                    Some(parent) => {
+                        let (snapshot, parent) = (b.snapshot, parent.to_path_buf());
+                        self.open(snapshot, parent);
+                    }
+                    None => self.browsing = None,
+                }
+                self.diff = None;
+            }
+            (Some(_), KeyCode::Char('d')) => {
+                let Some((snapshot, entry)) = self.selected_entry() else {
+                    return;
+                };
+                match snapshot::diff(&self.note_root, &snapshot.commit, &entry.path) {
+                    Ok(diff) if diff.is_empty() => {
+                        self.message = format!("'{}' has not changed since", entry.path.display());
+                        self.diff = None;
+                    }
+                    Ok(diff) => {
+                        self.diff = Some(diff.lines().map(str::to_string).collect());
+                        self.scroll = 0;
+                    }
+                    Err(e) => self.message = format!("Error: {:#}", e),
+                }
+            }
+            (Some(_), KeyCode::Char('r')) => {
+                let Some((snapshot, entry)) = self.selected_entry() else {
+                    return;
+                };
+                let path = entry.path.clone();
+                self.message = format!(
+                    "Restore '{}' as of {}? (y/n)",
+                    path.display(),
+                    snapshot.date
+                );
+                self.confirm = Some(path);
+            }
+            (_, KeyCode::Char('?')) => {
+                self.message = "j/k: move, Enter/l: open, h: back, d: diff with the current \
+                    version, J/K: scroll the diff, r: restore, q: quit"
+                    .to_string();
+            }
+            _ => {}
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [main, status] =
+            Layout::vertical([Constraint::Min(3), Constraint::Length(1)]).areas(frame.area());
+        let [list_area, diff_area] = if self.diff.is_some() {
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)]).areas(main)
+        } else {
+            [main, Rect::default()]
+        };
+
+        let (items, selected, title): (Vec<ListItem>, usize, String) = match &self.browsing {
+            None => (
+                self.snapshots
+                    .iter()
+                    .map(|s| ListItem::new(format!("{}  {}  {}", s.short(), s.date, s.message)))
+                    .collect(),
+                self.selected,
+                " Snapshots ".to_string(),
+            ),
+            Some(b) => {
+                let snapshot = &self.snapshots[b.snapshot];
+                (
+                    b.entries
+                        .iter()
+                        .map(|e| {
+                            let name = e.path.file_name().unwrap_or_default().to_string_lossy();
+                            if e.is_dir {
+                                ListItem::new(format!("{}/", name)).blue()
+                            } else {
+                                ListItem::new(name.to_string())
+                            }
+                        })
+                        .collect(),
+                    b.selected,
+                    format!(" {} /{} ", snapshot.short(), b.dir.display()),
+                )
+            }
+        };
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(theme().border)
+                    .title(title),
+            )
+            .highlight_style(theme().selection);
+        let mut state = ListState::default().with_selected(Some(selected));
+        frame.render_stateful_widget(list, list_area, &mut state);
+
+        if let Some(diff) = &self.diff {
+            let lines: Vec<Line> = diff
+                .iter()
+                .map(|l| {
+                    let line = Line::from(l.as_str());
+                    if l.starts_with("+++") || l.starts_with("---") {
+                        line.bold()
+                    } else if l.starts_with('+') {
+                        line.green()
+                    } else if l.starts_with('-') {
+                        line.red()
+                    } else if l.starts_with("@@") {
+                        line.cyan()
+                    } else {
+                        line
+                    }
+                })
+                .collect();
+            let paragraph = Paragraph::new(lines)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(" Snapshot -> current "),
+                )
+                .scroll((self.scroll, 0));
+            frame.render_widget(paragraph, diff_area);
+        }
+        frame.render_widget(
+            Paragraph::new(self.message.as_str()).style(theme().status_bar),
+            status,
+        );
+    }
+}
+
+/// Browse the agenda of a day, or of its week. Items are marked done or rescheduled in their
+/// notes, which are opened with `edit`.
+pub fn run_agenda(
+    note_root: &Path,
+    pattern: &str,
+    date: NaiveDate,
+    week: bool,
+    edit: &[OsString],
+) -> Result<()> {
+    let mut view = AgendaView::new(note_root, pattern, date, week, crate::journal::today())?;
+
+    let mut terminal = ratatui::init();
+    let result = (|| -> Result<()> {
+        while !view.quit {
+            terminal.draw(|frame| view.draw(frame))?;
+            if let Event::Key(key) = event::read()? {
+                view.handle_key(key);
+            }
+
+            if let Some(path) = view.open.take() {
+                ratatui::restore();
+                let result = exec_with(&path, edit);
+                terminal = ratatui::init();
+                result?;
+                view.refresh();
+            }
+        }
+        Ok(())
+    })();
+    ratatui::restore();
+
+    result
+}
+
+#[derive(Debug)]
+struct AgendaView {
+    note_root: PathBuf,
+    pattern: String,
+    date: NaiveDate,
+    week: bool,
+    today: NaiveDate,
+    items: Vec<AgendaItem>,
+    selected: usize,
+    open: Option<PathBuf>,
+    message: String,
+    quit: bool,
+}
+
+impl AgendaView {
+    fn new(
+        note_root: &Path,
+        pattern: &str,
+        date: NaiveDate,
+        week: bool,
+        today: NaiveDate,
+    ) -> Result<Self> {
+        let mut view = AgendaView {
+            note_root: note_root.to_path_buf(),
+            pattern: pattern.to_string(),
+            date,
+            week,
+            today,
+            items: Vec::new(),
+            selected: 0,
+            open: None,
+            message: "Press ? for help".to_string(),
+            quit: false,
+        };
+        view.reload()?;
+        Ok(view)
+    }
+
+    fn reload(&mut self) -> Result<()> {
+        let (from, to) = agenda::range(self.date, self.week);
+        self.items = agenda::collect(&self.note_root, &self.pattern, from, to, self.today)?;
+        self.selected = self.selected.min(self.items.len().saturating_sub(1));
+        Ok(())
+    }
+
+    fn refresh(&mut self) {
+        if let Err(e) = self.reload() {
+            self.message = format!("Error: {:#}", e);
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        if key.kind != KeyEventKind::Press {
+            return;
+        }
+        if let Err(e) = self.handle_code(key.code) {
+            self.message = format!("Error: {:#}", e);
+            self.refresh();
+        }
+    }
+
+    fn handle_code(&mut self, code: KeyCode) -> Result<()> {
+        let item = self.items.get(self.selected).cloned();
+        let days = if self.week { 7 } else { 1 };
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Char('j') | KeyCode::Down if self.selected + 1 < self.items.len() => {
+                self.selected += 1
+            }
+            KeyCode::Char('k') | KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Char('n') => {
+                self.date = self.date + Days::new(days);
+                self.selected = 0;
+                self.reload()?;
+            }
+            KeyCode::Char('p') => {
+                self.date = self.date - Days::new(days);
+                self.selected = 0;
+                self.reload()?;
+            }
+            KeyCode::Char('w') => {
+                self.week = !self.week;
+                self.reload()?;
+            }
+            KeyCode::Char('r') => {
+                self.reload()?;
+                self.message = "Refreshed".to_string();
+            }
+            KeyCode::Enter => self.open = item.map(|item| item.file),
+            KeyCode::Char(c @ (' ' | 'x' | '>' | '<' | 't')) => {
+                let Some(item) = item.filter(|item| item.kind != ItemKind::Journal) else {
+                    self.message = "Select a task or a reminder".to_string();
+                    return Ok(());
+                };
+                let shown = agenda::shown_on(&item, self.today);
+                self.message = match c {
+                    ' ' | 'x' => {
+                        agenda::set_done(&item, !item.done)?;
+                        if item.done {
+                            format!("Open again: {}", item.text)
+                        } else {
+                            format!("Done: {}", item.text)
+                        }
+                    }
+                    '>' => {
+                        agenda::reschedule(&item, shown + Days::new(1))?;
+                        format!("Moved to {}: {}", shown + Days::new(1), item.text)
+                    }
+                    '<' => {
+                        agenda::reschedule(&item, shown - Days::new(1))?;
+                        format!("Moved to {}: {}", shown - Days::new(1), item.text)
+                    }
+                    _ => {
+                        agenda::reschedule(&item, self.today)?;
+                        format!("Moved to today: {}", item.text)
+                    }
+                };
+                self.reload()?;
+            }
+            KeyCode::Char('?') => {
+                self.message = "j/k: move, x/Space: done/open again, >/<: a day later/earlier, \
+                    t: today, Enter: open the note, n/p: next/previous day or week, w: day/week, \
+                    r: refresh, q: quit"
+                    .to_string();
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [main, status] =
+            Layout::vertical([Constraint::Min(3), Constraint::Length(1)]).areas(frame.area());
+
+        let items: Vec<ListItem> = self
+            .items
+            .iter()
+            .map(|item| {
+                let day = agenda::shown_on(item, self.today);
+                let line = format!(
+                    "{}  {}",
+                    day.format("%a %m-%d"),
+                    agenda::describe(item, &self.note_root, self.today)
+                );
+                match item.kind {
+                    _ if item.done => ListItem::new(Line::from(line).dim()),
+                    ItemKind::Task if item.date < self.today => {
+                        ListItem::new(Line::from(line).fg(Color::Red))
+                    }
+                    ItemKind::Reminder => ListItem::new(Line::from(line).fg(Color::Yellow)),
+                    ItemKind::Journal => ListItem::new(Line::from(line).fg(Color::Blue)),
+                    ItemKind::Task => ListItem::new(line),
+                }
+            })
+            .collect();
+
+        let (from, to) = agenda::range(self.date, self.week);
+        let title = if self.week {
+            format!(" Agenda {} - {} ", from, to)
+        } else {
+            format!(" Agenda {} ", from.format("%A %Y-%m-%d"))
+        };
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(theme().border)
+                    .title(title),
+            )
+            .highlight_style(theme().selection);
+
+        let mut state = ListState::default().with_selected(Some(self.selected));
+        frame.render_stateful_widget(list, main, &mut state);
+        frame.render_widget(
+            Paragraph::new(self.message.as_str()).style(theme().status_bar),
+            status,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn press(app: &mut App, keys: &str) {
+        for c in keys.chars() {
+            let code = match c {
+                '\t' => KeyCode::Tab,
+                '\n' => KeyCode::Enter,
+                c => KeyCode::Char(c),
+            };
+            app.handle_key(KeyEvent::from(code));
+        }
+    }
+
+    #[test]
+    fn test_dual_pane_move() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        fs::create_dir_all(root.join("cat")).unwrap();
+        fs::create_dir_all(root.join("other")).unwrap();
+        fs::write(root.join("cat/a.md"), "# A\n").unwrap();
+        fs::write(root.join("b.md"), "[[cat/a]]\n").unwrap();
+
+        let mut app = App::new(root).unwrap();
+        assert_eq!(
+            app.panes[0]
+                .entries
+                .iter()
+                .map(|e| &e.path)
+                .collect::<Vec<_>>(),
+            [&root.join("cat"), &root.join("other"), &root.join("b.md")]
+        );
+
+        // Left pane in `cat`, right pane in `other`, then move `a.md` to the right
+        press(&mut app, "2\n\tj\n\tm");
+        assert_eq!(app.panes[1].dir, root.join("other"));
+        assert_eq!(app.queue.pending().len(), 1);
+        press(&mut app, "x");
+        assert!(root.join("other/a.md").is_file());
+        assert_eq!(
+            fs::read_to_string(root.join("b.md")).unwrap(),
+            "[[other/a]]\n"
+        );
+        assert!(app.panes[0].entries.is_empty());
+        assert_eq!(app.panes[1].entries.len(), 1);
+
+        press(&mut app, "u");
+        assert!(root.join("cat/a.md").is_file());
+        assert_eq!(
+            fs::read_to_string(root.join("b.md")).unwrap(),
+            "[[cat/a]]\n"
+        );
+
+        // Back to the root, `cat` stays selected
+        press(&mut app, "h");
+        assert_eq!(app.panes[0].dir, root);
+        assert_eq!(app.panes[0].selected, 0);
+
+        press(&mut app, "q");
+        assert!(app.quit);
+    }
+
+    #[test]
+    fn test_preview_lines() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        fs::write(
+            root.join("a.md"),
+            "---\ntitle: A\n---\n# Title\n\n- **one**\n\n```rust\nfn main() {}\n```\n",
+        )
+        .unwrap();
+        fs::write(root.join("b.typ"), "= Title\n#let x = 1\n").unwrap();
+        let text = |lines: &[Line]| -> Vec<String> {
+            lines
+                .iter()
+                .map(|line| line.spans.iter().map(|s| s.content.as_ref()).collect())
+                .collect()
+        };
+
+        let lines = preview_lines(&root.join("a.md"), 40).unwrap();
+        assert_eq!(text(&lines), ["# Title", "", "• one", "", "  fn main() {}"]);
+        assert_eq!(lines[4].spans[1].content, "fn");
+        assert_eq!(lines[4].spans[1].style.fg, Some(Color::Magenta));
+
+        let lines = preview_lines(&root.join("b.typ"), 40).unwrap();
+        assert_eq!(text(&lines), ["= Title", "#let x = 1"]);
+        assert_eq!(lines[0].spans[0].style.fg, Some(Color::Cyan));
+    }
+
+    #[test]
+    fn test_search_jump() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        fs::create_dir_all(root.join("lang")).unwrap();
+        fs::write(root.join("lang/go.md"), "# Go\n").unwrap();
+        fs::write(root.join("lang/rust.md"), "# Rust\nborrow checker\n").unwrap();
+        fs::write(root.join("b.md"), "# B\n").unwrap();
+
+        let mut app = App::new(root).unwrap();
+        press(&mut app, "/borrow");
+        assert_eq!(
+            app.finder.as_ref().unwrap().selected_note(),
+            Some(root.join("lang/rust.md").as_path())
+        );
+        // Keys go to the search, not to the panes
+        assert!(!app.quit);
+        press(&mut app, "\n");
+        assert!(app.finder.is_none());
+        assert_eq!(app.panes[0].dir, root.join("lang"));
+        assert_eq!(
+            app.panes[0].selected().unwrap().path,
+            root.join("lang/rust.md")
+        );
+    }
+
+    #[test]
+    fn test_new_note_form() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path().to_path_buf();
+        fs::create_dir_all(root.join("lang")).unwrap();
+        fs::write(root.join("lang/go.md"), "# Go\n").unwrap();
+
+        // Parsing the `new` command takes more stack than the test threads have, as in
+        // `test_palette`
+        let app = std::thread::Builder::new()
+            .stack_size(8 << 20)
+            .spawn({
+                let root = root.clone();
+                move || {
+                    let mut app = App::new(&root).unwrap();
+                    press(&mut app, "nrust\tla\t\n");
+                    // As the run loop does
+                    let words = app.new_note.take().unwrap();
+                    app.create(words);
+                    app
+                }
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert!(app.form.is_none() && !app.quit);
+        assert!(root.join("lang/rust").exists());
+        assert_eq!(app.panes[0].dir, root.join("lang"));
+        assert_eq!(
+            app.panes[0].selected().unwrap().path,
+            root.join("lang/rust")
+        );
+    }
+
+    #[test]
+    fn test_session() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        fs::create_dir_all(root.join("cat")).unwrap();
+        fs::write(root.join("cat/a.md"), "").unwrap();
+        fs::write(root.join("cat/b.md"), "").unwrap();
+
+        // Left pane on `cat/b.md`, right pane at the root
+        let mut app = App::new(root).unwrap();
+        press(&mut app, "\nj2");
+        let session = app.session();
+        assert_eq!(session.panes[0].dir, PathBuf::from("cat"));
+        assert_eq!(session.panes[0].selected, Some(PathBuf::from("cat/b.md")));
+        assert!(session.dual);
+
+        let store = StateStore::new(root);
+        store.save(SESSION, &session).unwrap();
+        let mut app = App::new(root).unwrap();
+        app.restore(&store.load(SESSION).unwrap());
+        assert_eq!(app.panes[0].dir, root.join("cat"));
+        assert_eq!(app.panes[0].selected, 1);
+        assert!(app.dual);
+        assert_eq!(app.session(), session);
+
+        // Whatever was removed since is skipped
+        fs::remove_file(root.join("cat/b.md")).unwrap();
+        let mut app = App::new(root).unwrap();
+        app.restore(&store.load(SESSION).unwrap());
+        assert_eq!(app.panes[0].dir, root.join("cat"));
+        assert_eq!(app.panes[0].selected, 0);
+        fs::remove_dir_all(root.join("cat")).unwrap();
+        let mut app = App::new(root).unwrap();
+        app.restore(&store.load(SESSION).unwrap());
+        assert_eq!(app.panes[0].dir, root);
+    }
+
+    #[test]
+    fn test_palette() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path().to_path_buf();
+        fs::write(root.join("a.md"), "").unwrap();
+
+        // Building the whole command line takes more stack, in debug builds, than the 2 MiB of
+        // the test threads
+        let app = std::thread::Builder::new()
+            .stack_size(8 << 20)
+            .spawn({
+                let root = root.clone();
+                move || {
+                    let mut app = App::new(&root).unwrap();
+                    // `edit` with the selected note, without options
+                    press(&mut app, ":edit\n\n\n");
+                    assert!(app.palette.is_none());
+                    assert_eq!(
+                        app.run.take(),
+                        Some(vec![
+                            "edit".to_string(),
+                            root.join("a.md").display().to_string()
+                        ])
+                    );
+
+                    // The command is now the first of the palette, run again with Enter
+                    press(&mut app, ":");
+                    let lines = app.palette.as_ref().unwrap().lines();
+                    assert!(lines[0].starts_with("↺ edit"));
+                    press(&mut app, "\n");
+                    assert_eq!(app.run.as_ref().unwrap()[0], "edit");
+                    app.run = None;
+                    app
+                }
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert!(app.run.is_none() && !app.quit);
+        assert_eq!(palette::load_history(&root).len(), 1);
+    }
+
+    #[test]
+    fn test_agenda_view() {
+        let today = NaiveDate::from_ymd_opt(2025, 3, 12).unwrap();
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        let note = root.join("tasks.md");
+        fs::write(
+            &note,
+            "- [ ] send the report 2025-03-12\n- [ ] call Bob 2025-03-13\n",
+        )
+        .unwrap();
+
+        let mut view = AgendaView::new(root, "journal/%Y-%m-%d.md", today, false, today).unwrap();
+        assert_eq!(view.items.len(), 1);
+        let press = |view: &mut AgendaView, keys: &str| {
+            for c in keys.chars() {
+                let code = match c {
+                    '\n' => KeyCode::Enter,
+                    c => KeyCode::Char(c),
+                };
+                view.handle_key(KeyEvent::from(code));
+            }
+        };
+
+        // Done, then a day later: the report leaves the day
+        press(&mut view, "x>");
+        assert_eq!(
+            fs::read_to_string(&note).unwrap(),
+            "- [x] send the report 2025-03-13\n- [ ] call Bob 2025-03-13\n"
+        );
+        assert!(view.items.is_empty());
+
+        // The week shows both on Thursday, Bob is brought back to today
+        press(&mut view, "wt");
+        assert_eq!(view.items.len(), 2);
+        assert!(
+            fs::read_to_string(&note)
+                .unwrap()
+                .contains("Bob 2025-03-12")
+        );
+        press(&mut view, "\n");
+        assert_eq!(view.open, Some(note.clone()));
+        press(&mut view, "q");
+        assert!(view.quit);
+    }
+
+    #[test]
+    fn test_folgezettel_view() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        for name in ["1 Start.md", "1a Branch.md", "1a1 Deeper.md", "1b Other.md"] {
+            fs::write(root.join(name), "").unwrap();
+        }
+
+        let chain = Folgezettel::build(root).unwrap();
+        let mut view = ZkView::new(&chain, None).unwrap();
+        assert_eq!(view.entries.len(), 4);
+        assert_eq!(view.entries[2].depth, 2);
+
+        let mut press = |keys: &str| {
+            for c in keys.chars() {
+                let code = match c {
+                    '\n' => KeyCode::Enter,
+                    c => KeyCode::Char(c),
+                };
+                view.handle_key(KeyEvent::from(code));
+            }
+        };
+        // Down to `1a1`, then up to its parent `1a`
+        press("llh");
+        press("\n");
+        assert!(view.quit);
+        assert_eq!(view.picked, Some(PathBuf::from("1a Branch.md")));
+    }
+
+    #[test]
+    fn test_include_view() {
+        let tmp_dir = tempdir().unwrap();
+        let note = tmp_dir.path().join("book");
+        fs::create_dir_all(&note).unwrap();
+        fs::write(
+            note.join("main.typ"),
+            "#include \"a.typ\"\n#include \"b.typ\"\n",
+        )
+        .unwrap();
+        fs::write(note.join("a.typ"), "#include \"a1.typ\"\n").unwrap();
+        fs::write(note.join("a1.typ"), "").unwrap();
+
+        let mut tree = IncludeTree::build(&note).unwrap();
+        tree.nodes[2].diagnostics.push(crate::include::Diagnostic {
+            line: 1,
+            column: 1,
+            severity: "error".into(),
+            message: "oops".into(),
+        });
+        let mut view = IncludeView::new(tree, false);
+        assert_eq!(
+            view.rows,
+            [
+                IncludeRow::File(0),
+                IncludeRow::File(1),
+                IncludeRow::File(2),
+                IncludeRow::Diagnostic(2, 0),
+                IncludeRow::File(3),
+            ]
+        );
+
+        let press = |view: &mut IncludeView, keys: &str| {
+            for c in keys.chars() {
+                let code = match c {
+                    '\n' => KeyCode::Enter,
+                    c => KeyCode::Char(c),
+                };
+                view.handle_key(KeyEvent::from(code));
+            }
+        };
+        // Into `a.typ`, collapse it, then back to `main.typ` and collapse everything
+        press(&mut view, "lh");
+        press(&mut view, "k");
+        press(&mut view, "h");
+        assert_eq!(view.rows, [IncludeRow::File(0)]);
+        press(&mut view, "ll");
+        assert_eq!(view.rows.len(), 3);
+        assert_eq!(view.rows[view.selected], IncludeRow::File(1));
+
+        // `b.typ` does not exist and cannot be opened
+        press(&mut view, "jj\n");
+        assert_eq!(view.open, None);
+        press(&mut view, "k\n");
+        assert_eq!(view.open, Some(note.join("a.typ")));
+    }
+
+    #[test]
+    fn test_restore_view() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        let git = |args: &[&str]| crate::sync::run_git(root, args);
+        if git(&["init", "--quiet"]).is_err() {
+            return;
+        }
+        fs::create_dir_all(root.join("cat")).unwrap();
+        fs::write(root.join("cat/a.md"), "old\n").unwrap();
+        git(&["add", "--all"]).unwrap();
+        git(&[
+            "-c",
+            "user.name=noxe",
+            "-c",
+            "user.email=noxe@example.com",
+            "commit",
+            "--quiet",
+            "-m",
+            "noxe sync",
+        ])
+        .unwrap();
+        fs::write(root.join("cat/a.md"), "new\n").unwrap();
+
+        let mut view = RestoreView::new(root).unwrap();
+        assert_eq!(view.snapshots.len(), 1);
+        let press = |view: &mut RestoreView, keys: &str| {
+            for c in keys.chars() {
+                let code = match c {
+                    '\n' => KeyCode::Enter,
+                    c => KeyCode::Char(c),
+                };
+                view.handle_key(KeyEvent::from(code));
+            }
+        };
+        // Into the snapshot, then `cat`, diff `a.md`
+        press(&mut view, "\n\nd");
+        assert_eq!(view.browsing.as_ref().unwrap().dir, Path::new("cat"));
+        assert!(view.diff.as_ref().unwrap().iter().any(|l| l == "+new"));
+
+        // Declined, then restored
+        press(&mut view, "rn");
+        assert_eq!(fs::read_to_string(root.join("cat/a.md")).unwrap(), "new\n");
+        press(&mut view, "ry");
+        assert_eq!(fs::read_to_string(root.join("cat/a.md")).unwrap(), "old\n");
+        assert!(view.diff.is_none());
+
+        // Back to `cat` in the snapshot root, then to the snapshots
+        press(&mut view, "h");
+        assert_eq!(view.browsing.as_ref().unwrap().selected, 0);
+        press(&mut view, "h");
+        assert!(view.browsing.is_none());
+        press(&mut view, "q");
+        assert!(view.quit);
+    }
+}
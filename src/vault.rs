@@ -0,0 +1,1176 @@
+use anyhow::{Context, Result, bail};
+use chrono::{Datelike, Timelike};
+use clap::ValueEnum;
+use ignore::{DirEntry, WalkBuilder};
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::OsStr,
+    fs,
+    io::Write,
+    ops::Deref,
+    path::{Path, PathBuf},
+};
+
+use crate::archive::{is_archived, is_trashed};
+use crate::cli::NoteType;
+use crate::context::{CONTEXT_FIELD, ContextItem};
+use crate::crypt::{encrypted_path, is_encrypted};
+use crate::index::NoteIndex;
+use crate::lang::note_lang;
+use crate::metadata::{
+    FieldFilter, Fields, NoteMetadata, matches_fields, read_metadata, set_field,
+};
+use crate::zk::strip_timestamp_id;
+
+/// A directory of notes, the entry point for using noxe as a library.
+///
+/// Notes are returned as paths: a filenote is its file, a dirnote its directory. When the vault
+/// has an index (`noxe index`), notes and metadata are read from it.
+#[derive(Debug, Clone)]
+pub struct Vault {
+    root: PathBuf,
+}
+
+impl Vault {
+    pub fn open(root: impl Into<PathBuf>) -> Result<Self> {
+        let root = root.into();
+        if !root.is_dir() {
+            bail!("'{}' is not a directory", root.display());
+        }
+        Ok(Vault { root })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn notes(&self) -> Result<Vec<PathBuf>> {
+        all_notes(&self.root)
+    }
+
+    pub fn categories(&self) -> Result<Vec<PathBuf>> {
+        let index = NoteIndex::open(&self.root)?;
+        match index {
+            Some(index) => Ok(index.categories(&self.root)),
+            // Dirnotes are searched too, so that their subdirectories are skipped
+            None => Ok(search(&self.root, false, true, true, &|_| true)?[2]
+                .iter()
+                .map(|e| e.path().to_path_buf())
+                .collect()),
+        }
+    }
+
+    /// The notes with the given name (`note` or `note.md` for a filenote), ignoring case.
+    pub fn find(&self, name: &str) -> Result<Vec<PathBuf>> {
+        Ok(self
+            .notes()?
+            .into_iter()
+            .filter(|p| {
+                p.note_name().eq_ignore_ascii_case(name)
+                    || p.file_name().is_some_and(|n| n.eq_ignore_ascii_case(name))
+            })
+            .collect())
+    }
+
+    pub fn metadata(&self, note: &Path) -> Result<NoteMetadata> {
+        let index = NoteIndex::open(&self.root)?;
+        match note_metadata(index.as_ref(), &self.root, note) {
+            Some(metadata) => Ok(metadata),
+            None => read_metadata(&note.main_file_path()?),
+        }
+    }
+
+    /// The notes whose name, title, author or keywords match the pattern, like
+    /// `noxe search --metadata`.
+    pub fn search(&self, pattern: &Regex) -> Result<Vec<PathBuf>> {
+        let index = NoteIndex::open(&self.root)?;
+        Ok(notes_in(index.as_ref(), &self.root)?
+            .into_iter()
+            .filter(|p| {
+                p.file_name()
+                    .and_then(OsStr::to_str)
+                    .is_some_and(|n| pattern.is_match(n))
+                    || note_metadata(index.as_ref(), &self.root, p)
+                        .is_some_and(|m| m.is_match(pattern))
+            })
+            .collect())
+    }
+
+    /// Create a note at a path relative to the vault. Returns the path of its main file.
+    pub fn create(&self, note_path: impl AsRef<Path>, options: &NewNote) -> Result<PathBuf> {
+        create_note(&self.root.join(note_path), options)
+    }
+}
+
+/// How to create a note.
+#[derive(Debug, Default)]
+pub struct NewNote {
+    pub note_type: NoteType,
+    /// Create a filenote instead of a dirnote
+    pub single_file: bool,
+    pub author: Option<String>,
+    pub keywords: Vec<String>,
+    /// The layout of a dirnote and the initial content of its main file
+    pub template: NoteTemplate,
+    /// Start the main file with the title, author, keywords and date
+    pub with_metadata: bool,
+    /// Variables of the template, in addition to (or overriding) the built-in ones
+    pub vars: TemplateVars,
+    /// The environment the note is created in, written to its `context` metadata field
+    pub context: Fields,
+}
+
+/// Create a note. A path with a note extension (`note.md`) gives the note type and makes a
+/// filenote. Returns the path of its main file.
+pub fn create_note(note_path: &Path, options: &NewNote) -> Result<PathBuf> {
+    let mut note_type = options.note_type;
+    let mut single_file = options.single_file;
+
+    // 如果note_path包含扩展名，则表明是单文件
+    if let Some(ext) = note_path.extension().and_then(|ext| ext.to_str())
+        && let Ok(t) = NoteType::try_from(ext)
+    {
+        note_type = t;
+        single_file = true;
+    }
+
+    let note_name = note_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .map(strip_timestamp_id)
+        .ok_or_else(|| anyhow::anyhow!("Failed to parse note name"))?;
+
+    // Check if the note already exists
+    if fs::metadata(note_path).is_ok() {
+        bail!("Note '{}' already exists", note_path.display());
+    }
+
+    let main_path = if single_file {
+        note_path.to_path_buf()
+    } else {
+        note_path.join(note_type.main_file())
+    };
+
+    let mut main_file_data = String::new();
+
+    // Optionally add metadata
+    if options.with_metadata {
+        main_file_data.push_str(&metadata(
+            note_name,
+            options.author.as_ref(),
+            note_type,
+            &options.keywords,
+        ));
+    }
+
+    let mut vars = TemplateVars::from([
+        ("title".to_string(), note_name.to_string()),
+        (
+            "author".to_string(),
+            options.author.clone().unwrap_or_default(),
+        ),
+        ("keywords".to_string(), options.keywords.join(", ")),
+        (
+            "date".to_string(),
+            chrono::Local::now().format("%Y-%m-%d").to_string(),
+        ),
+        (
+            "category".to_string(),
+            note_path
+                .parent()
+                .map(|p| p.to_string_lossy().replace('\\', "/"))
+                .unwrap_or_default()
+                .trim_start_matches("./")
+                .to_string(),
+        ),
+    ]);
+    vars.extend(options.vars.clone());
+
+    // Create the note template
+    let template = &options.template.render(&vars);
+    if !single_file {
+        create_note_template(note_path, template)?;
+    }
+
+    // Add main file data
+    if matches!(note_type, NoteType::Typ)
+        && let Some(main_typ) = &template.main_typ
+    {
+        main_file_data.push_str(main_typ);
+    } else if matches!(note_type, NoteType::Md)
+        && let Some(main_md) = &template.main_md
+    {
+        main_file_data.push_str(main_md);
+    }
+
+    if !options.context.is_empty() && !matches!(note_type, NoteType::Custom(_)) {
+        let context = Value::Object(options.context.clone().into_iter().collect());
+        main_file_data = set_field(&main_file_data, note_type, CONTEXT_FIELD, &context)?;
+    }
+
+    // Create the main file and write data
+    fs::write(&main_path, main_file_data)
+        .with_context(|| format!("Failed to create main file '{}'", main_path.display()))?;
+
+    Ok(main_path)
+}
+
+// TODO: 改为NewType
+/// The kinds of paths in a vault: filenotes (`note.md`), dirnotes (a directory with a
+/// `main.typ`, `main.md`, `main.org` or the main file of a note type of the config) and categories (other
+/// directories).
+pub trait Note {
+    fn note_type(&self) -> Result<NoteType>;
+
+    fn main_file_path(&self) -> Result<PathBuf>;
+
+    fn is_filenote(&self) -> bool;
+
+    fn is_dirnote(&self) -> bool;
+
+    fn is_category(&self) -> bool;
+
+    fn is_note_name(&self) -> bool;
+
+    /// The name of the note: the file stem of a filenote, the directory name otherwise
+    fn note_name(&self) -> String;
+}
+
+impl<T: Deref<Target = Path>> Note for T {
+    fn note_type(&self) -> Result<NoteType> {
+        if let Some(ext) = self.extension().and_then(|ext| ext.to_str())
+            && let Ok(note_type) = NoteType::try_from(ext)
+        {
+            Ok(note_type)
+        } else {
+            bail!("Failed to parse note type from '{}'", self.display());
+        }
+    }
+
+    fn main_file_path(&self) -> Result<PathBuf> {
+        let note_path = if self.is_dir() {
+            match NoteType::all()
+                .into_iter()
+                .flat_map(|t| main_files(self, t))
+                .find(|p| p.is_file())
+            {
+                Some(main_file) => main_file,
+                None => bail!("No main file found in '{}'", self.display()),
+            }
+        } else {
+            self.to_path_buf()
+        };
+
+        Ok(note_path)
+    }
+
+    fn is_filenote(&self) -> bool {
+        // The type of an encrypted note comes before `.age`: `secrets.md.age`
+        let file = if is_encrypted(self) {
+            self.with_extension("")
+        } else {
+            self.to_path_buf()
+        };
+        self.is_file()
+            && file
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .and_then(|ext| NoteType::try_from(ext).ok())
+                .is_some()
+    }
+
+    fn is_dirnote(&self) -> bool {
+        self.is_dir() && has_main_file(self)
+    }
+
+    fn is_category(&self) -> bool {
+        self.is_dir() && !has_main_file(self)
+    }
+
+    fn is_note_name(&self) -> bool {
+        self.components().count() == 1
+    }
+
+    fn note_name(&self) -> String {
+        let name = if self.is_filenote() && is_encrypted(self) {
+            self.file_stem()
+                .and_then(|stem| Path::new(stem).file_stem())
+        } else if self.is_filenote() {
+            self.file_stem()
+        } else {
+            self.file_name()
+        };
+        name.map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+}
+
+fn has_main_file(dir: &Path) -> bool {
+    NoteType::all()
+        .into_iter()
+        .flat_map(|t| main_files(dir, t))
+        .any(|p| p.is_file())
+}
+
+/// The main files a dirnote of the type may have: `main.md`, or `main.md.age` when encrypted.
+fn main_files(dir: &Path, note_type: NoteType) -> [PathBuf; 2] {
+    let main_file = dir.join(note_type.main_file());
+    let encrypted = encrypted_path(&main_file);
+    [main_file, encrypted]
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum PathContent {
+    Directory(HashMap<String, PathContent>), // 子目录
+    File(String),                            // 文件内容
+}
+
+/// The variables substituted in templates, as `{{name}}`.
+pub type TemplateVars = BTreeMap<String, String>;
+
+/// Replace the `{{name}}` variables of `text` (spaces inside the braces are allowed). Unknown
+/// variables are left as they are.
+pub fn render_template(text: &str, vars: &TemplateVars) -> String {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        match after
+            .find("}}")
+            .and_then(|end| Some((vars.get(after[..end].trim())?, end)))
+        {
+            Some((value, end)) => {
+                result.push_str(value);
+                rest = &after[end + 2..];
+            }
+            None => {
+                result.push_str("{{");
+                rest = after;
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+impl PathContent {
+    fn render(&self, vars: &TemplateVars) -> PathContent {
+        match self {
+            PathContent::Directory(content) => PathContent::Directory(render_paths(content, vars)),
+            PathContent::File(content) => PathContent::File(render_template(content, vars)),
+        }
+    }
+}
+
+fn render_paths(
+    paths: &HashMap<String, PathContent>,
+    vars: &TemplateVars,
+) -> HashMap<String, PathContent> {
+    paths
+        .iter()
+        .map(|(name, content)| (render_template(name, vars), content.render(vars)))
+        .collect()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NoteTemplate {
+    paths: HashMap<String, PathContent>, // 顶层路径
+    #[serde(rename = "main.typ")]
+    main_typ: Option<String>,
+    #[serde(rename = "main.md")]
+    main_md: Option<String>,
+    /// What to capture of the environment into the `context` metadata field of new notes
+    #[serde(default)]
+    context: Vec<ContextItem>,
+    /// The repository whose commit the `git` context item is, the current directory's by
+    /// default
+    context_repo: Option<PathBuf>,
+}
+
+impl NoteTemplate {
+    /// Substitute the variables in the path names, the files and the main file contents.
+    pub fn render(&self, vars: &TemplateVars) -> NoteTemplate {
+        NoteTemplate {
+            paths: render_paths(&self.paths, vars),
+            main_typ: self.main_typ.as_ref().map(|c| render_template(c, vars)),
+            main_md: self.main_md.as_ref().map(|c| render_template(c, vars)),
+            context: self.context.clone(),
+            context_repo: self.context_repo.clone(),
+        }
+    }
+
+    pub fn context(&self) -> &[ContextItem] {
+        &self.context
+    }
+
+    pub fn context_repo(&self) -> Option<&Path> {
+        self.context_repo.as_deref()
+    }
+}
+
+impl Default for NoteTemplate {
+    fn default() -> Self {
+        let mut paths = HashMap::new();
+
+        paths.insert("images".to_string(), PathContent::Directory(HashMap::new()));
+        paths.insert(
+            "chapter".to_string(),
+            PathContent::Directory(HashMap::new()),
+        );
+        paths.insert(
+            "bibliography".to_string(),
+            PathContent::Directory(HashMap::new()),
+        );
+
+        NoteTemplate {
+            paths,
+            main_typ: None,
+            main_md: None,
+            context: Vec::new(),
+            context_repo: None,
+        }
+    }
+}
+
+fn create_note_template(note_path: &Path, template: &NoteTemplate) -> Result<()> {
+    // 递归创建目录和文件
+    fn create_paths(dir: &Path, content: &HashMap<String, PathContent>) -> Result<()> {
+        for (name, path_content) in content {
+            let current_path = dir.join(name);
+
+            match path_content {
+                PathContent::Directory(sub_content) => {
+                    fs::create_dir_all(&current_path).with_context(|| {
+                        format!("Failed to create directory '{}'", current_path.display())
+                    })?;
+                    create_paths(&current_path, sub_content)?;
+                }
+                PathContent::File(file_content) => {
+                    if let Some(parent) = current_path.parent() {
+                        fs::create_dir_all(parent).with_context(|| {
+                            format!("Failed to create parent directory '{}'", parent.display())
+                        })?;
+                    }
+                    let mut file = fs::OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open(&current_path)
+                        .with_context(|| {
+                            format!("Failed to create file '{}'", current_path.display())
+                        })?;
+                    file.write_all(file_content.as_bytes()).with_context(|| {
+                        format!("Failed to write to file '{}'", current_path.display())
+                    })?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    create_paths(note_path, &template.paths)?;
+
+    Ok(())
+}
+
+pub fn load_note_template(file_path: &OsStr) -> Result<NoteTemplate> {
+    let content = fs::read_to_string(file_path)
+        .with_context(|| format!("Failed to read template file '{}'", file_path.display()))?;
+    let template: NoteTemplate = serde_yml::from_str(&content)
+        .with_context(|| format!("Failed to parse template file '{}'", file_path.display()))?;
+    Ok(template)
+}
+
+fn metadata(
+    note_name: &str,
+    note_author: Option<&String>,
+    note_type: NoteType,
+    keywords: &[String],
+) -> String {
+    let keywords = keywords.join(", ");
+    let now = chrono::Local::now();
+
+    match note_type {
+        NoteType::Md => {
+            let mut md_metadata = String::from("---\n");
+            md_metadata.push_str(&format!("title: \"{}\"\n", note_name));
+            if let Some(author) = note_author {
+                md_metadata.push_str(&format!("author: \"{}\"\n", author));
+            }
+            if !keywords.is_empty() {
+                md_metadata.push_str(&format!("keywords: [{}]\n", keywords));
+            }
+            md_metadata.push_str(&format!(
+                "date: \"{}\"\n---\n\n",
+                now.format("%Y-%m-%d %H:%M:%S")
+            ));
+            md_metadata
+        }
+        NoteType::Typ => {
+            let mut typ_metadata = format!("#set document(title: \"{}\"", note_name);
+            if let Some(author) = note_author {
+                typ_metadata.push_str(&format!(", author: \"{}\"", author));
+            }
+            if !keywords.is_empty() {
+                typ_metadata.push_str(&format!(", keywords: ({})", keywords));
+            }
+            typ_metadata.push_str(&format!(
+                ", date: datetime(year: {}, month: {}, day: {}, hour: {}, minute: {}, second: {}))\n\n",
+                now.year(),
+                now.month(),
+                now.day(),
+                now.hour(),
+                now.minute(),
+                now.second()
+            ));
+            typ_metadata
+        }
+        NoteType::Org => {
+            let mut org_metadata = format!("#+TITLE: {}\n", note_name);
+            if let Some(author) = note_author {
+                org_metadata.push_str(&format!("#+AUTHOR: {}\n", author));
+            }
+            if !keywords.is_empty() {
+                org_metadata.push_str(&format!("#+KEYWORDS: {}\n", keywords));
+            }
+            org_metadata.push_str(&format!("#+DATE: {}\n\n", now.format("%Y-%m-%d %H:%M:%S")));
+            org_metadata
+        }
+        NoteType::Custom(_) => String::new(),
+    }
+}
+
+/// All the notes under the note root, from the index of the vault when it has one.
+pub(crate) fn all_notes(note_root: &Path) -> Result<Vec<PathBuf>> {
+    notes_in(NoteIndex::open(note_root)?.as_ref(), note_root)
+}
+
+/// The notes of `dir`, the note root or one of its categories, that are in the scope.
+pub(crate) fn scoped_notes(note_root: &Path, dir: &Path, scope: Scope) -> Result<Vec<PathBuf>> {
+    let mut notes = all_notes(dir)?;
+    notes.retain(|note| scope.contains(note_root, note));
+    Ok(notes)
+}
+
+/// Which notes of the vault commands see, by whether they are archived or in the trash.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Scope {
+    /// The notes outside of `archive/` and `trash/`
+    #[default]
+    Active,
+    /// The notes of `archive/`
+    Archived,
+    /// The notes of `trash/`
+    Trash,
+    All,
+}
+
+impl Scope {
+    pub fn contains(self, note_root: &Path, note: &Path) -> bool {
+        let archived = is_archived(note_root, note);
+        let trashed = is_trashed(note_root, note);
+        match self {
+            Scope::Active => !archived && !trashed,
+            Scope::Archived => archived,
+            Scope::Trash => trashed,
+            Scope::All => true,
+        }
+    }
+}
+
+pub(crate) fn notes_in(index: Option<&NoteIndex>, note_root: &Path) -> Result<Vec<PathBuf>> {
+    match index {
+        Some(index) => Ok(index.notes(note_root)),
+        None => Ok(search(note_root, true, true, false, &|_| true)?
+            .concat()
+            .into_iter()
+            .map(|e| e.into_path())
+            .collect()),
+    }
+}
+
+/// The notes of the category directories, or of the whole vault when there is none. Without an
+/// index, only the directories of the categories are walked.
+pub(crate) fn notes_in_categories(
+    index: Option<&NoteIndex>,
+    note_root: &Path,
+    categories: &[PathBuf],
+) -> Result<Vec<PathBuf>> {
+    if categories.is_empty() {
+        return notes_in(index, note_root);
+    }
+    let mut notes = match index {
+        Some(index) => {
+            let mut notes = index.notes(note_root);
+            notes.retain(|note| categories.iter().any(|dir| note.starts_with(dir)));
+            notes
+        }
+        None => {
+            let mut notes = Vec::new();
+            for dir in categories {
+                notes.extend(notes_in(None, dir)?);
+            }
+            notes
+        }
+    };
+    // Nested categories give the same notes
+    notes.sort();
+    notes.dedup();
+    Ok(notes)
+}
+
+/// The directory of a category, like `work/projects`. Each directory name matches
+/// case-insensitively when no directory has the exact name.
+pub(crate) fn category_dir(note_root: &Path, category: &str) -> Result<PathBuf> {
+    let mut dir = note_root.to_path_buf();
+    for name in category.split(['/', '\\']).filter(|n| !n.is_empty()) {
+        if !dir.join(name).is_dir() {
+            let found = fs::read_dir(&dir)
+                .with_context(|| format!("Failed to read directory '{}'", dir.display()))?
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .find(|p| {
+                    p.is_dir()
+                        && p.file_name()
+                            .is_some_and(|n| n.to_string_lossy().eq_ignore_ascii_case(name))
+                });
+            match found {
+                Some(found) => dir = found,
+                None => bail!("No category '{}' in '{}'", category, note_root.display()),
+            }
+            continue;
+        }
+        dir.push(name);
+    }
+    if !dir.is_category() {
+        bail!("'{}' is not a category", category);
+    }
+    Ok(dir)
+}
+
+/// The names by which notes are looked up: the file name of filenotes, the directory name of
+/// dirnotes, sorted. Only the directories are walked, no note is read.
+pub(crate) fn note_names(note_root: &Path) -> Result<Vec<String>> {
+    let mut filenotes = Vec::new();
+    let mut dirnotes = Vec::new();
+    handle_notes(
+        note_root,
+        Some(&mut |entry: DirEntry| {
+            filenotes.push(entry.file_name().to_string_lossy().to_string());
+            Ok(())
+        }),
+        Some(&mut |entry: DirEntry| {
+            dirnotes.push(entry.file_name().to_string_lossy().to_string());
+            Ok(())
+        }),
+        None,
+    )?;
+
+    let mut names = [filenotes, dirnotes].concat();
+    names.sort();
+    names.dedup();
+    Ok(names)
+}
+
+/// The metadata of a note, from the index when given.
+pub(crate) fn note_metadata(
+    index: Option<&NoteIndex>,
+    note_root: &Path,
+    note: &Path,
+) -> Option<NoteMetadata> {
+    match index {
+        Some(index) => index.metadata(note_root, note).cloned(),
+        None => note.main_file_path().and_then(|m| read_metadata(&m)).ok(),
+    }
+}
+
+/// The language of a note, from the index when given. Notes the index has no language for,
+/// as in indexes older than language detection, are read.
+pub(crate) fn note_lang_in(
+    index: Option<&NoteIndex>,
+    note_root: &Path,
+    note: &Path,
+) -> Option<String> {
+    index
+        .and_then(|index| index.lang(note_root, note))
+        .map(str::to_string)
+        .or_else(|| note_lang(note))
+}
+
+/// Check whether a note matches all field filters, like `matches_fields` but with the
+/// metadata from the index when given.
+pub(crate) fn note_matches_fields(
+    index: Option<&NoteIndex>,
+    note_root: &Path,
+    note: &Path,
+    filters: &[FieldFilter],
+) -> bool {
+    match index {
+        Some(_) => {
+            filters.is_empty()
+                || note_metadata(index, note_root, note)
+                    .is_some_and(|m| filters.iter().all(|f| f.matches(&m)))
+        }
+        None => matches_fields(note, filters),
+    }
+}
+
+pub(crate) fn search(
+    note_root: &Path,
+    search_filenote: bool,
+    search_dirnote: bool,
+    search_category: bool,
+    eq: &dyn Fn(&OsStr) -> bool,
+) -> Result<[Vec<DirEntry>; 3]> {
+    let mut filenotes = Vec::new();
+    let mut dirnotes = Vec::new();
+    let mut categories = Vec::new();
+
+    let mut handle_filenote = if search_filenote {
+        Some(|entry: DirEntry| {
+            if eq(entry.file_name()) {
+                filenotes.push(entry);
+            }
+            Ok(())
+        })
+    } else {
+        None
+    };
+    let mut handle_dirnote = if search_dirnote {
+        Some(|entry: DirEntry| {
+            if eq(entry.file_name()) {
+                dirnotes.push(entry);
+            }
+            Ok(())
+        })
+    } else {
+        None
+    };
+    let mut handle_category = if search_category {
+        Some(|entry: DirEntry| {
+            if eq(entry.file_name()) {
+                categories.push(entry);
+            }
+            Ok(())
+        })
+    } else {
+        None
+    };
+
+    handle_notes(
+        note_root,
+        handle_filenote
+            .as_mut()
+            .map(|f| f as &mut dyn FnMut(DirEntry) -> Result<()>),
+        handle_dirnote
+            .as_mut()
+            .map(|f| f as &mut dyn FnMut(DirEntry) -> Result<()>),
+        handle_category
+            .as_mut()
+            .map(|f| f as &mut dyn FnMut(DirEntry) -> Result<()>),
+    )?;
+
+    Ok([filenotes, dirnotes, categories])
+}
+
+fn handle_notes(
+    root: &Path,
+    mut handle_filenote: Option<&mut dyn FnMut(DirEntry) -> Result<()>>,
+    mut handle_dirnote: Option<&mut dyn FnMut(DirEntry) -> Result<()>>,
+    mut handle_category: Option<&mut dyn FnMut(DirEntry) -> Result<()>>,
+) -> Result<()> {
+    let mut it = WalkBuilder::new(root).build();
+
+    it.next();
+    loop {
+        let entry = match it.next() {
+            Some(entry) => entry,
+            None => break,
+        }?;
+
+        if let Some(handle) = handle_filenote.as_mut()
+            && entry.path().is_filenote()
+        {
+            handle(entry)?;
+        } else if let Some(handle) = handle_dirnote.as_mut()
+            && entry.path().is_dirnote()
+        {
+            handle(entry)?;
+            it.skip_current_dir();
+        } else if let Some(handle) = handle_category.as_mut()
+            && entry.path().is_category()
+        {
+            handle(entry)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_vault() {
+        let tmp_dir = tempdir().unwrap();
+        let vault = Vault::open(tmp_dir.path()).unwrap();
+        fs::create_dir(tmp_dir.path().join("cat")).unwrap();
+
+        let options = NewNote {
+            note_type: NoteType::Md,
+            with_metadata: true,
+            keywords: vec!["rust".to_string()],
+            ..Default::default()
+        };
+        let main_file = vault.create("cat/first", &options).unwrap();
+        assert_eq!(main_file, tmp_dir.path().join("cat/first/main.md"));
+        assert!(tmp_dir.path().join("cat/first/images").is_dir());
+        vault.create("second.typ", &NewNote::default()).unwrap();
+        assert!(vault.create("second.typ", &NewNote::default()).is_err());
+
+        let context = NewNote {
+            note_type: NoteType::Md,
+            with_metadata: true,
+            context: Fields::from([
+                ("cwd".to_string(), Value::from("/tmp/lab")),
+                ("git_dirty".to_string(), Value::from(false)),
+            ]),
+            ..Default::default()
+        };
+        let lab_dir = tempdir().unwrap();
+        let main_file = create_note(&lab_dir.path().join("lab.md"), &context).unwrap();
+        let content = fs::read_to_string(main_file).unwrap();
+        assert!(
+            content.contains("context:\n  cwd: \"/tmp/lab\"\n  git_dirty: false\n---"),
+            "{}",
+            content
+        );
+        let main_file = create_note(&lab_dir.path().join("lab.typ"), &context).unwrap();
+        let content = fs::read_to_string(main_file).unwrap();
+        assert!(
+            content.contains("#metadata((context: (cwd: \"/tmp/lab\", git_dirty: false)))"),
+            "{}",
+            content
+        );
+
+        let mut notes = vault.notes().unwrap();
+        notes.sort();
+        assert_eq!(
+            notes,
+            [
+                tmp_dir.path().join("cat/first"),
+                tmp_dir.path().join("second.typ")
+            ]
+        );
+        assert_eq!(vault.categories().unwrap(), [tmp_dir.path().join("cat")]);
+        assert_eq!(
+            vault.find("SECOND").unwrap(),
+            [tmp_dir.path().join("second.typ")]
+        );
+        assert_eq!(
+            vault.metadata(&notes[0]).unwrap().keywords,
+            ["rust".to_string()]
+        );
+        assert_eq!(
+            vault.search(&Regex::new("^rust$").unwrap()).unwrap(),
+            [tmp_dir.path().join("cat/first")]
+        );
+
+        assert!(Vault::open(tmp_dir.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn test_notes_in_categories() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        fs::create_dir_all(root.join("Work/Projects")).unwrap();
+        fs::create_dir_all(root.join("home")).unwrap();
+        fs::create_dir_all(root.join("Work/paper")).unwrap();
+        fs::write(root.join("Work/paper/main.typ"), "").unwrap();
+        fs::write(root.join("Work/Projects/noxe.md"), "").unwrap();
+        fs::write(root.join("Work/todo.md"), "").unwrap();
+        fs::write(root.join("home/garden.md"), "").unwrap();
+
+        let projects = category_dir(root, "work/projects").unwrap();
+        assert_eq!(projects, root.join("Work/Projects"));
+        assert!(category_dir(root, "play").is_err());
+        assert!(category_dir(root, "work/paper").is_err());
+
+        let work = category_dir(root, "WORK").unwrap();
+        let notes = notes_in_categories(None, root, &[work.clone(), projects]).unwrap();
+        assert_eq!(
+            notes,
+            [
+                root.join("Work/Projects/noxe.md"),
+                root.join("Work/paper"),
+                root.join("Work/todo.md"),
+            ]
+        );
+        assert_eq!(notes_in_categories(None, root, &[]).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_create_note_template_function() {
+        let tmp_dir = tempdir().unwrap();
+        let note_root = tmp_dir.path().join("templated_note");
+
+        let mut sub_paths = HashMap::new();
+        sub_paths.insert(
+            "subfile.md".to_string(),
+            PathContent::File("content".to_string()),
+        );
+        let mut example_paths = HashMap::new();
+        example_paths.insert("subdir".to_string(), PathContent::Directory(sub_paths));
+
+        let template = NoteTemplate {
+            paths: example_paths,
+            main_typ: Some("Typ content".into()),
+            main_md: Some("Md content".into()),
+            ..Default::default()
+        };
+
+        let result = create_note_template(&note_root, &template);
+        assert!(result.is_ok(), "Failed to create note template");
+
+        let subdir = note_root.join("subdir");
+        let subfile = subdir.join("subfile.md");
+        assert!(subdir.is_dir(), "Subdirectory was not created");
+        assert!(subfile.is_file(), "Subfile.md was not created");
+        let content = fs::read_to_string(subfile).unwrap();
+        assert_eq!(&content, "content", "Wrong content in subfile.md");
+    }
+
+    #[test]
+    fn test_render_template() {
+        let vars = TemplateVars::from([
+            ("title".to_string(), "Paper".to_string()),
+            ("venue".to_string(), "OSDI".to_string()),
+        ]);
+        assert_eq!(
+            render_template("# {{title}} ({{ venue }}) {{missing}} {{", &vars),
+            "# Paper (OSDI) {{missing}} {{"
+        );
+
+        let tmp_dir = tempdir().unwrap();
+        let template: NoteTemplate = serde_yml::from_str(
+            "paths:\n  \"{{title}}-notes.md\": \"from {{category}}\"\n\"main.md\": \"# {{title}} by {{author}} at {{venue}}\\n\"\n",
+        )
+        .unwrap();
+        let note = tmp_dir.path().join("papers").join("raft");
+        fs::create_dir_all(note.parent().unwrap()).unwrap();
+        let main_file = create_note(
+            &note,
+            &NewNote {
+                note_type: NoteType::Md,
+                author: Some("me".to_string()),
+                template,
+                vars,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(main_file).unwrap(),
+            "# Paper by me at OSDI\n"
+        );
+        let category = tmp_dir.path().join("papers").to_string_lossy().to_string();
+        assert_eq!(
+            fs::read_to_string(note.join("Paper-notes.md")).unwrap(),
+            format!("from {}", category)
+        );
+    }
+
+    #[test]
+    fn test_load_note_template_function() {
+        // We'll write a sample YAML file to a temp location, then load it.
+        let tmp_dir = tempdir().unwrap();
+        let template_file = tmp_dir.path().join("template.yml");
+
+        // Example template in YAML
+        let yaml_content = r#"
+paths:
+  images: {}
+  chapter: {}
+  bibliography: {}
+"main.typ": "Typ content here"
+"main.md": "Markdown content here"
+"#;
+
+        {
+            let mut f = File::create(&template_file).unwrap();
+            writeln!(f, "{}", yaml_content).unwrap();
+        }
+
+        let loaded_template = load_note_template(template_file.as_os_str());
+        assert!(loaded_template.is_ok(), "Failed to load note template");
+        let loaded_template = loaded_template.unwrap();
+
+        assert!(
+            loaded_template.paths.contains_key("images"),
+            "images folder missing"
+        );
+        assert!(
+            loaded_template.paths.contains_key("chapter"),
+            "chapter folder missing"
+        );
+        assert!(
+            loaded_template.paths.contains_key("bibliography"),
+            "bibliography folder missing"
+        );
+        assert_eq!(
+            loaded_template.main_typ.as_deref(),
+            Some("Typ content here"),
+            "Wrong main_typ"
+        );
+        assert_eq!(
+            loaded_template.main_md.as_deref(),
+            Some("Markdown content here"),
+            "Wrong main_md"
+        );
+    }
+
+    #[test]
+    fn test_note_trait_methods() {
+        let tmp_dir = tempdir().unwrap();
+
+        // Test filenote
+        let filenote_md = tmp_dir.path().join("note.md");
+        fs::File::create(&filenote_md).unwrap();
+        assert!(filenote_md.is_filenote());
+        assert!(!filenote_md.is_dirnote());
+        assert!(!filenote_md.is_category());
+
+        let filenote_typ = tmp_dir.path().join("note.typ");
+        fs::File::create(&filenote_typ).unwrap();
+        assert!(filenote_typ.is_filenote());
+        assert!(!filenote_typ.is_dirnote());
+        assert!(!filenote_typ.is_category());
+
+        // Test dirnote
+        let dirnote = tmp_dir.path().join("dirnote");
+        fs::create_dir(&dirnote).unwrap();
+        fs::File::create(dirnote.join("main.md")).unwrap();
+        assert!(dirnote.is_dirnote());
+        assert!(!dirnote.is_filenote());
+        assert!(!dirnote.is_category());
+
+        // Test category
+        let category = tmp_dir.path().join("category");
+        fs::create_dir(&category).unwrap();
+        assert!(category.is_category());
+        assert!(!category.is_filenote());
+        assert!(!category.is_dirnote());
+    }
+
+    #[test]
+    fn test_metadata_generation() {
+        let note_name = "TestNote";
+        let author = Some("AuthorName".to_string());
+        let keywords = ["kw1".to_string(), "kw2".to_string()];
+
+        // Test Markdown metadata
+        let md_meta = metadata(note_name, author.as_ref(), NoteType::Md, &keywords);
+        assert!(md_meta.contains("title: \"TestNote\""));
+        assert!(md_meta.contains("author: \"AuthorName\""));
+        assert!(md_meta.contains("keywords: [kw1, kw2]"));
+        assert!(md_meta.starts_with("---\n"));
+
+        // Test Typst metadata
+        let typ_meta = metadata(note_name, author.as_ref(), NoteType::Typ, &keywords);
+        assert!(typ_meta.contains("#set document(title: \"TestNote\""));
+        assert!(typ_meta.contains("author: \"AuthorName\""));
+        assert!(typ_meta.contains("keywords: (kw1, kw2)"));
+        assert!(typ_meta.contains("date: datetime"));
+    }
+
+    #[test]
+    fn test_search_function() {
+        let tmp_dir = tempdir().unwrap();
+        let note_root = tmp_dir.path();
+
+        // Create test data
+        let filenote = note_root.join("file.md");
+        fs::File::create(&filenote).unwrap();
+
+        let dirnote = note_root.join("dirnote");
+        fs::create_dir(&dirnote).unwrap();
+        fs::File::create(dirnote.join("main.md")).unwrap();
+
+        let category = note_root.join("category");
+        fs::create_dir(&category).unwrap();
+
+        // Search filenotes
+        let [filenotes, _, _] = search(note_root, true, false, false, &|s| s == "file.md").unwrap();
+        assert_eq!(filenotes.len(), 1);
+
+        // Search dirnotes
+        let [_, dirnotes, _] = search(note_root, false, true, false, &|s| s == "dirnote").unwrap();
+        assert_eq!(dirnotes.len(), 1);
+
+        // Search categories
+        let [_, _, categories] =
+            search(note_root, false, false, true, &|s| s == "category").unwrap();
+        assert_eq!(categories.len(), 1);
+    }
+
+    #[test]
+    fn test_note_path_resolution() {
+        let tmp_dir = tempdir().unwrap();
+
+        // Test filenote path
+        let filenote = tmp_dir.path().join("note.md");
+        fs::File::create(&filenote).unwrap();
+        assert_eq!(filenote.main_file_path().unwrap(), filenote);
+
+        // Test dirnote path
+        let dirnote = tmp_dir.path().join("dirnote");
+        fs::create_dir(&dirnote).unwrap();
+        fs::File::create(dirnote.join("main.md")).unwrap();
+        assert_eq!(dirnote.main_file_path().unwrap(), dirnote.join("main.md"));
+
+        // Test invalid dirnote
+        let invalid_dirnote = tmp_dir.path().join("invalid_dirnote");
+        fs::create_dir(&invalid_dirnote).unwrap();
+        assert!(invalid_dirnote.main_file_path().is_err());
+    }
+
+    #[test]
+    fn test_template_creation() {
+        let tmp_dir = tempdir().unwrap();
+        let note_root = tmp_dir.path().join("templated_note");
+
+        let mut sub_paths = HashMap::new();
+        sub_paths.insert(
+            "subfile.md".to_string(),
+            PathContent::File("content".to_string()),
+        );
+        let mut example_paths = HashMap::new();
+        example_paths.insert("subdir".to_string(), PathContent::Directory(sub_paths));
+
+        let template = NoteTemplate {
+            paths: example_paths,
+            main_typ: Some("Typ content".into()),
+            main_md: Some("Md content".into()),
+            ..Default::default()
+        };
+
+        create_note_template(&note_root, &template).unwrap();
+
+        // Verify directory structure
+        let subdir = note_root.join("subdir");
+        assert!(subdir.is_dir());
+
+        // Verify file content
+        let subfile = subdir.join("subfile.md");
+        assert!(subfile.is_file());
+        assert_eq!(fs::read_to_string(subfile).unwrap(), "content");
+    }
+}
@@ -0,0 +1,79 @@
+use anyhow::Result;
+use std::{fs, path::Path};
+
+use crate::cli::NoteType;
+use crate::extract::extractor;
+use crate::vault::Note;
+
+/// The number of words of the main file of a note.
+pub fn note_words(note_path: &Path) -> Result<usize> {
+    let main_file = note_path.main_file_path()?;
+    let content = fs::read_to_string(&main_file)?;
+    Ok(count_words(&content, main_file.note_type()?))
+}
+
+/// Count the words of the prose of a note: the metadata, fenced code blocks and markup are left
+/// out. Each CJK character counts as a word.
+pub fn count_words(content: &str, note_type: NoteType) -> usize {
+    count_plain(&extractor(note_type).plain_text(content))
+}
+
+fn count_plain(text: &str) -> usize {
+    let mut count = 0;
+    let mut in_word = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if is_cjk(c) {
+            count += 1;
+            in_word = false;
+        } else if c.is_alphanumeric() {
+            if !in_word {
+                count += 1;
+                in_word = true;
+            }
+        } else if in_word
+            && matches!(c, '\'' | '’' | '-')
+            && chars
+                .peek()
+                .is_some_and(|n| n.is_alphanumeric() && !is_cjk(*n))
+        {
+            // `don't` and `well-known` are single words
+        } else {
+            in_word = false;
+        }
+    }
+
+    count
+}
+
+pub(crate) fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30ff}' // Hiragana and Katakana
+        | '\u{3400}'..='\u{4dbf}'
+        | '\u{4e00}'..='\u{9fff}'
+        | '\u{ac00}'..='\u{d7af}' // Hangul
+        | '\u{f900}'..='\u{faff}'
+        | '\u{20000}'..='\u{2ebef}')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_words() {
+        assert_eq!(count_plain("Don't panic, it's well-known -- really."), 5);
+        assert_eq!(count_plain("Rust的生命周期"), 6);
+        assert_eq!(count_plain("ひらがな and 한국"), 7);
+
+        let md = "---\ntitle: \"Many words here\"\n---\n# A title\n\nSee [the docs](https://example.com/a/b) <br/> now.\n\n```rust\nfn main() {}\n```\n\n~~~\nskipped\n~~~\n**Bold** text\n";
+        assert_eq!(count_words(md, NoteType::Md), 8);
+
+        let typ = "#metadata((title: \"Many words here\")) <meta>\n#set text(lang: \"zh\")\n#import \"lib.typ\": *\n= A title\n\nSome #strong[bold] text, see @intro and $x^2 + y$. // a comment\n/* another\ncomment */\n#figure(image(\"a.png\"), caption: [Skipped]) <fig>\n```rust\nfn main() {}\n```\n#link(\"https://example.com\")[the docs] \\#1\n";
+        assert_eq!(count_words(typ, NoteType::Typ), 10);
+
+        let org = "#+TITLE: Many words here\n* A title\n# a comment\n#+CAPTION: skipped\nSee [[https://example.com][the docs]] and [[file:a.org]].\n#+BEGIN_SRC rust\nfn main() {}\n#+END_SRC\n#+begin_quote\nQuoted\n#+end_quote\n";
+        assert_eq!(count_words(org, NoteType::Org), 7);
+    }
+}
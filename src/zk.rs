@@ -0,0 +1,386 @@
+use anyhow::{Result, bail};
+use chrono::{NaiveDateTime, TimeDelta};
+use std::{
+    cmp::Ordering,
+    fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::cli::ZkCommand;
+use crate::tui;
+use crate::vault::{Note, all_notes};
+
+pub fn process_zk_command(command: ZkCommand) -> Result<()> {
+    match command {
+        ZkCommand::Next { id, note_root } => {
+            let chain = Folgezettel::build(Path::new(&note_root))?;
+            match chain.next(&id.parse()?)? {
+                Some(note) => chain.print(note),
+                None => bail!("'{}' is the last note of the sequence", id),
+            }
+        }
+        ZkCommand::Prev { id, note_root } => {
+            let chain = Folgezettel::build(Path::new(&note_root))?;
+            match chain.prev(&id.parse()?)? {
+                Some(note) => chain.print(note),
+                None => bail!("'{}' is the first note of the sequence", id),
+            }
+        }
+        ZkCommand::Children { id, note_root } => {
+            let chain = Folgezettel::build(Path::new(&note_root))?;
+            for note in chain.children(&id.parse()?)? {
+                chain.print(note);
+            }
+        }
+        ZkCommand::Tree {
+            id,
+            note_root,
+            print,
+        } => {
+            let chain = Folgezettel::build(Path::new(&note_root))?;
+            let root = id.map(|id| id.parse()).transpose()?;
+            if print {
+                for (depth, note) in chain.tree(root.as_ref())? {
+                    print!("{}", "  ".repeat(depth));
+                    chain.print(note);
+                }
+            } else if let Some(note) = tui::run_folgezettel(&chain, root.as_ref())? {
+                println!("{}", note.display());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Number(u64),
+    Letters(String),
+}
+
+impl Ord for Segment {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Segment::Number(a), Segment::Number(b)) => a.cmp(b),
+            // `z` comes before `aa`
+            (Segment::Letters(a), Segment::Letters(b)) => (a.len(), a).cmp(&(b.len(), b)),
+            (Segment::Number(_), Segment::Letters(_)) => Ordering::Less,
+            (Segment::Letters(_), Segment::Number(_)) => Ordering::Greater,
+        }
+    }
+}
+
+impl PartialOrd for Segment {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// A folgezettel ID such as `12a3`: alternating numbers and letters, each segment branching
+/// off the note named by the segments before it. IDs are ordered as the notes are read:
+/// `12` < `12a` < `12a1` < `12b` < `13`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ZettelId(Vec<Segment>);
+
+impl ZettelId {
+    /// The ID this one branches off, e.g. `12a` for `12a3`.
+    pub fn parent(&self) -> Option<ZettelId> {
+        (self.0.len() > 1).then(|| ZettelId(self.0[..self.0.len() - 1].to_vec()))
+    }
+
+    pub fn depth(&self) -> usize {
+        self.0.len() - 1
+    }
+
+    fn starts_with(&self, prefix: &ZettelId) -> bool {
+        self.0.starts_with(&prefix.0)
+    }
+
+    /// The ID a note name starts with, e.g. `12a3` for `12a3 Some title` or `12a3-title`.
+    pub fn from_note_name(name: &str) -> Option<ZettelId> {
+        let end = name
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(name.len());
+        name[..end].parse().ok()
+    }
+}
+
+impl FromStr for ZettelId {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if !s.starts_with(|c: char| c.is_ascii_digit()) {
+            bail!("Invalid zettel ID '{}': it must start with a number", s);
+        }
+
+        let mut segments = Vec::new();
+        let mut rest = s;
+        while !rest.is_empty() {
+            let digits = rest.starts_with(|c: char| c.is_ascii_digit());
+            let end = rest
+                .find(|c: char| c.is_ascii_digit() != digits)
+                .unwrap_or(rest.len());
+            let (segment, tail) = rest.split_at(end);
+
+            segments.push(if digits {
+                Segment::Number(segment.parse()?)
+            } else if segment.chars().all(|c| c.is_ascii_alphabetic()) {
+                Segment::Letters(segment.to_ascii_lowercase())
+            } else {
+                bail!("Invalid zettel ID '{}': unexpected '{}'", s, segment);
+            });
+            rest = tail;
+        }
+
+        Ok(ZettelId(segments))
+    }
+}
+
+impl fmt::Display for ZettelId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for segment in &self.0 {
+            match segment {
+                Segment::Number(n) => write!(f, "{}", n)?,
+                Segment::Letters(l) => write!(f, "{}", l)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The format of the timestamp IDs `noxe new --id` prefixes notes with:
+/// `202505171230-title.md`.
+pub const TIMESTAMP_ID_FORMAT: &str = "%Y%m%d%H%M";
+
+/// The timestamp ID a note name starts with, e.g. `202505171230` for `202505171230-title` or
+/// `202505171230`. IDs with seconds (14 digits) are recognized too.
+pub fn timestamp_id(name: &str) -> Option<&str> {
+    let end = name
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(name.len());
+    let rest = &name[end..];
+    ((end == 12 || end == 14) && (rest.is_empty() || rest.starts_with(['-', '_', ' ', '.'])))
+        .then(|| &name[..end])
+}
+
+/// The name of a note without its timestamp ID: `title` for `202505171230-title`.
+pub fn strip_timestamp_id(name: &str) -> &str {
+    match timestamp_id(name) {
+        Some(id) => {
+            let rest = name[id.len()..].trim_start_matches(['-', '_', ' ']);
+            // A note named by its ID alone keeps it
+            if rest.is_empty() || rest.starts_with('.') {
+                name
+            } else {
+                rest
+            }
+        }
+        None => name,
+    }
+}
+
+/// Prefix the file name of a new note with the timestamp ID of `time`. When a note of its
+/// directory already has that ID, the next minute free is taken, so that IDs stay unique.
+pub fn with_timestamp_id(note_path: &Path, time: NaiveDateTime) -> PathBuf {
+    let dir = match note_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let taken: Vec<String> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|e| timestamp_id(&e.file_name().to_string_lossy()).map(str::to_string))
+        .collect();
+
+    let mut time = time;
+    let mut id = time.format(TIMESTAMP_ID_FORMAT).to_string();
+    while taken.contains(&id) {
+        time += TimeDelta::minutes(1);
+        id = time.format(TIMESTAMP_ID_FORMAT).to_string();
+    }
+    let name = note_path.file_name().unwrap_or_default().to_string_lossy();
+    note_path.with_file_name(format!("{}-{}", id, name))
+}
+
+/// The notes of a vault whose names start with a zettel ID, in reading order.
+#[derive(Debug)]
+pub struct Folgezettel {
+    note_root: PathBuf,
+    notes: Vec<(ZettelId, PathBuf)>,
+}
+
+impl Folgezettel {
+    pub fn build(note_root: &Path) -> Result<Self> {
+        let mut notes: Vec<(ZettelId, PathBuf)> = all_notes(note_root)?
+            .into_iter()
+            .filter_map(|note| Some((ZettelId::from_note_name(&note.note_name())?, note)))
+            .collect();
+        notes.sort();
+
+        Ok(Folgezettel {
+            note_root: note_root.to_path_buf(),
+            notes,
+        })
+    }
+
+    fn position(&self, id: &ZettelId) -> Result<usize> {
+        match self.notes.iter().position(|(i, _)| i == id) {
+            Some(pos) => Ok(pos),
+            None => bail!("No note with the zettel ID '{}'", id),
+        }
+    }
+
+    /// The note read after `id`: its first branch, or the next note in the sequence.
+    pub fn next(&self, id: &ZettelId) -> Result<Option<&(ZettelId, PathBuf)>> {
+        let pos = self.position(id)?;
+        Ok(self.notes[pos + 1..].iter().find(|(i, _)| i != id))
+    }
+
+    pub fn prev(&self, id: &ZettelId) -> Result<Option<&(ZettelId, PathBuf)>> {
+        let pos = self.position(id)?;
+        Ok(self.notes[..pos].last())
+    }
+
+    /// The notes branching directly off `id`.
+    pub fn children(&self, id: &ZettelId) -> Result<Vec<&(ZettelId, PathBuf)>> {
+        self.position(id)?;
+        Ok(self
+            .notes
+            .iter()
+            .filter(|(i, _)| i.parent().as_ref() == Some(id))
+            .collect())
+    }
+
+    /// The notes under `root` (or all of them) with their depth below it, in reading order.
+    pub fn tree(&self, root: Option<&ZettelId>) -> Result<Vec<(usize, &(ZettelId, PathBuf))>> {
+        let base = match root {
+            Some(root) => {
+                self.position(root)?;
+                root.depth()
+            }
+            None => 0,
+        };
+        Ok(self
+            .notes
+            .iter()
+            .filter(|(i, _)| root.is_none_or(|r| i.starts_with(r)))
+            .map(|note| (note.0.depth() - base, note))
+            .collect())
+    }
+
+    pub fn relative<'a>(&self, note: &'a Path) -> &'a Path {
+        note.strip_prefix(&self.note_root).unwrap_or(note)
+    }
+
+    fn print(&self, (id, note): &(ZettelId, PathBuf)) {
+        println!("{:<8} {}", id.to_string(), self.relative(note).display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_zettel_id() {
+        let id: ZettelId = "12a3".parse().unwrap();
+        assert_eq!(id.to_string(), "12a3");
+        assert_eq!(id.parent().unwrap().to_string(), "12a");
+        assert_eq!(id.depth(), 2);
+        assert!("a1".parse::<ZettelId>().is_err());
+        assert!("12-a".parse::<ZettelId>().is_err());
+
+        let mut ids: Vec<ZettelId> = ["13", "12b", "12a1", "12", "12aa", "12z", "12a"]
+            .iter()
+            .map(|id| id.parse().unwrap())
+            .collect();
+        ids.sort();
+        let ids: Vec<String> = ids.iter().map(ZettelId::to_string).collect();
+        assert_eq!(ids, ["12", "12a", "12a1", "12b", "12z", "12aa", "13"]);
+
+        assert_eq!(
+            ZettelId::from_note_name("12a3 On lifetimes").unwrap(),
+            "12a3".parse().unwrap()
+        );
+        assert!(ZettelId::from_note_name("lifetimes").is_none());
+    }
+
+    #[test]
+    fn test_folgezettel() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        for name in [
+            "1 Start.md",
+            "1a Branch.md",
+            "1a1 Deeper.md",
+            "1b Other.md",
+            "2 Next.md",
+        ] {
+            fs::write(root.join(name), "").unwrap();
+        }
+        fs::write(root.join("no-id.md"), "").unwrap();
+
+        let chain = Folgezettel::build(root).unwrap();
+        assert_eq!(chain.notes.len(), 5);
+
+        let id = |s: &str| s.parse::<ZettelId>().unwrap();
+        let name = |note: Option<&(ZettelId, PathBuf)>| note.map(|(i, _)| i.to_string());
+        assert_eq!(name(chain.next(&id("1")).unwrap()), Some("1a".into()));
+        assert_eq!(name(chain.next(&id("1a1")).unwrap()), Some("1b".into()));
+        assert_eq!(name(chain.next(&id("2")).unwrap()), None);
+        assert_eq!(name(chain.prev(&id("1b")).unwrap()), Some("1a1".into()));
+        assert!(chain.next(&id("3")).is_err());
+
+        let children: Vec<String> = chain
+            .children(&id("1"))
+            .unwrap()
+            .iter()
+            .map(|(i, _)| i.to_string())
+            .collect();
+        assert_eq!(children, ["1a", "1b"]);
+
+        let tree: Vec<(usize, String)> = chain
+            .tree(Some(&id("1a")))
+            .unwrap()
+            .into_iter()
+            .map(|(depth, (i, _))| (depth, i.to_string()))
+            .collect();
+        assert_eq!(tree, [(0, "1a".into()), (1, "1a1".into())]);
+    }
+
+    #[test]
+    fn test_timestamp_id() {
+        assert_eq!(timestamp_id("202505171230-title"), Some("202505171230"));
+        assert_eq!(timestamp_id("202505171230"), Some("202505171230"));
+        assert_eq!(timestamp_id("20250517123059 title"), Some("20250517123059"));
+        assert_eq!(timestamp_id("20250517 meeting"), None);
+        assert_eq!(timestamp_id("202505171230title"), None);
+        assert_eq!(
+            strip_timestamp_id("202505171230-On lifetimes"),
+            "On lifetimes"
+        );
+        assert_eq!(strip_timestamp_id("202505171230"), "202505171230");
+        assert_eq!(strip_timestamp_id("lifetimes"), "lifetimes");
+
+        let tmp_dir = tempdir().unwrap();
+        let time = chrono::NaiveDate::from_ymd_opt(2025, 5, 17)
+            .unwrap()
+            .and_hms_opt(12, 30, 0)
+            .unwrap();
+        let note = tmp_dir.path().join("lifetimes.md");
+        assert_eq!(
+            with_timestamp_id(&note, time),
+            tmp_dir.path().join("202505171230-lifetimes.md")
+        );
+        fs::create_dir(tmp_dir.path().join("202505171230-borrowing")).unwrap();
+        assert_eq!(
+            with_timestamp_id(&note, time),
+            tmp_dir.path().join("202505171231-lifetimes.md")
+        );
+    }
+}